@@ -0,0 +1,96 @@
+use clap::Subcommand;
+use qobuz_player_controls::{AppResult, alarms, database::Database, error::Error};
+
+use crate::{default_audio_quality, get_client};
+
+#[derive(Subcommand, Debug)]
+pub enum AlarmCommand {
+    /// List the alarms
+    List,
+
+    /// Add an alarm. The running player starts it at the times of the schedule
+    Add {
+        /// Cron schedule with minute, hour, day of month, month and day of week, like
+        /// "30 7 * * 1-5" for 7:30 on weekdays
+        schedule: String,
+
+        /// Qobuz url or album:, playlist: or track: id
+        link: String,
+
+        #[clap(long, default_value_t = 40, value_parser = clap::value_parser!(u8).range(0..=100))]
+        /// Volume in percent reached at the end of the ramp
+        volume: u8,
+    },
+
+    /// Delete an alarm
+    Remove { id: i64 },
+
+    /// Turn an alarm back on
+    Enable { id: i64 },
+
+    /// Keep an alarm without starting it
+    Disable { id: i64 },
+}
+
+pub(crate) async fn run(
+    command: AlarmCommand,
+    database: &Database,
+    headless: bool,
+) -> AppResult<()> {
+    match command {
+        AlarmCommand::List => {
+            let alarms = database.get_alarms().await?;
+            if alarms.is_empty() {
+                println!("No alarms.");
+            }
+
+            for alarm in alarms {
+                let disabled = match alarm.enabled {
+                    true => "",
+                    false => " (disabled)",
+                };
+                println!(
+                    "{:>3}  {:<16} {} at {}%{disabled}",
+                    alarm.id, alarm.schedule, alarm.title, alarm.volume
+                );
+            }
+        }
+        AlarmCommand::Add {
+            schedule,
+            link,
+            volume,
+        } => {
+            let max_audio_quality = default_audio_quality(database, None).await?;
+            let client = get_client(database, max_audio_quality, headless, None).await?;
+
+            let alarm = alarms::add(database, &client, &schedule, &link, volume).await?;
+            println!(
+                "Added alarm {} playing {} at {}.",
+                alarm.id, alarm.title, alarm.schedule
+            );
+        }
+        AlarmCommand::Remove { id } => {
+            found(id, database.remove_alarm(id).await?)?;
+            println!("Removed alarm {id}.");
+        }
+        AlarmCommand::Enable { id } => {
+            found(id, database.set_alarm_enabled(id, true).await?)?;
+            println!("Enabled alarm {id}.");
+        }
+        AlarmCommand::Disable { id } => {
+            found(id, database.set_alarm_enabled(id, false).await?)?;
+            println!("Disabled alarm {id}.");
+        }
+    }
+
+    Ok(())
+}
+
+fn found(id: i64, found: bool) -> AppResult<()> {
+    match found {
+        true => Ok(()),
+        false => Err(Error::Client {
+            message: format!("There is no alarm {id}"),
+        }),
+    }
+}
@@ -0,0 +1,290 @@
+use clap::{Subcommand, ValueEnum};
+use qobuz_player_controls::{
+    AppResult,
+    client::Client,
+    models::{AlbumSimple, Track},
+};
+use serde::Serialize;
+
+#[derive(Subcommand, Debug)]
+pub enum ApiCommand {
+    /// Search albums, artists, playlists and tracks
+    Search { query: String },
+
+    /// Album with its tracks
+    Album { id: String },
+
+    /// Artist with top tracks and releases
+    Artist { id: u32 },
+
+    /// Single track
+    Track { id: u32 },
+
+    /// Playlist with its tracks
+    Playlist { id: u32 },
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Full metadata as returned by the client
+    Json,
+    /// Tab separated columns with a header line
+    Tsv,
+    /// Aligned columns
+    #[default]
+    Table,
+}
+
+/// Columns printed for tsv and table output
+struct Rows {
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+pub(crate) async fn run(
+    command: ApiCommand,
+    output: OutputFormat,
+    client: &Client,
+) -> AppResult<()> {
+    let printed = match command {
+        ApiCommand::Search { query } => {
+            let results = client.search(query).await?;
+            let mut rows = vec![];
+            rows.extend(results.albums.iter().map(|album| {
+                vec![
+                    "album".into(),
+                    album.id.clone(),
+                    album.title.clone(),
+                    album.artist.name.clone(),
+                ]
+            }));
+            rows.extend(results.artists.iter().map(|artist| {
+                vec![
+                    "artist".into(),
+                    artist.id.to_string(),
+                    artist.name.clone(),
+                    String::new(),
+                ]
+            }));
+            rows.extend(results.playlists.iter().map(|playlist| {
+                vec![
+                    "playlist".into(),
+                    playlist.id.to_string(),
+                    playlist.title.clone(),
+                    String::new(),
+                ]
+            }));
+            rows.extend(results.tracks.iter().map(|track| {
+                vec![
+                    "track".into(),
+                    track.id.to_string(),
+                    track.title.clone(),
+                    track.artist_name.clone().unwrap_or_default(),
+                ]
+            }));
+
+            print(
+                output,
+                &results,
+                Rows {
+                    header: &["type", "id", "title", "artist"],
+                    rows,
+                },
+            )?
+        }
+        ApiCommand::Album { id } => {
+            let album = client.album(&id).await?;
+            print(output, &album, track_rows(&album.tracks))?
+        }
+        ApiCommand::Artist { id } => {
+            let artist = client.artist_page(id).await?;
+            let mut rows: Vec<Vec<String>> = artist
+                .top_tracks
+                .iter()
+                .map(|track| {
+                    vec![
+                        "top track".into(),
+                        track.id.to_string(),
+                        track.title.clone(),
+                        String::new(),
+                    ]
+                })
+                .collect();
+            for (kind, releases) in [
+                ("album", &artist.albums),
+                ("single", &artist.singles),
+                ("live", &artist.live),
+                ("compilation", &artist.compilations),
+            ] {
+                rows.extend(releases.iter().map(|album| release_row(kind, album)));
+            }
+
+            print(
+                output,
+                &artist,
+                Rows {
+                    header: &["type", "id", "title", "year"],
+                    rows,
+                },
+            )?
+        }
+        ApiCommand::Track { id } => {
+            let track = client.track(id).await?;
+            print(output, &track, track_rows(std::slice::from_ref(&track)))?
+        }
+        ApiCommand::Playlist { id } => {
+            let playlist = client.playlist(id).await?;
+            print(output, &playlist, track_rows(&playlist.tracks))?
+        }
+    };
+
+    println!("{printed}");
+    Ok(())
+}
+
+fn track_rows(tracks: &[Track]) -> Rows {
+    Rows {
+        header: &["number", "id", "title", "artist", "album", "duration"],
+        rows: tracks
+            .iter()
+            .map(|track| {
+                vec![
+                    track.number.to_string(),
+                    track.id.to_string(),
+                    track.title.clone(),
+                    track.artist_name.clone().unwrap_or_default(),
+                    track.album_title.clone().unwrap_or_default(),
+                    format_duration(track.duration_seconds),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn release_row(kind: &str, album: &AlbumSimple) -> Vec<String> {
+    vec![
+        kind.into(),
+        album.id.clone(),
+        album.title.clone(),
+        album.release_year.to_string(),
+    ]
+}
+
+fn print(output: OutputFormat, value: &impl Serialize, rows: Rows) -> AppResult<String> {
+    Ok(match output {
+        OutputFormat::Json => serde_json::to_string_pretty(value)?,
+        OutputFormat::Tsv => tsv(&rows),
+        OutputFormat::Table => table(&rows),
+    })
+}
+
+/// Tabs and line breaks in a field would end the column or the row
+fn clean(field: &str) -> String {
+    field.replace(['\t', '\n'], " ")
+}
+
+fn tsv(rows: &Rows) -> String {
+    std::iter::once(rows.header.join("\t"))
+        .chain(rows.rows.iter().map(|row| {
+            row.iter()
+                .map(|field| clean(field))
+                .collect::<Vec<_>>()
+                .join("\t")
+        }))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn table(rows: &Rows) -> String {
+    let cleaned: Vec<Vec<String>> = rows
+        .rows
+        .iter()
+        .map(|row| row.iter().map(|field| clean(field)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = rows
+        .header
+        .iter()
+        .map(|name| name.chars().count())
+        .collect();
+    for row in &cleaned {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+
+    let line = |fields: Vec<&str>| {
+        fields
+            .iter()
+            .zip(&widths)
+            .map(|(field, width)| format!("{field:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    std::iter::once(line(rows.header.to_vec()))
+        .chain(
+            cleaned
+                .iter()
+                .map(|row| line(row.iter().map(String::as_str).collect())),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Like 3:41, or 1:02:03 for tracks over an hour
+pub(crate) fn format_duration(seconds: u32) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let seconds = seconds % 60;
+
+    match hours {
+        0 => format!("{minutes}:{seconds:02}"),
+        hours => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rows, format_duration, table, tsv};
+
+    fn rows() -> Rows {
+        Rows {
+            header: &["id", "title", "artist"],
+            rows: vec![
+                vec!["1".into(), "So What".into(), "Miles Davis".into()],
+                vec!["12".into(), "Tab\tand\nnewline".into(), String::new()],
+            ],
+        }
+    }
+
+    #[test]
+    fn tsv_keeps_one_record_per_line() {
+        assert_eq!(
+            tsv(&rows()),
+            "id\ttitle\tartist\n1\tSo What\tMiles Davis\n12\tTab and newline\t"
+        );
+    }
+
+    #[test]
+    fn table_aligns_the_columns() {
+        assert_eq!(
+            table(&rows()),
+            [
+                "id  title            artist",
+                "1   So What          Miles Davis",
+                "12  Tab and newline",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn durations_show_hours_when_needed() {
+        assert_eq!(format_duration(0), "0:00");
+        assert_eq!(format_duration(221), "3:41");
+        assert_eq!(format_duration(3723), "1:02:03");
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use clap::Subcommand;
+use qobuz_player_controls::{
+    AppResult,
+    audio_cache::{self, format_size},
+    database::Database,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Show the size of the audio and artwork caches
+    Stats,
+
+    /// Delete the downloaded tracks and artwork
+    Clear,
+}
+
+pub(crate) async fn run(
+    command: CacheCommand,
+    database: &Database,
+    audio_cache_dir: &Path,
+    artwork_cache_dir: &Path,
+    audio_cache_max_bytes: Option<u64>,
+) -> AppResult<()> {
+    match command {
+        CacheCommand::Stats => {
+            let (audio, artwork) = {
+                let audio_cache_dir = audio_cache_dir.to_path_buf();
+                let artwork_cache_dir = artwork_cache_dir.to_path_buf();
+                tokio::task::spawn_blocking(move || {
+                    (
+                        audio_cache::usage(&audio_cache_dir),
+                        audio_cache::usage(&artwork_cache_dir),
+                    )
+                })
+                .await
+                .unwrap_or_default()
+            };
+            let limit = match audio_cache_max_bytes {
+                Some(max_bytes) => format!(" of {}", format_size(max_bytes)),
+                None => String::new(),
+            };
+
+            println!(
+                "audio cache:   {audio}{limit} in {}",
+                audio_cache_dir.display()
+            );
+            println!(
+                "artwork cache: {artwork} in {}",
+                artwork_cache_dir.display()
+            );
+        }
+        CacheCommand::Clear => {
+            database.clear_cache_entries().await?;
+            let audio = audio_cache::clear_directory(audio_cache_dir).await;
+            let artwork = audio_cache::clear_directory(artwork_cache_dir).await;
+
+            println!("Deleted {audio} of audio and {artwork} of artwork.");
+        }
+    }
+
+    Ok(())
+}
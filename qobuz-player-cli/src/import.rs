@@ -0,0 +1,139 @@
+use std::{
+    io::{BufRead, IsTerminal, Write},
+    path::Path,
+};
+
+use qobuz_player_controls::{
+    AppResult,
+    client::Client,
+    error::Error,
+    models::Track,
+    playlist_import::{self, ImportEntry, ImportMatch},
+};
+
+/// Qobuz accepts a limited number of track ids per request
+const ADD_TRACKS_CHUNK: usize = 50;
+
+pub(crate) async fn run(
+    client: &Client,
+    file: &Path,
+    name: Option<String>,
+    public: bool,
+    yes: bool,
+) -> AppResult<()> {
+    let import = playlist_import::read(file)?;
+    let name = name.or(import.name).unwrap_or_else(|| {
+        file.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Imported playlist".into())
+    });
+    // Without a terminal to answer on, ambiguous entries take the best candidate
+    let review = !yes && std::io::stdin().is_terminal();
+
+    let total = import.entries.len();
+    let mut track_ids = vec![];
+    let mut missing = vec![];
+    let mut failed = vec![];
+
+    for (index, entry) in import.entries.iter().enumerate() {
+        let found = match playlist_import::find_match(client, entry).await {
+            Ok(ImportMatch::Found(track)) => Some(*track),
+            Ok(ImportMatch::Ambiguous(candidates)) if review => {
+                choose(index + 1, total, entry, &candidates)
+            }
+            Ok(ImportMatch::Ambiguous(candidates)) => candidates.into_iter().next(),
+            Ok(ImportMatch::NotFound) => None,
+            Err(err) => {
+                failed.push((entry, err));
+                continue;
+            }
+        };
+
+        match found {
+            Some(track) => track_ids.push(track.id),
+            None => missing.push(entry),
+        }
+    }
+
+    if track_ids.is_empty() {
+        println!("None of the {total} tracks were found, no playlist created.");
+        print_failed(&failed);
+        return Ok(());
+    }
+
+    let playlist = client
+        .create_playlist(name, public, String::new(), None)
+        .await?;
+    for chunk in track_ids.chunks(ADD_TRACKS_CHUNK) {
+        // A partly filled playlist is removed, so the import can simply be run again
+        if let Err(err) = client.playlist_add_track(playlist.id, chunk).await {
+            _ = client.delete_playlist(playlist.id).await;
+            return Err(err);
+        }
+    }
+
+    println!(
+        "Created playlist \"{}\" ({}) with {} of {total} tracks.",
+        playlist.title,
+        playlist.id,
+        track_ids.len()
+    );
+    if !missing.is_empty() {
+        println!("Not found:");
+        for entry in missing {
+            println!("  {entry}");
+        }
+    }
+    print_failed(&failed);
+
+    Ok(())
+}
+
+fn print_failed(failed: &[(&ImportEntry, Error)]) {
+    if failed.is_empty() {
+        return;
+    }
+
+    println!("Search failed:");
+    for (entry, err) in failed {
+        println!("  {entry}: {err}");
+    }
+}
+
+/// Review step: lists the candidates and reads the choice from stdin. Empty skips the entry
+fn choose(number: usize, total: usize, entry: &ImportEntry, candidates: &[Track]) -> Option<Track> {
+    println!();
+    println!("[{number}/{total}] {entry}");
+    for (index, track) in candidates.iter().enumerate() {
+        println!(
+            "  {}) {} - {} ({})",
+            index + 1,
+            track.artist_name.as_deref().unwrap_or("Unknown artist"),
+            track.title,
+            track.album_title.as_deref().unwrap_or("Unknown album"),
+        );
+    }
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("Pick 1-{}, or enter to skip: ", candidates.len());
+        _ = std::io::stdout().flush();
+
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+            return None;
+        }
+
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return None;
+        }
+        if let Some(track) = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|choice| candidates.get(choice.checked_sub(1)?))
+        {
+            return Some(track.clone());
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use clap::Args;
+use qobuz_player_controls::{AppResult, error::Error};
+use reqwest::{
+    Method, RequestBuilder, Response, StatusCode,
+    header::{HeaderMap, SET_COOKIE},
+};
+
+/// Password of `--web-user`. Only read from the environment, so it stays out of the process list
+const WEB_PASSWORD_ENV: &str = "QOBUZ_PLAYER_WEB_PASSWORD";
+
+/// Where a running player is and how to sign in to its web api
+#[derive(Args, Debug, Clone)]
+pub struct InstanceArgs {
+    #[clap(long, default_value = "http://localhost:9888", global = true)]
+    /// Web address of the running player
+    pub instance: String,
+
+    #[clap(
+        long,
+        env = "QOBUZ_PLAYER_WEB_SECRET",
+        hide_env_values = true,
+        global = true
+    )]
+    /// Web secret of the running player
+    pub web_secret: Option<String>,
+
+    #[clap(long, env = "QOBUZ_PLAYER_WEB_USER", global = true)]
+    /// Named web user to sign in as. The password is read from QOBUZ_PLAYER_WEB_PASSWORD
+    pub web_user: Option<String>,
+}
+
+/// Web api of a running player, signed in when a secret or user is given
+pub(crate) struct Instance {
+    address: String,
+    http: reqwest::Client,
+    cookie: Option<String>,
+}
+
+impl Instance {
+    /// Signs in as `--web-user` first. `Ok(None)` when no player is listening
+    pub(crate) async fn connect(args: &InstanceArgs) -> AppResult<Option<Self>> {
+        // The login page is a redirect, which must not be followed
+        let http = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let mut instance = Self {
+            address: args.instance.trim_end_matches('/').to_string(),
+            http,
+            cookie: args
+                .web_secret
+                .as_ref()
+                .map(|secret| format!("secret={secret}")),
+        };
+
+        if let Some(user) = &args.web_user {
+            let password = std::env::var(WEB_PASSWORD_ENV).map_err(|_| Error::Client {
+                message: format!("Set {WEB_PASSWORD_ENV} to sign in as {user}"),
+            })?;
+
+            let response = match instance
+                .http
+                .post(format!("{}/auth/login", instance.address))
+                .form(&[("username", user.as_str()), ("password", &password)])
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(err) if err.is_connect() => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+
+            if response.status() == StatusCode::UNAUTHORIZED {
+                return Err(Error::Client {
+                    message: format!("{} rejected the web user {user}", args.instance),
+                });
+            }
+
+            instance.cookie = session_cookie(response.headers());
+        }
+
+        Ok(Some(instance))
+    }
+
+    pub(crate) fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub(crate) fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{path}", self.address));
+        match &self.cookie {
+            Some(cookie) => request.header(reqwest::header::COOKIE, cookie),
+            None => request,
+        }
+    }
+
+    /// Turns an answer the web api did not accept into an error
+    pub(crate) async fn accepted(&self, response: Response) -> AppResult<Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        if status.is_redirection()
+            || status == StatusCode::UNAUTHORIZED
+            || status == StatusCode::FORBIDDEN
+        {
+            return Err(Error::Client {
+                message: format!(
+                    "{} requires a login, pass --web-secret or --web-user",
+                    self.address
+                ),
+            });
+        }
+
+        let message = response.text().await.unwrap_or_default();
+        Err(status_error(&self.address, status, message))
+    }
+}
+
+/// `session=<token>` from the answer to a login
+fn session_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .find(|cookie| cookie.starts_with("session="))
+        .map(ToString::to_string)
+}
+
+fn status_error(address: &str, status: StatusCode, message: String) -> Error {
+    let message = match message.is_empty() {
+        true => status.to_string(),
+        false => message,
+    };
+
+    match status {
+        StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => Error::InvalidLink { message },
+        StatusCode::SERVICE_UNAVAILABLE => Error::Client {
+            message: format!("{address} can not do that right now: {message}"),
+        },
+        _ => Error::Client {
+            message: format!("{address} failed: {message}"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{
+        StatusCode,
+        header::{HeaderMap, HeaderValue, SET_COOKIE},
+    };
+
+    use super::{session_cookie, status_error};
+    use qobuz_player_controls::error::Error;
+
+    #[test]
+    fn finds_the_session_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("secret=; Max-Age=0"));
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("session=abc123; HttpOnly; SameSite=Strict; Path=/"),
+        );
+
+        assert_eq!(session_cookie(&headers), Some("session=abc123".to_string()));
+        assert_eq!(session_cookie(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn errors_follow_the_status() {
+        let error = status_error("http://player", StatusCode::NOT_FOUND, "No album".into());
+        assert!(matches!(error, Error::InvalidLink { message } if message == "No album"));
+
+        let error = status_error("http://player", StatusCode::BAD_REQUEST, String::new());
+        assert!(matches!(error, Error::InvalidLink { message } if message == "400 Bad Request"));
+
+        let error = status_error(
+            "http://player",
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Unable to reach Qobuz".into(),
+        );
+        assert_eq!(
+            error.to_string(),
+            "http://player can not do that right now: Unable to reach Qobuz"
+        );
+
+        let error = status_error(
+            "http://player",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "oops".into(),
+        );
+        assert_eq!(error.to_string(), "http://player failed: oops");
+    }
+}
@@ -1,27 +1,381 @@
-use clap::{Args, Subcommand};
+use clap::{
+    Args, CommandFactory, Subcommand,
+    builder::{EnumValueParser, TypedValueParser},
+};
 use qobuz_player_controls::{
-    AppResult, AudioQuality, client::Client, database::Database,
-    notification::NotificationBroadcast, player::Player,
+    AppResult, AudioQuality, VolumeCurve, alarms,
+    artwork_cache::ArtworkCache,
+    audio_backend::{AudioBackendKind, AudioOutput},
+    audio_cache::{self, format_size},
+    client::Client,
+    controls::{self, Controls},
+    database::{DEFAULT_PROFILE, Database},
+    error::Error,
+    group::{self, GroupRole},
+    hardware_mixer::HardwareMixer,
+    hooks::{self, Hooks},
+    network_monitor,
+    notification::NotificationBroadcast,
+    player::{Player, PlayerSettings},
+    quiet_hours::QuietHours,
+    release_notifications::{self, EmailSettings, ReleaseNotifiers},
+    webhooks,
+};
+use qobuz_player_display::{DisplayKind, DisplaySettings};
+use qobuz_player_gpio::GpioSettings;
+use std::{
+    fmt::Display,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
-use std::{path::PathBuf, sync::Arc, time::Duration};
 use tokio_schedule::{Job, every};
 
+pub use alarm::AlarmCommand;
+pub use api::{ApiCommand, OutputFormat};
+pub use cache::CacheCommand;
+pub use instance::InstanceArgs;
+pub use play::{PlayLink, spawn_play_link};
+pub use remote::RemoteCommand;
+
+mod alarm;
+mod api;
+mod cache;
+mod import;
+mod instance;
+mod play;
+mod remote;
+mod setup;
+
 #[derive(Args, Debug)]
 pub struct SharedArgs {
+    #[clap(long, default_value = DEFAULT_PROFILE, global = true)]
+    /// Profile holding the Qobuz account, settings and favorites to use
+    pub profile: String,
+
     #[clap(long)]
     pub audio_cache: Option<PathBuf>,
 
-    #[clap(long, default_value_t = 1)]
+    #[clap(long, default_value_t = 1, alias = "audio-cache-max-age-hours")]
+    /// Hours a track stays in the audio cache after it was last played. 0 keeps tracks
+    /// until they are evicted by size
     pub audio_cache_time_to_live: u32,
 
+    #[clap(long, default_value_t = 0)]
+    /// Maximum size of the audio cache in megabytes. The least recently played tracks are
+    /// deleted when a download goes over it. 0 disables the limit
+    pub audio_cache_max_size_mb: u64,
+
+    #[clap(long, default_value_t = false)]
+    /// Encrypt downloaded tracks at rest, with a key stored in the keyring of the user
+    /// (secret-tool on Linux, security on macOS)
+    pub encrypt_audio_cache: bool,
+
+    #[clap(long)]
+    /// Directory for downloaded and resized artwork
+    pub artwork_cache: Option<PathBuf>,
+
+    #[clap(long, default_value_t = 500)]
+    /// Maximum size of the artwork cache in megabytes. The artwork cached longest ago is
+    /// deleted when it goes over. 0 disables the limit
+    pub artwork_cache_max_size_mb: u64,
+
     #[clap(short, long)]
     /// Provide max audio quality (overrides any configured value)
     pub max_audio_quality: Option<AudioQuality>,
 
     #[clap(long)]
-    /// Use provided device for audio output, instead of default.
+    /// Use provided device for audio output, instead of the one picked in qobuz-player setup
+    /// or the default.
     /// Use qobuz-player list-devices for output device list
     pub output_device_id: Option<String>,
+
+    #[clap(long = "zone")]
+    /// Also play to this output device, as a zone with its own volume. Can be repeated.
+    /// Use qobuz-player list-devices for output device list
+    pub zones: Vec<String>,
+
+    #[clap(long, default_value_t = 0)]
+    /// Number of tracks to download into the audio cache when an album is opened.
+    /// 0 disables pre-caching
+    pub precache_album_tracks: usize,
+
+    #[clap(long, default_value_t = 10)]
+    /// Number of seconds to jump forward
+    pub jump_forward_seconds: u64,
+
+    #[clap(long, default_value_t = 10)]
+    /// Number of seconds to jump backward
+    pub jump_backward_seconds: u64,
+
+    #[clap(
+        long,
+        default_value = "cubic",
+        value_parser = EnumValueParser::<VolumeCurveArg>::new().map(VolumeCurve::from)
+    )]
+    /// Mapping from volume level to output gain. Use fixed for external volume control
+    pub volume_curve: VolumeCurve,
+
+    #[clap(long, default_value_t = 0)]
+    /// Fade volume in and out over this many milliseconds when playing, pausing and stopping.
+    /// 0 disables fading
+    pub volume_ramp_ms: u64,
+
+    #[clap(long, default_value_t = 100, value_parser = clap::value_parser!(u8).range(1..=100))]
+    /// Highest volume in percent, however the volume is set, including over MPRIS and Qobuz
+    /// Connect. Protects speakers and ears. It caps the shared volume, so zones are capped
+    /// alike and have no limit of their own
+    pub max_volume: u8,
+
+    #[clap(long, default_value_t = 0)]
+    /// Fade out the playing track and fade in the next one over this many milliseconds when
+    /// skipping manually. 0 cuts between them
+    pub skip_fade_ms: u64,
+
+    #[clap(long, default_value_t = 60)]
+    /// Start fetching the next track this many seconds before the current track ends.
+    /// The track after that is also fetched when the next track is shorter than this window
+    pub prefetch_seconds: u64,
+
+    #[clap(long, default_value_t = 20)]
+    /// Remember the position of tracks longer than this many minutes, like mixes and long
+    /// classical works, and offer to resume them when played again. 0 disables resume points
+    pub resume_threshold_minutes: u64,
+
+    #[clap(long, default_value_t = 15)]
+    /// Restart a track from a fresh url where it stopped, when its download or playback made
+    /// no progress for this many seconds. 0 disables the watchdog
+    pub stall_timeout_seconds: u64,
+
+    #[clap(long, default_value_t = 30)]
+    /// Minutes between reconciling favorites with the server, which also dates newly added
+    /// favorite albums. 0 disables the background refresh
+    pub favorites_refresh_minutes: u64,
+
+    #[clap(long)]
+    /// Control volume with this ALSA mixer element, for example PCM or Digital, instead of
+    /// attenuating samples in software. Keeps the output bit-perfect on DAC HATs
+    pub alsa_mixer_control: Option<String>,
+
+    #[clap(long, default_value = "default", requires = "alsa_mixer_control")]
+    /// ALSA mixer device holding the element set with --alsa-mixer-control, for example hw:0
+    pub alsa_mixer_device: String,
+
+    #[clap(long)]
+    /// Shell command filtering the decoded audio before it is played, for room correction or
+    /// crossfeed. It reads raw 32-bit float little endian interleaved samples on stdin and
+    /// writes them on stdout, in the sample rate and channels of QOBUZ_PLAYER_SAMPLE_RATE and
+    /// QOBUZ_PLAYER_CHANNELS. LADSPA plugins can be run with a host like sox
+    pub dsp_command: Option<String>,
+
+    #[clap(
+        long,
+        default_value = "rodio",
+        value_parser = EnumValueParser::<AudioBackendArg>::new().map(AudioBackendKind::from)
+    )]
+    /// Where decoded audio is played. alsa writes to an ALSA pcm directly, pipe writes
+    /// raw 32-bit float little endian interleaved samples for external processing, and wav
+    /// and null decode faster than realtime for testing without audio hardware
+    pub audio_backend: AudioBackendKind,
+
+    #[clap(long, default_value = "default")]
+    /// ALSA pcm used with --audio-backend alsa, for example hw:0 or plughw:0
+    pub alsa_device: String,
+
+    #[clap(long, default_value = "-")]
+    /// File or fifo written with --audio-backend pipe. - writes to stdout
+    pub pipe_path: PathBuf,
+
+    #[clap(long, default_value = "qobuz-player.wav")]
+    /// File written with --audio-backend wav. Tracks in another sample rate or channel
+    /// count go to numbered files next to it
+    pub wav_path: PathBuf,
+
+    #[cfg(feature = "fixtures")]
+    #[clap(long)]
+    /// Serve albums, playlists and audio from this fixture directory instead of Qobuz,
+    /// for tests and development without an account
+    pub fixtures: Option<PathBuf>,
+
+    #[clap(long = "webhook-url")]
+    /// POST a JSON event to this url when a track starts, pauses or finishes and when the
+    /// queue changes. Can be repeated
+    pub webhook_urls: Vec<String>,
+
+    #[clap(long = "release-webhook-url")]
+    /// POST a JSON event to this url when a followed artist releases something new. Can be
+    /// repeated
+    pub release_webhook_urls: Vec<String>,
+
+    #[clap(long = "release-ntfy-url")]
+    /// Publish new releases of followed artists to this ntfy topic, for example
+    /// https://ntfy.sh/my-releases. Can be repeated
+    pub release_ntfy_urls: Vec<String>,
+
+    #[clap(long, requires_all = ["release_email_from", "release_email_to"])]
+    /// Email new releases of followed artists through this SMTP server
+    pub release_smtp_server: Option<String>,
+
+    #[clap(long, default_value_t = 587)]
+    /// Port of the SMTP server. Port 465 connects with TLS, other ports upgrade the
+    /// connection with STARTTLS
+    pub release_smtp_port: u16,
+
+    #[clap(long, requires = "release_smtp_password")]
+    pub release_smtp_username: Option<String>,
+
+    #[clap(long, env = "QOBUZ_PLAYER_SMTP_PASSWORD", hide_env_values = true)]
+    pub release_smtp_password: Option<String>,
+
+    #[clap(long, requires = "release_smtp_server")]
+    /// Sender of the release emails, like qobuz-player <player@example.com>
+    pub release_email_from: Option<String>,
+
+    #[clap(long, requires = "release_smtp_server")]
+    /// Recipient of the release emails
+    pub release_email_to: Option<String>,
+
+    #[clap(long, default_value_t = 6)]
+    /// Hours between looking for new releases of followed artists. 0 disables the checks
+    pub release_check_hours: u64,
+
+    #[clap(long, default_value_t = 60)]
+    /// Seconds over which an alarm raises the volume from 0 to its volume. 0 starts at
+    /// the volume right away
+    pub alarm_ramp_seconds: u64,
+
+    #[clap(long)]
+    /// Shell command run when another track starts. The track is passed in the
+    /// QOBUZ_PLAYER_TITLE, QOBUZ_PLAYER_ARTIST, QOBUZ_PLAYER_ALBUM, QOBUZ_PLAYER_TRACK_ID,
+    /// QOBUZ_PLAYER_DURATION_SECONDS and QOBUZ_PLAYER_IMAGE environment variables
+    pub on_track_change: Option<String>,
+
+    #[clap(long)]
+    /// Shell command run when playback starts or resumes, with the track in the environment
+    /// like --on-track-change
+    pub on_play: Option<String>,
+
+    #[clap(long)]
+    /// Shell command run when playback is paused, with the track in the environment like
+    /// --on-track-change
+    pub on_pause: Option<String>,
+
+    #[clap(long)]
+    /// Shell command run when the queue runs out or is cleared
+    pub on_stop: Option<String>,
+
+    #[clap(long, conflicts_with = "group_leader")]
+    /// Lead group playback: instances started with --group-leader pointing at this port play
+    /// the same queue in sync
+    pub group_port: Option<u16>,
+
+    #[clap(long)]
+    /// Follow the group playback leader at this host:port, playing its queue in sync. Tracks
+    /// are streamed with the account of this instance
+    pub group_leader: Option<String>,
+
+    #[clap(long, env = "QOBUZ_PLAYER_GROUP_SECRET", hide_env_values = true)]
+    /// Secret followers present to the group playback leader. The leader listens on all
+    /// interfaces, so without it anyone on the network can follow
+    pub group_secret: Option<String>,
+}
+
+impl SharedArgs {
+    /// The one artwork cache of a binary, shared by its interfaces
+    pub fn artwork(&self) -> Arc<ArtworkCache> {
+        Arc::new(ArtworkCache::new(
+            default_artwork_cache(self.artwork_cache.clone()),
+            self.artwork_cache_max_bytes(),
+        ))
+    }
+
+    pub fn hooks(&self) -> Hooks {
+        Hooks {
+            on_track_change: self.on_track_change.clone(),
+            on_play: self.on_play.clone(),
+            on_pause: self.on_pause.clone(),
+            on_stop: self.on_stop.clone(),
+        }
+    }
+
+    pub fn release_notifiers(&self) -> ReleaseNotifiers {
+        let email = match (
+            &self.release_smtp_server,
+            &self.release_email_from,
+            &self.release_email_to,
+        ) {
+            (Some(server), Some(from), Some(to)) => Some(EmailSettings {
+                server: server.clone(),
+                port: self.release_smtp_port,
+                username: self.release_smtp_username.clone(),
+                password: self.release_smtp_password.clone(),
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            _ => None,
+        };
+
+        ReleaseNotifiers {
+            webhook_urls: self.release_webhook_urls.clone(),
+            ntfy_urls: self.release_ntfy_urls.clone(),
+            email,
+        }
+    }
+
+    pub fn group_role(&self) -> Option<GroupRole> {
+        match (self.group_port, &self.group_leader) {
+            (Some(port), _) => Some(GroupRole::Leader {
+                port,
+                secret: self.group_secret.clone(),
+            }),
+            (None, Some(leader)) => Some(GroupRole::Follower {
+                leader: leader.clone(),
+                secret: self.group_secret.clone(),
+            }),
+            (None, None) => None,
+        }
+    }
+
+    pub fn audio_cache_max_bytes(&self) -> Option<u64> {
+        (self.audio_cache_max_size_mb != 0).then(|| self.audio_cache_max_size_mb * 1_000_000)
+    }
+
+    pub fn artwork_cache_max_bytes(&self) -> Option<u64> {
+        (self.artwork_cache_max_size_mb != 0).then(|| self.artwork_cache_max_size_mb * 1_000_000)
+    }
+
+    /// Fixture directory served in place of Qobuz, in builds with the fixtures feature
+    pub fn fixtures(&self) -> Option<&Path> {
+        #[cfg(feature = "fixtures")]
+        return self.fixtures.as_deref();
+        #[cfg(not(feature = "fixtures"))]
+        None
+    }
+
+    /// `tui` is set when the terminal interface draws to stdout
+    pub fn audio_output(&self, tui: bool) -> AppResult<AudioOutput> {
+        let output = AudioOutput::new(
+            self.audio_backend,
+            self.alsa_device.clone(),
+            self.pipe_path.clone(),
+            self.wav_path.clone(),
+        );
+        if !output.writes_to_stdout() {
+            return Ok(output);
+        }
+
+        let message = if tui {
+            "--pipe-path - writes audio to stdout, which the terminal interface draws to. Use a file or fifo"
+        } else if std::io::stdout().is_terminal() {
+            "--pipe-path - writes audio to stdout, which is a terminal. Redirect stdout or use a file or fifo"
+        } else {
+            return Ok(output);
+        };
+        Err(Error::SinkDeviceError {
+            message: message.to_string(),
+        })
+    }
 }
 
 #[derive(Args, Debug)]
@@ -50,11 +404,157 @@ pub struct ConnectNameArgs {
     pub connect_name: String,
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
 pub struct GpioArgs {
     #[clap(long, default_value_t = false)]
     /// Enable gpio interface for raspberry pi. Pin 16 (gpio-23) will be high when playing
     pub gpio: bool,
+
+    #[clap(long, default_value_t = 23)]
+    /// Gpio pin set high when playing
+    pub gpio_status_pin: u8,
+
+    #[clap(long)]
+    /// Gpio pin of a button toggling play and pause. The button should connect the pin to ground
+    pub gpio_play_pause_pin: Option<u8>,
+
+    #[clap(long)]
+    /// Gpio pin of a button skipping to the next track
+    pub gpio_next_pin: Option<u8>,
+
+    #[clap(long)]
+    /// Gpio pin of a button skipping to the previous track
+    pub gpio_previous_pin: Option<u8>,
+
+    #[clap(long, requires = "gpio_rotary_b_pin")]
+    /// Gpio pin of the A (clock) output of a rotary encoder controlling the volume
+    pub gpio_rotary_a_pin: Option<u8>,
+
+    #[clap(long, requires = "gpio_rotary_a_pin")]
+    /// Gpio pin of the B (data) output of a rotary encoder controlling the volume
+    pub gpio_rotary_b_pin: Option<u8>,
+
+    #[clap(long, default_value_t = 0.05)]
+    /// Volume change per rotary encoder step
+    pub gpio_volume_step: f32,
+}
+
+impl GpioArgs {
+    pub fn settings(&self) -> GpioSettings {
+        GpioSettings {
+            status_pin: self.gpio_status_pin,
+            play_pause_pin: self.gpio_play_pause_pin,
+            next_pin: self.gpio_next_pin,
+            previous_pin: self.gpio_previous_pin,
+            rotary_pins: self.gpio_rotary_a_pin.zip(self.gpio_rotary_b_pin),
+            volume_step: self.gpio_volume_step,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DisplayArgs {
+    #[clap(
+        long,
+        value_parser = EnumValueParser::<DisplayKindArg>::new().map(DisplayKind::from)
+    )]
+    /// Show now playing on a display connected over I2C
+    pub display: Option<DisplayKind>,
+
+    #[clap(long, value_parser = parse_i2c_address)]
+    /// I2C address of the display, e.g. 0x3c. Defaults to 0x3c for ssd1306 and 0x27 for hd44780
+    pub display_address: Option<u16>,
+
+    #[clap(long, default_value_t = 16)]
+    /// Characters per line of a hd44780 display
+    pub display_columns: usize,
+
+    #[clap(long, default_value_t = 2)]
+    /// Lines of a hd44780 display
+    pub display_rows: usize,
+}
+
+impl DisplayArgs {
+    /// `None` when no display is connected
+    pub fn settings(&self) -> Option<DisplaySettings> {
+        self.display.map(|kind| DisplaySettings {
+            kind,
+            address: self.display_address,
+            columns: self.display_columns,
+            rows: self.display_rows,
+        })
+    }
+}
+
+/// Values of --volume-curve, the player itself does not depend on clap
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum VolumeCurveArg {
+    Linear,
+    Cubic,
+    /// Always output at full volume. Useful when volume is controlled by an external DAC or amplifier
+    Fixed,
+}
+
+impl From<VolumeCurveArg> for VolumeCurve {
+    fn from(value: VolumeCurveArg) -> Self {
+        match value {
+            VolumeCurveArg::Linear => VolumeCurve::Linear,
+            VolumeCurveArg::Cubic => VolumeCurve::Cubic,
+            VolumeCurveArg::Fixed => VolumeCurve::Fixed,
+        }
+    }
+}
+
+/// Values of --audio-backend
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum AudioBackendArg {
+    /// Output device through cpal
+    Rodio,
+    /// ALSA pcm written directly, without resampling or mixing. Linux only
+    Alsa,
+    /// Raw interleaved 32-bit float little endian samples written to a file, fifo or stdout
+    Pipe,
+    /// 32-bit float WAV file, written faster than realtime
+    Wav,
+    /// Decode faster than realtime without playing anything, for tests without audio hardware
+    Null,
+}
+
+impl From<AudioBackendArg> for AudioBackendKind {
+    fn from(value: AudioBackendArg) -> Self {
+        match value {
+            AudioBackendArg::Rodio => AudioBackendKind::Rodio,
+            AudioBackendArg::Alsa => AudioBackendKind::Alsa,
+            AudioBackendArg::Pipe => AudioBackendKind::Pipe,
+            AudioBackendArg::Wav => AudioBackendKind::Wav,
+            AudioBackendArg::Null => AudioBackendKind::Null,
+        }
+    }
+}
+
+/// Values of --display
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum DisplayKindArg {
+    /// 128x64 OLED
+    Ssd1306,
+    /// Character LCD with a PCF8574 I2C backpack
+    Hd44780,
+}
+
+impl From<DisplayKindArg> for DisplayKind {
+    fn from(value: DisplayKindArg) -> Self {
+        match value {
+            DisplayKindArg::Ssd1306 => DisplayKind::Ssd1306,
+            DisplayKindArg::Hd44780 => DisplayKind::Hd44780,
+        }
+    }
+}
+
+fn parse_i2c_address(value: &str) -> Result<u16, std::num::ParseIntError> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
 }
 
 #[derive(Args, Debug)]
@@ -76,18 +576,275 @@ pub enum SharedCommands {
     /// Logout from Qobuz
     Logout,
 
+    /// Log in and pick the output device and audio quality, step by step
+    Setup,
+
+    /// Print a completion script for the shell, like
+    /// `qobuz-player completions bash > /etc/bash_completion.d/qobuz-player`
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
     /// Persistently set the maximum audio quality
     SetMaxAudioQuality {
         #[clap(value_enum)]
         quality: AudioQuality,
     },
+
+    /// Persistently enable or disable data saver. Streams in mp3, skips pre-downloading
+    /// and uses smaller artwork
+    SetDataSaver {
+        #[clap(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+
+    /// Persistently enable or disable playing the preview clip of tracks only offered as a
+    /// preview, instead of skipping them
+    SetPlayPreviews {
+        #[clap(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+
+    /// Persistently set the daily quiet hours, like 22:00-07:00, during which the volume is
+    /// capped and release notifications wait. "off" turns them off. Applies when the player
+    /// starts
+    SetQuietHours {
+        hours: String,
+
+        #[clap(long, default_value_t = 30, value_parser = clap::value_parser!(u8).range(0..=100))]
+        /// Highest volume in percent during the quiet hours
+        max_volume: u8,
+    },
+
+    /// Play a Qobuz url or an album, track or playlist id on a running player, or start
+    /// this one when none is running
+    Play {
+        /// Qobuz url, id, or id prefixed with album:, track: or playlist:
+        link: String,
+
+        #[clap(flatten)]
+        instance: InstanceArgs,
+
+        #[clap(long, value_parser = parse_position)]
+        /// Start a track at this position, like 3:41 or 1:02:03
+        at: Option<Duration>,
+    },
+
+    /// Ask a running player what it is playing
+    Remote {
+        #[clap(subcommand)]
+        command: RemoteCommand,
+
+        #[clap(flatten)]
+        instance: InstanceArgs,
+    },
+
+    /// Query Qobuz metadata without starting the player
+    Api {
+        #[clap(subcommand)]
+        command: ApiCommand,
+
+        #[clap(long, value_enum, default_value_t, global = true)]
+        /// Output format
+        output: OutputFormat,
+    },
+
+    /// Create a Qobuz playlist from an M3U, CSV or Spotify export file. Tracks are matched
+    /// by searching Qobuz, ambiguous matches are reviewed interactively
+    ImportPlaylist {
+        /// .m3u, .m3u8, .csv with title and artist columns, or a Spotify .json export
+        file: PathBuf,
+
+        #[clap(long)]
+        /// Name of the playlist. Defaults to the name in the file or the file name
+        name: Option<String>,
+
+        #[clap(long, default_value_t = false)]
+        /// Make the playlist public
+        public: bool,
+
+        #[clap(long, short, default_value_t = false)]
+        /// Skip the review and take the best match of ambiguous tracks
+        yes: bool,
+    },
+
+    /// Inspect or empty the audio and artwork caches
+    Cache {
+        #[clap(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Start an album, playlist or track at scheduled times, like an alarm clock
+    Alarm {
+        #[clap(subcommand)]
+        command: AlarmCommand,
+    },
 }
 
-pub async fn handle_shared_commands(
+/// Which subsystems are active in the running player
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StartupSummary {
+    pub web_port: Option<u16>,
+    pub mpris: bool,
+    pub global_hotkeys: bool,
+    pub connect_name: Option<String>,
+    /// `None` when rfid is disabled, otherwise whether a reader is attached to stdin
+    pub rfid_reader_detected: Option<bool>,
+    pub gpio: bool,
+    pub display: bool,
+    pub webhooks: usize,
+    pub hooks: usize,
+    pub group: Option<String>,
+    /// Name advertised with mDNS, `None` when advertising is disabled
+    pub mdns_name: Option<String>,
+    pub output_device: Option<String>,
+    /// Volume ceiling in percent
+    pub max_volume: u8,
+    pub dsp_command: Option<String>,
+    pub audio_cache: PathBuf,
+    pub audio_cache_max_bytes: Option<u64>,
+    pub encrypt_audio_cache: bool,
+    pub max_audio_quality: String,
+    pub profile: String,
+}
+
+impl StartupSummary {
+    pub fn new(shared: &SharedArgs, max_audio_quality: AudioQuality) -> Self {
+        Self {
+            web_port: None,
+            mpris: false,
+            global_hotkeys: false,
+            connect_name: None,
+            rfid_reader_detected: None,
+            gpio: false,
+            display: false,
+            webhooks: shared.webhook_urls.len(),
+            hooks: shared.hooks().count(),
+            group: shared.group_role().map(|role| role.to_string()),
+            mdns_name: None,
+            output_device: shared.output_device_id.clone(),
+            max_volume: shared.max_volume,
+            dsp_command: shared.dsp_command.clone(),
+            audio_cache: default_audio_cache(shared.audio_cache.clone()),
+            audio_cache_max_bytes: shared.audio_cache_max_bytes(),
+            encrypt_audio_cache: shared.encrypt_audio_cache,
+            max_audio_quality: format!("{max_audio_quality:?}"),
+            profile: shared.profile.clone(),
+        }
+    }
+}
+
+impl Display for StartupSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn enabled(value: bool) -> &'static str {
+            if value { "enabled" } else { "disabled" }
+        }
+
+        writeln!(f, "qobuz-player subsystems:")?;
+        writeln!(f, "  profile:       {}", self.profile)?;
+        match self.web_port {
+            Some(port) => writeln!(f, "  web:           port {port}")?,
+            None => writeln!(f, "  web:           disabled")?,
+        }
+        if let Some(name) = &self.mdns_name {
+            writeln!(f, "  mdns:          {name}")?;
+        }
+        writeln!(f, "  mpris:         {}", enabled(self.mpris))?;
+        if self.global_hotkeys {
+            writeln!(f, "  hotkeys:       enabled")?;
+        }
+        match &self.connect_name {
+            Some(name) => writeln!(f, "  connect:       {name}")?,
+            None => writeln!(f, "  connect:       disabled")?,
+        }
+        match self.rfid_reader_detected {
+            Some(true) => writeln!(f, "  rfid:          reader detected")?,
+            Some(false) => writeln!(
+                f,
+                "  rfid:          enabled, but no reader detected on stdin"
+            )?,
+            None => writeln!(f, "  rfid:          disabled")?,
+        }
+        writeln!(f, "  gpio:          {}", enabled(self.gpio))?;
+        writeln!(f, "  display:       {}", enabled(self.display))?;
+        match self.webhooks {
+            0 => writeln!(f, "  webhooks:      disabled")?,
+            count => writeln!(f, "  webhooks:      {count} url(s)")?,
+        }
+        if self.hooks > 0 {
+            writeln!(f, "  hooks:         {} command(s)", self.hooks)?;
+        }
+        if let Some(group) = &self.group {
+            writeln!(f, "  group:         {group}")?;
+        }
+        writeln!(
+            f,
+            "  output device: {}",
+            self.output_device.as_deref().unwrap_or("default")
+        )?;
+        if self.max_volume < 100 {
+            writeln!(f, "  max volume:    {}%", self.max_volume)?;
+        }
+        if let Some(command) = &self.dsp_command {
+            writeln!(f, "  dsp:           {command}")?;
+        }
+        let encrypted = match self.encrypt_audio_cache {
+            true => ", encrypted",
+            false => "",
+        };
+        match self.audio_cache_max_bytes {
+            Some(max_bytes) => writeln!(
+                f,
+                "  audio cache:   {} (max {}{encrypted})",
+                self.audio_cache.display(),
+                format_size(max_bytes)
+            )?,
+            None if self.encrypt_audio_cache => writeln!(
+                f,
+                "  audio cache:   {} (encrypted)",
+                self.audio_cache.display()
+            )?,
+            None => writeln!(f, "  audio cache:   {}", self.audio_cache.display())?,
+        }
+        write!(f, "  max quality:   {}", self.max_audio_quality)
+    }
+}
+
+/// What a binary does once its subcommand ran
+pub enum Startup {
+    /// The subcommand was all there was to do
+    Exit,
+    /// Start the player. Holds the link to play when `play` found no running player
+    Run(Option<PlayLink>),
+}
+
+/// Runs the subcommand, if any. `Cli` holds the arguments of the binary, to complete them
+pub async fn handle_shared_commands<Cli: CommandFactory>(
+    command: Option<SharedCommands>,
+    shared: &SharedArgs,
+    database: &Database,
+    headless: bool,
+) -> AppResult<Startup> {
+    let Some(command) = command else {
+        return Ok(Startup::Run(None));
+    };
+
+    Ok(
+        match run_command::<Cli>(command, shared, database, headless).await? {
+            Some(link) => Startup::Run(Some(link)),
+            None => Startup::Exit,
+        },
+    )
+}
+
+/// Returns the link to play when `play` found no running player, so the caller starts one
+async fn run_command<Cli: CommandFactory>(
     command: SharedCommands,
+    shared: &SharedArgs,
     database: &Database,
     headless: bool,
-) -> AppResult<()> {
+) -> AppResult<Option<PlayLink>> {
     match command {
         SharedCommands::Login => {
             let (_client, oauth_result) =
@@ -95,27 +852,146 @@ pub async fn handle_shared_commands(
 
             database.set_credentials(oauth_result.into()).await?;
             println!("Login successful! You can now run qobuz-player.");
-            Ok(())
+            Ok(None)
         }
         SharedCommands::Logout => {
             database.clear_user_auth_token().await?;
             println!("Logout successful!");
-            Ok(())
+            Ok(None)
+        }
+        SharedCommands::Setup => {
+            setup::run(database, headless).await?;
+            Ok(None)
+        }
+        SharedCommands::Completions { shell } => {
+            // Named like the executable, since the binaries of each crate are all qobuz-player
+            let name = std::env::args_os()
+                .next()
+                .and_then(|path| Some(Path::new(&path).file_name()?.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "qobuz-player".into());
+            clap_complete::generate(shell, &mut Cli::command(), name, &mut std::io::stdout());
+            Ok(None)
         }
         SharedCommands::SetMaxAudioQuality { quality } => {
             database.set_max_audio_quality(quality).await?;
 
             println!("Max audio quality saved.");
-            Ok(())
+            Ok(None)
+        }
+        SharedCommands::SetDataSaver { enabled } => {
+            database.set_data_saver(enabled).await?;
+
+            println!(
+                "Data saver {}.",
+                if enabled { "enabled" } else { "disabled" }
+            );
+            Ok(None)
+        }
+        SharedCommands::SetPlayPreviews { enabled } => {
+            database.set_play_previews(enabled).await?;
+
+            println!(
+                "Playing previews {}.",
+                if enabled { "enabled" } else { "disabled" }
+            );
+            Ok(None)
+        }
+        SharedCommands::SetQuietHours { hours, max_volume } => {
+            let quiet_hours = match hours.as_str() {
+                "off" => None,
+                hours => Some(
+                    QuietHours::new(hours, max_volume)
+                        .map_err(|message| Error::Client { message })?,
+                ),
+            };
+            database.set_quiet_hours(quiet_hours).await?;
+
+            match quiet_hours {
+                Some(quiet_hours) => println!("Quiet hours set to {quiet_hours}."),
+                None => println!("Quiet hours turned off."),
+            }
+            Ok(None)
+        }
+        SharedCommands::Remote { command, instance } => {
+            remote::run(command, &instance).await?;
+            Ok(None)
+        }
+        SharedCommands::Api { command, output } => {
+            let max_audio_quality = default_audio_quality(database, None).await?;
+            let client = get_client(database, max_audio_quality, headless, None).await?;
+
+            api::run(command, output, &client).await?;
+            Ok(None)
+        }
+        SharedCommands::ImportPlaylist {
+            file,
+            name,
+            public,
+            yes,
+        } => {
+            let max_audio_quality = default_audio_quality(database, None).await?;
+            let client = get_client(database, max_audio_quality, headless, None).await?;
+
+            import::run(&client, &file, name, public, yes).await?;
+            Ok(None)
+        }
+        SharedCommands::Cache { command } => {
+            cache::run(
+                command,
+                database,
+                &default_audio_cache(shared.audio_cache.clone()),
+                &default_artwork_cache(shared.artwork_cache.clone()),
+                shared.audio_cache_max_bytes(),
+            )
+            .await?;
+            Ok(None)
+        }
+        SharedCommands::Alarm { command } => {
+            alarm::run(command, database, headless).await?;
+            Ok(None)
+        }
+        SharedCommands::Play { link, instance, at } => {
+            match play::play_on_instance(&instance, &link, at).await? {
+                true => Ok(None),
+                false => {
+                    println!(
+                        "No player is running at {}, starting one",
+                        instance.instance
+                    );
+                    Ok(Some(PlayLink { link, at }))
+                }
+            }
         }
     }
 }
 
+fn parse_position(text: &str) -> Result<Duration, String> {
+    controls::parse_position(text).ok_or_else(|| format!("{text} is not a position like 3:41"))
+}
+
+/// Client serving the fixture directory, when there is one
+#[cfg_attr(not(feature = "fixtures"), allow(unused_variables))]
+pub fn fixture_client(fixtures: Option<&Path>) -> AppResult<Option<Client>> {
+    #[cfg(feature = "fixtures")]
+    if let Some(fixtures) = fixtures {
+        return Ok(Some(Client::with_fixtures(
+            qobuz_player_controls::fixtures::Fixtures::load(fixtures)?,
+        )));
+    }
+
+    Ok(None)
+}
+
 pub async fn get_client(
     database: &Database,
     max_audio_quality: AudioQuality,
     headless: bool,
+    fixtures: Option<&Path>,
 ) -> AppResult<Client> {
+    if let Some(client) = fixture_client(fixtures)? {
+        return Ok(client);
+    }
+
     let database_credentials = database.get_credentials().await?;
 
     let client = match database_credentials {
@@ -143,7 +1019,7 @@ pub fn spawn_clean_up(database: Arc<Database>, audio_cache_time_to_live: u32) {
                     .await
                 {
                     for path in deleted_paths {
-                        _ = tokio::fs::remove_file(path.as_path()).await;
+                        audio_cache::remove_track(&path).await;
                     }
                 };
             }
@@ -153,7 +1029,127 @@ pub fn spawn_clean_up(database: Arc<Database>, audio_cache_time_to_live: u32) {
     }
 }
 
-fn default_audio_cache(path: Option<PathBuf>) -> PathBuf {
+/// Retry interval for the favorites refresh while the server can not be reached
+const FAVORITES_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Loads the favorites stored by an earlier session into the client, then keeps them
+/// in sync with the server. A failed refresh is retried every minute, so favorites are
+/// reconciled shortly after the connection comes back.
+pub async fn spawn_favorites_sync(
+    database: Arc<Database>,
+    client: Arc<Client>,
+    refresh_minutes: u64,
+) {
+    if let Some(favorites) = database.get_favorites().await {
+        client.set_cached_favorites(favorites).await;
+    }
+
+    if refresh_minutes == 0 {
+        return;
+    }
+
+    let refresh_interval = Duration::from_secs(refresh_minutes * 60);
+
+    tokio::spawn(async move {
+        loop {
+            let delay = match client.refresh_favorites().await {
+                Ok(favorites) => {
+                    _ = database.set_favorites(&favorites).await;
+                    refresh_interval
+                }
+                Err(_) => FAVORITES_RETRY_INTERVAL,
+            };
+
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
+
+/// Announces new releases of followed artists, when there is somewhere to announce them
+pub fn spawn_release_notifications(
+    database: Arc<Database>,
+    client: Arc<Client>,
+    shared: &SharedArgs,
+) {
+    let notifiers = shared.release_notifiers();
+    if notifiers.is_empty() || shared.release_check_hours == 0 {
+        return;
+    }
+
+    let interval = Duration::from_secs(shared.release_check_hours * 60 * 60);
+    tokio::spawn(release_notifications::run(
+        database, client, notifiers, interval,
+    ));
+}
+
+/// Starts the scheduled alarms of the database
+pub fn spawn_alarms(
+    database: Arc<Database>,
+    client: Arc<Client>,
+    player: &Player,
+    broadcast: Arc<NotificationBroadcast>,
+    ramp_seconds: u64,
+) {
+    tokio::spawn(alarms::run(
+        database,
+        client,
+        player.controls(),
+        player.volume(),
+        player.volume_cap(),
+        broadcast,
+        Duration::from_secs(ramp_seconds),
+    ));
+}
+
+/// Runs the shell commands of the hooks on playback events
+pub fn spawn_hooks(hooks: Hooks, player: &Player) {
+    tokio::spawn(hooks::init(
+        hooks,
+        player.tracklist(),
+        player.status(),
+        player.transitions(),
+    ));
+}
+
+/// Posts playback events to the webhook urls
+pub fn spawn_webhooks(urls: Vec<String>, player: &Player) {
+    tokio::spawn(webhooks::init(
+        urls,
+        player.tracklist(),
+        player.position(),
+        player.status(),
+        player.transitions(),
+    ));
+}
+
+/// Joins or leads a playback group. A group that can not start ends the process
+pub fn spawn_group(role: GroupRole, player: &Player) {
+    let controls = player.controls();
+    let tracklist_receiver = player.tracklist();
+    let status_receiver = player.status();
+    let position_receiver = player.position();
+    tokio::spawn(async move {
+        if let Err(e) = group::init(
+            role,
+            controls,
+            tracklist_receiver,
+            status_receiver,
+            position_receiver,
+        )
+        .await
+        {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    });
+}
+
+/// Restarts playback within seconds when the network changes or the computer wakes up
+pub fn spawn_network_monitor(client: Arc<Client>, controls: Controls) {
+    tokio::spawn(network_monitor::init(client, controls));
+}
+
+pub fn default_audio_cache(path: Option<PathBuf>) -> PathBuf {
     path.unwrap_or_else(|| {
         let mut cache_dir = std::env::temp_dir();
         cache_dir.push("qobuz-player-cache");
@@ -161,6 +1157,14 @@ fn default_audio_cache(path: Option<PathBuf>) -> PathBuf {
     })
 }
 
+pub fn default_artwork_cache(path: Option<PathBuf>) -> PathBuf {
+    path.unwrap_or_else(|| {
+        let mut cache_dir = std::env::temp_dir();
+        cache_dir.push("qobuz-player-artwork");
+        cache_dir
+    })
+}
+
 pub async fn default_audio_quality(
     database: &Database,
     args: Option<AudioQuality>,
@@ -174,32 +1178,92 @@ pub async fn default_audio_quality(
     }
 }
 
+/// The device saved by `setup` unless one is given
+pub async fn default_output_device(
+    database: &Database,
+    args: Option<String>,
+) -> AppResult<Option<String>> {
+    match args {
+        Some(device) => Ok(Some(device)),
+        None => Ok(database.get_configuration().await?.output_device),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn create_player(
-    audio_cache: Option<PathBuf>,
+    shared: &SharedArgs,
+    delay: Option<&DelayArgs>,
+    audio_output: AudioOutput,
     database: Arc<Database>,
     client: Arc<Client>,
     broadcast: Arc<NotificationBroadcast>,
-    state_change_delay_ms: Option<u64>,
-    sample_rate_change_delay_ms: Option<u64>,
-    output_device_id: Option<String>,
 ) -> AppResult<Player> {
     let tracklist = database.get_tracklist().await.unwrap_or_default();
     let volume = database.get_volume().await.unwrap_or(1.0);
-    let audio_cache = default_audio_cache(audio_cache);
+    let configuration = database.get_configuration().await?;
+    let quiet_hours = database.get_quiet_hours().await?;
+    let saved_zones = database.get_zones().await?;
+    client.set_data_saver(configuration.data_saver).await;
+    client.set_play_previews(configuration.play_previews);
+    if shared.encrypt_audio_cache {
+        client.enable_audio_cache_encryption()?;
+    }
+    let audio_cache = default_audio_cache(shared.audio_cache.clone());
+    if let Some(key) = client.audio_cache_key() {
+        tokio::spawn(audio_cache::encrypt_plain_files(audio_cache.clone(), key));
+    }
 
-    let state_change_delay = state_change_delay_ms.map(Duration::from_millis);
-    let sample_rate_change_delay = sample_rate_change_delay_ms.map(Duration::from_millis);
+    let state_change_delay = delay
+        .and_then(|delay| delay.state_change_delay_ms)
+        .map(Duration::from_millis);
+    let sample_rate_change_delay = delay
+        .and_then(|delay| delay.sample_rate_change_delay_ms)
+        .map(Duration::from_millis);
+    let volume_ramp =
+        (shared.volume_ramp_ms != 0).then(|| Duration::from_millis(shared.volume_ramp_ms));
+    let skip_fade = (shared.skip_fade_ms != 0).then(|| Duration::from_millis(shared.skip_fade_ms));
+    let resume_threshold = (shared.resume_threshold_minutes != 0)
+        .then(|| Duration::from_secs(shared.resume_threshold_minutes * 60));
+    let stall_timeout = (shared.stall_timeout_seconds != 0)
+        .then(|| Duration::from_secs(shared.stall_timeout_seconds));
+    let hardware_mixer = shared
+        .alsa_mixer_control
+        .clone()
+        .map(|control| HardwareMixer::open(shared.alsa_mixer_device.clone(), control))
+        .transpose()?;
 
     let player = Player::new(
         tracklist,
         client,
         volume,
         broadcast,
-        audio_cache,
         database,
-        state_change_delay,
-        sample_rate_change_delay,
-        output_device_id,
+        PlayerSettings {
+            audio_cache_dir: audio_cache,
+            audio_cache_max_bytes: shared.audio_cache_max_bytes(),
+            state_change_delay,
+            sample_rate_change_delay,
+            preferred_device_id: shared.output_device_id.clone(),
+            zone_devices: shared.zones.clone(),
+            saved_zones,
+            precache_album_tracks: shared.precache_album_tracks,
+            jump_forward_seconds: shared.jump_forward_seconds,
+            jump_backward_seconds: shared.jump_backward_seconds,
+            volume_curve: shared.volume_curve,
+            volume_ramp,
+            volume_limit: f32::from(shared.max_volume) / 100.0,
+            skip_fade,
+            balance: configuration.balance as f32,
+            mono_downmix: configuration.mono_downmix,
+            trim_silence: configuration.trim_silence,
+            dsp_command: shared.dsp_command.clone(),
+            prefetch_window: Duration::from_secs(shared.prefetch_seconds),
+            resume_threshold,
+            stall_timeout,
+            hardware_mixer,
+            audio_output,
+            quiet_hours,
+        },
     )?;
 
     Ok(player)
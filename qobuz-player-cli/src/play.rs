@@ -0,0 +1,73 @@
+use std::{sync::Arc, time::Duration};
+
+use qobuz_player_controls::{
+    AppResult, client::Client, controls::Controls, notification::NotificationBroadcast,
+};
+use reqwest::Method;
+
+use crate::instance::{Instance, InstanceArgs};
+
+/// Link given to `qobuz-player play`, with the position to start the track at
+#[derive(Debug, Clone)]
+pub struct PlayLink {
+    pub link: String,
+    pub at: Option<Duration>,
+}
+
+/// Asks the web api of a running player to play `link`. `Ok(false)` when nothing is listening
+pub(crate) async fn play_on_instance(
+    instance: &InstanceArgs,
+    link: &str,
+    at: Option<Duration>,
+) -> AppResult<bool> {
+    let Some(instance) = Instance::connect(instance).await? else {
+        return Ok(false);
+    };
+
+    let request = instance
+        .request(Method::PUT, "/api/play/link")
+        .json(&serde_json::json!({
+            "link": link,
+            "at": at.map(|at| at.as_secs().to_string()),
+        }));
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(err) if err.is_connect() => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+
+    let title = instance
+        .accepted(response)
+        .await?
+        .json::<serde_json::Value>()
+        .await?
+        .get("title")
+        .and_then(|title| title.as_str().map(ToString::to_string))
+        .unwrap_or_else(|| link.to_string());
+
+    println!("Playing {title} on {}", instance.address());
+    Ok(true)
+}
+
+/// Plays the link given to `qobuz-player play` once this player has started
+pub fn spawn_play_link(
+    link: Option<PlayLink>,
+    client: Arc<Client>,
+    controls: Controls,
+    broadcast: Arc<NotificationBroadcast>,
+) {
+    let Some(link) = link else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let played = client
+            .resolve_link(&link.link)
+            .await
+            .and_then(|entity| controls.play_linked_entity_at(&entity, link.at));
+        if let Err(err) = played {
+            broadcast.report(&err);
+        }
+    });
+}
@@ -0,0 +1,480 @@
+use std::time::Duration;
+
+use clap::{Subcommand, ValueEnum};
+use qobuz_player_controls::{AppResult, Status, error::Error};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+
+use crate::{
+    api::format_duration,
+    instance::{Instance, InstanceArgs},
+};
+
+/// Seconds each long poll of the event api waits, so the position is printed about every second
+const FOLLOW_POLL_SECONDS: u64 = 1;
+
+/// Longest wait between polls while the player can not be reached
+const FOLLOW_RETRY_MAX: Duration = Duration::from_secs(30);
+
+#[derive(Subcommand, Debug)]
+pub enum RemoteCommand {
+    /// Print the playing track
+    Current {
+        #[clap(long, default_value_t = false)]
+        /// Print the open.qobuz.com url of the track instead, to share it
+        url: bool,
+
+        #[clap(long, default_value_t = false, requires = "url")]
+        /// With --url, print the url of the album or playlist being played
+        list: bool,
+    },
+
+    /// Print the status as a JSON line, for scripts and status bars
+    Status {
+        #[clap(long, default_value_t = false)]
+        /// Keep printing a line on every track, playback and volume change, and the position
+        /// every second while playing
+        follow: bool,
+    },
+
+    /// Print the playing track in a format, for status bars like waybar and polybar. Prints an
+    /// empty line when nothing is playing
+    NowPlaying {
+        #[clap(long, default_value = "{artist} - {title}")]
+        /// Placeholders are {title}, {artist}, {list}, {url}, {position}, {duration}, {status}
+        /// and {volume}
+        format: String,
+
+        #[clap(long, value_enum, default_value_t = Escape::None)]
+        /// Escape the values for the markup of the status bar
+        escape: Escape,
+
+        #[clap(long, default_value_t = false)]
+        /// Keep printing a line on every change, and every second while playing
+        follow: bool,
+    },
+}
+
+/// The fields of the web api play info used here
+#[derive(Deserialize)]
+struct PlayingInfo {
+    title: String,
+    artist_name: Option<String>,
+    entity_title: Option<String>,
+    track_url: Option<String>,
+    list_url: Option<String>,
+    #[serde(default)]
+    now_playing_id: Option<u32>,
+    #[serde(default)]
+    status: Status,
+    #[serde(default)]
+    duration_ms: u64,
+    #[serde(default)]
+    position_ms: u64,
+    #[serde(default)]
+    current_volume: u32,
+}
+
+/// Line printed by the status command. Every line holds the whole status, and `event` names
+/// what changed
+#[derive(Serialize)]
+struct StatusLine {
+    event: &'static str,
+    #[serde(serialize_with = "serialize_status")]
+    status: Status,
+    track_id: Option<u32>,
+    title: Option<String>,
+    artist: Option<String>,
+    /// Album or playlist being played
+    list: Option<String>,
+    track_url: Option<String>,
+    position_ms: u64,
+    duration_ms: u64,
+    volume: u32,
+}
+
+impl StatusLine {
+    fn new(info: PlayingInfo) -> Self {
+        let playing = info.now_playing_id.is_some();
+        Self {
+            event: "status",
+            status: info.status,
+            track_id: info.now_playing_id,
+            title: playing.then_some(info.title),
+            artist: info.artist_name,
+            list: info.entity_title,
+            track_url: info.track_url,
+            position_ms: info.position_ms,
+            duration_ms: info.duration_ms,
+            volume: info.current_volume,
+        }
+    }
+
+    fn print(&mut self, event: &'static str) {
+        self.event = event;
+        println!("{}", serde_json::to_string(self).expect("infallible"));
+    }
+}
+
+fn serialize_status<S: serde::Serializer>(
+    status: &Status,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(status_name(*status))
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Playing => "playing",
+        Status::Buffering => "buffering",
+        Status::Paused => "paused",
+    }
+}
+
+/// A batch of the long poll event api of the web server
+#[derive(Deserialize)]
+struct EventBatch {
+    cursor: u64,
+    missed: bool,
+    position_ms: u64,
+    events: Vec<Event>,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    event: String,
+    data: Value,
+}
+
+pub(crate) async fn run(command: RemoteCommand, instance: &InstanceArgs) -> AppResult<()> {
+    let instance = &Instance::connect(instance)
+        .await?
+        .ok_or_else(|| not_running(&instance.instance))?;
+
+    match command {
+        RemoteCommand::Current { url, list } => {
+            let info: PlayingInfo = get_json(instance, "/api/play-info").await?;
+            let nothing_playing = || Error::Client {
+                message: format!("Nothing is playing on {}", instance.address()),
+            };
+
+            if url {
+                let url = match list {
+                    true => info.list_url,
+                    false => info.track_url,
+                };
+                println!("{}", url.ok_or_else(nothing_playing)?);
+                return Ok(());
+            }
+
+            if info.track_url.is_none() {
+                return Err(nothing_playing());
+            }
+            match info.artist_name {
+                Some(artist) => println!("{artist} - {}", info.title),
+                None => println!("{}", info.title),
+            }
+            if let Some(entity_title) = info.entity_title {
+                println!("From {entity_title}");
+            }
+            Ok(())
+        }
+        RemoteCommand::Status { follow } => {
+            let info: PlayingInfo = get_json(instance, "/api/play-info").await?;
+            let mut line = StatusLine::new(info);
+            line.print("status");
+
+            if follow {
+                follow_status(instance, line, StatusLine::print).await?;
+            }
+            Ok(())
+        }
+        RemoteCommand::NowPlaying {
+            format,
+            escape,
+            follow,
+        } => {
+            let print = |line: &mut StatusLine, _| println!("{}", render(&format, line, escape));
+
+            let info: PlayingInfo = get_json(instance, "/api/play-info").await?;
+            let mut line = StatusLine::new(info);
+            print(&mut line, "status");
+
+            if follow {
+                follow_status(instance, line, print).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Fills in the placeholders of a now playing format. Empty when nothing is playing, which
+/// hides the module in most status bars
+fn render(format: &str, line: &StatusLine, escape: Escape) -> String {
+    if line.title.is_none() {
+        return String::new();
+    }
+
+    let placeholders = [
+        ("{title}", line.title.clone().unwrap_or_default()),
+        ("{artist}", line.artist.clone().unwrap_or_default()),
+        ("{list}", line.list.clone().unwrap_or_default()),
+        ("{url}", line.track_url.clone().unwrap_or_default()),
+        (
+            "{position}",
+            format_duration((line.position_ms / 1000) as u32),
+        ),
+        (
+            "{duration}",
+            format_duration((line.duration_ms / 1000) as u32),
+        ),
+        ("{status}", status_name(line.status).to_string()),
+        ("{volume}", line.volume.to_string()),
+    ];
+
+    placeholders
+        .into_iter()
+        .fold(format.to_string(), |output, (placeholder, value)| {
+            output.replace(placeholder, &escape.apply(&value))
+        })
+}
+
+/// How values are escaped before they are put into the format
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Escape {
+    #[default]
+    None,
+    /// Pango markup, as used by waybar
+    Pango,
+    /// Polybar formatting tags
+    Polybar,
+    /// Inside a JSON string, for waybar modules with return-type json
+    Json,
+}
+
+impl Escape {
+    fn apply(self, value: &str) -> String {
+        match self {
+            Self::None => value.to_string(),
+            Self::Pango => value
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('\'', "&#39;")
+                .replace('"', "&quot;"),
+            Self::Polybar => value.replace('%', "%%"),
+            Self::Json => {
+                let quoted = serde_json::to_string(value).expect("infallible");
+                quoted[1..quoted.len() - 1].to_string()
+            }
+        }
+    }
+}
+
+/// Prints a line for every change from the event api of the running player. Polls that fail,
+/// like while the player restarts, are retried with a growing delay. `print` is given the
+/// status and what changed
+async fn follow_status(
+    instance: &Instance,
+    mut line: StatusLine,
+    print: impl Fn(&mut StatusLine, &'static str),
+) -> AppResult<()> {
+    let batch: EventBatch = get_json(instance, "/api/v1/events").await?;
+    let mut cursor = batch.cursor;
+    let mut position_printed = Instant::now();
+    let mut backoff = Backoff::default();
+
+    loop {
+        let polled = poll_status(instance, cursor, &mut line, &print).await;
+        match polled {
+            Ok(next_cursor) => {
+                cursor = next_cursor;
+                backoff.reset();
+            }
+            Err(err) => {
+                let delay = backoff.next_delay();
+                eprintln!("{err}, retrying in {} seconds", delay.as_secs());
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+        }
+
+        if line.status == Status::Playing
+            && position_printed.elapsed() >= Duration::from_secs(FOLLOW_POLL_SECONDS)
+        {
+            position_printed = Instant::now();
+            print(&mut line, "position");
+        }
+    }
+}
+
+/// Applies one batch of events after `cursor` to the line, and returns the next cursor
+async fn poll_status(
+    instance: &Instance,
+    cursor: u64,
+    line: &mut StatusLine,
+    print: &impl Fn(&mut StatusLine, &'static str),
+) -> AppResult<u64> {
+    let path = format!("/api/v1/events?cursor={cursor}&timeout={FOLLOW_POLL_SECONDS}");
+    let batch: EventBatch = get_json(instance, &path).await?;
+    line.position_ms = batch.position_ms;
+
+    // Events were dropped, or the player restarted
+    if batch.missed {
+        let info: PlayingInfo = get_json(instance, "/api/play-info").await?;
+        *line = StatusLine::new(info);
+        print(line, "status");
+        return Ok(batch.cursor);
+    }
+
+    for event in batch.events {
+        match event.event.as_str() {
+            "tracklist" => {
+                let track_id = event
+                    .data
+                    .get("now_playing_id")
+                    .and_then(Value::as_u64)
+                    .map(|id| id as u32);
+                if track_id == line.track_id {
+                    continue;
+                }
+                let info: PlayingInfo = get_json(instance, "/api/play-info").await?;
+                *line = StatusLine::new(info);
+                print(line, "track");
+            }
+            "status" => {
+                line.status = playback_status(&event.data);
+                print(line, "playback");
+            }
+            "volume" => {
+                line.volume = event.data.as_u64().unwrap_or_default() as u32;
+                print(line, "volume");
+            }
+            _ => {}
+        }
+    }
+
+    Ok(batch.cursor)
+}
+
+/// Status of a `status` event of the event api
+fn playback_status(data: &Value) -> Status {
+    match data.as_str() {
+        Some("play") => Status::Playing,
+        Some("buffering") => Status::Buffering,
+        _ => Status::Paused,
+    }
+}
+
+/// Delay before retrying a failed poll, doubling from a second up to `FOLLOW_RETRY_MAX`
+#[derive(Default)]
+struct Backoff {
+    delay: Option<Duration>,
+}
+
+impl Backoff {
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.delay.map_or(Duration::from_secs(1), |delay| {
+            (delay * 2).min(FOLLOW_RETRY_MAX)
+        });
+        self.delay = Some(delay);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.delay = None;
+    }
+}
+
+/// Fetches `path` from the web api of the running player
+async fn get_json<T: serde::de::DeserializeOwned>(instance: &Instance, path: &str) -> AppResult<T> {
+    let response = match instance.request(Method::GET, path).send().await {
+        Ok(response) => response,
+        Err(err) if err.is_connect() => return Err(not_running(instance.address())),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(instance.accepted(response).await?.json().await?)
+}
+
+fn not_running(address: &str) -> Error {
+    Error::Client {
+        message: format!("No player is running at {address}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn line(title: Option<&str>) -> StatusLine {
+        StatusLine {
+            event: "status",
+            status: Status::Playing,
+            track_id: title.map(|_| 1),
+            title: title.map(Into::into),
+            artist: Some("Artist & <Band>".into()),
+            list: Some("Album".into()),
+            track_url: Some("https://open.qobuz.com/track/1".into()),
+            position_ms: 61_500,
+            duration_ms: 3_723_000,
+            volume: 80,
+        }
+    }
+
+    #[test]
+    fn renders_the_placeholders() {
+        let output = render(
+            "{artist} - {title} ({list}) {position}/{duration} {status} {volume}%",
+            &line(Some("Title")),
+            Escape::None,
+        );
+
+        assert_eq!(
+            output,
+            "Artist & <Band> - Title (Album) 1:01/1:02:03 playing 80%"
+        );
+    }
+
+    #[test]
+    fn renders_nothing_when_nothing_plays() {
+        assert_eq!(render("{title}", &line(None), Escape::None), "");
+    }
+
+    #[test]
+    fn escapes_only_the_values() {
+        let output = render("<b>{artist}</b>", &line(Some("Title")), Escape::Pango);
+
+        assert_eq!(output, "<b>Artist &amp; &lt;Band&gt;</b>");
+    }
+
+    #[test]
+    fn retries_with_a_growing_delay() {
+        let mut backoff = Backoff::default();
+        let delays: Vec<_> = (0..7).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn reads_the_playback_status_events() {
+        assert_eq!(playback_status(&json!("play")), Status::Playing);
+        assert_eq!(playback_status(&json!("buffering")), Status::Buffering);
+        assert_eq!(playback_status(&json!("pause")), Status::Paused);
+        assert_eq!(playback_status(&Value::Null), Status::Paused);
+    }
+
+    #[test]
+    fn escapes_for_the_status_bars() {
+        assert_eq!(Escape::None.apply("50% \"off\""), "50% \"off\"");
+        assert_eq!(Escape::Pango.apply("It's"), "It&#39;s");
+        assert_eq!(Escape::Polybar.apply("50%"), "50%%");
+        assert_eq!(Escape::Json.apply("say \"hi\"\n"), "say \\\"hi\\\"\\n");
+    }
+}
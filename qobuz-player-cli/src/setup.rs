@@ -0,0 +1,95 @@
+use std::io::{BufRead, Write};
+
+use qobuz_player_controls::{
+    AppResult, AudioQuality, client::Client, database::Database, output_device_names,
+};
+
+const QUALITIES: [AudioQuality; 4] = [
+    AudioQuality::Mp3,
+    AudioQuality::CD,
+    AudioQuality::HIFI96,
+    AudioQuality::HIFI192,
+];
+
+/// Logs in, checks the account works and saves the output device and quality of the profile.
+/// Enter keeps the current value of each step
+pub(crate) async fn run(database: &Database, headless: bool) -> AppResult<()> {
+    let logged_in = database.get_credentials().await?.is_some();
+    let login = !logged_in
+        || prompt("Already logged in. Log in again? [y/N] ")
+            .is_some_and(|answer| answer.eq_ignore_ascii_case("y"));
+    if login {
+        let (_client, oauth_result) =
+            Client::new_with_oauth_login(AudioQuality::Mp3, headless).await?;
+        database.set_credentials(oauth_result.into()).await?;
+    }
+
+    let configuration = database.get_configuration().await?;
+    let credentials = database.get_credentials().await?;
+    let client = Client::new(credentials, configuration.max_audio_quality.into());
+    let favorites = client.favorites().await?;
+    println!(
+        "Login works, the account has {} favorite albums.",
+        favorites.albums.len()
+    );
+
+    println!();
+    println!(
+        "Output device, currently {}:",
+        configuration.output_device.as_deref().unwrap_or("default")
+    );
+    let devices = output_device_names();
+    println!("  0) Default device");
+    for (index, device) in devices.iter().enumerate() {
+        println!("  {}) {device}", index + 1);
+    }
+    if let Some(choice) = choose(devices.len() + 1) {
+        let device = choice.checked_sub(1).map(|index| devices[index].as_str());
+        database.set_output_device(device).await?;
+    }
+
+    println!();
+    let current_quality = AudioQuality::from(configuration.max_audio_quality) as i32;
+    println!("Maximum audio quality:");
+    for (index, quality) in QUALITIES.iter().enumerate() {
+        let current = match *quality as i32 == current_quality {
+            true => " (current)",
+            false => "",
+        };
+        println!("  {index}) {}{current}", quality.label());
+    }
+    if let Some(choice) = choose(QUALITIES.len()) {
+        database.set_max_audio_quality(QUALITIES[choice]).await?;
+    }
+
+    println!();
+    println!("Setup done! You can now run qobuz-player.");
+    Ok(())
+}
+
+/// Asks for a choice of `0..count`. `None` when enter is pressed or input ends
+fn choose(count: usize) -> Option<usize> {
+    loop {
+        let answer = prompt(&format!("Pick 0-{}, or enter to keep: ", count - 1))?;
+        if let Some(choice) = answer
+            .parse::<usize>()
+            .ok()
+            .filter(|choice| *choice < count)
+        {
+            return Some(choice);
+        }
+    }
+}
+
+/// Trimmed answer from stdin, `None` when it is empty or input ends
+fn prompt(question: &str) -> Option<String> {
+    print!("{question}");
+    _ = std::io::stdout().flush();
+
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).unwrap_or(0) == 0 {
+        return None;
+    }
+
+    Some(answer.trim().to_string()).filter(|answer| !answer.is_empty())
+}
@@ -9,14 +9,17 @@ use crate::{
         favorites::Favorites,
         featured::{FeaturedAlbumsResponse, FeaturedPlaylistsResponse},
         genre::{GenreFeaturedPlaylists, GenreResponse},
+        label::Label,
         playlist::{Playlist, UserPlaylistsResult},
         search_results::SearchAllResults,
         track::Track,
     },
     stream::{
+        cache_encryption::CacheKey,
         cmaf, crypto, fetch_segment,
         flac_source_stream::{
-            FlacSourceParams, FlacSourceStream, SeekableStreamReader, SegmentByteInfo,
+            DownloadProgress, FlacSourceParams, FlacSourceStream, SeekableStreamReader,
+            SegmentByteInfo,
         },
     },
 };
@@ -33,6 +36,7 @@ use std::{
     fmt::Display,
     net::TcpListener,
     path::PathBuf,
+    sync::Arc,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use stream_download::{Settings, StreamDownload, storage::temp::TempStorageProvider};
@@ -49,6 +53,58 @@ pub struct Client {
     user_token: String,
     user_id: i64,
     max_audio_quality: AudioQuality,
+    health: ApiHealth,
+}
+
+/// Outcome of the latest API calls, shared between clones so it can be read without the client
+#[derive(Debug, Default, Clone)]
+pub struct ApiHealth(Arc<parking_lot::Mutex<ApiHealthState>>);
+
+#[derive(Debug, Default, Clone)]
+pub struct ApiHealthState {
+    pub last_success: Option<SystemTime>,
+    pub last_error: Option<ApiError>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub message: String,
+    pub at: SystemTime,
+}
+
+impl ApiHealth {
+    pub fn state(&self) -> ApiHealthState {
+        self.0.lock().clone()
+    }
+
+    pub fn record_success(&self) {
+        self.0.lock().last_success = Some(SystemTime::now());
+    }
+
+    pub fn record_error(&self, message: String) {
+        self.0.lock().last_error = Some(ApiError {
+            message,
+            at: SystemTime::now(),
+        });
+    }
+
+    fn record<T>(&self, result: &Result<T>) {
+        match result {
+            Ok(_) => self.record_success(),
+            Err(error) => self.record_error(error.to_string()),
+        }
+    }
+}
+
+impl ApiHealthState {
+    /// The latest call succeeded, or no call has failed yet
+    pub fn healthy(&self) -> bool {
+        match (&self.last_error, self.last_success) {
+            (None, _) => true,
+            (Some(error), Some(success)) => success > error.at,
+            (Some(_), None) => false,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, clap::ValueEnum)]
@@ -70,23 +126,50 @@ impl Display for AudioQuality {
     }
 }
 
-impl From<Option<i64>> for AudioQuality {
-    fn from(value: Option<i64>) -> Self {
-        let default = AudioQuality::HIFI192;
+impl AudioQuality {
+    /// Next quality to try when this one is refused
+    pub fn lower(&self) -> Option<Self> {
+        match self {
+            AudioQuality::Mp3 => None,
+            AudioQuality::CD => Some(AudioQuality::Mp3),
+            AudioQuality::HIFI96 => Some(AudioQuality::CD),
+            AudioQuality::HIFI192 => Some(AudioQuality::HIFI96),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AudioQuality::Mp3 => "MP3",
+            AudioQuality::CD => "CD (16-bit / 44.1 kHz)",
+            AudioQuality::HIFI96 => "Hi-Res (24-bit / 96 kHz)",
+            AudioQuality::HIFI192 => "Hi-Res (24-bit / 192 kHz)",
+        }
+    }
+}
+
+impl TryFrom<i64> for AudioQuality {
+    type Error = String;
 
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
         match value {
-            Some(value) => match value {
-                5 => AudioQuality::Mp3,
-                6 => AudioQuality::CD,
-                7 => AudioQuality::HIFI96,
-                27 => AudioQuality::HIFI192,
-                _ => default,
-            },
-            None => default,
+            5 => Ok(AudioQuality::Mp3),
+            6 => Ok(AudioQuality::CD),
+            7 => Ok(AudioQuality::HIFI96),
+            27 => Ok(AudioQuality::HIFI192),
+            _ => Err(format!("{value} is not an audio quality")),
         }
     }
 }
 
+/// Stored quality, the highest one when none or an unknown one is stored
+impl From<Option<i64>> for AudioQuality {
+    fn from(value: Option<i64>) -> Self {
+        value
+            .and_then(|value| Self::try_from(value).ok())
+            .unwrap_or(AudioQuality::HIFI192)
+    }
+}
+
 pub enum ReleaseType {
     Albums,
     EPsAndSingles,
@@ -107,6 +190,20 @@ impl ReleaseType {
     }
 }
 
+pub enum ReleaseSort {
+    ReleaseDate,
+    Relevance,
+}
+
+impl ReleaseSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReleaseSort::ReleaseDate => "release_date",
+            ReleaseSort::Relevance => "relevant",
+        }
+    }
+}
+
 pub enum FeaturedAlbumType {
     PressAwards,
     MostStreamed,
@@ -168,6 +265,7 @@ pub struct FavoriteCollection {
 
 enum Endpoint {
     Album,
+    ArtistGet,
     ArtistPage,
     SimilarArtists,
     ArtistReleases,
@@ -194,12 +292,14 @@ enum Endpoint {
     GenreList,
     GenreFeatured,
     GenrePlaylists,
+    Label,
 }
 
 impl Display for Endpoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let endpoint = match self {
             Endpoint::Album => "album/get",
+            Endpoint::ArtistGet => "artist/get",
             Endpoint::ArtistPage => "artist/page",
             Endpoint::ArtistReleases => "artist/getReleasesList",
             Endpoint::SimilarArtists => "artist/getSimilarArtists",
@@ -226,6 +326,7 @@ impl Display for Endpoint {
             Endpoint::GenreList => "genre/list",
             Endpoint::GenreFeatured => "album/getFeatured",
             Endpoint::GenrePlaylists => "discover/playlists",
+            Endpoint::Label => "label/get",
         };
 
         f.write_str(endpoint)
@@ -373,6 +474,7 @@ impl Client {
             app_id,
             base_url,
             max_audio_quality,
+            health: Default::default(),
         };
 
         Ok(client)
@@ -386,6 +488,15 @@ impl Client {
         self.user_id
     }
 
+    pub fn set_max_audio_quality(&mut self, max_audio_quality: AudioQuality) {
+        self.max_audio_quality = max_audio_quality;
+    }
+
+    /// Report the outcome of API calls to a shared health record
+    pub fn set_health(&mut self, health: ApiHealth) {
+        self.health = health;
+    }
+
     pub async fn featured_albums(
         &self,
         featured_type: FeaturedAlbumType,
@@ -626,6 +737,7 @@ impl Client {
         &mut self,
         track_info: TrackInfo,
         cache_path: PathBuf,
+        cache_key: Option<CacheKey>,
     ) -> Result<SeekableStreamReader> {
         let session_infos = self.session_infos().map(|s| s.to_string());
 
@@ -680,13 +792,20 @@ impl Client {
             total_byte_len,
         );
 
+        let progress = Arc::new(DownloadProgress::new(
+            audio_segments as usize,
+            total_byte_len,
+        ));
+
         let params = FlacSourceParams {
             url_template: track_info.url_template,
             n_segments: n_segments_to_download,
             content_key,
             flac_header: init_info.flac_header,
             cache_path,
+            cache_key,
             segment_map: segment_map.clone(),
+            progress: progress.clone(),
         };
 
         let reader = StreamDownload::new::<FlacSourceStream>(
@@ -699,15 +818,24 @@ impl Client {
             message: format!("Failed to create stream: {e}"),
         })?;
 
-        Ok(SeekableStreamReader::new(reader, total_byte_len))
+        Ok(SeekableStreamReader::new(reader, total_byte_len, progress))
     }
 
     pub async fn track_url(&mut self, track_id: u32) -> Result<TrackInfo> {
+        self.track_url_in_quality(track_id, self.max_audio_quality)
+            .await
+    }
+
+    pub async fn track_url_in_quality(
+        &mut self,
+        track_id: u32,
+        quality: AudioQuality,
+    ) -> Result<TrackInfo> {
         self.ensure_valid_session().await?;
 
         let endpoint = format!("{}{}", &self.base_url, Endpoint::TrackURL);
         let now = format!("{}", time::OffsetDateTime::now_utc().unix_timestamp());
-        let quality_string = self.max_audio_quality.to_string();
+        let quality_string = quality.to_string();
         let track_id_str = track_id.to_string();
 
         let mut args = BTreeMap::<&str, String>::new();
@@ -727,7 +855,7 @@ impl Client {
 
         let session_id = self.session.as_ref().unwrap().session_id.clone();
 
-        match make_get_call(
+        let response = make_get_call(
             &endpoint,
             Some(&params),
             &self.http_client,
@@ -735,8 +863,10 @@ impl Client {
             Some(&self.user_token),
             Some(&session_id),
         )
-        .await
-        {
+        .await;
+        self.health.record(&response);
+
+        match response {
             Ok(response) => match serde_json::from_str::<TrackInfo>(response.as_str()) {
                 Ok(item) => Ok(item),
                 Err(error) => {
@@ -747,9 +877,7 @@ impl Client {
                     })
                 }
             },
-            Err(error) => Err(Error::Api {
-                message: error.to_string(),
-            }),
+            Err(error) => Err(error),
         }
     }
 
@@ -832,10 +960,16 @@ impl Client {
         self.post(&endpoint, form_data).await
     }
 
-    pub async fn search_all(&self, query: &str, limit: i32) -> Result<SearchAllResults> {
+    pub async fn search_all(
+        &self,
+        query: &str,
+        limit: i32,
+        offset: i32,
+    ) -> Result<SearchAllResults> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Search);
         let limit = limit.to_string();
-        let params = vec![("query", query), ("limit", &limit)];
+        let offset = offset.to_string();
+        let params = vec![("query", query), ("limit", &limit), ("offset", &offset)];
 
         self.get(&endpoint, Some(&params)).await
     }
@@ -928,6 +1062,35 @@ impl Client {
         Ok(item)
     }
 
+    /// Artist with the albums and tracks they are credited on, which for a composer
+    /// are the recordings of their works
+    pub async fn composer(&self, composer_id: u32) -> Result<Artist> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::ArtistGet);
+        let composer_id = composer_id.to_string();
+        let params = vec![
+            ("artist_id", composer_id.as_str()),
+            ("extra", "albums,tracks_appears_on"),
+            ("limit", "100"),
+            ("offset", "0"),
+        ];
+
+        self.get(&endpoint, Some(&params)).await
+    }
+
+    /// Label with the albums of its catalogue
+    pub async fn label(&self, label_id: u32) -> Result<Label> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Label);
+        let label_id = label_id.to_string();
+        let params = vec![
+            ("label_id", label_id.as_str()),
+            ("extra", "albums"),
+            ("limit", "100"),
+            ("offset", "0"),
+        ];
+
+        self.get(&endpoint, Some(&params)).await
+    }
+
     pub async fn similar_artists(
         &self,
         artist_id: u32,
@@ -951,6 +1114,7 @@ impl Client {
         &self,
         artist_id: u32,
         release_type: ReleaseType,
+        sort: ReleaseSort,
         limit: Option<i32>,
     ) -> Result<ReleaseQuery> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::ArtistReleases);
@@ -962,7 +1126,7 @@ impl Client {
             ("artist_id", artistid_string.as_str()),
             ("limit", &limit),
             ("release_type", release_type.as_str()),
-            ("sort", "release_date"),
+            ("sort", sort.as_str()),
             ("offset", "0"),
             ("track_size", "1"),
         ];
@@ -975,7 +1139,7 @@ impl Client {
         endpoint: &str,
         params: Option<&[(&str, &str)]>,
     ) -> Result<String> {
-        make_get_call(
+        let response = make_get_call(
             endpoint,
             params,
             &self.http_client,
@@ -983,7 +1147,9 @@ impl Client {
             Some(&self.user_token),
             None,
         )
-        .await
+        .await;
+        self.health.record(&response);
+        response
     }
 
     async fn make_post_call(&self, endpoint: &str, params: HashMap<&str, &str>) -> Result<String> {
@@ -996,9 +1162,14 @@ impl Client {
             .headers(headers)
             .form(&params)
             .send()
-            .await?;
+            .await;
 
-        handle_response(response).await
+        let response = match response {
+            Ok(response) => handle_response(response).await,
+            Err(error) => Err(error.into()),
+        };
+        self.health.record(&response);
+        response
     }
 }
 
@@ -1025,6 +1196,16 @@ async fn handle_response(response: Response) -> Result<String> {
     if response.status() == StatusCode::OK {
         let res = response.text().await.unwrap_or_default();
         Ok(res)
+    } else if response.status() == StatusCode::UNAUTHORIZED {
+        Err(Error::Unauthorized)
+    } else if response.status() == StatusCode::FORBIDDEN {
+        Err(Error::Unavailable {
+            message: response.text().await.unwrap_or_default(),
+        })
+    } else if response.status() == StatusCode::NOT_FOUND {
+        Err(Error::NotFound {
+            message: response.text().await.unwrap_or_default(),
+        })
     } else {
         Err(Error::Api {
             message: response.text().await.unwrap_or_default(),
@@ -1209,3 +1390,40 @@ async fn get_secrets(client: &reqwest::Client) -> Result<Secrets> {
 pub struct SuccessfulResponse {
     status: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AudioQuality;
+
+    #[test]
+    fn qualities_step_down_to_mp3() {
+        let mut qualities = vec![AudioQuality::HIFI192];
+        while let Some(lower) = qualities.last().and_then(AudioQuality::lower) {
+            qualities.push(lower);
+        }
+
+        let format_ids: Vec<i32> = qualities
+            .into_iter()
+            .map(|quality| quality as i32)
+            .collect();
+        assert_eq!(format_ids, [27, 7, 6, 5]);
+    }
+
+    #[test]
+    fn only_known_quality_ids_convert() {
+        for quality in [
+            AudioQuality::Mp3,
+            AudioQuality::CD,
+            AudioQuality::HIFI96,
+            AudioQuality::HIFI192,
+        ] {
+            let converted = AudioQuality::try_from(quality as i64).unwrap();
+            assert_eq!(converted as i32, quality as i32);
+        }
+
+        assert_eq!(
+            AudioQuality::try_from(8).unwrap_err(),
+            "8 is not an audio quality"
+        );
+    }
+}
@@ -16,6 +16,15 @@ pub enum Error {
     Login,
     #[snafu(display("Failed to create client"))]
     Create,
+    #[snafu(display("Qobuz rejected the login"))]
+    Unauthorized,
+    #[snafu(display("Unable to connect to Qobuz api"))]
+    Network,
+    /// The item is not available in the region or subscription of the user
+    #[snafu(display("{message}"))]
+    Unavailable { message: String },
+    #[snafu(display("{message}"))]
+    NotFound { message: String },
     #[snafu(display("{message}"))]
     Api { message: String },
     #[snafu(display("Failed to deserialize json: {message}"))]
@@ -29,12 +38,17 @@ impl From<reqwest::Error> for Error {
         let status = error.status();
 
         match status {
-            Some(status) => Error::Api {
+            Some(reqwest::StatusCode::UNAUTHORIZED) => Error::Unauthorized,
+            Some(status @ reqwest::StatusCode::FORBIDDEN) => Error::Unavailable {
                 message: status.to_string(),
             },
-            None => Error::Api {
-                message: "Unable to connect to Qobuz api".to_string(),
+            Some(status @ reqwest::StatusCode::NOT_FOUND) => Error::NotFound {
+                message: status.to_string(),
+            },
+            Some(status) => Error::Api {
+                message: status.to_string(),
             },
+            None => Error::Network,
         }
     }
 }
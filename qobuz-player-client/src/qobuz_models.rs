@@ -8,6 +8,7 @@ pub mod artist_page;
 pub mod favorites;
 pub mod featured;
 pub mod genre;
+pub mod label;
 pub mod playlist;
 pub mod search_results;
 pub mod track;
@@ -40,15 +41,34 @@ pub struct TrackInfo {
     #[serde(default)]
     pub sampling_rate: Option<u32>,
     #[serde(default)]
+    pub bit_depth: Option<u32>,
+    #[serde(default)]
     pub duration: Option<f64>,
     #[serde(default)]
     pub n_samples: Option<u64>,
+    /// Quality actually delivered, lower than requested when the release or subscription
+    /// does not offer it
+    #[serde(default)]
+    pub format_id: Option<i64>,
+    #[serde(default)]
+    pub restrictions: Vec<Restriction>,
+    /// A preview is delivered instead of the track, e.g. when it is locked in the region
+    #[serde(default)]
+    pub sample: bool,
+}
+
+/// Reason Qobuz gives for delivering less than what was asked for, like
+/// `FormatRestrictedByFormatAvailability` or `TrackRestrictedByRightHolders`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Restriction {
+    pub code: String,
 }
 
 pub enum UrlType {
     Album { id: String },
     Playlist { id: i64 },
     Track { id: i32 },
+    Artist { id: i32 },
 }
 
 #[derive(Snafu, Debug)]
@@ -97,6 +117,14 @@ pub fn parse_url(string_url: &str) -> ParseUrlResult<UrlType> {
                 .map_err(|_| UrlTypeError::InvalidPath)?;
             Ok(UrlType::Track { id })
         }
+        Some("artist") => {
+            tracing::debug!("this is an artist");
+            let id_str = path.next().ok_or(UrlTypeError::InvalidPath)?;
+            let id = id_str
+                .parse::<i32>()
+                .map_err(|_| UrlTypeError::InvalidPath)?;
+            Ok(UrlType::Artist { id })
+        }
         None => {
             tracing::debug!("no path, cannot use path");
             Err(UrlTypeError::InvalidPath)
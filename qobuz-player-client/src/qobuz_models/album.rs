@@ -7,14 +7,29 @@ pub struct Album {
     pub description: Option<String>,
     pub duration: Option<i64>,
     pub hires_streamable: bool,
+    pub genre: Option<AlbumGenre>,
     pub id: String,
     pub image: Image,
+    pub label: Option<AlbumLabel>,
     pub parental_warning: bool,
     pub release_date_original: String,
     pub streamable: bool,
     pub title: String,
     pub tracks: Option<Tracks>,
     pub tracks_count: i64,
+    pub upc: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlbumGenre {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlbumLabel {
+    pub id: i64,
+    pub name: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::qobuz_models::{Image, album::Albums};
+use crate::qobuz_models::{Image, album::Albums, track::Tracks};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ArtistSearchResults {
@@ -27,6 +27,7 @@ pub struct Artist {
     pub name: String,
     pub id: u32,
     pub albums: Option<Albums>,
+    pub tracks_appears_on: Option<Tracks>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+use crate::qobuz_models::album::Albums;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub id: u32,
+    pub name: String,
+    pub description: Option<String>,
+    pub albums: Option<Albums>,
+}
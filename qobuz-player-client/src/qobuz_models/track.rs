@@ -16,6 +16,9 @@ pub struct Track {
     pub hires_streamable: bool,
     pub id: u32,
     pub performer: Option<Performer>,
+    pub composer: Option<Performer>,
+    /// Classical work the track is a movement of
+    pub work: Option<String>,
     pub streamable: bool,
     pub title: String,
     pub track_number: u32,
@@ -1,5 +1,6 @@
 use crate::Error;
 
+pub mod cache_encryption;
 pub mod cmaf;
 pub mod crypto;
 pub mod flac_source_stream;
@@ -0,0 +1,219 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use aes::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+/// Key encrypting the audio cache at rest, one per install
+pub type CacheKey = [u8; 16];
+
+/// Encrypted files start with the magic and the iv, followed by the audio encrypted with
+/// AES-128-CTR, which can be decrypted from any position when seeking
+const MAGIC: &[u8; 8] = b"QPCACHE1";
+const IV_LEN: usize = 16;
+const HEADER_LEN: u64 = (MAGIC.len() + IV_LEN) as u64;
+
+/// Contents of a cache file encrypted with `key`, or unchanged without a key
+pub fn encrypt(key: Option<&CacheKey>, data: &[u8]) -> Vec<u8> {
+    let Some(key) = key else {
+        return data.to_vec();
+    };
+
+    let iv: [u8; IV_LEN] = rand::random();
+    let mut encrypted = Vec::with_capacity(HEADER_LEN as usize + data.len());
+    encrypted.extend_from_slice(MAGIC);
+    encrypted.extend_from_slice(&iv);
+    encrypted.extend_from_slice(data);
+
+    Aes128Ctr::new(key.into(), &iv.into()).apply_keystream(&mut encrypted[HEADER_LEN as usize..]);
+    encrypted
+}
+
+/// Contents of a cache file, which is decrypted if it was encrypted
+pub fn decrypt(key: Option<&CacheKey>, data: Vec<u8>) -> io::Result<Vec<u8>> {
+    let Some(iv) = header_iv(&data) else {
+        return Ok(data);
+    };
+
+    let mut decrypted = data[HEADER_LEN as usize..].to_vec();
+    cipher(key, iv)?.apply_keystream(&mut decrypted);
+    Ok(decrypted)
+}
+
+/// Encrypts a cache file written before encryption was enabled, in place. False when it was
+/// already encrypted
+pub fn encrypt_file(key: &CacheKey, path: &Path) -> io::Result<bool> {
+    let mut header = [0; HEADER_LEN as usize];
+    let read = File::open(path)?.read(&mut header)?;
+    if header[..read].starts_with(MAGIC) {
+        return Ok(false);
+    }
+
+    let data = std::fs::read(path)?;
+    let tmp = path.with_extension("encrypting");
+    std::fs::write(&tmp, encrypt(Some(key), &data))?;
+    std::fs::rename(&tmp, path).inspect_err(|_| _ = std::fs::remove_file(&tmp))?;
+    Ok(true)
+}
+
+fn header_iv(data: &[u8]) -> Option<[u8; IV_LEN]> {
+    let iv = data.strip_prefix(MAGIC)?.get(..IV_LEN)?;
+    iv.try_into().ok()
+}
+
+fn cipher(key: Option<&CacheKey>, iv: [u8; IV_LEN]) -> io::Result<Aes128Ctr> {
+    let key = key.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "The audio cache is encrypted, but encryption is disabled",
+        )
+    })?;
+
+    Ok(Aes128Ctr::new(key.into(), &iv.into()))
+}
+
+/// Cached track read as the plain audio, whether the file is encrypted or not
+pub struct CacheFile {
+    file: File,
+    cipher: Option<Aes128Ctr>,
+    /// Length of the audio, without the header
+    len: u64,
+    position: u64,
+}
+
+impl CacheFile {
+    pub fn open(path: &Path, key: Option<&CacheKey>) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+
+        let mut header = [0; HEADER_LEN as usize];
+        let encrypted = file_len >= HEADER_LEN && {
+            file.read_exact(&mut header)?;
+            header.starts_with(MAGIC)
+        };
+
+        if !encrypted {
+            file.rewind()?;
+            return Ok(Self {
+                file,
+                cipher: None,
+                len: file_len,
+                position: 0,
+            });
+        }
+
+        let iv = header_iv(&header).expect("header is read");
+        Ok(Self {
+            file,
+            cipher: Some(cipher(key, iv)?),
+            len: file_len - HEADER_LEN,
+            position: 0,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Read for CacheFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        if let Some(cipher) = &mut self.cipher {
+            cipher.apply_keystream(&mut buf[..read]);
+        }
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for CacheFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start"))?;
+
+        let offset = match self.cipher {
+            Some(_) => HEADER_LEN,
+            None => 0,
+        };
+        self.file.seek(SeekFrom::Start(position + offset))?;
+        if let Some(cipher) = &mut self.cipher {
+            cipher.seek(position);
+        }
+        self.position = position;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use super::{CacheFile, decrypt, encrypt, encrypt_file};
+
+    #[test]
+    fn encrypted_file_reads_and_seeks_as_plain_audio() {
+        let key = [7; 16];
+        let audio: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let encrypted = encrypt(Some(&key), &audio);
+        assert_ne!(
+            &encrypted[encrypted.len() - audio.len()..],
+            audio.as_slice()
+        );
+        assert_eq!(decrypt(Some(&key), encrypted.clone()).unwrap(), audio);
+        assert!(decrypt(None, encrypted.clone()).is_err());
+
+        let path = std::env::temp_dir().join(format!("cache-encryption-{}", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(&encrypted)
+            .unwrap();
+
+        let mut file = CacheFile::open(&path, Some(&key)).unwrap();
+        assert_eq!(file.len(), audio.len() as u64);
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, audio);
+
+        file.seek(SeekFrom::Start(517)).unwrap();
+        let mut byte = [0];
+        file.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], audio[517]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn plain_file_is_encrypted_in_place_once() {
+        let key = [3; 16];
+        let audio: Vec<u8> = (0..=255).cycle().take(100).collect();
+        let path = std::env::temp_dir().join(format!("cache-migration-{}", std::process::id()));
+        std::fs::write(&path, &audio).unwrap();
+
+        assert!(encrypt_file(&key, &path).unwrap());
+        let encrypted = std::fs::read(&path).unwrap();
+        assert!(!encrypt_file(&key, &path).unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), encrypted);
+
+        let mut read = Vec::new();
+        CacheFile::open(&path, Some(&key))
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, audio);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
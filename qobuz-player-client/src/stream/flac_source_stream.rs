@@ -1,18 +1,20 @@
 use std::{
+    collections::VecDeque,
     fs,
     io::{self, Read, Seek, SeekFrom},
-    path::PathBuf,
+    path::{Path, PathBuf},
     pin::Pin,
     sync::{
-        Arc,
-        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     },
     task::{Context, Poll},
 };
 
 use bytes::Bytes;
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use parking_lot::Mutex;
+use reqwest::{StatusCode, header::RANGE};
 use stream_download::{
     StreamDownload,
     source::{DecodeError, SourceStream, StreamOutcome},
@@ -20,7 +22,17 @@ use stream_download::{
 };
 use tokio::task::JoinHandle;
 
-use crate::stream::{cmaf, crypto};
+use crate::stream::{
+    cache_encryption::{self, CacheKey},
+    cmaf, crypto,
+};
+
+/// Number of segments downloaded at the same time, including the one being streamed.
+const PARALLEL_SEGMENTS: u8 = 3;
+/// How many times a dropped segment download is resumed before giving up.
+const MAX_RESUME_ATTEMPTS: usize = 5;
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
 
 #[derive(Debug, Clone)]
 pub struct SegmentByteInfo {
@@ -34,12 +46,14 @@ struct SharedDownloadState {
     content_key: Option<[u8; 16]>,
     flac_header: Vec<u8>,
     cache_path: PathBuf,
+    cache_key: Option<CacheKey>,
     segment_map: Vec<SegmentByteInfo>,
     downloaded: Mutex<Vec<Option<Vec<u8>>>>,
     /// Partial decrypted data from cancelled fetches, persists across task respawns.
     in_progress: Mutex<Vec<Option<Vec<u8>>>>,
     cache_written: AtomicBool,
     gap_fill_running: AtomicBool,
+    progress: Arc<DownloadProgress>,
 }
 
 pub struct FlacSourceParams {
@@ -48,7 +62,76 @@ pub struct FlacSourceParams {
     pub content_key: Option<[u8; 16]>,
     pub flac_header: Vec<u8>,
     pub cache_path: PathBuf,
+    /// Encrypts the cache file and the persisted segments when set
+    pub cache_key: Option<CacheKey>,
     pub segment_map: Vec<SegmentByteInfo>,
+    pub progress: Arc<DownloadProgress>,
+}
+
+/// Download progress of a streamed track, counted in completed segments.
+#[derive(Debug)]
+pub struct DownloadProgress {
+    total_segments: usize,
+    total_bytes: u64,
+    completed_segments: AtomicUsize,
+    downloaded_bytes: AtomicU64,
+    received_bytes: AtomicU64,
+}
+
+impl DownloadProgress {
+    pub fn new(total_segments: usize, total_bytes: u64) -> Self {
+        Self {
+            total_segments,
+            total_bytes,
+            completed_segments: AtomicUsize::new(0),
+            downloaded_bytes: AtomicU64::new(0),
+            received_bytes: AtomicU64::new(0),
+        }
+    }
+
+    pub fn percent(&self) -> u8 {
+        if self.total_segments == 0 {
+            return 100;
+        }
+
+        let completed = self.completed_segments.load(Ordering::Acquire);
+        (completed * 100 / self.total_segments).min(100) as u8
+    }
+
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.downloaded_bytes.load(Ordering::Acquire)
+    }
+
+    /// Bytes received from the network, also of segments that are not complete yet, to tell a
+    /// slow download from a stalled one
+    pub fn received_bytes(&self) -> u64 {
+        self.received_bytes.load(Ordering::Acquire)
+    }
+
+    fn add_received(&self, byte_len: usize) {
+        self.received_bytes
+            .fetch_add(byte_len as u64, Ordering::AcqRel);
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed_segments.load(Ordering::Acquire) >= self.total_segments
+    }
+
+    fn add_segment(&self, byte_len: usize) {
+        self.downloaded_bytes
+            .fetch_add(byte_len as u64, Ordering::AcqRel);
+        self.completed_segments.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Directory holding the decrypted segments of a track that has not been fully downloaded yet.
+/// Lets a download resume where it left off after a dropped connection or a restart.
+pub fn partial_segments_dir(cache_path: &Path) -> PathBuf {
+    cache_path.with_extension("parts")
 }
 
 pub struct FlacSourceStream {
@@ -84,12 +167,15 @@ impl SourceStream for FlacSourceStream {
             content_key: params.content_key,
             flac_header: params.flac_header,
             cache_path: params.cache_path,
+            cache_key: params.cache_key,
             segment_map: params.segment_map,
             downloaded: Mutex::new(vec![None; total_segs]),
             in_progress: Mutex::new(vec![None; total_segs]),
             cache_written: AtomicBool::new(false),
             gap_fill_running: AtomicBool::new(false),
+            progress: params.progress,
         });
+        shared.load_partial_segments();
 
         let shared_clone = shared.clone();
         tokio::spawn(async move {
@@ -168,19 +254,29 @@ impl Stream for FlacSourceStream {
 pub struct SeekableStreamReader {
     inner: StreamDownload<TempStorageProvider>,
     content_length: u64,
+    progress: Arc<DownloadProgress>,
 }
 
 impl SeekableStreamReader {
-    pub fn new(inner: StreamDownload<TempStorageProvider>, content_length: u64) -> Self {
+    pub fn new(
+        inner: StreamDownload<TempStorageProvider>,
+        content_length: u64,
+        progress: Arc<DownloadProgress>,
+    ) -> Self {
         Self {
             inner,
             content_length,
+            progress,
         }
     }
 
     pub fn content_length(&self) -> u64 {
         self.content_length
     }
+
+    pub fn progress(&self) -> Arc<DownloadProgress> {
+        self.progress.clone()
+    }
 }
 
 impl Read for SeekableStreamReader {
@@ -252,7 +348,7 @@ fn maybe_spawn_gap_fill(shared: Arc<SharedDownloadState>, start_seg: u8) {
 }
 
 /// Resolution order per segment: downloaded (complete) → in_progress (partial) → network.
-/// Prefetches the next segment in parallel for faster buffering.
+/// Prefetches the following segments in parallel for faster buffering.
 async fn download_segments(
     shared: &Arc<SharedDownloadState>,
     tx: &tokio::sync::mpsc::Sender<io::Result<Bytes>>,
@@ -260,30 +356,39 @@ async fn download_segments(
     to_seg: u8,
     skip_first_bytes: usize,
 ) {
-    let mut prefetch: Option<JoinHandle<()>> = None;
+    let mut prefetches: VecDeque<(u8, JoinHandle<()>)> = VecDeque::new();
 
     for seg in from_seg..to_seg {
         if tx.is_closed() {
-            if let Some(h) = prefetch.take() {
+            for (_, h) in prefetches.drain(..) {
                 h.abort();
             }
             return;
         }
 
-        if let Some(h) = prefetch.take() {
+        if prefetches.front().is_some_and(|(s, _)| *s == seg)
+            && let Some((_, h)) = prefetches.pop_front()
+        {
             let _ = h.await;
         }
 
         let idx = (seg - 1) as usize;
         let skip = if seg == from_seg { skip_first_bytes } else { 0 };
 
-        // Prefetch next segment in background
-        let next_seg = seg + 1;
-        if next_seg < to_seg && shared.downloaded.lock()[(next_seg - 1) as usize].is_none() {
+        // Prefetch the following segments in background
+        let prefetch_end = seg.saturating_add(PARALLEL_SEGMENTS).min(to_seg);
+        for next_seg in seg + 1..prefetch_end {
+            let queued = prefetches.iter().any(|(s, _)| *s == next_seg);
+            if queued || shared.downloaded.lock()[(next_seg - 1) as usize].is_some() {
+                continue;
+            }
             let shared_clone = shared.clone();
-            prefetch = Some(tokio::spawn(async move {
-                prefetch_segment(&shared_clone, next_seg).await;
-            }));
+            prefetches.push_back((
+                next_seg,
+                tokio::spawn(async move {
+                    prefetch_segment(&shared_clone, next_seg).await;
+                }),
+            ));
         }
 
         let complete = shared.downloaded.lock().get(idx).cloned().flatten();
@@ -344,10 +449,11 @@ async fn fill_missing_segments(shared: &Arc<SharedDownloadState>) {
     }
 
     tracing::info!("Filling {} missing segments for cache", missing.len());
-    for seg in missing {
-        let shared_clone = shared.clone();
-        prefetch_segment(&shared_clone, seg).await;
-    }
+    futures::stream::iter(missing)
+        .for_each_concurrent(PARALLEL_SEGMENTS as usize, |seg| async move {
+            prefetch_segment(shared, seg).await;
+        })
+        .await;
 }
 
 /// Prefetch a segment into `downloaded` without sending to the channel.
@@ -358,14 +464,21 @@ async fn prefetch_segment(shared: &SharedDownloadState, seg: u8) {
     }
 
     let url = shared.url_template.replace("$SEGMENT$", &seg.to_string());
-    let resp = match reqwest::get(&url).await {
+    let mut resp = match ResumableResponse::get(url).await {
         Ok(r) => r,
         Err(_) => return,
     };
-    let seg_bytes = match resp.bytes().await {
-        Ok(b) => b.to_vec(),
-        Err(_) => return,
-    };
+    let mut seg_bytes = Vec::new();
+    loop {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                shared.progress.add_received(chunk.len());
+                seg_bytes.extend_from_slice(&chunk)
+            }
+            Ok(None) => break,
+            Err(_) => return,
+        }
+    }
 
     let crypto = match cmaf::parse_segment_crypto(&seg_bytes) {
         Ok(c) => c,
@@ -395,8 +508,7 @@ async fn prefetch_segment(shared: &SharedDownloadState, seg: u8) {
         all_decrypted.extend_from_slice(&seg_bytes[data_pos..mdat_end]);
     }
 
-    shared.downloaded.lock()[idx] = Some(all_decrypted);
-    shared.in_progress.lock()[idx] = None;
+    shared.complete_segment(idx, all_decrypted);
     tracing::debug!("Segment {seg}/{}: prefetched", shared.n_segments - 1);
 }
 
@@ -436,7 +548,7 @@ async fn fetch_and_stream_segment(
     tx: &tokio::sync::mpsc::Sender<io::Result<Bytes>>,
 ) -> Result<(), String> {
     let url = shared.url_template.replace("$SEGMENT$", &seg.to_string());
-    let mut resp = reqwest::get(&url)
+    let mut resp = ResumableResponse::get(url)
         .await
         .map_err(|e| format!("Failed to fetch segment {seg}: {e}"))?;
 
@@ -448,6 +560,7 @@ async fn fetch_and_stream_segment(
             .map_err(|e| format!("Segment {seg}: {e}"))?
         {
             Some(chunk) => {
+                shared.progress.add_received(chunk.len());
                 buf.extend_from_slice(&chunk);
                 if let Ok(c) = cmaf::parse_segment_crypto(&buf) {
                     break c;
@@ -531,7 +644,10 @@ async fn fetch_and_stream_segment(
             .await
             .map_err(|e| format!("Segment {seg}: {e}"))?
         {
-            Some(chunk) => buf.extend_from_slice(&chunk),
+            Some(chunk) => {
+                shared.progress.add_received(chunk.len());
+                buf.extend_from_slice(&chunk)
+            }
             None => return Err(format!("Segment {seg}: truncated at frame")),
         }
         if tx.is_closed() {
@@ -561,8 +677,7 @@ async fn fetch_and_stream_segment(
         bytes_accumulated += trailing.len();
     }
 
-    shared.downloaded.lock()[idx] = Some(all_decrypted);
-    shared.in_progress.lock()[idx] = None;
+    shared.complete_segment(idx, all_decrypted);
 
     let total_sent = bytes_accumulated.saturating_sub(skip_bytes);
     tracing::debug!(
@@ -573,7 +688,140 @@ async fn fetch_and_stream_segment(
     Ok(())
 }
 
+/// Segment response body that resumes with a range request when the connection drops.
+struct ResumableResponse {
+    url: String,
+    response: reqwest::Response,
+    received: u64,
+    /// Bytes to drop when the server ignored the range and restarted from the beginning.
+    discard: u64,
+    attempts: usize,
+}
+
+impl ResumableResponse {
+    async fn get(url: String) -> Result<Self, reqwest::Error> {
+        let response = HTTP_CLIENT.get(&url).send().await?;
+        Ok(Self {
+            url,
+            response,
+            received: 0,
+            discard: 0,
+            attempts: 0,
+        })
+    }
+
+    async fn chunk(&mut self) -> Result<Option<Bytes>, reqwest::Error> {
+        loop {
+            match self.response.chunk().await {
+                Ok(Some(chunk)) => {
+                    let chunk = if self.discard > 0 {
+                        let dropped = self.discard.min(chunk.len() as u64);
+                        self.discard -= dropped;
+                        chunk.slice(dropped as usize..)
+                    } else {
+                        chunk
+                    };
+                    if chunk.is_empty() {
+                        continue;
+                    }
+                    self.received += chunk.len() as u64;
+                    return Ok(Some(chunk));
+                }
+                Ok(None) => return Ok(None),
+                Err(e) if self.attempts < MAX_RESUME_ATTEMPTS => {
+                    self.attempts += 1;
+                    tracing::warn!(
+                        "Segment download dropped after {} bytes, resuming ({}/{MAX_RESUME_ATTEMPTS}): {e}",
+                        self.received,
+                        self.attempts,
+                    );
+                    if let Err(e) = self.resume().await {
+                        tracing::warn!("Failed to resume segment download: {e}");
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn resume(&mut self) -> Result<(), reqwest::Error> {
+        let response = HTTP_CLIENT
+            .get(&self.url)
+            .header(RANGE, format!("bytes={}-", self.received))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        self.discard = if response.status() == StatusCode::PARTIAL_CONTENT {
+            0
+        } else {
+            self.received
+        };
+        self.response = response;
+        Ok(())
+    }
+}
+
 impl SharedDownloadState {
+    /// Stores a fully downloaded segment and persists it so the download can be resumed.
+    fn complete_segment(&self, idx: usize, data: Vec<u8>) {
+        let mut downloaded = self.downloaded.lock();
+        if downloaded[idx].is_some() {
+            return;
+        }
+
+        self.write_partial_segment(idx, &data);
+        self.progress.add_segment(data.len());
+        downloaded[idx] = Some(data);
+        drop(downloaded);
+
+        self.in_progress.lock()[idx] = None;
+    }
+
+    fn write_partial_segment(&self, idx: usize, data: &[u8]) {
+        let dir = partial_segments_dir(&self.cache_path);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            tracing::warn!("Failed to create partial download directory: {e}");
+            return;
+        }
+
+        let path = dir.join(idx.to_string());
+        let tmp = path.with_extension("partial");
+        let data = cache_encryption::encrypt(self.cache_key.as_ref(), data);
+        if fs::write(&tmp, data).is_err() || fs::rename(&tmp, &path).is_err() {
+            let _ = fs::remove_file(&tmp);
+            tracing::warn!("Failed to persist segment {}", idx + 1);
+        }
+    }
+
+    /// Picks up segments persisted by an earlier, interrupted download of the same track.
+    fn load_partial_segments(&self) {
+        let dir = partial_segments_dir(&self.cache_path);
+        if !dir.is_dir() {
+            return;
+        }
+
+        let mut downloaded = self.downloaded.lock();
+        let mut resumed = 0;
+        for (idx, slot) in downloaded.iter_mut().enumerate() {
+            let data = fs::read(dir.join(idx.to_string()))
+                .and_then(|data| cache_encryption::decrypt(self.cache_key.as_ref(), data));
+            if let Ok(data) = data {
+                self.progress.add_segment(data.len());
+                *slot = Some(data);
+                resumed += 1;
+            }
+        }
+
+        if resumed > 0 {
+            tracing::info!(
+                "Resuming download of {}: {resumed}/{} segments already downloaded",
+                self.cache_path.display(),
+                downloaded.len(),
+            );
+        }
+    }
+
     fn try_write_cache(&self) {
         if self.cache_written.swap(true, Ordering::AcqRel) {
             return;
@@ -603,12 +851,14 @@ impl SharedDownloadState {
             let _ = fs::create_dir_all(parent);
         }
         let tmp = self.cache_path.with_extension("partial");
+        let cache_data = cache_encryption::encrypt(self.cache_key.as_ref(), &cache_data);
         if let Err(e) = fs::write(&tmp, &cache_data) {
             tracing::warn!("Failed to write cache: {e}");
         } else if let Err(e) = fs::rename(&tmp, &self.cache_path) {
             let _ = fs::remove_file(&tmp);
             tracing::warn!("Failed to finalize cache: {e}");
         } else {
+            let _ = fs::remove_dir_all(partial_segments_dir(&self.cache_path));
             tracing::info!(
                 "Cached: {} ({} bytes)",
                 self.cache_path.display(),
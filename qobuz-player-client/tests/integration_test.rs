@@ -1,5 +1,6 @@
 use qobuz_player_client::client::{
-    Client, FeaturedAlbumType, FeaturedGenreAlbumType, FeaturedPlaylistType, ReleaseType,
+    Client, FeaturedAlbumType, FeaturedGenreAlbumType, FeaturedPlaylistType, ReleaseSort,
+    ReleaseType,
 };
 use qobuz_player_controls::database::{Credentials, Database};
 
@@ -113,7 +114,7 @@ async fn playlist() {
 async fn search() {
     let client = get_client().await.unwrap();
     client
-        .search_all("a light for attracting attention", 3)
+        .search_all("a light for attracting attention", 3, 0)
         .await
         .unwrap();
 }
@@ -158,19 +159,39 @@ async fn similar_artist() {
 async fn artist_releases() {
     let client = get_client().await.unwrap();
     client
-        .artist_releases(9316383, ReleaseType::Albums, Some(3))
+        .artist_releases(
+            9316383,
+            ReleaseType::Albums,
+            ReleaseSort::ReleaseDate,
+            Some(3),
+        )
         .await
         .unwrap();
     client
-        .artist_releases(9316383, ReleaseType::EPsAndSingles, Some(3))
+        .artist_releases(
+            9316383,
+            ReleaseType::EPsAndSingles,
+            ReleaseSort::ReleaseDate,
+            Some(3),
+        )
         .await
         .unwrap();
     client
-        .artist_releases(9316383, ReleaseType::Live, Some(3))
+        .artist_releases(
+            9316383,
+            ReleaseType::Live,
+            ReleaseSort::ReleaseDate,
+            Some(3),
+        )
         .await
         .unwrap();
     client
-        .artist_releases(9316383, ReleaseType::Compilations, Some(3))
+        .artist_releases(
+            9316383,
+            ReleaseType::Compilations,
+            ReleaseSort::ReleaseDate,
+            Some(3),
+        )
         .await
         .unwrap();
 }
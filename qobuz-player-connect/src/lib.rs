@@ -1,10 +1,14 @@
-use std::time::{Duration, SystemTime};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use qobuz_player_controls::{
-    AppResult, AudioQuality, PositionReceiver, Status, StatusReceiver, TracklistReceiver,
-    VolumeReceiver,
+    AppResult, AudioQuality, MutedReceiver, PositionReceiver, Status, StatusReceiver,
+    TracklistReceiver, VolumeReceiver,
     controls::{Controls, NewQueueItem},
     error::Error,
+    notification::{self, NotificationBroadcast},
     tracklist::Tracklist,
 };
 
@@ -13,15 +17,80 @@ use qonductor::{
     SessionEvent, SessionManager,
     msg::{self, Position, QueueRendererState, report::VolumeChanged},
 };
+use serde::Serialize;
+use tokio::{
+    sync::{oneshot, watch},
+    task::JoinHandle,
+};
+
+/// Delay before announcing the device again after the connection dropped, doubled per failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    /// Announced on the network, waiting for a Qobuz app to connect
+    #[default]
+    Waiting,
+    Connected,
+    /// The connection dropped and the device is announced again after a delay
+    Reconnecting,
+}
+
+pub type ConnectionStatusReceiver = watch::Receiver<ConnectionStatus>;
 
 struct ConnectState {
     controls: Controls,
+    broadcast: Arc<NotificationBroadcast>,
+    connection_status: watch::Sender<ConnectionStatus>,
     position_receiver: PositionReceiver,
     tracklist_receiver: TracklistReceiver,
     status_receiver: StatusReceiver,
     volume_receiver: VolumeReceiver,
+    muted_receiver: MutedReceiver,
     audio_quality: i32,
     connected: bool,
+    /// Selected as the playback device in a Qobuz app
+    active: bool,
+    /// Report everything once the new session is active
+    resync: bool,
+    backoff: Duration,
+}
+
+/// Announced device with its discovery manager running in the background
+struct ConnectSession {
+    session: DeviceSession,
+    stop: oneshot::Sender<()>,
+    manager: JoinHandle<()>,
+}
+
+impl ConnectSession {
+    async fn start(app_id: &str, connect_name: &str) -> qonductor::Result<Self> {
+        let mut manager = SessionManager::start(0, app_id).await?;
+        let session = manager.add_device(DeviceConfig::new(connect_name)).await?;
+
+        let (stop, stopped) = oneshot::channel();
+        let manager = tokio::spawn(async move {
+            tokio::select! {
+                _ = manager.run() => {}
+                _ = stopped => {}
+            }
+            manager.shutdown().await;
+        });
+
+        Ok(Self {
+            session,
+            stop,
+            manager,
+        })
+    }
+
+    /// Withdraws the announcement so the Qobuz app forgets the dropped session
+    async fn stop(self) {
+        _ = self.stop.send(());
+        _ = self.manager.await;
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -29,26 +98,35 @@ pub async fn init(
     app_id: &str,
     connect_name: String,
     controls: Controls,
+    broadcast: Arc<NotificationBroadcast>,
+    connection_status: watch::Sender<ConnectionStatus>,
     position_receiver: PositionReceiver,
     tracklist_receiver: TracklistReceiver,
     status_receiver: StatusReceiver,
     volume_receiver: VolumeReceiver,
+    muted_receiver: MutedReceiver,
     max_audio_quality: AudioQuality,
 ) -> AppResult<()> {
     let audio_quality = convert_audio_quality(max_audio_quality);
 
     let mut connect_state = ConnectState {
         controls,
+        broadcast,
+        connection_status,
         position_receiver,
         tracklist_receiver,
         status_receiver,
         volume_receiver: volume_receiver.clone(),
+        muted_receiver,
         audio_quality,
         connected: false,
+        active: false,
+        resync: false,
+        backoff: INITIAL_BACKOFF,
     };
 
     connect_state
-        .run(app_id, connect_name)
+        .run(app_id, &connect_name)
         .await
         .map_err(map_err)?;
 
@@ -104,20 +182,35 @@ fn convert_audio_quality(max_audio_quality: AudioQuality) -> i32 {
     }
 }
 
+fn parse_audio_quality(audio_quality: i32) -> Option<AudioQuality> {
+    match audio_quality {
+        1 => Some(AudioQuality::Mp3),
+        2 => Some(AudioQuality::CD),
+        3 => Some(AudioQuality::HIFI96),
+        4 => Some(AudioQuality::HIFI192),
+        _ => None,
+    }
+}
+
 fn convert_volume(volume: f32) -> u32 {
     ((volume * 100.0) as u32).clamp(0, 100)
 }
 
 impl ConnectState {
+    /// State is only reported while a Qobuz app controls this device
+    fn reporting(&self) -> bool {
+        self.connected && self.active
+    }
+
+    // Each handler marks its value as seen before anything else, as a watch left changed would
+    // wake the select loop again right away
+
     async fn handle_position_changed(&mut self, session: &DeviceSession) -> qonductor::Result<()> {
-        if !self.connected {
+        let position = *self.position_receiver.borrow_and_update();
+        if !self.reporting() {
             return Ok(());
         }
-        let position = {
-            let position = self.position_receiver.borrow_and_update();
-            *position
-        };
-        let status = { *self.status_receiver.borrow() };
+        let status = *self.status_receiver.borrow();
         let tracklist = self.tracklist_receiver.borrow().clone();
 
         let new_state = current_state(&status, &position, &tracklist);
@@ -127,15 +220,12 @@ impl ConnectState {
     }
 
     async fn handle_tracklist_changed(&mut self, session: &DeviceSession) -> qonductor::Result<()> {
-        if !self.connected {
+        let tracklist = self.tracklist_receiver.borrow_and_update().clone();
+        if !self.reporting() {
             return Ok(());
         }
-        let tracklist = self.tracklist_receiver.borrow_and_update().clone();
-        let position = {
-            let position = self.position_receiver.borrow();
-            *position
-        };
-        let status = { *self.status_receiver.borrow() };
+        let position = *self.position_receiver.borrow();
+        let status = *self.status_receiver.borrow();
         let new_state = current_state(&status, &position, &tracklist);
 
         tracing::info!("Updating current state after tracklist change");
@@ -144,58 +234,128 @@ impl ConnectState {
     }
 
     async fn handle_volume_changed(&mut self, session: &DeviceSession) -> qonductor::Result<()> {
-        if !self.connected {
+        let volume = convert_volume(*self.volume_receiver.borrow_and_update());
+        if !self.reporting() {
             return Ok(());
         }
-        let volume = convert_volume(*self.volume_receiver.borrow_and_update());
         tracing::info!("Updating volume state after volume change");
         session.report_volume(volume).await?;
         Ok(())
     }
 
+    async fn handle_muted_changed(&mut self, session: &DeviceSession) -> qonductor::Result<()> {
+        let muted = *self.muted_receiver.borrow_and_update();
+        if !self.reporting() {
+            return Ok(());
+        }
+        tracing::info!("Updating muted state after mute change");
+        session.report_muted(muted).await?;
+        Ok(())
+    }
+
     async fn handle_status_changed(&mut self, session: &DeviceSession) -> qonductor::Result<()> {
-        if !self.connected {
+        let status = *self.status_receiver.borrow_and_update();
+        if !self.reporting() {
             return Ok(());
         }
-        let position = {
-            let position = self.position_receiver.borrow();
-            *position
-        };
-        let status = { *self.status_receiver.borrow_and_update() };
+        let position = *self.position_receiver.borrow();
         let tracklist = self.tracklist_receiver.borrow().clone();
         let new_state = current_state(&status, &position, &tracklist);
         session.report_state(new_state).await?;
         Ok(())
     }
 
-    async fn run(&mut self, app_id: &str, connect_name: String) -> qonductor::Result<()> {
-        let mut manager = SessionManager::start(0, app_id).await?;
+    async fn report_all(&mut self, session: &DeviceSession) -> qonductor::Result<()> {
+        self.handle_status_changed(session).await?;
+        self.handle_volume_changed(session).await?;
+        self.handle_muted_changed(session).await
+    }
+
+    /// Only a failure to announce the device at startup is fatal. Once running, the session is
+    /// started again whenever the connection drops
+    async fn run(&mut self, app_id: &str, connect_name: &str) -> qonductor::Result<()> {
+        let mut connect_session = ConnectSession::start(app_id, connect_name).await?;
+
+        loop {
+            if let Err(err) = self.drive(&mut connect_session.session).await {
+                tracing::warn!("Qobuz Connect session failed: {err}");
+            }
+            connect_session.stop().await;
 
-        let mut session = manager.add_device(DeviceConfig::new(connect_name)).await?;
+            self.connected = false;
+            self.deactivate();
+            self.connection_status
+                .send_replace(ConnectionStatus::Reconnecting);
 
-        tokio::spawn(async move { manager.run().await });
+            connect_session = loop {
+                tracing::info!("Announcing {connect_name} again in {:?}", self.backoff);
+                tokio::time::sleep(self.backoff).await;
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
 
+                match ConnectSession::start(app_id, connect_name).await {
+                    Ok(connect_session) => break connect_session,
+                    Err(err) => tracing::warn!("Unable to announce {connect_name}: {err}"),
+                }
+            };
+            self.connection_status
+                .send_replace(ConnectionStatus::Waiting);
+        }
+    }
+
+    /// Handles one session until its connection drops
+    async fn drive(&mut self, session: &mut DeviceSession) -> qonductor::Result<()> {
         loop {
             tokio::select! {
-                Some(event) = session.recv() => {
+                event = session.recv() => {
+                    let Some(event) = event else {
+                        return Ok(());
+                    };
+                    let disconnected = matches!(
+                        event,
+                        SessionEvent::Notification(Notification::Disconnected { .. })
+                    );
                     self.handle_event(event);
+                    if disconnected {
+                        return Ok(());
+                    }
+
+                    if self.resync && self.reporting() {
+                        self.resync = false;
+                        self.report_all(session).await?;
+                    }
                 }
                 Ok(_) = self.position_receiver.changed() => {
-                    self.handle_position_changed(&session).await?;
+                    self.handle_position_changed(session).await?;
                 },
                 Ok(_) = self.tracklist_receiver.changed() => {
-                    self.handle_tracklist_changed(&session).await?;
+                    self.handle_tracklist_changed(session).await?;
                 },
                 Ok(_) = self.volume_receiver.changed() => {
-                    self.handle_volume_changed(&session).await?;
+                    self.handle_volume_changed(session).await?;
+                }
+                Ok(_) = self.muted_receiver.changed() => {
+                    self.handle_muted_changed(session).await?;
                 }
                 Ok(_) = self.status_receiver.changed() => {
-                    self.handle_status_changed(&session).await?;
+                    self.handle_status_changed(session).await?;
                 }
             }
         }
     }
 
+    /// Another device took over, so stop playing here until selected again
+    fn deactivate(&mut self) {
+        if !self.active {
+            return;
+        }
+
+        self.active = false;
+        self.controls.pause();
+        self.broadcast.send(notification::Notification::Info(
+            "No longer controlled by Qobuz app".into(),
+        ));
+    }
+
     fn handle_event(&mut self, event: SessionEvent) {
         match event {
             SessionEvent::Command(command) => match command {
@@ -240,6 +400,12 @@ impl ConnectState {
                 }
                 Command::SetActive { respond, cmd: _cmd } => {
                     tracing::info!("Device activated!");
+                    if !self.active {
+                        self.active = true;
+                        self.broadcast.send(notification::Notification::Info(
+                            "Controlled by Qobuz app".into(),
+                        ));
+                    }
 
                     let current_volume = convert_volume(*self.volume_receiver.borrow());
                     let status = self.status_receiver.borrow();
@@ -248,7 +414,7 @@ impl ConnectState {
                     let response = current_state(&status, &position, &tracklist);
 
                     respond.send(ActivationState {
-                        muted: false,
+                        muted: *self.muted_receiver.borrow(),
                         volume: current_volume,
                         max_quality: self.audio_quality,
                         playback: response,
@@ -286,21 +452,18 @@ impl ConnectState {
             SessionEvent::Notification(n) => match n {
                 Notification::Connected => {
                     self.connected = true;
+                    self.resync = true;
+                    self.backoff = INITIAL_BACKOFF;
+                    self.connection_status
+                        .send_replace(ConnectionStatus::Connected);
                     tracing::info!("Connected!")
                 }
                 Notification::DeviceRegistered { renderer_id, .. } => {
                     tracing::info!("Ignoring device registered as renderer {}", renderer_id);
                 }
                 Notification::QueueState(queue) => {
-                    let queue_items = queue
-                        .tracks
-                        .into_iter()
-                        .map(|x| NewQueueItem {
-                            track_id: x.track_id(),
-                            queue_id: x.queue_item_id,
-                        })
-                        .collect();
-                    self.controls.new_queue(queue_items, false);
+                    self.controls
+                        .new_queue(new_queue_items(queue.tracks), false);
                 }
                 Notification::SessionState(session_state) => {
                     tracing::info!("Ignoring session state message: {:?}", session_state);
@@ -311,15 +474,8 @@ impl ConnectState {
                 Notification::QueueLoadTracks(queue) => {
                     tracing::info!("Queue load tracks: {:?}", queue);
 
-                    let queue_items = queue
-                        .tracks
-                        .into_iter()
-                        .map(|x| NewQueueItem {
-                            track_id: x.track_id(),
-                            queue_id: x.queue_item_id,
-                        })
-                        .collect();
-                    self.controls.new_queue(queue_items, false);
+                    self.controls
+                        .new_queue(new_queue_items(queue.tracks), false);
 
                     let current_position = self.tracklist_receiver.borrow().current_position();
 
@@ -331,19 +487,47 @@ impl ConnectState {
                         self.controls.skip_to_position(tracklist_position, true);
                     }
                 }
-                Notification::QueueTracksAdded(queue_tracks_added) => {
-                    // Added in end of queue
-                    tracing::info!("Queue tracks added: {:?}", queue_tracks_added);
-                }
-                Notification::QueueTracksInserted(queue_tracks_inserted) => {
-                    // Next in queue
-                    tracing::info!("Queue tracks inserted: {:?}", queue_tracks_inserted);
+                Notification::QueueTracksAdded(added) => {
+                    tracing::info!("Queue tracks added: {:?}", added.tracks);
+                    self.controls
+                        .insert_queue_items(new_queue_items(added.tracks), None);
+                }
+                Notification::QueueTracksInserted(inserted) => {
+                    tracing::info!("Queue tracks inserted: {:?}", inserted.tracks);
+                    let tracklist = self.tracklist_receiver.borrow().clone();
+                    let index = match inserted.insert_after {
+                        Some(queue_id) if queue_id >= 0 => {
+                            position_of(&tracklist, queue_id as u64).map(|index| index + 1)
+                        }
+                        Some(_) => Some(0),
+                        None => Some(tracklist.current_position() + 1),
+                    };
+                    self.controls
+                        .insert_queue_items(new_queue_items(inserted.tracks), index);
                 }
-                Notification::QueueTracksRemoved(queue_tracks_removed) => {
-                    tracing::info!("Queue tracks removed: {:?}", queue_tracks_removed);
+                Notification::QueueTracksRemoved(removed) => {
+                    tracing::info!("Queue tracks removed: {:?}", removed.queue_item_ids);
+                    let queue_ids = removed.queue_item_ids.into_iter().map(u64::from).collect();
+                    self.controls.remove_queue_items(queue_ids);
                 }
                 Notification::QueueTracksReordered(reordered) => {
-                    tracing::info!("Queue tracks reordered: {:?}", reordered);
+                    tracing::info!("Queue tracks reordered: {:?}", reordered.queue_item_ids);
+                    let tracklist = self.tracklist_receiver.borrow().clone();
+                    if tracklist.from_connect() {
+                        let moved: Vec<u64> = reordered
+                            .queue_item_ids
+                            .into_iter()
+                            .map(u64::from)
+                            .collect();
+                        let new_order = reordered_positions(
+                            &tracklist,
+                            &moved,
+                            reordered.insert_after.map(u64::from),
+                        );
+                        self.controls.reorder_queue(new_order);
+                    } else {
+                        tracing::debug!("Ignoring the reorder, the local queue is playing");
+                    }
                 }
                 Notification::VolumeChanged(volume) => {
                     let volume = volume.volume;
@@ -386,11 +570,20 @@ impl ConnectState {
                     // let state = state_msg.state;
                     // tracing::info!("Error. Renderer state not supported: {:?}", state);
                 }
-                Notification::VolumeMuted(_) => {
-                    tracing::info!("Error. Muting not supported");
+                Notification::VolumeMuted(muted) => {
+                    tracing::info!("Volume muted received: {:?}", muted.value);
+                    self.controls.set_mute(muted.value.unwrap_or(false));
                 }
-                Notification::MaxAudioQualityChanged(_) => {
-                    tracing::info!("Error. Audio quality change in runtime is not supported");
+                Notification::MaxAudioQualityChanged(quality) => {
+                    tracing::info!("Max audio quality received: {:?}", quality);
+
+                    match quality.max_audio_quality.and_then(parse_audio_quality) {
+                        Some(max_audio_quality) => {
+                            self.audio_quality = convert_audio_quality(max_audio_quality);
+                            self.controls.set_max_audio_quality(max_audio_quality);
+                        }
+                        None => tracing::info!("Error. Unknown audio quality"),
+                    }
                 }
                 Notification::FileAudioQualityChanged(_) => {
                     tracing::info!("Error. Audio quality change in runtime is not supported");
@@ -399,7 +592,8 @@ impl ConnectState {
                     tracing::info!("Error. Audio quality change in runtime is not supported");
                 }
                 Notification::Deactivated => {
-                    tracing::info!("Error. Deactivate not supported. Exit?");
+                    tracing::info!("Device deactivated");
+                    self.deactivate();
                 }
                 Notification::RestoreState(srvr_ctrl_renderer_state_updated) => {
                     tracing::info!("Restore state: {:?}", srvr_ctrl_renderer_state_updated);
@@ -407,6 +601,7 @@ impl ConnectState {
                 Notification::Disconnected { session_id, reason } => {
                     tracing::info!("Disconnect: {}, {:?}", session_id, reason);
                     self.connected = false;
+                    self.deactivate();
                 }
                 Notification::SessionClosed { device_uuid } => {
                     tracing::info!("Session closed: {:?}", device_uuid);
@@ -417,8 +612,96 @@ impl ConnectState {
     }
 }
 
+fn new_queue_items(tracks: Vec<msg::QueueTrackRef>) -> Vec<NewQueueItem> {
+    tracks
+        .into_iter()
+        .map(|x| NewQueueItem {
+            track_id: x.track_id(),
+            queue_id: x.queue_item_id,
+        })
+        .collect()
+}
+
+fn position_of(tracklist: &Tracklist, queue_id: u64) -> Option<usize> {
+    tracklist
+        .queue()
+        .iter()
+        .position(|item| item.queue_id == queue_id)
+}
+
+/// Positions of the current queue with `moved` placed after the `insert_after` item,
+/// or first when there is no anchor
+fn reordered_positions(
+    tracklist: &Tracklist,
+    moved: &[u64],
+    insert_after: Option<u64>,
+) -> Vec<usize> {
+    let queue = tracklist.queue();
+    let mut order: Vec<usize> = (0..queue.len())
+        .filter(|&index| !moved.contains(&queue[index].queue_id))
+        .collect();
+
+    let anchor = insert_after
+        .and_then(|queue_id| {
+            order
+                .iter()
+                .position(|&index| queue[index].queue_id == queue_id)
+        })
+        .map(|position| position + 1)
+        .unwrap_or(0);
+
+    let moved_positions = moved
+        .iter()
+        .filter_map(|&queue_id| position_of(tracklist, queue_id));
+    order.splice(anchor..anchor, moved_positions);
+
+    order
+}
+
 fn map_err(err: qonductor::Error) -> Error {
     Error::ConnectError {
         error: err.to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use qobuz_player_controls::{
+        models::Track,
+        tracklist::{QueueItem, Tracklist, TracklistType},
+    };
+
+    use super::reordered_positions;
+
+    fn tracklist(queue_ids: &[u64]) -> Tracklist {
+        let items = queue_ids
+            .iter()
+            .enumerate()
+            .map(|(index, &queue_id)| QueueItem {
+                track: Track::default(),
+                queue_id,
+                index,
+                queued_by_user: false,
+            })
+            .collect();
+        Tracklist::new_with_id(TracklistType::Tracks, items)
+    }
+
+    #[test]
+    fn moves_items_after_the_anchor() {
+        let tracklist = tracklist(&[10, 11, 12, 13]);
+
+        assert_eq!(
+            reordered_positions(&tracklist, &[10], Some(12)),
+            [1, 2, 0, 3]
+        );
+        assert_eq!(
+            reordered_positions(&tracklist, &[13, 12], None),
+            [3, 2, 0, 1]
+        );
+        assert_eq!(
+            reordered_positions(&tracklist, &[99], Some(10)),
+            [0, 1, 2, 3]
+        );
+    }
+}
@@ -1,11 +1,16 @@
+#[cfg(feature = "display")]
+use qobuz_player_cli::DisplayArgs;
 #[cfg(feature = "gpio")]
 use qobuz_player_cli::GpioArgs;
 use qobuz_player_cli::{
-    ConnectNameArgs, DelayArgs, SharedArgs, SharedCommands, create_player, default_audio_quality,
-    get_client, handle_shared_commands, spawn_clean_up,
+    ConnectNameArgs, DelayArgs, SharedArgs, SharedCommands, Startup, StartupSummary, create_player,
+    default_audio_quality, default_output_device, get_client, handle_shared_commands, spawn_alarms,
+    spawn_clean_up, spawn_favorites_sync, spawn_group, spawn_hooks, spawn_network_monitor,
+    spawn_play_link, spawn_release_notifications, spawn_webhooks,
 };
+use qobuz_player_connect::ConnectionStatus;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
 
 use clap::Parser;
 use qobuz_player_controls::{
@@ -28,6 +33,10 @@ struct Arguments {
     #[clap(flatten)]
     gpio: GpioArgs,
 
+    #[cfg(feature = "display")]
+    #[clap(flatten)]
+    display: DisplayArgs,
+
     #[clap(subcommand)]
     command: Option<SharedCommands>,
 }
@@ -43,40 +52,115 @@ async fn main() {
 }
 
 pub async fn run() -> AppResult<()> {
-    let args = Arguments::parse();
+    let mut args = Arguments::parse();
     let database = Arc::new(Database::new().await?);
+    database.set_profile(&args.shared.profile).await?;
+    args.shared.output_device_id =
+        default_output_device(&database, args.shared.output_device_id.take()).await?;
     let headless = true;
 
-    if let Some(command) = args.command {
-        handle_shared_commands(command, &database, headless).await?;
-        return Ok(());
-    }
+    let play_link =
+        match handle_shared_commands::<Arguments>(args.command, &args.shared, &database, headless)
+            .await?
+        {
+            Startup::Run(play_link) => play_link,
+            Startup::Exit => return Ok(()),
+        };
 
     let (_, exit_receiver) = broadcast::channel(5);
 
     let max_audio_quality = default_audio_quality(&database, args.shared.max_audio_quality).await?;
-    let client = get_client(&database, max_audio_quality, headless).await?;
+    let client = get_client(
+        &database,
+        max_audio_quality,
+        headless,
+        args.shared.fixtures(),
+    )
+    .await?;
     let client = Arc::new(client);
+    spawn_favorites_sync(
+        database.clone(),
+        client.clone(),
+        args.shared.favorites_refresh_minutes,
+    )
+    .await;
+    spawn_release_notifications(database.clone(), client.clone(), &args.shared);
 
     let broadcast = Arc::new(NotificationBroadcast::new());
 
+    let mut summary = StartupSummary::new(&args.shared, max_audio_quality);
+    summary.connect_name = Some(args.connect.connect_name.clone());
+    #[cfg(feature = "gpio")]
+    {
+        summary.gpio = args.gpio.gpio;
+    }
+    #[cfg(feature = "display")]
+    {
+        summary.display = args.display.display.is_some();
+    }
+    println!("{summary}");
+
+    let audio_output = args.shared.audio_output(false)?;
+    let hooks = args.shared.hooks();
+    let group_role = args.shared.group_role();
+
     let mut player = create_player(
-        args.shared.audio_cache,
+        &args.shared,
+        Some(&args.delay),
+        audio_output,
         database.clone(),
         client.clone(),
         broadcast.clone(),
-        args.delay.state_change_delay_ms,
-        args.delay.sample_rate_change_delay_ms,
-        args.shared.output_device_id,
     )
     .await?;
+    spawn_network_monitor(client.clone(), player.controls());
+    spawn_alarms(
+        database.clone(),
+        client.clone(),
+        &player,
+        broadcast.clone(),
+        args.shared.alarm_ramp_seconds,
+    );
+    spawn_play_link(
+        play_link,
+        client.clone(),
+        player.controls(),
+        broadcast.clone(),
+    );
 
     #[cfg(feature = "gpio")]
     if args.gpio.gpio {
+        let gpio_settings = args.gpio.settings();
         let status_receiver = player.status();
+        let volume_receiver = player.volume();
+        let controls = player.controls();
         tokio::spawn(async move {
-            if let Err(e) = qobuz_player_gpio::init(status_receiver).await {
-                error_exit(e.into());
+            if let Err(e) =
+                qobuz_player_gpio::init(gpio_settings, status_receiver, volume_receiver, controls)
+                    .await
+            {
+                error_exit(e);
+            }
+        });
+    }
+
+    #[cfg(feature = "display")]
+    if let Some(display_settings) = args.display.settings() {
+        let tracklist_receiver = player.tracklist();
+        let position_receiver = player.position();
+        let status_receiver = player.status();
+        let volume_receiver = player.volume();
+        tokio::spawn(async move {
+            if let Err(e) = qobuz_player_display::init(
+                display_settings,
+                tracklist_receiver,
+                position_receiver,
+                status_receiver,
+                volume_receiver,
+            )
+            .await
+            {
+                error_exit(e);
             }
         });
     }
@@ -86,18 +170,24 @@ pub async fn run() -> AppResult<()> {
         let position_receiver = player.position();
         let tracklist_receiver = player.tracklist();
         let volume_receiver = player.volume();
+        let muted_receiver = player.muted();
         let status_receiver = player.status();
         let controls = player.controls();
+        let broadcast = broadcast.clone();
+        let (connection_status, _) = watch::channel(ConnectionStatus::default());
 
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_connect::init(
                 &app_id,
                 args.connect.connect_name,
                 controls,
+                broadcast,
+                connection_status,
                 position_receiver,
                 tracklist_receiver,
                 status_receiver,
                 volume_receiver,
+                muted_receiver,
                 max_audio_quality,
             )
             .await
@@ -107,6 +197,14 @@ pub async fn run() -> AppResult<()> {
         });
     }
 
+    spawn_webhooks(args.shared.webhook_urls.clone(), &player);
+
+    spawn_hooks(hooks, &player);
+
+    if let Some(group_role) = group_role {
+        spawn_group(group_role, &player);
+    }
+
     spawn_clean_up(database, args.shared.audio_cache_time_to_live);
     player.player_loop(exit_receiver).await?;
 
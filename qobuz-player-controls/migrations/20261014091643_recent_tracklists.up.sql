@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "recent_tracklists" (
+    "id" text primary key not null,
+    "tracklist_type" text not null,
+    "last_played" integer not null
+);
@@ -0,0 +1,2 @@
+ALTER TABLE configuration DROP COLUMN mono_downmix;
+ALTER TABLE configuration DROP COLUMN balance;
@@ -0,0 +1,2 @@
+ALTER TABLE configuration ADD COLUMN balance REAL NOT NULL DEFAULT 0;
+ALTER TABLE configuration ADD COLUMN mono_downmix BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1 @@
+ALTER TABLE configuration ADD COLUMN data_saver BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+CREATE TABLE IF NOT EXISTS "favorites" (
+	"favorites"	string not null
+);
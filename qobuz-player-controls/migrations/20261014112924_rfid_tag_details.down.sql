@@ -0,0 +1,2 @@
+ALTER TABLE rfid_references DROP COLUMN name;
+ALTER TABLE rfid_references DROP COLUMN last_scanned;
@@ -0,0 +1,2 @@
+ALTER TABLE rfid_references ADD COLUMN name TEXT;
+ALTER TABLE rfid_references ADD COLUMN last_scanned TEXT;
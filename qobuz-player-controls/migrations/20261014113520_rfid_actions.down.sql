@@ -0,0 +1,2 @@
+DELETE FROM rfid_references WHERE reference_type > 2;
+ALTER TABLE rfid_references DROP COLUMN volume;
@@ -0,0 +1 @@
+ALTER TABLE rfid_references ADD COLUMN volume INT;
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "web_sessions" (
+	"token"	TEXT NOT NULL,
+	"user"	TEXT,
+	"expires_at"	TEXT NOT NULL,
+	PRIMARY KEY("token")
+);
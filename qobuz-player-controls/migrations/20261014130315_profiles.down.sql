@@ -0,0 +1,9 @@
+DELETE FROM credentials WHERE profile != 'default';
+DELETE FROM configuration WHERE profile != 'default';
+DELETE FROM favorites WHERE profile != 'default';
+DROP INDEX credentials_profile;
+DROP INDEX configuration_profile;
+DROP INDEX favorites_profile;
+ALTER TABLE credentials DROP COLUMN profile;
+ALTER TABLE configuration DROP COLUMN profile;
+ALTER TABLE favorites DROP COLUMN profile;
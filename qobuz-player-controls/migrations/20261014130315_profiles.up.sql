@@ -0,0 +1,6 @@
+ALTER TABLE credentials ADD COLUMN profile TEXT NOT NULL DEFAULT 'default';
+CREATE UNIQUE INDEX credentials_profile ON credentials (profile);
+ALTER TABLE configuration ADD COLUMN profile TEXT NOT NULL DEFAULT 'default';
+CREATE UNIQUE INDEX configuration_profile ON configuration (profile);
+ALTER TABLE favorites ADD COLUMN profile TEXT NOT NULL DEFAULT 'default';
+CREATE UNIQUE INDEX favorites_profile ON favorites (profile);
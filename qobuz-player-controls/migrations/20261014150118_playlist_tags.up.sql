@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "playlist_tags" (
+	"profile"	TEXT NOT NULL,
+	"playlist_id"	INTEGER NOT NULL,
+	"tag"	TEXT NOT NULL,
+	PRIMARY KEY("profile", "playlist_id", "tag")
+);
@@ -0,0 +1,3 @@
+DROP TABLE favorite_tracks_added;
+DROP INDEX play_history_played_at;
+DROP TABLE play_history;
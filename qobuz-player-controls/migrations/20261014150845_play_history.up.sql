@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS "play_history" (
+	"profile"	TEXT NOT NULL,
+	"track_id"	INTEGER NOT NULL,
+	"played_at"	INTEGER NOT NULL
+);
+CREATE INDEX play_history_played_at ON play_history (profile, played_at);
+CREATE TABLE IF NOT EXISTS "favorite_tracks_added" (
+	"profile"	TEXT NOT NULL,
+	"track_id"	INTEGER NOT NULL,
+	"added_at"	INTEGER NOT NULL,
+	PRIMARY KEY("profile", "track_id")
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS "banned" (
+	"profile"	TEXT NOT NULL,
+	"kind"	TEXT NOT NULL,
+	"id"	INTEGER NOT NULL,
+	"name"	TEXT NOT NULL,
+	PRIMARY KEY("profile", "kind", "id")
+);
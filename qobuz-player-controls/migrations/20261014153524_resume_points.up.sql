@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "resume_points" (
+	"profile"	TEXT NOT NULL,
+	"track_id"	INTEGER NOT NULL,
+	"position_seconds"	INTEGER NOT NULL,
+	PRIMARY KEY("profile", "track_id")
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "search_history" (
+	"profile"	TEXT NOT NULL,
+	"query"	TEXT NOT NULL,
+	"searched_at"	INTEGER NOT NULL,
+	PRIMARY KEY("profile", "query")
+);
@@ -0,0 +1 @@
+ALTER TABLE configuration ADD COLUMN output_device TEXT;
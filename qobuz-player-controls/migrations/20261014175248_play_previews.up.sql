@@ -0,0 +1 @@
+ALTER TABLE configuration ADD COLUMN play_previews BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,2 @@
+ALTER TABLE cache_entries DROP COLUMN hash;
+ALTER TABLE cache_entries DROP COLUMN size;
@@ -0,0 +1,2 @@
+ALTER TABLE cache_entries ADD COLUMN size INTEGER;
+ALTER TABLE cache_entries ADD COLUMN hash TEXT;
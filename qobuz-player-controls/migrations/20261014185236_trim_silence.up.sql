@@ -0,0 +1 @@
+ALTER TABLE configuration ADD COLUMN trim_silence BOOLEAN NOT NULL DEFAULT FALSE;
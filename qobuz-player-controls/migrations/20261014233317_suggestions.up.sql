@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "suggestions" (
+	"track_id"	INTEGER NOT NULL,
+	"title"	TEXT NOT NULL,
+	"artist_name"	TEXT,
+	"suggested_by"	TEXT,
+	"suggested_at"	TEXT NOT NULL,
+	PRIMARY KEY("track_id")
+);
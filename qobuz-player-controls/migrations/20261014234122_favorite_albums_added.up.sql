@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "favorite_albums_added" (
+	"profile"	TEXT NOT NULL,
+	"album_id"	TEXT NOT NULL,
+	"added_at"	INTEGER,
+	PRIMARY KEY("profile", "album_id")
+);
@@ -0,0 +1,2 @@
+DROP TABLE artist_releases_seen;
+DROP TABLE followed_artists;
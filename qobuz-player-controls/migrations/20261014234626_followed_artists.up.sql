@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS "followed_artists" (
+	"profile"	TEXT NOT NULL,
+	"artist_id"	INTEGER NOT NULL,
+	"name"	TEXT NOT NULL,
+	PRIMARY KEY("profile", "artist_id")
+);
+CREATE TABLE IF NOT EXISTS "artist_releases_seen" (
+	"profile"	TEXT NOT NULL,
+	"artist_id"	INTEGER NOT NULL,
+	"album_id"	TEXT NOT NULL,
+	PRIMARY KEY("profile", "artist_id", "album_id")
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS "alarms" (
+	"id"	INTEGER NOT NULL,
+	"schedule"	TEXT NOT NULL,
+	"link"	TEXT NOT NULL,
+	"title"	TEXT NOT NULL,
+	"volume"	INTEGER NOT NULL,
+	"enabled"	INTEGER NOT NULL DEFAULT 1,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
@@ -0,0 +1 @@
+DROP TABLE quiet_hours;
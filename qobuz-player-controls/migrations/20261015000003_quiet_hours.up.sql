@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "quiet_hours" (
+	"start_minute"	INTEGER NOT NULL,
+	"end_minute"	INTEGER NOT NULL,
+	"max_volume"	INTEGER NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE followed_artists DROP COLUMN checked_at;
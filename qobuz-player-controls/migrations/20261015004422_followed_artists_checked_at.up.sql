@@ -0,0 +1,6 @@
+ALTER TABLE followed_artists ADD COLUMN checked_at INTEGER;
+UPDATE followed_artists SET checked_at = unixepoch() WHERE EXISTS (
+	SELECT 1 FROM artist_releases_seen
+	WHERE artist_releases_seen.profile = followed_artists.profile
+	AND artist_releases_seen.artist_id = followed_artists.artist_id
+);
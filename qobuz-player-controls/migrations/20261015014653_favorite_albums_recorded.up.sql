@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS "favorite_albums_recorded" (
+	"profile"	TEXT NOT NULL PRIMARY KEY
+);
+INSERT OR IGNORE INTO favorite_albums_recorded (profile) SELECT DISTINCT profile FROM favorite_albums_added;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "favorite_tracks_recorded" (
+	"profile"	TEXT NOT NULL PRIMARY KEY
+);
+INSERT OR IGNORE INTO favorite_tracks_recorded (profile) SELECT DISTINCT profile FROM favorite_tracks_added;
+UPDATE favorite_tracks_added SET added_at = 0 WHERE added_at = (
+	SELECT min(added_at) FROM favorite_tracks_added AS first_recorded
+	WHERE first_recorded.profile = favorite_tracks_added.profile
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "zones" (
+	"name"	TEXT NOT NULL PRIMARY KEY,
+	"volume"	REAL NOT NULL,
+	"enabled"	BOOLEAN NOT NULL
+);
@@ -0,0 +1,333 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use chrono::{Datelike, Local, Timelike};
+
+use crate::{
+    AppResult, Error, VolumeReceiver,
+    client::Client,
+    controls::Controls,
+    database::{Alarm, Database},
+    notification::{Notification, NotificationBroadcast},
+    player::VolumeCap,
+};
+
+/// Largest difference between the volume set by the ramp and the volume of the player
+/// before the ramp counts as overruled
+const VOLUME_TOLERANCE: f32 = 0.005;
+
+/// Cron schedule with the fields minute, hour, day of month, month and day of week. Fields
+/// take `*`, numbers, ranges like `1-5`, lists like `1,3,5` and steps like `*/15`. Day of
+/// week 0 and 7 are both Sunday. When neither day field starts with `*`, either of them
+/// matches, like in cron
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// `weekday` counts from Sunday as 0
+    pub fn matches(&self, minute: u32, hour: u32, day: u32, month: u32, weekday: u32) -> bool {
+        let day_matches = self.days & (1 << day) != 0;
+        let weekday_matches = self.weekdays & (1 << weekday) != 0;
+        let date_matches = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday_matches,
+            (false, true) => day_matches,
+            (false, false) => day_matches || weekday_matches,
+        };
+
+        self.minutes & (1 << minute) != 0
+            && self.hours & (1 << hour) != 0
+            && self.months & (1 << month) != 0
+            && date_matches
+    }
+
+    fn matches_now(&self) -> bool {
+        let now = Local::now();
+        self.matches(
+            now.minute(),
+            now.hour(),
+            now.day(),
+            now.month(),
+            now.weekday().num_days_from_sunday(),
+        )
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "{text} is not a schedule with minute, hour, day, month and weekday, like 30 7 * * 1-5"
+            ));
+        };
+
+        // Sunday is both 0 and 7
+        let weekday_bits = parse_field(weekdays, 0, 7)?;
+        let weekday_bits = (weekday_bits | weekday_bits >> 7) & 0x7f;
+
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)? as u32,
+            days: parse_field(days, 1, 31)? as u32,
+            months: parse_field(months, 1, 12)? as u16,
+            weekdays: weekday_bits as u8,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+}
+
+/// Bit n is set when the field matches n
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |text: &str| {
+        text.parse::<u32>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(|| format!("{text} is not a number from {min} to {max}"))
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("{step} is not a step")),
+            },
+            None => (part, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // A single value with a step repeats until the end, like 5/15
+            None if step.is_some() => (number(range)?, max),
+            None => {
+                let value = number(range)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(format!("{range} is not an increasing range"));
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// Adds an alarm starting `link` at the times of `schedule`, named after the album,
+/// playlist or track the link points to
+pub async fn add(
+    database: &Database,
+    client: &Client,
+    schedule: &str,
+    link: &str,
+    volume: u8,
+) -> AppResult<Alarm> {
+    let schedule = schedule.split_whitespace().collect::<Vec<_>>().join(" ");
+    schedule
+        .parse::<Schedule>()
+        .map_err(|message| Error::Client { message })?;
+
+    let link = link.trim();
+    let entity = client.resolve_link(link).await?;
+    let volume = volume.min(100);
+    let id = database
+        .add_alarm(&schedule, link, entity.title(), volume)
+        .await?;
+
+    Ok(Alarm {
+        id,
+        schedule,
+        link: link.to_string(),
+        title: entity.title().to_string(),
+        volume,
+        enabled: true,
+    })
+}
+
+/// Starts the enabled alarms at the start of each minute their schedule matches. The
+/// volume starts at 0 and rises to the volume of the alarm over `ramp`
+pub async fn run(
+    database: Arc<Database>,
+    client: Arc<Client>,
+    controls: Controls,
+    volume: VolumeReceiver,
+    volume_cap: VolumeCap,
+    broadcast: Arc<NotificationBroadcast>,
+    ramp: Duration,
+) {
+    loop {
+        let now = Local::now();
+        let into_minute = Duration::new(u64::from(now.second()), now.nanosecond());
+        // Wakes just after the minute starts, so a clock running a bit fast does not check
+        // the same minute twice
+        let until_next_minute = Duration::from_secs(61).saturating_sub(into_minute);
+        tokio::time::sleep(until_next_minute).await;
+
+        let alarms = match database.get_alarms().await {
+            Ok(alarms) => alarms,
+            Err(err) => {
+                tracing::warn!("Unable to read the alarms: {err}");
+                continue;
+            }
+        };
+
+        for alarm in alarms.into_iter().filter(|alarm| alarm.enabled) {
+            match alarm.schedule.parse::<Schedule>() {
+                Ok(schedule) if schedule.matches_now() => {
+                    tokio::spawn(start(
+                        alarm,
+                        client.clone(),
+                        controls.clone(),
+                        volume.clone(),
+                        volume_cap.clone(),
+                        broadcast.clone(),
+                        ramp,
+                    ));
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!("Alarm {} has an invalid schedule: {err}", alarm.id),
+            }
+        }
+    }
+}
+
+async fn start(
+    alarm: Alarm,
+    client: Arc<Client>,
+    controls: Controls,
+    volume: VolumeReceiver,
+    volume_cap: VolumeCap,
+    broadcast: Arc<NotificationBroadcast>,
+    ramp: Duration,
+) {
+    let entity = match client.resolve_link(&alarm.link).await {
+        Ok(entity) => entity,
+        Err(err) => {
+            broadcast.report(&err);
+            return;
+        }
+    };
+
+    let target = f32::from(alarm.volume.min(100)) / 100.0;
+    if ramp.is_zero() {
+        controls.set_volume(target);
+    } else {
+        controls.set_volume(0.0);
+    }
+    if let Err(err) = controls.play_linked_entity_at(&entity, None) {
+        broadcast.report(&err);
+        return;
+    }
+
+    tracing::info!("Alarm {} started {}", alarm.id, entity.title());
+    broadcast.send(Notification::Info(format!("Alarm: {}", entity.title())));
+
+    if !ramp.is_zero() {
+        ramp_volume(&controls, volume, volume_cap, target, ramp).await;
+    }
+}
+
+/// Raises the volume a step every second until it reaches `target`. Stops when someone else
+/// changes the volume, so turning it down while the alarm plays is not overruled
+async fn ramp_volume(
+    controls: &Controls,
+    volume: VolumeReceiver,
+    volume_cap: VolumeCap,
+    target: f32,
+    ramp: Duration,
+) {
+    let steps = ramp.as_secs().max(1) as u32;
+    let interval = ramp / steps;
+    let mut expected = 0.0;
+
+    for step in 1..=steps {
+        tokio::time::sleep(interval).await;
+
+        if overruled(*volume.borrow(), expected, volume_cap.max_volume()) {
+            return;
+        }
+
+        expected = target * step as f32 / steps as f32;
+        controls.set_volume(expected);
+    }
+}
+
+/// Whether the volume differs from the one the ramp set last, which the player lowers to
+/// `max_volume`
+fn overruled(volume: f32, expected: f32, max_volume: f32) -> bool {
+    (volume - expected.min(max_volume)).abs() > VOLUME_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Schedule, overruled};
+
+    #[test]
+    fn capped_volume_does_not_stop_the_ramp() {
+        assert!(!overruled(0.4, 0.4, 1.0));
+        assert!(!overruled(0.5, 0.8, 0.5));
+        assert!(overruled(0.3, 0.8, 0.5));
+        assert!(overruled(0.6, 0.4, 1.0));
+    }
+
+    #[test]
+    fn weekday_mornings() {
+        let schedule: Schedule = "30 7 * * 1-5".parse().unwrap();
+
+        // Monday 2026-10-12 and Sunday 2026-10-18
+        assert!(schedule.matches(30, 7, 12, 10, 1));
+        assert!(!schedule.matches(31, 7, 12, 10, 1));
+        assert!(!schedule.matches(30, 19, 12, 10, 1));
+        assert!(!schedule.matches(30, 7, 18, 10, 0));
+    }
+
+    #[test]
+    fn lists_steps_and_sunday_as_seven() {
+        let schedule: Schedule = "*/20 6,8 * 1-3 7".parse().unwrap();
+
+        assert!(schedule.matches(0, 6, 1, 2, 0));
+        assert!(schedule.matches(40, 8, 1, 2, 0));
+        assert!(!schedule.matches(10, 8, 1, 2, 0));
+        assert!(!schedule.matches(0, 7, 1, 2, 0));
+        assert!(!schedule.matches(0, 6, 1, 4, 0));
+        assert!(!schedule.matches(0, 6, 1, 2, 6));
+    }
+
+    #[test]
+    fn either_day_field_matches_when_both_are_given() {
+        let schedule: Schedule = "0 9 1 * 6".parse().unwrap();
+
+        assert!(schedule.matches(0, 9, 1, 5, 3));
+        assert!(schedule.matches(0, 9, 17, 5, 6));
+        assert!(!schedule.matches(0, 9, 17, 5, 3));
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        for text in [
+            "30 7 * *",
+            "60 7 * * *",
+            "0 7 0 * *",
+            "0 7 * * 8",
+            "0 5-1 * * *",
+            "*/0 7 * * *",
+        ] {
+            assert!(text.parse::<Schedule>().is_err(), "{text}");
+        }
+    }
+}
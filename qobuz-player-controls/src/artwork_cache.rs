@@ -0,0 +1,281 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::{ImageFormat, load_from_memory};
+use tokio::sync::Mutex;
+
+use crate::{AppResult, audio_cache, error::Error};
+
+/// Largest variant that is stored, bigger requests get this size.
+pub const MAX_ARTWORK_SIZE: u32 = 1200;
+const ORIGINAL_FILE: &str = "original";
+/// Registered urls kept in memory, the least recently used are forgotten first.
+const MAX_SOURCES: u64 = 10_000;
+
+/// Downloads album and artist art once and keeps resized variants on disk.
+#[derive(Debug)]
+pub struct ArtworkCache {
+    dir: PathBuf,
+    http: reqwest::Client,
+    sources: moka::sync::Cache<String, String>,
+    max_bytes: Option<u64>,
+    /// Held while evicting, so two evictions do not both delete
+    eviction: Mutex<()>,
+}
+
+impl ArtworkCache {
+    /// The artwork changed longest ago is deleted when the cache grows over `max_bytes`.
+    pub fn new(dir: PathBuf, max_bytes: Option<u64>) -> Self {
+        Self {
+            dir,
+            http: reqwest::Client::new(),
+            sources: moka::sync::Cache::new(MAX_SOURCES),
+            max_bytes,
+            eviction: Mutex::new(()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    /// Registers an artwork url and returns the id it can be fetched with.
+    pub fn register(&self, url: &str) -> String {
+        let id = format!("{:x}", md5::compute(url));
+        self.sources.get_with(id.clone(), || url.to_string());
+        id
+    }
+
+    /// Artwork for `url`, scaled to fit within `size` pixels.
+    pub async fn image(&self, url: &str, size: u32) -> AppResult<Vec<u8>> {
+        let id = self.register(url);
+        self.get(&id, size).await
+    }
+
+    /// `file://` url of the artwork for `url`, for clients that will not fetch remote images.
+    pub async fn file_url(&self, url: &str, size: u32) -> AppResult<String> {
+        let id = self.register(url);
+        let path = self.path(&id, size).await?;
+        let path = std::path::absolute(&path).map_err(artwork_error)?;
+
+        reqwest::Url::from_file_path(&path)
+            .map(String::from)
+            .map_err(|_| Error::Artwork {
+                message: format!("Invalid artwork path: {path:?}"),
+            })
+    }
+
+    /// Registered artwork, scaled to fit within `size` pixels.
+    pub async fn get(&self, id: &str, size: u32) -> AppResult<Vec<u8>> {
+        let path = self.path(id, size).await?;
+        tokio::fs::read(&path).await.map_err(artwork_error)
+    }
+
+    /// Path of the registered artwork scaled to fit within `size` pixels.
+    /// The original is downloaded and the variant is resized on first use.
+    pub async fn path(&self, id: &str, size: u32) -> AppResult<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::Artwork {
+                message: format!("Invalid artwork id: {id}"),
+            });
+        }
+
+        let size = size.clamp(1, MAX_ARTWORK_SIZE);
+        let dir = self.dir.join(id);
+        let variant = dir.join(format!("{size}.jpg"));
+
+        if tokio::fs::try_exists(&variant).await.unwrap_or(false) {
+            return Ok(variant);
+        }
+
+        let original = self.original(id, &dir).await?;
+        let target = variant.clone();
+        tokio::task::spawn_blocking(move || resize(&original, &target, size))
+            .await
+            .map_err(artwork_error)??;
+
+        self.evict(id).await;
+
+        Ok(variant)
+    }
+
+    /// Brings the cache back under its size limit. The artwork in `keep` was just
+    /// written and stays
+    async fn evict(&self, keep: &str) {
+        let Some(max_bytes) = self.max_bytes else {
+            return;
+        };
+
+        let _guard = self.eviction.lock().await;
+        let dir = self.dir.clone();
+        let keep = keep.to_string();
+        match tokio::task::spawn_blocking(move || evict_to_size(&dir, max_bytes, &keep)).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(freed)) => {
+                tracing::info!("Evicted {} of artwork", audio_cache::format_size(freed))
+            }
+            Ok(Err(err)) => tracing::warn!("Unable to evict artwork: {err}"),
+            Err(err) => tracing::warn!("Unable to evict artwork: {err}"),
+        }
+    }
+
+    async fn original(&self, id: &str, dir: &Path) -> AppResult<Vec<u8>> {
+        let path = dir.join(ORIGINAL_FILE);
+        if let Ok(bytes) = tokio::fs::read(&path).await {
+            return Ok(bytes);
+        }
+
+        let Some(url) = self.sources.get(id) else {
+            return Err(Error::Artwork {
+                message: format!("Unknown artwork: {id}"),
+            });
+        };
+
+        tracing::info!("Caching artwork: {url}");
+        let bytes = self
+            .http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec();
+
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(artwork_error)?;
+        write_atomic(&path, &bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+/// Deletes the artwork directories changed longest ago until `dir` takes at most
+/// `max_bytes`. Returns the bytes freed
+fn evict_to_size(dir: &Path, max_bytes: u64, keep: &str) -> std::io::Result<u64> {
+    let mut entries = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            let bytes = audio_cache::usage(&entry.path()).bytes;
+            entries.push((metadata.modified()?, bytes, entry));
+        }
+    }
+
+    let mut total: u64 = entries.iter().map(|(_, bytes, _)| bytes).sum();
+    let mut freed = 0;
+    entries.sort_by_key(|(modified, _, _)| *modified);
+
+    for (_, bytes, entry) in entries {
+        if total <= max_bytes {
+            break;
+        }
+        if entry.file_name() == keep {
+            continue;
+        }
+
+        std::fs::remove_dir_all(entry.path())?;
+        total -= bytes;
+        freed += bytes;
+    }
+
+    Ok(freed)
+}
+
+fn resize(original: &[u8], target: &Path, size: u32) -> AppResult<()> {
+    let image = load_from_memory(original).map_err(artwork_error)?;
+    let image = if image.width() > size || image.height() > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+
+    let mut encoded = Cursor::new(Vec::new());
+    image
+        .to_rgb8()
+        .write_to(&mut encoded, ImageFormat::Jpeg)
+        .map_err(artwork_error)?;
+
+    write_atomic(target, encoded.get_ref())
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> AppResult<()> {
+    let tmp = path.with_extension("partial");
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            artwork_error(e)
+        })
+}
+
+fn artwork_error(error: impl std::fmt::Display) -> Error {
+    Error::Artwork {
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArtworkCache, evict_to_size};
+    use image::{DynamicImage, ImageFormat, load_from_memory};
+    use std::{
+        io::Cursor,
+        time::{Duration, SystemTime},
+    };
+
+    #[tokio::test]
+    async fn test_resizes_cached_original() {
+        let dir = std::env::temp_dir().join(format!("qobuz-player-artwork-{}", std::process::id()));
+        let cache = ArtworkCache::new(dir.clone(), None);
+        let id = cache.register("https://static.qobuz.com/images/covers/test_max.jpg");
+
+        let mut original = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(800, 400)
+            .write_to(&mut original, ImageFormat::Png)
+            .unwrap();
+        std::fs::create_dir_all(dir.join(&id)).unwrap();
+        std::fs::write(dir.join(&id).join("original"), original.get_ref()).unwrap();
+
+        let resized = load_from_memory(&cache.get(&id, 200).await.unwrap()).unwrap();
+        assert_eq!((resized.width(), resized.height()), (200, 100));
+        assert!(cache.get("../secret", 200).await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_the_oldest_artwork() {
+        let dir = std::env::temp_dir().join(format!(
+            "qobuz-player-artwork-eviction-{}",
+            std::process::id()
+        ));
+        let now = SystemTime::now();
+        for (minutes, id) in [(3, "a"), (2, "b"), (1, "c")] {
+            let path = dir.join(id);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("original"), [0; 1000]).unwrap();
+            std::fs::File::open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(minutes * 60))
+                .unwrap();
+        }
+
+        // "a" is the oldest, but was just written
+        assert_eq!(evict_to_size(&dir, 2000, "a").unwrap(), 1000);
+        assert!(dir.join("a").exists());
+        assert!(!dir.join("b").exists());
+        assert!(dir.join("c").exists());
+        assert_eq!(evict_to_size(&dir, 2000, "a").unwrap(), 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
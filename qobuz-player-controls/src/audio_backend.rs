@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use qobuz_player_client::stream::flac_source_stream::SeekableStreamReader;
+use tokio::sync::watch::Receiver;
+
+use crate::AppResult;
+use crate::transitions::StreamFormat;
+
+/// Plays the decoded tracks queued by the player
+pub trait AudioBackend: Send + Sync {
+    /// Changes to the time the last sample of a finished track was handed to the output
+    fn track_finished(&self) -> Receiver<SystemTime>;
+
+    /// Position in the playing track
+    fn position(&self) -> Duration;
+
+    /// Fades in when a volume ramp is set, without waiting for it
+    fn play(&self);
+
+    /// Fades out when a volume ramp is set, without waiting for it
+    fn pause(&self);
+
+    /// Starts fading out the playing track before a manual skip clears the queue, so the next
+    /// track fades in when it plays. How long the fade takes, `None` when nothing fades
+    fn fade_out(&self) -> Option<Duration>;
+
+    fn seek(&self, duration: Duration) -> AppResult<()>;
+
+    /// Stops playback and releases the output
+    fn clear(&mut self) -> AppResult<()>;
+
+    /// Drops the queued tracks but keeps the output open
+    fn clear_queue(&mut self) -> AppResult<()>;
+
+    /// Whether the output is released, so the next track must be queried from the start
+    fn is_empty(&self) -> bool;
+
+    /// Queue a downloaded track after the playing one
+    fn query_track(&mut self, track_id: u32, track_path: &Path) -> AppResult<QueryTrackResult>;
+
+    /// Queue a track while it is still downloading
+    fn query_track_stream(
+        &mut self,
+        track_id: u32,
+        reader: SeekableStreamReader,
+    ) -> AppResult<QueryTrackResult>;
+
+    /// Apply a changed volume level or zone volume
+    fn sync_volume(&self);
+}
+
+pub enum QueryTrackResult {
+    Queued(StreamFormat),
+    /// The output is open in another sample rate or channel count. Clear it and query again
+    RecreateStreamRequired,
+}
+
+/// Backend selected on the command line
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum AudioBackendKind {
+    /// Output device through cpal
+    #[default]
+    Rodio,
+    /// ALSA pcm written directly, without resampling or mixing. Linux only
+    Alsa,
+    /// Raw interleaved 32-bit float little endian samples written to a file, fifo or stdout
+    Pipe,
+    /// 32-bit float WAV file, written faster than realtime
+    Wav,
+    /// Decode faster than realtime without playing anything, for tests without audio hardware
+    Null,
+}
+
+/// Destination of the decoded audio
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioOutput {
+    /// The preferred output device, or the default one
+    Device,
+    /// ALSA pcm, like hw:0 or plughw:0
+    Alsa {
+        device: String,
+    },
+    /// `-` writes to stdout
+    Pipe {
+        path: PathBuf,
+    },
+    Wav {
+        path: PathBuf,
+    },
+    Null,
+}
+
+impl AudioOutput {
+    pub fn new(
+        kind: AudioBackendKind,
+        alsa_device: String,
+        pipe_path: PathBuf,
+        wav_path: PathBuf,
+    ) -> Self {
+        match kind {
+            AudioBackendKind::Rodio => AudioOutput::Device,
+            AudioBackendKind::Alsa => AudioOutput::Alsa {
+                device: alsa_device,
+            },
+            AudioBackendKind::Pipe => AudioOutput::Pipe { path: pipe_path },
+            AudioBackendKind::Wav => AudioOutput::Wav { path: wav_path },
+            AudioBackendKind::Null => AudioOutput::Null,
+        }
+    }
+
+    /// Whether the samples are written to stdout, which must then not be a terminal
+    pub fn writes_to_stdout(&self) -> bool {
+        matches!(self, AudioOutput::Pipe { path } if path.as_os_str() == "-")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{AudioBackendKind, AudioOutput};
+
+    fn output(kind: AudioBackendKind, pipe_path: &str) -> AudioOutput {
+        AudioOutput::new(
+            kind,
+            "hw:0".to_string(),
+            PathBuf::from(pipe_path),
+            PathBuf::from("out.wav"),
+        )
+    }
+
+    #[test]
+    fn backend_selects_the_output() {
+        assert_eq!(output(AudioBackendKind::Rodio, "-"), AudioOutput::Device);
+        assert_eq!(
+            output(AudioBackendKind::Alsa, "-"),
+            AudioOutput::Alsa {
+                device: "hw:0".to_string()
+            }
+        );
+        assert_eq!(
+            output(AudioBackendKind::Wav, "-"),
+            AudioOutput::Wav {
+                path: PathBuf::from("out.wav")
+            }
+        );
+    }
+
+    #[test]
+    fn only_a_pipe_to_dash_writes_to_stdout() {
+        assert!(output(AudioBackendKind::Pipe, "-").writes_to_stdout());
+        assert!(!output(AudioBackendKind::Pipe, "/tmp/audio.fifo").writes_to_stdout());
+        assert!(!output(AudioBackendKind::Rodio, "-").writes_to_stdout());
+    }
+}
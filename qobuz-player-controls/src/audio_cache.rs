@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+
+use qobuz_player_client::stream::{
+    cache_encryption::{self, CacheKey},
+    flac_source_stream::partial_segments_dir,
+};
+
+use crate::{AppResult, database::Database};
+
+/// Files and bytes in a cache directory
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct CacheUsage {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl std::fmt::Display for CacheUsage {
+    /// Like "12 files, 340.5 MB"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} files, {}", self.files, format_size(self.bytes))
+    }
+}
+
+/// Walks the whole directory, so call it off the async runtime
+pub fn usage(path: &Path) -> CacheUsage {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return CacheUsage::default();
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => usage(&entry.path()),
+            Ok(metadata) => CacheUsage {
+                files: 1,
+                bytes: metadata.len(),
+            },
+            Err(_) => CacheUsage::default(),
+        })
+        .fold(CacheUsage::default(), |total, usage| CacheUsage {
+            files: total.files + usage.files,
+            bytes: total.bytes + usage.bytes,
+        })
+}
+
+/// Decimal units, like "1.5 GB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Deletes a cached track and the segments of an unfinished download
+pub async fn remove_track(path: &Path) {
+    _ = tokio::fs::remove_file(path).await;
+    _ = tokio::fs::remove_dir_all(partial_segments_dir(path)).await;
+}
+
+/// Deletes the least recently played tracks until the cached tracks take at most
+/// `max_bytes`. The track in `keep` is being played and never deleted
+pub async fn evict_to_size(
+    database: &Database,
+    max_bytes: u64,
+    keep: &Path,
+) -> AppResult<Vec<PathBuf>> {
+    let entries = database.get_cache_entries().await?;
+    let entries = tokio::task::spawn_blocking(move || {
+        entries
+            .into_iter()
+            .map(|path| {
+                let size = track_size(&path);
+                (path, size)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let mut total: u64 = entries.iter().map(|(_, size)| size).sum();
+    let mut evicted = vec![];
+
+    for (path, size) in entries {
+        if total <= max_bytes {
+            break;
+        }
+        if path == keep {
+            continue;
+        }
+
+        database.remove_cache_entry(&path).await?;
+        remove_track(&path).await;
+        total = total.saturating_sub(size);
+        evicted.push(path);
+    }
+
+    Ok(evicted)
+}
+
+/// Deletes everything in a cache directory. Returns what was freed
+pub async fn clear_directory(dir: &Path) -> CacheUsage {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let freed = usage(&dir);
+        _ = std::fs::remove_dir_all(&dir);
+        _ = std::fs::create_dir_all(&dir);
+        freed
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Encrypts the tracks and segments cached before encryption was enabled
+pub async fn encrypt_plain_files(dir: PathBuf, key: CacheKey) {
+    let encrypted = tokio::task::spawn_blocking(move || encrypt_directory(&dir, &key))
+        .await
+        .unwrap_or_default();
+    if encrypted > 0 {
+        tracing::info!("Encrypted {encrypted} files cached before encryption was enabled");
+    }
+}
+
+/// Number of files encrypted. Files still being written are skipped
+fn encrypt_directory(dir: &Path, key: &CacheKey) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            let writing = path
+                .extension()
+                .is_some_and(|extension| extension == "partial" || extension == "encrypting");
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => encrypt_directory(&path, key),
+                Ok(_) if !writing => match cache_encryption::encrypt_file(key, &path) {
+                    Ok(encrypted) => u64::from(encrypted),
+                    Err(err) => {
+                        tracing::warn!("Unable to encrypt {}: {err}", path.display());
+                        0
+                    }
+                },
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+/// Including the segments of an unfinished download
+fn track_size(path: &Path) -> u64 {
+    let file = std::fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or_default();
+
+    file + usage(&partial_segments_dir(path)).bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::database::Database;
+
+    use super::{evict_to_size, format_size};
+
+    #[sqlx::test]
+    async fn evicts_the_least_recently_played_tracks(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let database = Database::init(pool).await.unwrap();
+        let dir = std::env::temp_dir().join(format!(
+            "qobuz-player-audio-cache-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let tracks: Vec<PathBuf> = ["oldest", "playing", "older", "newest"]
+            .iter()
+            .map(|name| dir.join(format!("{name}.flac")))
+            .collect();
+        for track in &tracks {
+            std::fs::write(track, [0; 100]).unwrap();
+            database.set_cache_entry(track).await;
+        }
+
+        let evicted = evict_to_size(&database, 200, &tracks[1]).await.unwrap();
+        let remaining = database.get_cache_entries().await.unwrap();
+        let files_left: Vec<bool> = tracks.iter().map(|track| track.exists()).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(evicted, [tracks[0].clone(), tracks[2].clone()]);
+        assert_eq!(remaining, [tracks[1].clone(), tracks[3].clone()]);
+        assert_eq!(files_left, [false, true, false, true]);
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(1_500), "1.5 kB");
+        assert_eq!(format_size(340_500_000), "340.5 MB");
+        assert_eq!(format_size(2_000_000_000_000_000), "2000.0 TB");
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+/// Balance and mono downmix shared between the player and the playing sources
+#[derive(Debug, Default)]
+pub struct ChannelMixSettings {
+    balance: AtomicU32,
+    mono_downmix: AtomicBool,
+}
+
+impl ChannelMixSettings {
+    pub fn new(balance: f32, mono_downmix: bool) -> Self {
+        let settings = Self::default();
+        settings.set_balance(balance);
+        settings.set_mono_downmix(mono_downmix);
+        settings
+    }
+
+    /// -1.0 is fully left and 1.0 is fully right
+    pub fn set_balance(&self, balance: f32) {
+        let balance = balance.clamp(-1.0, 1.0);
+        self.balance.store(balance.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn balance(&self) -> f32 {
+        f32::from_bits(self.balance.load(Ordering::Relaxed))
+    }
+
+    pub fn set_mono_downmix(&self, mono_downmix: bool) {
+        self.mono_downmix.store(mono_downmix, Ordering::Relaxed);
+    }
+
+    pub fn mono_downmix(&self) -> bool {
+        self.mono_downmix.load(Ordering::Relaxed)
+    }
+}
+
+/// Applies [`ChannelMixSettings`] to every frame of the inner source
+pub struct ChannelMix<I> {
+    input: I,
+    settings: Arc<ChannelMixSettings>,
+    frame: Vec<Sample>,
+    index: usize,
+}
+
+impl<I: Source> ChannelMix<I> {
+    pub fn new(input: I, settings: Arc<ChannelMixSettings>) -> Self {
+        Self {
+            input,
+            settings,
+            frame: Vec::with_capacity(2),
+            index: 0,
+        }
+    }
+
+    fn next_frame(&mut self) -> Option<()> {
+        let channels = self.input.channels().get() as usize;
+
+        self.frame.clear();
+        self.index = 0;
+        self.frame.extend(self.input.by_ref().take(channels));
+
+        if self.frame.is_empty() {
+            return None;
+        }
+
+        if self.settings.mono_downmix() && self.frame.len() > 1 {
+            let mono = self.frame.iter().sum::<Sample>() / self.frame.len() as Sample;
+            self.frame.fill(mono);
+        }
+
+        let balance = self.settings.balance();
+        if balance != 0.0 && self.frame.len() == 2 {
+            self.frame[0] *= (1.0 - balance).min(1.0);
+            self.frame[1] *= (1.0 + balance).min(1.0);
+        }
+
+        Some(())
+    }
+}
+
+impl<I: Source> Iterator for ChannelMix<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.frame.len() {
+            self.next_frame()?;
+        }
+
+        let sample = self.frame[self.index];
+        self.index += 1;
+        Some(sample)
+    }
+}
+
+impl<I: Source> Source for ChannelMix<I> {
+    fn current_span_len(&self) -> Option<usize> {
+        let buffered = self.frame.len() - self.index;
+        self.input.current_span_len().map(|len| len + buffered)
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.frame.clear();
+        self.index = 0;
+        self.input.try_seek(pos)
+    }
+}
@@ -1,25 +1,39 @@
-use std::{path::PathBuf, sync::Mutex};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
+#[cfg(any(test, feature = "fixtures"))]
+use crate::fixtures::Fixtures;
 use crate::{
     database::Credentials,
+    keyring,
     models::{
-        Album, AlbumSimple, ArtistPage, Favorites, Genre, Playlist, PlaylistSimple, SearchResults,
-        Track,
+        Album, AlbumSimple, ArtistPage, Composer, DeliveredFormat, DiscographySort, Favorites,
+        Genre, LabelPage, LinkedEntity, Playlist, PlaylistSimple, SearchResults, Track,
+        TrackRestriction,
         mapper::{
-            parse_album, parse_album_simple, parse_artist, parse_artist_page, parse_featured_album,
-            parse_genre, parse_playlist, parse_playlist_simple, parse_search_results, parse_track,
+            parse_album, parse_album_simple, parse_artist, parse_artist_page, parse_composer,
+            parse_featured_album, parse_genre, parse_label_page, parse_playlist,
+            parse_playlist_simple, parse_search_results, parse_track,
         },
     },
 };
 use futures::future::join_all;
 use moka::future::Cache;
+#[cfg(not(any(test, feature = "fixtures")))]
+use no_fixtures::Fixtures;
 use qobuz_player_client::{
     client::{
-        AudioQuality, FeaturedAlbumType, FeaturedGenreAlbumType, FeaturedPlaylistType, OAuthResult,
-        ReleaseType, browser_oauth_login,
+        ApiHealth, AudioQuality, FeaturedAlbumType, FeaturedGenreAlbumType, FeaturedPlaylistType,
+        OAuthResult, ReleaseSort, ReleaseType, browser_oauth_login,
     },
-    qobuz_models::TrackInfo,
-    stream::flac_source_stream::SeekableStreamReader,
+    qobuz_models::{TrackInfo, UrlType, parse_url},
+    stream::{cache_encryption::CacheKey, flac_source_stream::SeekableStreamReader},
 };
 use time::Duration;
 use tokio::{
@@ -27,18 +41,35 @@ use tokio::{
     try_join,
 };
 
-use crate::{AppResult, error::Error, simple_cache::SimpleCache};
+use crate::{
+    AppResult,
+    error::{Error, ErrorKind},
+    simple_cache::SimpleCache,
+};
 
+pub use qobuz_player_client::client::ApiHealthState;
 pub use qobuz_player_client::client::exchange_oauth_code;
 pub use qobuz_player_client::client::get_app_id;
 
 type QobuzClient = qobuz_player_client::client::Client;
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// Number of results of each category in a search page
+pub const SEARCH_PAGE_SIZE: usize = 20;
+
+/// Number of results of each category suggested while typing a search
+pub const SUGGESTION_LIMIT: usize = 3;
+
+/// Releases of each type fetched when looking for new releases of an artist
+const LATEST_RELEASES_LIMIT: i32 = 10;
+
 pub struct Client {
     qobuz_client: OnceCell<RwLock<QobuzClient>>,
     credentials: Mutex<Option<Credentials>>,
-    max_audio_quality: AudioQuality,
+    api_health: ApiHealth,
+    max_audio_quality: parking_lot::Mutex<AudioQuality>,
+    data_saver: AtomicBool,
+    play_previews: AtomicBool,
     favorites_cache: SimpleCache<Favorites>,
     featured_albums_cache: SimpleCache<Vec<(String, Vec<AlbumSimple>)>>,
     featured_playlists_cache: SimpleCache<Vec<(String, Vec<Playlist>)>>,
@@ -46,15 +77,27 @@ pub struct Client {
     genre_albums_cache: Cache<u32, Vec<(String, Vec<AlbumSimple>)>>,
     genre_playlists_cache: Cache<u32, Vec<PlaylistSimple>>,
     album_cache: Cache<String, Album>,
-    artist_cache: Cache<u32, ArtistPage>,
+    artist_cache: Cache<(u32, DiscographySort), ArtistPage>,
+    composer_cache: Cache<u32, Composer>,
+    label_cache: Cache<u32, LabelPage>,
     playlist_cache: Cache<u32, Playlist>,
     suggested_albums_cache: Cache<String, Vec<AlbumSimple>>,
-    search_cache: Cache<String, SearchResults>,
+    search_cache: Cache<(String, usize), SearchResults>,
+    /// Restrictions found out when streaming tracks, by track id
+    restrictions: moka::sync::Cache<u32, TrackRestriction>,
+    /// Formats tracks were last streamed in, by track id
+    delivered_formats: moka::sync::Cache<u32, String>,
+    /// Qualities asked for the next time a track is streamed, by track id
+    quality_overrides: parking_lot::Mutex<HashMap<u32, AudioQuality>>,
+    /// Encrypts the audio cache at rest when set
+    audio_cache_key: parking_lot::Mutex<Option<CacheKey>>,
+    /// Answers in place of the api in tests and without an account
+    fixtures: Option<Fixtures>,
 }
 
 impl Client {
     pub fn credentials_is_set(&self) -> AppResult<bool> {
-        Ok(self.credentials.lock()?.is_some())
+        Ok(self.fixtures.is_some() || self.credentials.lock()?.is_some())
     }
 
     pub fn set_credentials(&self, credentials: Credentials) -> AppResult<()> {
@@ -63,6 +106,117 @@ impl Client {
         Ok(())
     }
 
+    /// Whether a session with Qobuz has been established
+    pub fn logged_in(&self) -> bool {
+        self.fixtures.is_some() || self.qobuz_client.initialized()
+    }
+
+    /// Audio file of a track in the fixtures
+    pub fn fixture_track_path(&self, track_id: u32) -> Option<PathBuf> {
+        self.fixtures.as_ref()?.track_path(track_id)
+    }
+
+    pub fn api_health(&self) -> ApiHealthState {
+        self.api_health.state()
+    }
+
+    pub fn max_audio_quality(&self) -> AudioQuality {
+        *self.max_audio_quality.lock()
+    }
+
+    pub fn data_saver(&self) -> bool {
+        self.data_saver.load(Ordering::Relaxed)
+    }
+
+    /// Data saver streams in mp3 regardless of the max audio quality
+    pub async fn set_data_saver(&self, data_saver: bool) {
+        self.data_saver.store(data_saver, Ordering::Relaxed);
+        self.sync_streaming_audio_quality().await;
+    }
+
+    pub fn audio_cache_key(&self) -> Option<CacheKey> {
+        *self.audio_cache_key.lock()
+    }
+
+    /// Encrypts downloaded tracks with the key of the install, stored in the keyring
+    pub fn enable_audio_cache_encryption(&self) -> AppResult<()> {
+        *self.audio_cache_key.lock() = Some(keyring::audio_cache_key()?);
+        Ok(())
+    }
+
+    pub fn play_previews(&self) -> bool {
+        self.play_previews.load(Ordering::Relaxed)
+    }
+
+    /// Plays the preview clip of tracks only offered as a preview, instead of skipping them
+    pub fn set_play_previews(&self, play_previews: bool) {
+        self.play_previews.store(play_previews, Ordering::Relaxed);
+    }
+
+    fn streaming_audio_quality(&self) -> AudioQuality {
+        match self.data_saver() {
+            true => AudioQuality::Mp3,
+            false => self.max_audio_quality(),
+        }
+    }
+
+    async fn sync_streaming_audio_quality(&self) {
+        if let Some(client) = self.qobuz_client.get() {
+            client
+                .write()
+                .await
+                .set_max_audio_quality(self.streaming_audio_quality());
+        }
+    }
+
+    /// Affects future track urls. Cached entities are cleared as their availability depends on the quality
+    pub async fn set_max_audio_quality(&self, max_audio_quality: AudioQuality) {
+        *self.max_audio_quality.lock() = max_audio_quality;
+        self.sync_streaming_audio_quality().await;
+        self.clear_caches().await;
+    }
+
+    /// Signs in as another Qobuz user. Cached entities belong to the previous user and are dropped
+    pub async fn switch_account(&self, credentials: Credentials) -> Result<()> {
+        let previous = self.credentials.lock()?.replace(credentials);
+
+        if let Some(cell) = self.qobuz_client.get() {
+            match self.init_client().await {
+                Ok(client) => *cell.write().await = client,
+                Err(err) => {
+                    *self.credentials.lock()? = previous;
+                    return Err(err);
+                }
+            }
+        }
+
+        self.clear_caches().await;
+        Ok(())
+    }
+
+    /// Signs in again after the network changed, so requests do not wait on connections to
+    /// the previous network. The current session is kept when signing in fails
+    pub async fn refresh_session(&self) -> Result<()> {
+        if let Some(cell) = self.qobuz_client.get() {
+            *cell.write().await = self.init_client().await?;
+        }
+        Ok(())
+    }
+
+    async fn clear_caches(&self) {
+        self.favorites_cache.clear().await;
+        self.featured_albums_cache.clear().await;
+        self.featured_playlists_cache.clear().await;
+        self.genre_albums_cache.invalidate_all();
+        self.album_cache.invalidate_all();
+        self.artist_cache.invalidate_all();
+        self.composer_cache.invalidate_all();
+        self.label_cache.invalidate_all();
+        self.playlist_cache.invalidate_all();
+        self.suggested_albums_cache.invalidate_all();
+        self.search_cache.invalidate_all();
+    }
+
     pub async fn app_id(&self) -> AppResult<String> {
         let client = self.get_client().await?;
         Ok(client.app_id().to_string())
@@ -94,6 +248,14 @@ impl Client {
             .time_to_live(std::time::Duration::from_secs(60 * 60 * 24))
             .build();
 
+        let composer_cache = moka::future::CacheBuilder::new(1000)
+            .time_to_live(std::time::Duration::from_secs(60 * 60 * 24))
+            .build();
+
+        let label_cache = moka::future::CacheBuilder::new(1000)
+            .time_to_live(std::time::Duration::from_secs(60 * 60 * 24))
+            .build();
+
         let playlist_cache = moka::future::CacheBuilder::new(1000)
             .time_to_live(std::time::Duration::from_secs(60 * 60 * 24))
             .build();
@@ -119,7 +281,10 @@ impl Client {
         Self {
             qobuz_client: Default::default(),
             credentials,
-            max_audio_quality,
+            api_health: Default::default(),
+            max_audio_quality: parking_lot::Mutex::new(max_audio_quality),
+            data_saver: AtomicBool::new(false),
+            play_previews: AtomicBool::new(true),
             favorites_cache: SimpleCache::new(Duration::days(1)),
             featured_albums_cache: SimpleCache::new(Duration::days(1)),
             featured_playlists_cache: SimpleCache::new(Duration::days(1)),
@@ -128,13 +293,35 @@ impl Client {
             genre_playlists_cache,
             album_cache,
             artist_cache,
+            composer_cache,
+            label_cache,
             playlist_cache,
             suggested_albums_cache,
             search_cache,
+            restrictions: moka::sync::Cache::new(1000),
+            delivered_formats: moka::sync::Cache::new(1000),
+            quality_overrides: Default::default(),
+            audio_cache_key: Default::default(),
+            fixtures: None,
+        }
+    }
+
+    /// Serves the catalog of the fixtures without connecting to Qobuz
+    #[cfg(any(test, feature = "fixtures"))]
+    pub fn with_fixtures(fixtures: Fixtures) -> Self {
+        Self {
+            fixtures: Some(fixtures),
+            ..Self::new(None, AudioQuality::HIFI192)
         }
     }
 
     async fn init_client(&self) -> Result<QobuzClient> {
+        if self.fixtures.is_some() {
+            return Err(Error::Client {
+                message: "Not available with fixtures".to_string(),
+            });
+        }
+
         let credentials = self.credentials.lock()?.clone();
 
         let Some(credentials) = credentials else {
@@ -143,12 +330,16 @@ impl Client {
             });
         };
 
-        let client = QobuzClient::new(
+        let mut client = QobuzClient::new(
             &credentials.user_auth_token,
             credentials.user_id,
-            self.max_audio_quality,
+            self.streaming_audio_quality(),
         )
-        .await?;
+        .await
+        .inspect_err(|error| self.api_health.record_error(error.to_string()))?;
+
+        self.api_health.record_success();
+        client.set_health(self.api_health.clone());
 
         Ok(client)
     }
@@ -177,10 +368,79 @@ impl Client {
         Ok(cell.write().await)
     }
 
+    /// Streams the track in `quality` the next time it is played, if it is below the max audio
+    /// quality, like playing a single track in CD quality
+    pub fn override_quality(&self, track_id: u32, quality: AudioQuality) {
+        self.quality_overrides.lock().insert(track_id, quality);
+    }
+
+    /// Steps down in quality while Qobuz refuses the quality for the subscription of the user.
+    /// Previews, offered in place of tracks locked in the region of the user, are refused unless
+    /// previews are played
     pub async fn track_url(&self, track_id: u32) -> Result<TrackInfo> {
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.track_info(track_id);
+        }
+
+        let streaming = self.streaming_audio_quality();
+        let requested = match self.quality_overrides.lock().remove(&track_id) {
+            Some(quality) if (quality as i32) < streaming as i32 => quality,
+            _ => streaming,
+        };
+        let mut quality = requested;
         let mut client = self.get_client_mut().await?;
-        let info = client.track_url(track_id).await?;
-        Ok(info)
+        let info = loop {
+            match client.track_url_in_quality(track_id, quality).await {
+                // Refused in this quality. A missing track is not asked for again
+                Err(qobuz_player_client::Error::Unavailable { message }) => {
+                    let Some(lower) = quality.lower() else {
+                        tracing::info!("Track {track_id} is unavailable: {message}");
+                        return Err(Error::ContentUnavailable);
+                    };
+                    tracing::info!(
+                        "Track {track_id} is refused in {}, trying {}",
+                        quality.label(),
+                        lower.label()
+                    );
+                    quality = lower;
+                }
+                result => break result?,
+            }
+        };
+        drop(client);
+
+        let restriction = track_restriction(&info, requested, quality, self.play_previews());
+        match restriction {
+            Some(restriction) => self.restrictions.insert(track_id, restriction),
+            None => self.restrictions.invalidate(&track_id),
+        };
+
+        self.delivered_formats.insert(
+            track_id,
+            DeliveredFormat::new(&info.mime_type, info.bit_depth, info.sampling_rate).to_string(),
+        );
+
+        match restriction {
+            Some(TrackRestriction::Region) => Err(Error::RegionLocked),
+            _ => Ok(info),
+        }
+    }
+
+    /// Restriction found out the last time the track was streamed
+    pub fn restriction(&self, track_id: u32) -> Option<TrackRestriction> {
+        self.restrictions.get(&track_id)
+    }
+
+    /// Format the track was last streamed in
+    pub fn delivered_format(&self, track_id: u32) -> Option<String> {
+        self.delivered_formats.get(&track_id)
+    }
+
+    fn apply_streaming_details(&self, tracks: &mut [Track]) {
+        for track in tracks {
+            track.restriction = self.restrictions.get(&track.id);
+            track.delivered_format = self.delivered_formats.get(&track.id);
+        }
     }
 
     pub async fn stream_track(
@@ -189,18 +449,30 @@ impl Client {
         track_info: TrackInfo,
     ) -> Result<SeekableStreamReader> {
         let mut client = self.get_client_mut().await?;
-        let stream = client.stream_track(track_info, cache_path).await?;
+        let stream = client
+            .stream_track(track_info, cache_path, self.audio_cache_key())
+            .await?;
         Ok(stream)
     }
 
     pub async fn album(&self, id: &str) -> Result<Album> {
+        let mut album = self.catalog_album(id).await?;
+        self.apply_streaming_details(&mut album.tracks);
+        Ok(album)
+    }
+
+    async fn catalog_album(&self, id: &str) -> Result<Album> {
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.album(id);
+        }
+
         if let Some(cache) = self.album_cache.get(id).await {
             return Ok(cache);
         }
 
         let client = self.get_client().await?;
         let album = client.album(id).await?;
-        let album = parse_album(album, &self.max_audio_quality);
+        let album = parse_album(album, &self.max_audio_quality());
 
         self.album_cache.insert(id.to_string(), album.clone()).await;
 
@@ -208,56 +480,126 @@ impl Client {
     }
 
     pub async fn search(&self, query: String) -> Result<SearchResults> {
-        if let Some(cache) = self.search_cache.get(&query).await {
+        self.search_page(query, 0).await
+    }
+
+    /// Results of each category starting at `offset`, [`SEARCH_PAGE_SIZE`] at a time
+    pub async fn search_page(&self, query: String, offset: usize) -> Result<SearchResults> {
+        if let Some(fixtures) = &self.fixtures {
+            return Ok(match offset {
+                0 => fixtures.search(&query),
+                _ => SearchResults {
+                    query,
+                    ..Default::default()
+                },
+            });
+        }
+
+        let key = (query, offset);
+        if let Some(cache) = self.search_cache.get(&key).await {
             return Ok(cache);
         }
 
         let client = self.get_client().await?;
-        let results = client.search_all(&query, 20).await?;
+        let results = client
+            .search_all(&key.0, SEARCH_PAGE_SIZE as i32, offset as i32)
+            .await?;
         let user_id = self.get_client().await?.user_id();
 
-        let out = parse_search_results(results, user_id, &self.max_audio_quality);
+        let out = parse_search_results(results, user_id, &self.max_audio_quality());
 
-        self.search_cache.insert(query, out.clone()).await;
+        self.search_cache.insert(key, out.clone()).await;
         Ok(out)
     }
 
+    /// Top [`SUGGESTION_LIMIT`] results of each category, for suggestions while typing.
+    /// Not cached, every keystroke is a new query
+    pub async fn suggestions(&self, query: String) -> Result<SearchResults> {
+        let results = match &self.fixtures {
+            Some(fixtures) => fixtures.search(&query),
+            None => match self.search_cache.get(&(query.clone(), 0)).await {
+                Some(cache) => cache,
+                None => {
+                    let client = self.get_client().await?;
+                    let results = client
+                        .search_all(&query, SUGGESTION_LIMIT as i32, 0)
+                        .await?;
+                    let user_id = client.user_id();
+
+                    parse_search_results(results, user_id, &self.max_audio_quality())
+                }
+            },
+        };
+
+        Ok(SearchResults {
+            albums: results.albums.into_iter().take(SUGGESTION_LIMIT).collect(),
+            artists: results.artists.into_iter().take(SUGGESTION_LIMIT).collect(),
+            playlists: results
+                .playlists
+                .into_iter()
+                .take(SUGGESTION_LIMIT)
+                .collect(),
+            tracks: results.tracks.into_iter().take(SUGGESTION_LIMIT).collect(),
+            composers: results
+                .composers
+                .into_iter()
+                .take(SUGGESTION_LIMIT)
+                .collect(),
+            ..results
+        })
+    }
+
     pub async fn artist_page(&self, id: u32) -> Result<ArtistPage> {
-        if let Some(cache) = self.artist_cache.get(&id).await {
+        self.artist_page_sorted(id, DiscographySort::default())
+            .await
+    }
+
+    pub async fn artist_page_sorted(&self, id: u32, sort: DiscographySort) -> Result<ArtistPage> {
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.artist_page(id);
+        }
+
+        if let Some(cache) = self.artist_cache.get(&(id, sort)).await {
             return Ok(cache);
         }
 
         let client = self.get_client().await?;
 
+        // Qobuz has no title order, the releases are sorted after fetching them instead
+        let release_sort = || match sort {
+            DiscographySort::Popularity => ReleaseSort::Relevance,
+            DiscographySort::Year | DiscographySort::Title => ReleaseSort::ReleaseDate,
+        };
+
         let (artist, albums, singles, live, compilations, similar_artists) = try_join!(
             client.artist(id),
-            client.artist_releases(id, ReleaseType::Albums, None),
-            client.artist_releases(id, ReleaseType::EPsAndSingles, None),
-            client.artist_releases(id, ReleaseType::Live, None),
-            client.artist_releases(id, ReleaseType::Compilations, None),
+            client.artist_releases(id, ReleaseType::Albums, release_sort(), None),
+            client.artist_releases(id, ReleaseType::EPsAndSingles, release_sort(), None),
+            client.artist_releases(id, ReleaseType::Live, release_sort(), None),
+            client.artist_releases(id, ReleaseType::Compilations, release_sort(), None),
             client.similar_artists(id, None),
         )?;
 
-        let artist = parse_artist_page(
+        let mut artist = parse_artist_page(
             artist,
             albums
                 .items
                 .into_iter()
-                .map(|x| parse_album_simple(x, &self.max_audio_quality))
+                .map(|x| parse_album_simple(x, &self.max_audio_quality()))
                 .collect(),
             singles
                 .items
                 .into_iter()
-                .map(|x| parse_album_simple(x, &self.max_audio_quality))
+                .map(|x| parse_album_simple(x, &self.max_audio_quality()))
                 .collect(),
             live.items
                 .into_iter()
-                .map(|x| parse_album_simple(x, &self.max_audio_quality))
+                .map(|x| parse_album_simple(x, &self.max_audio_quality()))
                 .collect(),
             compilations
                 .items
                 .into_iter()
-                .map(|x| parse_album_simple(x, &self.max_audio_quality))
+                .map(|x| parse_album_simple(x, &self.max_audio_quality()))
                 .collect(),
             similar_artists
                 .artists
@@ -267,10 +609,77 @@ impl Client {
                 .collect(),
         );
 
-        self.artist_cache.insert(id, artist.clone()).await;
+        if sort == DiscographySort::Title {
+            for releases in [
+                &mut artist.albums,
+                &mut artist.singles,
+                &mut artist.live,
+                &mut artist.compilations,
+            ] {
+                releases.sort_by_cached_key(|album| album.title.to_lowercase());
+            }
+        }
+
+        self.artist_cache.insert((id, sort), artist.clone()).await;
         Ok(artist)
     }
 
+    /// Newest releases of every type of an artist. Skips the artist page cache, so a new
+    /// release is found as soon as Qobuz has it
+    pub async fn latest_releases(&self, artist_id: u32) -> Result<Vec<AlbumSimple>> {
+        if let Some(fixtures) = &self.fixtures {
+            let page = fixtures.artist_page(artist_id)?;
+            return Ok([page.albums, page.singles, page.live, page.compilations].concat());
+        }
+
+        let client = self.get_client().await?;
+        let releases = |release_type| {
+            client.artist_releases(
+                artist_id,
+                release_type,
+                ReleaseSort::ReleaseDate,
+                Some(LATEST_RELEASES_LIMIT),
+            )
+        };
+
+        let (albums, singles, live, compilations) = try_join!(
+            releases(ReleaseType::Albums),
+            releases(ReleaseType::EPsAndSingles),
+            releases(ReleaseType::Live),
+            releases(ReleaseType::Compilations),
+        )?;
+
+        Ok([albums, singles, live, compilations]
+            .into_iter()
+            .flat_map(|releases| releases.items)
+            .map(|release| parse_album_simple(release, &self.max_audio_quality()))
+            .collect())
+    }
+
+    pub async fn composer(&self, id: u32) -> Result<Composer> {
+        if let Some(cache) = self.composer_cache.get(&id).await {
+            return Ok(cache);
+        }
+
+        let client = self.get_client().await?;
+        let composer = parse_composer(client.composer(id).await?, &self.max_audio_quality());
+
+        self.composer_cache.insert(id, composer.clone()).await;
+        Ok(composer)
+    }
+
+    pub async fn label(&self, id: u32) -> Result<LabelPage> {
+        if let Some(cache) = self.label_cache.get(&id).await {
+            return Ok(cache);
+        }
+
+        let client = self.get_client().await?;
+        let label = parse_label_page(client.label(id).await?, &self.max_audio_quality());
+
+        self.label_cache.insert(id, label.clone()).await;
+        Ok(label)
+    }
+
     pub async fn tracks(&self, ids: Vec<u32>) -> Result<Vec<Track>> {
         let futures = ids.into_iter().map(|id| self.track(id));
         let results = join_all(futures).await;
@@ -278,13 +687,89 @@ impl Client {
     }
 
     pub async fn track(&self, id: u32) -> Result<Track> {
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.track(id);
+        }
+
         let client = self.get_client().await?;
         let track = client.track(id).await?;
-        let track = parse_track(track, &self.max_audio_quality);
+        let track = parse_track(track, &self.max_audio_quality());
         Ok(track)
     }
 
+    pub async fn resolve_url(&self, url: &str) -> Result<LinkedEntity> {
+        let url_type = parse_url(url.trim()).map_err(|err| Error::InvalidLink {
+            message: err.to_string(),
+        })?;
+
+        match url_type {
+            UrlType::Album { id } => Ok(LinkedEntity::Album(self.album(&id).await?)),
+            UrlType::Playlist { id } => Ok(LinkedEntity::Playlist(self.playlist(id as u32).await?)),
+            UrlType::Track { id } => Ok(LinkedEntity::Track(self.track(id as u32).await?)),
+            UrlType::Artist { .. } => Err(Error::InvalidLink {
+                message: "Artist links can not be played".into(),
+            }),
+        }
+    }
+
+    /// Qobuz url, `album:<id>`, `track:<id>`, `playlist:<id>` or a bare id, which is tried
+    /// as an album, then as a track and last as a playlist
+    pub async fn resolve_link(&self, link: &str) -> Result<LinkedEntity> {
+        let link = link.trim();
+        if link.contains("://") {
+            return self.resolve_url(link).await;
+        }
+
+        let invalid = || Error::InvalidLink {
+            message: format!("{link} is not a Qobuz url or id"),
+        };
+
+        match link.split_once(':') {
+            Some(("album", id)) => Ok(LinkedEntity::Album(self.album(id).await?)),
+            Some(("track", id)) => {
+                let id = id.parse().map_err(|_| invalid())?;
+                Ok(LinkedEntity::Track(self.track(id).await?))
+            }
+            Some(("playlist", id)) => {
+                let id = id.parse().map_err(|_| invalid())?;
+                Ok(LinkedEntity::Playlist(self.playlist(id).await?))
+            }
+            Some(_) => Err(invalid()),
+            None => {
+                // Only a missing album or track moves on to the next guess
+                let unreachable = |err: &Error| {
+                    matches!(err.kind(), ErrorKind::Network | ErrorKind::Authorization)
+                };
+
+                match self.album(link).await {
+                    Ok(album) => return Ok(LinkedEntity::Album(album)),
+                    Err(err) if unreachable(&err) => return Err(err),
+                    Err(_) => {}
+                }
+
+                let id: u32 = link.parse().map_err(|_| invalid())?;
+                match self.track(id).await {
+                    Ok(track) => return Ok(LinkedEntity::Track(track)),
+                    Err(err) if unreachable(&err) => return Err(err),
+                    Err(_) => {}
+                }
+
+                match self.playlist(id).await {
+                    Ok(playlist) => Ok(LinkedEntity::Playlist(playlist)),
+                    Err(err) if unreachable(&err) => Err(err),
+                    Err(_) => Err(Error::InvalidLink {
+                        message: format!("No album, track or playlist with id {link}"),
+                    }),
+                }
+            }
+        }
+    }
+
     pub async fn suggested_albums(&self, id: &str) -> Result<Vec<AlbumSimple>> {
+        if self.fixtures.is_some() {
+            return Ok(vec![]);
+        }
+
         if let Some(cache) = self.suggested_albums_cache.get(id).await {
             return Ok(cache);
         }
@@ -295,7 +780,7 @@ impl Client {
             .albums
             .items
             .into_iter()
-            .map(|x| parse_album_simple(x, &self.max_audio_quality))
+            .map(|x| parse_album_simple(x, &self.max_audio_quality()))
             .collect();
 
         self.suggested_albums_cache
@@ -306,6 +791,10 @@ impl Client {
     }
 
     pub async fn featured_albums(&self) -> Result<Vec<(String, Vec<AlbumSimple>)>> {
+        if self.fixtures.is_some() {
+            return Ok(vec![]);
+        }
+
         if let Some(cache) = self.featured_albums_cache.get().await {
             return Ok(cache);
         }
@@ -381,6 +870,10 @@ impl Client {
     }
 
     pub async fn featured_playlists(&self) -> Result<Vec<(String, Vec<Playlist>)>> {
+        if self.fixtures.is_some() {
+            return Ok(vec![]);
+        }
+
         if let Some(cache) = self.featured_playlists_cache.get().await {
             return Ok(cache);
         }
@@ -392,7 +885,7 @@ impl Client {
             .playlists
             .items
             .into_iter()
-            .map(|x| parse_playlist(x, client.user_id(), &self.max_audio_quality))
+            .map(|x| parse_playlist(x, client.user_id(), &self.max_audio_quality()))
             .collect();
 
         let playlists = vec![("Editor picks".to_string(), editor_picks)];
@@ -403,13 +896,23 @@ impl Client {
     }
 
     pub async fn playlist(&self, id: u32) -> Result<Playlist> {
+        let mut playlist = self.catalog_playlist(id).await?;
+        self.apply_streaming_details(&mut playlist.tracks);
+        Ok(playlist)
+    }
+
+    async fn catalog_playlist(&self, id: u32) -> Result<Playlist> {
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.playlist(id);
+        }
+
         if let Some(cache) = self.playlist_cache.get(&id).await {
             return Ok(cache);
         }
 
         let client = self.get_client().await?;
         let playlist = client.playlist(id).await?;
-        let playlist = parse_playlist(playlist, client.user_id(), &self.max_audio_quality);
+        let playlist = parse_playlist(playlist, client.user_id(), &self.max_audio_quality());
 
         self.playlist_cache.insert(id, playlist.clone()).await;
         Ok(playlist)
@@ -418,17 +921,59 @@ impl Client {
     pub async fn add_favorite_track(&self, id: u32) -> Result<()> {
         let client = self.get_client().await?;
         client.add_favorite_track(id).await?;
-        self.favorites_cache.clear().await;
+
+        match (self.favorites_cache.get_stale().await, self.track(id).await) {
+            (Some(mut cache), Ok(track)) => {
+                if !cache.tracks.iter().any(|track| track.id == id) {
+                    cache.tracks.push(track);
+                    cache.tracks.sort_by_key(|a| a.title.to_lowercase());
+                }
+                self.favorites_cache.set(cache).await;
+            }
+            _ => self.favorites_cache.clear().await,
+        }
         Ok(())
     }
 
     pub async fn remove_favorite_track(&self, id: u32) -> Result<()> {
         let client = self.get_client().await?;
         client.remove_favorite_track(id).await?;
-        self.favorites_cache.clear().await;
+
+        match self.favorites_cache.get_stale().await {
+            Some(mut cache) => {
+                cache.tracks.retain(|track| track.id != id);
+                self.favorites_cache.set(cache).await;
+            }
+            None => self.favorites_cache.clear().await,
+        }
         Ok(())
     }
 
+    /// Looks only at the cached favorites, so a track is not a favorite until they are loaded
+    pub fn is_favorite_track(&self, id: u32) -> bool {
+        self.favorites_cache
+            .inspect(|favorites| favorites.tracks.iter().any(|track| track.id == id))
+            .unwrap_or(false)
+    }
+
+    /// Adds the track to the favorites, or removes it if it is one. Returns whether it is a
+    /// favorite afterwards
+    pub async fn toggle_favorite_track(&self, id: u32) -> Result<bool> {
+        let is_favorite = self
+            .favorites()
+            .await?
+            .tracks
+            .iter()
+            .any(|track| track.id == id);
+
+        if is_favorite {
+            self.remove_favorite_track(id).await?;
+        } else {
+            self.add_favorite_track(id).await?;
+        }
+        Ok(!is_favorite)
+    }
+
     pub async fn add_favorite_album(&self, id: &str) -> Result<()> {
         let client = self.get_client().await?;
         client.add_favorite_album(id).await?;
@@ -476,6 +1021,24 @@ impl Client {
             return Ok(cache);
         }
 
+        match self.refresh_favorites().await {
+            Ok(favorites) => Ok(favorites),
+            // Fall back to the last known favorites while the server can not be reached
+            Err(error) => self.favorites_cache.get_stale().await.ok_or(error),
+        }
+    }
+
+    /// Seeds the favorites cache, e.g. with favorites persisted by an earlier session
+    pub async fn set_cached_favorites(&self, favorites: Favorites) {
+        self.favorites_cache.set(favorites).await;
+    }
+
+    /// Fetches the favorites from the server and replaces the cached favorites
+    pub async fn refresh_favorites(&self) -> Result<Favorites> {
+        if let Some(fixtures) = &self.fixtures {
+            return Ok(fixtures.favorites());
+        }
+
         let client = self.get_client().await?;
 
         let favorites_result = client.favorites(1000).await?;
@@ -485,7 +1048,7 @@ impl Client {
             .albums
             .items
             .into_iter()
-            .map(|x| parse_album(x, &self.max_audio_quality).into())
+            .map(|x| parse_album(x, &self.max_audio_quality()).into())
             .collect();
 
         albums.sort_by(|a: &AlbumSimple, b| {
@@ -501,25 +1064,25 @@ impl Client {
             .into_iter()
             .map(parse_artist)
             .collect();
-        artists.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        artists.sort_by_key(|a| a.name.to_lowercase());
 
         let mut playlists: Vec<_> = user_playlists
             .playlists
             .items
             .into_iter()
-            .map(|x| parse_playlist(x, client.user_id(), &self.max_audio_quality))
+            .map(|x| parse_playlist(x, client.user_id(), &self.max_audio_quality()))
             .collect();
 
-        playlists.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+        playlists.sort_by_key(|a| a.title.to_lowercase());
 
         let mut tracks: Vec<_> = favorites_result
             .tracks
             .items
             .into_iter()
-            .map(|x| parse_track(x, &self.max_audio_quality))
+            .map(|x| parse_track(x, &self.max_audio_quality()))
             .collect();
 
-        tracks.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+        tracks.sort_by_key(|a| a.title.to_lowercase());
 
         let favorites = Favorites {
             albums,
@@ -543,7 +1106,7 @@ impl Client {
         let playlist = client
             .create_playlist(name, is_public, description, is_collaborative)
             .await?;
-        let playlist = parse_playlist(playlist, client.user_id(), &self.max_audio_quality);
+        let playlist = parse_playlist(playlist, client.user_id(), &self.max_audio_quality());
         let cache = self.favorites_cache.get().await;
 
         if let Some(mut cache) = cache {
@@ -610,6 +1173,10 @@ impl Client {
     }
 
     pub async fn genres(&self) -> Result<Vec<Genre>> {
+        if self.fixtures.is_some() {
+            return Ok(vec![]);
+        }
+
         if let Some(cache) = self.genres_cache.get().await {
             return Ok(cache);
         }
@@ -720,3 +1287,151 @@ impl Client {
         Ok(playlists)
     }
 }
+
+/// Stands in for the fixtures in builds without them. It has no values, so the fixture
+/// branches of the client are never taken
+#[cfg(not(any(test, feature = "fixtures")))]
+mod no_fixtures {
+    use std::path::PathBuf;
+
+    use qobuz_player_client::qobuz_models::TrackInfo;
+
+    use crate::{
+        AppResult,
+        models::{Album, ArtistPage, Favorites, Playlist, SearchResults, Track},
+    };
+
+    pub(super) enum Fixtures {}
+
+    impl Fixtures {
+        pub fn album(&self, _id: &str) -> AppResult<Album> {
+            match *self {}
+        }
+
+        pub fn playlist(&self, _id: u32) -> AppResult<Playlist> {
+            match *self {}
+        }
+
+        pub fn track(&self, _id: u32) -> AppResult<Track> {
+            match *self {}
+        }
+
+        pub fn artist_page(&self, _id: u32) -> AppResult<ArtistPage> {
+            match *self {}
+        }
+
+        pub fn search(&self, _query: &str) -> SearchResults {
+            match *self {}
+        }
+
+        pub fn favorites(&self) -> Favorites {
+            match *self {}
+        }
+
+        pub fn track_path(&self, _id: u32) -> Option<PathBuf> {
+            match *self {}
+        }
+
+        pub fn track_info(&self, _id: u32) -> AppResult<TrackInfo> {
+            match *self {}
+        }
+    }
+}
+
+/// Qobuz delivers a lower format than `requested` when it may not stream it, with the reasons
+/// in `restrictions`. A release that is not offered in the quality is not a restriction.
+/// `streamed` is the quality asked for after the refused ones
+fn track_restriction(
+    info: &TrackInfo,
+    requested: AudioQuality,
+    streamed: AudioQuality,
+    play_previews: bool,
+) -> Option<TrackRestriction> {
+    if info.sample {
+        return Some(match play_previews {
+            true => TrackRestriction::Preview,
+            false => TrackRestriction::Region,
+        });
+    }
+
+    let refused = streamed as i64 != requested as i64;
+    let delivered_lower = info
+        .format_id
+        .is_some_and(|format_id| format_id < requested as i64);
+    let not_offered = !info.restrictions.is_empty()
+        && info
+            .restrictions
+            .iter()
+            .all(|restriction| restriction.code == "FormatRestrictedByFormatAvailability");
+
+    (refused || (delivered_lower && !not_offered)).then_some(TrackRestriction::LowerQuality)
+}
+
+#[cfg(test)]
+mod tests {
+    use qobuz_player_client::{
+        client::AudioQuality,
+        qobuz_models::{Restriction, TrackInfo},
+    };
+
+    use super::track_restriction;
+    use crate::models::TrackRestriction;
+
+    fn info(format_id: i64, restrictions: &[&str]) -> TrackInfo {
+        TrackInfo {
+            format_id: Some(format_id),
+            restrictions: restrictions
+                .iter()
+                .map(|code| Restriction {
+                    code: code.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn delivered_format_is_compared_with_the_requested_one() {
+        let hires = AudioQuality::HIFI192;
+        assert_eq!(track_restriction(&info(27, &[]), hires, hires, false), None);
+        assert_eq!(
+            track_restriction(
+                &info(6, &["FormatRestrictedByUserRights"]),
+                hires,
+                hires,
+                false
+            ),
+            Some(TrackRestriction::LowerQuality)
+        );
+        assert_eq!(
+            track_restriction(
+                &info(6, &["FormatRestrictedByFormatAvailability"]),
+                hires,
+                hires,
+                false
+            ),
+            None
+        );
+        assert_eq!(
+            track_restriction(&info(6, &[]), hires, AudioQuality::CD, false),
+            Some(TrackRestriction::LowerQuality)
+        );
+    }
+
+    #[test]
+    fn previews_are_restricted_to_the_region() {
+        let preview = TrackInfo {
+            sample: true,
+            ..info(5, &["TrackRestrictedByPurchaseCredentials"])
+        };
+        let cd = AudioQuality::CD;
+        assert_eq!(
+            track_restriction(&preview, cd, cd, false),
+            Some(TrackRestriction::Region)
+        );
+        assert_eq!(
+            track_restriction(&preview, cd, cd, true),
+            Some(TrackRestriction::Preview)
+        );
+    }
+}
@@ -1,5 +1,10 @@
 use std::time::Duration;
 
+use crate::{
+    AppResult, AudioQuality, error::Error, models::LinkedEntity, quiet_hours::QuietHours,
+    tracklist::TracklistType,
+};
+
 #[derive(Debug)]
 pub enum ControlCommand {
     Album {
@@ -22,6 +27,16 @@ pub enum ControlCommand {
     Track {
         id: u32,
     },
+    /// Plays the track once in a quality below the max audio quality
+    TrackInQuality {
+        id: u32,
+        quality: AudioQuality,
+    },
+    /// Plays the track from `position`, like resuming a long mix
+    PlayTrackAt {
+        id: u32,
+        position: Duration,
+    },
     SkipToPosition {
         new_position: usize,
         force: bool,
@@ -33,12 +48,51 @@ pub enum ControlCommand {
     Pause,
     JumpForward,
     JumpBackward,
+    JumpBy {
+        offset_us: i64,
+    },
     Seek {
         time: Duration,
     },
+    SeekPercentage {
+        percentage: f32,
+    },
     SetVolume {
         volume: f32,
     },
+    SetMute {
+        muted: bool,
+    },
+    ToggleMute,
+    SetQuietHours {
+        quiet_hours: Option<QuietHours>,
+    },
+    SetBalance {
+        balance: f32,
+    },
+    SetMonoDownmix {
+        mono_downmix: bool,
+    },
+    SetTrimSilence {
+        enabled: bool,
+    },
+    SetMaxAudioQuality {
+        quality: AudioQuality,
+    },
+    SetDataSaver {
+        enabled: bool,
+    },
+    SetPlayPreviews {
+        enabled: bool,
+    },
+    SetZoneVolume {
+        zone: usize,
+        volume: f32,
+    },
+    SetZoneEnabled {
+        zone: usize,
+        enabled: bool,
+    },
     AddTracksToQueue {
         ids: Vec<u32>,
     },
@@ -55,7 +109,22 @@ pub enum ControlCommand {
         items: Vec<NewQueueItem>,
         play: bool,
     },
+    /// Insert items keeping their queue ids. Appended when `index` is `None`
+    InsertQueueItems {
+        items: Vec<NewQueueItem>,
+        index: Option<usize>,
+    },
+    RemoveQueueItems {
+        queue_ids: Vec<u64>,
+    },
     ClearQueue,
+    PrecacheAlbum {
+        id: String,
+    },
+    /// The network changed, the current track is streamed again if it is still downloading
+    RefreshStream,
+    /// The ban list changed, banned tracks leave the queue
+    ApplyBans,
 }
 
 #[derive(Debug, Clone)]
@@ -103,12 +172,63 @@ impl Controls {
             .expect("infallible");
     }
 
+    pub fn play_tracklist_type(&self, list_type: &TracklistType) {
+        match list_type {
+            TracklistType::Album(album) => self.play_album(&album.id, 0),
+            TracklistType::Playlist(playlist) => self.play_playlist(playlist.id, 0, false),
+            TracklistType::TopTracks(top_tracks) => self.play_top_tracks(top_tracks.id, 0),
+            TracklistType::Tracks => {}
+        }
+    }
+
+    pub fn play_linked_entity(&self, entity: &LinkedEntity) {
+        match entity {
+            LinkedEntity::Album(album) => self.play_album(&album.id, 0),
+            LinkedEntity::Playlist(playlist) => self.play_playlist(playlist.id, 0, false),
+            LinkedEntity::Track(track) => self.play_track(track.id),
+        }
+    }
+
+    /// Starts at `position` when given, which only tracks can
+    pub fn play_linked_entity_at(
+        &self,
+        entity: &LinkedEntity,
+        position: Option<Duration>,
+    ) -> AppResult<()> {
+        match (entity, position) {
+            (_, None) => self.play_linked_entity(entity),
+            (LinkedEntity::Track(track), Some(position)) => self.play_track_at(track.id, position),
+            (_, Some(_)) => {
+                return Err(Error::InvalidLink {
+                    message: format!(
+                        "{} is not a track, only tracks start at a position",
+                        entity.title()
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn play_track(&self, id: u32) {
         self.tx
             .send(ControlCommand::Track { id })
             .expect("infallible");
     }
 
+    pub fn play_track_in_quality(&self, id: u32, quality: AudioQuality) {
+        self.tx
+            .send(ControlCommand::TrackInQuality { id, quality })
+            .expect("infallible");
+    }
+
+    pub fn play_track_at(&self, id: u32, position: Duration) {
+        self.tx
+            .send(ControlCommand::PlayTrackAt { id, position })
+            .expect("infallible");
+    }
+
     pub fn play_tracks(&self, ids: Vec<u32>, shuffle: bool) {
         self.tx
             .send(ControlCommand::Tracks { ids, shuffle })
@@ -154,6 +274,76 @@ impl Controls {
             .expect("infallible");
     }
 
+    pub fn set_mute(&self, muted: bool) {
+        self.tx
+            .send(ControlCommand::SetMute { muted })
+            .expect("infallible");
+    }
+
+    pub fn toggle_mute(&self) {
+        self.tx
+            .send(ControlCommand::ToggleMute)
+            .expect("infallible");
+    }
+
+    /// `None` turns quiet hours off
+    pub fn set_quiet_hours(&self, quiet_hours: Option<QuietHours>) {
+        self.tx
+            .send(ControlCommand::SetQuietHours { quiet_hours })
+            .expect("infallible");
+    }
+
+    /// -1.0 is fully left and 1.0 is fully right
+    pub fn set_balance(&self, balance: f32) {
+        self.tx
+            .send(ControlCommand::SetBalance { balance })
+            .expect("infallible");
+    }
+
+    pub fn set_mono_downmix(&self, mono_downmix: bool) {
+        self.tx
+            .send(ControlCommand::SetMonoDownmix { mono_downmix })
+            .expect("infallible");
+    }
+
+    /// Applies from the next track that is queued
+    pub fn set_trim_silence(&self, enabled: bool) {
+        self.tx
+            .send(ControlCommand::SetTrimSilence { enabled })
+            .expect("infallible");
+    }
+
+    pub fn set_max_audio_quality(&self, quality: AudioQuality) {
+        self.tx
+            .send(ControlCommand::SetMaxAudioQuality { quality })
+            .expect("infallible");
+    }
+
+    pub fn set_data_saver(&self, enabled: bool) {
+        self.tx
+            .send(ControlCommand::SetDataSaver { enabled })
+            .expect("infallible");
+    }
+
+    pub fn set_play_previews(&self, enabled: bool) {
+        self.tx
+            .send(ControlCommand::SetPlayPreviews { enabled })
+            .expect("infallible");
+    }
+
+    /// Volume of one output relative to the player volume, from 0.0 to 1.0
+    pub fn set_zone_volume(&self, zone: usize, volume: f32) {
+        self.tx
+            .send(ControlCommand::SetZoneVolume { zone, volume })
+            .expect("infallible");
+    }
+
+    pub fn set_zone_enabled(&self, zone: usize, enabled: bool) {
+        self.tx
+            .send(ControlCommand::SetZoneEnabled { zone, enabled })
+            .expect("infallible");
+    }
+
     pub fn seek(&self, time: Duration) {
         self.tx
             .send(ControlCommand::Seek { time })
@@ -172,6 +362,19 @@ impl Controls {
             .expect("infallible");
     }
 
+    /// Seeks by a signed offset in microseconds, like the mpris seek
+    pub fn jump_by(&self, offset_us: i64) {
+        self.tx
+            .send(ControlCommand::JumpBy { offset_us })
+            .expect("infallible");
+    }
+
+    pub fn seek_percentage(&self, percentage: f32) {
+        self.tx
+            .send(ControlCommand::SeekPercentage { percentage })
+            .expect("infallible");
+    }
+
     pub fn reorder_queue(&self, new_order: Vec<usize>) {
         self.tx
             .send(ControlCommand::ReorderQueue { new_order })
@@ -184,11 +387,39 @@ impl Controls {
             .expect("infallible");
     }
 
+    pub fn insert_queue_items(&self, items: Vec<NewQueueItem>, index: Option<usize>) {
+        self.tx
+            .send(ControlCommand::InsertQueueItems { items, index })
+            .expect("infallible");
+    }
+
+    pub fn remove_queue_items(&self, queue_ids: Vec<u64>) {
+        self.tx
+            .send(ControlCommand::RemoveQueueItems { queue_ids })
+            .expect("infallible");
+    }
+
     pub fn clear_queue(&self) {
         self.tx
             .send(ControlCommand::ClearQueue)
             .expect("infallible");
     }
+
+    pub fn precache_album(&self, id: &str) {
+        self.tx
+            .send(ControlCommand::PrecacheAlbum { id: id.to_string() })
+            .expect("infallible");
+    }
+
+    pub fn refresh_stream(&self) {
+        self.tx
+            .send(ControlCommand::RefreshStream)
+            .expect("infallible");
+    }
+
+    pub fn apply_bans(&self) {
+        self.tx.send(ControlCommand::ApplyBans).expect("infallible");
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -196,3 +427,35 @@ pub struct NewQueueItem {
     pub track_id: u32,
     pub queue_id: u64,
 }
+
+/// Position in a track as seconds, `m:ss` or `h:mm:ss`, like `3:41`
+pub fn parse_position(text: &str) -> Option<Duration> {
+    let mut seconds: u64 = 0;
+    for (index, part) in text.trim().split(':').enumerate() {
+        if index > 2 {
+            return None;
+        }
+        let value: u64 = part.parse().ok()?;
+        if index > 0 && value >= 60 {
+            return None;
+        }
+        seconds = seconds * 60 + value;
+    }
+
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_positions() {
+        assert_eq!(parse_position("221"), Some(Duration::from_secs(221)));
+        assert_eq!(parse_position("3:41"), Some(Duration::from_secs(221)));
+        assert_eq!(parse_position("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_position("3:75"), None);
+        assert_eq!(parse_position("1:2:3:4"), None);
+        assert_eq!(parse_position("soon"), None);
+    }
+}
@@ -1,12 +1,28 @@
-use crate::{AppResult, AudioQuality, Error, Tracklist};
+use crate::{
+    AppResult, AudioQuality, Error, Tracklist, models::Favorites, quiet_hours::QuietHours,
+    tracklist::TracklistType, zones::Zone,
+};
+use parking_lot::RwLock;
 use qobuz_player_client::client::OAuthResult;
 use serde_json::to_string;
 use sqlx::types::Json;
 use sqlx::{Pool, Sqlite, SqlitePool, sqlite::SqliteConnectOptions};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+const MAX_RECENT_TRACKLISTS: i64 = 20;
+const MAX_SEARCH_HISTORY: i64 = 20;
+/// A search refining one made this recently replaces it, so searching while typing keeps
+/// only the finished query
+const SEARCH_REFINE_NANOS: i64 = 60_000_000_000;
+
+/// Profile used when none is given with `--profile`
+pub const DEFAULT_PROFILE: &str = "default";
+
 pub struct Database {
     pool: Pool<Sqlite>,
+    /// Credentials, configuration and favorites are read from and written to this profile
+    profile: RwLock<String>,
 }
 
 impl Database {
@@ -40,23 +56,58 @@ impl Database {
         Database::init(pool).await
     }
 
-    async fn init(pool: sqlx::Pool<sqlx::Sqlite>) -> AppResult<Self> {
+    pub(crate) async fn init(pool: sqlx::Pool<sqlx::Sqlite>) -> AppResult<Self> {
         sqlx::migrate!("./migrations").run(&pool).await?;
 
-        create_credentials_row(&pool).await?;
-        create_configuration(&pool).await?;
+        create_profile(&pool, DEFAULT_PROFILE).await?;
+
+        Ok(Self {
+            pool,
+            profile: RwLock::new(DEFAULT_PROFILE.to_string()),
+        })
+    }
+
+    pub fn profile(&self) -> String {
+        self.profile.read().clone()
+    }
+
+    /// Creates the profile when it does not exist yet
+    pub async fn set_profile(&self, name: &str) -> AppResult<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::Client {
+                message: "Profile name must not be empty".to_string(),
+            });
+        }
+
+        create_profile(&self.pool, name).await?;
+        *self.profile.write() = name.to_string();
+        Ok(())
+    }
 
-        Ok(Self { pool })
+    pub async fn get_profiles(&self) -> AppResult<Vec<Profile>> {
+        Ok(sqlx::query_as!(
+            Profile,
+            r#"
+            select profile as name, user_auth_token is not null as "logged_in: bool"
+            from credentials
+            order by profile
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?)
     }
 
     pub async fn set_credentials(&self, credentials: Credentials) -> AppResult<()> {
         let token = credentials.user_auth_token;
         let user_id = credentials.user_id;
+        let profile = self.profile();
 
         sqlx::query!(
-            "update credentials set user_auth_token = ?, user_id = ? where rowid = 1",
+            "update credentials set user_auth_token = ?, user_id = ? where profile = ?",
             token,
-            user_id
+            user_id,
+            profile
         )
         .execute(&self.pool)
         .await?;
@@ -64,9 +115,13 @@ impl Database {
     }
 
     pub async fn clear_user_auth_token(&self) -> AppResult<()> {
-        sqlx::query!("update credentials set user_auth_token = null where rowid = 1")
-            .execute(&self.pool)
-            .await?;
+        let profile = self.profile();
+        sqlx::query!(
+            "update credentials set user_auth_token = null where profile = ?",
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
@@ -95,6 +150,293 @@ impl Database {
         row.ok().map(|x| x.tracklist.0)
     }
 
+    pub async fn set_favorites(&self, favorites: &Favorites) -> AppResult<()> {
+        let serialized = to_string(&favorites)?;
+        let profile = self.profile();
+
+        sqlx::query!(
+            "insert or replace into favorites (favorites, profile) values (?1, ?2)",
+            serialized,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let track_ids: Vec<u32> = favorites.tracks.iter().map(|track| track.id).collect();
+        self.record_favorite_tracks(&track_ids).await?;
+
+        let album_ids: Vec<String> = favorites
+            .albums
+            .iter()
+            .map(|album| album.id.clone())
+            .collect();
+        self.record_favorite_albums(&album_ids).await
+    }
+
+    /// Remembers when favorite albums were first seen and forgets albums no longer
+    /// favorite. Albums seen by the first recording of the profile get no time, since
+    /// they were added at some unknown point before it
+    pub async fn record_favorite_albums(&self, album_ids: &[String]) -> AppResult<()> {
+        let profile = self.profile();
+        let mut transaction = self.pool.begin().await?;
+
+        let recorded_before = sqlx::query!(
+            "select profile from favorite_albums_recorded where profile = ?1",
+            profile
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .is_some();
+
+        let known: Vec<String> = sqlx::query!(
+            "select album_id from favorite_albums_added where profile = ?1",
+            profile
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .map(|row| row.album_id)
+        .collect();
+
+        let added_at = recorded_before.then(|| time::OffsetDateTime::now_utc().unix_timestamp());
+
+        for album_id in known.iter().filter(|id| !album_ids.contains(id)) {
+            sqlx::query!(
+                "delete from favorite_albums_added where profile = ?1 and album_id = ?2",
+                profile,
+                album_id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        for album_id in album_ids {
+            sqlx::query!(
+                "insert or ignore into favorite_albums_added (profile, album_id, added_at) values (?1, ?2, ?3)",
+                profile,
+                album_id,
+                added_at
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        sqlx::query!(
+            "insert or ignore into favorite_albums_recorded (profile) values (?1)",
+            profile
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// When each favorite album was first seen, for the albums with a known time
+    pub async fn favorite_albums_added(&self) -> AppResult<HashMap<String, time::OffsetDateTime>> {
+        let profile = self.profile();
+        let rows = sqlx::query!(
+            "select album_id, added_at from favorite_albums_added where profile = ?1",
+            profile
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let added_at = time::OffsetDateTime::from_unix_timestamp(row.added_at?).ok()?;
+                Some((row.album_id, added_at))
+            })
+            .collect())
+    }
+
+    /// Remembers when favorite tracks were first seen, for the recently favorited smart
+    /// playlist. Tracks seen before keep their time. Tracks seen by the first recording of
+    /// the profile get time 0, since they were added at some unknown point before it
+    pub async fn record_favorite_tracks(&self, track_ids: &[u32]) -> AppResult<()> {
+        let profile = self.profile();
+        let mut transaction = self.pool.begin().await?;
+
+        let recorded_before = sqlx::query!(
+            "select profile from favorite_tracks_recorded where profile = ?1",
+            profile
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .is_some();
+        let added_at = match recorded_before {
+            true => time::OffsetDateTime::now_utc().unix_timestamp(),
+            false => 0,
+        };
+
+        for track_id in track_ids {
+            sqlx::query!(
+                "insert or ignore into favorite_tracks_added (profile, track_id, added_at) values (?1, ?2, ?3)",
+                profile,
+                track_id,
+                added_at
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        sqlx::query!(
+            "insert or ignore into favorite_tracks_recorded (profile) values (?1)",
+            profile
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Tracks with a known time they were first seen as favorite, newest first
+    pub async fn recently_favorited_tracks(&self, limit: u32) -> AppResult<Vec<u32>> {
+        let profile = self.profile();
+        let rows = sqlx::query!(
+            "select track_id from favorite_tracks_added where profile = ?1 and added_at > 0 order by added_at desc, rowid desc limit ?2",
+            profile,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.track_id as u32).collect())
+    }
+
+    pub async fn add_track_play(&self, track_id: u32) -> AppResult<()> {
+        let profile = self.profile();
+        let now = time::OffsetDateTime::now_utc().unix_timestamp();
+
+        sqlx::query!(
+            "insert into play_history (profile, track_id, played_at) values (?1, ?2, ?3)",
+            profile,
+            track_id,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Tracks played most often since `since`, most played first
+    pub async fn most_played_tracks(
+        &self,
+        since: time::OffsetDateTime,
+        limit: u32,
+    ) -> AppResult<Vec<u32>> {
+        let profile = self.profile();
+        let since = since.unix_timestamp();
+        let rows = sqlx::query!(
+            r#"
+                select track_id from play_history
+                where profile = ?1 and played_at >= ?2
+                group by track_id
+                order by count(*) desc, max(played_at) desc
+                limit ?3
+            "#,
+            profile,
+            since,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.track_id as u32).collect())
+    }
+
+    /// When each track in the history was last played
+    pub async fn tracks_last_played(&self) -> AppResult<HashMap<u32, time::OffsetDateTime>> {
+        let profile = self.profile();
+        let rows = sqlx::query!(
+            r#"
+                select track_id, max(played_at) as "played_at!: i64" from play_history
+                where profile = ?1
+                group by track_id
+            "#,
+            profile
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let played_at = time::OffsetDateTime::from_unix_timestamp(row.played_at).ok()?;
+                Some((row.track_id as u32, played_at))
+            })
+            .collect())
+    }
+
+    pub async fn get_favorites(&self) -> Option<Favorites> {
+        let profile = self.profile();
+        let row = sqlx::query_as!(
+            FavoritesDb,
+            r#"SELECT favorites as "favorites: Json<Favorites>" FROM favorites WHERE profile = ?"#,
+            profile
+        )
+        .fetch_one(&self.pool)
+        .await;
+
+        row.ok().map(|x| x.favorites.0)
+    }
+
+    pub async fn add_recent_tracklist(&self, list_type: &TracklistType) -> AppResult<()> {
+        let id = match list_type {
+            TracklistType::Album(album) => format!("album:{}", album.id),
+            TracklistType::Playlist(playlist) => format!("playlist:{}", playlist.id),
+            TracklistType::TopTracks(top_tracks) => format!("top-tracks:{}", top_tracks.id),
+            TracklistType::Tracks => return Ok(()),
+        };
+
+        let serialized = to_string(list_type)?;
+        let now = time::OffsetDateTime::now_utc().unix_timestamp_nanos() as i64;
+
+        sqlx::query!(
+            r#"
+                insert into recent_tracklists (id, tracklist_type, last_played)
+                values (?1, ?2, ?3)
+                on conflict(id) do update set
+                    tracklist_type = excluded.tracklist_type,
+                    last_played = excluded.last_played
+            "#,
+            id,
+            serialized,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let max = MAX_RECENT_TRACKLISTS;
+        sqlx::query!(
+            r#"
+                delete from recent_tracklists
+                where id not in (
+                    select id from recent_tracklists order by last_played desc limit ?1
+                )
+            "#,
+            max
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_recent_tracklists(&self) -> AppResult<Vec<TracklistType>> {
+        let rows = sqlx::query_as!(
+            RecentTracklistDb,
+            r#"SELECT tracklist_type as "tracklist_type: Json<TracklistType>" FROM recent_tracklists ORDER BY last_played DESC"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|x| x.tracklist_type.0).collect())
+    }
+
     pub async fn set_volume(&self, volume: f32) -> AppResult<()> {
         sqlx::query!("delete from volume")
             .execute(&self.pool)
@@ -115,16 +457,47 @@ impl Database {
         row.ok().map(|x| x.volume as f32)
     }
 
+    /// Volume and state of a zone, by the name of its device
+    pub async fn set_zone(&self, zone: &Zone) -> AppResult<()> {
+        sqlx::query!(
+            "insert or replace into zones (name, volume, enabled) values (?1, ?2, ?3)",
+            zone.name,
+            zone.volume,
+            zone.enabled
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_zones(&self) -> AppResult<Vec<Zone>> {
+        let rows = sqlx::query!(r#"select name, volume, enabled as "enabled: bool" from zones"#)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Zone {
+                name: row.name,
+                volume: row.volume as f32,
+                enabled: row.enabled,
+            })
+            .collect())
+    }
+
     pub async fn set_max_audio_quality(&self, quality: AudioQuality) -> AppResult<()> {
         let quality_id = quality as i32;
+        let profile = self.profile();
 
         sqlx::query!(
             r#"
             update configuration
             set max_audio_quality=?1
-            where rowid = 1
+            where profile = ?2
             "#,
-            quality_id
+            quality_id,
+            profile
         )
         .execute(&self.pool)
         .await?;
@@ -133,9 +506,11 @@ impl Database {
     }
 
     pub async fn get_credentials(&self) -> AppResult<Option<Credentials>> {
+        let profile = self.profile();
         let credentials = sqlx::query_as!(
             DatabaseCredentials,
-            "select user_auth_token, user_id from credentials where rowid = 1"
+            "select user_auth_token, user_id from credentials where profile = ?",
+            profile
         )
         .fetch_one(&self.pool)
         .await?;
@@ -151,10 +526,96 @@ impl Database {
         Ok(credentials)
     }
 
+    pub async fn set_balance(&self, balance: f32) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "update configuration set balance = ?1 where profile = ?2",
+            balance,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_mono_downmix(&self, mono_downmix: bool) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "update configuration set mono_downmix = ?1 where profile = ?2",
+            mono_downmix,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_trim_silence(&self, trim_silence: bool) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "update configuration set trim_silence = ?1 where profile = ?2",
+            trim_silence,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_data_saver(&self, data_saver: bool) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "update configuration set data_saver = ?1 where profile = ?2",
+            data_saver,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_play_previews(&self, play_previews: bool) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "update configuration set play_previews = ?1 where profile = ?2",
+            play_previews,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// `None` plays to the default output device
+    pub async fn set_output_device(&self, output_device: Option<&str>) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "update configuration set output_device = ?1 where profile = ?2",
+            output_device,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_configuration(&self) -> AppResult<DatabaseConfiguration> {
+        let profile = self.profile();
         Ok(sqlx::query_as!(
             DatabaseConfiguration,
-            "select * from configuration where rowid = 1"
+            r#"
+            select max_audio_quality, balance, mono_downmix, trim_silence, data_saver, play_previews,
+                output_device
+            from configuration
+            where profile = ?
+            "#,
+            profile
         )
         .fetch_one(&self.pool)
         .await?)
@@ -165,30 +626,34 @@ impl Database {
         rfid_id: String,
         reference: ReferenceType,
     ) -> AppResult<()> {
-        match reference {
-            ReferenceType::Album(id) => {
-                let id = Some(id);
-
-                sqlx::query!(
-                    "insert into rfid_references (id, reference_type, album_id, playlist_id) values ($1, $2, $3, $4) on conflict(id) do update set reference_type = excluded.reference_type, album_id = excluded.album_id, playlist_id = excluded.playlist_id returning *",
-                    rfid_id,
-                    1,
-                    id,
-                    None::<u32>,
-                ).fetch_one(&self.pool).await?;
+        let (reference_type, album_id, playlist_id, volume) = match reference {
+            ReferenceType::Album(id) => (ReferenceTypeDatabase::Album, Some(id), None, None),
+            ReferenceType::Playlist(id) => (ReferenceTypeDatabase::Playlist, None, Some(id), None),
+            ReferenceType::QueueAlbum(id) => {
+                (ReferenceTypeDatabase::QueueAlbum, Some(id), None, None)
             }
-            ReferenceType::Playlist(id) => {
-                let id = Some(id);
-
-                sqlx::query!(
-                    "insert into rfid_references (id, reference_type, album_id, playlist_id) values ($1, $2, $3, $4) on conflict(id) do update set reference_type = excluded.reference_type, album_id = excluded.album_id, playlist_id = excluded.playlist_id returning *",
-                    rfid_id,
-                    2,
-                    None::<String>,
-                    id,
-                ).fetch_one(&self.pool).await?;
+            ReferenceType::QueuePlaylist(id) => {
+                (ReferenceTypeDatabase::QueuePlaylist, None, Some(id), None)
             }
-        }
+            ReferenceType::PlayPause => (ReferenceTypeDatabase::PlayPause, None, None, None),
+            ReferenceType::Stop => (ReferenceTypeDatabase::Stop, None, None, None),
+            ReferenceType::Volume(volume) => {
+                (ReferenceTypeDatabase::Volume, None, None, Some(volume))
+            }
+        };
+        let reference_type = reference_type as i64;
+
+        sqlx::query!(
+            "insert into rfid_references (id, reference_type, album_id, playlist_id, volume) values ($1, $2, $3, $4, $5) on conflict(id) do update set reference_type = excluded.reference_type, album_id = excluded.album_id, playlist_id = excluded.playlist_id, volume = excluded.volume",
+            rfid_id,
+            reference_type,
+            album_id,
+            playlist_id,
+            volume,
+        )
+        .execute(&self.pool)
+        .await?;
+
         Ok(())
     }
 
@@ -205,95 +670,952 @@ impl Database {
             Err(_) => return None,
         };
 
-        match db_reference.reference_type {
-            ReferenceTypeDatabase::Album => Some(ReferenceType::Album(db_reference.album_id?)),
-            ReferenceTypeDatabase::Playlist => {
-                Some(ReferenceType::Playlist(db_reference.playlist_id? as u32))
-            }
-        }
+        db_reference.reference()
     }
 
-    pub async fn clean_up_cache_entries(
-        &self,
-        older_than: time::Duration,
-    ) -> AppResult<Vec<PathBuf>> {
-        let cutoff = time::OffsetDateTime::now_utc() - older_than;
-        let cutoff_str = cutoff
-            .format(&time::format_description::well_known::Rfc3339)
-            .expect("infallible");
-
-        let rows = sqlx::query!(
-            "SELECT path FROM cache_entries WHERE last_opened < ?",
-            cutoff_str
+    pub async fn get_rfid_tags(&self) -> AppResult<Vec<RfidTag>> {
+        let rows = sqlx::query_as!(
+            RFIDReference,
+            "select * from rfid_references order by name is null, name, id"
         )
         .fetch_all(&self.pool)
         .await?;
 
-        sqlx::query!(
-            "DELETE FROM cache_entries WHERE last_opened < ?",
-            cutoff_str
-        )
-        .execute(&self.pool)
-        .await?;
-
-        let paths: Vec<PathBuf> = rows
+        Ok(rows
             .into_iter()
-            .map(|row| PathBuf::from(row.path))
-            .collect();
-
-        Ok(paths)
+            .filter_map(|row| {
+                Some(RfidTag {
+                    reference: row.reference()?,
+                    last_scanned: row.last_scanned.as_deref().and_then(|last_scanned| {
+                        time::OffsetDateTime::parse(
+                            last_scanned,
+                            &time::format_description::well_known::Rfc3339,
+                        )
+                        .ok()
+                    }),
+                    id: row.id,
+                    name: row.name,
+                })
+            })
+            .collect())
     }
 
-    pub async fn set_cache_entry(&self, path: &Path) {
+    pub async fn set_rfid_tag_scanned(&self, id: &str) -> AppResult<()> {
         let now = time::OffsetDateTime::now_utc()
             .format(&time::format_description::well_known::Rfc3339)
             .expect("infallible");
 
-        let path_str: String = path.to_string_lossy().into_owned();
-
         sqlx::query!(
-            r#"
-                insert into cache_entries (path, last_opened)
-                values (?, ?)
-                on conflict(path) do update set
-                    path = excluded.path,
-                    last_opened = excluded.last_opened
-            "#,
-            path_str,
-            now
+            "update rfid_references set last_scanned = ?1 where id = ?2",
+            now,
+            id
         )
         .execute(&self.pool)
-        .await
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn rename_rfid_tag(&self, id: &str, name: Option<String>) -> AppResult<()> {
+        sqlx::query!(
+            "update rfid_references set name = ?1 where id = ?2",
+            name,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_rfid_tag(&self, id: &str) -> AppResult<()> {
+        sqlx::query!("delete from rfid_references where id = ?1", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Moves the target and name of a tag to a replacement tag
+    pub async fn relink_rfid_tag(&self, id: &str, new_id: &str) -> AppResult<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query!(
+            "delete from rfid_references where id = ?1 and id != ?2",
+            new_id,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "update rfid_references set id = ?1, last_scanned = null where id = ?2",
+            new_id,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Local tags of the playlists of the current profile, sorted by tag
+    pub async fn get_playlist_tags(&self) -> AppResult<HashMap<u32, Vec<String>>> {
+        let profile = self.profile();
+        let rows = sqlx::query!(
+            "select playlist_id, tag from playlist_tags where profile = ?1 order by tag",
+            profile
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tags: HashMap<u32, Vec<String>> = HashMap::new();
+        for row in rows {
+            tags.entry(row.playlist_id as u32)
+                .or_default()
+                .push(row.tag);
+        }
+
+        Ok(tags)
+    }
+
+    /// Replaces the tags of a playlist. Tags are trimmed and empty ones skipped, so no tags
+    /// removes them
+    pub async fn set_playlist_tags(&self, playlist_id: u32, tags: &[String]) -> AppResult<()> {
+        let profile = self.profile();
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query!(
+            "delete from playlist_tags where profile = ?1 and playlist_id = ?2",
+            profile,
+            playlist_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        for tag in tags.iter().map(|tag| tag.trim()) {
+            if tag.is_empty() {
+                continue;
+            }
+
+            sqlx::query!(
+                "insert or ignore into playlist_tags (profile, playlist_id, tag) values (?1, ?2, ?3)",
+                profile,
+                playlist_id,
+                tag
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Tracks and artists of the current profile that are never played, sorted by name
+    pub async fn get_ban_list(&self) -> AppResult<BanList> {
+        let profile = self.profile();
+        let rows = sqlx::query!(
+            "select kind, id, name from banned where profile = ?1 order by name collate nocase",
+            profile
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut ban_list = BanList::default();
+        for row in rows {
+            let ban = Ban {
+                id: row.id as u32,
+                name: row.name,
+            };
+            match BanKind::parse(&row.kind) {
+                Some(BanKind::Track) => ban_list.tracks.push(ban),
+                Some(BanKind::Artist) => ban_list.artists.push(ban),
+                None => {}
+            }
+        }
+
+        Ok(ban_list)
+    }
+
+    /// `name` is shown in the ban list, as the item may no longer be on Qobuz
+    pub async fn ban(&self, kind: BanKind, id: u32, name: &str) -> AppResult<()> {
+        let profile = self.profile();
+        let kind = kind.as_str();
+        sqlx::query!(
+            "insert or replace into banned (profile, kind, id, name) values (?1, ?2, ?3, ?4)",
+            profile,
+            kind,
+            id,
+            name
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn unban(&self, kind: BanKind, id: u32) -> AppResult<()> {
+        let profile = self.profile();
+        let kind = kind.as_str();
+        sqlx::query!(
+            "delete from banned where profile = ?1 and kind = ?2 and id = ?3",
+            profile,
+            kind,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Saved position of a long track, in seconds
+    pub async fn get_resume_point(&self, track_id: u32) -> AppResult<Option<u64>> {
+        let profile = self.profile();
+        let row = sqlx::query!(
+            "select position_seconds from resume_points where profile = ?1 and track_id = ?2",
+            profile,
+            track_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.position_seconds as u64))
+    }
+
+    pub async fn set_resume_point(&self, track_id: u32, position_seconds: u64) -> AppResult<()> {
+        let profile = self.profile();
+        let position_seconds = position_seconds as i64;
+        sqlx::query!(
+            "insert or replace into resume_points (profile, track_id, position_seconds) values (?1, ?2, ?3)",
+            profile,
+            track_id,
+            position_seconds
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn clear_resume_point(&self, track_id: u32) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "delete from resume_points where profile = ?1 and track_id = ?2",
+            profile,
+            track_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn add_search_query(&self, query: &str) -> AppResult<()> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(());
+        }
+
+        let profile = self.profile();
+        let now = time::OffsetDateTime::now_utc().unix_timestamp_nanos() as i64;
+        let refine_since = now - SEARCH_REFINE_NANOS;
+
+        sqlx::query!(
+            r#"
+                delete from search_history
+                where profile = ?1 and searched_at >= ?2 and substr(?3, 1, length(query)) = query
+            "#,
+            profile,
+            refine_since,
+            query
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            "insert or replace into search_history (profile, query, searched_at) values (?1, ?2, ?3)",
+            profile,
+            query,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let max = MAX_SEARCH_HISTORY;
+        sqlx::query!(
+            r#"
+                delete from search_history
+                where profile = ?1 and query not in (
+                    select query from search_history where profile = ?1 order by searched_at desc limit ?2
+                )
+            "#,
+            profile,
+            max
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Recent search queries, newest first
+    pub async fn get_search_history(&self) -> AppResult<Vec<String>> {
+        let profile = self.profile();
+        let rows = sqlx::query!(
+            "select query from search_history where profile = ?1 order by searched_at desc",
+            profile
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.query).collect())
+    }
+
+    pub async fn clear_search_history(&self) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!("delete from search_history where profile = ?1", profile)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// `user` is `None` for sessions opened with the shared web secret
+    pub async fn create_web_session(
+        &self,
+        token: &str,
+        user: Option<&str>,
+        lifetime: time::Duration,
+    ) -> AppResult<()> {
+        let now = time::OffsetDateTime::now_utc();
+        let now_str = now
+            .format(&time::format_description::well_known::Rfc3339)
+            .expect("infallible");
+        let expires_at = (now + lifetime)
+            .format(&time::format_description::well_known::Rfc3339)
+            .expect("infallible");
+
+        sqlx::query!("DELETE FROM web_sessions WHERE expires_at <= ?", now_str)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query!(
+            "INSERT INTO web_sessions (token, user, expires_at) VALUES (?1, ?2, ?3)",
+            token,
+            user,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The user of an unexpired session. `Some(None)` for a web secret session
+    pub async fn get_web_session(&self, token: &str) -> AppResult<Option<Option<String>>> {
+        let now = time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .expect("infallible");
+
+        let row = sqlx::query!(
+            "SELECT user FROM web_sessions WHERE token = ?1 AND expires_at > ?2",
+            token,
+            now
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.user))
+    }
+
+    pub async fn delete_web_session(&self, token: &str) -> AppResult<()> {
+        sqlx::query!("DELETE FROM web_sessions WHERE token = ?1", token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Track suggested by a party guest, waiting for approval. Suggesting a pending track
+    /// again keeps the first suggestion
+    pub async fn add_suggestion(
+        &self,
+        track: &crate::models::Track,
+        suggested_by: Option<&str>,
+    ) -> AppResult<()> {
+        let suggested_at = time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .expect("infallible");
+
+        sqlx::query!(
+            "insert or ignore into suggestions (track_id, title, artist_name, suggested_by, suggested_at) values (?1, ?2, ?3, ?4, ?5)",
+            track.id,
+            track.title,
+            track.artist_name,
+            suggested_by,
+            suggested_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Pending suggestions, oldest first
+    pub async fn get_suggestions(&self) -> AppResult<Vec<Suggestion>> {
+        let rows = sqlx::query!(
+            "select track_id, title, artist_name, suggested_by from suggestions order by suggested_at"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Suggestion {
+                track_id: row.track_id as u32,
+                title: row.title,
+                artist_name: row.artist_name,
+                suggested_by: row.suggested_by,
+            })
+            .collect())
+    }
+
+    /// Whether the track was still pending, so it is only queued once when approved twice
+    pub async fn remove_suggestion(&self, track_id: u32) -> AppResult<bool> {
+        let result = sqlx::query!("delete from suggestions where track_id = ?1", track_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Follows an artist, to be notified about its new releases
+    pub async fn follow_artist(&self, artist_id: u32, name: &str) -> AppResult<()> {
+        let profile = self.profile();
+        sqlx::query!(
+            "insert or replace into followed_artists (profile, artist_id, name) values (?1, ?2, ?3)",
+            profile,
+            artist_id,
+            name
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Unfollows an artist and forgets its releases seen so far
+    pub async fn unfollow_artist(&self, artist_id: u32) -> AppResult<()> {
+        let profile = self.profile();
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query!(
+            "delete from followed_artists where profile = ?1 and artist_id = ?2",
+            profile,
+            artist_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "delete from artist_releases_seen where profile = ?1 and artist_id = ?2",
+            profile,
+            artist_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Followed artists, sorted by name
+    pub async fn get_followed_artists(&self) -> AppResult<Vec<FollowedArtist>> {
+        let profile = self.profile();
+        let rows = sqlx::query!(
+            "select artist_id, name from followed_artists where profile = ?1 order by name",
+            profile
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| FollowedArtist {
+                id: row.artist_id as u32,
+                name: row.name,
+            })
+            .collect())
+    }
+
+    /// Remembers the releases of a followed artist and returns the ones not seen before.
+    /// Nothing is new at the first check of an artist, so following an artist does not
+    /// report its whole discography
+    pub async fn record_artist_releases(
+        &self,
+        artist_id: u32,
+        album_ids: &[String],
+    ) -> AppResult<Vec<String>> {
+        let profile = self.profile();
+        let mut transaction = self.pool.begin().await?;
+
+        let checked_at = sqlx::query!(
+            "select checked_at from followed_artists where profile = ?1 and artist_id = ?2",
+            profile,
+            artist_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .and_then(|row| row.checked_at);
+
+        let seen: Vec<String> = sqlx::query!(
+            "select album_id from artist_releases_seen where profile = ?1 and artist_id = ?2",
+            profile,
+            artist_id
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .map(|row| row.album_id)
+        .collect();
+
+        let first_check = checked_at.is_none();
+        let mut new_releases = vec![];
+
+        for album_id in album_ids.iter().filter(|id| !seen.contains(id)) {
+            sqlx::query!(
+                "insert or ignore into artist_releases_seen (profile, artist_id, album_id) values (?1, ?2, ?3)",
+                profile,
+                artist_id,
+                album_id
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            if !first_check {
+                new_releases.push(album_id.clone());
+            }
+        }
+
+        let now = time::OffsetDateTime::now_utc().unix_timestamp();
+        sqlx::query!(
+            "update followed_artists set checked_at = ?3 where profile = ?1 and artist_id = ?2",
+            profile,
+            artist_id,
+            now
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(new_releases)
+    }
+
+    /// Alarm starting `link` whenever `schedule` matches. Returns the id of the alarm
+    pub async fn add_alarm(
+        &self,
+        schedule: &str,
+        link: &str,
+        title: &str,
+        volume: u8,
+    ) -> AppResult<i64> {
+        let result = sqlx::query!(
+            "insert into alarms (schedule, link, title, volume) values (?1, ?2, ?3, ?4)",
+            schedule,
+            link,
+            title,
+            volume
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Alarms in the order they were added
+    pub async fn get_alarms(&self) -> AppResult<Vec<Alarm>> {
+        let rows = sqlx::query!(
+            "select id, schedule, link, title, volume, enabled from alarms order by id"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Alarm {
+                id: row.id,
+                schedule: row.schedule,
+                link: row.link,
+                title: row.title,
+                volume: row.volume.clamp(0, 100) as u8,
+                enabled: row.enabled != 0,
+            })
+            .collect())
+    }
+
+    /// Whether there was an alarm with the id
+    pub async fn remove_alarm(&self, id: i64) -> AppResult<bool> {
+        let result = sqlx::query!("delete from alarms where id = ?1", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether there was an alarm with the id
+    pub async fn set_alarm_enabled(&self, id: i64, enabled: bool) -> AppResult<bool> {
+        let result = sqlx::query!("update alarms set enabled = ?1 where id = ?2", enabled, id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Quiet hours apply to every profile, like the volume
+    pub async fn set_quiet_hours(&self, quiet_hours: Option<QuietHours>) -> AppResult<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query!("delete from quiet_hours")
+            .execute(&mut *transaction)
+            .await?;
+
+        if let Some(quiet_hours) = quiet_hours {
+            sqlx::query!(
+                "insert into quiet_hours (start_minute, end_minute, max_volume) values (?1, ?2, ?3)",
+                quiet_hours.start,
+                quiet_hours.end,
+                quiet_hours.max_volume
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_quiet_hours(&self) -> AppResult<Option<QuietHours>> {
+        let row = sqlx::query!("select start_minute, end_minute, max_volume from quiet_hours")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| QuietHours {
+            start: row.start_minute as u16,
+            end: row.end_minute as u16,
+            max_volume: row.max_volume.clamp(0, 100) as u8,
+        }))
+    }
+
+    pub async fn clean_up_cache_entries(
+        &self,
+        older_than: time::Duration,
+    ) -> AppResult<Vec<PathBuf>> {
+        let cutoff = time::OffsetDateTime::now_utc() - older_than;
+        let cutoff_str = cutoff
+            .format(&time::format_description::well_known::Rfc3339)
+            .expect("infallible");
+
+        let rows = sqlx::query!(
+            "SELECT path FROM cache_entries WHERE last_opened < ?",
+            cutoff_str
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM cache_entries WHERE last_opened < ?",
+            cutoff_str
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let paths: Vec<PathBuf> = rows
+            .into_iter()
+            .map(|row| PathBuf::from(row.path))
+            .collect();
+
+        Ok(paths)
+    }
+
+    /// Cached tracks, least recently opened first
+    pub async fn get_cache_entries(&self) -> AppResult<Vec<PathBuf>> {
+        let rows = sqlx::query!("SELECT path FROM cache_entries ORDER BY last_opened")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PathBuf::from(row.path))
+            .collect())
+    }
+
+    pub async fn remove_cache_entry(&self, path: &Path) -> AppResult<()> {
+        let path_str = path.to_string_lossy();
+        sqlx::query!("DELETE FROM cache_entries WHERE path = ?", path_str)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Forgets every cached track and returns their paths
+    pub async fn clear_cache_entries(&self) -> AppResult<Vec<PathBuf>> {
+        let rows = sqlx::query!("DELETE FROM cache_entries RETURNING path")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PathBuf::from(row.path))
+            .collect())
+    }
+
+    pub async fn set_cache_entry(&self, path: &Path) {
+        let now = time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .expect("infallible");
+
+        let path_str: String = path.to_string_lossy().into_owned();
+
+        sqlx::query!(
+            r#"
+                insert into cache_entries (path, last_opened)
+                values (?, ?)
+                on conflict(path) do update set
+                    path = excluded.path,
+                    last_opened = excluded.last_opened
+            "#,
+            path_str,
+            now
+        )
+        .execute(&self.pool)
+        .await
         .expect("infallible");
     }
+
+    /// Size and hash of the audio of a cached track, recorded once it was downloaded
+    pub async fn get_cache_integrity(&self, path: &Path) -> AppResult<Option<CacheIntegrity>> {
+        let path_str = path.to_string_lossy();
+        let row = sqlx::query!(
+            "SELECT size, hash FROM cache_entries WHERE path = ?",
+            path_str
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.and_then(|row| {
+            Some(CacheIntegrity {
+                size: row.size? as u64,
+                hash: row.hash?,
+            })
+        }))
+    }
+
+    /// `None` forgets the integrity, like when the file is removed
+    pub async fn set_cache_integrity(
+        &self,
+        path: &Path,
+        integrity: Option<&CacheIntegrity>,
+    ) -> AppResult<()> {
+        let path_str = path.to_string_lossy();
+        let size = integrity.map(|integrity| integrity.size as i64);
+        let hash = integrity.map(|integrity| integrity.hash.as_str());
+        sqlx::query!(
+            "UPDATE cache_entries SET size = ?1, hash = ?2 WHERE path = ?3",
+            size,
+            hash,
+            path_str
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub enum ReferenceType {
     Album(String),
     Playlist(u32),
+    QueueAlbum(String),
+    QueuePlaylist(u32),
+    PlayPause,
+    /// Pauses playback, like the MPRIS stop action
+    Stop,
+    /// Volume preset in percent
+    Volume(u8),
+}
+
+impl ReferenceType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReferenceType::Album(_) => "Play album",
+            ReferenceType::Playlist(_) => "Play playlist",
+            ReferenceType::QueueAlbum(_) => "Queue album",
+            ReferenceType::QueuePlaylist(_) => "Queue playlist",
+            ReferenceType::PlayPause => "Play/pause",
+            ReferenceType::Stop => "Stop",
+            ReferenceType::Volume(_) => "Volume",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BanKind {
+    Track,
+    Artist,
+}
+
+impl BanKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            BanKind::Track => "track",
+            BanKind::Artist => "artist",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "track" => Some(BanKind::Track),
+            "artist" => Some(BanKind::Artist),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Ban {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Tracks and artists playback skips
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct BanList {
+    pub tracks: Vec<Ban>,
+    pub artists: Vec<Ban>,
+}
+
+impl BanList {
+    pub fn is_track_banned(&self, id: u32) -> bool {
+        self.tracks.iter().any(|ban| ban.id == id)
+    }
+
+    pub fn is_artist_banned(&self, id: u32) -> bool {
+        self.artists.iter().any(|ban| ban.id == id)
+    }
+
+    /// Banned itself or by its artist
+    pub fn is_banned(&self, track: &crate::models::Track) -> bool {
+        self.is_track_banned(track.id)
+            || track
+                .artist_id
+                .is_some_and(|artist_id| self.is_artist_banned(artist_id))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty() && self.artists.is_empty()
+    }
+}
+
+/// Track a party guest asked to have queued
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Suggestion {
+    pub track_id: u32,
+    pub title: String,
+    pub artist_name: Option<String>,
+    /// Name the guest gave, if any
+    pub suggested_by: Option<String>,
+}
+
+/// Artist whose new releases are notified about
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FollowedArtist {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Album, playlist or track started at the times of a cron schedule
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Alarm {
+    pub id: i64,
+    /// Cron schedule like `30 7 * * 1-5`, see [`crate::alarms::Schedule`]
+    pub schedule: String,
+    /// Qobuz url or `album:`, `playlist:` or `track:` id
+    pub link: String,
+    pub title: String,
+    /// Volume in percent reached at the end of the ramp
+    pub volume: u8,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RfidTag {
+    pub id: String,
+    pub name: Option<String>,
+    pub reference: ReferenceType,
+    pub last_scanned: Option<time::OffsetDateTime>,
 }
 
 #[derive(sqlx::FromRow)]
 struct RFIDReference {
-    #[allow(dead_code)]
     id: String,
-    reference_type: ReferenceTypeDatabase,
+    reference_type: i64,
     album_id: Option<String>,
     playlist_id: Option<i64>,
+    name: Option<String>,
+    last_scanned: Option<String>,
+    volume: Option<i64>,
+}
+
+impl RFIDReference {
+    /// `None` for a reference this version does not know, like one written by a newer one
+    fn reference(&self) -> Option<ReferenceType> {
+        match ReferenceTypeDatabase::try_from(self.reference_type).ok()? {
+            ReferenceTypeDatabase::Album => Some(ReferenceType::Album(self.album_id.clone()?)),
+            ReferenceTypeDatabase::Playlist => {
+                Some(ReferenceType::Playlist(self.playlist_id? as u32))
+            }
+            ReferenceTypeDatabase::QueueAlbum => {
+                Some(ReferenceType::QueueAlbum(self.album_id.clone()?))
+            }
+            ReferenceTypeDatabase::QueuePlaylist => {
+                Some(ReferenceType::QueuePlaylist(self.playlist_id? as u32))
+            }
+            ReferenceTypeDatabase::PlayPause => Some(ReferenceType::PlayPause),
+            ReferenceTypeDatabase::Stop => Some(ReferenceType::Stop),
+            ReferenceTypeDatabase::Volume => Some(ReferenceType::Volume(self.volume? as u8)),
+        }
+    }
 }
 
 enum ReferenceTypeDatabase {
     Album = 1,
     Playlist = 2,
+    QueueAlbum = 3,
+    QueuePlaylist = 4,
+    PlayPause = 5,
+    Stop = 6,
+    Volume = 7,
 }
 
-impl From<i64> for ReferenceTypeDatabase {
-    fn from(value: i64) -> Self {
+impl TryFrom<i64> for ReferenceTypeDatabase {
+    type Error = i64;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
         match value {
-            1 => ReferenceTypeDatabase::Album,
-            2 => ReferenceTypeDatabase::Playlist,
-            _ => panic!("Unable to parse reference type!"),
+            1 => Ok(ReferenceTypeDatabase::Album),
+            2 => Ok(ReferenceTypeDatabase::Playlist),
+            3 => Ok(ReferenceTypeDatabase::QueueAlbum),
+            4 => Ok(ReferenceTypeDatabase::QueuePlaylist),
+            5 => Ok(ReferenceTypeDatabase::PlayPause),
+            6 => Ok(ReferenceTypeDatabase::Stop),
+            7 => Ok(ReferenceTypeDatabase::Volume),
+            value => Err(value),
         }
     }
 }
@@ -318,8 +1640,28 @@ impl From<OAuthResult> for Credentials {
     }
 }
 
+/// Qobuz account with its own settings and favorites
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub logged_in: bool,
+}
+
+/// Length and md5 hash of the audio of a cached track, decrypted when the cache is encrypted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheIntegrity {
+    pub size: u64,
+    pub hash: String,
+}
+
 pub struct DatabaseConfiguration {
     pub max_audio_quality: Option<i64>,
+    pub balance: f64,
+    pub mono_downmix: bool,
+    pub trim_silence: bool,
+    pub data_saver: bool,
+    pub play_previews: bool,
+    pub output_device: Option<String>,
 }
 
 #[derive(Debug, sqlx::FromRow, serde::Deserialize)]
@@ -327,28 +1669,31 @@ struct TracklistDb {
     tracklist: Json<Tracklist>,
 }
 
+#[derive(Debug, sqlx::FromRow, serde::Deserialize)]
+struct FavoritesDb {
+    favorites: Json<Favorites>,
+}
+
+#[derive(Debug, sqlx::FromRow, serde::Deserialize)]
+struct RecentTracklistDb {
+    tracklist_type: Json<TracklistType>,
+}
+
 #[derive(Debug, sqlx::FromRow, serde::Deserialize)]
 struct VolumeDb {
     volume: f64,
 }
 
-async fn create_credentials_row(pool: &Pool<Sqlite>) -> AppResult<()> {
-    let rowid = 1;
-
+async fn create_profile(pool: &Pool<Sqlite>, profile: &str) -> AppResult<()> {
     sqlx::query!(
-        "insert or ignore into credentials (rowid) values (?1)",
-        rowid
+        "insert or ignore into credentials (profile) values (?1)",
+        profile
     )
     .execute(pool)
     .await?;
-    Ok(())
-}
-
-async fn create_configuration(pool: &Pool<Sqlite>) -> AppResult<()> {
-    let rowid = 1;
     sqlx::query!(
-        "insert or ignore into configuration (rowid) values (?1)",
-        rowid
+        "insert or ignore into configuration (profile) values (?1)",
+        profile
     )
     .execute(pool)
     .await?;
@@ -360,6 +1705,49 @@ mod tests {
     use super::*;
     use time::{Duration, OffsetDateTime};
 
+    #[sqlx::test]
+    async fn zones_are_saved_by_name(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+        assert_eq!(db.get_zones().await.unwrap(), vec![]);
+
+        let kitchen = Zone {
+            name: "Kitchen".into(),
+            volume: 0.5,
+            enabled: true,
+        };
+        db.set_zone(&kitchen).await.unwrap();
+        let kitchen = Zone {
+            enabled: false,
+            ..kitchen
+        };
+        db.set_zone(&kitchen).await.unwrap();
+
+        assert_eq!(db.get_zones().await.unwrap(), vec![kitchen]);
+    }
+
+    #[sqlx::test]
+    async fn cache_integrity_is_kept_when_the_track_is_played_again(
+        pool: sqlx::Pool<sqlx::Sqlite>,
+    ) {
+        let db = Database::init(pool).await.unwrap();
+        let path = Path::new("path/track.flac");
+        db.set_cache_entry(path).await;
+        assert_eq!(db.get_cache_integrity(path).await.unwrap(), None);
+
+        let integrity = CacheIntegrity {
+            size: 1024,
+            hash: "d41d8cd98f00b204e9800998ecf8427e".into(),
+        };
+        db.set_cache_integrity(path, Some(&integrity))
+            .await
+            .unwrap();
+        db.set_cache_entry(path).await;
+        assert_eq!(db.get_cache_integrity(path).await.unwrap(), Some(integrity));
+
+        db.set_cache_integrity(path, None).await.unwrap();
+        assert_eq!(db.get_cache_integrity(path).await.unwrap(), None);
+    }
+
     #[sqlx::test]
     async fn clean_up_cache_entries(pool: sqlx::Pool<sqlx::Sqlite>) {
         let db = Database::init(pool).await.unwrap();
@@ -393,4 +1781,446 @@ mod tests {
         assert_eq!(remaining, vec![new_path_str]);
         assert_eq!(deleted, vec![old_path]);
     }
+
+    #[sqlx::test]
+    async fn recent_tracklists_are_deduplicated_and_trimmed(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        for id in 0..MAX_RECENT_TRACKLISTS + 5 {
+            let list_type = TracklistType::Playlist(crate::tracklist::PlaylistTracklist {
+                title: format!("Playlist {id}"),
+                id: id as u32,
+                image: None,
+            });
+            db.add_recent_tracklist(&list_type).await.unwrap();
+        }
+
+        let first = TracklistType::Playlist(crate::tracklist::PlaylistTracklist {
+            title: "Playlist 5".into(),
+            id: 5,
+            image: None,
+        });
+        db.add_recent_tracklist(&first).await.unwrap();
+        db.add_recent_tracklist(&TracklistType::Tracks)
+            .await
+            .unwrap();
+
+        let recent = db.get_recent_tracklists().await.unwrap();
+
+        assert_eq!(recent.len(), MAX_RECENT_TRACKLISTS as usize);
+        assert_eq!(recent.first(), Some(&first));
+    }
+
+    #[sqlx::test]
+    async fn favorites_are_replaced(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+        assert!(db.get_favorites().await.is_none());
+
+        let track = |id: u32| crate::models::Track {
+            id,
+            ..Default::default()
+        };
+
+        for id in 0..2 {
+            let favorites = Favorites {
+                tracks: vec![track(id)],
+                ..Default::default()
+            };
+            db.set_favorites(&favorites).await.unwrap();
+        }
+
+        let favorites = db.get_favorites().await.unwrap();
+        assert_eq!(favorites.tracks, vec![track(1)]);
+    }
+
+    #[sqlx::test]
+    async fn profiles_keep_their_own_credentials_and_favorites(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+        db.set_credentials(Credentials {
+            user_auth_token: "token".into(),
+            user_id: 1,
+        })
+        .await
+        .unwrap();
+        db.set_favorites(&Favorites::default()).await.unwrap();
+
+        db.set_profile("guest").await.unwrap();
+        assert!(db.get_credentials().await.unwrap().is_none());
+        assert!(db.get_favorites().await.is_none());
+        db.set_data_saver(true).await.unwrap();
+        db.set_output_device(Some("USB DAC")).await.unwrap();
+        assert_eq!(
+            db.get_configuration()
+                .await
+                .unwrap()
+                .output_device
+                .as_deref(),
+            Some("USB DAC")
+        );
+
+        db.set_profile(DEFAULT_PROFILE).await.unwrap();
+        assert_eq!(db.get_credentials().await.unwrap().unwrap().user_id, 1);
+        assert!(db.get_favorites().await.is_some());
+        assert!(!db.get_configuration().await.unwrap().data_saver);
+        assert!(db.get_configuration().await.unwrap().play_previews);
+        assert!(
+            db.get_configuration()
+                .await
+                .unwrap()
+                .output_device
+                .is_none()
+        );
+
+        let profiles = db.get_profiles().await.unwrap();
+        let logged_in: Vec<_> = profiles
+            .iter()
+            .map(|profile| (profile.name.as_str(), profile.logged_in))
+            .collect();
+        assert_eq!(logged_in, vec![("default", true), ("guest", false)]);
+    }
+
+    #[sqlx::test]
+    async fn relinked_rfid_tag_keeps_name_and_target(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.add_rfid_reference("old".into(), ReferenceType::Playlist(1))
+            .await
+            .unwrap();
+        db.add_rfid_reference("new".into(), ReferenceType::Playlist(2))
+            .await
+            .unwrap();
+        db.rename_rfid_tag("old", Some("Bedtime".into()))
+            .await
+            .unwrap();
+        db.set_rfid_tag_scanned("old").await.unwrap();
+
+        db.relink_rfid_tag("old", "new").await.unwrap();
+
+        let tags = db.get_rfid_tags().await.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].id, "new");
+        assert_eq!(tags[0].name.as_deref(), Some("Bedtime"));
+        assert!(matches!(tags[0].reference, ReferenceType::Playlist(1)));
+        assert!(tags[0].last_scanned.is_none());
+    }
+
+    #[sqlx::test]
+    async fn unknown_rfid_references_are_skipped(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.add_rfid_reference("stop".into(), ReferenceType::Stop)
+            .await
+            .unwrap();
+        sqlx::query("insert into rfid_references (id, reference_type) values ('newer', 99)")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        assert!(db.get_reference("newer").await.is_none());
+        let tags = db.get_rfid_tags().await.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert!(matches!(tags[0].reference, ReferenceType::Stop));
+    }
+
+    #[sqlx::test]
+    async fn web_sessions_expire(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.create_web_session("secret", None, Duration::hours(1))
+            .await
+            .unwrap();
+        db.create_web_session("alice", Some("alice"), Duration::hours(1))
+            .await
+            .unwrap();
+        db.create_web_session("expired", Some("bob"), Duration::seconds(-1))
+            .await
+            .unwrap();
+
+        assert_eq!(db.get_web_session("secret").await.unwrap(), Some(None));
+        assert_eq!(
+            db.get_web_session("alice").await.unwrap(),
+            Some(Some("alice".to_string()))
+        );
+        assert_eq!(db.get_web_session("expired").await.unwrap(), None);
+
+        db.delete_web_session("alice").await.unwrap();
+        assert_eq!(db.get_web_session("alice").await.unwrap(), None);
+    }
+
+    #[sqlx::test]
+    async fn playlist_tags_are_replaced_per_profile(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.set_playlist_tags(1, &["workout".into(), "running".into()])
+            .await
+            .unwrap();
+        db.set_playlist_tags(1, &[" running ".into(), "".into()])
+            .await
+            .unwrap();
+        db.set_playlist_tags(2, &["chill".into()]).await.unwrap();
+
+        let tags = db.get_playlist_tags().await.unwrap();
+        assert_eq!(tags.get(&1), Some(&vec!["running".to_string()]));
+        assert_eq!(tags.get(&2), Some(&vec!["chill".to_string()]));
+
+        db.set_profile("other").await.unwrap();
+        assert!(db.get_playlist_tags().await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn play_history_counts_plays(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        for track_id in [1, 2, 2, 3, 2, 3] {
+            db.add_track_play(track_id).await.unwrap();
+        }
+
+        let since = OffsetDateTime::now_utc() - Duration::days(30);
+        assert_eq!(db.most_played_tracks(since, 2).await.unwrap(), vec![2, 3]);
+
+        let future = OffsetDateTime::now_utc() + Duration::days(1);
+        assert!(db.most_played_tracks(future, 2).await.unwrap().is_empty());
+        assert_eq!(db.tracks_last_played().await.unwrap().len(), 3);
+    }
+
+    #[sqlx::test]
+    async fn ban_list_covers_tracks_and_their_artists(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.ban(BanKind::Track, 1, "Banned track").await.unwrap();
+        db.ban(BanKind::Artist, 10, "Banned artist").await.unwrap();
+        db.ban(BanKind::Artist, 11, "Forgiven artist")
+            .await
+            .unwrap();
+        db.unban(BanKind::Artist, 11).await.unwrap();
+
+        let track = |id, artist_id| crate::models::Track {
+            id,
+            artist_id: Some(artist_id),
+            ..Default::default()
+        };
+        let ban_list = db.get_ban_list().await.unwrap();
+        assert!(ban_list.is_banned(&track(1, 20)));
+        assert!(ban_list.is_banned(&track(2, 10)));
+        assert!(!ban_list.is_banned(&track(3, 11)));
+
+        db.set_profile("other").await.unwrap();
+        assert!(db.get_ban_list().await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn resume_points_are_per_profile(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.set_resume_point(1, 600).await.unwrap();
+        db.set_resume_point(1, 2832).await.unwrap();
+        assert_eq!(db.get_resume_point(1).await.unwrap(), Some(2832));
+
+        db.set_profile("other").await.unwrap();
+        assert_eq!(db.get_resume_point(1).await.unwrap(), None);
+
+        db.set_profile(DEFAULT_PROFILE).await.unwrap();
+        db.clear_resume_point(1).await.unwrap();
+        assert_eq!(db.get_resume_point(1).await.unwrap(), None);
+    }
+
+    #[sqlx::test]
+    async fn search_history_keeps_finished_queries(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.add_search_query("radio").await.unwrap();
+        db.add_search_query("radiohead").await.unwrap();
+        db.add_search_query("  ").await.unwrap();
+        db.add_search_query("bjork").await.unwrap();
+        db.add_search_query("radiohead").await.unwrap();
+        assert_eq!(
+            db.get_search_history().await.unwrap(),
+            vec!["radiohead".to_string(), "bjork".to_string()]
+        );
+
+        for index in 0..MAX_SEARCH_HISTORY {
+            db.add_search_query(&format!("query {index:02}"))
+                .await
+                .unwrap();
+        }
+        let history = db.get_search_history().await.unwrap();
+        assert_eq!(history.len(), MAX_SEARCH_HISTORY as usize);
+        assert_eq!(history[0], format!("query {:02}", MAX_SEARCH_HISTORY - 1));
+
+        db.set_profile("other").await.unwrap();
+        assert!(db.get_search_history().await.unwrap().is_empty());
+
+        db.set_profile(DEFAULT_PROFILE).await.unwrap();
+        db.clear_search_history().await.unwrap();
+        assert!(db.get_search_history().await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn suggestions_are_pending_until_removed(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        let track = |id, title: &str| crate::models::Track {
+            id,
+            title: title.into(),
+            ..Default::default()
+        };
+        db.add_suggestion(&track(1, "First"), Some("Guest"))
+            .await
+            .unwrap();
+        db.add_suggestion(&track(2, "Second"), None).await.unwrap();
+        db.add_suggestion(&track(1, "First again"), None)
+            .await
+            .unwrap();
+
+        let suggestions = db.get_suggestions().await.unwrap();
+        let titles: Vec<_> = suggestions
+            .iter()
+            .map(|suggestion| suggestion.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["First", "Second"]);
+        assert_eq!(suggestions[0].suggested_by.as_deref(), Some("Guest"));
+
+        assert!(db.remove_suggestion(1).await.unwrap());
+        assert!(!db.remove_suggestion(1).await.unwrap());
+        assert_eq!(db.get_suggestions().await.unwrap().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn favorite_tracks_are_dated_after_the_first_recording(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.record_favorite_tracks(&[1, 2]).await.unwrap();
+        assert!(db.recently_favorited_tracks(10).await.unwrap().is_empty());
+
+        db.record_favorite_tracks(&[1, 2, 3]).await.unwrap();
+        assert_eq!(db.recently_favorited_tracks(10).await.unwrap(), [3]);
+    }
+
+    #[sqlx::test]
+    async fn favorite_albums_are_dated_after_the_first_recording(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        db.record_favorite_albums(&ids(&["a", "b"])).await.unwrap();
+        assert!(db.favorite_albums_added().await.unwrap().is_empty());
+
+        db.record_favorite_albums(&ids(&["a", "c"])).await.unwrap();
+        let added = db.favorite_albums_added().await.unwrap();
+        assert_eq!(added.keys().collect::<Vec<_>>(), vec!["c"]);
+
+        let known: Vec<_> = sqlx::query_scalar::<_, String>(
+            "select album_id from favorite_albums_added order by album_id",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(known, vec!["a", "c"]);
+
+        db.record_favorite_albums(&[]).await.unwrap();
+        db.record_favorite_albums(&ids(&["d"])).await.unwrap();
+        let added = db.favorite_albums_added().await.unwrap();
+        assert_eq!(added.keys().collect::<Vec<_>>(), vec!["d"]);
+    }
+
+    #[sqlx::test]
+    async fn only_releases_after_the_first_check_are_new(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        db.follow_artist(1, "Artist").await.unwrap();
+        assert_eq!(
+            db.get_followed_artists().await.unwrap(),
+            vec![FollowedArtist {
+                id: 1,
+                name: "Artist".into()
+            }]
+        );
+
+        let new = db
+            .record_artist_releases(1, &ids(&["a", "b"]))
+            .await
+            .unwrap();
+        assert!(new.is_empty());
+
+        let new = db
+            .record_artist_releases(1, &ids(&["a", "b", "c"]))
+            .await
+            .unwrap();
+        assert_eq!(new, vec!["c"]);
+
+        let new = db
+            .record_artist_releases(1, &ids(&["a", "b", "c"]))
+            .await
+            .unwrap();
+        assert!(new.is_empty());
+
+        db.unfollow_artist(1).await.unwrap();
+        assert!(db.get_followed_artists().await.unwrap().is_empty());
+        let new = db.record_artist_releases(1, &ids(&["d"])).await.unwrap();
+        assert!(new.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn first_release_of_an_artist_without_releases_is_new(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        db.follow_artist(1, "Newcomer").await.unwrap();
+        let new = db.record_artist_releases(1, &[]).await.unwrap();
+        assert!(new.is_empty());
+
+        let new = db
+            .record_artist_releases(1, &["debut".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(new, vec!["debut"]);
+    }
+
+    #[sqlx::test]
+    async fn alarms_can_be_disabled_and_removed(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+
+        let id = db
+            .add_alarm("30 7 * * 1-5", "album:1", "Album", 40)
+            .await
+            .unwrap();
+        db.add_alarm("0 9 * * 0,6", "playlist:2", "Playlist", 20)
+            .await
+            .unwrap();
+
+        assert!(db.set_alarm_enabled(id, false).await.unwrap());
+        let alarms = db.get_alarms().await.unwrap();
+        assert_eq!(
+            alarms[0],
+            Alarm {
+                id,
+                schedule: "30 7 * * 1-5".into(),
+                link: "album:1".into(),
+                title: "Album".into(),
+                volume: 40,
+                enabled: false,
+            }
+        );
+        assert!(alarms[1].enabled);
+
+        assert!(db.remove_alarm(id).await.unwrap());
+        assert!(!db.remove_alarm(id).await.unwrap());
+        assert!(!db.set_alarm_enabled(id, true).await.unwrap());
+        assert_eq!(db.get_alarms().await.unwrap().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn quiet_hours_are_replaced_and_cleared(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let db = Database::init(pool).await.unwrap();
+        assert_eq!(db.get_quiet_hours().await.unwrap(), None);
+
+        let night = QuietHours::new("22:00-07:00", 30).unwrap();
+        db.set_quiet_hours(Some(QuietHours::new("13:00-15:00", 10).unwrap()))
+            .await
+            .unwrap();
+        db.set_quiet_hours(Some(night)).await.unwrap();
+        assert_eq!(db.get_quiet_hours().await.unwrap(), Some(night));
+
+        db.set_quiet_hours(None).await.unwrap();
+        assert_eq!(db.get_quiet_hours().await.unwrap(), None);
+    }
 }
@@ -0,0 +1,178 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// A read of a streaming track taking this long stalled the decoder
+const STALL_THRESHOLD: Duration = Duration::from_millis(50);
+
+/// Playback problems since the player started, to find the cause of stuttering
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Diagnostics {
+    pub tracks_played: u64,
+    /// The output ran out of samples
+    pub underruns: u64,
+    /// Other errors reported by the output device
+    pub output_errors: u64,
+    /// Tracks that could not be decoded
+    pub decode_errors: u64,
+    /// Times the decoder waited for the download of a streaming track
+    pub rebuffers: u64,
+    pub rebuffer_ms: u64,
+    /// Tracks started after the previous one ended instead of being queued, because they
+    /// were not downloaded in time or needed another sample rate
+    pub gaps: u64,
+    /// Downloads that made no progress for a while and were started again
+    pub download_stalls: u64,
+    /// Times the playing track stopped advancing and was restarted where it stopped
+    pub recoveries: u64,
+}
+
+/// Counters behind [`Diagnostics`], shared by the player, the sink and the outputs
+#[derive(Debug, Default)]
+pub(crate) struct PlaybackStats {
+    tracks_played: AtomicU64,
+    underruns: AtomicU64,
+    output_errors: AtomicU64,
+    decode_errors: AtomicU64,
+    rebuffers: AtomicU64,
+    rebuffer_ms: AtomicU64,
+    gaps: AtomicU64,
+    download_stalls: AtomicU64,
+    recoveries: AtomicU64,
+}
+
+impl PlaybackStats {
+    pub fn record_track_played(&self) {
+        self.tracks_played.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_output_error(&self) {
+        self.output_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a track the sink could not queue when the decoder failed on it. Failures to
+    /// read the file or open the output are counted elsewhere, or not at all
+    pub fn record_query_error(&self, err: &Error) {
+        if matches!(err, Error::Decode { .. }) {
+            self.decode_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_rebuffer(&self, waited: Duration) {
+        self.rebuffers.fetch_add(1, Ordering::Relaxed);
+        self.rebuffer_ms
+            .fetch_add(waited.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_gap(&self) {
+        self.gaps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_download_stall(&self) {
+        self.download_stalls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_recovery(&self) {
+        self.recoveries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Diagnostics {
+        Diagnostics {
+            tracks_played: self.tracks_played.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            output_errors: self.output_errors.load(Ordering::Relaxed),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            rebuffers: self.rebuffers.load(Ordering::Relaxed),
+            rebuffer_ms: self.rebuffer_ms.load(Ordering::Relaxed),
+            gaps: self.gaps.load(Ordering::Relaxed),
+            download_stalls: self.download_stalls.load(Ordering::Relaxed),
+            recoveries: self.recoveries.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Error callback of the cpal streams
+    pub fn stream_error_callback(
+        self: &Arc<Self>,
+    ) -> impl FnMut(rodio::cpal::StreamError) + Send + Clone + 'static {
+        let stats = self.clone();
+        move |err| match err {
+            rodio::cpal::StreamError::BufferUnderrun => stats.record_underrun(),
+            err => {
+                tracing::error!("audio stream error: {err}");
+                stats.record_output_error();
+            }
+        }
+    }
+}
+
+/// Counts reads of a streaming track that kept the decoder waiting. Reads are only counted
+/// once armed, since opening the decoder waits for the first segment before playback starts
+pub(crate) struct MonitoredReader<R> {
+    inner: R,
+    stats: Arc<PlaybackStats>,
+    armed: Arc<AtomicBool>,
+}
+
+impl<R> MonitoredReader<R> {
+    pub fn new(inner: R, stats: Arc<PlaybackStats>) -> (Self, Arc<AtomicBool>) {
+        let armed = Arc::new(AtomicBool::new(false));
+        let reader = Self {
+            inner,
+            stats,
+            armed: armed.clone(),
+        };
+        (reader, armed)
+    }
+}
+
+impl<R: Read> Read for MonitoredReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let started = Instant::now();
+        let read = self.inner.read(buf);
+
+        let waited = started.elapsed();
+        if waited >= STALL_THRESHOLD && self.armed.load(Ordering::Relaxed) {
+            self.stats.record_rebuffer(waited);
+        }
+
+        read
+    }
+}
+
+impl<R: Seek> Seek for MonitoredReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+
+    use super::PlaybackStats;
+
+    #[test]
+    fn only_decoder_failures_are_decode_errors() {
+        let stats = PlaybackStats::default();
+
+        stats.record_query_error(&Error::Network);
+        stats.record_query_error(&Error::InvalidCredentials);
+        stats.record_query_error(&Error::StreamError {
+            message: "Failed to read file".into(),
+        });
+        assert_eq!(stats.snapshot().decode_errors, 0);
+
+        let undecodable = rodio::Decoder::new(std::io::Cursor::new(vec![0_u8; 64]))
+            .map(|_| ())
+            .unwrap_err();
+        stats.record_query_error(&undecodable.into());
+        assert_eq!(stats.snapshot().decode_errors, 1);
+    }
+}
@@ -1,58 +1,405 @@
 use std::{
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
 };
 
-use qobuz_player_client::stream::flac_source_stream::SeekableStreamReader;
+use parking_lot::Mutex;
+use tokio::sync::{Semaphore, mpsc::UnboundedSender};
 
-use crate::{AppResult, client::Client, database::Database, models::Track};
+use qobuz_player_client::stream::{
+    cache_encryption::CacheFile,
+    flac_source_stream::{DownloadProgress, SeekableStreamReader},
+};
+
+use crate::{
+    AppResult, audio_cache,
+    client::Client,
+    database::{CacheIntegrity, Database},
+    error::Error,
+    models::Track,
+    notification::{BufferProgress, Notification, NotificationBroadcast},
+};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// Progress intervals to wait for the cache file to be written once the download is complete
+const CACHE_WRITE_ATTEMPTS: usize = 40;
+/// Pre-caches downloading at once, the others wait for their turn
+const CONCURRENT_PRECACHES: usize = 2;
 
 pub enum DownloadResult {
     Cached(PathBuf),
     Streaming(SeekableStreamReader),
 }
 
+#[derive(Clone)]
 pub struct Downloader {
     audio_cache_dir: PathBuf,
+    /// Least recently played tracks are evicted above this size
+    audio_cache_max_bytes: Option<u64>,
     database: Arc<Database>,
     client: Arc<Client>,
+    broadcast: Arc<NotificationBroadcast>,
+    /// Streaming downloads without progress for this long are reported on `stalled`
+    stall_timeout: Option<Duration>,
+    stalled: UnboundedSender<u32>,
+    /// Progress of the tracks being streamed, by track id
+    streaming: Arc<Mutex<HashMap<u32, Weak<DownloadProgress>>>>,
+    /// Cached files hashed since the player started
+    verified: Arc<Mutex<HashSet<PathBuf>>>,
+    /// Held while evicting, so evictions after downloads finishing together run one by one
+    eviction: Arc<tokio::sync::Mutex<()>>,
+    /// Bounds the pre-caches running next to playback
+    precaches: Arc<Semaphore>,
 }
 
 impl Downloader {
-    pub fn new(audio_cache_dir: PathBuf, database: Arc<Database>, client: Arc<Client>) -> Self {
+    pub fn new(
+        audio_cache_dir: PathBuf,
+        audio_cache_max_bytes: Option<u64>,
+        database: Arc<Database>,
+        client: Arc<Client>,
+        broadcast: Arc<NotificationBroadcast>,
+        stall_timeout: Option<Duration>,
+        stalled: UnboundedSender<u32>,
+    ) -> Self {
         Self {
             audio_cache_dir,
+            audio_cache_max_bytes,
             database,
             client,
+            broadcast,
+            stall_timeout,
+            stalled,
+            streaming: Default::default(),
+            verified: Default::default(),
+            eviction: Default::default(),
+            precaches: Arc::new(Semaphore::new(CONCURRENT_PRECACHES)),
         }
     }
 
+    /// Whether the track is streamed and not completely downloaded yet
+    pub fn is_streaming(&self, track_id: u32) -> bool {
+        self.streaming
+            .lock()
+            .get(&track_id)
+            .and_then(Weak::upgrade)
+            .is_some_and(|progress| !progress.is_complete())
+    }
+
     pub async fn ensure_track_is_downloaded(&mut self, track: &Track) -> AppResult<DownloadResult> {
+        if let Some(path) = self.client.fixture_track_path(track.id) {
+            return Ok(DownloadResult::Cached(path));
+        }
+
         let track_info = self.client.track_url(track.id).await?;
 
         let cache_path = cache_path(
             track,
             &track_info.mime_type,
             track_info.sampling_rate,
+            track_info.sample,
             &self.audio_cache_dir,
         );
         self.database.set_cache_entry(cache_path.as_path()).await;
 
-        if cache_path.exists() {
+        if self.is_cached(&cache_path).await {
             tracing::info!("Playing from cache: {}", cache_path.display());
             return Ok(DownloadResult::Cached(cache_path));
         }
 
-        let stream = self.client.stream_track(cache_path, track_info).await?;
+        let stream = self
+            .client
+            .stream_track(cache_path.clone(), track_info)
+            .await?;
+        let progress = stream.progress();
+        {
+            let mut streaming = self.streaming.lock();
+            streaming.retain(|_, progress| progress.strong_count() > 0);
+            streaming.insert(track.id, Arc::downgrade(&progress));
+        }
+        self.report_progress(track.id, progress, cache_path);
 
         Ok(DownloadResult::Streaming(stream))
     }
+
+    /// Checks the size recorded when the track was downloaded. Corrupted tracks, and encrypted
+    /// tracks once encryption is disabled, are removed to be downloaded again
+    async fn is_cached(&self, cache_path: &Path) -> bool {
+        if !cache_path.exists() {
+            return false;
+        }
+
+        let expected = self
+            .database
+            .get_cache_integrity(cache_path)
+            .await
+            .ok()
+            .flatten();
+        let key = self.client.audio_cache_key();
+        let size = CacheFile::open(cache_path, key.as_ref()).map(|file| file.len());
+
+        let reason = match (size, &expected) {
+            (Ok(size), Some(integrity)) if size == integrity.size => {
+                self.verify_hash(cache_path, expected);
+                return true;
+            }
+            // Downloaded before the integrity was recorded
+            (Ok(_), None) => {
+                self.verify_hash(cache_path, None);
+                return true;
+            }
+            (Ok(size), Some(integrity)) => {
+                format!("{size} bytes instead of {}", integrity.size)
+            }
+            (Err(err), _) => format!("unreadable: {err}"),
+        };
+
+        tracing::warn!(
+            "Downloading {} again, the cached file is {reason}",
+            cache_path.display()
+        );
+        self.remove_cached(cache_path).await;
+        false
+    }
+
+    /// Hashes the cached file in the background, once per session, so playback does not wait
+    /// on reading the whole file. A file not matching the recorded hash is removed, to be
+    /// downloaded again the next time it is played
+    fn verify_hash(&self, cache_path: &Path, expected: Option<CacheIntegrity>) {
+        if !self.verified.lock().insert(cache_path.to_path_buf()) {
+            return;
+        }
+
+        let downloader = self.clone();
+        let cache_path = cache_path.to_path_buf();
+        tokio::spawn(async move {
+            match (downloader.cache_integrity(&cache_path).await, expected) {
+                (Ok(actual), Some(expected)) if actual == expected => {}
+                (Ok(actual), None) => {
+                    downloader
+                        .set_cache_integrity(&cache_path, Some(&actual))
+                        .await;
+                }
+                (result, _) => {
+                    tracing::warn!(
+                        "Removing {}, the cached file is corrupted: {result:?}",
+                        cache_path.display()
+                    );
+                    downloader.remove_cached(&cache_path).await;
+                }
+            }
+        });
+    }
+
+    async fn remove_cached(&self, cache_path: &Path) {
+        _ = tokio::fs::remove_file(cache_path).await;
+        self.set_cache_integrity(cache_path, None).await;
+    }
+
+    async fn cache_integrity(&self, cache_path: &Path) -> std::io::Result<CacheIntegrity> {
+        let key = self.client.audio_cache_key();
+        let cache_path = cache_path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let mut file = CacheFile::open(&cache_path, key.as_ref())?;
+            let mut hash = md5::Context::new();
+            let size = std::io::copy(&mut file, &mut hash)?;
+            Ok(CacheIntegrity {
+                size,
+                hash: format!("{:x}", hash.finalize()),
+            })
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+
+    async fn set_cache_integrity(&self, cache_path: &Path, integrity: Option<&CacheIntegrity>) {
+        if let Err(err) = self
+            .database
+            .set_cache_integrity(cache_path, integrity)
+            .await
+        {
+            tracing::warn!(
+                "Unable to record the integrity of {}: {err}",
+                cache_path.display()
+            );
+        }
+    }
+
+    /// Records the integrity of a downloaded track, once the cache file is written, and makes
+    /// room for it in the cache. A file that does not hold all of the downloaded audio is removed
+    async fn record_integrity(&self, cache_path: &Path, expected_size: u64) {
+        let mut attempts = 0;
+        while !cache_path.exists() {
+            attempts += 1;
+            if attempts > CACHE_WRITE_ATTEMPTS {
+                return;
+            }
+            tokio::time::sleep(PROGRESS_INTERVAL).await;
+        }
+
+        match self.cache_integrity(cache_path).await {
+            Ok(integrity) if integrity.size == expected_size => {
+                self.verified.lock().insert(cache_path.to_path_buf());
+                self.set_cache_integrity(cache_path, Some(&integrity)).await;
+                self.enforce_max_size(cache_path);
+            }
+            result => {
+                tracing::warn!(
+                    "Removing {}, it was not cached correctly: {result:?}",
+                    cache_path.display()
+                );
+                _ = tokio::fs::remove_file(cache_path).await;
+            }
+        }
+    }
+
+    /// Evicts the least recently played tracks in the background when the cache is too big.
+    /// Runs once a download is written, so its size is counted
+    fn enforce_max_size(&self, keep: &Path) {
+        let Some(max_bytes) = self.audio_cache_max_bytes else {
+            return;
+        };
+        let database = self.database.clone();
+        let eviction = self.eviction.clone();
+        let keep = keep.to_path_buf();
+
+        tokio::spawn(async move {
+            let _evicting = eviction.lock().await;
+            match audio_cache::evict_to_size(&database, max_bytes, &keep).await {
+                Ok(evicted) if !evicted.is_empty() => {
+                    tracing::info!("Evicted {} tracks from the audio cache", evicted.len());
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!("Unable to evict from the audio cache: {err}"),
+            }
+        });
+    }
+
+    /// Broadcasts the download percentage until the track is downloaded or the stream is dropped.
+    /// The integrity of the cache file is recorded once it is downloaded. A download making no
+    /// progress for the stall timeout is reported once
+    fn report_progress(&self, track_id: u32, progress: Arc<DownloadProgress>, cache_path: PathBuf) {
+        let broadcast = self.broadcast.clone();
+        let downloader = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+            let mut last_percent = None;
+            let mut last_progress = (progress.received_bytes(), Instant::now());
+            let mut stall_reported = false;
+
+            while Arc::strong_count(&progress) > 1 {
+                interval.tick().await;
+
+                let received_bytes = progress.received_bytes();
+                if received_bytes != last_progress.0 {
+                    last_progress = (received_bytes, Instant::now());
+                } else if !stall_reported
+                    && !progress.is_complete()
+                    && downloader
+                        .stall_timeout
+                        .is_some_and(|timeout| last_progress.1.elapsed() >= timeout)
+                {
+                    tracing::warn!(
+                        "Download of track {track_id} stalled at {received_bytes} bytes"
+                    );
+                    stall_reported = true;
+                    _ = downloader.stalled.send(track_id);
+                }
+
+                let percent = progress.percent();
+                if last_percent != Some(percent) {
+                    last_percent = Some(percent);
+                    broadcast.send(Notification::BufferProgress(BufferProgress {
+                        track_id,
+                        downloaded_bytes: progress.downloaded_bytes(),
+                        total_bytes: progress.total_bytes(),
+                        percent,
+                    }));
+                }
+
+                if progress.is_complete() {
+                    downloader
+                        .record_integrity(&cache_path, progress.total_bytes())
+                        .await;
+                    break;
+                }
+            }
+        });
+    }
+
+    pub async fn precache_album(&self, album_id: &str, number_of_tracks: usize) -> AppResult<()> {
+        let _permit = self.precaches.acquire().await.expect("infallible");
+        let album = self.client.album(album_id).await?;
+
+        for track in album
+            .tracks
+            .iter()
+            .filter(|t| t.available)
+            .take(number_of_tracks)
+        {
+            self.download_to_cache(track).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn precache_track(&self, track: &Track) -> AppResult<()> {
+        let _permit = self.precaches.acquire().await.expect("infallible");
+        self.download_to_cache(track).await
+    }
+
+    async fn download_to_cache(&self, track: &Track) -> AppResult<()> {
+        if self.client.fixture_track_path(track.id).is_some() {
+            return Ok(());
+        }
+
+        let track_info = self.client.track_url(track.id).await?;
+
+        let cache_path = cache_path(
+            track,
+            &track_info.mime_type,
+            track_info.sampling_rate,
+            track_info.sample,
+            &self.audio_cache_dir,
+        );
+
+        if self.is_cached(&cache_path).await {
+            return Ok(());
+        }
+
+        tracing::info!("Pre-caching: {}", track.title);
+        self.database.set_cache_entry(cache_path.as_path()).await;
+
+        let mut stream = self
+            .client
+            .stream_track(cache_path.clone(), track_info)
+            .await?;
+        let total_bytes = stream.progress().total_bytes();
+
+        // Reading the stream to the end drives the segment downloads, which writes the cache file.
+        tokio::task::spawn_blocking(move || std::io::copy(&mut stream, &mut std::io::sink()))
+            .await
+            .map_err(|e| Error::StreamError {
+                message: e.to_string(),
+            })?
+            .map_err(|e| Error::StreamError {
+                message: e.to_string(),
+            })?;
+
+        self.record_integrity(&cache_path, total_bytes).await;
+
+        Ok(())
+    }
 }
 
 fn cache_path(
     track: &Track,
     mime: &str,
     sample_rate: Option<u32>,
+    preview: bool,
     audio_cache_dir: &Path,
 ) -> PathBuf {
     let artist_name = track.artist_name.as_deref().unwrap_or("unknown");
@@ -77,12 +424,15 @@ fn cache_path(
     let extension = guess_extension(mime);
 
     let sample_rate_suffix = sample_rate.map(|sr| format!("_{sr}")).unwrap_or_default();
+    // A preview must not be played from the cache once the full track is available
+    let preview_suffix = if preview { "_preview" } else { "" };
 
     let track_file = format!(
-        "{}_{}{}.{}",
+        "{}_{}{}{}.{}",
         track.number,
         sanitize_name(track_title),
         sample_rate_suffix,
+        preview_suffix,
         extension
     );
 
@@ -135,3 +485,119 @@ fn guess_extension(mime: &str) -> String {
         _ => "unknown".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Fixtures;
+
+    async fn downloader(dir: &Path, pool: sqlx::Pool<sqlx::Sqlite>) -> Downloader {
+        std::fs::write(dir.join("catalog.json"), "{}").unwrap();
+        let client = Client::with_fixtures(Fixtures::load(dir).unwrap());
+        Downloader::new(
+            dir.to_path_buf(),
+            None,
+            Arc::new(Database::init(pool).await.unwrap()),
+            Arc::new(client),
+            Arc::new(NotificationBroadcast::new()),
+            None,
+            tokio::sync::mpsc::unbounded_channel().0,
+        )
+    }
+
+    #[sqlx::test]
+    async fn cached_tracks_are_checked_by_size_and_hashed_in_the_background(
+        pool: sqlx::Pool<sqlx::Sqlite>,
+    ) {
+        let dir = std::env::temp_dir().join(format!(
+            "qobuz-player-downloader-{}-{:?}",
+            std::process::id(),
+            Instant::now()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let downloader = downloader(&dir, pool).await;
+        let database = downloader.database.clone();
+
+        let intact = dir.join("intact.flac");
+        let truncated = dir.join("truncated.flac");
+        let corrupted = dir.join("corrupted.flac");
+        for path in [&intact, &truncated, &corrupted] {
+            std::fs::write(path, b"audio").unwrap();
+            database.set_cache_entry(path).await;
+        }
+        let integrity = CacheIntegrity {
+            size: 5,
+            hash: format!("{:x}", md5::compute(b"audio")),
+        };
+        database
+            .set_cache_integrity(&intact, Some(&integrity))
+            .await
+            .unwrap();
+        std::fs::write(&truncated, b"aud").unwrap();
+        database
+            .set_cache_integrity(&truncated, Some(&integrity))
+            .await
+            .unwrap();
+        std::fs::write(&corrupted, b"AUDIO").unwrap();
+        database
+            .set_cache_integrity(&corrupted, Some(&integrity))
+            .await
+            .unwrap();
+
+        assert!(downloader.is_cached(&intact).await);
+        assert!(!downloader.is_cached(&truncated).await);
+        assert!(!truncated.exists());
+
+        // Same size, so it plays while the hash is checked
+        assert!(downloader.is_cached(&corrupted).await);
+        let removed = tokio::time::timeout(Duration::from_secs(5), async {
+            while corrupted.exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+
+        let intact_kept = intact.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(removed.is_ok());
+        assert!(intact_kept);
+        assert_eq!(
+            database.get_cache_integrity(&corrupted).await.unwrap(),
+            None
+        );
+    }
+
+    #[sqlx::test]
+    async fn precaches_wait_for_a_free_slot(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = std::env::temp_dir().join(format!(
+            "qobuz-player-precache-{}-{:?}",
+            std::process::id(),
+            Instant::now()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let downloader = downloader(&dir, pool).await;
+        let track = Track {
+            id: 1,
+            ..Default::default()
+        };
+
+        let running = downloader
+            .precaches
+            .clone()
+            .acquire_many_owned(CONCURRENT_PRECACHES as u32)
+            .await
+            .unwrap();
+        let waited = tokio::time::timeout(
+            Duration::from_millis(100),
+            downloader.precache_track(&track),
+        )
+        .await;
+        assert!(waited.is_err());
+
+        drop(running);
+        let ran =
+            tokio::time::timeout(Duration::from_secs(5), downloader.precache_track(&track)).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(ran.is_ok());
+    }
+}
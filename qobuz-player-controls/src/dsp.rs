@@ -0,0 +1,281 @@
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use rodio::source::SeekError;
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+use crate::error::Error;
+use crate::notification::NotificationBroadcast;
+
+/// Samples written to the dsp command at a time
+const CHUNK_SAMPLES: usize = 4096;
+
+/// Chunks read ahead from the dsp command, so the output rarely waits for it
+const BUFFERED_CHUNKS: usize = 16;
+
+/// Runs the decoded audio through an external command, like a room correction or crossfeed
+/// filter. The command reads raw interleaved 32-bit float little endian samples on stdin and
+/// writes them in the same format, sample rate and channels on stdout. It is started for every
+/// track and again after seeking, with `QOBUZ_PLAYER_SAMPLE_RATE` and `QOBUZ_PLAYER_CHANNELS`
+/// set. A command that fails is reported to the UIs
+pub struct DspPipe<I> {
+    input: Arc<Mutex<I>>,
+    command: String,
+    broadcast: Arc<NotificationBroadcast>,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    total_duration: Option<Duration>,
+    process: DspProcess,
+}
+
+impl<I: Source + Send + 'static> DspPipe<I> {
+    pub fn new(
+        input: I,
+        command: String,
+        broadcast: Arc<NotificationBroadcast>,
+    ) -> io::Result<Self> {
+        let channels = input.channels();
+        let sample_rate = input.sample_rate();
+        let total_duration = input.total_duration();
+        let input = Arc::new(Mutex::new(input));
+        let process = DspProcess::spawn(
+            &command,
+            input.clone(),
+            channels,
+            sample_rate,
+            broadcast.clone(),
+        )?;
+
+        Ok(Self {
+            input,
+            command,
+            broadcast,
+            channels,
+            sample_rate,
+            total_duration,
+            process,
+        })
+    }
+}
+
+/// Running dsp command, fed from the input by one thread and read by another
+struct DspProcess {
+    child: Arc<Mutex<Child>>,
+    chunks: mpsc::Receiver<Vec<Sample>>,
+    chunk: std::vec::IntoIter<Sample>,
+    /// Stops the feeding thread before the input is seeked or dropped
+    stopped: Arc<AtomicBool>,
+}
+
+impl DspProcess {
+    fn spawn<I: Source + Send + 'static>(
+        command: &str,
+        input: Arc<Mutex<I>>,
+        channels: ChannelCount,
+        sample_rate: SampleRate,
+        broadcast: Arc<NotificationBroadcast>,
+    ) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("QOBUZ_PLAYER_SAMPLE_RATE", sample_rate.get().to_string())
+            .env("QOBUZ_PLAYER_CHANNELS", channels.get().to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let child = Arc::new(Mutex::new(child));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let feeder_stopped = stopped.clone();
+        std::thread::spawn(move || {
+            let mut bytes = Vec::with_capacity(CHUNK_SAMPLES * size_of::<Sample>());
+            loop {
+                bytes.clear();
+                {
+                    let mut input = input.lock();
+                    if feeder_stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    for sample in input.by_ref().take(CHUNK_SAMPLES) {
+                        bytes.extend_from_slice(&sample.to_le_bytes());
+                    }
+                }
+
+                // Closing stdin at the end of the track lets the command flush its output
+                if bytes.is_empty() || stdin.write_all(&bytes).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let (chunks_tx, chunks) = mpsc::sync_channel(BUFFERED_CHUNKS);
+        let reader_child = child.clone();
+        let reader_stopped = stopped.clone();
+        let command = command.to_string();
+        std::thread::spawn(move || {
+            read_chunks(stdout, &chunks_tx);
+
+            // A command killed for a seek or a skip did not fail
+            if reader_stopped.load(Ordering::Relaxed) {
+                return;
+            }
+            if let Ok(status) = reader_child.lock().wait()
+                && !status.success()
+            {
+                broadcast.report(&Error::Dsp {
+                    message: format!("{command} failed with {status}"),
+                });
+            }
+        });
+
+        Ok(Self {
+            child,
+            chunks,
+            chunk: Vec::new().into_iter(),
+            stopped,
+        })
+    }
+
+    /// Only waits when the command falls behind the output
+    fn read_sample(&mut self) -> Option<Sample> {
+        loop {
+            if let Some(sample) = self.chunk.next() {
+                return Some(sample);
+            }
+            self.chunk = self.chunks.recv().ok()?.into_iter();
+        }
+    }
+}
+
+/// Sends the samples written by the command until it closes its output
+fn read_chunks(mut stdout: ChildStdout, chunks: &mpsc::SyncSender<Vec<Sample>>) {
+    let mut bytes = vec![0; CHUNK_SAMPLES * size_of::<Sample>()];
+    // Bytes of a sample split over two reads
+    let mut partial = 0;
+
+    loop {
+        let read = match stdout.read(&mut bytes[partial..]) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => partial + read,
+        };
+        let whole = read - read % size_of::<Sample>();
+
+        let samples = bytes[..whole]
+            .chunks_exact(size_of::<Sample>())
+            .map(|sample| Sample::from_le_bytes(sample.try_into().expect("sized chunk")))
+            .collect();
+        if chunks.send(samples).is_err() {
+            return;
+        }
+
+        bytes.copy_within(whole..read, 0);
+        partial = read - whole;
+    }
+}
+
+impl Drop for DspProcess {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        let mut child = self.child.lock();
+        _ = child.kill();
+        _ = child.wait();
+    }
+}
+
+impl<I: Source + Send + 'static> Iterator for DspPipe<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.process.read_sample()
+    }
+}
+
+impl<I: Source + Send + 'static> Source for DspPipe<I> {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    /// The command is restarted from the new position, so it does not play buffered audio
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.process.stopped.store(true, Ordering::Relaxed);
+        self.input.lock().try_seek(pos)?;
+
+        self.process = DspProcess::spawn(
+            &self.command,
+            self.input.clone(),
+            self.channels,
+            self.sample_rate,
+            self.broadcast.clone(),
+        )
+        .map_err(|err| SeekError::Other(Arc::new(err)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+    use std::sync::Arc;
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::DspPipe;
+    use crate::notification::{Notification, NotificationBroadcast};
+
+    fn buffer(samples: Vec<f32>) -> SamplesBuffer {
+        SamplesBuffer::new(
+            NonZero::new(2).unwrap(),
+            NonZero::new(44100).unwrap(),
+            samples,
+        )
+    }
+
+    #[test]
+    fn audio_passes_through_the_command() {
+        let samples: Vec<_> = (0..10_000).map(|sample| sample as f32 / 10_000.0).collect();
+        let broadcast = Arc::new(NotificationBroadcast::new());
+
+        let played: Vec<_> = DspPipe::new(buffer(samples.clone()), "cat".to_string(), broadcast)
+            .unwrap()
+            .collect();
+        assert_eq!(played, samples);
+    }
+
+    #[test]
+    fn failing_command_is_reported() {
+        let broadcast = Arc::new(NotificationBroadcast::new());
+        let mut notifications = broadcast.subscribe();
+
+        let played: Vec<_> = DspPipe::new(buffer(vec![0.5; 1000]), "exit 3".to_string(), broadcast)
+            .unwrap()
+            .collect();
+        assert!(played.is_empty());
+
+        match notifications.try_recv() {
+            Ok(Notification::Error(message)) => assert_eq!(
+                message,
+                "Unable to run the dsp command: exit 3 failed with exit status: 3"
+            ),
+            other => panic!("expected the failure to be reported, got {other:?}"),
+        }
+    }
+}
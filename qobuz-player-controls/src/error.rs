@@ -11,6 +11,15 @@ pub enum Error {
     Login {
         message: String,
     },
+    /// The stored token is expired or revoked, a new login is needed
+    #[snafu(display("The Qobuz login is no longer valid, sign in again"))]
+    InvalidCredentials,
+    #[snafu(display("Unable to reach Qobuz, check the network connection"))]
+    Network,
+    #[snafu(display("Not available in your region or with your subscription"))]
+    ContentUnavailable,
+    #[snafu(display("Not available in your region, Qobuz only offers a preview"))]
+    RegionLocked,
     #[snafu(display("Failed to seek"))]
     Seek,
     #[snafu(display("{message}"))]
@@ -23,6 +32,11 @@ pub enum Error {
     StreamError {
         message: String,
     },
+    /// The audio of the track is not in a format the decoder reads
+    #[snafu(display("Unable to decode the track: {message}"))]
+    Decode {
+        message: String,
+    },
     #[snafu(display("{message}"))]
     SinkDeviceError {
         message: String,
@@ -35,6 +49,14 @@ pub enum Error {
     MprisPropertyError {
         property: String,
     },
+    #[snafu(display("Unable to register global media keys: {message}"))]
+    HotkeyInitError {
+        message: String,
+    },
+    #[snafu(display("Unable to start media controls: {message}"))]
+    MediaControlsError {
+        message: String,
+    },
     #[snafu(display("Unable to connect to database"))]
     DatabaseConnectError,
     #[snafu(display("Unable to migrate database to latest version"))]
@@ -55,6 +77,15 @@ pub enum Error {
     GpioUnavailable {
         pin: u8,
     },
+    #[snafu(display("Hardware mixer {control} is unavailable: {message}"))]
+    HardwareMixerUnavailable {
+        control: String,
+        message: String,
+    },
+    #[snafu(display("Display is unavailable: {message}"))]
+    DisplayUnavailable {
+        message: String,
+    },
     #[snafu(display("Rfid prompt input error"))]
     RfidInputPanic,
     #[snafu(display("Port already in use: {port}"))]
@@ -63,10 +94,61 @@ pub enum Error {
     },
     #[snafu(display("Unable to reorder playlist"))]
     PlaylistReorderError,
+    #[snafu(display("Unable to import playlist: {message}"))]
+    PlaylistImport {
+        message: String,
+    },
+    #[snafu(display("Invalid Qobuz link: {message}"))]
+    InvalidLink {
+        message: String,
+    },
+    #[snafu(display("Unable to load artwork: {message}"))]
+    Artwork {
+        message: String,
+    },
     #[snafu(display("{error}"))]
     ConnectError {
         error: String,
     },
+    #[snafu(display("Unable to use the keyring: {message}"))]
+    Keyring {
+        message: String,
+    },
+    #[snafu(display("Unable to run the dsp command: {message}"))]
+    Dsp {
+        message: String,
+    },
+}
+
+/// What went wrong, for the UIs to tell the user what to do about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// Qobuz can not be reached
+    Network,
+    /// The login is missing, expired or rejected
+    Authorization,
+    /// The album, track or playlist does not exist or can not be streamed by the user
+    ContentUnavailable,
+    /// The audio output can not be opened or failed
+    AudioDevice,
+    Other,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Network => ErrorKind::Network,
+            Error::Login { .. } | Error::InvalidCredentials => ErrorKind::Authorization,
+            Error::ContentUnavailable | Error::RegionLocked | Error::InvalidLink { .. } => {
+                ErrorKind::ContentUnavailable
+            }
+            Error::SinkDeviceError { .. }
+            | Error::HardwareMixerUnavailable { .. }
+            | Error::Dsp { .. } => ErrorKind::AudioDevice,
+            _ => ErrorKind::Other,
+        }
+    }
 }
 
 impl From<sqlx::migrate::MigrateError> for Error {
@@ -107,23 +189,23 @@ impl From<rodio::source::SeekError> for Error {
 
 impl From<rodio::DeviceSinkError> for Error {
     fn from(value: rodio::DeviceSinkError) -> Self {
-        Self::StreamError {
-            message: value.to_string(),
+        Self::SinkDeviceError {
+            message: format!("Audio device is busy or unavailable: {value}"),
         }
     }
 }
 
 impl From<rodio::DevicesError> for Error {
     fn from(value: rodio::DevicesError) -> Self {
-        Self::StreamError {
-            message: value.to_string(),
+        Self::SinkDeviceError {
+            message: format!("Unable to list audio devices: {value}"),
         }
     }
 }
 
 impl From<rodio::decoder::DecoderError> for Error {
     fn from(value: rodio::decoder::DecoderError) -> Self {
-        Self::StreamError {
+        Self::Decode {
             message: value.to_string(),
         }
     }
@@ -131,16 +213,28 @@ impl From<rodio::decoder::DecoderError> for Error {
 
 impl From<reqwest::Error> for Error {
     fn from(value: reqwest::Error) -> Self {
-        Self::StreamError {
-            message: value.to_string(),
+        match value.is_connect() || value.is_timeout() {
+            true => Self::Network,
+            false => Self::StreamError {
+                message: value.to_string(),
+            },
         }
     }
 }
 
 impl From<qobuz_player_client::Error> for Error {
     fn from(value: qobuz_player_client::Error) -> Self {
-        Error::Client {
-            message: value.to_string(),
+        match value {
+            qobuz_player_client::Error::Unauthorized => Error::InvalidCredentials,
+            qobuz_player_client::Error::Network => Error::Network,
+            qobuz_player_client::Error::Unavailable { message }
+            | qobuz_player_client::Error::NotFound { message } => {
+                tracing::debug!("Qobuz says unavailable: {message}");
+                Error::ContentUnavailable
+            }
+            value => Error::Client {
+                message: value.to_string(),
+            },
         }
     }
 }
@@ -150,3 +244,40 @@ impl From<tokio::sync::broadcast::error::SendError<Notification>> for Error {
         Self::Notification
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_errors_keep_their_kind() {
+        let kind = |error: qobuz_player_client::Error| Error::from(error).kind();
+
+        assert_eq!(
+            kind(qobuz_player_client::Error::Unauthorized),
+            ErrorKind::Authorization
+        );
+        assert_eq!(
+            kind(qobuz_player_client::Error::Network),
+            ErrorKind::Network
+        );
+        assert_eq!(
+            kind(qobuz_player_client::Error::Unavailable {
+                message: "No result matching given argument".into()
+            }),
+            ErrorKind::ContentUnavailable
+        );
+        assert_eq!(
+            kind(qobuz_player_client::Error::NotFound {
+                message: "No result matching given argument".into()
+            }),
+            ErrorKind::ContentUnavailable
+        );
+        assert_eq!(
+            kind(qobuz_player_client::Error::Api {
+                message: "500 Internal Server Error".into()
+            }),
+            ErrorKind::Other
+        );
+    }
+}
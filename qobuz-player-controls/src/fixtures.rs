@@ -0,0 +1,314 @@
+use std::path::{Path, PathBuf};
+
+use qobuz_player_client::qobuz_models::TrackInfo;
+use serde::Deserialize;
+
+use crate::{
+    AppResult,
+    error::Error,
+    models::{Album, AlbumSimple, Artist, ArtistPage, Favorites, Playlist, SearchResults, Track},
+};
+
+/// Canned catalog standing in for the Qobuz api, so the player loop, the queue and the
+/// interfaces can run in tests and without an account.
+///
+/// The directory holds a `catalog.json` with albums and playlists, and the audio of each
+/// track as `tracks/<track id>.flac`, `.mp3` or `.wav`:
+///
+/// ```json
+/// {
+///   "albums": [{
+///     "id": "album-1", "title": "Album", "release_year": 2024,
+///     "artist": { "id": 1, "name": "Artist" },
+///     "tracks": [{ "id": 1, "title": "First", "duration_seconds": 3 }]
+///   }],
+///   "playlists": [{ "id": 1, "title": "Playlist", "track_ids": [1] }]
+/// }
+/// ```
+///
+/// Tracks marked `"region_locked": true` are refused like tracks locked in the region of the
+/// user. Everything in the catalog is a favorite. Anything else needing the api fails
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    albums: Vec<Album>,
+    playlists: Vec<Playlist>,
+    region_locked: Vec<u32>,
+    tracks_dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Catalog {
+    #[serde(default)]
+    albums: Vec<CatalogAlbum>,
+    #[serde(default)]
+    playlists: Vec<CatalogPlaylist>,
+}
+
+#[derive(Deserialize)]
+struct CatalogAlbum {
+    id: String,
+    title: String,
+    artist: CatalogArtist,
+    #[serde(default)]
+    release_year: u32,
+    #[serde(default)]
+    image: String,
+    tracks: Vec<CatalogTrack>,
+}
+
+#[derive(Deserialize)]
+struct CatalogArtist {
+    id: u32,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct CatalogTrack {
+    id: u32,
+    title: String,
+    duration_seconds: u32,
+    #[serde(default)]
+    region_locked: bool,
+}
+
+#[derive(Deserialize)]
+struct CatalogPlaylist {
+    id: u32,
+    title: String,
+    track_ids: Vec<u32>,
+}
+
+impl Fixtures {
+    pub fn load(dir: &Path) -> AppResult<Self> {
+        let catalog_path = dir.join("catalog.json");
+        let catalog = std::fs::read_to_string(&catalog_path).map_err(|err| Error::Client {
+            message: format!("Unable to read {}: {err}", catalog_path.display()),
+        })?;
+        let catalog: Catalog = serde_json::from_str(&catalog).map_err(|err| Error::Client {
+            message: format!("Invalid {}: {err}", catalog_path.display()),
+        })?;
+
+        let region_locked = catalog
+            .albums
+            .iter()
+            .flat_map(|album| &album.tracks)
+            .filter(|track| track.region_locked)
+            .map(|track| track.id)
+            .collect();
+        let albums: Vec<Album> = catalog.albums.into_iter().map(album).collect();
+        let tracks: Vec<&Track> = albums.iter().flat_map(|album| &album.tracks).collect();
+        let playlists = catalog
+            .playlists
+            .into_iter()
+            .map(|playlist| {
+                let tracks: Vec<Track> = playlist
+                    .track_ids
+                    .iter()
+                    .filter_map(|id| tracks.iter().find(|track| track.id == *id))
+                    .map(|track| (*track).clone())
+                    .collect();
+                Playlist {
+                    is_owned: true,
+                    title: playlist.title,
+                    duration_seconds: tracks.iter().map(|track| track.duration_seconds).sum(),
+                    tracks_count: tracks.len() as u32,
+                    id: playlist.id,
+                    image: None,
+                    tracks,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            albums,
+            playlists,
+            region_locked,
+            tracks_dir: dir.join("tracks"),
+        })
+    }
+
+    pub fn album(&self, id: &str) -> AppResult<Album> {
+        self.albums
+            .iter()
+            .find(|album| album.id == id)
+            .cloned()
+            .ok_or_else(|| not_found(format!("album {id}")))
+    }
+
+    pub fn playlist(&self, id: u32) -> AppResult<Playlist> {
+        self.playlists
+            .iter()
+            .find(|playlist| playlist.id == id)
+            .cloned()
+            .ok_or_else(|| not_found(format!("playlist {id}")))
+    }
+
+    pub fn track(&self, id: u32) -> AppResult<Track> {
+        self.albums
+            .iter()
+            .flat_map(|album| &album.tracks)
+            .find(|track| track.id == id)
+            .cloned()
+            .ok_or_else(|| not_found(format!("track {id}")))
+    }
+
+    pub fn artist_page(&self, id: u32) -> AppResult<ArtistPage> {
+        let albums: Vec<&Album> = self
+            .albums
+            .iter()
+            .filter(|album| album.artist.id == id)
+            .collect();
+        let artist = albums
+            .first()
+            .map(|album| album.artist.clone())
+            .ok_or_else(|| not_found(format!("artist {id}")))?;
+
+        Ok(ArtistPage {
+            id,
+            name: artist.name,
+            image: None,
+            top_tracks: albums
+                .iter()
+                .flat_map(|album| album.tracks.iter().cloned())
+                .collect(),
+            albums: albums.into_iter().cloned().map(Into::into).collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Albums, artists, playlists and tracks with the query in their title or name
+    pub fn search(&self, query: &str) -> SearchResults {
+        let needle = query.to_lowercase();
+        let matches = |text: &str| text.to_lowercase().contains(&needle);
+
+        let mut artists: Vec<Artist> = vec![];
+        for album in &self.albums {
+            if matches(&album.artist.name) && !artists.contains(&album.artist) {
+                artists.push(album.artist.clone());
+            }
+        }
+
+        SearchResults {
+            query: query.to_string(),
+            albums: self
+                .albums
+                .iter()
+                .filter(|album| matches(&album.title))
+                .cloned()
+                .collect(),
+            artists,
+            playlists: self
+                .playlists
+                .iter()
+                .filter(|playlist| matches(&playlist.title))
+                .cloned()
+                .collect(),
+            tracks: self
+                .albums
+                .iter()
+                .flat_map(|album| &album.tracks)
+                .filter(|track| matches(&track.title))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn favorites(&self) -> Favorites {
+        let mut artists: Vec<Artist> = vec![];
+        for album in &self.albums {
+            if !artists.contains(&album.artist) {
+                artists.push(album.artist.clone());
+            }
+        }
+
+        Favorites {
+            albums: self.albums.iter().cloned().map(AlbumSimple::from).collect(),
+            artists,
+            playlists: self.playlists.clone(),
+            tracks: vec![],
+        }
+    }
+
+    /// Audio file of the track
+    pub fn track_path(&self, id: u32) -> Option<PathBuf> {
+        ["flac", "mp3", "wav"]
+            .iter()
+            .map(|extension| self.tracks_dir.join(format!("{id}.{extension}")))
+            .find(|path| path.exists())
+    }
+
+    pub fn track_info(&self, id: u32) -> AppResult<TrackInfo> {
+        if self.region_locked.contains(&id) {
+            return Err(Error::RegionLocked);
+        }
+
+        let path = self
+            .track_path(id)
+            .ok_or_else(|| not_found(format!("audio of track {id}")))?;
+        let mime_type = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("flac") => "audio/flac",
+            Some("mp3") => "audio/mpeg",
+            _ => "audio/wav",
+        };
+
+        Ok(TrackInfo {
+            url_template: path.to_string_lossy().into_owned(),
+            mime_type: mime_type.to_string(),
+            n_segments: 1,
+            key_id: None,
+            key: None,
+            sampling_rate: None,
+            duration: None,
+            n_samples: None,
+            ..Default::default()
+        })
+    }
+}
+
+fn album(album: CatalogAlbum) -> Album {
+    let artist = Artist {
+        id: album.artist.id,
+        name: album.artist.name,
+        image: None,
+    };
+    let tracks: Vec<Track> = album
+        .tracks
+        .into_iter()
+        .enumerate()
+        .map(|(index, track)| Track {
+            id: track.id,
+            title: track.title,
+            number: index as u32 + 1,
+            available: true,
+            image: Some(album.image.clone()),
+            image_thumbnail: Some(album.image.clone()),
+            duration_seconds: track.duration_seconds,
+            artist_name: Some(artist.name.clone()),
+            artist_id: Some(artist.id),
+            album_title: Some(album.title.clone()),
+            album_id: Some(album.id.clone()),
+            ..Default::default()
+        })
+        .collect();
+
+    Album {
+        duration_seconds: tracks.iter().map(|track| track.duration_seconds).sum(),
+        total_tracks: tracks.len() as u32,
+        id: album.id,
+        title: album.title,
+        artist,
+        release_year: album.release_year,
+        available: true,
+        image_thumbnail: album.image.clone(),
+        image: album.image,
+        tracks,
+        ..Default::default()
+    }
+}
+
+fn not_found(what: String) -> Error {
+    Error::Client {
+        message: format!("No {what} in the fixtures"),
+    }
+}
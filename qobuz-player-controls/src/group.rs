@@ -0,0 +1,669 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    AppResult, PositionReceiver, Status, StatusReceiver, TracklistReceiver, constant_time_eq,
+    controls::{Controls, NewQueueItem},
+    error::Error,
+    tracklist::Tracklist,
+};
+
+/// Followers further than this from the leader seek to its position
+const MAX_DRIFT: Duration = Duration::from_millis(150);
+/// Time for playback to settle after a correction before the drift is measured again
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+/// Clock measurements kept, the one with the shortest round trip is the most accurate
+const CLOCK_SAMPLES: usize = 8;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// A queue or skip the player has not confirmed yet is sent again after this
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Part an instance plays in group playback
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupRole {
+    /// Sends the queue and position to the followers connecting on this port. Followers have
+    /// to present the same secret
+    Leader { port: u16, secret: Option<String> },
+    /// Plays the queue of the leader at this `host:port`, in sync with it
+    Follower {
+        leader: String,
+        secret: Option<String>,
+    },
+}
+
+impl Display for GroupRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupRole::Leader { port, .. } => write!(f, "leading on port {port}"),
+            GroupRole::Follower { leader, .. } => write!(f, "following {leader}"),
+        }
+    }
+}
+
+/// Newline delimited JSON messages between the leader and a follower. Times are microseconds
+/// since the unix epoch on the clock of the sender
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// First message of a follower
+    Hello {
+        secret: Option<String>,
+    },
+    Ping {
+        sent_at: i64,
+    },
+    Pong {
+        sent_at: i64,
+        leader_time: i64,
+    },
+    State(GroupState),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct GroupState {
+    /// Track id and queue id of every queue item
+    queue: Vec<(u32, u64)>,
+    current_queue_id: Option<u64>,
+    status: Status,
+    /// `None` until the position of a new track is known
+    position: Option<LeaderPosition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct LeaderPosition {
+    millis: u64,
+    /// Leader clock when the position was read
+    sampled_at: i64,
+}
+
+fn now_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as i64
+}
+
+fn queue_of(tracklist: &Tracklist) -> Vec<(u32, u64)> {
+    tracklist
+        .queue()
+        .iter()
+        .map(|item| (item.track.id, item.queue_id))
+        .collect()
+}
+
+async fn send(writer: &mut (impl AsyncWrite + Unpin), message: &Message) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await
+}
+
+/// Plays the same queue on several instances on the network. Followers get the queue as track
+/// ids, stream the tracks themselves and seek when they drift from the position of the leader,
+/// measured against its clock. Changes made on a follower are undone by the leader
+pub async fn init(
+    role: GroupRole,
+    controls: Controls,
+    tracklist_receiver: TracklistReceiver,
+    status_receiver: StatusReceiver,
+    position_receiver: PositionReceiver,
+) -> AppResult<()> {
+    match role {
+        GroupRole::Leader { port, secret } => {
+            lead(
+                port,
+                secret,
+                tracklist_receiver,
+                status_receiver,
+                position_receiver,
+            )
+            .await
+        }
+        GroupRole::Follower { leader, secret } => {
+            let mut follower = Follower::new(
+                controls,
+                tracklist_receiver,
+                status_receiver,
+                position_receiver,
+            );
+            follower.follow(&leader, secret).await;
+            Ok(())
+        }
+    }
+}
+
+async fn lead(
+    port: u16,
+    secret: Option<String>,
+    tracklist_receiver: TracklistReceiver,
+    status_receiver: StatusReceiver,
+    position_receiver: PositionReceiver,
+) -> AppResult<()> {
+    if secret.is_none() {
+        tracing::warn!(
+            "Leading group playback without --group-secret, anyone on the network can follow"
+        );
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .or(Err(Error::PortInUse { port }))?;
+
+    loop {
+        let (stream, address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                tracing::warn!("Unable to accept group follower: {err}");
+                continue;
+            }
+        };
+
+        let leader = Leader {
+            tracklist_receiver: tracklist_receiver.clone(),
+            status_receiver: status_receiver.clone(),
+            position_receiver: position_receiver.clone(),
+            current_queue_id: None,
+            position: None,
+        };
+        let secret = secret.clone();
+        tokio::spawn(async move {
+            match leader.serve(stream, secret.as_deref()).await {
+                Ok(()) => tracing::info!("Group follower {address} disconnected"),
+                Err(err) => tracing::info!("Group follower {address} disconnected: {err}"),
+            }
+        });
+    }
+}
+
+/// Connection to one follower
+struct Leader {
+    tracklist_receiver: TracklistReceiver,
+    status_receiver: StatusReceiver,
+    position_receiver: PositionReceiver,
+    current_queue_id: Option<u64>,
+    position: Option<LeaderPosition>,
+}
+
+impl Leader {
+    fn sample_position(&mut self) {
+        self.position = Some(LeaderPosition {
+            millis: self.position_receiver.borrow_and_update().as_millis() as u64,
+            sampled_at: now_micros(),
+        });
+    }
+
+    fn state(&self) -> GroupState {
+        let tracklist = self.tracklist_receiver.borrow();
+        GroupState {
+            queue: queue_of(&tracklist),
+            current_queue_id: tracklist.current_queue_id(),
+            status: *self.status_receiver.borrow(),
+            position: self.position,
+        }
+    }
+
+    /// Sends the state whenever the queue, status or position changes, and answers pings.
+    /// Followers without the secret are turned away
+    async fn serve(mut self, stream: TcpStream, secret: Option<&str>) -> io::Result<()> {
+        let address = stream.peer_addr()?;
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        let hello = lines.next_line().await?.unwrap_or_default();
+        match serde_json::from_str(&hello) {
+            Ok(Message::Hello { secret: sent }) if secret_matches(sent.as_deref(), secret) => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "wrong group secret",
+                ));
+            }
+        }
+        tracing::info!("Group follower {address} connected");
+
+        self.current_queue_id = self
+            .tracklist_receiver
+            .borrow_and_update()
+            .current_queue_id();
+        self.status_receiver.mark_unchanged();
+        self.sample_position();
+        send(&mut writer, &Message::State(self.state())).await?;
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let Some(line) = line? else {
+                        return Ok(());
+                    };
+                    if let Ok(Message::Ping { sent_at }) = serde_json::from_str(&line) {
+                        let pong = Message::Pong { sent_at, leader_time: now_micros() };
+                        send(&mut writer, &pong).await?;
+                    }
+                    continue;
+                }
+                changed = self.position_receiver.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    self.sample_position();
+                }
+                changed = self.status_receiver.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    self.status_receiver.mark_unchanged();
+                    // The position is not reported while paused, so it still holds on resume
+                    if let Some(position) = &mut self.position {
+                        position.sampled_at = now_micros();
+                    }
+                }
+                changed = self.tracklist_receiver.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let current_queue_id = self.tracklist_receiver.borrow_and_update().current_queue_id();
+                    // The position of the new track is reported with the next tick
+                    if current_queue_id != self.current_queue_id {
+                        self.current_queue_id = current_queue_id;
+                        self.position = None;
+                    }
+                }
+            }
+
+            send(&mut writer, &Message::State(self.state())).await?;
+        }
+    }
+}
+
+/// Offset of the leader clock from the follower clock, measured NTP style
+#[derive(Debug, Default)]
+struct ClockSync {
+    /// Round trip and offset of the latest measurements, in microseconds
+    samples: VecDeque<(i64, i64)>,
+}
+
+impl ClockSync {
+    fn add(&mut self, sent_at: i64, leader_time: i64, received_at: i64) {
+        let round_trip = received_at - sent_at;
+        let offset = leader_time - (sent_at + received_at) / 2;
+
+        self.samples.push_back((round_trip, offset));
+        if self.samples.len() > CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    fn offset(&self) -> Option<i64> {
+        self.samples
+            .iter()
+            .min_by_key(|(round_trip, _)| *round_trip)
+            .map(|(_, offset)| *offset)
+    }
+
+    /// Where the leader is now, extrapolated from the position it reported
+    fn leader_position(&self, state: &GroupState) -> Option<Duration> {
+        let position = state.position?;
+        if state.status != Status::Playing {
+            return Some(Duration::from_millis(position.millis));
+        }
+
+        let leader_now = now_micros() + self.offset()?;
+        let elapsed = (leader_now - position.sampled_at).max(0) as u64;
+        Some(Duration::from_millis(position.millis) + Duration::from_micros(elapsed))
+    }
+}
+
+/// Change sent to the player that it has not confirmed yet
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Queue(Vec<(u32, u64)>),
+    Skip(u64),
+}
+
+struct Follower {
+    controls: Controls,
+    tracklist_receiver: TracklistReceiver,
+    status_receiver: StatusReceiver,
+    position_receiver: PositionReceiver,
+    clock: ClockSync,
+    /// Own position and when it was reported. `None` until it is reported for the current
+    /// track and status
+    position: Option<(Duration, Instant)>,
+    /// Drift is not corrected before this
+    settled_at: Instant,
+    /// Latest step and when it was sent, not repeated while the player works on it
+    pending: Option<(Step, Instant)>,
+}
+
+impl Follower {
+    fn new(
+        controls: Controls,
+        tracklist_receiver: TracklistReceiver,
+        status_receiver: StatusReceiver,
+        position_receiver: PositionReceiver,
+    ) -> Self {
+        Self {
+            controls,
+            tracklist_receiver,
+            status_receiver,
+            position_receiver,
+            clock: ClockSync::default(),
+            position: None,
+            settled_at: Instant::now(),
+            pending: None,
+        }
+    }
+
+    async fn follow(&mut self, leader: &str, secret: Option<String>) {
+        loop {
+            match TcpStream::connect(leader).await {
+                Ok(stream) => {
+                    tracing::info!("Following group leader {leader}");
+                    if let Err(err) = self.run(stream, secret.clone()).await {
+                        tracing::warn!("Lost group leader {leader}: {err}");
+                    }
+                }
+                Err(err) => tracing::warn!("Unable to connect to group leader {leader}: {err}"),
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn run(&mut self, stream: TcpStream, secret: Option<String>) -> io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        self.clock = ClockSync::default();
+        self.pending = None;
+        send(&mut writer, &Message::Hello { secret }).await?;
+
+        loop {
+            tokio::select! {
+                _ = ping.tick() => {
+                    send(&mut writer, &Message::Ping { sent_at: now_micros() }).await?;
+                }
+                line = lines.next_line() => {
+                    let Some(line) = line? else {
+                        return Ok(());
+                    };
+                    match serde_json::from_str(&line) {
+                        Ok(Message::Pong { sent_at, leader_time }) => {
+                            self.clock.add(sent_at, leader_time, now_micros());
+                        }
+                        Ok(Message::State(state)) => self.apply(&state),
+                        Ok(Message::Ping { .. } | Message::Hello { .. }) => {}
+                        Err(err) => tracing::warn!("Invalid group message: {err}"),
+                    }
+                }
+                changed = self.position_receiver.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    self.position = Some((*self.position_receiver.borrow_and_update(), Instant::now()));
+                }
+                changed = self.status_receiver.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    self.status_receiver.mark_unchanged();
+                    self.position = None;
+                }
+                changed = self.tracklist_receiver.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    self.tracklist_receiver.mark_unchanged();
+                    self.position = None;
+                    self.pending = None;
+                }
+            }
+        }
+    }
+
+    fn settle(&mut self) {
+        self.settled_at = Instant::now() + SETTLE_TIME;
+    }
+
+    /// Whether the step has to be sent. The same step is not sent again until the tracklist
+    /// changes or the player has had time to confirm it
+    fn should_send(&mut self, step: Step) -> bool {
+        if let Some((pending, sent_at)) = &self.pending
+            && *pending == step
+            && sent_at.elapsed() < RETRY_AFTER
+        {
+            return false;
+        }
+
+        self.pending = Some((step, Instant::now()));
+        true
+    }
+
+    /// Brings the queue, current track, status and position in line with the leader, one
+    /// step at a time as each step is confirmed by the player
+    fn apply(&mut self, state: &GroupState) {
+        let tracklist = self.tracklist_receiver.borrow().clone();
+
+        if queue_of(&tracklist) != state.queue {
+            if !self.should_send(Step::Queue(state.queue.clone())) {
+                return;
+            }
+            match state.queue.is_empty() {
+                true => self.controls.clear_queue(),
+                false => {
+                    let items = state
+                        .queue
+                        .iter()
+                        .map(|&(track_id, queue_id)| NewQueueItem { track_id, queue_id })
+                        .collect();
+                    self.controls.new_queue(items, false);
+                }
+            }
+            self.settle();
+            return;
+        }
+
+        let Some(index) = state.current_queue_id.and_then(|current_queue_id| {
+            state
+                .queue
+                .iter()
+                .position(|&(_, queue_id)| queue_id == current_queue_id)
+        }) else {
+            return;
+        };
+
+        if tracklist.current_queue_id() != state.current_queue_id {
+            if let Some(current_queue_id) = state.current_queue_id
+                && !self.should_send(Step::Skip(current_queue_id))
+            {
+                return;
+            }
+            self.controls.skip_to_position(index, true);
+            self.settle();
+            return;
+        }
+        self.pending = None;
+
+        let status = *self.status_receiver.borrow();
+        match (state.status, status) {
+            (Status::Playing, Status::Paused) => {
+                self.controls.play();
+                self.settle();
+                return;
+            }
+            (Status::Paused, Status::Playing | Status::Buffering) => {
+                // Seeking resumes playback, so it goes first
+                if let Some(position) = self.clock.leader_position(state) {
+                    self.controls.seek(position);
+                }
+                self.controls.pause();
+                self.settle();
+                return;
+            }
+            _ => {}
+        }
+
+        if state.status != Status::Playing
+            || status != Status::Playing
+            || Instant::now() < self.settled_at
+        {
+            return;
+        }
+
+        let (Some(expected), Some((position, reported_at))) =
+            (self.clock.leader_position(state), self.position)
+        else {
+            return;
+        };
+
+        let actual = position + reported_at.elapsed();
+        if expected.abs_diff(actual) > MAX_DRIFT {
+            tracing::info!("Drifted {actual:?} from the group leader at {expected:?}, seeking");
+            self.controls.seek(expected);
+            self.settle();
+        }
+    }
+}
+
+/// The secret a follower sent against the one of the leader, compared in constant time
+fn secret_matches(sent: Option<&str>, secret: Option<&str>) -> bool {
+    match (sent, secret) {
+        (Some(sent), Some(secret)) => constant_time_eq(sent, secret),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::{mpsc, watch};
+
+    use super::{
+        ClockSync, Follower, GroupState, LeaderPosition, Message, now_micros, secret_matches,
+    };
+    use crate::{
+        Status,
+        controls::{ControlCommand, Controls},
+        models::{Track, TrackStatus},
+        tracklist::{QueueItem, Tracklist, TracklistType},
+    };
+
+    fn tracklist(queue: &[(u32, u64)], playing: Option<u64>) -> Tracklist {
+        let items = queue
+            .iter()
+            .enumerate()
+            .map(|(index, &(id, queue_id))| QueueItem {
+                track: Track {
+                    id,
+                    status: match Some(queue_id) == playing {
+                        true => TrackStatus::Playing,
+                        false => TrackStatus::Unplayed,
+                    },
+                    ..Default::default()
+                },
+                queue_id,
+                index,
+                queued_by_user: false,
+            })
+            .collect();
+        Tracklist::new(TracklistType::Tracks, items)
+    }
+
+    #[test]
+    fn steps_are_sent_once_until_the_tracklist_changes() {
+        let (tx, mut commands) = mpsc::unbounded_channel();
+        let (tracklist_tx, tracklist_receiver) = watch::channel(Tracklist::default());
+        let (_status_tx, status_receiver) = watch::channel(Status::Paused);
+        let (_position_tx, position_receiver) = watch::channel(Duration::ZERO);
+        let mut follower = Follower::new(
+            Controls::new(tx),
+            tracklist_receiver,
+            status_receiver,
+            position_receiver,
+        );
+
+        let queue = vec![(10, 1), (20, 2)];
+        let state = GroupState {
+            queue: queue.clone(),
+            current_queue_id: Some(2),
+            status: Status::Paused,
+            position: None,
+        };
+
+        // The player is still loading the queue when the next states arrive
+        follower.apply(&state);
+        follower.apply(&state);
+        assert!(matches!(
+            commands.try_recv(),
+            Ok(ControlCommand::NewQueue { play: false, .. })
+        ));
+        assert!(commands.try_recv().is_err());
+
+        tracklist_tx.send(tracklist(&queue, None)).unwrap();
+        follower.apply(&state);
+        follower.apply(&state);
+        assert!(matches!(
+            commands.try_recv(),
+            Ok(ControlCommand::SkipToPosition {
+                new_position: 1,
+                force: true
+            })
+        ));
+        assert!(commands.try_recv().is_err());
+
+        tracklist_tx.send(tracklist(&queue, Some(2))).unwrap();
+        follower.apply(&state);
+        assert!(follower.pending.is_none());
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[test]
+    fn clock_offset_is_taken_from_the_shortest_round_trip() {
+        let mut clock = ClockSync::default();
+        assert_eq!(clock.offset(), None);
+
+        // Leader clock 5 seconds ahead, with a slow reply that makes the offset look larger
+        clock.add(1_000_000, 6_400_000, 1_600_000);
+        clock.add(2_000_000, 7_002_000, 2_004_000);
+        assert_eq!(clock.offset(), Some(5_000_000));
+
+        let state = GroupState {
+            queue: vec![(1, 1)],
+            current_queue_id: Some(1),
+            status: Status::Playing,
+            position: Some(LeaderPosition {
+                millis: 10_000,
+                sampled_at: now_micros() + 5_000_000 - 2_000_000,
+            }),
+        };
+        let position = clock.leader_position(&state).unwrap().as_millis();
+        assert!((12_000..12_100).contains(&position), "{position}");
+
+        let line = serde_json::to_string(&Message::State(state.clone())).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Message>(&line).unwrap(),
+            Message::State(state)
+        );
+    }
+
+    #[test]
+    fn followers_need_the_secret_of_the_leader() {
+        assert!(secret_matches(Some("hunter2"), Some("hunter2")));
+        assert!(secret_matches(None, None));
+        assert!(!secret_matches(Some("hunter3"), Some("hunter2")));
+        assert!(!secret_matches(None, Some("hunter2")));
+        assert!(!secret_matches(Some("hunter2"), None));
+    }
+}
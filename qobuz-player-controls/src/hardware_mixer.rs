@@ -0,0 +1,60 @@
+use crate::{AppResult, error::Error};
+
+/// Playback volume element of an ALSA mixer, set in place of software attenuation
+/// so the samples reach the DAC untouched
+#[derive(Debug, Clone)]
+pub struct HardwareMixer {
+    device: String,
+    control: String,
+}
+
+impl HardwareMixer {
+    /// Verify the element exists and has a playback volume before using it
+    pub fn open(device: String, control: String) -> AppResult<Self> {
+        let mixer = Self { device, control };
+        mixer.playback_volume(None)?;
+        Ok(mixer)
+    }
+
+    /// Set the volume level (0.0-1.0) linearly across the raw range of the element
+    pub fn set_volume(&self, volume: f32) -> AppResult<()> {
+        self.playback_volume(Some(volume.clamp(0.0, 1.0)))
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::HardwareMixerUnavailable {
+            control: self.control.clone(),
+            message,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn playback_volume(&self, volume: Option<f32>) -> AppResult<()> {
+        use alsa::mixer::{Mixer, SelemId};
+
+        let mixer = Mixer::new(&self.device, false)
+            .map_err(|e| self.error(format!("unable to open {}: {e}", self.device)))?;
+        let selem = mixer
+            .find_selem(&SelemId::new(&self.control, 0))
+            .ok_or_else(|| self.error(format!("no such element on {}", self.device)))?;
+
+        if !selem.has_playback_volume() {
+            return Err(self.error("element has no playback volume".to_string()));
+        }
+
+        if let Some(volume) = volume {
+            let (min, max) = selem.get_playback_volume_range();
+            let value = min + ((max - min) as f64 * volume as f64).round() as i64;
+            selem
+                .set_playback_volume_all(value)
+                .map_err(|e| self.error(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn playback_volume(&self, _volume: Option<f32>) -> AppResult<()> {
+        Err(self.error("ALSA mixers are only available on Linux".to_string()))
+    }
+}
@@ -0,0 +1,260 @@
+use tokio::{process::Command, sync::broadcast::error::RecvError};
+
+use crate::{
+    Status, StatusReceiver, TracklistReceiver, TransitionReceiver, models::Track,
+    tracklist::Tracklist, transitions::TransitionEvent,
+};
+
+/// Shell commands run on playback events, with the track in `QOBUZ_PLAYER_*` environment
+/// variables
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    pub on_track_change: Option<String>,
+    pub on_play: Option<String>,
+    pub on_pause: Option<String>,
+    pub on_stop: Option<String>,
+}
+
+impl Hooks {
+    pub fn count(&self) -> usize {
+        [
+            &self.on_track_change,
+            &self.on_play,
+            &self.on_pause,
+            &self.on_stop,
+        ]
+        .into_iter()
+        .flatten()
+        .count()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HookEvent {
+    TrackChange,
+    Play,
+    Pause,
+    Stop,
+}
+
+impl HookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::TrackChange => "track_change",
+            HookEvent::Play => "play",
+            HookEvent::Pause => "pause",
+            HookEvent::Stop => "stop",
+        }
+    }
+}
+
+/// Playback as far as the hooks know it, to turn receiver updates into events
+#[derive(Debug, Default)]
+struct HookState {
+    playing: bool,
+    has_track: bool,
+    /// Track the player started, reported once the tracklist shows it as playing
+    started: Option<u32>,
+}
+
+impl HookState {
+    /// Events for the latest tracklist and status
+    fn update(&mut self, tracklist: &Tracklist, status: Status) -> Vec<HookEvent> {
+        let mut events = vec![];
+
+        if let Some(track_id) = self.started
+            && tracklist.currently_playing() == Some(track_id)
+        {
+            self.started = None;
+            events.push(HookEvent::TrackChange);
+        }
+
+        let has_track = tracklist.current_queue_id().is_some();
+        if self.has_track && !has_track {
+            events.push(HookEvent::Stop);
+        }
+
+        match status {
+            Status::Playing if !self.playing => events.push(HookEvent::Play),
+            Status::Paused if self.playing && has_track => events.push(HookEvent::Pause),
+            _ => {}
+        }
+
+        if status != Status::Buffering {
+            self.playing = status == Status::Playing;
+        }
+        self.has_track = has_track;
+        events
+    }
+}
+
+/// Runs the hooks when another track starts, when playback starts or pauses and when the
+/// queue runs out or is cleared
+pub async fn init(
+    hooks: Hooks,
+    mut tracklist_receiver: TracklistReceiver,
+    mut status_receiver: StatusReceiver,
+    mut transition_receiver: TransitionReceiver,
+) {
+    if hooks.count() == 0 {
+        return;
+    }
+
+    let mut state = {
+        let tracklist = tracklist_receiver.borrow_and_update();
+        HookState {
+            playing: *status_receiver.borrow_and_update() == Status::Playing,
+            has_track: tracklist.current_queue_id().is_some(),
+            started: None,
+        }
+    };
+
+    loop {
+        tokio::select! {
+            // The player announces a start before it updates the tracklist and status
+            biased;
+            event = transition_receiver.recv() => match event {
+                Ok(TransitionEvent::TrackStarted { track_id, .. }) => state.started = Some(track_id),
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+            changed = tracklist_receiver.changed() => if changed.is_err() { return },
+            changed = status_receiver.changed() => if changed.is_err() { return },
+        }
+
+        let tracklist = tracklist_receiver.borrow_and_update().clone();
+        let status = *status_receiver.borrow_and_update();
+
+        for event in state.update(&tracklist, status) {
+            let command = match event {
+                HookEvent::TrackChange => &hooks.on_track_change,
+                HookEvent::Play => &hooks.on_play,
+                HookEvent::Pause => &hooks.on_pause,
+                HookEvent::Stop => &hooks.on_stop,
+            };
+            run(command, event, &tracklist);
+        }
+    }
+}
+
+fn run(command: &Option<String>, event: HookEvent, tracklist: &Tracklist) {
+    let Some(command) = command else {
+        return;
+    };
+
+    let mut process = Command::new("sh");
+    process
+        .arg("-c")
+        .arg(command)
+        .env("QOBUZ_PLAYER_EVENT", event.name())
+        .env(
+            "QOBUZ_PLAYER_QUEUE_POSITION",
+            tracklist.current_position().to_string(),
+        )
+        .env("QOBUZ_PLAYER_QUEUE_LENGTH", tracklist.total().to_string());
+    if let Some(track) = tracklist.current_track() {
+        process.envs(track_variables(track));
+    }
+
+    let command = command.clone();
+    tokio::spawn(async move {
+        match process.status().await {
+            Ok(status) if status.success() => {
+                tracing::debug!("Hook {} ran: {command}", event.name())
+            }
+            Ok(status) => tracing::warn!("Hook {} failed with {status}: {command}", event.name()),
+            Err(err) => tracing::warn!("Unable to run hook {}: {command}: {err}", event.name()),
+        }
+    });
+}
+
+fn track_variables(track: &Track) -> Vec<(&'static str, String)> {
+    let mut variables = vec![
+        ("QOBUZ_PLAYER_TRACK_ID", track.id.to_string()),
+        ("QOBUZ_PLAYER_TITLE", track.title.clone()),
+        (
+            "QOBUZ_PLAYER_DURATION_SECONDS",
+            track.duration_seconds.to_string(),
+        ),
+    ];
+    if let Some(artist) = &track.artist_name {
+        variables.push(("QOBUZ_PLAYER_ARTIST", artist.clone()));
+    }
+    if let Some(album) = &track.album_title {
+        variables.push(("QOBUZ_PLAYER_ALBUM", album.clone()));
+    }
+    if let Some(image) = &track.image {
+        variables.push(("QOBUZ_PLAYER_IMAGE", image.clone()));
+    }
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HookEvent, HookState};
+    use crate::{
+        Status,
+        models::{Track, TrackStatus},
+        tracklist::{QueueItem, Tracklist, TracklistType},
+    };
+
+    /// Queue of the track ids with queue ids counting from 0, like every new album
+    fn tracklist(track_ids: &[u32], playing: Option<usize>) -> Tracklist {
+        let items = track_ids
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| QueueItem {
+                track: Track {
+                    id,
+                    status: match Some(index) == playing {
+                        true => TrackStatus::Playing,
+                        false => TrackStatus::Unplayed,
+                    },
+                    ..Default::default()
+                },
+                queue_id: index as u64,
+                index,
+                queued_by_user: false,
+            })
+            .collect();
+        Tracklist::new(TracklistType::Tracks, items)
+    }
+
+    #[test]
+    fn new_album_at_the_same_position_changes_the_track() {
+        let mut state = HookState::default();
+        let first_album = tracklist(&[1, 2], Some(0));
+        let second_album = tracklist(&[3, 4], Some(0));
+
+        state.started = Some(1);
+        assert_eq!(
+            state.update(&first_album, Status::Playing),
+            vec![HookEvent::TrackChange, HookEvent::Play]
+        );
+        assert_eq!(state.update(&first_album, Status::Playing), vec![]);
+
+        // Started before the tracklist of the new album is sent
+        state.started = Some(3);
+        assert_eq!(state.update(&first_album, Status::Playing), vec![]);
+        assert_eq!(
+            state.update(&second_album, Status::Playing),
+            vec![HookEvent::TrackChange]
+        );
+    }
+
+    #[test]
+    fn pause_play_and_stop() {
+        let mut state = HookState::default();
+        let album = tracklist(&[1, 2], Some(1));
+
+        assert_eq!(state.update(&album, Status::Buffering), vec![]);
+        assert_eq!(state.update(&album, Status::Playing), vec![HookEvent::Play]);
+        assert_eq!(state.update(&album, Status::Paused), vec![HookEvent::Pause]);
+        assert_eq!(state.update(&album, Status::Playing), vec![HookEvent::Play]);
+        assert_eq!(
+            state.update(&tracklist(&[1, 2], None), Status::Paused),
+            vec![HookEvent::Stop]
+        );
+        assert_eq!(state.update(&Tracklist::default(), Status::Paused), vec![]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+use qobuz_player_client::stream::cache_encryption::CacheKey;
+
+use crate::{AppResult, error::Error};
+
+const SERVICE: &str = "qobuz-player";
+const ACCOUNT: &str = "audio-cache-key";
+
+/// The key encrypting the audio cache of this install, created and stored in the keyring of
+/// the user the first time. Uses `secret-tool` on Linux and `security` on macOS
+pub(crate) fn audio_cache_key() -> AppResult<CacheKey> {
+    if let Some(secret) = lookup()? {
+        // A new key would leave the cached tracks unreadable
+        return decode(&secret).ok_or_else(|| Error::Keyring {
+            message: "The audio cache key in the keyring is not valid".into(),
+        });
+    }
+
+    let key: CacheKey = rand::random();
+    store(&encode(&key))?;
+    tracing::info!("Created an audio cache key in the keyring");
+    Ok(key)
+}
+
+/// `None` when the keyring has no key yet
+fn lookup() -> AppResult<Option<String>> {
+    let output = run(&mut lookup_command(), None)?;
+    match output.status.success() {
+        true => Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        )),
+        false
+            if is_missing(
+                output.status.code(),
+                &String::from_utf8_lossy(&output.stderr),
+            ) =>
+        {
+            Ok(None)
+        }
+        false => Err(failure("Reading the audio cache key failed", &output)),
+    }
+}
+
+fn store(secret: &str) -> AppResult<()> {
+    let (mut command, input) = store_command(secret);
+    let output = run(&mut command, Some(&input))?;
+    // `security -i` succeeds when a command it reads fails, but reports it on stderr
+    match output.status.success() && output.stderr.is_empty() {
+        true => Ok(()),
+        false => Err(failure("Storing the audio cache key failed", &output)),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn lookup_command() -> Command {
+    let mut command = Command::new("security");
+    command.args(["find-generic-password", "-s", SERVICE, "-a", ACCOUNT, "-w"]);
+    command
+}
+
+/// The secret is written to the interactive mode of `security`, so it is not visible in the
+/// arguments of the process
+#[cfg(target_os = "macos")]
+fn store_command(secret: &str) -> (Command, String) {
+    let mut command = Command::new("security");
+    command.arg("-i");
+    let input = format!("add-generic-password -s {SERVICE} -a {ACCOUNT} -w {secret}\n");
+    (command, input)
+}
+
+/// `security` exits with errSecItemNotFound
+#[cfg(target_os = "macos")]
+fn is_missing(code: Option<i32>, _stderr: &str) -> bool {
+    code == Some(44)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn lookup_command() -> Command {
+    let mut command = Command::new("secret-tool");
+    command.args(["lookup", "service", SERVICE, "account", ACCOUNT]);
+    command
+}
+
+#[cfg(not(target_os = "macos"))]
+fn store_command(secret: &str) -> (Command, String) {
+    let mut command = Command::new("secret-tool");
+    command.args([
+        "store",
+        "--label=Qobuz Player audio cache key",
+        "service",
+        SERVICE,
+        "account",
+        ACCOUNT,
+    ]);
+    (command, secret.to_string())
+}
+
+/// `secret-tool` exits with 1 for a missing secret and for errors, but only reports errors
+#[cfg(not(target_os = "macos"))]
+fn is_missing(code: Option<i32>, stderr: &str) -> bool {
+    code == Some(1) && stderr.trim().is_empty()
+}
+
+fn run(command: &mut Command, stdin: Option<&str>) -> AppResult<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::Keyring {
+            message: format!("{:?} is not available: {err}", command.get_program()),
+        })?;
+
+    if let Some(input) = stdin
+        && let Some(mut pipe) = child.stdin.take()
+    {
+        pipe.write_all(input.as_bytes())
+            .map_err(|err| Error::Keyring {
+                message: err.to_string(),
+            })?;
+    }
+
+    child.wait_with_output().map_err(|err| Error::Keyring {
+        message: err.to_string(),
+    })
+}
+
+fn failure(message: &str, output: &Output) -> Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Error::Keyring {
+        message: match stderr.trim() {
+            "" => format!("{message} ({})", output.status),
+            stderr => format!("{message}: {stderr}"),
+        },
+    }
+}
+
+fn encode(key: &CacheKey) -> String {
+    key.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode(secret: &str) -> Option<CacheKey> {
+    let bytes: Vec<u8> = (0..secret.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(secret.get(index..index + 2)?, 16).ok())
+        .collect::<Option<_>>()?;
+    bytes.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, store_command};
+
+    #[test]
+    fn key_round_trips_through_the_secret() {
+        let key = [
+            0, 1, 127, 128, 254, 255, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+        ];
+        assert_eq!(decode(&encode(&key)), Some(key));
+        assert_eq!(decode("00ff"), None);
+        assert_eq!(decode("not hex"), None);
+    }
+
+    #[test]
+    fn secret_is_not_an_argument() {
+        let (command, input) = store_command("00ff");
+        assert!(command.get_args().all(|arg| arg != "00ff"));
+        assert!(input.contains("00ff"));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn missing_secret_is_told_apart_from_a_failure() {
+        use super::is_missing;
+
+        assert!(is_missing(Some(1), ""));
+        assert!(!is_missing(
+            Some(1),
+            "Cannot autolaunch D-Bus without X11 $DISPLAY"
+        ));
+        assert!(!is_missing(None, ""));
+    }
+}
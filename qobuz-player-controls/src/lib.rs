@@ -4,26 +4,58 @@ use std::time::Duration;
 use tokio::sync::{broadcast, watch};
 
 pub use qobuz_player_client::client::AudioQuality;
+pub use qobuz_player_client::stream::flac_source_stream::partial_segments_dir;
+pub use sink::{output_device_available, output_device_names};
 
+pub mod alarms;
+pub mod artwork_cache;
+pub mod audio_backend;
+pub mod audio_cache;
+mod channel_mix;
 pub mod client;
 pub mod controls;
 pub mod database;
+pub mod diagnostics;
 mod downloader;
+mod dsp;
 pub mod error;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+pub mod group;
+pub mod hardware_mixer;
+pub mod hooks;
+mod keyring;
+pub mod log_buffer;
 pub mod models;
+pub mod network_monitor;
 pub mod notification;
+mod pcm_output;
 pub mod player;
+pub mod playlist_import;
+pub mod profile;
+pub mod quiet_hours;
+pub mod release_notifications;
+mod silence_trim;
 mod simple_cache;
 mod sink;
+pub mod smart_playlists;
 mod stderr_redirect;
 pub mod tracklist;
+pub mod transitions;
+pub mod webhooks;
+pub mod zones;
 
 pub type AppResult<T, E = Error> = std::result::Result<T, E>;
 
 pub type PositionReceiver = watch::Receiver<Duration>;
 pub type VolumeReceiver = watch::Receiver<f32>;
+pub type MutedReceiver = watch::Receiver<bool>;
 pub type StatusReceiver = watch::Receiver<Status>;
 pub type TracklistReceiver = watch::Receiver<Tracklist>;
+pub type ZonesReceiver = watch::Receiver<Vec<zones::Zone>>;
+/// Quiet hours while they are in effect
+pub type QuietHoursReceiver = watch::Receiver<Option<quiet_hours::QuietHours>>;
+pub type TransitionReceiver = broadcast::Receiver<transitions::TransitionEvent>;
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum Status {
@@ -33,6 +65,27 @@ pub enum Status {
     Paused,
 }
 
+/// Mapping from the volume level to the output gain
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum VolumeCurve {
+    Linear,
+    #[default]
+    Cubic,
+    /// Always output at full volume. Useful when volume is controlled by an external DAC or amplifier
+    Fixed,
+}
+
+impl VolumeCurve {
+    pub fn gain(&self, volume: f32) -> f32 {
+        let volume = volume.clamp(0.0, 1.0);
+        match self {
+            VolumeCurve::Linear => volume,
+            VolumeCurve::Cubic => volume.powi(3),
+            VolumeCurve::Fixed => 1.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum Notification {
     Error(String),
@@ -43,3 +96,26 @@ pub enum Notification {
 
 pub type ExitReceiver = broadcast::Receiver<bool>;
 pub type ExitSender = broadcast::Sender<bool>;
+
+/// Compares every byte, so the time taken does not tell how much of a guess was right
+pub fn constant_time_eq(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::constant_time_eq;
+
+    #[test]
+    fn compares_secrets() {
+        assert!(constant_time_eq("hunter2", "hunter2"));
+        assert!(!constant_time_eq("hunter3", "hunter2"));
+        assert!(!constant_time_eq("hunter", "hunter2"));
+        assert!(!constant_time_eq("", "hunter2"));
+    }
+}
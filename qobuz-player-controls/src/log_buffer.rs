@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::watch;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+
+/// Captured log event
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LogLine {
+    /// UTC, like "2024-05-01 12:30:00"
+    pub time: String,
+    pub level: String,
+    pub target: String,
+    /// The message followed by the other fields as `key=value`
+    pub message: String,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:>5} {}: {}",
+            self.time, self.level, self.target, self.message
+        )
+    }
+}
+
+/// The last log lines, kept in memory for the log viewers of the interfaces
+#[derive(Debug)]
+pub struct LogBuffer {
+    lines: Mutex<VecDeque<LogLine>>,
+    capacity: usize,
+    /// Number of lines captured since startup
+    captured: watch::Sender<u64>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        let (captured, _) = watch::channel(0);
+        Arc::new(Self {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            captured,
+        })
+    }
+
+    /// Tracing layer capturing into this buffer
+    pub fn layer(self: &Arc<Self>) -> LogLayer {
+        LogLayer(self.clone())
+    }
+
+    /// Oldest first
+    pub fn lines(&self) -> Vec<LogLine> {
+        self.lines.lock().iter().cloned().collect()
+    }
+
+    /// Changes when a line is captured
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.captured.subscribe()
+    }
+
+    fn push(&self, line: LogLine) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        {
+            let mut lines = self.lines.lock();
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+        self.captured.send_modify(|captured| *captured += 1);
+    }
+}
+
+pub struct LogLayer(Arc<LogBuffer>);
+
+impl<S: Subscriber> Layer<S> for LogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        self.0.push(LogLine {
+            time: now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.insert_str(0, value);
+        } else {
+            _ = write!(self.message, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message.insert_str(0, &format!("{value:?}"));
+        } else {
+            _ = write!(self.message, " {}={value:?}", field.name());
+        }
+    }
+}
+
+fn now() -> String {
+    let now = time::OffsetDateTime::now_utc();
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+
+    fn line(message: &str) -> LogLine {
+        LogLine {
+            time: "2024-05-01 12:30:00".into(),
+            level: "INFO".into(),
+            target: "test".into(),
+            message: message.into(),
+        }
+    }
+
+    fn messages(buffer: &LogBuffer) -> Vec<String> {
+        buffer
+            .lines()
+            .into_iter()
+            .map(|line| line.message)
+            .collect()
+    }
+
+    #[test]
+    fn drops_the_oldest_line_when_full() {
+        let buffer = LogBuffer::new(2);
+        let captured = buffer.subscribe();
+
+        buffer.push(line("first"));
+        buffer.push(line("second"));
+        buffer.push(line("third"));
+
+        assert_eq!(messages(&buffer), ["second", "third"]);
+        assert_eq!(*captured.borrow(), 3);
+    }
+
+    #[test]
+    fn keeps_nothing_without_capacity() {
+        let buffer = LogBuffer::new(0);
+        let captured = buffer.subscribe();
+
+        buffer.push(line("first"));
+
+        assert!(buffer.lines().is_empty());
+        assert_eq!(*captured.borrow(), 0);
+    }
+
+    #[test]
+    fn captures_the_message_and_fields() {
+        let buffer = LogBuffer::new(10);
+        let subscriber = tracing_subscriber::registry().with(buffer.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(track_id = 42, "Track skipped");
+        });
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].level, "WARN");
+        assert_eq!(lines[0].message, "Track skipped track_id=42");
+    }
+}
@@ -1,3 +1,11 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+use qobuz_player_client::qobuz_models::{UrlType, parse_url};
+use time::{Duration, OffsetDateTime};
+
 pub mod mapper;
 
 #[derive(Debug, Default, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -26,6 +34,76 @@ pub struct Track {
     pub album_title: Option<String>,
     pub album_id: Option<String>,
     pub playlist_track_id: Option<u64>,
+    pub composer_name: Option<String>,
+    pub composer_id: Option<u32>,
+    /// Classical work the track is a movement of
+    pub work: Option<String>,
+    /// Set once Qobuz refused to stream the track as asked
+    #[serde(default)]
+    pub restriction: Option<TrackRestriction>,
+    /// Format the track was streamed in, like "FLAC 24/96". Set once it was streamed
+    #[serde(default)]
+    pub delivered_format: Option<String>,
+}
+
+/// Format Qobuz delivered a track in, which can be lower than the max audio quality
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveredFormat {
+    pub lossless: bool,
+    pub bit_depth: Option<u32>,
+    /// In Hz
+    pub sampling_rate: Option<u32>,
+}
+
+impl DeliveredFormat {
+    pub fn new(mime_type: &str, bit_depth: Option<u32>, sampling_rate: Option<u32>) -> Self {
+        Self {
+            lossless: !mime_type.contains("mpeg"),
+            bit_depth,
+            sampling_rate,
+        }
+    }
+}
+
+impl std::fmt::Display for DeliveredFormat {
+    /// Like "FLAC 24/96" or "MP3 320"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.lossless {
+            // Qobuz streams mp3 in 320 kbps only
+            return write!(f, "MP3 320");
+        }
+
+        write!(f, "FLAC")?;
+        match (self.bit_depth, self.sampling_rate) {
+            (Some(bit_depth), Some(sampling_rate)) => {
+                write!(f, " {bit_depth}/{}", sampling_rate as f64 / 1000.0)
+            }
+            (None, Some(sampling_rate)) => write!(f, " {} kHz", sampling_rate as f64 / 1000.0),
+            (Some(bit_depth), None) => write!(f, " {bit_depth}-bit"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Limit Qobuz put on streaming a track, found out when it is played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum TrackRestriction {
+    /// Streamed below the max audio quality, as the subscription does not allow it
+    LowerQuality,
+    /// Only a preview is offered in the region of the user
+    Region,
+    /// Only a preview is offered, and it is played in place of the track
+    Preview,
+}
+
+impl TrackRestriction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TrackRestriction::LowerQuality => "Lower quality",
+            TrackRestriction::Region => "Not in your region",
+            TrackRestriction::Preview => "Preview",
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -43,6 +121,62 @@ pub struct Album {
     pub image_thumbnail: String,
     pub duration_seconds: u32,
     pub description: Option<String>,
+    pub label: Option<Label>,
+    pub genre: Option<String>,
+    /// Barcode of the release
+    pub upc: Option<String>,
+    /// Original release date, like 2024-03-01
+    pub release_date: Option<String>,
+}
+
+/// Record label releasing an album
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Label {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Catalogue of a record label
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LabelPage {
+    pub id: u32,
+    pub name: String,
+    pub description: Option<String>,
+    pub albums: Vec<AlbumSimple>,
+}
+
+/// Consecutive album tracks belonging to the same work
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct AlbumWork {
+    pub title: Option<String>,
+    /// Position of the first track of the work in the album
+    pub start: usize,
+    pub tracks: Vec<Track>,
+}
+
+impl Album {
+    /// Groups consecutive tracks sharing the same work. Tracks without work
+    /// metadata are grouped together without a title.
+    pub fn works(&self) -> Vec<AlbumWork> {
+        let mut works: Vec<AlbumWork> = vec![];
+
+        for (position, track) in self.tracks.iter().enumerate() {
+            match works.last_mut() {
+                Some(work) if work.title == track.work => work.tracks.push(track.clone()),
+                _ => works.push(AlbumWork {
+                    title: track.work.clone(),
+                    start: position,
+                    tracks: vec![track.clone()],
+                }),
+            }
+        }
+
+        works
+    }
+
+    pub fn has_works(&self) -> bool {
+        self.tracks.iter().any(|track| track.work.is_some())
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -56,6 +190,9 @@ pub struct AlbumSimple {
     pub release_year: u32,
     pub explicit: bool,
     pub duration_seconds: u32,
+    /// Original release date, like 2024-03-01
+    #[serde(default)]
+    pub release_date: Option<String>,
 }
 
 impl From<Album> for AlbumSimple {
@@ -70,6 +207,7 @@ impl From<Album> for AlbumSimple {
             explicit: value.explicit,
             duration_seconds: value.duration_seconds,
             release_year: value.release_year,
+            release_date: value.release_date,
         }
     }
 }
@@ -81,6 +219,123 @@ pub struct SearchResults {
     pub artists: Vec<Artist>,
     pub playlists: Vec<Playlist>,
     pub tracks: Vec<Track>,
+    /// Composers of the found tracks
+    pub composers: Vec<Artist>,
+    /// Whether a page at a later offset has more results of the category
+    pub more_albums: bool,
+    pub more_artists: bool,
+    pub more_playlists: bool,
+    pub more_tracks: bool,
+}
+
+/// Narrows search results. Playlists are never filtered, and the release year
+/// only applies to albums since tracks do not carry one.
+#[derive(Default, Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct SearchFilter {
+    pub hires_only: bool,
+    pub hide_explicit: bool,
+    pub min_year: Option<u32>,
+    pub max_year: Option<u32>,
+}
+
+impl SearchFilter {
+    fn allows(&self, hires_available: bool, explicit: bool) -> bool {
+        (!self.hires_only || hires_available) && !(self.hide_explicit && explicit)
+    }
+
+    fn allows_year(&self, year: u32) -> bool {
+        self.min_year.is_none_or(|min| year >= min) && self.max_year.is_none_or(|max| year <= max)
+    }
+
+    pub fn apply(&self, mut results: SearchResults) -> SearchResults {
+        results.albums.retain(|album| {
+            self.allows(album.hires_available, album.explicit)
+                && self.allows_year(album.release_year)
+        });
+        results
+            .tracks
+            .retain(|track| self.allows(track.hires_available, track.explicit));
+        results
+    }
+}
+
+/// Order of the releases on an artist page
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscographySort {
+    /// Newest release first
+    #[default]
+    Year,
+    Title,
+    /// As ranked by Qobuz
+    Popularity,
+}
+
+impl DiscographySort {
+    pub const VALUES: [Self; 3] = [Self::Year, Self::Title, Self::Popularity];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiscographySort::Year => "Year",
+            DiscographySort::Title => "Title",
+            DiscographySort::Popularity => "Popularity",
+        }
+    }
+}
+
+/// Order of the favorite albums
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FavoriteAlbumSort {
+    #[default]
+    Artist,
+    /// Most recently added first
+    Added,
+    /// Newest release first
+    Released,
+}
+
+impl FavoriteAlbumSort {
+    pub const VALUES: [Self; 3] = [Self::Artist, Self::Added, Self::Released];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FavoriteAlbumSort::Artist => "Artist",
+            FavoriteAlbumSort::Added => "Date added",
+            FavoriteAlbumSort::Released => "Release date",
+        }
+    }
+
+    /// Sorts albums in artist order, as the favorites come. Albums without a known date
+    /// keep that order after the dated ones
+    pub fn sort(&self, albums: &mut [AlbumSimple], added: &HashMap<String, OffsetDateTime>) {
+        match self {
+            FavoriteAlbumSort::Artist => {
+                albums.sort_by_key(|album| album.artist.name.to_lowercase());
+            }
+            FavoriteAlbumSort::Added => {
+                albums.sort_by_key(|album| Reverse(added.get(&album.id).copied()));
+            }
+            FavoriteAlbumSort::Released => {
+                albums.sort_by_key(|album| Reverse(album.release_date.clone()));
+            }
+        }
+    }
+}
+
+/// Favorite albums added within this many days are shown as new
+pub const NEW_FAVORITE_DAYS: i64 = 7;
+
+/// Ids of the albums added to the favorites within the last week
+pub fn new_favorite_albums(added: &HashMap<String, OffsetDateTime>) -> HashSet<String> {
+    let since = OffsetDateTime::now_utc() - Duration::days(NEW_FAVORITE_DAYS);
+    added
+        .iter()
+        .filter(|(_, added_at)| **added_at >= since)
+        .map(|(id, _)| id.clone())
+        .collect()
 }
 
 #[derive(Default, Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -98,6 +353,22 @@ pub struct Artist {
     pub image: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Composer {
+    pub id: u32,
+    pub name: String,
+    pub image: Option<String>,
+    pub works: Vec<Work>,
+    pub albums: Vec<AlbumSimple>,
+}
+
+/// Recordings of a composition, grouped by the work metadata of the tracks
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Work {
+    pub title: String,
+    pub tracks: Vec<Track>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct ArtistPage {
     pub id: u32,
@@ -164,3 +435,138 @@ pub struct Genre {
     pub name: String,
     pub id: u32,
 }
+
+/// Item a Qobuz url points to
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    Album(String),
+    Playlist(u32),
+    Track(u32),
+    Artist(u32),
+}
+
+impl LinkTarget {
+    /// `None` unless the text is a play.qobuz.com or open.qobuz.com url
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if !text.contains("://") {
+            return None;
+        }
+
+        match parse_url(text).ok()? {
+            UrlType::Album { id } => Some(Self::Album(id)),
+            UrlType::Playlist { id } => Some(Self::Playlist(id as u32)),
+            UrlType::Track { id } => Some(Self::Track(id as u32)),
+            UrlType::Artist { id } => Some(Self::Artist(id as u32)),
+        }
+    }
+
+    /// open.qobuz.com url, opening the Qobuz app or web player for whoever it is shared with
+    pub fn url(&self) -> String {
+        match self {
+            Self::Album(id) => format!("https://open.qobuz.com/album/{id}"),
+            Self::Playlist(id) => format!("https://open.qobuz.com/playlist/{id}"),
+            Self::Track(id) => format!("https://open.qobuz.com/track/{id}"),
+            Self::Artist(id) => format!("https://open.qobuz.com/artist/{id}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum LinkedEntity {
+    Album(Album),
+    Playlist(Playlist),
+    Track(Track),
+}
+
+impl LinkedEntity {
+    pub fn title(&self) -> &str {
+        match self {
+            LinkedEntity::Album(album) => &album.title,
+            LinkedEntity::Playlist(playlist) => &playlist.title,
+            LinkedEntity::Track(track) => &track.title,
+        }
+    }
+
+    pub fn tracks(&self) -> Vec<&Track> {
+        match self {
+            LinkedEntity::Album(album) => album.tracks.iter().collect(),
+            LinkedEntity::Playlist(playlist) => playlist.tracks.iter().collect(),
+            LinkedEntity::Track(track) => vec![track],
+        }
+    }
+
+    pub fn available_track_ids(&self) -> Vec<u32> {
+        self.tracks()
+            .into_iter()
+            .filter(|track| track.available)
+            .map(|track| track.id)
+            .collect()
+    }
+}
+
+/// Smaller variant of a Qobuz cover url, used by data saver
+pub fn reduced_artwork_url(url: &str) -> String {
+    url.replace("_max.", "_230.").replace("_600.", "_230.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn album(id: &str, artist: &str, release_date: Option<&str>) -> AlbumSimple {
+        AlbumSimple {
+            id: id.into(),
+            artist: Artist {
+                name: artist.into(),
+                ..Default::default()
+            },
+            release_date: release_date.map(Into::into),
+            ..Default::default()
+        }
+    }
+
+    fn ids(albums: &[AlbumSimple]) -> Vec<&str> {
+        albums.iter().map(|album| album.id.as_str()).collect()
+    }
+
+    #[test]
+    fn sorts_favorite_albums() {
+        let now = OffsetDateTime::now_utc();
+        let added = HashMap::from([
+            ("a".to_string(), now - Duration::days(3)),
+            ("c".to_string(), now - Duration::days(1)),
+        ]);
+        let mut albums = vec![
+            album("a", "beta", Some("2020-01-01")),
+            album("b", "Alpha", None),
+            album("c", "gamma", Some("2024-03-01")),
+        ];
+
+        FavoriteAlbumSort::Artist.sort(&mut albums, &added);
+        assert_eq!(ids(&albums), ["b", "a", "c"]);
+
+        FavoriteAlbumSort::Added.sort(&mut albums, &added);
+        assert_eq!(ids(&albums), ["c", "a", "b"]);
+
+        FavoriteAlbumSort::Released.sort(&mut albums, &added);
+        assert_eq!(ids(&albums), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn only_albums_of_the_last_week_are_new() {
+        let now = OffsetDateTime::now_utc();
+        let added = HashMap::from([
+            ("recent".to_string(), now - Duration::days(1)),
+            (
+                "old".to_string(),
+                now - Duration::days(NEW_FAVORITE_DAYS + 1),
+            ),
+        ]);
+
+        assert_eq!(
+            new_favorite_albums(&added),
+            HashSet::from(["recent".to_string()])
+        );
+    }
+}
@@ -5,7 +5,8 @@ use qobuz_player_client::{
 use time::macros::format_description;
 
 use crate::models::{
-    Album, AlbumSimple, Artist, ArtistPage, Genre, Playlist, PlaylistSimple, SearchResults, Track,
+    Album, AlbumSimple, Artist, ArtistPage, Composer, Genre, Label, LabelPage, Playlist,
+    PlaylistSimple, SearchResults, Track, Work,
 };
 
 pub fn parse_featured_album(value: qobuz_models::featured::FeaturedAlbum) -> AlbumSimple {
@@ -19,6 +20,7 @@ pub fn parse_featured_album(value: qobuz_models::featured::FeaturedAlbum) -> Alb
         image: value.image.large,
         duration_seconds: value.duration,
         release_year: extract_year(&value.release_date_original),
+        release_date: Some(value.release_date_original).filter(|date| !date.is_empty()),
     }
 }
 
@@ -27,7 +29,46 @@ pub fn parse_search_results(
     user_id: i64,
     max_audio_quality: &AudioQuality,
 ) -> SearchResults {
+    let has_more = |offset: i64, count: usize, total: i64| offset + (count as i64) < total;
+
+    let mut composers: Vec<Artist> = vec![];
+    for composer in search_results
+        .tracks
+        .items
+        .iter()
+        .filter_map(|track| track.composer.as_ref())
+    {
+        if !composers.iter().any(|known| known.id == composer.id as u32) {
+            composers.push(Artist {
+                id: composer.id as u32,
+                name: composer.name.clone(),
+                image: None,
+            });
+        }
+    }
+
     SearchResults {
+        composers,
+        more_albums: has_more(
+            search_results.albums.offset,
+            search_results.albums.items.len(),
+            search_results.albums.total,
+        ),
+        more_artists: has_more(
+            search_results.artists.offset,
+            search_results.artists.items.len(),
+            search_results.artists.total,
+        ),
+        more_playlists: has_more(
+            search_results.playlists.offset,
+            search_results.playlists.items.len(),
+            search_results.playlists.total,
+        ),
+        more_tracks: has_more(
+            search_results.tracks.offset,
+            search_results.tracks.items.len(),
+            search_results.tracks.total,
+        ),
         query: search_results.query,
         albums: search_results
             .albums
@@ -79,6 +120,7 @@ pub fn parse_album_simple(
         image: s.image.large,
         duration_seconds: s.duration,
         release_year: extract_year(&s.dates.original),
+        release_date: Some(s.dates.original).filter(|date| !date.is_empty()),
     }
 }
 
@@ -111,6 +153,11 @@ pub fn parse_album(value: qobuz_models::album::Album, max_audio_quality: &AudioQ
                 album_title: Some(value.title.clone()),
                 album_id: Some(value.id.clone()),
                 playlist_track_id: None,
+                composer_name: t.composer.as_ref().map(|c| c.name.clone()),
+                composer_id: t.composer.as_ref().map(|c| c.id as u32),
+                work: t.work,
+                restriction: None,
+                delivered_format: None,
             })
             .collect()
     });
@@ -132,6 +179,13 @@ pub fn parse_album(value: qobuz_models::album::Album, max_audio_quality: &AudioQ
         image_thumbnail: value.image.small,
         duration_seconds: value.duration.map_or(0, |duration| duration as u32),
         description: sanitize_html(value.description),
+        label: value.label.map(|label| Label {
+            id: label.id as u32,
+            name: label.name,
+        }),
+        genre: value.genre.map(|genre| genre.name),
+        upc: value.upc.filter(|upc| !upc.is_empty()),
+        release_date: Some(value.release_date_original).filter(|date| !date.is_empty()),
     }
 }
 
@@ -210,6 +264,11 @@ pub fn parse_artist_page(
                     album_title: Some(t.album.title),
                     album_id: Some(t.album.id),
                     playlist_track_id: None,
+                    composer_name: None,
+                    composer_id: None,
+                    work: None,
+                    restriction: None,
+                    delivered_format: None,
                 }
             })
             .collect(),
@@ -308,6 +367,75 @@ pub fn parse_track(value: qobuz_models::track::Track, max_audio_quality: &AudioQ
         album_title: value.album.as_ref().map(|a| a.title.clone()),
         album_id: value.album.as_ref().map(|a| a.id.clone()),
         playlist_track_id: value.playlist_track_id,
+        composer_name: value.composer.as_ref().map(|c| c.name.clone()),
+        composer_id: value.composer.as_ref().map(|c| c.id as u32),
+        work: value.work,
+        restriction: None,
+        delivered_format: None,
+    }
+}
+
+/// Groups the tracks the composer is credited on by work, keeping the order of
+/// first appearance. Tracks without work metadata are a work of their own.
+pub fn parse_composer(
+    value: qobuz_models::artist::Artist,
+    max_audio_quality: &AudioQuality,
+) -> Composer {
+    let mut works: Vec<Work> = vec![];
+    let tracks = value
+        .tracks_appears_on
+        .map(|tracks| tracks.items)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|track| {
+            track
+                .composer
+                .as_ref()
+                .is_some_and(|composer| composer.id as u32 == value.id)
+        })
+        .map(|track| parse_track(track, max_audio_quality));
+
+    for track in tracks {
+        let title = track.work.clone().unwrap_or_else(|| track.title.clone());
+        match works.iter_mut().find(|work| work.title == title) {
+            Some(work) => work.tracks.push(track),
+            None => works.push(Work {
+                title,
+                tracks: vec![track],
+            }),
+        }
+    }
+
+    Composer {
+        id: value.id,
+        name: value.name,
+        image: value.image.map(|image| image.large),
+        works,
+        albums: value
+            .albums
+            .map(|albums| albums.items)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|album| parse_album(album, max_audio_quality).into())
+            .collect(),
+    }
+}
+
+pub fn parse_label_page(
+    value: qobuz_models::label::Label,
+    max_audio_quality: &AudioQuality,
+) -> LabelPage {
+    LabelPage {
+        id: value.id,
+        name: value.name,
+        description: sanitize_html(value.description),
+        albums: value
+            .albums
+            .map(|albums| albums.items)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|album| parse_album(album, max_audio_quality).into())
+            .collect(),
     }
 }
 
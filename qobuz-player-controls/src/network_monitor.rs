@@ -0,0 +1,152 @@
+use std::{
+    collections::BTreeSet,
+    net::{IpAddr, Ipv6Addr},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use tokio::time::MissedTickBehavior;
+
+use crate::{client::Client, controls::Controls};
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The wall clock moving this much further than the monotonic clock between two probes means
+/// the computer was suspended, as the monotonic clock stops meanwhile
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// Signs in again and restarts the track being downloaded when the network addresses change or
+/// the computer resumes from suspend, instead of waiting for the old connections to time out
+pub async fn init(client: Arc<Client>, controls: Controls) {
+    let mut addresses = network_addresses();
+    let mut last_probe = (Instant::now(), SystemTime::now());
+
+    let mut interval = tokio::time::interval(PROBE_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let probe = (Instant::now(), SystemTime::now());
+        let wall_clock = probe.1.duration_since(last_probe.1).unwrap_or_default();
+        let resumed = wall_clock.saturating_sub(probe.0 - last_probe.0) >= SUSPEND_THRESHOLD;
+        last_probe = probe;
+
+        let current_addresses = network_addresses();
+        let network_changed = current_addresses != addresses;
+        addresses = current_addresses;
+
+        // Without an address there is nothing to reconnect with, the next address is waited for
+        if !(resumed || network_changed) || addresses.is_empty() {
+            continue;
+        }
+
+        match resumed {
+            true => tracing::info!("Resumed from suspend, refreshing the session"),
+            false => tracing::info!("Network addresses changed, refreshing the session"),
+        }
+
+        if let Err(err) = client.refresh_session().await {
+            tracing::warn!("Unable to refresh the session: {err}");
+        }
+        controls.refresh_stream();
+    }
+}
+
+/// Identifies the network an address is on. IPv6 addresses keep only their /64 prefix, as
+/// temporary privacy addresses rotate the rest routinely without the network changing
+#[cfg_attr(not(unix), allow(dead_code))]
+fn network_of(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V4(_) => address,
+        IpAddr::V6(address) => {
+            let prefix = u128::from(address) & !u128::from(u64::MAX);
+            IpAddr::V6(Ipv6Addr::from(prefix))
+        }
+    }
+}
+
+/// Networks of the interfaces that are up, other than loopback
+#[cfg(unix)]
+fn network_addresses() -> BTreeSet<(String, IpAddr)> {
+    use std::{ffi::CStr, net::Ipv4Addr};
+
+    let mut addresses = BTreeSet::new();
+
+    let mut interfaces: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut interfaces) } != 0 {
+        tracing::debug!(
+            "Unable to list network interfaces: {}",
+            std::io::Error::last_os_error()
+        );
+        return addresses;
+    }
+
+    let mut interface = interfaces;
+    while !interface.is_null() {
+        // Entries stay valid until the list is freed below
+        let entry = unsafe { &*interface };
+        interface = entry.ifa_next;
+
+        let flags = entry.ifa_flags as libc::c_int;
+        if entry.ifa_addr.is_null() || flags & libc::IFF_UP == 0 || flags & libc::IFF_LOOPBACK != 0
+        {
+            continue;
+        }
+
+        let address = match unsafe { (*entry.ifa_addr).sa_family } as libc::c_int {
+            libc::AF_INET => {
+                let address = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in) };
+                IpAddr::V4(Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)))
+            }
+            libc::AF_INET6 => {
+                let address = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in6) };
+                IpAddr::V6(Ipv6Addr::from(address.sin6_addr.s6_addr))
+            }
+            _ => continue,
+        };
+
+        let name = unsafe { CStr::from_ptr(entry.ifa_name) }
+            .to_string_lossy()
+            .into_owned();
+        addresses.insert((name, network_of(address)));
+    }
+
+    unsafe { libc::freeifaddrs(interfaces) };
+    addresses
+}
+
+/// Without a portable way to list the interfaces, only resuming from suspend is noticed
+#[cfg(not(unix))]
+fn network_addresses() -> BTreeSet<(String, IpAddr)> {
+    BTreeSet::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::{network_addresses, network_of};
+
+    #[test]
+    fn loopback_is_not_a_network_address() {
+        assert!(
+            network_addresses()
+                .iter()
+                .all(|(_, address)| !address.is_loopback())
+        );
+    }
+
+    #[test]
+    fn rotating_privacy_addresses_stay_on_the_same_network() {
+        let stable: IpAddr = "2001:db8:1:2:aaaa:bbbb:cccc:dddd".parse().unwrap();
+        let temporary: IpAddr = "2001:db8:1:2:1234:5678:9abc:def0".parse().unwrap();
+        let other_network: IpAddr = "2001:db8:1:3:aaaa:bbbb:cccc:dddd".parse().unwrap();
+
+        assert_eq!(network_of(stable), network_of(temporary));
+        assert_ne!(network_of(stable), network_of(other_network));
+
+        let v4: IpAddr = "192.168.1.20".parse().unwrap();
+        assert_eq!(network_of(v4), v4);
+    }
+}
@@ -1,11 +1,61 @@
 use tokio::sync::broadcast::{self, Receiver, Sender};
 
+use crate::diagnostics::Diagnostics;
+use crate::error::Error;
+use crate::transitions::StreamFormat;
+
 #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum Notification {
     Error(String),
     Warning(String),
     Success(String),
     Info(String),
+    BufferProgress(BufferProgress),
+    StreamFormat(TrackFormat),
+    /// Playback counters, sent when one of them changes
+    Diagnostics(Diagnostics),
+    /// A long track started from the beginning and was stopped partway last time
+    ResumeAvailable(ResumePoint),
+    /// Qobuz rejected the stored login, the user has to sign in again
+    SignInRequired,
+}
+
+impl From<&Error> for Notification {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::InvalidCredentials => Notification::SignInRequired,
+            error => Notification::Error(error.to_string()),
+        }
+    }
+}
+
+/// Download progress of a track that is being streamed
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct BufferProgress {
+    pub track_id: u32,
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+    pub percent: u8,
+}
+
+impl BufferProgress {
+    pub fn is_complete(&self) -> bool {
+        self.percent >= 100
+    }
+}
+
+/// Decoded format of a track, sent when it starts playing
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct TrackFormat {
+    pub track_id: u32,
+    pub format: StreamFormat,
+}
+
+/// Position of a long track when it was last paused or skipped
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ResumePoint {
+    pub track_id: u32,
+    pub position_seconds: u64,
 }
 
 #[derive(Debug)]
@@ -31,6 +81,18 @@ impl NotificationBroadcast {
             .expect("infallible");
     }
 
+    /// Like [`Self::send_error`], but an expired login is sent as [`Notification::SignInRequired`]
+    /// so the UIs can offer to sign in again
+    pub fn report(&self, error: &Error) {
+        match error {
+            Error::InvalidCredentials => {
+                tracing::error!("{error}");
+                self.send(error.into());
+            }
+            error => self.send_error(error.to_string()),
+        }
+    }
+
     pub fn subscribe(&self) -> Receiver<Notification> {
         self.rx.resubscribe()
     }
@@ -0,0 +1,638 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+use crate::audio_backend::AudioOutput;
+use crate::diagnostics::PlaybackStats;
+use crate::notification::NotificationBroadcast;
+
+/// Frames written to the output at once
+const CHUNK_FRAMES: usize = 1024;
+/// How far a realtime output may run ahead of the wall clock
+const PACING_LEAD: Duration = Duration::from_millis(200);
+
+/// Samples of the sink player, in the format of the open stream
+pub(crate) struct PcmSource {
+    pub source: Box<dyn Source + Send>,
+    pub channels: ChannelCount,
+    pub sample_rate: SampleRate,
+}
+
+/// Output writing the samples pulled from the sink on a thread of its own, for outputs
+/// without a cpal device. The output stays open while streams come and go, so a reader of
+/// a fifo does not see the end of the file between tracks
+pub(crate) struct PcmOutput {
+    sources: Sender<PcmSource>,
+}
+
+impl PcmOutput {
+    /// `None` for the device output, which is played through cpal
+    pub fn open(
+        output: &AudioOutput,
+        broadcast: Arc<NotificationBroadcast>,
+        stats: Arc<PlaybackStats>,
+    ) -> Option<Self> {
+        let writer: Box<dyn PcmWriter> = match output {
+            AudioOutput::Device => return None,
+            AudioOutput::Alsa { device } => Box::new(AlsaWriter::new(device.clone(), stats)),
+            AudioOutput::Pipe { path } => Box::new(PipeWriter::new(path.clone())),
+            AudioOutput::Wav { path } => Box::new(WavWriter::new(path.clone())),
+            AudioOutput::Null => Box::new(NullWriter),
+        };
+
+        let (sources, receiver) = mpsc::channel();
+        std::thread::spawn(move || write_sources(writer, receiver, broadcast));
+        Some(Self { sources })
+    }
+
+    /// Replaces the stream being written
+    pub fn play(&self, source: PcmSource) {
+        if self.sources.send(source).is_err() {
+            tracing::warn!("Audio output thread has stopped");
+        }
+    }
+}
+
+/// Destination of the raw samples
+trait PcmWriter: Send {
+    /// Called before the first samples of a stream
+    fn configure(&mut self, channels: ChannelCount, sample_rate: SampleRate) -> io::Result<()>;
+
+    fn write(&mut self, samples: &[Sample]) -> io::Result<()>;
+
+    /// Writers that do not block like a sound card are held back to the playback speed.
+    /// Other writers run as fast as the decoder, but silence, like while paused or between
+    /// tracks, is always written in realtime
+    fn realtime(&self) -> bool;
+}
+
+fn write_sources(
+    mut writer: Box<dyn PcmWriter>,
+    sources: Receiver<PcmSource>,
+    broadcast: Arc<NotificationBroadcast>,
+) {
+    let mut buffer = Vec::new();
+
+    // Ends when the sink is dropped
+    while let Ok(mut stream) = sources.recv() {
+        'stream: loop {
+            if let Err(err) = writer.configure(stream.channels, stream.sample_rate) {
+                broadcast.send_error(format!("Unable to open audio output: {err}"));
+                break 'stream;
+            }
+
+            let mut clock = Instant::now();
+            let mut frames_written = 0_u64;
+            let chunk_len = CHUNK_FRAMES * stream.channels.get() as usize;
+
+            loop {
+                match sources.try_recv() {
+                    Ok(next) => {
+                        stream = next;
+                        continue 'stream;
+                    }
+                    Err(TryRecvError::Disconnected) => return,
+                    Err(TryRecvError::Empty) => {}
+                }
+
+                buffer.clear();
+                buffer.extend(stream.source.by_ref().take(chunk_len));
+                // The sink player was dropped
+                if buffer.is_empty() {
+                    break 'stream;
+                }
+
+                if let Err(err) = writer.write(&buffer) {
+                    broadcast.send_error(format!("Audio output failed: {err}"));
+                    break 'stream;
+                }
+
+                if !writer.realtime() && buffer.iter().any(|sample| *sample != 0.0) {
+                    clock = Instant::now();
+                    frames_written = 0;
+                    continue;
+                }
+
+                frames_written += (buffer.len() / stream.channels.get() as usize) as u64;
+                let played = Duration::from_secs_f64(
+                    frames_written as f64 / stream.sample_rate.get() as f64,
+                );
+                if let Some(ahead) = played.checked_sub(clock.elapsed() + PACING_LEAD) {
+                    std::thread::sleep(ahead);
+                }
+            }
+        }
+    }
+}
+
+/// Interleaved 32-bit float little endian samples. The file is opened on the first stream,
+/// so opening a fifo waits for its reader without holding up the player
+struct PipeWriter {
+    path: PathBuf,
+    output: Option<Box<dyn Write + Send>>,
+    bytes: Vec<u8>,
+}
+
+impl PipeWriter {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            output: None,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl PcmWriter for PipeWriter {
+    fn configure(&mut self, channels: ChannelCount, sample_rate: SampleRate) -> io::Result<()> {
+        tracing::info!(
+            "Writing {channels} channels at {sample_rate} Hz to {}",
+            self.path.display()
+        );
+
+        if self.output.is_none() {
+            let output: Box<dyn Write + Send> = match self.path.to_str() {
+                Some("-") => Box::new(io::stdout()),
+                _ => Box::new(BufWriter::new(
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&self.path)?,
+                )),
+            };
+            self.output = Some(output);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[Sample]) -> io::Result<()> {
+        let Some(output) = self.output.as_mut() else {
+            return Ok(());
+        };
+
+        self.bytes.clear();
+        for sample in samples {
+            self.bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let result = output.write_all(&self.bytes).and_then(|_| output.flush());
+        // Reopened on the next stream, for a reader that comes back
+        if result.is_err() {
+            self.output = None;
+        }
+        result
+    }
+
+    fn realtime(&self) -> bool {
+        true
+    }
+}
+
+/// Decodes without playing, for tests without audio hardware
+struct NullWriter;
+
+impl PcmWriter for NullWriter {
+    fn configure(&mut self, _channels: ChannelCount, _sample_rate: SampleRate) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, _samples: &[Sample]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn realtime(&self) -> bool {
+        false
+    }
+}
+
+/// 32-bit float WAV file. Tracks in another format than the open file start a new file
+/// with a numbered name, since a WAV file has a single format
+struct WavWriter {
+    path: PathBuf,
+    file: Option<WavFile>,
+    files_written: usize,
+    bytes: Vec<u8>,
+}
+
+struct WavFile {
+    file: BufWriter<File>,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            file: None,
+            files_written: 0,
+            bytes: Vec::new(),
+        }
+    }
+
+    /// The configured path, then name-2.wav, name-3.wav and so on
+    fn next_path(&mut self) -> PathBuf {
+        self.files_written += 1;
+        if self.files_written == 1 {
+            return self.path.clone();
+        }
+
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = self
+            .path
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "wav".to_string());
+        self.path
+            .with_file_name(format!("{stem}-{}.{extension}", self.files_written))
+    }
+}
+
+impl WavFile {
+    const FORMAT_IEEE_FLOAT: u16 = 3;
+    const BITS_PER_SAMPLE: u16 = 32;
+
+    fn create(path: &Path, channels: ChannelCount, sample_rate: SampleRate) -> io::Result<Self> {
+        let mut file = Self {
+            file: BufWriter::new(File::create(path)?),
+            channels,
+            sample_rate,
+            data_len: 0,
+        };
+        file.write_header()?;
+        Ok(file)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let channels = self.channels.get();
+        let block_align = channels * Self::BITS_PER_SAMPLE / 8;
+        let byte_rate = self.sample_rate.get() * block_align as u32;
+
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(36 + self.data_len).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16_u32.to_le_bytes());
+        header.extend_from_slice(&Self::FORMAT_IEEE_FLOAT.to_le_bytes());
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&self.sample_rate.get().to_le_bytes());
+        header.extend_from_slice(&byte_rate.to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&Self::BITS_PER_SAMPLE.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&self.data_len.to_le_bytes());
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.data_len = self.data_len.saturating_add(bytes.len() as u32);
+        Ok(())
+    }
+
+    /// Patches the lengths into the header, which holds zero lengths until then
+    fn finish(&mut self) -> io::Result<()> {
+        self.write_header()?;
+        self.file.flush()
+    }
+}
+
+impl Drop for WavFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            tracing::warn!("Unable to finish WAV file: {err}");
+        }
+    }
+}
+
+impl PcmWriter for WavWriter {
+    fn configure(&mut self, channels: ChannelCount, sample_rate: SampleRate) -> io::Result<()> {
+        let unchanged = self
+            .file
+            .as_ref()
+            .is_some_and(|file| file.channels == channels && file.sample_rate == sample_rate);
+        if unchanged {
+            return Ok(());
+        }
+
+        if let Some(mut file) = self.file.take() {
+            file.finish()?;
+        }
+
+        let path = self.next_path();
+        tracing::info!(
+            "Writing {channels} channels at {sample_rate} Hz to {}",
+            path.display()
+        );
+        self.file = Some(WavFile::create(&path, channels, sample_rate)?);
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[Sample]) -> io::Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+
+        self.bytes.clear();
+        for sample in samples {
+            self.bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        file.append(&self.bytes)
+    }
+
+    fn realtime(&self) -> bool {
+        false
+    }
+}
+
+/// ALSA pcm opened for each change of format, in the first sample format it accepts, so
+/// the samples reach the device without the resampling and mixing of cpal
+struct AlsaWriter {
+    device: String,
+    #[cfg(target_os = "linux")]
+    pcm: Option<AlsaPcm>,
+    #[cfg(target_os = "linux")]
+    stats: Arc<PlaybackStats>,
+}
+
+#[cfg(target_os = "linux")]
+struct AlsaPcm {
+    pcm: alsa::PCM,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    format: AlsaFormat,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy)]
+enum AlsaFormat {
+    Float,
+    S32,
+    S16,
+}
+
+impl AlsaWriter {
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn new(device: String, stats: Arc<PlaybackStats>) -> Self {
+        Self {
+            device,
+            #[cfg(target_os = "linux")]
+            pcm: None,
+            #[cfg(target_os = "linux")]
+            stats,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl PcmWriter for AlsaWriter {
+    fn configure(&mut self, channels: ChannelCount, sample_rate: SampleRate) -> io::Result<()> {
+        use alsa::pcm::{Access, Format, HwParams};
+        use alsa::{Direction, PCM, ValueOr};
+
+        let unchanged = self
+            .pcm
+            .as_ref()
+            .is_some_and(|pcm| pcm.channels == channels && pcm.sample_rate == sample_rate);
+        if unchanged {
+            return Ok(());
+        }
+
+        // The device is released before it is opened again
+        self.pcm = None;
+        let device = &self.device;
+        let error = |err: alsa::Error| io::Error::other(format!("{device}: {err}"));
+
+        let pcm = PCM::new(device, Direction::Playback, false).map_err(error)?;
+        let format = {
+            let params = HwParams::any(&pcm).map_err(error)?;
+            params.set_access(Access::RWInterleaved).map_err(error)?;
+            params.set_channels(channels.get() as u32).map_err(error)?;
+            params
+                .set_rate(sample_rate.get(), ValueOr::Nearest)
+                .map_err(error)?;
+
+            let (alsa_format, format) = [
+                (Format::float(), AlsaFormat::Float),
+                (Format::s32(), AlsaFormat::S32),
+                (Format::s16(), AlsaFormat::S16),
+            ]
+            .into_iter()
+            .find(|(format, _)| params.test_format(*format).is_ok())
+            .ok_or_else(|| io::Error::other(format!("{device}: no supported sample format")))?;
+            params.set_format(alsa_format).map_err(error)?;
+            pcm.hw_params(&params).map_err(error)?;
+            format
+        };
+
+        // A hw device may only offer a nearby rate, the samples would then play at the
+        // wrong speed
+        let rate = pcm
+            .hw_params_current()
+            .and_then(|params| params.get_rate())
+            .map_err(error)?;
+        check_rate(device, sample_rate.get(), rate)?;
+
+        tracing::info!(
+            "Opened ALSA {device} with {channels} channels at {sample_rate} Hz as {format:?}"
+        );
+        self.pcm = Some(AlsaPcm {
+            pcm,
+            channels,
+            sample_rate,
+            format,
+        });
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[Sample]) -> io::Result<()> {
+        let Some(pcm) = self.pcm.as_ref() else {
+            return Ok(());
+        };
+        let error = |err: alsa::Error| io::Error::other(err.to_string());
+        let channels = pcm.channels.get() as usize;
+
+        match pcm.format {
+            AlsaFormat::Float => write_frames(
+                &pcm.pcm,
+                pcm.pcm.io_f32().map_err(error)?,
+                samples,
+                channels,
+                &self.stats,
+            ),
+            AlsaFormat::S32 => {
+                let samples: Vec<i32> = samples
+                    .iter()
+                    .map(|sample| (sample.clamp(-1.0, 1.0) as f64 * i32::MAX as f64) as i32)
+                    .collect();
+                write_frames(
+                    &pcm.pcm,
+                    pcm.pcm.io_i32().map_err(error)?,
+                    &samples,
+                    channels,
+                    &self.stats,
+                )
+            }
+            AlsaFormat::S16 => {
+                let samples: Vec<i16> = samples
+                    .iter()
+                    .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+                    .collect();
+                write_frames(
+                    &pcm.pcm,
+                    pcm.pcm.io_i16().map_err(error)?,
+                    &samples,
+                    channels,
+                    &self.stats,
+                )
+            }
+        }
+    }
+
+    fn realtime(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl PcmWriter for AlsaWriter {
+    fn configure(&mut self, _channels: ChannelCount, _sample_rate: SampleRate) -> io::Result<()> {
+        Err(io::Error::other(format!(
+            "{}: ALSA output is only available on Linux",
+            self.device
+        )))
+    }
+
+    fn write(&mut self, _samples: &[Sample]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn realtime(&self) -> bool {
+        true
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn check_rate(device: &str, requested: u32, actual: u32) -> io::Result<()> {
+    match requested == actual {
+        true => Ok(()),
+        false => Err(io::Error::other(format!(
+            "{device} plays at {actual} Hz instead of {requested} Hz, use a plughw device to resample"
+        ))),
+    }
+}
+
+/// Blocks until all frames are written, recovering from underruns
+#[cfg(target_os = "linux")]
+fn write_frames<S: Copy>(
+    pcm: &alsa::PCM,
+    io: alsa::pcm::IO<'_, S>,
+    mut samples: &[S],
+    channels: usize,
+    stats: &PlaybackStats,
+) -> io::Result<()> {
+    while !samples.is_empty() {
+        match io.writei(samples) {
+            Ok(frames) => samples = &samples[(frames * channels).min(samples.len())..],
+            Err(err) => {
+                if err.errno() == libc::EPIPE {
+                    stats.record_underrun();
+                }
+                pcm.try_recover(err, true)
+                    .map_err(|err| io::Error::other(err.to_string()))?
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("qobuz-player-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn pipe_writes_little_endian_floats() {
+        let path = temp_path("pipe.raw");
+        let mut writer = PipeWriter::new(path.clone());
+        writer
+            .configure(NonZero::new(2).unwrap(), NonZero::new(44100).unwrap())
+            .unwrap();
+        writer.write(&[0.5, -1.0]).unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let samples: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(samples, [0.5, -1.0]);
+    }
+
+    #[test]
+    fn wav_files_of_other_formats_are_numbered() {
+        let mut writer = WavWriter::new(PathBuf::from("/music/out.wav"));
+        assert_eq!(writer.next_path(), PathBuf::from("/music/out.wav"));
+        assert_eq!(writer.next_path(), PathBuf::from("/music/out-2.wav"));
+        assert_eq!(writer.next_path(), PathBuf::from("/music/out-3.wav"));
+    }
+
+    #[test]
+    fn wav_header_holds_the_lengths_once_finished() {
+        let path = temp_path("out.wav");
+        let mut writer = WavWriter::new(path.clone());
+        writer
+            .configure(NonZero::new(2).unwrap(), NonZero::new(48000).unwrap())
+            .unwrap();
+        writer.write(&[0.5, -1.0]).unwrap();
+        writer.write(&[0.25, 0.0]).unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let u16_at = |at: usize| u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+        assert_eq!(bytes.len(), 44 + 16);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + 16);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(20), WavFile::FORMAT_IEEE_FLOAT);
+        assert_eq!(u16_at(22), 2);
+        assert_eq!(u32_at(24), 48000);
+        assert_eq!(u32_at(28), 48000 * 8);
+        assert_eq!(u16_at(32), 8);
+        assert_eq!(u16_at(34), 32);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(40), 16);
+        assert_eq!(f32::from_le_bytes(bytes[48..52].try_into().unwrap()), -1.0);
+    }
+
+    #[test]
+    fn alsa_rate_must_match() {
+        assert!(check_rate("hw:0", 44100, 44100).is_ok());
+        let error = check_rate("hw:0", 44100, 48000).unwrap_err();
+        assert!(error.to_string().contains("48000 Hz instead of 44100 Hz"));
+    }
+}
@@ -2,35 +2,53 @@ use crate::{
     controls::NewQueueItem,
     models::{Album, Track, TrackStatus},
 };
+use futures::{StreamExt, stream};
 use rand::seq::SliceRandom;
 use tokio::{
     select,
     sync::{
-        mpsc,
+        broadcast, mpsc,
         watch::{self, Receiver, Sender},
     },
-    time::sleep,
+    time::{sleep, sleep_until},
 };
 
 use crate::{
-    AppResult, ExitReceiver, PositionReceiver, Status, StatusReceiver, TracklistReceiver,
-    VolumeReceiver,
+    AppResult, AudioQuality, ExitReceiver, MutedReceiver, PositionReceiver, QuietHoursReceiver,
+    Status, StatusReceiver, TracklistReceiver, TransitionReceiver, VolumeCurve, VolumeReceiver,
+    ZonesReceiver,
+    audio_backend::{AudioBackend, AudioOutput, QueryTrackResult},
     controls::{ControlCommand, Controls},
-    database::Database,
+    database::{BanList, Database},
+    diagnostics::{Diagnostics, PlaybackStats},
     downloader::{DownloadResult, Downloader},
-    notification::{Notification, NotificationBroadcast},
-    sink::QueryTrackResult,
+    error::Error,
+    hardware_mixer::HardwareMixer,
+    notification::{Notification, NotificationBroadcast, ResumePoint},
+    quiet_hours::QuietHours,
+    smart_playlists::CONCURRENT_LOOKUPS,
     tracklist::{QueueItem, TracklistType},
+    transitions::{StreamFormat, TransitionEvent, timestamp_us},
+    zones::{Zone, ZoneSettings},
+};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
 };
-use std::{sync::Arc, time::Duration};
 
 use crate::{
+    channel_mix::ChannelMixSettings,
     client::Client,
     sink::Sink,
     tracklist::{self, Tracklist},
 };
 
 const INTERVAL_MS: u64 = 500;
+/// Resume points of long tracks are saved this often while playing
+const RESUME_SAVE_INTERVAL_SECONDS: u64 = 30;
 
 pub struct Player {
     broadcast: Arc<NotificationBroadcast>,
@@ -38,10 +56,26 @@ pub struct Player {
     tracklist_rx: Receiver<Tracklist>,
     target_status: Sender<Status>,
     client: Arc<Client>,
-    sink: Sink,
+    sink: Box<dyn AudioBackend>,
     volume: Sender<f32>,
+    muted: Sender<bool>,
+    pre_mute_volume: f32,
+    /// Ceiling of the volume, whoever sets it
+    volume_limit: f32,
+    quiet_hours: Option<QuietHours>,
+    /// `quiet_hours` while they are in effect
+    active_quiet_hours: Sender<Option<QuietHours>>,
+    channel_mix: Arc<ChannelMixSettings>,
+    /// Read when each track is queued
+    trim_silence: Arc<AtomicBool>,
+    zones: Vec<Arc<ZoneSettings>>,
+    zones_tx: Sender<Vec<Zone>>,
     position: Sender<Duration>,
-    track_finished: Receiver<()>,
+    track_finished: Receiver<SystemTime>,
+    transitions: broadcast::Sender<TransitionEvent>,
+    current_format: Option<StreamFormat>,
+    /// Track queued in the sink after the current one, with its format
+    queued_next: Option<(u32, StreamFormat)>,
     controls_rx: mpsc::UnboundedReceiver<ControlCommand>,
     controls: Controls,
     database: Arc<Database>,
@@ -50,29 +84,202 @@ pub struct Player {
     downloader: Downloader,
     state_change_delay: Option<Duration>,
     sample_rate_change_delay: Option<Duration>,
+    precache_album_tracks: usize,
+    jump_forward_seconds: u64,
+    jump_backward_seconds: u64,
+    prefetch_window: Duration,
+    /// Tracks at least this long get resume points
+    resume_threshold: Option<Duration>,
+    /// Long track the resume point was looked up for, so it is offered once per play
+    resume_checked: Option<u32>,
+    /// Long track and the save interval its resume point was last saved in
+    resume_saved: Option<(u32, u64)>,
+    stats: Arc<PlaybackStats>,
+    /// Last diagnostics broadcast, so only changes are sent
+    last_diagnostics: Diagnostics,
+    /// Tracks whose download stalled
+    stalled_rx: mpsc::UnboundedReceiver<u32>,
+    stall_watchdog: StallWatchdog,
+    /// Manual skip to this queue position, once the playing track faded out at the instant
+    pending_skip: Option<(i32, Instant)>,
+}
+
+/// Notices when the playing track stops advancing
+struct StallWatchdog {
+    /// Playback making no progress for this long is restarted
+    timeout: Option<Duration>,
+    /// Last position the playing track advanced to, and when
+    last_progress: Option<(Duration, Instant)>,
+}
+
+impl StallWatchdog {
+    fn reset(&mut self) {
+        self.last_progress = None;
+    }
+
+    /// Whether `position` has not advanced for the timeout
+    fn stalled(&mut self, position: Duration, now: Instant) -> bool {
+        let Some(timeout) = self.timeout else {
+            return false;
+        };
+
+        match self.last_progress {
+            Some((last_position, since)) if last_position == position => {
+                now.duration_since(since) >= timeout
+            }
+            _ => {
+                self.last_progress = Some((position, now));
+                false
+            }
+        }
+    }
+}
+
+/// Highest volume the player allows, from the volume limit and the quiet hours in effect
+#[derive(Debug, Clone)]
+pub struct VolumeCap {
+    limit: f32,
+    quiet_hours: QuietHoursReceiver,
+}
+
+impl VolumeCap {
+    pub fn max_volume(&self) -> f32 {
+        self.quiet_hours
+            .borrow()
+            .map_or(1.0, |quiet_hours| quiet_hours.max_volume())
+            .min(self.limit)
+    }
+}
+
+/// Playback settings, mostly from the command line
+pub struct PlayerSettings {
+    pub audio_cache_dir: std::path::PathBuf,
+    pub audio_cache_max_bytes: Option<u64>,
+    pub state_change_delay: Option<Duration>,
+    pub sample_rate_change_delay: Option<Duration>,
+    pub preferred_device_id: Option<String>,
+    pub zone_devices: Vec<String>,
+    /// Volume and state the zones had last time, by name
+    pub saved_zones: Vec<Zone>,
+    pub precache_album_tracks: usize,
+    pub jump_forward_seconds: u64,
+    pub jump_backward_seconds: u64,
+    pub volume_curve: VolumeCurve,
+    pub volume_ramp: Option<Duration>,
+    /// Highest volume, between 0 and 1
+    pub volume_limit: f32,
+    pub skip_fade: Option<Duration>,
+    pub balance: f32,
+    pub mono_downmix: bool,
+    pub trim_silence: bool,
+    pub dsp_command: Option<String>,
+    pub prefetch_window: Duration,
+    pub resume_threshold: Option<Duration>,
+    pub stall_timeout: Option<Duration>,
+    pub hardware_mixer: Option<HardwareMixer>,
+    pub audio_output: AudioOutput,
+    pub quiet_hours: Option<QuietHours>,
 }
 
 impl Player {
-    #[allow(clippy::too_many_arguments)]
     pub fn new(
         tracklist: Tracklist,
         client: Arc<Client>,
         volume: f32,
         broadcast: Arc<NotificationBroadcast>,
-        audio_cache_dir: std::path::PathBuf,
         database: Arc<Database>,
-        state_change_delay: Option<Duration>,
-        sample_rate_change_delay: Option<Duration>,
-        preferred_device_id: Option<String>,
+        settings: PlayerSettings,
     ) -> AppResult<Self> {
+        let PlayerSettings {
+            audio_cache_dir,
+            audio_cache_max_bytes,
+            state_change_delay,
+            sample_rate_change_delay,
+            preferred_device_id,
+            zone_devices,
+            saved_zones,
+            precache_album_tracks,
+            jump_forward_seconds,
+            jump_backward_seconds,
+            volume_curve,
+            volume_ramp,
+            volume_limit,
+            skip_fade,
+            balance,
+            mono_downmix,
+            trim_silence,
+            dsp_command,
+            prefetch_window,
+            resume_threshold,
+            stall_timeout,
+            hardware_mixer,
+            audio_output,
+            quiet_hours,
+        } = settings;
+        let volume_limit = volume_limit.clamp(0.0, 1.0);
+        let active_quiet_hours = quiet_hours.filter(QuietHours::is_active);
+        let volume = active_quiet_hours
+            .map_or(volume, |quiet_hours| volume.min(quiet_hours.max_volume()))
+            .min(volume_limit);
         let (volume, volume_receiver) = watch::channel(volume);
-        let sink = Sink::new(volume_receiver, preferred_device_id)?;
-
-        let downloader = Downloader::new(audio_cache_dir, database.clone(), client.clone());
+        let (active_quiet_hours, _) = watch::channel(active_quiet_hours);
+        let channel_mix = Arc::new(ChannelMixSettings::new(balance, mono_downmix));
+        let trim_silence = Arc::new(AtomicBool::new(trim_silence));
+        let main_zone = ZoneSettings::new(
+            preferred_device_id
+                .clone()
+                .unwrap_or_else(|| "Default output".to_string()),
+            preferred_device_id.clone(),
+        );
+        let zones: Vec<_> = std::iter::once(main_zone)
+            .chain(
+                zone_devices
+                    .into_iter()
+                    .map(|device| ZoneSettings::new(device.clone(), Some(device))),
+            )
+            .map(Arc::new)
+            .collect();
+        for settings in &zones {
+            if let Some(saved) = saved_zones.iter().find(|zone| zone.name == settings.name) {
+                settings.set_volume(saved.volume);
+                settings.set_enabled(saved.enabled);
+            }
+        }
+        let (zones_tx, _) = watch::channel(zones.iter().map(|zone| zone.zone()).collect());
+        let stats = Arc::new(PlaybackStats::default());
+        let sink = Box::new(Sink::new(
+            volume_receiver,
+            audio_output,
+            preferred_device_id,
+            volume_curve,
+            volume_ramp,
+            skip_fade,
+            channel_mix.clone(),
+            trim_silence.clone(),
+            dsp_command,
+            hardware_mixer,
+            zones.clone(),
+            broadcast.clone(),
+            stats.clone(),
+            client.audio_cache_key(),
+        )?);
+
+        let (stalled_tx, stalled_rx) = mpsc::unbounded_channel();
+        let downloader = Downloader::new(
+            audio_cache_dir,
+            audio_cache_max_bytes,
+            database.clone(),
+            client.clone(),
+            broadcast.clone(),
+            stall_timeout,
+            stalled_tx,
+        );
 
         let track_finished = sink.track_finished();
+        let (transitions, _) = broadcast::channel(32);
 
         let (position, _) = watch::channel(Default::default());
+        let (muted, _) = watch::channel(false);
         let (target_status, _) = watch::channel(Default::default());
         let (tracklist_tx, tracklist_rx) = watch::channel(tracklist);
 
@@ -89,14 +296,41 @@ impl Player {
             client,
             sink,
             volume,
+            muted,
+            pre_mute_volume: Default::default(),
+            volume_limit,
+            quiet_hours,
+            active_quiet_hours,
+            channel_mix,
+            trim_silence,
+            zones,
+            zones_tx,
             position,
             track_finished,
+            transitions,
+            current_format: None,
+            queued_next: None,
             database,
             next_track_in_sink_queue: false,
             next_track_is_queried: false,
             downloader,
             state_change_delay,
             sample_rate_change_delay,
+            precache_album_tracks,
+            jump_forward_seconds,
+            jump_backward_seconds,
+            prefetch_window,
+            resume_threshold,
+            resume_checked: None,
+            resume_saved: None,
+            stats,
+            last_diagnostics: Default::default(),
+            stalled_rx,
+            stall_watchdog: StallWatchdog {
+                timeout: stall_timeout,
+                last_progress: None,
+            },
+            pending_skip: None,
         })
     }
 
@@ -104,6 +338,10 @@ impl Player {
         self.controls.clone()
     }
 
+    pub fn transitions(&self) -> TransitionReceiver {
+        self.transitions.subscribe()
+    }
+
     pub fn status(&self) -> StatusReceiver {
         self.target_status.subscribe()
     }
@@ -112,6 +350,25 @@ impl Player {
         self.volume.subscribe()
     }
 
+    pub fn muted(&self) -> MutedReceiver {
+        self.muted.subscribe()
+    }
+
+    pub fn zones(&self) -> ZonesReceiver {
+        self.zones_tx.subscribe()
+    }
+
+    pub fn quiet_hours(&self) -> QuietHoursReceiver {
+        self.active_quiet_hours.subscribe()
+    }
+
+    pub fn volume_cap(&self) -> VolumeCap {
+        VolumeCap {
+            limit: self.volume_limit,
+            quiet_hours: self.quiet_hours(),
+        }
+    }
+
     pub fn position(&self) -> PositionReceiver {
         self.position.subscribe()
     }
@@ -124,7 +381,7 @@ impl Player {
         let target_status = *self.target_status.borrow();
 
         match target_status {
-            Status::Playing | Status::Buffering => self.pause(),
+            Status::Playing | Status::Buffering => self.pause().await,
             Status::Paused => self.play().await?,
         }
 
@@ -159,9 +416,67 @@ impl Player {
         }
     }
 
-    fn pause(&mut self) {
+    async fn pause(&mut self) {
         self.set_target_status(Status::Paused);
         self.sink.pause();
+        self.save_resume_point().await;
+    }
+
+    fn is_long(&self, track: &Track) -> bool {
+        self.resume_threshold
+            .is_some_and(|threshold| u64::from(track.duration_seconds) >= threshold.as_secs())
+    }
+
+    /// Saves the exact position of a long track, when it is paused
+    async fn save_resume_point(&mut self) {
+        let track_id = match self.tracklist_rx.borrow().current_track() {
+            Some(track) if self.is_long(track) => track.id,
+            _ => return,
+        };
+        let seconds = self.sink.position().as_secs();
+        if seconds < RESUME_SAVE_INTERVAL_SECONDS {
+            return;
+        }
+
+        if let Err(err) = self.database.set_resume_point(track_id, seconds).await {
+            tracing::warn!("Unable to save resume point: {err}");
+        }
+        self.resume_saved = Some((track_id, seconds / RESUME_SAVE_INTERVAL_SECONDS));
+    }
+
+    /// Offers the saved position when a long track starts from the beginning, and saves the
+    /// position every [`RESUME_SAVE_INTERVAL_SECONDS`] while it plays
+    async fn update_resume_point(&mut self, track_id: u32, position: Duration) {
+        let seconds = position.as_secs();
+
+        if self.resume_checked != Some(track_id) {
+            self.resume_checked = Some(track_id);
+
+            // Started at a position, like from a deep link, so there is nothing to offer
+            if seconds < RESUME_SAVE_INTERVAL_SECONDS {
+                match self.database.get_resume_point(track_id).await {
+                    Ok(Some(position_seconds)) => {
+                        self.broadcast
+                            .send(Notification::ResumeAvailable(ResumePoint {
+                                track_id,
+                                position_seconds,
+                            }))
+                    }
+                    Ok(None) => {}
+                    Err(err) => tracing::warn!("Unable to get resume point: {err}"),
+                }
+            }
+        }
+
+        let interval = seconds / RESUME_SAVE_INTERVAL_SECONDS;
+        if interval == 0 || self.resume_saved == Some((track_id, interval)) {
+            return;
+        }
+
+        if let Err(err) = self.database.set_resume_point(track_id, seconds).await {
+            tracing::warn!("Unable to save resume point: {err}");
+        }
+        self.resume_saved = Some((track_id, interval));
     }
 
     fn set_target_status(&self, status: Status) {
@@ -179,19 +494,23 @@ impl Player {
             self.next_track_is_queried = true;
         }
 
-        let download_result = self.downloader.ensure_track_is_downloaded(track).await?;
+        let download_result = self.downloader.ensure_track_is_downloaded(track).await;
+        self.sync_streaming_details(track).await?;
+        let download_result = download_result?;
 
         self.wait_for_state_change_delay().await;
 
         let query_result = match download_result {
-            DownloadResult::Cached(track_path) => self.sink.query_track(&track_path)?,
-            DownloadResult::Streaming(reader) => self.sink.query_track_stream(reader)?,
+            DownloadResult::Cached(track_path) => self.sink.query_track(track.id, &track_path),
+            DownloadResult::Streaming(reader) => self.sink.query_track_stream(track.id, reader),
         };
+        let query_result = query_result.inspect_err(|err| self.stats.record_query_error(err))?;
 
         if next_track {
             self.next_track_in_sink_queue = match query_result {
-                QueryTrackResult::Queued => {
+                QueryTrackResult::Queued(format) => {
                     tracing::info!("In queue");
+                    self.queued_next = Some((track.id, format));
                     true
                 }
                 QueryTrackResult::RecreateStreamRequired => {
@@ -199,6 +518,9 @@ impl Player {
                     false
                 }
             };
+        } else if let QueryTrackResult::Queued(format) = query_result {
+            self.queued_next = None;
+            self.send_track_started(track.id, format, SystemTime::now());
         }
         self.sink.play();
         self.set_target_status(Status::Playing);
@@ -206,13 +528,187 @@ impl Player {
         Ok(())
     }
 
+    /// Queries the current track of the tracklist, moving past the tracks locked in the region.
+    /// False when none of the remaining tracks can be played
+    async fn query_playable_track(&mut self, tracklist: &mut Tracklist) -> AppResult<bool> {
+        loop {
+            let Some(track) = tracklist.current_track().cloned() else {
+                return Ok(false);
+            };
+
+            match self.query_track(&track, false).await {
+                Err(Error::RegionLocked) => {
+                    tracing::info!("{} is locked in this region, skipping", track.title);
+                    tracklist.set_streaming_details(
+                        track.id,
+                        self.client.restriction(track.id),
+                        self.client.delivered_format(track.id),
+                    );
+                    let next_position = tracklist.current_position() + 1;
+                    tracklist.skip_to_track(next_position as i32);
+                }
+                result => return result.map(|()| true),
+            }
+        }
+    }
+
+    /// Badges the track in the queue with the format it is streamed in, and once Qobuz limited
+    /// its quality or locked it in the region
+    async fn sync_streaming_details(&self, track: &Track) -> AppResult<()> {
+        let restriction = self.client.restriction(track.id);
+        let delivered_format = self.client.delivered_format(track.id);
+        if restriction == track.restriction && delivered_format == track.delivered_format {
+            return Ok(());
+        }
+
+        let mut tracklist = self.tracklist_tx.borrow().clone();
+        tracklist.set_streaming_details(track.id, restriction, delivered_format);
+        self.broadcast_tracklist(tracklist).await
+    }
+
+    /// Highest volume allowed right now
+    fn max_volume(&self) -> f32 {
+        self.active_quiet_hours
+            .borrow()
+            .map_or(1.0, |quiet_hours| quiet_hours.max_volume())
+            .min(self.volume_limit)
+    }
+
     async fn set_volume(&self, volume: f32) -> AppResult<()> {
+        let volume = volume.min(self.max_volume());
+        self.muted
+            .send_if_modified(|muted| std::mem::replace(muted, false));
         self.volume.send(volume)?;
         self.sink.sync_volume();
         self.database.set_volume(volume).await?;
         Ok(())
     }
 
+    /// Muting broadcasts a volume of 0 without persisting it, so the volume is restored on unmute
+    fn set_mute(&mut self, muted: bool) -> AppResult<()> {
+        if *self.muted.borrow() == muted {
+            return Ok(());
+        }
+
+        let volume = if muted {
+            self.pre_mute_volume = *self.volume.borrow();
+            0.0
+        } else {
+            self.pre_mute_volume.min(self.max_volume())
+        };
+
+        self.muted.send_replace(muted);
+        self.volume.send(volume)?;
+        self.sink.sync_volume();
+        Ok(())
+    }
+
+    async fn set_quiet_hours(&mut self, quiet_hours: Option<QuietHours>) -> AppResult<()> {
+        self.quiet_hours = quiet_hours;
+        self.database.set_quiet_hours(quiet_hours).await?;
+        self.update_quiet_hours().await
+    }
+
+    /// Caps the volume when quiet hours start. The volume stays lowered when they end
+    async fn update_quiet_hours(&mut self) -> AppResult<()> {
+        let active = self.quiet_hours.filter(QuietHours::is_active);
+        let changed = self
+            .active_quiet_hours
+            .send_if_modified(|current| std::mem::replace(current, active) != active);
+        if !changed {
+            return Ok(());
+        }
+
+        let Some(quiet_hours) = active else {
+            self.broadcast
+                .send(Notification::Info("Quiet hours ended".into()));
+            return Ok(());
+        };
+
+        self.broadcast.send(Notification::Info(format!(
+            "Quiet hours until {}, volume up to {}%",
+            quiet_hours.end_time(),
+            quiet_hours.max_volume
+        )));
+
+        let max_volume = quiet_hours.max_volume();
+        if *self.muted.borrow() {
+            self.pre_mute_volume = self.pre_mute_volume.min(max_volume);
+        } else if *self.volume.borrow() > max_volume {
+            self.set_volume(max_volume).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_balance(&self, balance: f32) -> AppResult<()> {
+        self.channel_mix.set_balance(balance);
+        self.database
+            .set_balance(self.channel_mix.balance())
+            .await?;
+        Ok(())
+    }
+
+    async fn set_mono_downmix(&self, mono_downmix: bool) -> AppResult<()> {
+        self.channel_mix.set_mono_downmix(mono_downmix);
+        self.database.set_mono_downmix(mono_downmix).await?;
+        Ok(())
+    }
+
+    async fn set_trim_silence(&self, enabled: bool) -> AppResult<()> {
+        self.trim_silence.store(enabled, Ordering::Relaxed);
+        self.database.set_trim_silence(enabled).await?;
+        Ok(())
+    }
+
+    async fn set_max_audio_quality(&self, quality: AudioQuality) -> AppResult<()> {
+        self.client.set_max_audio_quality(quality).await;
+        self.database.set_max_audio_quality(quality).await?;
+        self.broadcast.send(Notification::Info(format!(
+            "Max audio quality set to {}",
+            quality.label()
+        )));
+        Ok(())
+    }
+
+    async fn set_data_saver(&self, enabled: bool) -> AppResult<()> {
+        self.client.set_data_saver(enabled).await;
+        self.database.set_data_saver(enabled).await?;
+        self.broadcast.send(Notification::Info(
+            match enabled {
+                true => "Data saver enabled",
+                false => "Data saver disabled",
+            }
+            .into(),
+        ));
+        Ok(())
+    }
+
+    async fn set_play_previews(&self, enabled: bool) -> AppResult<()> {
+        self.client.set_play_previews(enabled);
+        self.database.set_play_previews(enabled).await?;
+        self.broadcast.send(Notification::Info(
+            match enabled {
+                true => "Previews are played for tracks not available in full",
+                false => "Tracks not available in full are skipped",
+            }
+            .into(),
+        ));
+        Ok(())
+    }
+
+    async fn set_zone(&self, zone: usize, update: impl FnOnce(&ZoneSettings)) -> AppResult<()> {
+        let Some(settings) = self.zones.get(zone) else {
+            return Ok(());
+        };
+
+        update(settings);
+        self.sink.sync_volume();
+        self.zones_tx
+            .send_replace(self.zones.iter().map(|zone| zone.zone()).collect());
+        self.database.set_zone(&settings.zone()).await
+    }
+
     async fn broadcast_tracklist(&self, tracklist: Tracklist) -> AppResult<()> {
         self.database.set_tracklist(&tracklist).await?;
         self.tracklist_tx.send(tracklist)?;
@@ -232,44 +728,53 @@ impl Player {
         Ok(())
     }
 
-    fn jump_forward(&mut self) -> AppResult<()> {
-        let duration = self
-            .tracklist_rx
+    fn current_track_duration(&self) -> Option<Duration> {
+        self.tracklist_rx
             .borrow()
             .current_track()
-            .map(|x| Duration::from_secs(x.duration_seconds as u64));
-
-        if let Some(duration) = duration {
-            let ten_seconds = Duration::from_secs(10);
-            let next_position = self.sink.position() + ten_seconds;
-
-            if next_position < duration {
-                self.seek(next_position)?;
-            } else {
-                self.seek(duration)?;
-            }
-        }
+            .map(|x| Duration::from_secs(x.duration_seconds as u64))
+    }
 
-        Ok(())
+    fn jump_forward(&mut self) -> AppResult<()> {
+        self.jump_by(self.jump_forward_seconds as i64 * 1_000_000)
     }
 
     fn jump_backward(&mut self) -> AppResult<()> {
+        self.jump_by(-(self.jump_backward_seconds as i64) * 1_000_000)
+    }
+
+    fn jump_by(&mut self, offset_us: i64) -> AppResult<()> {
         let current_position = self.sink.position();
 
-        if current_position.as_millis() < 10000 {
-            self.seek(Duration::default())?;
+        let seek_position = if offset_us.is_negative() {
+            current_position.saturating_sub(Duration::from_micros(offset_us.unsigned_abs()))
         } else {
-            let ten_seconds = Duration::from_secs(10);
-            let seek_position = current_position - ten_seconds;
+            let Some(duration) = self.current_track_duration() else {
+                return Ok(());
+            };
 
-            self.seek(seek_position)?;
-        }
-        Ok(())
+            (current_position + Duration::from_micros(offset_us as u64)).min(duration)
+        };
+
+        self.seek(seek_position)
+    }
+
+    fn seek_percentage(&mut self, percentage: f32) -> AppResult<()> {
+        let Some(duration) = self.current_track_duration() else {
+            return Ok(());
+        };
+
+        self.seek(duration.mul_f32(percentage.clamp(0.0, 100.0) / 100.0))
     }
 
     async fn skip_to_position(&mut self, new_position: i32, force: bool) -> AppResult<()> {
-        let mut tracklist = self.tracklist_rx.borrow().clone();
-        let current_position = tracklist.current_position();
+        // Skipping again during the fade only moves where it ends up
+        if let Some((position, _)) = &mut self.pending_skip {
+            *position = new_position;
+            return Ok(());
+        }
+
+        let current_position = self.tracklist_rx.borrow().current_position();
 
         // Typical previous skip functionality where if,
         // the track is greater than 1 second into playing,
@@ -283,6 +788,24 @@ impl Player {
             return Ok(());
         }
 
+        // The player loop skips when the fade is done, and keeps handling commands meanwhile
+        if let Some(fade) = self.sink.fade_out() {
+            self.pending_skip = Some((new_position, Instant::now() + fade));
+            return Ok(());
+        }
+
+        self.skip_now(new_position).await
+    }
+
+    async fn finish_skip(&mut self) -> AppResult<()> {
+        match self.pending_skip.take() {
+            Some((position, _)) => self.skip_now(position).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn skip_now(&mut self, new_position: i32) -> AppResult<()> {
+        let mut tracklist = self.tracklist_rx.borrow().clone();
         self.position.send(Default::default())?;
 
         if tracklist.skip_to_track(new_position).is_some() {
@@ -300,18 +823,24 @@ impl Player {
     }
 
     async fn next(&mut self) -> AppResult<()> {
-        let current_position = self.tracklist_rx.borrow().current_position();
-        self.skip_to_position((current_position + 1) as i32, true)
-            .await
+        self.skip_to_position(self.skip_origin() + 1, true).await
     }
 
     async fn previous(&mut self) -> AppResult<()> {
-        let current_position = self.tracklist_rx.borrow().current_position();
-        self.skip_to_position(current_position as i32 - 1, false)
-            .await
+        self.skip_to_position(self.skip_origin() - 1, false).await
+    }
+
+    /// Position next and previous count from, the one a pending skip ends up at
+    fn skip_origin(&self) -> i32 {
+        match self.pending_skip {
+            Some((position, _)) => position,
+            None => self.tracklist_rx.borrow().current_position() as i32,
+        }
     }
 
     async fn new_queue(&mut self, tracklist: Tracklist) -> AppResult<()> {
+        // A new queue replaces the one a pending skip was going to move through
+        self.pending_skip = None;
         self.sink.clear()?;
         self.next_track_is_queried = false;
         self.next_track_in_sink_queue = false;
@@ -321,6 +850,13 @@ impl Player {
             self.query_track(first_track, false).await?;
         }
 
+        if let Err(err) = self
+            .database
+            .add_recent_tracklist(tracklist.list_type())
+            .await
+        {
+            tracing::warn!("Unable to save recent queue: {err}");
+        }
         self.broadcast_tracklist(tracklist).await?;
 
         Ok(())
@@ -331,16 +867,7 @@ impl Player {
         self.next_track_is_queried = false;
         self.next_track_in_sink_queue = false;
 
-        let mut queue_items = vec![];
-        for (index, item) in items.iter().enumerate() {
-            let track = self.client.track(item.track_id).await?;
-            let queue_item = QueueItem {
-                track,
-                queue_id: item.queue_id,
-                index,
-            };
-            queue_items.push(queue_item);
-        }
+        let mut queue_items = self.connect_queue_items(items, 0).await;
 
         if let Some(item) = queue_items.first_mut() {
             item.track.status = TrackStatus::Playing;
@@ -359,7 +886,7 @@ impl Player {
     }
 
     async fn clear_queue(&mut self) -> AppResult<()> {
-        self.pause();
+        self.pause().await;
         self.sink.clear()?;
         self.next_track_is_queried = false;
         self.next_track_in_sink_queue = false;
@@ -385,14 +912,25 @@ impl Player {
         self.new_queue(tracklist).await
     }
 
+    /// Unlike a seek from the controls, a failed seek is an error, as the track would
+    /// otherwise quietly play from the start
+    async fn play_track_at(&mut self, track_id: u32, position: Duration) -> AppResult<()> {
+        self.play_track(track_id).await?;
+        self.sink.seek(position)?;
+        self.position.send(self.sink.position())?;
+        Ok(())
+    }
+
     async fn play_album(&mut self, album_id: &str, index: usize) -> AppResult<()> {
         let album: Album = self.client.album(album_id).await?;
+        let ban_list = self.ban_list().await;
+        let skipped = |t: &Track| !t.available || ban_list.is_banned(t);
 
         let unstreamable_tracks_to_index = album
             .tracks
             .iter()
             .take(index)
-            .filter(|t| !t.available)
+            .filter(|t| skipped(t))
             .count() as i32;
 
         let mut tracklist = Tracklist::new(
@@ -401,7 +939,7 @@ impl Player {
                 id: album.id,
                 image: Some(album.image),
             }),
-            tracks_to_queue_items(album.tracks.into_iter().filter(|t| t.available).collect()),
+            tracks_to_queue_items(album.tracks.into_iter().filter(|t| !skipped(t)).collect()),
         );
 
         tracklist.skip_to_track(index as i32 - unstreamable_tracks_to_index);
@@ -411,8 +949,10 @@ impl Player {
     async fn play_top_tracks(&mut self, artist_id: u32, index: usize) -> AppResult<()> {
         let artist = self.client.artist_page(artist_id).await?;
         let tracks = artist.top_tracks;
+        let ban_list = self.ban_list().await;
+        let skipped = |t: &Track| !t.available || ban_list.is_banned(t);
         let unstreamable_tracks_to_index =
-            tracks.iter().take(index).filter(|t| !t.available).count() as i32;
+            tracks.iter().take(index).filter(|t| skipped(t)).count() as i32;
 
         let mut tracklist = Tracklist::new(
             TracklistType::TopTracks(tracklist::TopTracklist {
@@ -420,7 +960,7 @@ impl Player {
                 id: artist_id,
                 image: artist.image,
             }),
-            tracks_to_queue_items(tracks.into_iter().filter(|t| t.available).collect()),
+            tracks_to_queue_items(tracks.into_iter().filter(|t| !skipped(t)).collect()),
         );
 
         tracklist.skip_to_track(index as i32 - unstreamable_tracks_to_index);
@@ -428,12 +968,13 @@ impl Player {
     }
 
     async fn play_tracks(&mut self, ids: Vec<u32>, shuffle: bool) -> AppResult<()> {
+        let ban_list = self.ban_list().await;
         let mut tracks: Vec<_> = self
             .client
             .tracks(ids)
             .await?
             .into_iter()
-            .filter(|t| t.available)
+            .filter(|t| t.available && !ban_list.is_banned(t))
             .collect();
 
         if shuffle {
@@ -453,6 +994,8 @@ impl Player {
         shuffle: bool,
     ) -> AppResult<()> {
         let playlist = self.client.playlist(playlist_id).await?;
+        let ban_list = self.ban_list().await;
+        let skipped = |t: &Track| !t.available || ban_list.is_banned(t);
 
         let unstreamable_tracks_to_index = match shuffle {
             true => 0,
@@ -460,7 +1003,7 @@ impl Player {
                 .tracks
                 .iter()
                 .take(index)
-                .filter(|t| !t.available)
+                .filter(|t| skipped(t))
                 .count() as i32,
         };
 
@@ -468,7 +1011,7 @@ impl Player {
             playlist
                 .tracks
                 .into_iter()
-                .filter(|t| t.available)
+                .filter(|t| !skipped(t))
                 .collect(),
         );
 
@@ -490,6 +1033,32 @@ impl Player {
         self.new_queue(tracklist).await
     }
 
+    /// Short tracks can finish before the following track is prefetched, so it is downloaded ahead
+    fn precache_track(&self, track: Track) {
+        let downloader = self.downloader.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = downloader.precache_track(&track).await {
+                tracing::warn!("Unable to pre-cache track {}: {err}", track.title);
+            }
+        });
+    }
+
+    fn precache_album(&self, album_id: String) {
+        if self.precache_album_tracks == 0 || self.client.data_saver() {
+            return;
+        }
+
+        let downloader = self.downloader.clone();
+        let number_of_tracks = self.precache_album_tracks;
+
+        tokio::spawn(async move {
+            if let Err(err) = downloader.precache_album(&album_id, number_of_tracks).await {
+                tracing::warn!("Unable to pre-cache album {album_id}: {err}");
+            }
+        });
+    }
+
     async fn remove_index_from_queue(&mut self, index: usize) -> AppResult<()> {
         let mut tracklist = self.tracklist_rx.borrow().clone();
 
@@ -541,6 +1110,107 @@ impl Player {
         Ok(())
     }
 
+    /// Looks the tracks up a few at a time. Tracks that can not be found are left out, so one
+    /// missing track does not lose the rest of the queue
+    async fn connect_queue_items(
+        &self,
+        items: Vec<NewQueueItem>,
+        first_index: usize,
+    ) -> Vec<QueueItem> {
+        let client = &self.client;
+        let tracks: Vec<_> = stream::iter(items)
+            .map(|item| async move { (item.queue_id, client.track(item.track_id).await) })
+            .buffered(CONCURRENT_LOOKUPS)
+            .collect()
+            .await;
+
+        tracks
+            .into_iter()
+            .filter_map(|(queue_id, track)| match track {
+                Ok(track) => Some((queue_id, track)),
+                Err(err) => {
+                    tracing::warn!("Leaving a track out of the queue: {err}");
+                    None
+                }
+            })
+            .enumerate()
+            .map(|(offset, (queue_id, track))| QueueItem {
+                track,
+                queue_id,
+                index: first_index + offset,
+                queued_by_user: false,
+            })
+            .collect()
+    }
+
+    async fn insert_queue_items(
+        &mut self,
+        items: Vec<NewQueueItem>,
+        index: Option<usize>,
+    ) -> AppResult<()> {
+        let mut tracklist = self.tracklist_rx.borrow().clone();
+        if !tracklist.from_connect() {
+            tracing::debug!("Ignoring a Connect queue edit, the local queue is playing");
+            return Ok(());
+        }
+        tracklist.set_list_type(TracklistType::Tracks);
+
+        let index = index.unwrap_or(tracklist.total());
+        let queue_items = self.connect_queue_items(items, index).await;
+        tracklist.insert_items(index, queue_items);
+
+        self.update_queue(tracklist).await
+    }
+
+    /// The bans, or none when they can not be read, so playback does not fail over them
+    async fn ban_list(&self) -> BanList {
+        self.database.get_ban_list().await.unwrap_or_else(|err| {
+            tracing::warn!("Unable to read the ban list: {err}");
+            BanList::default()
+        })
+    }
+
+    /// Drops the banned tracks still to come from the queue, and skips the playing track
+    /// when it is banned
+    async fn apply_bans(&mut self) -> AppResult<()> {
+        let ban_list = self.ban_list().await;
+        let mut tracklist = self.tracklist_rx.borrow().clone();
+
+        let playing_is_banned = tracklist
+            .current_track()
+            .is_some_and(|track| ban_list.is_banned(track));
+        let banned: Vec<u64> = tracklist
+            .queue()
+            .iter()
+            .filter(|item| {
+                item.track.status == TrackStatus::Unplayed && ban_list.is_banned(&item.track)
+            })
+            .map(|item| item.queue_id)
+            .collect();
+
+        if !banned.is_empty() {
+            tracklist.remove_queue_ids(&banned);
+            self.update_queue(tracklist).await?;
+        }
+
+        if playing_is_banned {
+            self.next().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn remove_queue_items(&mut self, queue_ids: Vec<u64>) -> AppResult<()> {
+        let mut tracklist = self.tracklist_rx.borrow().clone();
+        if !tracklist.from_connect() {
+            tracing::debug!("Ignoring a Connect queue edit, the local queue is playing");
+            return Ok(());
+        }
+
+        tracklist.remove_queue_ids(&queue_ids);
+        self.update_queue(tracklist).await
+    }
+
     async fn reorder_queue(&mut self, new_order: Vec<usize>) -> AppResult<()> {
         let mut tracklist = self.tracklist_rx.borrow().clone();
 
@@ -553,32 +1223,63 @@ impl Player {
     }
 
     async fn tick(&mut self) -> AppResult<()> {
+        self.update_quiet_hours().await?;
+
+        let diagnostics = self.stats.snapshot();
+        if diagnostics != self.last_diagnostics {
+            self.last_diagnostics = diagnostics;
+            self.broadcast.send(Notification::Diagnostics(diagnostics));
+        }
+
         if *self.target_status.borrow() != Status::Playing {
+            self.stall_watchdog.reset();
             return Ok(());
         }
 
         let position = self.sink.position();
         self.position.send(position)?;
 
-        let duration = self
+        if self.playback_stalled(position) {
+            return self.recover_stalled_playback(position).await;
+        }
+
+        let current_track = self
             .tracklist_rx
             .borrow()
             .current_track()
-            .map(|x| x.duration_seconds);
+            .map(|x| (x.id, x.duration_seconds, self.is_long(x)));
 
-        if let Some(duration) = duration {
-            let position = position.as_secs();
+        if let Some((track_id, _, true)) = current_track {
+            self.update_resume_point(track_id, position).await;
+        }
 
-            let track_about_to_finish = (duration as i16 - position as i16) < 60;
+        if let Some((_, duration, _)) = current_track {
+            let remaining = Duration::from_secs(duration.into()).saturating_sub(position);
+            let track_about_to_finish = remaining < self.prefetch_window;
 
-            if track_about_to_finish && !self.next_track_is_queried {
+            if track_about_to_finish && !self.next_track_is_queried && !self.client.data_saver() {
                 tracing::info!("Track about to finish");
 
                 let tracklist = self.tracklist_rx.borrow().clone();
 
                 if let Some(next_track) = tracklist.next_track() {
                     tracing::info!("Query next track: {} from tick", &next_track.title);
-                    self.query_track(next_track, true).await?;
+                    match self.query_track(next_track, true).await {
+                        // Skipped when the current track finishes
+                        Err(Error::RegionLocked) => {
+                            tracing::info!("{} is locked in this region", next_track.title);
+                            self.next_track_in_sink_queue = false;
+                            return Ok(());
+                        }
+                        result => result?,
+                    }
+
+                    if Duration::from_secs(next_track.duration_seconds.into())
+                        < self.prefetch_window
+                        && let Some(track_after_next) = tracklist.track_after_next()
+                    {
+                        self.precache_track(track_after_next.clone());
+                    }
                 }
             }
         }
@@ -586,6 +1287,111 @@ impl Player {
         Ok(())
     }
 
+    /// Whether the playing track has not advanced for the stall timeout, like when the output
+    /// was starved and never picked up again. A track still downloading is only rebuffering,
+    /// the download has a watchdog of its own
+    fn playback_stalled(&mut self, position: Duration) -> bool {
+        let downloading = self
+            .tracklist_rx
+            .borrow()
+            .current_track()
+            .is_some_and(|track| self.downloader.is_streaming(track.id));
+        if downloading || self.sink.is_empty() {
+            self.stall_watchdog.reset();
+            return false;
+        }
+
+        self.stall_watchdog.stalled(position, Instant::now())
+    }
+
+    async fn recover_stalled_playback(&mut self, position: Duration) -> AppResult<()> {
+        let Some(track) = self.tracklist_rx.borrow().current_track().cloned() else {
+            return Ok(());
+        };
+
+        tracing::warn!("{} stopped at {position:?}, restarting it", track.title);
+        self.stats.record_recovery();
+        self.broadcast.send(Notification::Warning(format!(
+            "Playback of {} stopped, restarting it",
+            track.title
+        )));
+        self.restart_current_track(&track, position).await
+    }
+
+    async fn download_stalled(&mut self, track_id: u32) -> AppResult<()> {
+        self.stats.record_download_stall();
+
+        let current_track = self.tracklist_rx.borrow().current_track().cloned();
+        let Some(track) = current_track.filter(|track| track.id == track_id) else {
+            // The next track is restarted by the playback watchdog if it stalls once playing
+            tracing::info!("Download of queued track {track_id} stalled");
+            return Ok(());
+        };
+
+        if *self.target_status.borrow() == Status::Paused {
+            return Ok(());
+        }
+
+        tracing::warn!("Download of {} stalled, restarting it", track.title);
+        self.broadcast.send(Notification::Warning(format!(
+            "Download of {} stalled, retrying",
+            track.title
+        )));
+        let position = self.sink.position();
+        self.restart_current_track(&track, position).await
+    }
+
+    /// Connections made on the previous network may never complete, so a track still downloading
+    /// is restarted right away rather than once the download stalls
+    async fn refresh_stream(&mut self) -> AppResult<()> {
+        if *self.target_status.borrow() == Status::Paused {
+            return Ok(());
+        }
+
+        let Some(track) = self.tracklist_rx.borrow().current_track().cloned() else {
+            return Ok(());
+        };
+        if !self.downloader.is_streaming(track.id) {
+            return Ok(());
+        }
+
+        tracing::info!(
+            "Network changed while downloading {}, restarting it",
+            track.title
+        );
+        self.stats.record_recovery();
+        let position = self.sink.position();
+        self.restart_current_track(&track, position).await
+    }
+
+    /// Streams the track again from a fresh url and continues where it stopped. Segments that
+    /// were already downloaded are kept
+    async fn restart_current_track(&mut self, track: &Track, position: Duration) -> AppResult<()> {
+        self.stall_watchdog.reset();
+        self.sink.clear()?;
+        self.next_track_is_queried = false;
+        self.next_track_in_sink_queue = false;
+        self.queued_next = None;
+
+        self.set_target_status(Status::Buffering);
+        let mut result = self.query_track(track, false).await;
+        if result.is_ok() && !position.is_zero() {
+            result = self.sink.seek(position);
+        }
+
+        match result {
+            Ok(()) => {
+                self.position.send(self.sink.position())?;
+                Ok(())
+            }
+            // Paused rather than left buffering, so playing again retries
+            Err(err) => {
+                self.pause().await;
+                Err(err)
+            }
+        }
+    }
+
     async fn handle_message(&mut self, notification: ControlCommand) -> AppResult<()> {
         match notification {
             ControlCommand::Album { id, index } => {
@@ -600,6 +1406,13 @@ impl Player {
             ControlCommand::Track { id } => {
                 self.play_track(id).await?;
             }
+            ControlCommand::TrackInQuality { id, quality } => {
+                self.client.override_quality(id, quality);
+                self.play_track(id).await?;
+            }
+            ControlCommand::PlayTrackAt { id, position } => {
+                self.play_track_at(id, position).await?;
+            }
             ControlCommand::Tracks { ids, shuffle } => {
                 self.play_tracks(ids, shuffle).await?;
             }
@@ -616,7 +1429,7 @@ impl Player {
                 self.play().await?;
             }
             ControlCommand::Pause => {
-                self.pause();
+                self.pause().await;
             }
             ControlCommand::SkipToPosition {
                 new_position,
@@ -630,12 +1443,44 @@ impl Player {
             ControlCommand::JumpBackward => {
                 self.jump_backward()?;
             }
+            ControlCommand::JumpBy { offset_us } => {
+                self.jump_by(offset_us)?;
+            }
             ControlCommand::Seek { time } => {
                 self.seek(time)?;
             }
+            ControlCommand::SeekPercentage { percentage } => {
+                self.seek_percentage(percentage)?;
+            }
             ControlCommand::SetVolume { volume } => {
                 self.set_volume(volume).await?;
             }
+            ControlCommand::SetBalance { balance } => self.set_balance(balance).await?,
+            ControlCommand::SetMonoDownmix { mono_downmix } => {
+                self.set_mono_downmix(mono_downmix).await?
+            }
+            ControlCommand::SetTrimSilence { enabled } => self.set_trim_silence(enabled).await?,
+            ControlCommand::SetMaxAudioQuality { quality } => {
+                self.set_max_audio_quality(quality).await?
+            }
+            ControlCommand::SetDataSaver { enabled } => self.set_data_saver(enabled).await?,
+            ControlCommand::SetPlayPreviews { enabled } => self.set_play_previews(enabled).await?,
+            ControlCommand::SetZoneVolume { zone, volume } => {
+                self.set_zone(zone, |settings| settings.set_volume(volume))
+                    .await?
+            }
+            ControlCommand::SetZoneEnabled { zone, enabled } => {
+                self.set_zone(zone, |settings| settings.set_enabled(enabled))
+                    .await?
+            }
+            ControlCommand::SetMute { muted } => self.set_mute(muted)?,
+            ControlCommand::SetQuietHours { quiet_hours } => {
+                self.set_quiet_hours(quiet_hours).await?
+            }
+            ControlCommand::ToggleMute => {
+                let muted = *self.muted.borrow();
+                self.set_mute(!muted)?;
+            }
             ControlCommand::AddTracksToQueue { ids } => self.add_tracks_to_queue(ids).await?,
             ControlCommand::RemoveIndexFromQueue { index } => {
                 self.remove_index_from_queue(index).await?
@@ -643,41 +1488,92 @@ impl Player {
             ControlCommand::PlayTracksNext { ids } => self.play_tracks_next(ids).await?,
             ControlCommand::ReorderQueue { new_order } => self.reorder_queue(new_order).await?,
             ControlCommand::NewQueue { items, play } => self.new_track_queue(items, play).await?,
+            ControlCommand::InsertQueueItems { items, index } => {
+                self.insert_queue_items(items, index).await?
+            }
+            ControlCommand::RemoveQueueItems { queue_ids } => {
+                self.remove_queue_items(queue_ids).await?
+            }
             ControlCommand::ClearQueue => self.clear_queue().await?,
+            ControlCommand::PrecacheAlbum { id } => self.precache_album(id),
+            ControlCommand::RefreshStream => self.refresh_stream().await?,
+            ControlCommand::ApplyBans => self.apply_bans().await?,
         }
         Ok(())
     }
 
+    fn send_track_started(&mut self, track_id: u32, format: StreamFormat, time: SystemTime) {
+        let timestamp_us = timestamp_us(time);
+        let previous = self.current_format.replace(format);
+
+        // Nobody listening is not an error
+        if previous != Some(format) {
+            _ = self.transitions.send(TransitionEvent::FormatChanged {
+                timestamp_us,
+                previous,
+                format,
+            });
+        }
+        _ = self.transitions.send(TransitionEvent::TrackStarted {
+            track_id,
+            timestamp_us,
+            format,
+        });
+    }
+
     async fn track_finished(&mut self) -> AppResult<()> {
         let mut tracklist = self.tracklist_rx.borrow().clone();
+        let finished_at = *self.track_finished.borrow();
+
+        if let Some(track) = tracklist.current_track() {
+            self.stats.record_track_played();
+            if let Err(err) = self.database.add_track_play(track.id).await {
+                tracing::warn!("Unable to add track to play history: {err}");
+            }
+            if self.is_long(track) {
+                if let Err(err) = self.database.clear_resume_point(track.id).await {
+                    tracing::warn!("Unable to clear resume point: {err}");
+                }
+                self.resume_checked = None;
+                self.resume_saved = None;
+            }
+            _ = self.transitions.send(TransitionEvent::TrackEnded {
+                track_id: track.id,
+                timestamp_us: timestamp_us(finished_at),
+            });
+        }
 
         let current_position = tracklist.current_position();
         let new_position = current_position + 1;
 
-        let next_track = tracklist.skip_to_track(new_position as i32);
+        let mut has_next_track = tracklist.skip_to_track(new_position as i32).is_some();
 
-        match next_track {
-            Some(next_track) => {
-                if !self.next_track_in_sink_queue {
-                    tracing::info!(
-                        "Track finished and next track is not in queue. Resetting queue, and querying track."
-                    );
-                    self.sink.clear()?;
-                    if let Some(delay) = self.sample_rate_change_delay {
-                        tracing::info!("Waiting for sample rate change delay");
-                        sleep(delay).await;
-                    }
-                    self.query_track(next_track, false).await?;
+        if has_next_track {
+            if self.next_track_in_sink_queue {
+                if let Some((track_id, format)) = self.queued_next.take() {
+                    self.send_track_started(track_id, format, finished_at);
                 }
-            }
-            None => {
-                tracklist.reset();
-                self.set_target_status(Status::Paused);
-                self.sink.pause();
+            } else {
+                tracing::info!(
+                    "Track finished and next track is not in queue. Resetting queue, and querying track."
+                );
+                self.stats.record_gap();
                 self.sink.clear()?;
-                self.position.send(Default::default())?;
+                if let Some(delay) = self.sample_rate_change_delay {
+                    tracing::info!("Waiting for sample rate change delay");
+                    sleep(delay).await;
+                }
+                has_next_track = self.query_playable_track(&mut tracklist).await?;
             }
         }
+
+        if !has_next_track {
+            tracklist.reset();
+            self.set_target_status(Status::Paused);
+            self.sink.pause();
+            self.sink.clear()?;
+            self.position.send(Default::default())?;
+        }
         self.next_track_is_queried = false;
         self.broadcast_tracklist(tracklist).await?;
         Ok(())
@@ -687,22 +1583,36 @@ impl Player {
         let mut interval = tokio::time::interval(Duration::from_millis(INTERVAL_MS));
 
         loop {
+            let skip_at = self.pending_skip.map(|(_, at)| at);
+
             select! {
                 _ = interval.tick() => {
                     if let Err(err) = self.tick().await {
-                        self.broadcast.send_error(err.to_string());
+                        self.broadcast.report(&err);
                     };
                 }
 
                 Some(notification) = self.controls_rx.recv() => {
                     if let Err(err) = self.handle_message(notification).await {
-                        self.broadcast.send_error(err.to_string());
+                        self.broadcast.report(&err);
+                    };
+                }
+
+                _ = sleep_until(skip_at.unwrap_or_else(Instant::now).into()), if skip_at.is_some() => {
+                    if let Err(err) = self.finish_skip().await {
+                        self.broadcast.report(&err);
+                    };
+                }
+
+                Some(track_id) = self.stalled_rx.recv() => {
+                    if let Err(err) = self.download_stalled(track_id).await {
+                        self.broadcast.report(&err);
                     };
                 }
 
                 Ok(_) = self.track_finished.changed() => {
                     if let Err(err) = self.track_finished().await {
-                        self.broadcast.send_error(err.to_string());
+                        self.broadcast.report(&err);
                     };
                 }
 
@@ -724,6 +1634,828 @@ fn tracks_to_queue_items(tracks: Vec<Track>) -> Vec<QueueItem> {
             track,
             queue_id: i as u64,
             index: i,
+            queued_by_user: false,
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::BanKind, fixtures::Fixtures};
+    use std::path::{Path, PathBuf};
+
+    /// Half a second of a 440 Hz tone as 16-bit mono WAV
+    fn write_tone(path: &Path) {
+        let sample_rate = 44_100_u32;
+        let samples: Vec<u8> = (0..sample_rate / 2)
+            .map(|index| {
+                let time = index as f32 / sample_rate as f32;
+                ((time * 440.0 * std::f32::consts::TAU).sin() * 8_000.0) as i16
+            })
+            .flat_map(i16::to_le_bytes)
+            .collect();
+
+        let mut wav = vec![];
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16_u32.to_le_bytes());
+        wav.extend_from_slice(&1_u16.to_le_bytes());
+        wav.extend_from_slice(&1_u16.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        wav.extend_from_slice(&2_u16.to_le_bytes());
+        wav.extend_from_slice(&16_u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&samples);
+        std::fs::write(path, wav).unwrap();
+    }
+
+    /// Fixture catalog of one album with two half second tracks
+    fn fixture_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "qobuz-player-fixtures-{}-{}",
+            std::process::id(),
+            timestamp_us(SystemTime::now())
+        ));
+        std::fs::create_dir_all(dir.join("tracks")).unwrap();
+        std::fs::write(
+            dir.join("catalog.json"),
+            r#"{
+                "albums": [{
+                    "id": "album-1", "title": "Album",
+                    "artist": { "id": 1, "name": "Artist" },
+                    "tracks": [
+                        { "id": 1, "title": "First", "duration_seconds": 1 },
+                        { "id": 2, "title": "Second", "duration_seconds": 1 }
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+        write_tone(&dir.join("tracks/1.wav"));
+        write_tone(&dir.join("tracks/2.wav"));
+        dir
+    }
+
+    /// Mono 16-bit FLAC of the samples, in verbatim subframes of 4096 frames
+    fn write_flac(path: &Path, sample_rate: u32, samples: &[i16]) {
+        const BLOCK_SIZE: usize = 4096;
+
+        fn crc8(bytes: &[u8]) -> u8 {
+            bytes.iter().fold(0, |crc, byte| {
+                (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+                    0 => crc << 1,
+                    _ => (crc << 1) ^ 0x07,
+                })
+            })
+        }
+
+        fn crc16(bytes: &[u8]) -> u16 {
+            bytes.iter().fold(0, |crc, byte| {
+                (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+                    0 => crc << 1,
+                    _ => (crc << 1) ^ 0x8005,
+                })
+            })
+        }
+
+        let mut flac = b"fLaC".to_vec();
+        flac.extend_from_slice(&[0x80, 0, 0, 34]);
+        flac.extend_from_slice(&(BLOCK_SIZE as u16).to_be_bytes());
+        flac.extend_from_slice(&(BLOCK_SIZE as u16).to_be_bytes());
+        flac.extend_from_slice(&[0; 6]);
+        let stream_info = ((sample_rate as u64) << 44) | ((16 - 1) << 36) | samples.len() as u64;
+        flac.extend_from_slice(&stream_info.to_be_bytes());
+        flac.extend_from_slice(&[0; 16]);
+
+        for (frame_number, block) in samples.chunks(BLOCK_SIZE).enumerate() {
+            assert!(frame_number < 128, "frame numbers are written in one byte");
+            let mut frame = vec![0xFF, 0xF8, 0x70, 0x08, frame_number as u8];
+            frame.extend_from_slice(&(block.len() as u16 - 1).to_be_bytes());
+            frame.push(crc8(&frame));
+            frame.push(0x02);
+            frame.extend(block.iter().flat_map(|sample| sample.to_be_bytes()));
+            frame.extend_from_slice(&crc16(&frame).to_be_bytes());
+            flac.extend_from_slice(&frame);
+        }
+
+        std::fs::write(path, flac).unwrap();
+    }
+
+    /// Samples of a track that is never silent, so a gap shows up as zeros in the output
+    fn audible_samples(sample_rate: u32, seconds: u32) -> Vec<i16> {
+        (0..sample_rate * seconds)
+            .map(|index| {
+                let time = index as f32 / sample_rate as f32;
+                (8_000.0 + (time * 440.0 * std::f32::consts::TAU).sin() * 4_000.0) as i16
+            })
+            .collect()
+    }
+
+    /// Fixture catalog of one album with two FLAC tracks of two seconds, in these sample rates
+    fn flac_fixture_dir(sample_rates: [u32; 2]) -> PathBuf {
+        let dir = fixture_dir();
+        for (id, sample_rate) in [1, 2].into_iter().zip(sample_rates) {
+            std::fs::remove_file(dir.join(format!("tracks/{id}.wav"))).unwrap();
+            write_flac(
+                &dir.join(format!("tracks/{id}.flac")),
+                sample_rate,
+                &audible_samples(sample_rate, 2),
+            );
+        }
+        std::fs::write(
+            dir.join("catalog.json"),
+            r#"{
+                "albums": [{
+                    "id": "album-1", "title": "Album",
+                    "artist": { "id": 1, "name": "Artist" },
+                    "tracks": [
+                        { "id": 1, "title": "First", "duration_seconds": 2 },
+                        { "id": 2, "title": "Second", "duration_seconds": 2 }
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+        dir
+    }
+
+    async fn fixture_player(
+        dir: &Path,
+        pool: sqlx::Pool<sqlx::Sqlite>,
+        broadcast: Arc<NotificationBroadcast>,
+        audio_output: AudioOutput,
+        quiet_hours: Option<QuietHours>,
+        volume_limit: f32,
+    ) -> Player {
+        let settings = fixture_settings(dir, audio_output, quiet_hours, volume_limit);
+        fixture_player_with(dir, pool, broadcast, settings).await
+    }
+
+    async fn fixture_player_with(
+        dir: &Path,
+        pool: sqlx::Pool<sqlx::Sqlite>,
+        broadcast: Arc<NotificationBroadcast>,
+        settings: PlayerSettings,
+    ) -> Player {
+        let database = Arc::new(Database::init(pool).await.unwrap());
+        let client = Arc::new(Client::with_fixtures(Fixtures::load(dir).unwrap()));
+        Player::new(
+            Tracklist::default(),
+            client,
+            1.0,
+            broadcast,
+            database,
+            settings,
+        )
+        .unwrap()
+    }
+
+    fn fixture_settings(
+        dir: &Path,
+        audio_output: AudioOutput,
+        quiet_hours: Option<QuietHours>,
+        volume_limit: f32,
+    ) -> PlayerSettings {
+        PlayerSettings {
+            audio_cache_dir: dir.join("cache"),
+            audio_cache_max_bytes: None,
+            state_change_delay: None,
+            sample_rate_change_delay: None,
+            preferred_device_id: None,
+            zone_devices: vec![],
+            saved_zones: vec![],
+            precache_album_tracks: 0,
+            jump_forward_seconds: 10,
+            jump_backward_seconds: 10,
+            volume_curve: VolumeCurve::default(),
+            volume_ramp: None,
+            volume_limit,
+            skip_fade: None,
+            balance: 0.0,
+            mono_downmix: false,
+            trim_silence: false,
+            dsp_command: None,
+            prefetch_window: Duration::from_secs(60),
+            resume_threshold: None,
+            stall_timeout: None,
+            hardware_mixer: None,
+            audio_output,
+            quiet_hours,
+        }
+    }
+
+    /// The front ends watch the status and position, which the player sends to
+    fn watch(player: &Player) -> (StatusReceiver, PositionReceiver) {
+        (player.status(), player.position())
+    }
+
+    /// Plays the album and returns the transitions until the second track ended, with the
+    /// number of gaps the player recorded
+    async fn play_album_transitions(mut player: Player) -> (Vec<TransitionEvent>, u64) {
+        let _watching = watch(&player);
+        let controls = player.controls();
+        let stats = player.stats.clone();
+        let mut transitions = player.transitions();
+        let (exit_sender, exit_receiver) = broadcast::channel(1);
+        let player_loop = tokio::spawn(async move { player.player_loop(exit_receiver).await });
+
+        controls.play_album("album-1", 0);
+
+        let mut events = vec![];
+        let played = tokio::time::timeout(Duration::from_secs(20), async {
+            while let Ok(event) = transitions.recv().await {
+                let last = matches!(event, TransitionEvent::TrackEnded { track_id: 2, .. });
+                events.push(event);
+                if last {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        exit_sender.send(true).unwrap();
+        player_loop.await.unwrap().unwrap();
+
+        assert!(played.is_ok(), "album did not finish, got {events:?}");
+        (events, stats.snapshot().gaps)
+    }
+
+    #[sqlx::test]
+    async fn plays_fixture_album_through_null_output(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            1.0,
+        )
+        .await;
+        let _watching = watch(&player);
+
+        let controls = player.controls();
+        let mut transitions = player.transitions();
+        let (exit_sender, exit_receiver) = broadcast::channel(1);
+        let player_loop = tokio::spawn(async move { player.player_loop(exit_receiver).await });
+
+        controls.play_album("album-1", 0);
+
+        let mut events = vec![];
+        let played = tokio::time::timeout(Duration::from_secs(10), async {
+            while let Ok(event) = transitions.recv().await {
+                match event {
+                    TransitionEvent::TrackStarted { track_id, .. } => {
+                        events.push(("started", track_id))
+                    }
+                    TransitionEvent::TrackEnded { track_id, .. } => {
+                        events.push(("ended", track_id));
+                        if track_id == 2 {
+                            break;
+                        }
+                    }
+                    TransitionEvent::FormatChanged { .. } => {}
+                }
+            }
+        })
+        .await;
+
+        exit_sender.send(true).unwrap();
+        player_loop.await.unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(played.is_ok(), "album did not finish, got {events:?}");
+        assert_eq!(
+            events,
+            vec![("started", 1), ("ended", 1), ("started", 2), ("ended", 2)]
+        );
+    }
+
+    #[sqlx::test]
+    async fn plays_when_the_queue_history_fails(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let mut player = fixture_player(
+            &dir,
+            pool.clone(),
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            1.0,
+        )
+        .await;
+        let _watching = watch(&player);
+
+        sqlx::query("DROP TABLE recent_tracklists")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        player.play_album("album-1", 0).await.unwrap();
+        assert_eq!(
+            player
+                .tracklist_rx
+                .borrow()
+                .current_track()
+                .map(|track| track.id),
+            Some(1)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[sqlx::test]
+    async fn skips_once_the_fade_is_done(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let settings = PlayerSettings {
+            skip_fade: Some(Duration::from_secs(60)),
+            ..fixture_settings(&dir, AudioOutput::Null, None, 1.0)
+        };
+        let broadcast = Arc::new(NotificationBroadcast::new());
+        let mut player = fixture_player_with(&dir, pool, broadcast, settings).await;
+        let _watching = watch(&player);
+
+        player.play_album("album-1", 0).await.unwrap();
+
+        // The player loop keeps handling commands while the track fades out
+        tokio::time::timeout(Duration::from_secs(1), player.next())
+            .await
+            .expect("next waited for the fade")
+            .unwrap();
+        assert_eq!(player.tracklist_rx.borrow().current_position(), 0);
+        assert_eq!(player.skip_origin(), 1);
+
+        player.finish_skip().await.unwrap();
+        assert_eq!(player.tracklist_rx.borrow().current_position(), 1);
+        assert!(player.pending_skip.is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[sqlx::test]
+    async fn volume_ramps_do_not_hold_up_the_player(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let settings = PlayerSettings {
+            volume_ramp: Some(Duration::from_secs(60)),
+            ..fixture_settings(&dir, AudioOutput::Null, None, 1.0)
+        };
+        let broadcast = Arc::new(NotificationBroadcast::new());
+        let mut player = fixture_player_with(&dir, pool, broadcast, settings).await;
+        let _watching = watch(&player);
+
+        player.play_album("album-1", 0).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), player.pause())
+            .await
+            .expect("pause waited for the ramp");
+        assert_eq!(*player.target_status.borrow(), Status::Paused);
+
+        tokio::time::timeout(Duration::from_secs(1), player.play())
+            .await
+            .expect("play waited for the ramp")
+            .unwrap();
+        assert_eq!(*player.target_status.borrow(), Status::Playing);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[sqlx::test]
+    async fn tracks_locked_in_the_region_are_skipped(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        std::fs::remove_file(dir.join("tracks/2.wav")).unwrap();
+        write_tone(&dir.join("tracks/3.wav"));
+        std::fs::write(
+            dir.join("catalog.json"),
+            r#"{
+                "albums": [{
+                    "id": "album-1", "title": "Album",
+                    "artist": { "id": 1, "name": "Artist" },
+                    "tracks": [
+                        { "id": 1, "title": "First", "duration_seconds": 1 },
+                        { "id": 2, "title": "Second", "duration_seconds": 1, "region_locked": true },
+                        { "id": 3, "title": "Third", "duration_seconds": 1 }
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            1.0,
+        )
+        .await;
+        let _watching = watch(&player);
+
+        let controls = player.controls();
+        let mut transitions = player.transitions();
+        let (exit_sender, exit_receiver) = broadcast::channel(1);
+        let player_loop = tokio::spawn(async move { player.player_loop(exit_receiver).await });
+
+        controls.play_album("album-1", 0);
+
+        let mut started = vec![];
+        let played = tokio::time::timeout(Duration::from_secs(10), async {
+            while let Ok(event) = transitions.recv().await {
+                match event {
+                    TransitionEvent::TrackStarted { track_id, .. } => started.push(track_id),
+                    TransitionEvent::TrackEnded { track_id: 3, .. } => break,
+                    _ => {}
+                }
+            }
+        })
+        .await;
+
+        exit_sender.send(true).unwrap();
+        player_loop.await.unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(played.is_ok(), "album did not finish, started {started:?}");
+        assert_eq!(started, vec![1, 3]);
+    }
+
+    #[sqlx::test]
+    async fn failing_command_keeps_player_loop_running(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let notifications = Arc::new(NotificationBroadcast::new());
+        let mut notification_receiver = notifications.subscribe();
+        let mut player =
+            fixture_player(&dir, pool, notifications, AudioOutput::Null, None, 1.0).await;
+        let _watching = watch(&player);
+
+        let controls = player.controls();
+        let mut transitions = player.transitions();
+        let (exit_sender, exit_receiver) = broadcast::channel(1);
+        let player_loop = tokio::spawn(async move { player.player_loop(exit_receiver).await });
+
+        controls.play_album("missing-album", 0);
+        let error = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(Notification::Error(message)) = notification_receiver.recv().await {
+                    break message;
+                }
+            }
+        })
+        .await;
+
+        controls.play_album("album-1", 0);
+        let started = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(TransitionEvent::TrackStarted { track_id, .. }) = transitions.recv().await
+                {
+                    break track_id;
+                }
+            }
+        })
+        .await;
+
+        let still_running = !player_loop.is_finished();
+        exit_sender.send(true).unwrap();
+        player_loop.await.unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(error.is_ok_and(|message| message.contains("missing-album")));
+        assert!(still_running);
+        assert_eq!(started.ok(), Some(1));
+    }
+
+    #[test]
+    fn playback_stalls_when_the_position_stops_advancing() {
+        let start = Instant::now();
+        let mut watchdog = StallWatchdog {
+            timeout: Some(Duration::from_secs(10)),
+            last_progress: None,
+        };
+
+        assert!(!watchdog.stalled(Duration::from_secs(1), start));
+        assert!(!watchdog.stalled(Duration::from_secs(1), start + Duration::from_secs(9)));
+        assert!(watchdog.stalled(Duration::from_secs(1), start + Duration::from_secs(10)));
+
+        assert!(!watchdog.stalled(Duration::from_secs(2), start + Duration::from_secs(11)));
+        assert!(!watchdog.stalled(Duration::from_secs(2), start + Duration::from_secs(20)));
+
+        watchdog.reset();
+        assert!(!watchdog.stalled(Duration::from_secs(2), start + Duration::from_secs(30)));
+
+        let mut disabled = StallWatchdog {
+            timeout: None,
+            last_progress: None,
+        };
+        assert!(!disabled.stalled(Duration::ZERO, start));
+        assert!(!disabled.stalled(Duration::ZERO, start + Duration::from_secs(3600)));
+    }
+
+    #[sqlx::test]
+    async fn failed_restart_pauses_playback(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        std::fs::remove_file(dir.join("tracks/1.wav")).unwrap();
+        std::fs::write(
+            dir.join("catalog.json"),
+            r#"{
+                "albums": [{
+                    "id": "album-1", "title": "Album",
+                    "artist": { "id": 1, "name": "Artist" },
+                    "tracks": [
+                        { "id": 1, "title": "First", "duration_seconds": 1, "region_locked": true }
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            1.0,
+        )
+        .await;
+        let _watching = watch(&player);
+
+        let track = player.client.track(1).await.unwrap();
+        let result = player
+            .restart_current_track(&track, Duration::from_secs(1))
+            .await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(result, Err(Error::RegionLocked)));
+        assert_eq!(*player.target_status.borrow(), Status::Paused);
+    }
+
+    #[sqlx::test]
+    async fn plays_a_track_from_the_position(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            1.0,
+        )
+        .await;
+        let (_status, position) = watch(&player);
+
+        let result = player.play_track_at(1, Duration::from_millis(300)).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok());
+        assert!(*position.borrow() >= Duration::from_millis(300));
+    }
+
+    #[sqlx::test]
+    async fn bans_reach_the_queue_and_the_playing_track(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            1.0,
+        )
+        .await;
+        let _watching = watch(&player);
+        player.play_album("album-1", 0).await.unwrap();
+
+        player
+            .database
+            .ban(BanKind::Track, 2, "Second")
+            .await
+            .unwrap();
+        player.apply_bans().await.unwrap();
+        let queue: Vec<u32> = player
+            .tracklist_rx
+            .borrow()
+            .queue()
+            .iter()
+            .map(|item| item.track.id)
+            .collect();
+        assert_eq!(queue, [1]);
+
+        player
+            .database
+            .ban(BanKind::Track, 1, "First")
+            .await
+            .unwrap();
+        player.apply_bans().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(*player.target_status.borrow(), Status::Paused);
+    }
+
+    #[sqlx::test]
+    async fn connect_queue_edits_only_reach_connect_queues(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            1.0,
+        )
+        .await;
+        let _watching = watch(&player);
+        let queue = |player: &Player| -> Vec<(u32, u64)> {
+            player
+                .tracklist_rx
+                .borrow()
+                .queue()
+                .iter()
+                .map(|item| (item.track.id, item.queue_id))
+                .collect()
+        };
+        let item = |track_id, queue_id| NewQueueItem { track_id, queue_id };
+
+        player
+            .new_track_queue(vec![item(1, 10), item(404, 11), item(2, 12)], false)
+            .await
+            .unwrap();
+        assert_eq!(queue(&player), [(1, 10), (2, 12)]);
+
+        player
+            .insert_queue_items(vec![item(1, 13)], None)
+            .await
+            .unwrap();
+        player.remove_queue_items(vec![10]).await.unwrap();
+        assert_eq!(queue(&player), [(2, 12), (1, 13)]);
+
+        player.play_album("album-1", 0).await.unwrap();
+        let local = queue(&player);
+        player.remove_queue_items(vec![0, 1]).await.unwrap();
+        player
+            .insert_queue_items(vec![item(2, 14)], None)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(queue(&player), local);
+    }
+
+    #[sqlx::test]
+    async fn volume_is_capped_during_quiet_hours(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let all_day = QuietHours::new("00:00-00:00", 30).unwrap();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            Some(all_day),
+            1.0,
+        )
+        .await;
+
+        assert_eq!(*player.volume.borrow(), all_day.max_volume());
+        assert_eq!(*player.quiet_hours().borrow(), Some(all_day));
+        assert_eq!(player.max_volume(), all_day.max_volume());
+
+        player.set_volume(0.8).await.unwrap();
+        assert_eq!(*player.volume.borrow(), all_day.max_volume());
+
+        player.set_quiet_hours(None).await.unwrap();
+        assert_eq!(*player.quiet_hours().borrow(), None);
+        assert_eq!(player.max_volume(), 1.0);
+        player.set_volume(0.8).await.unwrap();
+        assert_eq!(*player.volume.borrow(), 0.8);
+
+        player.set_quiet_hours(Some(all_day)).await.unwrap();
+        assert_eq!(*player.volume.borrow(), all_day.max_volume());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[sqlx::test]
+    async fn volume_never_exceeds_the_limit(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = fixture_dir();
+        let night = QuietHours::new("00:00-00:00", 30).unwrap();
+        let mut player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Null,
+            None,
+            0.7,
+        )
+        .await;
+
+        assert_eq!(player.max_volume(), 0.7);
+        assert_eq!(*player.volume.borrow(), 0.7);
+
+        player.set_volume(1.0).await.unwrap();
+        assert_eq!(*player.volume.borrow(), 0.7);
+        player.set_volume(0.5).await.unwrap();
+        assert_eq!(*player.volume.borrow(), 0.5);
+
+        player.set_quiet_hours(Some(night)).await.unwrap();
+        assert_eq!(player.max_volume(), night.max_volume());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// When the first track ended, when the second started and its format
+    fn track_change(events: &[TransitionEvent]) -> (u64, u64, StreamFormat) {
+        let ended = events.iter().find_map(|event| match event {
+            TransitionEvent::TrackEnded {
+                track_id: 1,
+                timestamp_us,
+            } => Some(*timestamp_us),
+            _ => None,
+        });
+        let started = events.iter().find_map(|event| match event {
+            TransitionEvent::TrackStarted {
+                track_id: 2,
+                timestamp_us,
+                format,
+            } => Some((*timestamp_us, *format)),
+            _ => None,
+        });
+
+        let (Some(ended), Some((started, format))) = (ended, started) else {
+            panic!("missing transitions in {events:?}");
+        };
+        (ended, started, format)
+    }
+
+    #[sqlx::test]
+    async fn same_sample_rate_tracks_play_gapless(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = flac_fixture_dir([44_100, 44_100]);
+        let output_path = dir.join("output.raw");
+        let player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Pipe {
+                path: output_path.clone(),
+            },
+            None,
+            1.0,
+        )
+        .await;
+
+        let (events, gaps) = play_album_transitions(player).await;
+        let (ended, started, _) = track_change(&events);
+
+        // The output thread may still be writing the end of the second track
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let output: Vec<f32> = std::fs::read(&output_path)
+            .unwrap()
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(gaps, 0);
+        assert_eq!(ended, started);
+
+        let first = output.iter().position(|sample| *sample != 0.0).unwrap();
+        let last = output.iter().rposition(|sample| *sample != 0.0).unwrap();
+        let audio = &output[first..=last];
+        let silent = audio.iter().filter(|sample| **sample == 0.0).count();
+        assert_eq!(silent, 0, "{silent} silent samples between the tracks");
+        let track: Vec<f32> = audible_samples(44_100, 2)
+            .into_iter()
+            .map(|sample| sample as f32 / 32_768.0)
+            .collect();
+        let transition = [&track[track.len() - 1000..], &track[..1000]].concat();
+        assert!(
+            audio
+                .windows(transition.len())
+                .any(|window| window == transition.as_slice()),
+            "the end of the first track is not followed by the start of the second"
+        );
+    }
+
+    #[sqlx::test]
+    async fn sample_rate_change_has_a_bounded_gap(pool: sqlx::Pool<sqlx::Sqlite>) {
+        let dir = flac_fixture_dir([44_100, 48_000]);
+        let player = fixture_player(
+            &dir,
+            pool,
+            Arc::new(NotificationBroadcast::new()),
+            AudioOutput::Pipe {
+                path: dir.join("output.raw"),
+            },
+            None,
+            1.0,
+        )
+        .await;
+
+        let (events, gaps) = play_album_transitions(player).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (ended, started, format) = track_change(&events);
+
+        assert_eq!(gaps, 1);
+        assert_eq!(format.sample_rate, 48_000);
+        let gap = Duration::from_micros(started.saturating_sub(ended));
+        assert!(gap < Duration::from_secs(1), "gap of {gap:?}");
+    }
+}
@@ -0,0 +1,444 @@
+use std::path::Path;
+
+use crate::{AppResult, client::Client, error::Error, models::Track};
+
+/// Candidates offered for review when no single search result matches an entry
+const MAX_CANDIDATES: usize = 5;
+/// Title and artist each score 2 when equal and 1 when one contains the other. Results
+/// below this, like another song by the same artist, are not candidates
+const MIN_SCORE: u8 = 3;
+
+/// Track to find on Qobuz, as listed in the imported file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportEntry {
+    pub title: String,
+    pub artist: Option<String>,
+}
+
+impl std::fmt::Display for ImportEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.artist {
+            Some(artist) => write!(f, "{artist} - {}", self.title),
+            None => write!(f, "{}", self.title),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportMatch {
+    /// A single result with the same title and artist
+    Found(Box<Track>),
+    /// Results to pick from, best first
+    Ambiguous(Vec<Track>),
+    NotFound,
+}
+
+/// A playlist read from an M3U, CSV or Spotify export file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportFile {
+    /// Name of the playlist in the file, if it has one
+    pub name: Option<String>,
+    pub entries: Vec<ImportEntry>,
+}
+
+/// Reads the file, picking the format from its extension
+pub fn read(path: &Path) -> AppResult<ImportFile> {
+    let contents = std::fs::read_to_string(path).map_err(|err| Error::PlaylistImport {
+        message: format!("Unable to read {}: {err}", path.display()),
+    })?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let file = match extension.as_str() {
+        "m3u" | "m3u8" => parse_m3u(&contents),
+        "csv" => parse_csv(&contents),
+        "json" => parse_spotify(&contents)?,
+        _ => {
+            return Err(Error::PlaylistImport {
+                message: format!(
+                    "Unknown format of {}, expected .m3u, .csv or .json",
+                    path.display()
+                ),
+            });
+        }
+    };
+
+    if file.entries.is_empty() {
+        return Err(Error::PlaylistImport {
+            message: format!("No tracks found in {}", path.display()),
+        });
+    }
+
+    Ok(file)
+}
+
+/// Uses the `#EXTINF` titles, or the file names of the entries, as "Artist - Title"
+pub fn parse_m3u(contents: &str) -> ImportFile {
+    let mut name = None;
+    let mut entries = vec![];
+    let mut pending_title = None;
+
+    for line in contents.lines().map(str::trim) {
+        if let Some(playlist) = line.strip_prefix("#PLAYLIST:") {
+            name = Some(playlist.trim().to_string());
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            pending_title = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_string());
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else {
+            let title = pending_title.take().unwrap_or_else(|| {
+                let file_name = line.rsplit(['/', '\\']).next().unwrap_or(line);
+                let stem = file_name
+                    .rsplit_once('.')
+                    .map_or(file_name, |(stem, _)| stem);
+                stem.to_string()
+            });
+            entries.push(artist_and_title(&title));
+        }
+    }
+
+    ImportFile { name, entries }
+}
+
+/// A header naming the title and artist columns, as exported by most tools, or title and
+/// artist as the first two columns
+pub fn parse_csv(contents: &str) -> ImportFile {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let Some(first) = lines.next() else {
+        return ImportFile {
+            name: None,
+            entries: vec![],
+        };
+    };
+
+    let header: Vec<String> = csv_fields(first)
+        .iter()
+        .map(|field| field.to_lowercase())
+        .collect();
+    let find_column = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| header.iter().position(|column| column == name))
+    };
+    let title_column = find_column(&["title", "track name", "track", "name", "song"]);
+    let artist_column = find_column(&["artist", "artist name(s)", "artist name", "artists"]);
+
+    let (title_column, artist_column, rows) = match title_column {
+        Some(title_column) => (title_column, artist_column, lines.collect::<Vec<_>>()),
+        None => (0, Some(1), std::iter::once(first).chain(lines).collect()),
+    };
+
+    let entries = rows
+        .into_iter()
+        .filter_map(|row| {
+            let fields = csv_fields(row);
+            let title = fields.get(title_column)?.trim().to_string();
+            if title.is_empty() {
+                return None;
+            }
+            let artist = artist_column
+                .and_then(|column| fields.get(column))
+                .map(|artist| first_artist(artist))
+                .filter(|artist| !artist.is_empty());
+            Some(ImportEntry { title, artist })
+        })
+        .collect();
+
+    ImportFile {
+        name: None,
+        entries,
+    }
+}
+
+/// The playlists export (`Playlist1.json`, the first playlist is used) or the library export
+/// (`YourLibrary.json`) of a Spotify account data download
+pub fn parse_spotify(contents: &str) -> AppResult<ImportFile> {
+    #[derive(serde::Deserialize)]
+    struct Export {
+        #[serde(default)]
+        playlists: Vec<ExportPlaylist>,
+        #[serde(default)]
+        tracks: Vec<LibraryTrack>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct ExportPlaylist {
+        name: String,
+        items: Vec<PlaylistItem>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct PlaylistItem {
+        track: Option<PlaylistTrack>,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PlaylistTrack {
+        track_name: String,
+        artist_name: String,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct LibraryTrack {
+        track: String,
+        artist: String,
+    }
+
+    let export: Export = serde_json::from_str(contents).map_err(|err| Error::PlaylistImport {
+        message: format!("Not a Spotify export: {err}"),
+    })?;
+
+    if let Some(playlist) = export.playlists.into_iter().next() {
+        return Ok(ImportFile {
+            name: Some(playlist.name),
+            entries: playlist
+                .items
+                .into_iter()
+                .filter_map(|item| item.track)
+                .map(|track| ImportEntry {
+                    title: track.track_name,
+                    artist: Some(track.artist_name),
+                })
+                .collect(),
+        });
+    }
+
+    Ok(ImportFile {
+        name: None,
+        entries: export
+            .tracks
+            .into_iter()
+            .map(|track| ImportEntry {
+                title: track.track,
+                artist: Some(track.artist),
+            })
+            .collect(),
+    })
+}
+
+/// Searches Qobuz for the entry, by artist and title first and by title alone if that
+/// finds nothing
+pub async fn find_match(client: &Client, entry: &ImportEntry) -> AppResult<ImportMatch> {
+    let mut tracks = match &entry.artist {
+        Some(artist) => {
+            client
+                .search(format!("{artist} {}", entry.title))
+                .await?
+                .tracks
+        }
+        None => vec![],
+    };
+    if tracks.is_empty() {
+        tracks = client.search(entry.title.clone()).await?.tracks;
+    }
+
+    Ok(pick_match(entry, tracks))
+}
+
+fn pick_match(entry: &ImportEntry, tracks: Vec<Track>) -> ImportMatch {
+    let title = normalize(&entry.title);
+    let artist = entry.artist.as_deref().map(normalize);
+
+    let mut scored: Vec<(u8, Track)> = tracks
+        .into_iter()
+        .filter(|track| track.available)
+        .map(|track| {
+            let title_score = match normalize(&track.title) {
+                found if found == title => 2,
+                found if found.contains(&title) || title.contains(&found) => 1,
+                _ => 0,
+            };
+            let artist_score = match (&artist, track.artist_name.as_deref().map(normalize)) {
+                (Some(artist), Some(found)) if found == *artist => 2,
+                (Some(artist), Some(found))
+                    if found.contains(artist) || artist.contains(&found) =>
+                {
+                    1
+                }
+                (None, _) => 1,
+                _ => 0,
+            };
+            (title_score + artist_score, track)
+        })
+        .filter(|(score, _)| *score >= MIN_SCORE)
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    let exact = scored.iter().filter(|(score, _)| *score == 4).count();
+    match scored.first() {
+        None => ImportMatch::NotFound,
+        Some((4, track)) if exact == 1 => ImportMatch::Found(Box::new(track.clone())),
+        Some(_) => ImportMatch::Ambiguous(
+            scored
+                .into_iter()
+                .take(MAX_CANDIDATES)
+                .map(|(_, track)| track)
+                .collect(),
+        ),
+    }
+}
+
+/// "Artist - Title", or only the title when there is no separator
+fn artist_and_title(text: &str) -> ImportEntry {
+    match text.split_once(" - ") {
+        Some((artist, title)) => ImportEntry {
+            title: title.trim().to_string(),
+            artist: Some(artist.trim().to_string()),
+        },
+        None => ImportEntry {
+            title: text.trim().to_string(),
+            artist: None,
+        },
+    }
+}
+
+/// Exports list several artists separated by commas or semicolons. Qobuz search works best
+/// with the main one
+fn first_artist(artists: &str) -> String {
+    artists
+        .split([',', ';'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Lowercase letters and digits, so punctuation and spacing differences still match
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|char| char.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Fields of a CSV line, with quoted fields containing commas or doubled quotes
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            char => field.push(char),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_m3u_and_csv() {
+        let m3u = parse_m3u(
+            "#EXTM3U\n#PLAYLIST:Road trip\n#EXTINF:215,Daft Punk - One More Time\n\
+             music/one_more_time.flac\nmusic/Air - La femme d'argent.mp3\n",
+        );
+        assert_eq!(m3u.name.as_deref(), Some("Road trip"));
+        assert_eq!(
+            m3u.entries,
+            vec![
+                ImportEntry {
+                    title: "One More Time".into(),
+                    artist: Some("Daft Punk".into()),
+                },
+                ImportEntry {
+                    title: "La femme d'argent".into(),
+                    artist: Some("Air".into()),
+                },
+            ]
+        );
+
+        let csv = parse_csv(
+            "\"Track URI\",\"Track Name\",\"Artist Name(s)\"\n\
+             spotify:track:1,\"Hello, Goodbye\",\"The Beatles\"\n\
+             spotify:track:2,Under Pressure,\"Queen, David Bowie\"\n",
+        );
+        assert_eq!(
+            csv.entries,
+            vec![
+                ImportEntry {
+                    title: "Hello, Goodbye".into(),
+                    artist: Some("The Beatles".into()),
+                },
+                ImportEntry {
+                    title: "Under Pressure".into(),
+                    artist: Some("Queen".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_single_exact_match() {
+        let track = |id, title: &str, artist: &str| Track {
+            id,
+            title: title.into(),
+            artist_name: Some(artist.into()),
+            available: true,
+            ..Default::default()
+        };
+        let entry = ImportEntry {
+            title: "Under Pressure".into(),
+            artist: Some("Queen".into()),
+        };
+
+        let found = pick_match(
+            &entry,
+            vec![
+                track(1, "Under Pressure (Live)", "Queen"),
+                track(2, "Under pressure", "Queen"),
+            ],
+        );
+        assert_eq!(
+            found,
+            ImportMatch::Found(Box::new(track(2, "Under pressure", "Queen")))
+        );
+
+        let ambiguous = pick_match(
+            &entry,
+            vec![
+                track(1, "Under Pressure", "Queen"),
+                track(2, "Under Pressure", "Queen"),
+            ],
+        );
+        assert!(matches!(ambiguous, ImportMatch::Ambiguous(tracks) if tracks.len() == 2));
+
+        let close = pick_match(
+            &entry,
+            vec![
+                track(1, "Bohemian Rhapsody", "Queen"),
+                track(2, "Under Pressure (Live)", "Queen"),
+            ],
+        );
+        assert_eq!(
+            close,
+            ImportMatch::Ambiguous(vec![track(2, "Under Pressure (Live)", "Queen")])
+        );
+
+        let unrelated = pick_match(
+            &entry,
+            vec![
+                track(1, "Bohemian Rhapsody", "Queen"),
+                track(2, "Under Pressure", "Someone Else"),
+            ],
+        );
+        assert_eq!(unrelated, ImportMatch::NotFound);
+    }
+}
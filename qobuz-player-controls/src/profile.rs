@@ -0,0 +1,77 @@
+use crate::{
+    AppResult,
+    client::Client,
+    controls::Controls,
+    database::{Credentials, Database},
+    error::Error,
+};
+
+/// Signs in with the credentials of another profile and applies its settings and favorites.
+/// Profiles without credentials are rejected, as logging in requires a browser
+pub async fn switch_profile(
+    database: &Database,
+    client: &Client,
+    controls: &Controls,
+    name: &str,
+) -> AppResult<()> {
+    let previous = database.profile();
+    if previous == name {
+        return Ok(());
+    }
+
+    let logged_in = database
+        .get_profiles()
+        .await?
+        .iter()
+        .any(|profile| profile.name == name && profile.logged_in);
+    if !logged_in {
+        return Err(not_logged_in(name));
+    }
+
+    database.set_profile(name).await?;
+    let result = match database.get_credentials().await {
+        Ok(Some(credentials)) => client.switch_account(credentials).await,
+        Ok(None) => Err(not_logged_in(name)),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        database.set_profile(&previous).await?;
+        return Err(err);
+    }
+
+    let configuration = database.get_configuration().await?;
+    client
+        .set_max_audio_quality(configuration.max_audio_quality.into())
+        .await;
+    client.set_data_saver(configuration.data_saver).await;
+    client.set_play_previews(configuration.play_previews);
+    if let Some(favorites) = database.get_favorites().await {
+        client.set_cached_favorites(favorites).await;
+    }
+
+    controls.set_balance(configuration.balance as f32);
+    controls.set_mono_downmix(configuration.mono_downmix);
+    controls.set_trim_silence(configuration.trim_silence);
+
+    Ok(())
+}
+
+/// Replaces the login of the current profile, once Qobuz rejected the stored one. The new
+/// credentials are only stored when Qobuz accepts them
+pub async fn replace_credentials(
+    database: &Database,
+    client: &Client,
+    credentials: Credentials,
+) -> AppResult<()> {
+    client.switch_account(credentials.clone()).await?;
+    client.refresh_favorites().await?;
+    database.set_credentials(credentials).await
+}
+
+fn not_logged_in(name: &str) -> Error {
+    Error::Login {
+        message: format!(
+            "Profile {name} is not logged in. Run qobuz-player --profile {name} login"
+        ),
+    }
+}
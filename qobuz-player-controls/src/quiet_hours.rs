@@ -0,0 +1,148 @@
+use std::{fmt::Display, time::Duration};
+
+use chrono::{Local, Timelike};
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Daily period, like 22:00-07:00, in which the volume is capped and release notifications
+/// wait until it ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct QuietHours {
+    /// Minutes after midnight
+    pub start: u16,
+    /// Minutes after midnight, may be before `start` to end the next day
+    pub end: u16,
+    /// Highest volume in percent
+    pub max_volume: u8,
+}
+
+impl QuietHours {
+    /// `hours` is a period like 22:00-07:00. The same start and end cover the whole day
+    pub fn new(hours: &str, max_volume: u8) -> Result<Self, String> {
+        let invalid = || format!("{hours} is not a period like 22:00-07:00");
+        let (start, end) = hours.trim().split_once('-').ok_or_else(invalid)?;
+        let start = parse_time(start).ok_or_else(invalid)?;
+        let end = parse_time(end).ok_or_else(invalid)?;
+
+        Ok(Self {
+            start,
+            end,
+            max_volume: max_volume.min(100),
+        })
+    }
+
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        match self.start < self.end {
+            true => (self.start..self.end).contains(&minute_of_day),
+            false => minute_of_day >= self.start || minute_of_day < self.end,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.contains(minute_of_day())
+    }
+
+    /// Highest volume from 0.0 to 1.0
+    pub fn max_volume(&self) -> f32 {
+        f32::from(self.max_volume) / 100.0
+    }
+
+    /// Time until the quiet hours end, rounded up to the minute
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs(u64::from(self.minutes_until_end(minute_of_day())) * 60)
+    }
+
+    fn minutes_until_end(&self, minute_of_day: u16) -> u16 {
+        ((self.end + MINUTES_PER_DAY - minute_of_day) % MINUTES_PER_DAY).max(1)
+    }
+
+    /// Start of the quiet hours, like 22:00
+    pub fn start_time(&self) -> String {
+        format_time(self.start)
+    }
+
+    /// End of the quiet hours, like 07:00
+    pub fn end_time(&self) -> String {
+        format_time(self.end)
+    }
+}
+
+impl Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}, volume up to {}%",
+            self.start_time(),
+            self.end_time(),
+            self.max_volume
+        )
+    }
+}
+
+fn minute_of_day() -> u16 {
+    let now = Local::now();
+    (now.hour() * 60 + now.minute()) as u16
+}
+
+/// Minutes after midnight of a time like 7:30 or 07:30
+fn parse_time(text: &str) -> Option<u16> {
+    let (hour, minute) = text.trim().split_once(':')?;
+    let hour: u16 = hour.parse().ok().filter(|hour| *hour < 24)?;
+    let minute: u16 = minute.parse().ok().filter(|minute| *minute < 60)?;
+    Some(hour * 60 + minute)
+}
+
+fn format_time(minutes: u16) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuietHours;
+
+    #[test]
+    fn quiet_hours_may_end_the_next_day() {
+        let night = QuietHours::new("22:00-7:30", 30).unwrap();
+        assert_eq!((night.start, night.end), (22 * 60, 7 * 60 + 30));
+        assert!(night.contains(23 * 60));
+        assert!(night.contains(0));
+        assert!(night.contains(7 * 60 + 29));
+        assert!(!night.contains(7 * 60 + 30));
+        assert!(!night.contains(12 * 60));
+        assert_eq!(night.to_string(), "22:00-07:30, volume up to 30%");
+
+        let nap = QuietHours::new("13:00-15:00", 10).unwrap();
+        assert!(nap.contains(14 * 60));
+        assert!(!nap.contains(15 * 60));
+        assert!(!nap.contains(23 * 60));
+
+        let day = QuietHours::new("07:00-07:00", 30).unwrap();
+        assert!(day.contains(7 * 60));
+        assert!(day.contains(6 * 60 + 59));
+    }
+
+    #[test]
+    fn volume_is_capped_in_percent() {
+        let night = QuietHours::new("22:00-07:00", 30).unwrap();
+        assert_eq!(night.max_volume(), 0.3);
+
+        let loud = QuietHours::new("22:00-07:00", 150).unwrap();
+        assert_eq!(loud.max_volume, 100);
+        assert_eq!(loud.max_volume(), 1.0);
+    }
+
+    #[test]
+    fn minutes_until_the_quiet_hours_end() {
+        let night = QuietHours::new("22:00-07:30", 30).unwrap();
+        assert_eq!(night.minutes_until_end(23 * 60), 8 * 60 + 30);
+        assert_eq!(night.minutes_until_end(7 * 60), 30);
+        assert_eq!(night.minutes_until_end(7 * 60 + 30), 1);
+    }
+
+    #[test]
+    fn invalid_quiet_hours() {
+        for hours in ["22:00", "24:00-07:00", "22:60-07:00", "22-07"] {
+            assert!(QuietHours::new(hours, 30).is_err(), "{hours}");
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::{sync::Arc, time::Duration};
+
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+use serde::Serialize;
+
+use crate::{
+    AppResult,
+    client::Client,
+    database::{Database, FollowedArtist},
+    models::{AlbumSimple, LinkTarget},
+    quiet_hours::QuietHours,
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Port of SMTP servers expecting TLS from the start instead of STARTTLS
+const SMTPS_PORT: u16 = 465;
+
+/// Where new releases of followed artists are announced
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseNotifiers {
+    /// Each url gets a JSON POST per release
+    pub webhook_urls: Vec<String>,
+    /// ntfy topic urls, like https://ntfy.sh/my-releases
+    pub ntfy_urls: Vec<String>,
+    pub email: Option<EmailSettings>,
+}
+
+impl ReleaseNotifiers {
+    pub fn is_empty(&self) -> bool {
+        self.webhook_urls.is_empty() && self.ntfy_urls.is_empty() && self.email.is_none()
+    }
+}
+
+/// SMTP server and addresses of release emails
+#[derive(Debug, Clone)]
+pub struct EmailSettings {
+    pub server: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ReleasePayload<'a> {
+    event: &'static str,
+    artist: &'a FollowedArtist,
+    album: &'a AlbumSimple,
+    url: &'a str,
+}
+
+/// Follows an artist. Its current releases count as seen, so only later releases are
+/// announced
+pub async fn follow(
+    database: &Database,
+    client: &Client,
+    artist_id: u32,
+    name: &str,
+) -> AppResult<()> {
+    let releases = client.latest_releases(artist_id).await?;
+    database.follow_artist(artist_id, name).await?;
+    database
+        .record_artist_releases(artist_id, &release_ids(&releases))
+        .await?;
+    Ok(())
+}
+
+/// Checks for new releases right away and then every `interval`. No checks are made during
+/// quiet hours
+pub async fn run(
+    database: Arc<Database>,
+    client: Arc<Client>,
+    notifiers: ReleaseNotifiers,
+    interval: Duration,
+) {
+    loop {
+        let quiet_hours = database
+            .get_quiet_hours()
+            .await
+            .ok()
+            .flatten()
+            .filter(QuietHours::is_active);
+
+        let delay = match quiet_hours {
+            // Releases out during quiet hours are announced once they end
+            Some(quiet_hours) => quiet_hours.remaining(),
+            None => {
+                if let Err(err) = check(&database, &client, &notifiers).await {
+                    tracing::warn!("Unable to check for new releases: {err}");
+                }
+                interval
+            }
+        };
+
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Looks for releases of the followed artists not seen before and announces them. An
+/// artist that can not be fetched is skipped until the next check
+pub async fn check(
+    database: &Database,
+    client: &Client,
+    notifiers: &ReleaseNotifiers,
+) -> AppResult<()> {
+    let http = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+
+    for artist in database.get_followed_artists().await? {
+        let releases = match client.latest_releases(artist.id).await {
+            Ok(releases) => releases,
+            Err(err) => {
+                tracing::warn!("Unable to fetch the releases of {}: {err}", artist.name);
+                continue;
+            }
+        };
+
+        let new_releases = database
+            .record_artist_releases(artist.id, &release_ids(&releases))
+            .await?;
+        for album_id in new_releases {
+            if let Some(album) = releases.iter().find(|album| album.id == album_id) {
+                notify(&http, notifiers, &artist, album).await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A release can be listed under more than one release type
+fn release_ids(releases: &[AlbumSimple]) -> Vec<String> {
+    let mut album_ids: Vec<String> = releases.iter().map(|album| album.id.clone()).collect();
+    album_ids.sort();
+    album_ids.dedup();
+    album_ids
+}
+
+async fn notify(
+    http: &reqwest::Client,
+    notifiers: &ReleaseNotifiers,
+    artist: &FollowedArtist,
+    album: &AlbumSimple,
+) {
+    let url = LinkTarget::Album(album.id.clone()).url();
+    let message = format!("{} released {}", artist.name, album.title);
+    tracing::info!("{message}");
+
+    let payload = ReleasePayload {
+        event: "new_release",
+        artist,
+        album,
+        url: &url,
+    };
+    for webhook_url in &notifiers.webhook_urls {
+        let result = http
+            .post(webhook_url)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        if let Err(err) = result {
+            tracing::warn!("Release webhook to {webhook_url} failed: {err}");
+        }
+    }
+
+    for ntfy_url in &notifiers.ntfy_urls {
+        let result = ntfy_request(http, ntfy_url, &message, &url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        if let Err(err) = result {
+            tracing::warn!("Release notification to {ntfy_url} failed: {err}");
+        }
+    }
+
+    if let Some(email) = &notifiers.email
+        && let Err(err) = send_email(email, &message, &url).await
+    {
+        tracing::warn!("Release email to {} failed: {err}", email.to);
+    }
+}
+
+fn ntfy_request(
+    http: &reqwest::Client,
+    ntfy_url: &str,
+    message: &str,
+    url: &str,
+) -> reqwest::RequestBuilder {
+    http.post(ntfy_url)
+        .header("Title", "New release")
+        .header("Click", url)
+        .header("Tags", "cd")
+        .body(message.to_string())
+}
+
+async fn send_email(settings: &EmailSettings, subject: &str, url: &str) -> Result<(), String> {
+    let from = settings
+        .from
+        .parse::<Mailbox>()
+        .map_err(|err| err.to_string())?;
+    let to = settings
+        .to
+        .parse::<Mailbox>()
+        .map_err(|err| err.to_string())?;
+    let message = Message::builder()
+        .from(from)
+        .to(to)
+        .subject(subject)
+        .body(format!("{subject}\n\n{url}\n"))
+        .map_err(|err| err.to_string())?;
+
+    let transport = match settings.port {
+        SMTPS_PORT => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.server),
+        _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.server),
+    }
+    .map_err(|err| err.to_string())?
+    .port(settings.port);
+
+    let transport = match (&settings.username, &settings.password) {
+        (Some(username), Some(password)) => {
+            transport.credentials(Credentials::new(username.clone(), password.clone()))
+        }
+        _ => transport,
+    };
+
+    transport
+        .build()
+        .send(message)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn album(id: &str) -> AlbumSimple {
+        AlbumSimple {
+            id: id.to_string(),
+            title: "Debut".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn releases_listed_under_several_types_count_once() {
+        let releases = [album("b"), album("a"), album("b")];
+        assert_eq!(release_ids(&releases), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn webhook_payload() {
+        let artist = FollowedArtist {
+            id: 1,
+            name: "Artist".to_string(),
+        };
+        let album = album("a");
+        let payload = ReleasePayload {
+            event: "new_release",
+            artist: &artist,
+            album: &album,
+            url: "https://open.qobuz.com/album/a",
+        };
+
+        let value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(value["event"], "new_release");
+        assert_eq!(value["artist"], json!({"id": 1, "name": "Artist"}));
+        assert_eq!(value["album"]["id"], "a");
+        assert_eq!(value["album"]["title"], "Debut");
+        assert_eq!(value["url"], "https://open.qobuz.com/album/a");
+    }
+
+    #[test]
+    fn ntfy_headers() {
+        let request = ntfy_request(
+            &reqwest::Client::new(),
+            "https://ntfy.sh/releases",
+            "Artist released Debut",
+            "https://open.qobuz.com/album/a",
+        )
+        .build()
+        .unwrap();
+
+        let header = |name| request.headers()[name].to_str().unwrap();
+        assert_eq!(request.url().as_str(), "https://ntfy.sh/releases");
+        assert_eq!(header("Title"), "New release");
+        assert_eq!(header("Click"), "https://open.qobuz.com/album/a");
+        assert_eq!(header("Tags"), "cd");
+        assert_eq!(
+            request.body().and_then(|body| body.as_bytes()),
+            Some("Artist released Debut".as_bytes())
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+/// Samples quieter than this, about -60 dBFS, count as silence
+const SILENCE_THRESHOLD: Sample = 0.001;
+
+/// Longest silence held back to find out whether it lasts until the end of the track. Longer
+/// silences in the middle of a track are played
+const MAX_HELD_SILENCE: Duration = Duration::from_secs(30);
+
+/// Skips the silence a track starts with and drops the silence it ends with, so tracks padded
+/// with silence flow into each other
+pub struct SilenceTrim<I> {
+    input: I,
+    enabled: bool,
+    /// Still at the start of the track, where silent frames are skipped
+    leading: bool,
+    /// Silent frames read after the audio, dropped if nothing but silence follows
+    held: VecDeque<Sample>,
+    output: VecDeque<Sample>,
+    frame: Vec<Sample>,
+    /// Frames skipped or dropped since the start or the last seek
+    trimmed_frames: Arc<AtomicU64>,
+}
+
+impl<I: Source> SilenceTrim<I> {
+    pub fn new(input: I, enabled: bool, trimmed_frames: Arc<AtomicU64>) -> Self {
+        Self {
+            input,
+            enabled,
+            leading: enabled,
+            held: VecDeque::new(),
+            output: VecDeque::new(),
+            frame: Vec::with_capacity(2),
+            trimmed_frames,
+        }
+    }
+
+    fn max_held_samples(&self) -> usize {
+        let samples_per_second =
+            self.input.sample_rate().get() as usize * self.input.channels().get() as usize;
+        samples_per_second * MAX_HELD_SILENCE.as_secs() as usize
+    }
+
+    fn trim_frame(&self) {
+        self.trimmed_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Reads until there is audio to play, or the track ends
+    fn fill_output(&mut self) {
+        let channels = self.input.channels().get() as usize;
+
+        while self.output.is_empty() {
+            self.frame.clear();
+            self.frame.extend(self.input.by_ref().take(channels));
+
+            if self.frame.is_empty() {
+                let held_frames = self.held.len() / channels.max(1);
+                self.trimmed_frames
+                    .fetch_add(held_frames as u64, Ordering::Relaxed);
+                self.held.clear();
+                return;
+            }
+
+            let silent = self
+                .frame
+                .iter()
+                .all(|sample| sample.abs() <= SILENCE_THRESHOLD);
+
+            match (silent, self.leading) {
+                (true, true) => self.trim_frame(),
+                (true, false) => {
+                    self.held.extend(&self.frame);
+                    if self.held.len() > self.max_held_samples() {
+                        self.output.append(&mut self.held);
+                    }
+                }
+                (false, _) => {
+                    self.leading = false;
+                    self.output.append(&mut self.held);
+                    self.output.extend(&self.frame);
+                }
+            }
+        }
+    }
+}
+
+impl<I: Source> Iterator for SilenceTrim<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.enabled {
+            return self.input.next();
+        }
+
+        if self.output.is_empty() {
+            self.fill_output();
+        }
+
+        self.output.pop_front()
+    }
+}
+
+impl<I: Source> Source for SilenceTrim<I> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.input
+            .current_span_len()
+            .map(|len| len + self.output.len())
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    /// Silence is only skipped at the start of the track, not where it is seeked to
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.leading = false;
+        self.held.clear();
+        self.output.clear();
+        self.trimmed_frames.store(0, Ordering::Relaxed);
+        self.input.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::SilenceTrim;
+
+    #[test]
+    fn trims_leading_and_trailing_silence_but_not_pauses() {
+        let samples = [
+            vec![0.0; 8],
+            vec![0.5, 0.5],
+            vec![0.0; 4],
+            vec![-0.5, 0.5],
+            vec![0.0005; 6],
+        ]
+        .concat();
+        let buffer = SamplesBuffer::new(
+            NonZero::new(2).unwrap(),
+            NonZero::new(44100).unwrap(),
+            samples.clone(),
+        );
+
+        let trimmed = Arc::new(AtomicU64::default());
+        let played: Vec<_> = SilenceTrim::new(buffer.clone(), true, trimmed.clone()).collect();
+        assert_eq!(
+            played,
+            [vec![0.5, 0.5], vec![0.0; 4], vec![-0.5, 0.5]].concat()
+        );
+        assert_eq!(trimmed.load(Ordering::Relaxed), 7);
+
+        let untouched: Vec<_> = SilenceTrim::new(buffer, false, Default::default()).collect();
+        assert_eq!(untouched, samples);
+    }
+}
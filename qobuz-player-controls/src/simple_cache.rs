@@ -1,6 +1,9 @@
+use parking_lot::RwLock;
 use time::Duration;
-use tokio::{sync::RwLock, time::Instant};
+use tokio::time::Instant;
 
+/// Holds a single value for a while. The locks are only held to copy the value in or out,
+/// so they are never held across an await
 #[derive(Debug)]
 pub struct SimpleCache<T> {
     value: RwLock<Option<T>>,
@@ -21,25 +24,38 @@ impl<T> SimpleCache<T> {
     where
         T: Clone,
     {
-        if self.valid().await {
-            self.value.read().await.clone()
+        if self.valid() {
+            self.value.read().clone()
         } else {
             None
         }
     }
 
+    /// Returns the value even if it has expired
+    pub async fn get_stale(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.value.read().clone()
+    }
+
+    /// Reads the value, even if it has expired, without cloning it
+    pub fn inspect<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.value.read().as_ref().map(f)
+    }
+
     pub async fn set(&self, value: T) {
-        *self.value.write().await = Some(value);
-        *self.created.write().await = Some(Instant::now());
+        *self.value.write() = Some(value);
+        *self.created.write() = Some(Instant::now());
     }
 
     pub async fn clear(&self) {
-        *self.value.write().await = None;
-        *self.created.write().await = None;
+        *self.value.write() = None;
+        *self.created.write() = None;
     }
 
-    async fn valid(&self) -> bool {
-        match *self.created.read().await {
+    fn valid(&self) -> bool {
+        match *self.created.read() {
             Some(created) => created.elapsed() < self.ttl,
             None => false,
         }
@@ -65,4 +81,15 @@ mod tests {
 
         assert_eq!(cache.get().await, None);
     }
+
+    #[tokio::test]
+    async fn inspects_expired_values() {
+        let cache = SimpleCache::new(Duration::ZERO);
+        assert_eq!(cache.inspect(|value: &Vec<u32>| value.len()), None);
+
+        cache.set(vec![1, 2]).await;
+
+        assert_eq!(cache.get().await, None);
+        assert_eq!(cache.inspect(|value| value.contains(&2)), Some(true));
+    }
 }
@@ -1,54 +1,382 @@
-use std::fs;
 use std::io::{Read, Seek};
 use std::num::NonZero;
 use std::path::Path;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
 
 use parking_lot::Mutex;
+use qobuz_player_client::stream::cache_encryption::{CacheFile, CacheKey};
 use qobuz_player_client::stream::flac_source_stream::SeekableStreamReader;
 use rodio::cpal::traits::HostTrait;
 use rodio::queue::queue;
-use rodio::{Decoder, DeviceTrait, Player, Source};
+use rodio::source::{EmptyCallback, UniformSourceIterator};
+use rodio::{ChannelCount, Decoder, DeviceTrait, Player, SampleRate, Source};
+use tokio::sync::mpsc;
 use tokio::sync::watch::{self, Receiver, Sender};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
+use crate::audio_backend::{AudioBackend, AudioOutput, QueryTrackResult};
+use crate::channel_mix::{ChannelMix, ChannelMixSettings};
+use crate::diagnostics::{MonitoredReader, PlaybackStats};
+use crate::dsp::DspPipe;
 use crate::error::Error;
+use crate::hardware_mixer::HardwareMixer;
+use crate::notification::{Notification, NotificationBroadcast, TrackFormat};
+use crate::pcm_output::{PcmOutput, PcmSource};
+use crate::silence_trim::SilenceTrim;
 use crate::stderr_redirect::silence_stderr;
-use crate::{AppResult, VolumeReceiver};
+use crate::transitions::StreamFormat;
+use crate::zones::{ZoneBuffer, ZoneSettings, ZoneSource, ZoneTee};
+use crate::{AppResult, VolumeCurve, VolumeReceiver};
+
+const VOLUME_RAMP_STEP: Duration = Duration::from_millis(20);
 
 pub struct Sink {
-    sink: Option<Player>,
-    output_stream: Option<rodio::MixerDeviceSink>,
+    sink: Option<Arc<Player>>,
+    output_stream: Option<OutputStream>,
+    /// Writes the main output when it is not a cpal device
+    pcm_output: Option<PcmOutput>,
     sender: Option<Arc<rodio::queue::SourcesQueueInput>>,
     volume: VolumeReceiver,
-    track_finished: Sender<()>,
+    /// Time the last sample of the finished track was handed to the output
+    track_finished: Sender<SystemTime>,
     track_handle: Option<JoinHandle<()>>,
+    broadcast: Arc<NotificationBroadcast>,
+    stats: Arc<PlaybackStats>,
+    /// Format of the track queued after the playing one, sent when the playing one ends
+    next_format: Arc<Mutex<Option<TrackFormat>>>,
     duration_played: Arc<Mutex<Duration>>,
     preferred_device_id: Option<String>,
+    volume_curve: VolumeCurve,
+    volume_ramp: Option<Duration>,
+    skip_fade: Option<Duration>,
+    /// The playing track was faded out for a skip, so the next one fades in
+    fade_in_pending: AtomicBool,
+    /// Volume ramp running next to the player loop
+    ramp: Mutex<Option<JoinHandle<()>>>,
+    channel_mix: Arc<ChannelMixSettings>,
+    trim_silence: Arc<AtomicBool>,
+    /// Filters the decoded audio before it is mixed and played
+    dsp_command: Option<String>,
+    hardware_mixer: Option<HardwareMixer>,
+    /// The first zone is the preferred device, the others get a copy of its audio
+    zones: Vec<Arc<ZoneSettings>>,
+    zone_outputs: Vec<ZoneOutput>,
+    zone_channels: Option<ChannelCount>,
+    /// Decrypts tracks in the audio cache
+    cache_key: Option<CacheKey>,
+}
+
+/// Open stream of the main output
+enum OutputStream {
+    Device(rodio::MixerDeviceSink),
+    /// Written by the pcm output in this sample rate
+    Pcm(SampleRate),
+}
+
+impl OutputStream {
+    fn sample_rate(&self) -> SampleRate {
+        match self {
+            OutputStream::Device(mixer) => mixer.config().sample_rate(),
+            OutputStream::Pcm(sample_rate) => *sample_rate,
+        }
+    }
+}
+
+/// Open output device of a secondary zone
+struct ZoneOutput {
+    settings: Arc<ZoneSettings>,
+    buffer: Arc<ZoneBuffer>,
+    player: Player,
+    _stream: rodio::MixerDeviceSink,
 }
 
 impl Sink {
-    pub fn new(volume: VolumeReceiver, preferred_device_id: Option<String>) -> AppResult<Self> {
-        let (track_finished, _) = watch::channel(());
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        volume: VolumeReceiver,
+        output: AudioOutput,
+        preferred_device_id: Option<String>,
+        volume_curve: VolumeCurve,
+        volume_ramp: Option<Duration>,
+        skip_fade: Option<Duration>,
+        channel_mix: Arc<ChannelMixSettings>,
+        trim_silence: Arc<AtomicBool>,
+        dsp_command: Option<String>,
+        hardware_mixer: Option<HardwareMixer>,
+        zones: Vec<Arc<ZoneSettings>>,
+        broadcast: Arc<NotificationBroadcast>,
+        stats: Arc<PlaybackStats>,
+        cache_key: Option<CacheKey>,
+    ) -> AppResult<Self> {
+        let (track_finished, _) = watch::channel(SystemTime::UNIX_EPOCH);
+        if let Some(mixer) = &hardware_mixer {
+            mixer.set_volume(*volume.borrow())?;
+        }
         Ok(Self {
             sink: None,
             output_stream: None,
+            pcm_output: PcmOutput::open(&output, broadcast.clone(), stats.clone()),
             sender: None,
             volume,
             track_finished,
             track_handle: Default::default(),
+            broadcast,
+            stats,
+            next_format: Default::default(),
             duration_played: Default::default(),
             preferred_device_id,
+            volume_curve,
+            volume_ramp,
+            skip_fade,
+            fade_in_pending: Default::default(),
+            ramp: Default::default(),
+            channel_mix,
+            trim_silence,
+            dsp_command,
+            hardware_mixer,
+            zones,
+            zone_outputs: Vec::new(),
+            zone_channels: None,
+            cache_key,
         })
     }
 
-    pub fn track_finished(&self) -> Receiver<()> {
+    /// Ramps the volume on a task of its own, so commands are handled during the ramp, and
+    /// calls `then` when it is done. A new ramp replaces the running one
+    fn start_ramp(
+        &self,
+        player: &Arc<Player>,
+        from: f32,
+        to: f32,
+        ramp: Duration,
+        then: impl FnOnce(&Player) + Send + 'static,
+    ) {
+        self.stop_ramp();
+        let player = player.clone();
+        let task = tokio::spawn(async move {
+            ramp_volume(&player, from, to, ramp).await;
+            then(&player);
+        });
+        *self.ramp.lock() = Some(task);
+    }
+
+    /// Whether a ramp was still running
+    fn stop_ramp(&self) -> bool {
+        match self.ramp.lock().take() {
+            Some(ramp) => {
+                ramp.abort();
+                !ramp.is_finished()
+            }
+            None => false,
+        }
+    }
+
+    fn ramping(&self) -> bool {
+        self.ramp
+            .lock()
+            .as_ref()
+            .is_some_and(|ramp| !ramp.is_finished())
+    }
+
+    fn gain(&self) -> f32 {
+        let main_zone = self.zones.first().map(|zone| zone.gain()).unwrap_or(1.0);
+        if self.hardware_mixer.is_some() {
+            return main_zone;
+        }
+        self.volume_curve.gain(*self.volume.borrow()) * main_zone
+    }
+
+    fn zone_gain(&self, zone: &ZoneSettings) -> f32 {
+        self.volume_curve.gain(*self.volume.borrow()) * zone.gain()
+    }
+
+    fn set_volume(&self, player: &Player) {
+        player.set_volume(self.gain());
+    }
+
+    fn queue_decoder<R: Read + Seek + Send + Sync + 'static>(
+        &mut self,
+        track_id: u32,
+        source: Decoder<R>,
+        bit_depth: Option<u32>,
+    ) -> AppResult<QueryTrackResult> {
+        let sample_rate = source.sample_rate();
+        let format = StreamFormat {
+            sample_rate: sample_rate.get(),
+            bit_depth,
+            channels: source.channels().get(),
+        };
+        let same_sample_rate = self
+            .output_stream
+            .as_ref()
+            .map(|stream| stream.sample_rate() == sample_rate)
+            .unwrap_or(true);
+
+        // Secondary zones play raw samples, so they can not follow a change of channels
+        let same_channels =
+            self.zone_outputs.is_empty() || self.zone_channels == Some(source.channels());
+
+        if !same_sample_rate || !same_channels {
+            return Ok(QueryTrackResult::RecreateStreamRequired);
+        }
+
+        let trimmed_frames = Arc::new(AtomicU64::default());
+        let source = SilenceTrim::new(
+            source,
+            self.trim_silence.load(Ordering::Relaxed),
+            trimmed_frames.clone(),
+        );
+        let source: Box<dyn Source + Send> = match &self.dsp_command {
+            Some(command) => Box::new(
+                DspPipe::new(source, command.clone(), self.broadcast.clone()).map_err(|err| {
+                    Error::Dsp {
+                        message: format!("{command}: {err}"),
+                    }
+                })?,
+            ),
+            None => Box::new(source),
+        };
+
+        let needs_stream = self.output_stream.is_none() || self.sink.is_none();
+
+        if needs_stream {
+            let channels = source.channels();
+            let (player, stream) = match &self.pcm_output {
+                Some(pcm_output) => {
+                    let (player, output) = Player::new();
+                    pcm_output.play(PcmSource {
+                        source: Box::new(UniformSourceIterator::new(output, channels, sample_rate)),
+                        channels,
+                        sample_rate,
+                    });
+                    (player, OutputStream::Pcm(sample_rate))
+                }
+                None => {
+                    let mut mixer = if let Some(preferred_device_name) =
+                        self.preferred_device_id.as_deref()
+                    {
+                        silence_stderr(|| {
+                            open_preferred_stream(sample_rate, preferred_device_name, &self.stats)
+                        })?
+                    } else {
+                        open_default_stream(sample_rate, &self.stats)?
+                    };
+                    mixer.log_on_drop(false);
+                    let player = Player::connect_new(mixer.mixer());
+                    (player, OutputStream::Device(mixer))
+                }
+            };
+
+            let (sender, receiver) = queue(true);
+            player.append(receiver);
+            match self.fade_in_pending.load(Ordering::Relaxed) {
+                true => player.set_volume(0.0),
+                false => self.set_volume(&player),
+            }
+
+            self.sink = Some(Arc::new(player));
+            self.sender = Some(sender);
+            self.output_stream = Some(stream);
+            self.open_zones(source.channels(), sample_rate);
+        }
+
+        let track_format = TrackFormat { track_id, format };
+        let playing = self
+            .track_handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished());
+        match playing {
+            true => *self.next_format.lock() = Some(track_format),
+            false => self
+                .broadcast
+                .send(Notification::StreamFormat(track_format)),
+        }
+
+        let broadcast = self.broadcast.clone();
+        let next_format = self.next_format.clone();
+        let track_finished = self.track_finished.clone();
+        let track_duration = source.total_duration().unwrap_or_default();
+
+        let duration_played = self.duration_played.clone();
+        let source = ChannelMix::new(source, self.channel_mix.clone());
+        let buffers = self
+            .zone_outputs
+            .iter()
+            .map(|output| (output.settings.clone(), output.buffer.clone()))
+            .collect();
+        let source = ZoneTee::new(source, buffers);
+        let sender = self.sender.as_ref().unwrap();
+        sender.append(source);
+
+        // Called from the output thread once the last sample was handed over. The callback
+        // is dropped when the queue is cleared, which ends the task
+        let (finished_tx, mut finished_rx) = mpsc::unbounded_channel();
+        sender.append(EmptyCallback::new(Box::new(move || {
+            _ = finished_tx.send(SystemTime::now());
+        })));
+        let track_handle = tokio::spawn(async move {
+            if let Some(finished_at) = finished_rx.recv().await {
+                let trimmed = Duration::from_secs_f64(
+                    trimmed_frames.load(Ordering::Relaxed) as f64 / sample_rate.get() as f64,
+                );
+                *duration_played.lock() += track_duration.saturating_sub(trimmed);
+                track_finished.send(finished_at).expect("infallible");
+                if let Some(next_format) = next_format.lock().take() {
+                    broadcast.send(Notification::StreamFormat(next_format));
+                }
+            }
+        });
+
+        self.track_handle = Some(track_handle);
+
+        Ok(QueryTrackResult::Queued(format))
+    }
+
+    /// A zone whose device can not be opened is skipped, so the other zones keep playing
+    fn open_zones(&mut self, channels: ChannelCount, sample_rate: SampleRate) {
+        self.zone_channels = Some(channels);
+        self.zone_outputs = self
+            .zones
+            .iter()
+            .skip(1)
+            .filter_map(|settings| {
+                let device = settings.device.as_deref()?;
+                let stream =
+                    silence_stderr(|| open_preferred_stream(sample_rate, device, &self.stats));
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        tracing::warn!("Unable to open zone {}: {err}", settings.name);
+                        return None;
+                    }
+                };
+                stream.log_on_drop(false);
+
+                let buffer = Arc::new(ZoneBuffer::new(channels, sample_rate));
+                let player = rodio::Player::connect_new(stream.mixer());
+                player.append(ZoneSource::new(buffer.clone(), channels, sample_rate));
+                player.set_volume(self.zone_gain(settings));
+
+                Some(ZoneOutput {
+                    settings: settings.clone(),
+                    buffer,
+                    player,
+                    _stream: stream,
+                })
+            })
+            .collect();
+    }
+}
+
+impl AudioBackend for Sink {
+    fn track_finished(&self) -> Receiver<SystemTime> {
         self.track_finished.subscribe()
     }
 
-    pub fn position(&self) -> Duration {
+    fn position(&self) -> Duration {
         let position = self.sink.as_ref().map(|x| x.get_pos()).unwrap_or_default();
 
         let duration_played = *self.duration_played.lock();
@@ -60,28 +388,77 @@ impl Sink {
         position - duration_played
     }
 
-    pub fn play(&self) {
-        if let Some(player) = &self.sink {
-            player.play();
+    fn play(&self) {
+        let Some(player) = &self.sink else {
+            return;
+        };
+
+        let fade_in = self.fade_in_pending.swap(false, Ordering::Relaxed);
+        // Takes back a pause that is still ramping down, from where it got to
+        let ramping = self.stop_ramp();
+        if !player.is_paused() && !fade_in && !ramping {
+            return;
+        }
+
+        let from = match player.is_paused() {
+            true => 0.0,
+            false => player.volume(),
+        };
+        let ramp = match fade_in {
+            true => self.skip_fade,
+            false => self.volume_ramp,
+        };
+
+        match ramp {
+            Some(ramp) => {
+                player.set_volume(from);
+                player.play();
+                self.start_ramp(player, from, self.gain(), ramp, |_| {});
+            }
+            None => {
+                self.set_volume(player);
+                player.play();
+            }
         }
     }
 
-    pub fn pause(&self) {
-        if let Some(player) = &self.sink {
-            player.pause();
+    fn pause(&self) {
+        let Some(player) = &self.sink else {
+            return;
+        };
+        if player.is_paused() {
+            return;
+        }
+
+        let gain = self.gain();
+        match self.volume_ramp {
+            Some(ramp) => self.start_ramp(player, player.volume(), 0.0, ramp, move |player| {
+                player.pause();
+                player.set_volume(gain);
+            }),
+            None => player.pause(),
         }
     }
 
-    pub fn seek(&self, duration: Duration) -> AppResult<()> {
+    fn fade_out(&self) -> Option<Duration> {
+        let fade = self.skip_fade?;
+        let player = self.sink.as_ref().filter(|player| !player.is_paused())?;
+
+        self.fade_in_pending.store(true, Ordering::Relaxed);
+        self.start_ramp(player, self.gain(), 0.0, fade, |_| {});
+        Some(fade)
+    }
+
+    fn seek(&self, duration: Duration) -> AppResult<()> {
         if let Some(player) = &self.sink {
-            let current_volume = *self.volume.borrow();
+            self.stop_ramp();
             player.set_volume(0.0);
             player.pause();
 
             let result = player.try_seek(duration);
 
             player.play();
-            set_volume(player, &current_volume);
+            self.set_volume(player);
 
             match result {
                 Ok(_) => {
@@ -97,13 +474,17 @@ impl Sink {
         Ok(())
     }
 
-    pub fn clear(&mut self) -> AppResult<()> {
+    fn clear(&mut self) -> AppResult<()> {
         tracing::info!("Clearing sink");
         self.clear_queue()?;
+        // The ramp holds on to the player, which must be dropped to release the output
+        self.stop_ramp();
 
         self.sink = None;
         self.output_stream = None;
         self.sender = None;
+        self.zone_outputs.clear();
+        self.zone_channels = None;
 
         *self.duration_played.lock() = Default::default();
 
@@ -114,9 +495,10 @@ impl Sink {
         Ok(())
     }
 
-    pub fn clear_queue(&mut self) -> AppResult<()> {
+    fn clear_queue(&mut self) -> AppResult<()> {
         tracing::info!("Clearing sink queue");
         *self.duration_played.lock() = Default::default();
+        *self.next_format.lock() = None;
 
         if let Some(sender) = self.sender.as_ref() {
             sender.clear();
@@ -124,120 +506,103 @@ impl Sink {
         Ok(())
     }
 
-    pub fn is_empty(&self) -> bool {
+    fn is_empty(&self) -> bool {
         self.sink.is_none()
     }
 
-    pub fn query_track(&mut self, track_path: &Path) -> AppResult<QueryTrackResult> {
+    fn query_track(&mut self, track_id: u32, track_path: &Path) -> AppResult<QueryTrackResult> {
         tracing::info!("Sink query track: {}", track_path.to_string_lossy());
 
-        let file = fs::File::open(track_path).map_err(|err| Error::StreamError {
-            message: format!("Failed to read file: {track_path:?}: {err}"),
+        let mut file = CacheFile::open(track_path, self.cache_key.as_ref()).map_err(|err| {
+            Error::StreamError {
+                message: format!("Failed to read file: {track_path:?}: {err}"),
+            }
         })?;
 
-        let source = Decoder::try_from(file)?;
-        self.queue_decoder(source)
+        let bit_depth = flac_bit_depth(&mut file);
+        let byte_len = file.len();
+        let source = Decoder::builder()
+            .with_data(file)
+            .with_byte_len(byte_len)
+            .with_seekable(true)
+            .build()?;
+        self.queue_decoder(track_id, source, bit_depth)
     }
 
-    pub fn query_track_stream(
+    fn query_track_stream(
         &mut self,
-        reader: SeekableStreamReader,
+        track_id: u32,
+        mut reader: SeekableStreamReader,
     ) -> AppResult<QueryTrackResult> {
         tracing::info!("Sink query track (streaming)");
 
+        let bit_depth = flac_bit_depth(&mut reader);
         let byte_len = reader.content_length();
+        let (reader, armed) = MonitoredReader::new(reader, self.stats.clone());
         let source = Decoder::builder()
             .with_data(reader)
             .with_byte_len(byte_len)
             .with_seekable(true)
             .build()
-            .map_err(|e| Error::StreamError {
+            .map_err(|e| Error::Decode {
                 message: format!("Failed to decode streaming FLAC: {e}"),
             })?;
+        armed.store(true, Ordering::Relaxed);
 
-        self.queue_decoder(source)
+        self.queue_decoder(track_id, source, bit_depth)
     }
 
-    fn queue_decoder<R: Read + Seek + Send + Sync + 'static>(
-        &mut self,
-        source: Decoder<R>,
-    ) -> AppResult<QueryTrackResult> {
-        let sample_rate = source.sample_rate();
-        let same_sample_rate = self
-            .output_stream
-            .as_ref()
-            .map(|mixer| mixer.config().sample_rate() == sample_rate)
-            .unwrap_or(true);
-
-        if !same_sample_rate {
-            return Ok(QueryTrackResult::RecreateStreamRequired);
+    fn sync_volume(&self) {
+        if let Some(mixer) = &self.hardware_mixer
+            && let Err(err) = mixer.set_volume(*self.volume.borrow())
+        {
+            tracing::warn!("{err}");
         }
 
-        let needs_stream = self.output_stream.is_none() || self.sink.is_none();
-
-        if needs_stream {
-            let mut mixer = if let Some(preferred_device_name) = self.preferred_device_id.as_deref()
-            {
-                silence_stderr(|| open_preferred_stream(sample_rate, preferred_device_name))?
-            } else {
-                open_default_stream(sample_rate)?
-            };
-            mixer.log_on_drop(false);
-
-            let (sender, receiver) = queue(true);
-            let player = rodio::Player::connect_new(mixer.mixer());
-            player.append(receiver);
-            set_volume(&player, &self.volume.borrow());
-
-            self.sink = Some(player);
-            self.sender = Some(sender);
-            self.output_stream = Some(mixer);
+        // A running ramp sets the volume of the main output until it is done
+        if let Some(player) = &self.sink
+            && !self.ramping()
+        {
+            self.set_volume(player);
         }
 
-        let track_finished = self.track_finished.clone();
-        let track_duration = source.total_duration().unwrap_or_default();
-
-        let duration_played = self.duration_played.clone();
-        let signal = self.sender.as_ref().unwrap().append_with_signal(source);
-
-        let track_handle = tokio::spawn(async move {
-            loop {
-                if signal.try_recv().is_ok() {
-                    *duration_played.lock() += track_duration;
-                    track_finished.send(()).expect("infallible");
-                    break;
-                }
-                sleep(Duration::from_millis(200)).await;
-            }
-        });
-
-        self.track_handle = Some(track_handle);
-
-        Ok(QueryTrackResult::Queued)
-    }
-
-    pub fn sync_volume(&self) {
-        if let Some(player) = &self.sink {
-            set_volume(player, &self.volume.borrow());
+        for output in &self.zone_outputs {
+            output.player.set_volume(self.zone_gain(&output.settings));
         }
     }
 }
 
-fn set_volume(sink: &rodio::Player, volume: &f32) {
-    let volume = volume.clamp(0.0, 1.0).powi(3);
-    sink.set_volume(volume);
+async fn ramp_volume(player: &Player, from: f32, to: f32, ramp: Duration) {
+    let steps = (ramp.as_millis() / VOLUME_RAMP_STEP.as_millis()).max(1) as u32;
+
+    for step in 1..=steps {
+        let progress = step as f32 / steps as f32;
+        player.set_volume(from + (to - from) * progress);
+        sleep(VOLUME_RAMP_STEP).await;
+    }
 }
 
-fn open_default_stream(sample_rate: NonZero<u32>) -> AppResult<rodio::MixerDeviceSink> {
+fn open_default_stream(
+    sample_rate: NonZero<u32>,
+    stats: &Arc<PlaybackStats>,
+) -> AppResult<rodio::MixerDeviceSink> {
     rodio::DeviceSinkBuilder::from_default_device()
-        .and_then(|x| x.with_sample_rate(sample_rate).open_stream())
+        .and_then(|x| {
+            x.with_sample_rate(sample_rate)
+                .with_error_callback(stats.stream_error_callback())
+                .open_stream()
+        })
         .or_else(|original_err| {
             let mut devices = rodio::cpal::default_host().output_devices()?;
 
             Ok(devices
                 .find_map(|d| {
                     rodio::DeviceSinkBuilder::from_device(d)
-                        .and_then(|x| x.with_sample_rate(sample_rate).open_sink_or_fallback())
+                        .and_then(|x| {
+                            x.with_sample_rate(sample_rate)
+                                .with_error_callback(stats.stream_error_callback())
+                                .open_sink_or_fallback()
+                        })
                         .ok()
                 })
                 .ok_or(original_err)?)
@@ -247,6 +612,7 @@ fn open_default_stream(sample_rate: NonZero<u32>) -> AppResult<rodio::MixerDevic
 fn open_preferred_stream(
     sample_rate: NonZero<u32>,
     preferred_device_name: &str,
+    stats: &Arc<PlaybackStats>,
 ) -> AppResult<rodio::MixerDeviceSink> {
     let devices = rodio::cpal::default_host().output_devices()?;
 
@@ -254,9 +620,11 @@ fn open_preferred_stream(
         if device.description().map(|x| x.to_string()).ok().as_deref()
             == Some(preferred_device_name)
         {
-            let Ok(stream) = rodio::DeviceSinkBuilder::from_device(device)
-                .and_then(|x| x.with_sample_rate(sample_rate).open_sink_or_fallback())
-            else {
+            let Ok(stream) = rodio::DeviceSinkBuilder::from_device(device).and_then(|x| {
+                x.with_sample_rate(sample_rate)
+                    .with_error_callback(stats.stream_error_callback())
+                    .open_sink_or_fallback()
+            }) else {
                 break;
             };
 
@@ -275,9 +643,48 @@ fn open_preferred_stream(
     })
 }
 
-pub enum QueryTrackResult {
-    Queued,
-    RecreateStreamRequired,
+/// Whether the preferred output device, or any device when none is preferred, is present
+pub fn output_device_available(preferred_device_name: Option<&str>) -> bool {
+    silence_stderr(|| {
+        let Ok(mut devices) = rodio::cpal::default_host().output_devices() else {
+            return false;
+        };
+
+        match preferred_device_name {
+            Some(name) => devices.any(|device| {
+                device.description().map(|x| x.to_string()).ok().as_deref() == Some(name)
+            }),
+            None => devices.next().is_some(),
+        }
+    })
+}
+
+/// Names accepted by `--output-device-id`
+pub fn output_device_names() -> Vec<String> {
+    silence_stderr(|| {
+        let Ok(devices) = rodio::cpal::default_host().output_devices() else {
+            return vec![];
+        };
+
+        devices
+            .flat_map(|device| device.description().map(|x| x.to_string()))
+            .collect()
+    })
+}
+
+/// Bits per sample from the STREAMINFO block of a FLAC stream. The reader is rewound
+fn flac_bit_depth<R: Read + Seek>(reader: &mut R) -> Option<u32> {
+    let mut header = [0; 22];
+    let read = reader.read_exact(&mut header);
+    reader.rewind().ok()?;
+    read.ok()?;
+
+    if &header[..4] != b"fLaC" {
+        return None;
+    }
+
+    let bits_per_sample = (((header[20] & 0x01) << 4) | (header[21] >> 4)) + 1;
+    Some(bits_per_sample.into())
 }
 
 impl Drop for Sink {
@@ -285,3 +692,42 @@ impl Drop for Sink {
         self.clear().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Seek};
+
+    use super::flac_bit_depth;
+
+    /// Start of a FLAC stream up to the end of STREAMINFO
+    fn flac_header(sample_rate: u64, channels: u64, bits_per_sample: u64) -> Vec<u8> {
+        let mut header = b"fLaC".to_vec();
+        header.extend([0x80, 0, 0, 34]);
+        header.extend([0; 10]);
+        let packed = (sample_rate << 44) | ((channels - 1) << 41) | ((bits_per_sample - 1) << 36);
+        header.extend(packed.to_be_bytes());
+        header.extend([0; 16]);
+        header
+    }
+
+    #[test]
+    fn reads_the_bit_depth_of_flac_streams() {
+        let mut reader = Cursor::new(flac_header(96_000, 2, 24));
+        assert_eq!(flac_bit_depth(&mut reader), Some(24));
+        assert_eq!(reader.stream_position().unwrap(), 0);
+
+        let mut reader = Cursor::new(flac_header(44_100, 2, 16));
+        assert_eq!(flac_bit_depth(&mut reader), Some(16));
+    }
+
+    #[test]
+    fn other_streams_have_no_bit_depth() {
+        let mut reader = Cursor::new(b"ID3\x04\x00\x00\x00\x00\x00\x00 an mp3 file".to_vec());
+        assert_eq!(flac_bit_depth(&mut reader), None);
+        assert_eq!(reader.stream_position().unwrap(), 0);
+
+        let mut reader = Cursor::new(b"fLaC".to_vec());
+        assert_eq!(flac_bit_depth(&mut reader), None);
+        assert_eq!(reader.stream_position().unwrap(), 0);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use futures::{StreamExt, stream};
+use time::{Duration, OffsetDateTime};
+
+use crate::{AppResult, client::Client, database::Database, models::Track};
+
+/// Tracks in a smart playlist at most
+const MAX_TRACKS: u32 = 100;
+
+/// Tracks looked up at once when they are not among the favorites
+pub(crate) const CONCURRENT_LOOKUPS: usize = 8;
+
+/// Playlists computed locally from the play history and the favorites
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmartPlaylist {
+    MostPlayedThisMonth,
+    NotPlayedInAYear,
+    RecentlyFavorited,
+}
+
+impl SmartPlaylist {
+    pub const ALL: [Self; 3] = [
+        Self::MostPlayedThisMonth,
+        Self::NotPlayedInAYear,
+        Self::RecentlyFavorited,
+    ];
+
+    /// Used in urls
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::MostPlayedThisMonth => "most-played-this-month",
+            Self::NotPlayedInAYear => "not-played-in-a-year",
+            Self::RecentlyFavorited => "recently-favorited",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::MostPlayedThisMonth => "Most played this month",
+            Self::NotPlayedInAYear => "Not played in a year",
+            Self::RecentlyFavorited => "Recently favorited",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::MostPlayedThisMonth => "Tracks played to the end most often in the last 30 days",
+            Self::NotPlayedInAYear => "Favorites and played tracks not heard for a year",
+            Self::RecentlyFavorited => "Tracks added to the favorites, newest first",
+        }
+    }
+
+    pub async fn tracks(self, database: &Database, client: &Client) -> AppResult<Vec<Track>> {
+        let now = OffsetDateTime::now_utc();
+        let favorites = client.favorites().await?;
+
+        let track_ids = match self {
+            Self::MostPlayedThisMonth => {
+                database
+                    .most_played_tracks(now - Duration::days(30), MAX_TRACKS)
+                    .await?
+            }
+            Self::NotPlayedInAYear => {
+                let last_played = database.tracks_last_played().await?;
+                not_played_since(&last_played, &favorites.tracks, now - Duration::days(365))
+            }
+            Self::RecentlyFavorited => database
+                .recently_favorited_tracks(MAX_TRACKS)
+                .await?
+                .into_iter()
+                .filter(|id| favorites.tracks.iter().any(|track| track.id == *id))
+                .collect(),
+        };
+
+        let favorite_tracks: HashMap<u32, &Track> = favorites
+            .tracks
+            .iter()
+            .map(|track| (track.id, track))
+            .collect();
+        let favorite_tracks = &favorite_tracks;
+        let tracks: Vec<_> = stream::iter(track_ids)
+            .map(|id| async move {
+                match favorite_tracks.get(&id) {
+                    Some(track) => Ok((*track).clone()),
+                    None => client.track(id).await,
+                }
+            })
+            .buffered(CONCURRENT_LOOKUPS)
+            .collect()
+            .await;
+
+        // Tracks removed from Qobuz since they were played are left out
+        Ok(tracks.into_iter().flatten().collect())
+    }
+}
+
+/// Favorites never played since `cutoff`, then the history tracks last played before it,
+/// longest ago first
+fn not_played_since(
+    last_played: &HashMap<u32, OffsetDateTime>,
+    favorites: &[Track],
+    cutoff: OffsetDateTime,
+) -> Vec<u32> {
+    let mut forgotten: Vec<(Option<OffsetDateTime>, u32)> = favorites
+        .iter()
+        .map(|track| (last_played.get(&track.id).copied(), track.id))
+        .chain(
+            last_played
+                .iter()
+                .map(|(id, played_at)| (Some(*played_at), *id)),
+        )
+        .filter(|(played_at, _)| played_at.is_none_or(|played_at| played_at < cutoff))
+        .collect();
+    forgotten.sort();
+    forgotten.dedup_by_key(|(_, id)| *id);
+
+    forgotten
+        .into_iter()
+        .take(MAX_TRACKS as usize)
+        .map(|(_, id)| id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: u32) -> Track {
+        Track {
+            id,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn forgotten_favorites_come_first_then_the_oldest_plays() {
+        let now = OffsetDateTime::now_utc();
+        let cutoff = now - Duration::days(365);
+        let last_played = HashMap::from([
+            (1, now - Duration::days(400)),
+            (2, now - Duration::days(10)),
+            (3, now - Duration::days(500)),
+            (4, now - Duration::days(380)),
+        ]);
+        let favorites = [track(1), track(2), track(5)];
+
+        assert_eq!(
+            not_played_since(&last_played, &favorites, cutoff),
+            [5, 3, 1, 4]
+        );
+    }
+
+    #[test]
+    fn recently_played_favorites_are_left_out() {
+        let now = OffsetDateTime::now_utc();
+        let last_played = HashMap::from([(1, now)]);
+
+        assert!(not_played_since(&last_played, &[track(1)], now - Duration::days(365)).is_empty());
+    }
+}
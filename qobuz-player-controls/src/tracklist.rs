@@ -1,6 +1,6 @@
 use std::ops::Index;
 
-use crate::models::{Track, TrackStatus};
+use crate::models::{LinkTarget, Track, TrackRestriction, TrackStatus};
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct AlbumTracklist {
@@ -36,11 +36,14 @@ pub enum TracklistType {
 pub struct Tracklist {
     queue: Vec<QueueItem>,
     list_type: TracklistType,
+    /// The queue ids were given by Qobuz Connect, so its queue edits can find the items
+    #[serde(default)]
+    connect: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum PlayingEntity {
-    Track(Track),
+    Track(Box<Track>),
     Playlist(PlayingPlaylist),
 }
 
@@ -57,6 +60,9 @@ pub struct QueueItem {
     pub track: Track,
     pub queue_id: u64,
     pub index: usize,
+    /// Added to the queue by the user, rather than part of the played list
+    #[serde(default)]
+    pub queued_by_user: bool,
 }
 
 impl Tracklist {
@@ -71,20 +77,30 @@ impl Tracklist {
         //     })
         //     .collect();
 
-        Self { queue, list_type }
+        Self {
+            queue,
+            list_type,
+            connect: false,
+        }
     }
 
     pub fn set_list_type(&mut self, list_type: TracklistType) {
         self.list_type = list_type
     }
 
+    /// Queue whose items keep the ids Qobuz Connect gave them
     pub fn new_with_id(list_type: TracklistType, items: Vec<QueueItem>) -> Self {
         Self {
             queue: items,
             list_type,
+            connect: true,
         }
     }
 
+    pub fn from_connect(&self) -> bool {
+        self.connect
+    }
+
     pub fn queue(&self) -> Vec<&QueueItem> {
         self.queue.iter().collect()
     }
@@ -115,7 +131,7 @@ impl Tracklist {
                     playlist_id: playlist_tracklist.id,
                 })
             }
-            _ => PlayingEntity::Track(queue_item.track.clone()),
+            _ => PlayingEntity::Track(Box::new(queue_item.track.clone())),
         })
     }
 
@@ -123,6 +139,21 @@ impl Tracklist {
         self.next_track().map(|x| x.id)
     }
 
+    pub fn set_streaming_details(
+        &mut self,
+        track_id: u32,
+        restriction: Option<TrackRestriction>,
+        delivered_format: Option<String>,
+    ) {
+        self.queue
+            .iter_mut()
+            .filter(|item| item.track.id == track_id)
+            .for_each(|item| {
+                item.track.restriction = restriction;
+                item.track.delivered_format = delivered_format.clone();
+            });
+    }
+
     pub fn remove_track(&mut self, index: usize) {
         self.queue.remove(index);
     }
@@ -131,8 +162,9 @@ impl Tracklist {
         let id = self.total() + 1;
         let item = QueueItem {
             track,
-            queue_id: id as u64,
+            queue_id: self.next_queue_id(),
             index: id,
+            queued_by_user: true,
         };
         self.queue.push(item);
     }
@@ -141,12 +173,33 @@ impl Tracklist {
         let id = self.total() + 1;
         let item = QueueItem {
             track,
-            queue_id: id as u64,
+            queue_id: self.next_queue_id(),
             index: id,
+            queued_by_user: true,
         };
         self.queue.insert(index, item);
     }
 
+    /// An id no item has, so removing by queue id never takes another item along
+    fn next_queue_id(&self) -> u64 {
+        self.queue
+            .iter()
+            .map(|item| item.queue_id + 1)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Insert items at `index`, or append them when it is past the end
+    pub fn insert_items(&mut self, index: usize, items: Vec<QueueItem>) {
+        let index = index.min(self.queue.len());
+        self.queue.splice(index..index, items);
+    }
+
+    pub fn remove_queue_ids(&mut self, queue_ids: &[u64]) {
+        self.queue
+            .retain(|item| !queue_ids.contains(&item.queue_id));
+    }
+
     pub fn reorder_queue(&mut self, new_order: Vec<usize>) {
         if new_order.iter().enumerate().all(|(i, &v)| i == v) {
             return;
@@ -214,6 +267,15 @@ impl Tracklist {
         Some(&self.queue.index(next_position).track)
     }
 
+    pub fn track_after_next(&self) -> Option<&Track> {
+        let position = self.current_position() + 2;
+        if self.total() <= position {
+            return None;
+        }
+
+        Some(&self.queue.index(position).track)
+    }
+
     pub fn current_track(&self) -> Option<&Track> {
         self.queue
             .iter()
@@ -221,6 +283,26 @@ impl Tracklist {
             .find(|t| t.status == TrackStatus::Playing)
     }
 
+    /// Qobuz item of the playing track, to share it
+    pub fn current_track_link(&self) -> Option<LinkTarget> {
+        self.current_track()
+            .map(|track| LinkTarget::Track(track.id))
+    }
+
+    /// Qobuz item of the album, playlist or artist being played. Tracks queued one by one
+    /// share the album of the playing track
+    pub fn current_list_link(&self) -> Option<LinkTarget> {
+        match &self.list_type {
+            TracklistType::Album(album) => Some(LinkTarget::Album(album.id.clone())),
+            TracklistType::Playlist(playlist) => Some(LinkTarget::Playlist(playlist.id)),
+            TracklistType::TopTracks(artist) => Some(LinkTarget::Artist(artist.id)),
+            TracklistType::Tracks => self
+                .current_track()
+                .and_then(|track| track.album_id.clone())
+                .map(LinkTarget::Album),
+        }
+    }
+
     pub fn skip_to_track(&mut self, new_position: i32) -> Option<&Track> {
         if new_position < 0 {
             return None;
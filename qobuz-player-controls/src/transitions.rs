@@ -0,0 +1,50 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Decoded format of the audio handed to the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StreamFormat {
+    pub sample_rate: u32,
+    /// `None` when the codec does not have a fixed bit depth, like mp3
+    pub bit_depth: Option<u32>,
+    pub channels: u16,
+}
+
+impl std::fmt::Display for StreamFormat {
+    /// Like "24-bit / 96 kHz"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kilohertz = self.sample_rate as f64 / 1000.0;
+        match self.bit_depth {
+            Some(bit_depth) => write!(f, "{bit_depth}-bit / {kilohertz} kHz"),
+            None => write!(f, "{kilohertz} kHz"),
+        }
+    }
+}
+
+/// Track boundaries and format changes, so external DSP or room correction software can
+/// resynchronize. Timestamps are microseconds since the unix epoch, taken when the last
+/// sample of a track was handed to the output, or when the first sample was queued
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TransitionEvent {
+    TrackStarted {
+        track_id: u32,
+        timestamp_us: u64,
+        format: StreamFormat,
+    },
+    TrackEnded {
+        track_id: u32,
+        timestamp_us: u64,
+    },
+    /// Sent before the track started event of the first track in the new format
+    FormatChanged {
+        timestamp_us: u64,
+        previous: Option<StreamFormat>,
+        format: StreamFormat,
+    },
+}
+
+pub(crate) fn timestamp_us(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as u64)
+        .unwrap_or_default()
+}
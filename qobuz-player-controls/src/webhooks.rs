@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    PositionReceiver, Status, StatusReceiver, TracklistReceiver, TransitionReceiver, models::Track,
+    tracklist::Tracklist, transitions::TransitionEvent,
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum WebhookEvent {
+    TrackStarted,
+    Paused,
+    TrackFinished,
+    QueueChanged,
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    event: WebhookEvent,
+    track: Option<&'a Track>,
+    position_seconds: u64,
+    queue_length: usize,
+    queue_position: usize,
+}
+
+/// What was last reported, to turn receiver updates into events
+#[derive(Debug, Default)]
+struct Reported {
+    playing: bool,
+    /// Queue and track id of every queue item
+    queue: Vec<(u64, u32)>,
+    /// Track the player started, reported once the tracklist shows it as playing
+    started: Option<u32>,
+}
+
+impl Reported {
+    /// Events for the latest tracklist and status
+    fn update(&mut self, tracklist: &Tracklist, status: Status) -> Vec<WebhookEvent> {
+        let mut events = vec![];
+
+        let queue = queue_of(tracklist);
+        if queue != self.queue {
+            events.push(WebhookEvent::QueueChanged);
+            self.queue = queue;
+        }
+
+        match self.started {
+            Some(track_id) if tracklist.currently_playing() == Some(track_id) => {
+                self.started = None;
+                self.playing = true;
+                events.push(WebhookEvent::TrackStarted);
+                return events;
+            }
+            // The start is reported with the track
+            Some(_) => {}
+            None if status == Status::Playing && !self.playing => {
+                events.push(WebhookEvent::TrackStarted)
+            }
+            None if status == Status::Paused && self.playing => events.push(WebhookEvent::Paused),
+            None => {}
+        }
+
+        if status != Status::Buffering {
+            self.playing = status == Status::Playing;
+        }
+        events
+    }
+}
+
+/// POST a JSON event to every url when a track starts, pauses or finishes and when the queue changes
+pub async fn init(
+    urls: Vec<String>,
+    mut tracklist_receiver: TracklistReceiver,
+    position_receiver: PositionReceiver,
+    mut status_receiver: StatusReceiver,
+    mut transition_receiver: TransitionReceiver,
+) {
+    if urls.is_empty() {
+        return;
+    }
+
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(err) => {
+            tracing::warn!("Unable to send webhooks: {err}");
+            return;
+        }
+    };
+
+    let mut reported = Reported {
+        playing: *status_receiver.borrow_and_update() == Status::Playing,
+        queue: queue_of(&tracklist_receiver.borrow_and_update()),
+        started: None,
+    };
+
+    loop {
+        tokio::select! {
+            // The player announces a start or end before it updates the tracklist and status
+            biased;
+            event = transition_receiver.recv() => match event {
+                Ok(TransitionEvent::TrackStarted { track_id, .. }) => {
+                    reported.started = Some(track_id);
+                }
+                Ok(TransitionEvent::TrackEnded { track_id, .. }) => {
+                    let tracklist = tracklist_receiver.borrow().clone();
+                    if let Some(item) = tracklist.queue().into_iter().find(|item| item.track.id == track_id) {
+                        let duration = Duration::from_secs(item.track.duration_seconds.into());
+                        send(&client, &urls, WebhookEvent::TrackFinished, Some(&item.track), duration, &tracklist);
+                    }
+                    continue;
+                }
+                Ok(TransitionEvent::FormatChanged { .. }) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+            changed = tracklist_receiver.changed() => if changed.is_err() { return },
+            changed = status_receiver.changed() => if changed.is_err() { return },
+        }
+
+        let tracklist = tracklist_receiver.borrow_and_update().clone();
+        let status = *status_receiver.borrow_and_update();
+        let position = *position_receiver.borrow();
+
+        for event in reported.update(&tracklist, status) {
+            send(
+                &client,
+                &urls,
+                event,
+                tracklist.current_track(),
+                position,
+                &tracklist,
+            );
+        }
+    }
+}
+
+fn queue_of(tracklist: &Tracklist) -> Vec<(u64, u32)> {
+    tracklist
+        .queue()
+        .iter()
+        .map(|item| (item.queue_id, item.track.id))
+        .collect()
+}
+
+fn send(
+    client: &reqwest::Client,
+    urls: &[String],
+    event: WebhookEvent,
+    track: Option<&Track>,
+    position: Duration,
+    tracklist: &Tracklist,
+) {
+    let payload = WebhookPayload {
+        event,
+        track,
+        position_seconds: position.as_secs(),
+        queue_length: tracklist.total(),
+        queue_position: tracklist.current_position(),
+    };
+
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::warn!("Unable to serialize webhook {event:?}: {err}");
+            return;
+        }
+    };
+
+    for url in urls {
+        let request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        let url = url.clone();
+
+        tokio::spawn(async move {
+            match request.send().await.and_then(|res| res.error_for_status()) {
+                Ok(_) => tracing::debug!("Webhook {event:?} sent to {url}"),
+                Err(err) => tracing::warn!("Webhook {event:?} to {url} failed: {err}"),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reported, WebhookEvent, queue_of};
+    use crate::{
+        Status,
+        models::{Track, TrackStatus},
+        tracklist::{QueueItem, Tracklist, TracklistType},
+    };
+
+    /// Queue of the track ids with queue ids counting from 0, like every new album
+    fn tracklist(track_ids: &[u32], playing: usize) -> Tracklist {
+        let items = track_ids
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| QueueItem {
+                track: Track {
+                    id,
+                    status: match index == playing {
+                        true => TrackStatus::Playing,
+                        false => TrackStatus::Unplayed,
+                    },
+                    ..Default::default()
+                },
+                queue_id: index as u64,
+                index,
+                queued_by_user: false,
+            })
+            .collect();
+        Tracklist::new(TracklistType::Tracks, items)
+    }
+
+    #[test]
+    fn new_album_of_the_same_length_changes_the_queue_and_starts_a_track() {
+        let first_album = tracklist(&[1, 2], 0);
+        let mut reported = Reported {
+            playing: true,
+            queue: queue_of(&first_album),
+            started: None,
+        };
+
+        // Started before the tracklist of the new album is sent
+        reported.started = Some(3);
+        assert_eq!(reported.update(&first_album, Status::Playing), vec![]);
+        assert_eq!(
+            reported.update(&tracklist(&[3, 4], 0), Status::Playing),
+            vec![WebhookEvent::QueueChanged, WebhookEvent::TrackStarted]
+        );
+    }
+
+    #[test]
+    fn resuming_starts_the_track_once() {
+        let album = tracklist(&[1, 2], 1);
+        let mut reported = Reported {
+            playing: false,
+            queue: queue_of(&album),
+            started: None,
+        };
+
+        assert_eq!(reported.update(&album, Status::Buffering), vec![]);
+        assert_eq!(
+            reported.update(&album, Status::Playing),
+            vec![WebhookEvent::TrackStarted]
+        );
+        assert_eq!(reported.update(&album, Status::Playing), vec![]);
+        assert_eq!(
+            reported.update(&album, Status::Paused),
+            vec![WebhookEvent::Paused]
+        );
+
+        // A start from pause is reported with the track, not again with the status
+        reported.started = Some(2);
+        assert_eq!(
+            reported.update(&album, Status::Paused),
+            vec![WebhookEvent::TrackStarted]
+        );
+        assert_eq!(reported.update(&album, Status::Playing), vec![]);
+    }
+}
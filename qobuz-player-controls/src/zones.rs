@@ -0,0 +1,402 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use rodio::source::SeekError;
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+/// Samples moved between a [`ZoneTee`] and a [`ZoneSource`] at once
+const CHUNK_LEN: usize = 1024;
+
+/// Output device playing the same audio as the other zones
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Zone {
+    pub name: String,
+    pub volume: f32,
+    pub enabled: bool,
+}
+
+/// Volume of a zone, shared between the player and the sink
+#[derive(Debug)]
+pub struct ZoneSettings {
+    pub name: String,
+    /// Output device. `None` for the default device
+    pub device: Option<String>,
+    volume: AtomicU32,
+    enabled: AtomicBool,
+}
+
+impl ZoneSettings {
+    pub fn new(name: String, device: Option<String>) -> Self {
+        Self {
+            name,
+            device,
+            volume: AtomicU32::new(1.0_f32.to_bits()),
+            enabled: AtomicBool::new(true),
+        }
+    }
+
+    /// Relative to the player volume, from 0.0 to 1.0
+    pub fn set_volume(&self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Factor applied on top of the player volume
+    pub fn gain(&self) -> f32 {
+        match self.enabled() {
+            true => self.volume(),
+            false => 0.0,
+        }
+    }
+
+    pub fn zone(&self) -> Zone {
+        Zone {
+            name: self.name.clone(),
+            volume: self.volume(),
+            enabled: self.enabled(),
+        }
+    }
+}
+
+/// Samples waiting to be played by a secondary zone. Old samples are dropped when the
+/// zone falls behind, so it never drifts further than the capacity. Samples are only moved
+/// in whole frames to keep the channels in place.
+///
+/// The clocks of two devices never run at quite the same rate, so the fill level is kept
+/// around a target by dropping or repeating a single frame per chunk
+pub struct ZoneBuffer {
+    queue: Mutex<ZoneQueue>,
+    capacity: usize,
+    target: usize,
+    channels: usize,
+}
+
+#[derive(Default)]
+struct ZoneQueue {
+    samples: VecDeque<Sample>,
+    /// Whether the target was reached since the buffer last ran empty
+    primed: bool,
+}
+
+impl ZoneBuffer {
+    /// Holds half a second of audio and aims for a tenth of a second
+    pub fn new(channels: ChannelCount, sample_rate: SampleRate) -> Self {
+        let channels = channels.get() as usize;
+        let capacity = (channels * sample_rate.get() as usize / 2).max(8 * CHUNK_LEN);
+        let target = (channels * sample_rate.get() as usize / 10).max(2 * CHUNK_LEN);
+        Self {
+            queue: Mutex::new(ZoneQueue {
+                samples: VecDeque::with_capacity(capacity),
+                primed: false,
+            }),
+            capacity,
+            target,
+            channels,
+        }
+    }
+
+    fn push(&self, chunk: &[Sample]) {
+        let samples = &mut self.queue.lock().samples;
+        samples.extend(chunk);
+        let overflow = samples.len().saturating_sub(self.capacity);
+        let overflow = (overflow.div_ceil(self.channels) * self.channels).min(samples.len());
+        samples.drain(..overflow);
+    }
+
+    /// Moves the next chunk into `chunk`. Nothing is moved until the target is reached
+    fn take(&self, chunk: &mut VecDeque<Sample>) {
+        let mut queue = self.queue.lock();
+        if !queue.primed && queue.samples.len() < self.target {
+            return;
+        }
+        queue.primed = true;
+
+        let samples = &mut queue.samples;
+        let len = samples.len().min(CHUNK_LEN);
+        let len = len - len % self.channels;
+        if len == 0 {
+            queue.primed = false;
+            return;
+        }
+        chunk.extend(samples.drain(..len));
+
+        if samples.len() > self.target + CHUNK_LEN {
+            // The zone plays slower than the main output
+            let skipped = self.channels.min(samples.len());
+            samples.drain(..skipped);
+        } else if samples.len() + CHUNK_LEN < self.target {
+            // The zone plays faster than the main output
+            let last_frame: Vec<_> = chunk
+                .range(chunk.len() - self.channels..)
+                .copied()
+                .collect();
+            chunk.extend(last_frame);
+        }
+    }
+
+    pub fn clear(&self) {
+        *self.queue.lock() = ZoneQueue::default();
+    }
+}
+
+/// Copies the samples of the inner source to the buffers of the secondary zones
+pub struct ZoneTee<I> {
+    input: I,
+    buffers: Vec<(Arc<ZoneSettings>, Arc<ZoneBuffer>)>,
+    pending: Vec<Sample>,
+}
+
+impl<I: Source> ZoneTee<I> {
+    pub fn new(input: I, buffers: Vec<(Arc<ZoneSettings>, Arc<ZoneBuffer>)>) -> Self {
+        Self {
+            input,
+            buffers,
+            pending: Vec::with_capacity(CHUNK_LEN),
+        }
+    }
+
+    fn flush(&mut self) {
+        for (settings, buffer) in &self.buffers {
+            if settings.enabled() {
+                buffer.push(&self.pending);
+            }
+        }
+        self.pending.clear();
+    }
+}
+
+impl<I: Source> Iterator for ZoneTee<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some(sample) = self.input.next() else {
+            self.flush();
+            return None;
+        };
+
+        if !self.buffers.is_empty() {
+            self.pending.push(sample);
+            let whole_frames = self
+                .pending
+                .len()
+                .is_multiple_of(self.input.channels().get() as usize);
+            if self.pending.len() >= CHUNK_LEN && whole_frames {
+                self.flush();
+            }
+        }
+
+        Some(sample)
+    }
+}
+
+impl<I: Source> Source for ZoneTee<I> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.input.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.pending.clear();
+        for (_, buffer) in &self.buffers {
+            buffer.clear();
+        }
+        self.input.try_seek(pos)
+    }
+}
+
+/// Endless source playing a [`ZoneBuffer`], with silence while it is empty
+pub struct ZoneSource {
+    buffer: Arc<ZoneBuffer>,
+    chunk: VecDeque<Sample>,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+}
+
+impl ZoneSource {
+    pub fn new(buffer: Arc<ZoneBuffer>, channels: ChannelCount, sample_rate: SampleRate) -> Self {
+        Self {
+            buffer,
+            chunk: VecDeque::with_capacity(CHUNK_LEN),
+            channels,
+            sample_rate,
+        }
+    }
+}
+
+impl Iterator for ZoneSource {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunk.is_empty() {
+            self.buffer.take(&mut self.chunk);
+        }
+        if self.chunk.is_empty() {
+            self.chunk
+                .extend(std::iter::repeat_n(0.0, self.channels.get() as usize));
+        }
+
+        self.chunk.pop_front()
+    }
+}
+
+impl Source for ZoneSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    use rodio::{ChannelCount, SampleRate, Source, buffer::SamplesBuffer};
+
+    use super::{CHUNK_LEN, ZoneBuffer, ZoneSettings, ZoneSource, ZoneTee};
+
+    fn stereo() -> ChannelCount {
+        ChannelCount::new(2).unwrap()
+    }
+
+    /// Small enough for the minimum sizes: a capacity of 8 and a target of 2 chunks
+    fn rate() -> SampleRate {
+        SampleRate::new(1000).unwrap()
+    }
+
+    fn ramp(from: usize, len: usize) -> Vec<f32> {
+        (from..from + len).map(|sample| sample as f32).collect()
+    }
+
+    #[test]
+    fn waits_for_the_target_before_playing() {
+        let buffer = ZoneBuffer::new(stereo(), rate());
+        let mut chunk = VecDeque::new();
+
+        buffer.push(&ramp(0, CHUNK_LEN));
+        buffer.take(&mut chunk);
+        assert!(chunk.is_empty());
+
+        buffer.push(&ramp(CHUNK_LEN, CHUNK_LEN));
+        buffer.take(&mut chunk);
+        assert_eq!(chunk.len(), CHUNK_LEN);
+        assert_eq!(chunk.front(), Some(&0.0));
+    }
+
+    #[test]
+    fn drops_the_oldest_whole_frames_when_full() {
+        let buffer = ZoneBuffer::new(stereo(), rate());
+        buffer.push(&ramp(0, 8 * CHUNK_LEN + 3));
+
+        let mut chunk = VecDeque::new();
+        buffer.take(&mut chunk);
+        assert_eq!(chunk.front(), Some(&4.0));
+    }
+
+    #[test]
+    fn skips_a_frame_when_the_zone_falls_behind() {
+        let buffer = ZoneBuffer::new(stereo(), rate());
+        buffer.push(&ramp(0, 5 * CHUNK_LEN));
+
+        let mut chunk = VecDeque::new();
+        buffer.take(&mut chunk);
+        chunk.clear();
+        buffer.take(&mut chunk);
+        assert_eq!(chunk.front(), Some(&(CHUNK_LEN as f32 + 2.0)));
+    }
+
+    #[test]
+    fn repeats_a_frame_when_the_zone_runs_ahead() {
+        let buffer = ZoneBuffer::new(stereo(), rate());
+        buffer.push(&ramp(0, 2 * CHUNK_LEN));
+
+        let mut chunk = VecDeque::new();
+        buffer.take(&mut chunk);
+        chunk.clear();
+        buffer.take(&mut chunk);
+
+        let last = (2 * CHUNK_LEN) as f32;
+        assert_eq!(chunk.len(), CHUNK_LEN + 2);
+        assert_eq!(
+            chunk.iter().rev().take(4).copied().collect::<Vec<_>>(),
+            vec![last - 1.0, last - 2.0, last - 1.0, last - 2.0]
+        );
+    }
+
+    #[test]
+    fn tee_copies_to_enabled_zones_only() {
+        let enabled = Arc::new(ZoneSettings::new("Kitchen".into(), None));
+        let disabled = Arc::new(ZoneSettings::new("Garden".into(), None));
+        disabled.set_enabled(false);
+        let enabled_buffer = Arc::new(ZoneBuffer::new(stereo(), rate()));
+        let disabled_buffer = Arc::new(ZoneBuffer::new(stereo(), rate()));
+
+        let input = SamplesBuffer::new(stereo(), rate(), ramp(0, 2 * CHUNK_LEN));
+        let tee = ZoneTee::new(
+            input,
+            vec![
+                (enabled, enabled_buffer.clone()),
+                (disabled, disabled_buffer.clone()),
+            ],
+        );
+        assert_eq!(tee.count(), 2 * CHUNK_LEN);
+
+        let played: Vec<_> = ZoneSource::new(enabled_buffer, stereo(), rate())
+            .take(2 * CHUNK_LEN)
+            .collect();
+        assert_eq!(played, ramp(0, 2 * CHUNK_LEN));
+
+        let mut silence = ZoneSource::new(disabled_buffer, stereo(), rate());
+        assert_eq!(silence.channels(), stereo());
+        assert!(silence.by_ref().take(8).all(|sample| sample == 0.0));
+    }
+
+    #[test]
+    fn disabled_zones_are_muted() {
+        let zone = ZoneSettings::new("Kitchen".into(), None);
+        zone.set_volume(1.5);
+        assert_eq!(zone.gain(), 1.0);
+
+        zone.set_volume(0.4);
+        zone.set_enabled(false);
+        assert_eq!(zone.gain(), 0.0);
+        assert_eq!(zone.zone().volume, 0.4);
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use qobuz_player_controls::{
+    AppResult, PositionReceiver, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
+    error::Error, models::Track,
+};
+use rppal::i2c::I2c;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{DisplayKind, DisplaySettings, hd44780::Hd44780, ssd1306::Ssd1306};
+
+/// Redraw interval, so the elapsed time keeps moving while playing
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+const SSD1306_ADDRESS: u16 = 0x3C;
+const HD44780_ADDRESS: u16 = 0x27;
+
+/// Text display showing the now playing lines
+pub(crate) trait Display {
+    fn columns(&self) -> usize;
+    fn rows(&self) -> usize;
+    fn draw(&mut self, lines: &[String]) -> rppal::i2c::Result<()>;
+}
+
+/// Draws now playing on the display. The I2C writes block, so the display is driven
+/// from a thread of its own
+pub async fn init(
+    settings: DisplaySettings,
+    mut tracklist_receiver: TracklistReceiver,
+    position_receiver: PositionReceiver,
+    mut status_receiver: StatusReceiver,
+    mut volume_receiver: VolumeReceiver,
+) -> AppResult<()> {
+    let (opened_tx, opened_rx) = oneshot::channel();
+    let (lines_tx, mut lines_rx) = mpsc::channel::<Vec<String>>(1);
+
+    std::thread::spawn(move || {
+        let mut display = match open(&settings) {
+            Ok(display) => display,
+            Err(err) => {
+                _ = opened_tx.send(Err(err.to_string()));
+                return;
+            }
+        };
+        _ = opened_tx.send(Ok((display.columns(), display.rows())));
+
+        while let Some(lines) = lines_rx.blocking_recv() {
+            if let Err(err) = display.draw(&lines) {
+                tracing::warn!("Unable to draw display: {err}");
+            }
+        }
+    });
+
+    let (columns, rows) = match opened_rx.await {
+        Ok(Ok(size)) => size,
+        Ok(Err(message)) => return Err(Error::DisplayUnavailable { message }),
+        Err(_) => {
+            return Err(Error::DisplayUnavailable {
+                message: "the display thread stopped".into(),
+            });
+        }
+    };
+    tracing::info!("Display claimed");
+
+    loop {
+        let lines = {
+            let tracklist = tracklist_receiver.borrow_and_update();
+            now_playing_lines(
+                tracklist.current_track(),
+                *position_receiver.borrow(),
+                *status_receiver.borrow_and_update(),
+                *volume_receiver.borrow_and_update(),
+                columns,
+                rows,
+            )
+        };
+
+        if lines_tx.send(lines).await.is_err() {
+            return Ok(());
+        }
+
+        tokio::select! {
+            changed = tracklist_receiver.changed() => if changed.is_err() { return Ok(()) },
+            changed = status_receiver.changed() => if changed.is_err() { return Ok(()) },
+            changed = volume_receiver.changed() => if changed.is_err() { return Ok(()) },
+            _ = tokio::time::sleep(REFRESH_INTERVAL) => {}
+        }
+    }
+}
+
+fn open(settings: &DisplaySettings) -> rppal::i2c::Result<Box<dyn Display>> {
+    let mut i2c = I2c::new()?;
+
+    Ok(match settings.kind {
+        DisplayKind::Ssd1306 => {
+            i2c.set_slave_address(settings.address.unwrap_or(SSD1306_ADDRESS))?;
+            Box::new(Ssd1306::new(i2c)?)
+        }
+        DisplayKind::Hd44780 => {
+            i2c.set_slave_address(settings.address.unwrap_or(HD44780_ADDRESS))?;
+            Box::new(Hd44780::new(i2c, settings.columns, settings.rows)?)
+        }
+    })
+}
+
+/// Lines in order of importance: title, artist, album, progress bar, time and volume.
+/// Small displays keep the title and combine time and volume on one line
+fn now_playing_lines(
+    track: Option<&Track>,
+    position: Duration,
+    status: Status,
+    volume: f32,
+    columns: usize,
+    rows: usize,
+) -> Vec<String> {
+    let Some(track) = track else {
+        return vec![
+            fit("qobuz-player", columns),
+            fit("Nothing playing", columns),
+        ];
+    };
+
+    let duration = Duration::from_secs(track.duration_seconds.into());
+    let time = format!("{} / {}", format_time(position), format_time(duration));
+    let volume = format!("Vol {}%", (volume * 100.0).round());
+    let state = match status {
+        Status::Playing => "",
+        Status::Buffering => "Buffering",
+        Status::Paused => "Paused",
+    };
+
+    let mut lines = vec![fit(&track.title, columns)];
+
+    if rows >= 3 {
+        lines.push(fit(
+            track.artist_name.as_deref().unwrap_or_default(),
+            columns,
+        ));
+    }
+
+    if rows >= 6 {
+        lines.push(fit(
+            track.album_title.as_deref().unwrap_or_default(),
+            columns,
+        ));
+    }
+
+    if rows >= 5 {
+        let progress = match duration.is_zero() {
+            true => 0.0,
+            false => position.as_secs_f32() / duration.as_secs_f32(),
+        };
+        lines.push(progress_bar(progress, columns));
+    }
+
+    if rows >= 4 {
+        lines.push(spread(&time, state, columns));
+        lines.push(fit(&volume, columns));
+    } else {
+        lines.push(spread(&time, &volume, columns));
+    }
+
+    lines
+}
+
+/// Replaces characters the displays can not show and truncates to the display width
+fn fit(text: &str, columns: usize) -> String {
+    text.chars()
+        .map(|character| match character {
+            ' '..='~' => character,
+            _ => '?',
+        })
+        .take(columns)
+        .collect()
+}
+
+/// Places the texts at either end of the line
+fn spread(left: &str, right: &str, columns: usize) -> String {
+    let padding = columns.saturating_sub(left.len() + right.len()).max(1);
+    fit(&format!("{left}{}{right}", " ".repeat(padding)), columns)
+}
+
+fn progress_bar(progress: f32, columns: usize) -> String {
+    let width = columns.saturating_sub(2);
+    let filled = ((progress.clamp(0.0, 1.0) * width as f32).round() as usize).min(width);
+
+    format!("[{}{}]", "=".repeat(filled), " ".repeat(width - filled))
+}
+
+fn format_time(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
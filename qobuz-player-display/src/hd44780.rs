@@ -0,0 +1,107 @@
+use std::{thread::sleep, time::Duration};
+
+use rppal::i2c::{I2c, Result};
+
+use crate::display::Display;
+
+// Pins of the PCF8574 I2C backpack
+const REGISTER_SELECT: u8 = 0x01;
+const ENABLE: u8 = 0x04;
+const BACKLIGHT: u8 = 0x08;
+
+/// Character LCD driven in 4-bit mode through a PCF8574 I2C backpack
+pub(crate) struct Hd44780 {
+    i2c: I2c,
+    columns: usize,
+    rows: usize,
+    /// Lines currently shown, only changed lines are written
+    shown: Vec<String>,
+}
+
+impl Hd44780 {
+    pub(crate) fn new(i2c: I2c, columns: usize, rows: usize) -> Result<Self> {
+        let mut display = Self {
+            i2c,
+            columns,
+            rows,
+            shown: vec![],
+        };
+
+        sleep(Duration::from_millis(50));
+
+        // Reset into 8-bit mode before switching to 4-bit mode
+        for delay in [5, 5, 1] {
+            display.write_nibble(0x03, 0)?;
+            sleep(Duration::from_millis(delay));
+        }
+        display.write_nibble(0x02, 0)?;
+
+        let function_set = if rows > 1 { 0x28 } else { 0x20 };
+        for command in [function_set, 0x0C, 0x06, 0x01] {
+            display.command(command)?;
+        }
+        sleep(Duration::from_millis(2));
+
+        Ok(display)
+    }
+
+    fn write_nibble(&mut self, nibble: u8, mode: u8) -> Result<()> {
+        let value = (nibble << 4) | mode | BACKLIGHT;
+        self.i2c.write(&[value | ENABLE])?;
+        self.i2c.write(&[value])?;
+        Ok(())
+    }
+
+    fn write_byte(&mut self, byte: u8, mode: u8) -> Result<()> {
+        self.write_nibble(byte >> 4, mode)?;
+        self.write_nibble(byte & 0x0F, mode)
+    }
+
+    fn command(&mut self, command: u8) -> Result<()> {
+        self.write_byte(command, 0)
+    }
+
+    fn set_cursor(&mut self, row: usize) -> Result<()> {
+        let offset = match row {
+            0 => 0x00,
+            1 => 0x40,
+            2 => self.columns,
+            _ => 0x40 + self.columns,
+        };
+
+        self.command(0x80 | offset as u8)
+    }
+}
+
+impl Display for Hd44780 {
+    fn columns(&self) -> usize {
+        self.columns
+    }
+
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn draw(&mut self, lines: &[String]) -> Result<()> {
+        for row in 0..self.rows {
+            let line = lines.get(row).map(String::as_str).unwrap_or_default();
+            let line = format!("{line:<width$}", width = self.columns);
+
+            if self.shown.get(row) == Some(&line) {
+                continue;
+            }
+
+            self.set_cursor(row)?;
+            for character in line.bytes() {
+                self.write_byte(character, REGISTER_SELECT)?;
+            }
+
+            if self.shown.len() <= row {
+                self.shown.resize(row + 1, String::new());
+            }
+            self.shown[row] = line;
+        }
+
+        Ok(())
+    }
+}
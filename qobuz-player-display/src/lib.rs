@@ -0,0 +1,31 @@
+#[cfg(feature = "display")]
+mod display;
+#[cfg(feature = "display")]
+mod font;
+#[cfg(feature = "display")]
+mod hd44780;
+#[cfg(feature = "display")]
+mod ssd1306;
+
+#[cfg(feature = "display")]
+pub use display::init;
+
+/// Kind of display connected over I2C
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayKind {
+    /// 128x64 OLED
+    Ssd1306,
+    /// Character LCD with a PCF8574 I2C backpack
+    Hd44780,
+}
+
+#[derive(Debug, Clone)]
+pub struct DisplaySettings {
+    pub kind: DisplayKind,
+    /// I2C address, the usual one of the kind when not set
+    pub address: Option<u16>,
+    /// Characters per line of a hd44780 display
+    pub columns: usize,
+    /// Lines of a hd44780 display
+    pub rows: usize,
+}
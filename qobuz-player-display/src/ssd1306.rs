@@ -0,0 +1,79 @@
+use rppal::i2c::{I2c, Result};
+
+use crate::{display::Display, font};
+
+const WIDTH: usize = 128;
+const PAGES: usize = 8;
+const CHARACTER_WIDTH: usize = font::GLYPH_WIDTH + 1;
+
+const COMMAND: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+/// Initialisation sequence for a 128x64 panel with internal charge pump
+const INIT: [u8; 25] = [
+    0xAE, // Display off
+    0xD5, 0x80, // Clock divide ratio
+    0xA8, 0x3F, // Multiplex ratio: 64 lines
+    0xD3, 0x00, // Display offset
+    0x40, // Start line 0
+    0x8D, 0x14, // Enable charge pump
+    0x20, 0x00, // Horizontal addressing mode
+    0xA1, // Segment remap
+    0xC8, // Scan COM outputs in reverse
+    0xDA, 0x12, // COM pins configuration
+    0x81, 0xCF, // Contrast
+    0xD9, 0xF1, // Pre-charge period
+    0xDB, 0x40, // VCOMH deselect level
+    0xA4, // Show RAM content
+    0xA6, // Normal, not inverted
+    0xAF, // Display on
+];
+
+pub(crate) struct Ssd1306 {
+    i2c: I2c,
+}
+
+impl Ssd1306 {
+    pub(crate) fn new(mut i2c: I2c) -> Result<Self> {
+        for command in INIT {
+            i2c.write(&[COMMAND, command])?;
+        }
+
+        Ok(Self { i2c })
+    }
+}
+
+impl Display for Ssd1306 {
+    fn columns(&self) -> usize {
+        WIDTH / CHARACTER_WIDTH
+    }
+
+    fn rows(&self) -> usize {
+        PAGES
+    }
+
+    fn draw(&mut self, lines: &[String]) -> Result<()> {
+        let mut buffer = [0u8; WIDTH * PAGES];
+
+        for (page, line) in lines.iter().take(PAGES).enumerate() {
+            for (index, character) in line.chars().take(self.columns()).enumerate() {
+                let start = page * WIDTH + index * CHARACTER_WIDTH;
+                buffer[start..start + font::GLYPH_WIDTH].copy_from_slice(&font::glyph(character));
+            }
+        }
+
+        // Column and page address ranges cover the whole panel
+        for command in [0x21, 0, (WIDTH - 1) as u8, 0x22, 0, (PAGES - 1) as u8] {
+            self.i2c.write(&[COMMAND, command])?;
+        }
+
+        for chunk in buffer.chunks(16) {
+            let mut data = Vec::with_capacity(chunk.len() + 1);
+            data.push(DATA);
+            data.extend_from_slice(chunk);
+            self.i2c.write(&data)?;
+        }
+
+        Ok(())
+    }
+}
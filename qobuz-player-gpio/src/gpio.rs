@@ -1,16 +1,59 @@
-use qobuz_player_controls::{AppResult, StatusReceiver, error::Error};
-use rppal::gpio::Gpio;
+use std::time::Duration;
 
-const GPIO: u8 = 23;
+use qobuz_player_controls::{
+    AppResult, StatusReceiver, VolumeReceiver, controls::Controls, error::Error,
+};
+use rppal::gpio::{Gpio, InputPin, Trigger};
 
-pub async fn init(mut status_receiver: StatusReceiver) -> AppResult<()> {
-    let mut pin = Gpio::new()
-        .or(Err(Error::GpioUnavailable { pin: GPIO }))?
-        .get(GPIO)
-        .or(Err(Error::GpioUnavailable { pin: GPIO }))?
+use crate::GpioSettings;
+
+const BUTTON_DEBOUNCE: Duration = Duration::from_millis(50);
+const ROTARY_DEBOUNCE: Duration = Duration::from_millis(2);
+
+pub async fn init(
+    settings: GpioSettings,
+    mut status_receiver: StatusReceiver,
+    volume_receiver: VolumeReceiver,
+    controls: Controls,
+) -> AppResult<()> {
+    let status_pin = settings.status_pin;
+    let gpio = Gpio::new().or(Err(Error::GpioUnavailable { pin: status_pin }))?;
+
+    let mut pin = gpio
+        .get(status_pin)
+        .or(Err(Error::GpioUnavailable { pin: status_pin }))?
         .into_output();
     tracing::info!("Pin claimed");
 
+    // Interrupts are cleared when the input pins are dropped
+    let mut inputs = vec![];
+
+    if let Some(pin) = settings.play_pause_pin {
+        let controls = controls.clone();
+        inputs.push(button(&gpio, pin, move || controls.play_pause())?);
+    }
+
+    if let Some(pin) = settings.next_pin {
+        let controls = controls.clone();
+        inputs.push(button(&gpio, pin, move || controls.next())?);
+    }
+
+    if let Some(pin) = settings.previous_pin {
+        let controls = controls.clone();
+        inputs.push(button(&gpio, pin, move || controls.previous())?);
+    }
+
+    if let Some((pin_a, pin_b)) = settings.rotary_pins {
+        inputs.push(rotary_encoder(
+            &gpio,
+            pin_a,
+            pin_b,
+            settings.volume_step,
+            volume_receiver,
+            controls,
+        )?);
+    }
+
     loop {
         if status_receiver.changed().await.is_ok() {
             let status = status_receiver.borrow_and_update();
@@ -28,3 +71,53 @@ pub async fn init(mut status_receiver: StatusReceiver) -> AppResult<()> {
         }
     }
 }
+
+fn input(gpio: &Gpio, pin: u8) -> AppResult<InputPin> {
+    Ok(gpio
+        .get(pin)
+        .or(Err(Error::GpioUnavailable { pin }))?
+        .into_input_pullup())
+}
+
+fn button<F>(gpio: &Gpio, pin: u8, mut action: F) -> AppResult<InputPin>
+where
+    F: FnMut() + Send + 'static,
+{
+    let mut input = input(gpio, pin)?;
+    input
+        .set_async_interrupt(Trigger::FallingEdge, Some(BUTTON_DEBOUNCE), move |_| {
+            action()
+        })
+        .or(Err(Error::GpioUnavailable { pin }))?;
+
+    tracing::info!("Button on gpio {pin} claimed");
+    Ok(input)
+}
+
+/// The B output leads the A output when turning clockwise, so B is high on a falling edge of A
+fn rotary_encoder(
+    gpio: &Gpio,
+    pin_a: u8,
+    pin_b: u8,
+    volume_step: f32,
+    volume_receiver: VolumeReceiver,
+    controls: Controls,
+) -> AppResult<InputPin> {
+    let input_b = input(gpio, pin_b)?;
+    let mut input_a = input(gpio, pin_a)?;
+
+    input_a
+        .set_async_interrupt(Trigger::FallingEdge, Some(ROTARY_DEBOUNCE), move |_| {
+            let step = match input_b.is_high() {
+                true => volume_step,
+                false => -volume_step,
+            };
+
+            let volume = (*volume_receiver.borrow() + step).clamp(0.0, 1.0);
+            controls.set_volume(volume);
+        })
+        .or(Err(Error::GpioUnavailable { pin: pin_a }))?;
+
+    tracing::info!("Rotary encoder on gpio {pin_a} and {pin_b} claimed");
+    Ok(input_a)
+}
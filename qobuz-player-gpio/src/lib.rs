@@ -3,3 +3,17 @@ mod gpio;
 
 #[cfg(feature = "gpio")]
 pub use gpio::init;
+
+/// Pins of the buttons, rotary encoder and status led
+#[derive(Debug, Clone)]
+pub struct GpioSettings {
+    /// Set high when playing
+    pub status_pin: u8,
+    pub play_pause_pin: Option<u8>,
+    pub next_pin: Option<u8>,
+    pub previous_pin: Option<u8>,
+    /// A (clock) and B (data) outputs of a rotary encoder controlling the volume
+    pub rotary_pins: Option<(u8, u8)>,
+    /// Volume change per rotary encoder step
+    pub volume_step: f32,
+}
@@ -1,10 +1,13 @@
 use qobuz_player_cli::{
-    ConnectArgs, SharedArgs, create_player, default_audio_quality, spawn_clean_up,
+    ConnectArgs, SharedArgs, StartupSummary, create_player, default_audio_quality,
+    default_output_device, fixture_client, spawn_alarms, spawn_clean_up, spawn_favorites_sync,
+    spawn_group, spawn_hooks, spawn_network_monitor, spawn_release_notifications, spawn_webhooks,
 };
+use qobuz_player_connect::ConnectionStatus;
 #[cfg(any(windows, target_os = "linux", target_os = "macos"))]
 use qobuz_player_controls::StatusReceiver;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
 
 use clap::Parser;
 use qobuz_player_controls::{
@@ -18,6 +21,11 @@ use qobuz_player_controls::{
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Arguments {
+    /// Handle the play/pause, next and previous media keys system wide, where neither mpris nor
+    /// the system media controls are available
+    #[clap(long)]
+    global_hotkeys: bool,
+
     #[clap(flatten)]
     shared: SharedArgs,
 
@@ -38,9 +46,12 @@ async fn main() {
 pub async fn run() -> AppResult<()> {
     tracing_subscriber::fmt().compact().init();
 
-    let args = Arguments::parse();
+    let mut args = Arguments::parse();
 
     let database = Arc::new(Database::new().await?);
+    database.set_profile(&args.shared.profile).await?;
+    args.shared.output_device_id =
+        default_output_device(&database, args.shared.output_device_id.take()).await?;
 
     let (exit_sender, exit_receiver) = broadcast::channel(5);
 
@@ -48,28 +59,65 @@ pub async fn run() -> AppResult<()> {
     let credentials = database.get_credentials().await?;
 
     let app_id = get_app_id().await?;
-    let client = Arc::new(Client::new(credentials, max_audio_quality));
+    let client = match fixture_client(args.shared.fixtures())? {
+        Some(client) => client,
+        None => Client::new(credentials, max_audio_quality),
+    };
+    let client = Arc::new(client);
+    spawn_favorites_sync(
+        database.clone(),
+        client.clone(),
+        args.shared.favorites_refresh_minutes,
+    )
+    .await;
+    spawn_release_notifications(database.clone(), client.clone(), &args.shared);
 
     let broadcast = Arc::new(NotificationBroadcast::new());
 
+    let mut summary = StartupSummary::new(&args.shared, max_audio_quality);
+    #[cfg(target_os = "linux")]
+    {
+        summary.mpris = qobuz_player_mpris::session_bus_available();
+    }
+    summary.global_hotkeys = args.global_hotkeys;
+    summary.connect_name = args
+        .connect
+        .connect
+        .then(|| args.connect.name_args.connect_name.clone());
+    println!("{summary}");
+
+    let audio_output = args.shared.audio_output(false)?;
+    let hooks = args.shared.hooks();
+    let group_role = args.shared.group_role();
+    #[cfg(target_os = "linux")]
+    let artwork_cache = args.shared.artwork();
+
     let mut player = create_player(
-        args.shared.audio_cache,
+        &args.shared,
+        None,
+        audio_output,
         database.clone(),
         client.clone(),
         broadcast.clone(),
-        None,
-        None,
-        args.shared.output_device_id,
     )
     .await?;
+    spawn_network_monitor(client.clone(), player.controls());
+    spawn_alarms(
+        database.clone(),
+        client.clone(),
+        &player,
+        broadcast.clone(),
+        args.shared.alarm_ramp_seconds,
+    );
 
     #[cfg(target_os = "linux")]
-    {
+    if summary.mpris {
         let position_receiver = player.position();
         let tracklist_receiver = player.tracklist();
         let volume_receiver = player.volume();
         let status_receiver = player.status();
         let controls = player.controls();
+        let broadcast = broadcast.clone();
         let exit_sender = exit_sender.clone();
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_mpris::init(
@@ -78,6 +126,8 @@ pub async fn run() -> AppResult<()> {
                 volume_receiver,
                 status_receiver,
                 controls,
+                broadcast,
+                artwork_cache,
                 exit_sender,
             )
             .await
@@ -87,6 +137,37 @@ pub async fn run() -> AppResult<()> {
         });
     }
 
+    #[cfg(any(windows, target_os = "macos"))]
+    let media_keys_handled = match qobuz_player_media_controls::init(
+        player.position(),
+        player.tracklist(),
+        player.status(),
+        player.controls(),
+        exit_sender.clone(),
+    ) {
+        Ok(media_controls) => {
+            let broadcast = broadcast.clone();
+            tokio::spawn(async move {
+                if let Err(e) = media_controls.await {
+                    broadcast.report(&e);
+                }
+            });
+            true
+        }
+        Err(e) => {
+            broadcast.report(&e);
+            false
+        }
+    };
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let media_keys_handled = summary.mpris;
+
+    // The system media controls already get the media keys, registering them twice would
+    // toggle twice
+    if args.global_hotkeys && !media_keys_handled {
+        qobuz_player_hotkeys::init(player.controls())?;
+    }
+
     #[cfg(any(windows, target_os = "linux", target_os = "macos"))]
     {
         let status_receiver = player.status();
@@ -99,19 +180,25 @@ pub async fn run() -> AppResult<()> {
         let position_receiver = player.position();
         let tracklist_receiver = player.tracklist();
         let volume_receiver = player.volume();
+        let muted_receiver = player.muted();
         let status_receiver = player.status();
         let controls = player.controls();
         let app_id = app_id.clone();
+        let broadcast = broadcast.clone();
+        let (connection_status, _) = watch::channel(ConnectionStatus::default());
 
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_connect::init(
                 &app_id,
                 args.connect.name_args.connect_name,
                 controls,
+                broadcast,
+                connection_status,
                 position_receiver,
                 tracklist_receiver,
                 status_receiver,
                 volume_receiver,
+                muted_receiver,
                 max_audio_quality,
             )
             .await
@@ -141,6 +228,14 @@ pub async fn run() -> AppResult<()> {
         };
     });
 
+    spawn_webhooks(args.shared.webhook_urls.clone(), &player);
+
+    spawn_hooks(hooks, &player);
+
+    if let Some(group_role) = group_role {
+        spawn_group(group_role, &player);
+    }
+
     spawn_clean_up(database, args.shared.audio_cache_time_to_live);
     player.player_loop(exit_receiver).await?;
 
@@ -0,0 +1,138 @@
+use global_hotkey::{
+    GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState,
+    hotkey::{Code, HotKey},
+};
+use qobuz_player_controls::{AppResult, controls::Controls, error::Error};
+
+/// Media keys handled system wide
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MediaKey {
+    PlayPause,
+    Next,
+    Previous,
+    Stop,
+}
+
+impl MediaKey {
+    const ALL: [MediaKey; 4] = [
+        MediaKey::PlayPause,
+        MediaKey::Next,
+        MediaKey::Previous,
+        MediaKey::Stop,
+    ];
+
+    fn hotkey(self) -> HotKey {
+        let code = match self {
+            // X11 has no play/pause key, the play key toggles
+            MediaKey::PlayPause if cfg!(target_os = "linux") => Code::MediaPlay,
+            MediaKey::PlayPause => Code::MediaPlayPause,
+            MediaKey::Next => Code::MediaTrackNext,
+            MediaKey::Previous => Code::MediaTrackPrevious,
+            MediaKey::Stop => Code::MediaStop,
+        };
+        HotKey::new(None, code)
+    }
+
+    fn from_id(id: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.hotkey().id() == id)
+    }
+
+    fn send(self, controls: &Controls) {
+        match self {
+            MediaKey::PlayPause => controls.play_pause(),
+            MediaKey::Next => controls.next(),
+            MediaKey::Previous => controls.previous(),
+            MediaKey::Stop => controls.pause(),
+        }
+    }
+}
+
+/// Routes the media keys into `controls`, for when neither mpris nor the system media
+/// controls take them. macOS delivers the keys through the main run loop
+pub fn init(controls: Controls) -> AppResult<()> {
+    GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+        if event.state() == HotKeyState::Pressed
+            && let Some(key) = MediaKey::from_id(event.id())
+        {
+            key.send(&controls);
+        }
+    }));
+
+    let (registered_tx, registered_rx) = std::sync::mpsc::channel();
+
+    // On Windows the manager must live on the thread pumping its messages
+    std::thread::spawn(move || {
+        let manager = match GlobalHotKeyManager::new() {
+            Ok(manager) => manager,
+            Err(err) => {
+                _ = registered_tx.send(Err(err.to_string()));
+                return;
+            }
+        };
+
+        let mut registered = vec![];
+        for key in MediaKey::ALL {
+            match manager.register(key.hotkey()) {
+                Ok(()) => registered.push(key.hotkey()),
+                Err(err) => tracing::warn!("Unable to register media key {key:?}: {err}"),
+            }
+        }
+
+        if registered.is_empty() {
+            _ = registered_tx.send(Err(
+                "no media key could be registered, another application may own them".into(),
+            ));
+            return;
+        }
+        _ = registered_tx.send(Ok(()));
+
+        pump_messages();
+        _ = manager.unregister_all(&registered);
+    });
+
+    match registered_rx.recv() {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(message)) => Err(Error::HotkeyInitError { message }),
+        Err(_) => Err(Error::HotkeyInitError {
+            message: "the hotkey thread stopped".into(),
+        }),
+    }
+}
+
+#[cfg(windows)]
+fn pump_messages() {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, GetMessageW, MSG, TranslateMessage,
+    };
+
+    let mut message = MSG::default();
+    // SAFETY: `message` outlives the calls and is only read after `GetMessageW` filled it
+    while unsafe { GetMessageW(&mut message, std::ptr::null_mut(), 0, 0) } > 0 {
+        unsafe {
+            TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+}
+
+/// X11 listens on a thread of its own and macOS on the main run loop, this one only keeps
+/// the manager alive
+#[cfg(not(windows))]
+fn pump_messages() {
+    loop {
+        std::thread::park();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MediaKey;
+
+    #[test]
+    fn hotkey_ids_map_back_to_their_key() {
+        for key in MediaKey::ALL {
+            assert_eq!(MediaKey::from_id(key.hotkey().id()), Some(key));
+        }
+        assert_eq!(MediaKey::from_id(0), None);
+    }
+}
@@ -0,0 +1,235 @@
+use std::time::{Duration, Instant};
+
+use qobuz_player_controls::{
+    AppResult, ExitSender, PositionReceiver, Status, StatusReceiver, TracklistReceiver,
+    controls::Controls, error::Error, models::Track,
+};
+
+#[cfg(target_os = "macos")]
+mod now_playing;
+#[cfg(windows)]
+mod smtc;
+
+#[cfg(target_os = "macos")]
+use now_playing::MediaSession;
+#[cfg(windows)]
+use smtc::MediaSession;
+
+/// What the operating system shows for the playing track
+#[derive(Debug, Clone, Default, PartialEq)]
+struct NowPlaying {
+    title: String,
+    artist: Option<String>,
+    album: Option<String>,
+    cover_url: Option<String>,
+    duration: Duration,
+    status: Status,
+    position: Duration,
+}
+
+/// How far the position may drift from the clock before it counts as a seek
+const SEEK_TOLERANCE: Duration = Duration::from_secs(2);
+
+impl NowPlaying {
+    fn new(track: Option<&Track>, status: Status, position: Duration) -> Self {
+        let now_playing = track
+            .map(|track| NowPlaying {
+                title: track.title.clone(),
+                artist: track.artist_name.clone(),
+                album: track.album_title.clone(),
+                cover_url: track.image.clone(),
+                duration: Duration::from_secs(track.duration_seconds.into()),
+                ..Default::default()
+            })
+            .unwrap_or_default();
+
+        NowPlaying {
+            status,
+            position,
+            ..now_playing
+        }
+    }
+
+    /// Whether `next`, seen `elapsed` after this was shown, has to be shown
+    fn outdated_by(&self, next: &NowPlaying, elapsed: Duration) -> bool {
+        let moved_on = NowPlaying {
+            position: self.position,
+            ..next.clone()
+        };
+        moved_on != *self || self.seeked(elapsed, next.position)
+    }
+
+    /// The operating system moves the position on by itself while playing, so only a
+    /// position off from that is worth an update
+    fn seeked(&self, elapsed: Duration, position: Duration) -> bool {
+        let expected = match self.status {
+            Status::Playing => self.position + elapsed,
+            Status::Paused | Status::Buffering => self.position,
+        };
+        expected.abs_diff(position) > SEEK_TOLERANCE
+    }
+}
+
+/// Shows the playing track in the Windows System Media Transport Controls or the macOS
+/// Now Playing center, and routes their buttons and the media keys into `controls`.
+/// Fails when the session can not be created, otherwise the returned future keeps it up to date
+pub fn init(
+    mut position_receiver: PositionReceiver,
+    mut tracklist_receiver: TracklistReceiver,
+    mut status_receiver: StatusReceiver,
+    controls: Controls,
+    exit_sender: ExitSender,
+) -> AppResult<impl Future<Output = AppResult<()>>> {
+    let mut exit_receiver = exit_sender.subscribe();
+    let session = MediaSession::new(controls)?;
+
+    Ok(async move {
+        let mut published: Option<(NowPlaying, Instant)> = None;
+
+        loop {
+            let tracklist = tracklist_receiver.borrow_and_update().clone();
+            let now_playing = NowPlaying::new(
+                tracklist.current_track(),
+                *status_receiver.borrow_and_update(),
+                *position_receiver.borrow_and_update(),
+            );
+
+            if published
+                .as_ref()
+                .is_none_or(|(published, at)| published.outdated_by(&now_playing, at.elapsed()))
+            {
+                session.update(&now_playing)?;
+                published = Some((now_playing, Instant::now()));
+            }
+
+            tokio::select! {
+                Ok(_) = tracklist_receiver.changed() => {},
+                Ok(_) = status_receiver.changed() => {},
+                Ok(_) = position_receiver.changed() => {},
+                Ok(exit) = exit_receiver.recv() => {
+                    if exit {
+                        break Ok(());
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Runs `app` on a thread of its own while the main thread serves the run loop, which is
+/// where macOS delivers the Now Playing commands and media keys. Elsewhere `app` runs on
+/// the calling thread
+pub fn run_with_main_loop(app: impl FnOnce() + Send + 'static) {
+    #[cfg(target_os = "macos")]
+    {
+        std::thread::spawn(move || {
+            app();
+            std::process::exit(0);
+        });
+        now_playing::run_main_loop();
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    app();
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+struct MediaSession;
+
+#[cfg(not(any(windows, target_os = "macos")))]
+impl MediaSession {
+    fn new(_controls: Controls) -> AppResult<Self> {
+        Err(Error::MediaControlsError {
+            message: "not available on this platform, use mpris".into(),
+        })
+    }
+
+    fn update(&self, _now_playing: &NowPlaying) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use qobuz_player_controls::{Status, models::Track};
+
+    use super::NowPlaying;
+
+    fn playing(position: u64) -> NowPlaying {
+        NowPlaying {
+            status: Status::Playing,
+            position: Duration::from_secs(position),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn playback_moving_on_is_no_seek() {
+        let elapsed = Duration::from_secs(10);
+
+        assert!(!playing(30).seeked(elapsed, Duration::from_secs(40)));
+        assert!(!playing(30).seeked(elapsed, Duration::from_millis(41_500)));
+    }
+
+    #[test]
+    fn jumps_are_seeks() {
+        let elapsed = Duration::from_secs(10);
+
+        assert!(playing(30).seeked(elapsed, Duration::from_secs(90)));
+        assert!(playing(30).seeked(elapsed, Duration::from_secs(5)));
+
+        let paused = NowPlaying {
+            status: Status::Paused,
+            ..playing(30)
+        };
+        assert!(!paused.seeked(elapsed, Duration::from_secs(30)));
+        assert!(paused.seeked(elapsed, Duration::from_secs(40)));
+    }
+
+    #[test]
+    fn track_and_status_changes_are_shown() {
+        let elapsed = Duration::from_secs(1);
+
+        assert!(!playing(30).outdated_by(&playing(31), elapsed));
+        assert!(playing(30).outdated_by(&playing(90), elapsed));
+
+        let paused = NowPlaying {
+            status: Status::Paused,
+            ..playing(31)
+        };
+        assert!(playing(30).outdated_by(&paused, elapsed));
+
+        let next_track = NowPlaying {
+            title: "Next".into(),
+            ..playing(31)
+        };
+        assert!(playing(30).outdated_by(&next_track, elapsed));
+    }
+
+    #[test]
+    fn shows_the_current_track() {
+        let track = Track {
+            title: "Blue in Green".into(),
+            artist_name: Some("Miles Davis".into()),
+            duration_seconds: 337,
+            ..Default::default()
+        };
+
+        let now_playing = NowPlaying::new(Some(&track), Status::Playing, Duration::from_secs(3));
+        assert_eq!(now_playing.title, "Blue in Green");
+        assert_eq!(now_playing.artist.as_deref(), Some("Miles Davis"));
+        assert_eq!(now_playing.duration, Duration::from_secs(337));
+        assert_eq!(now_playing.position, Duration::from_secs(3));
+
+        let nothing = NowPlaying::new(None, Status::Paused, Duration::ZERO);
+        assert_eq!(
+            nothing,
+            NowPlaying {
+                status: Status::Paused,
+                ..Default::default()
+            }
+        );
+    }
+}
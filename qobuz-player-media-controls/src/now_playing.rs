@@ -0,0 +1,114 @@
+use block2::RcBlock;
+use objc2::{class, msg_send, rc::Retained, runtime::AnyObject};
+use objc2_foundation::{NSMutableDictionary, NSNumber, NSObject, NSString};
+use qobuz_player_controls::{AppResult, Status, controls::Controls};
+
+use crate::NowPlaying;
+
+const HANDLER_STATUS_SUCCESS: isize = 0;
+const PLAYBACK_STATE_PLAYING: usize = 1;
+const PLAYBACK_STATE_PAUSED: usize = 2;
+
+#[link(name = "MediaPlayer", kind = "framework")]
+unsafe extern "C" {
+    static MPMediaItemPropertyTitle: &'static NSString;
+    static MPMediaItemPropertyArtist: &'static NSString;
+    static MPMediaItemPropertyAlbumTitle: &'static NSString;
+    static MPMediaItemPropertyPlaybackDuration: &'static NSString;
+    static MPNowPlayingInfoPropertyElapsedPlaybackTime: &'static NSString;
+    static MPNowPlayingInfoPropertyPlaybackRate: &'static NSString;
+}
+
+/// Remote commands are delivered on the main run loop, so buttons and media keys only reach
+/// front-ends running one
+pub(crate) struct MediaSession;
+
+impl MediaSession {
+    pub(crate) fn new(controls: Controls) -> AppResult<Self> {
+        // SAFETY: the shared command center lives as long as the process
+        unsafe {
+            let center: Retained<AnyObject> =
+                msg_send![class!(MPRemoteCommandCenter), sharedCommandCenter];
+
+            let command: Retained<AnyObject> = msg_send![&center, playCommand];
+            add_handler(&command, controls.clone(), Controls::play);
+            let command: Retained<AnyObject> = msg_send![&center, pauseCommand];
+            add_handler(&command, controls.clone(), Controls::pause);
+            let command: Retained<AnyObject> = msg_send![&center, togglePlayPauseCommand];
+            add_handler(&command, controls.clone(), Controls::play_pause);
+            let command: Retained<AnyObject> = msg_send![&center, nextTrackCommand];
+            add_handler(&command, controls.clone(), Controls::next);
+            let command: Retained<AnyObject> = msg_send![&center, previousTrackCommand];
+            add_handler(&command, controls, Controls::previous);
+        }
+
+        Ok(Self)
+    }
+
+    pub(crate) fn update(&self, now_playing: &NowPlaying) -> AppResult<()> {
+        let info = NSMutableDictionary::<NSString, NSObject>::new();
+        let playing = now_playing.status == Status::Playing;
+
+        // SAFETY: the keys are constants of the MediaPlayer framework
+        unsafe {
+            info.insert(
+                MPMediaItemPropertyTitle,
+                &NSString::from_str(&now_playing.title),
+            );
+            if let Some(artist) = &now_playing.artist {
+                info.insert(MPMediaItemPropertyArtist, &NSString::from_str(artist));
+            }
+            if let Some(album) = &now_playing.album {
+                info.insert(MPMediaItemPropertyAlbumTitle, &NSString::from_str(album));
+            }
+            info.insert(
+                MPMediaItemPropertyPlaybackDuration,
+                &NSNumber::new_f64(now_playing.duration.as_secs_f64()),
+            );
+            info.insert(
+                MPNowPlayingInfoPropertyElapsedPlaybackTime,
+                &NSNumber::new_f64(now_playing.position.as_secs_f64()),
+            );
+            info.insert(
+                MPNowPlayingInfoPropertyPlaybackRate,
+                &NSNumber::new_f64(if playing { 1.0 } else { 0.0 }),
+            );
+
+            let center: Retained<AnyObject> =
+                msg_send![class!(MPNowPlayingInfoCenter), defaultCenter];
+            let _: () = msg_send![&center, setNowPlayingInfo: &*info];
+            let state = match playing {
+                true => PLAYBACK_STATE_PLAYING,
+                false => PLAYBACK_STATE_PAUSED,
+            };
+            let _: () = msg_send![&center, setPlaybackState: state];
+        }
+
+        Ok(())
+    }
+}
+
+/// # Safety
+///
+/// `command` must be an `MPRemoteCommand`
+unsafe fn add_handler(command: &AnyObject, controls: Controls, action: fn(&Controls)) {
+    let handler = RcBlock::new(move |_event: *mut AnyObject| -> isize {
+        action(&controls);
+        HANDLER_STATUS_SUCCESS
+    });
+    let _: Retained<AnyObject> = unsafe { msg_send![command, addTargetWithHandler: &*handler] };
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+unsafe extern "C" {
+    fn CFRunLoopRun();
+}
+
+/// Serves the run loop of the main thread, the commands of the command center arrive on it
+pub(crate) fn run_main_loop() -> ! {
+    loop {
+        // SAFETY: only called from the main thread, returns when the run loop has no sources
+        unsafe { CFRunLoopRun() };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,107 @@
+use qobuz_player_controls::{AppResult, Status, controls::Controls, error::Error};
+use windows::{
+    Foundation::{TimeSpan, TypedEventHandler, Uri},
+    Media::{
+        MediaPlaybackStatus, MediaPlaybackType, Playback::MediaPlayer,
+        SystemMediaTransportControls, SystemMediaTransportControlsButton,
+        SystemMediaTransportControlsButtonPressedEventArgs,
+        SystemMediaTransportControlsTimelineProperties,
+    },
+    Storage::Streams::RandomAccessStreamReference,
+    core::HSTRING,
+};
+
+use crate::NowPlaying;
+
+/// Desktop apps get the transport controls through a media player with its own
+/// command handling turned off
+pub(crate) struct MediaSession {
+    _player: MediaPlayer,
+    transport_controls: SystemMediaTransportControls,
+}
+
+impl MediaSession {
+    pub(crate) fn new(controls: Controls) -> AppResult<Self> {
+        Self::create(controls).map_err(media_controls_error)
+    }
+
+    fn create(controls: Controls) -> windows::core::Result<Self> {
+        let player = MediaPlayer::new()?;
+        player.CommandManager()?.SetIsEnabled(false)?;
+
+        let transport_controls = player.SystemMediaTransportControls()?;
+        transport_controls.SetIsEnabled(true)?;
+        transport_controls.SetIsPlayEnabled(true)?;
+        transport_controls.SetIsPauseEnabled(true)?;
+        transport_controls.SetIsNextEnabled(true)?;
+        transport_controls.SetIsPreviousEnabled(true)?;
+
+        let handler = TypedEventHandler::<
+            SystemMediaTransportControls,
+            SystemMediaTransportControlsButtonPressedEventArgs,
+        >::new(move |_, args| {
+            match args.ok()?.Button()? {
+                SystemMediaTransportControlsButton::Play => controls.play(),
+                SystemMediaTransportControlsButton::Pause
+                | SystemMediaTransportControlsButton::Stop => controls.pause(),
+                SystemMediaTransportControlsButton::Next => controls.next(),
+                SystemMediaTransportControlsButton::Previous => controls.previous(),
+                _ => {}
+            }
+            Ok(())
+        });
+        transport_controls.ButtonPressed(&handler)?;
+
+        Ok(Self {
+            _player: player,
+            transport_controls,
+        })
+    }
+
+    pub(crate) fn update(&self, now_playing: &NowPlaying) -> AppResult<()> {
+        self.set(now_playing).map_err(media_controls_error)
+    }
+
+    fn set(&self, now_playing: &NowPlaying) -> windows::core::Result<()> {
+        let status = match now_playing.status {
+            Status::Playing => MediaPlaybackStatus::Playing,
+            Status::Paused => MediaPlaybackStatus::Paused,
+            Status::Buffering => MediaPlaybackStatus::Changing,
+        };
+        self.transport_controls.SetPlaybackStatus(status)?;
+
+        let timeline = SystemMediaTransportControlsTimelineProperties::new()?;
+        timeline.SetStartTime(TimeSpan::default())?;
+        timeline.SetMinSeekTime(TimeSpan::default())?;
+        timeline.SetEndTime(now_playing.duration.into())?;
+        timeline.SetMaxSeekTime(now_playing.duration.into())?;
+        timeline.SetPosition(now_playing.position.into())?;
+        self.transport_controls
+            .UpdateTimelineProperties(&timeline)?;
+
+        let updater = self.transport_controls.DisplayUpdater()?;
+        updater.SetType(MediaPlaybackType::Music)?;
+
+        let properties = updater.MusicProperties()?;
+        properties.SetTitle(&HSTRING::from(now_playing.title.as_str()))?;
+        properties.SetArtist(&HSTRING::from(
+            now_playing.artist.as_deref().unwrap_or_default(),
+        ))?;
+        properties.SetAlbumTitle(&HSTRING::from(
+            now_playing.album.as_deref().unwrap_or_default(),
+        ))?;
+
+        if let Some(cover_url) = &now_playing.cover_url {
+            let uri = Uri::CreateUri(&HSTRING::from(cover_url.as_str()))?;
+            updater.SetThumbnail(&RandomAccessStreamReference::CreateFromUri(&uri)?)?;
+        }
+
+        updater.Update()
+    }
+}
+
+fn media_controls_error(error: windows::core::Error) -> Error {
+    Error::MediaControlsError {
+        message: error.message(),
+    }
+}
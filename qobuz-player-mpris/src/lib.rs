@@ -1,5 +1,3 @@
-use std::time::Duration;
-
 use mpris_server::{
     LoopStatus, Metadata, PlaybackRate, PlaybackStatus, PlayerInterface, Property, RootInterface,
     Server, Time, TrackId, Volume,
@@ -7,8 +5,29 @@ use mpris_server::{
 };
 use qobuz_player_controls::{
     AppResult, ExitSender, PositionReceiver, Status, StatusReceiver, TracklistReceiver,
-    VolumeReceiver, controls::Controls, error::Error, models::Track,
+    VolumeReceiver,
+    artwork_cache::ArtworkCache,
+    controls::Controls,
+    error::Error,
+    models::Track,
+    notification::{Notification, NotificationBroadcast, TrackFormat},
 };
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+
+/// Size of the cover handed to the desktop
+const ART_SIZE: u32 = 600;
+
+/// Check for a D-Bus session bus before starting the mpris server
+pub fn session_bus_available() -> bool {
+    if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some() {
+        return true;
+    }
+
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| std::path::Path::new(&dir).join("bus").exists())
+        .unwrap_or(false)
+}
 
 struct MprisPlayer {
     controls: Controls,
@@ -16,6 +35,9 @@ struct MprisPlayer {
     tracklist_receiver: TracklistReceiver,
     volume_receiver: VolumeReceiver,
     status_receiver: StatusReceiver,
+    stream_format: watch::Receiver<Option<TrackFormat>>,
+    /// Local cover of a track, by track id
+    art_url: watch::Receiver<Option<(u32, String)>>,
     exit_sender: ExitSender,
 }
 
@@ -93,19 +115,7 @@ impl PlayerInterface for MprisPlayer {
     }
 
     async fn seek(&self, offset: Time) -> fdo::Result<()> {
-        let current_position = *self.position_receiver.borrow();
-        let offset_millis = offset.as_millis();
-
-        let new_position = match offset_millis < 0 {
-            true => {
-                current_position - Duration::from_millis(offset_millis.abs().try_into().unwrap())
-            }
-            false => {
-                current_position + Duration::from_millis(offset_millis.abs().try_into().unwrap())
-            }
-        };
-
-        self.controls.seek(new_position);
+        self.controls.jump_by(offset.as_micros());
         Ok(())
     }
 
@@ -155,7 +165,9 @@ impl PlayerInterface for MprisPlayer {
         let current_track = tracklist.current_track();
 
         if let Some(current_track) = current_track {
-            return Ok(track_to_metadata(current_track));
+            let stream_format = *self.stream_format.borrow();
+            let art_url = art_url(&self.art_url.borrow(), current_track);
+            return Ok(track_to_metadata(current_track, stream_format, art_url));
         };
 
         Ok(Metadata::new())
@@ -210,15 +222,23 @@ impl PlayerInterface for MprisPlayer {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn init(
     position_receiver: PositionReceiver,
     mut tracklist_receiver: TracklistReceiver,
     mut volume_receiver: VolumeReceiver,
     mut status_receiver: StatusReceiver,
     controls: Controls,
+    broadcast: Arc<NotificationBroadcast>,
+    artwork_cache: Arc<ArtworkCache>,
     exit_sender: ExitSender,
 ) -> AppResult<()> {
     let mut exit_receiver = exit_sender.subscribe();
+    let mut notifications = broadcast.subscribe();
+    let (stream_format, stream_format_receiver) = watch::channel(None);
+    let (local_art, local_art_receiver) = watch::channel(None);
+    let (art_sender, mut art_receiver) = mpsc::unbounded_channel::<(u32, String)>();
+    let mut requested_art = None;
 
     let Ok(server) = Server::new(
         "qobuz-player",
@@ -228,6 +248,8 @@ pub async fn init(
             tracklist_receiver: tracklist_receiver.clone(),
             volume_receiver: volume_receiver.clone(),
             status_receiver: status_receiver.clone(),
+            stream_format: stream_format_receiver,
+            art_url: local_art_receiver,
             exit_sender,
         },
     )
@@ -243,7 +265,24 @@ pub async fn init(
                 let current_track = tracklist.current_track();
 
                 if let Some(current_track) = current_track {
-                    let metadata = track_to_metadata(current_track);
+                    // The remote cover is shown until the local one is downloaded
+                    if requested_art != Some(current_track.id)
+                        && let Some(image) = current_track.image.clone()
+                    {
+                        requested_art = Some(current_track.id);
+                        let artwork_cache = artwork_cache.clone();
+                        let art_sender = art_sender.clone();
+                        let track_id = current_track.id;
+                        tokio::spawn(async move {
+                            match artwork_cache.file_url(&image, ART_SIZE).await {
+                                Ok(url) => _ = art_sender.send((track_id, url)),
+                                Err(err) => tracing::warn!("Unable to cache cover for mpris: {err}"),
+                            }
+                        });
+                    }
+
+                    let art_url = art_url(&local_art.borrow(), current_track);
+                    let metadata = track_to_metadata(current_track, *stream_format.borrow(), art_url);
 
                     let current_position = tracklist.current_position();
                     let total_tracks = tracklist.total();
@@ -262,6 +301,23 @@ pub async fn init(
                         };
                 }
             },
+            Some(art) = art_receiver.recv() => {
+                let track_id = art.0;
+                local_art.send_replace(Some(art));
+
+                let tracklist = tracklist_receiver.borrow().clone();
+                if let Some(current_track) = tracklist.current_track()
+                    && current_track.id == track_id
+                {
+                    let art_url = art_url(&local_art.borrow(), current_track);
+                    let metadata = track_to_metadata(current_track, *stream_format.borrow(), art_url);
+                    let Ok(_) = server
+                        .properties_changed([Property::Metadata(metadata)])
+                        .await else {
+                            return Err(Error::MprisPropertyError { property: "Metadata".into() });
+                        };
+                }
+            },
             Ok(_) = volume_receiver.changed() => {
                 let volume = *volume_receiver.borrow_and_update();
                 let Ok(_) = server
@@ -293,6 +349,25 @@ pub async fn init(
                         return Err(Error::MprisPropertyError { property: "CanPlay, CanPause, PlaybackStatus".into() });
                     };
             },
+            Ok(notification) = notifications.recv() => {
+                let Notification::StreamFormat(format) = notification else {
+                    continue;
+                };
+                stream_format.send_replace(Some(format));
+
+                let tracklist = tracklist_receiver.borrow().clone();
+                if let Some(current_track) = tracklist.current_track()
+                    && current_track.id == format.track_id
+                {
+                    let art_url = art_url(&local_art.borrow(), current_track);
+                    let metadata = track_to_metadata(current_track, Some(format), art_url);
+                    let Ok(_) = server
+                        .properties_changed([Property::Metadata(metadata)])
+                        .await else {
+                            return Err(Error::MprisPropertyError { property: "Metadata".into() });
+                        };
+                }
+            },
             Ok(exit) = exit_receiver.recv() => {
                 if exit {
                     break Ok(());
@@ -302,13 +377,26 @@ pub async fn init(
     }
 }
 
-fn track_to_metadata(track: &Track) -> Metadata {
+/// Cached cover of `track`, or its remote url while it is not cached
+fn art_url(local_art: &Option<(u32, String)>, track: &Track) -> Option<String> {
+    match local_art {
+        Some((track_id, url)) if *track_id == track.id => Some(url.clone()),
+        _ => track.image.clone(),
+    }
+}
+
+/// The stream format is only added when it belongs to `track`
+fn track_to_metadata(
+    track: &Track,
+    stream_format: Option<TrackFormat>,
+    art_url: Option<String>,
+) -> Metadata {
     let mut metadata = Metadata::new();
     let duration = mpris_server::Time::from_secs(track.duration_seconds as i64);
     metadata.set_length(Some(duration));
 
     metadata.set_album(track.album_title.clone());
-    metadata.set_art_url(track.image.clone());
+    metadata.set_art_url(art_url);
 
     // artist
     let artist_name = track.artist_name.clone();
@@ -320,5 +408,108 @@ fn track_to_metadata(track: &Track) -> Metadata {
     metadata.set_title(Some(track.title.clone()));
     metadata.set_track_number(Some(track.number as i32));
 
+    if let Some(TrackFormat { format, .. }) =
+        stream_format.filter(|stream_format| stream_format.track_id == track.id)
+    {
+        metadata.set("qobuz-player:format", Some(format.to_string()));
+        metadata.set("qobuz-player:sampleRate", Some(format.sample_rate));
+        metadata.set("qobuz-player:bitDepth", format.bit_depth);
+        metadata.set("qobuz-player:channels", Some(format.channels));
+    }
+
     metadata
 }
+
+#[cfg(test)]
+mod tests {
+    use mpris_server::{PlayerInterface, Time};
+    use qobuz_player_controls::{
+        controls::{ControlCommand, Controls},
+        models::Track,
+        notification::TrackFormat,
+        transitions::StreamFormat,
+    };
+    use tokio::sync::{broadcast, mpsc, watch};
+
+    use super::{MprisPlayer, art_url, track_to_metadata};
+
+    fn track(id: u32) -> Track {
+        Track {
+            id,
+            title: format!("Track {id}"),
+            image: Some(format!("https://static.qobuz.com/{id}.jpg")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn local_cover_is_only_used_for_its_track() {
+        let local_art = Some((1, "file:///tmp/1.jpg".to_string()));
+
+        assert_eq!(
+            art_url(&local_art, &track(1)),
+            Some("file:///tmp/1.jpg".to_string())
+        );
+        assert_eq!(
+            art_url(&local_art, &track(2)),
+            Some("https://static.qobuz.com/2.jpg".to_string())
+        );
+        assert_eq!(
+            art_url(&None, &track(1)),
+            Some("https://static.qobuz.com/1.jpg".to_string())
+        );
+    }
+
+    #[test]
+    fn stream_format_is_only_added_for_its_track() {
+        let format = StreamFormat {
+            sample_rate: 96_000,
+            bit_depth: Some(24),
+            channels: 2,
+        };
+
+        let metadata = track_to_metadata(
+            &track(1),
+            Some(TrackFormat {
+                track_id: 1,
+                format,
+            }),
+            None,
+        );
+        assert_eq!(metadata.title(), Some("Track 1"));
+        assert!(metadata.get_value("qobuz-player:format").is_some());
+
+        let metadata = track_to_metadata(
+            &track(2),
+            Some(TrackFormat {
+                track_id: 1,
+                format,
+            }),
+            None,
+        );
+        assert!(metadata.get_value("qobuz-player:format").is_none());
+    }
+
+    #[tokio::test]
+    async fn seek_keeps_sub_second_offsets() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let player = MprisPlayer {
+            controls: Controls::new(tx),
+            position_receiver: watch::channel(Default::default()).1,
+            tracklist_receiver: watch::channel(Default::default()).1,
+            volume_receiver: watch::channel(1.0).1,
+            status_receiver: watch::channel(Default::default()).1,
+            stream_format: watch::channel(None).1,
+            art_url: watch::channel(None).1,
+            exit_sender: broadcast::channel(1).0,
+        };
+
+        player.seek(Time::from_millis(-1500)).await.unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(ControlCommand::JumpBy {
+                offset_us: -1_500_000
+            })
+        ));
+    }
+}
@@ -1,12 +1,13 @@
 use qobuz_player_controls::{
     AppResult,
+    client::Client,
     controls::Controls,
     database::{Database, ReferenceType},
     error::Error,
     notification::NotificationBroadcast,
 };
 use reqwest::{RequestBuilder, header::CONTENT_TYPE};
-use std::sync::Arc;
+use std::{io::IsTerminal, sync::Arc};
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt},
     sync::Mutex,
@@ -14,12 +15,25 @@ use tokio::{
 
 #[derive(Debug, Clone, Default)]
 pub struct RfidState {
-    link_request: Arc<Mutex<Option<ReferenceType>>>,
+    link_request: Arc<Mutex<Option<LinkRequest>>>,
+}
+
+#[derive(Debug, Clone)]
+enum LinkRequest {
+    Link(ReferenceType),
+    /// Replace the tag with the given id by the next scanned tag
+    Relink(String),
+}
+
+/// Rfid readers act as keyboards, so a reader can only be used when stdin is a terminal
+pub fn reader_detected() -> bool {
+    std::io::stdin().is_terminal()
 }
 
 pub async fn init(
     state: RfidState,
     controls: Controls,
+    client: Arc<Client>,
     database: Arc<Database>,
     broadcast: Arc<NotificationBroadcast>,
     rfid_server_base_address: Option<String>,
@@ -53,34 +67,26 @@ pub async fn init(
         };
 
         match maybe_request {
-            Some(ReferenceType::Album(album_id)) => {
-                submit_link_album(
+            Some(LinkRequest::Link(reference)) => {
+                submit_link(
                     state.clone(),
                     database.clone(),
                     broadcast.clone(),
                     res,
-                    &album_id,
+                    reference,
                     rfid_server_base_address.as_deref(),
                     rfid_server_secret.as_deref(),
                 )
                 .await
             }
-            Some(ReferenceType::Playlist(playlist_id)) => {
-                submit_link_playlist(
-                    state.clone(),
-                    database.clone(),
-                    broadcast.clone(),
-                    res,
-                    playlist_id,
-                    rfid_server_base_address.as_deref(),
-                    rfid_server_secret.as_deref(),
-                )
-                .await
+            Some(LinkRequest::Relink(tag_id)) => {
+                submit_relink(state.clone(), &database, &broadcast, &tag_id, res).await
             }
             None => {
                 handle_play_scan(
                     &database,
                     &controls,
+                    &client,
                     &broadcast,
                     res,
                     rfid_server_base_address.as_deref(),
@@ -96,6 +102,7 @@ pub async fn init(
 pub async fn handle_play_scan(
     database: &Database,
     controls: &Controls,
+    client: &Client,
     broadcast: &NotificationBroadcast,
     reference_id: &str,
     rfid_server_base_address: Option<&str>,
@@ -106,7 +113,7 @@ pub async fn handle_play_scan(
             let client = reqwest::Client::new();
             let url = format!("{}/api/rfid/reference/{}", server, reference_id);
 
-            let mut request = client.get(&url);
+            let mut request = client.post(&url);
             request = set_secret_header(request, rfid_server_secret);
 
             let response = match request.send().await.and_then(|x| x.error_for_status()) {
@@ -126,7 +133,10 @@ pub async fn handle_play_scan(
             }
         }
         None => match database.get_reference(reference_id).await {
-            Some(reference) => reference,
+            Some(reference) => {
+                _ = database.set_rfid_tag_scanned(reference_id).await;
+                reference
+            }
             None => {
                 return;
             }
@@ -140,19 +150,63 @@ pub async fn handle_play_scan(
         ReferenceType::Playlist(id) => {
             controls.play_playlist(id, 0, false);
         }
+        ReferenceType::QueueAlbum(id) => match client.album(&id).await {
+            Ok(album) => {
+                controls.add_tracks_to_queue(album.tracks.into_iter().map(|x| x.id).collect());
+                broadcast.send(qobuz_player_controls::notification::Notification::Info(
+                    format!("{} added to queue", album.title),
+                ));
+            }
+            Err(err) => broadcast.report(&err),
+        },
+        ReferenceType::QueuePlaylist(id) => match client.playlist(id).await {
+            Ok(playlist) => {
+                controls.add_tracks_to_queue(playlist.tracks.into_iter().map(|x| x.id).collect());
+                broadcast.send(qobuz_player_controls::notification::Notification::Info(
+                    format!("{} added to queue", playlist.title),
+                ));
+            }
+            Err(err) => broadcast.report(&err),
+        },
+        ReferenceType::PlayPause => controls.play_pause(),
+        ReferenceType::Stop => controls.pause(),
+        ReferenceType::Volume(volume) => controls.set_volume(volume.min(100) as f32 / 100.0),
     }
 }
 
 pub async fn link(state: RfidState, request: ReferenceType, broadcast: Arc<NotificationBroadcast>) {
-    set_state(&state, Some(request.clone())).await;
+    let label = request.label().to_lowercase();
 
-    let type_string = match request {
-        ReferenceType::Album(_) => "album",
-        ReferenceType::Playlist(_) => "playlist",
-    };
+    start_request(
+        state,
+        LinkRequest::Link(request),
+        format!("Scan rfid to link {label}"),
+        broadcast,
+    )
+    .await;
+}
+
+/// Links the target of an existing tag to the next scanned tag, replacing the existing tag
+pub async fn relink(state: RfidState, tag_id: String, broadcast: Arc<NotificationBroadcast>) {
+    start_request(
+        state,
+        LinkRequest::Relink(tag_id),
+        "Scan rfid to replace tag".to_string(),
+        broadcast,
+    )
+    .await;
+}
+
+async fn start_request(
+    state: RfidState,
+    request: LinkRequest,
+    message: String,
+    broadcast: Arc<NotificationBroadcast>,
+) {
+    set_state(&state, Some(request)).await;
 
     broadcast.send(qobuz_player_controls::notification::Notification::Info(
-        format!("Scan rfid to link {type_string}"),
+        message,
     ));
 
     tokio::spawn(async move {
@@ -169,53 +223,27 @@ pub async fn link(state: RfidState, request: ReferenceType, broadcast: Arc<Notif
     });
 }
 
-async fn set_state(state: &RfidState, request: Option<ReferenceType>) {
+async fn set_state(state: &RfidState, request: Option<LinkRequest>) {
     let mut request_lock = state.link_request.lock().await;
     *request_lock = request;
 }
 
-async fn submit_link_album(
-    state: RfidState,
-    database: Arc<Database>,
-    broadcast: Arc<NotificationBroadcast>,
-    rfid_id: &str,
-    id: &str,
-    rfid_server_base_address: Option<&str>,
-    rfid_server_secret: Option<&str>,
-) {
-    let reference = ReferenceType::Album(id.to_owned());
-    submit_link(
-        state,
-        database,
-        broadcast,
-        rfid_id,
-        reference,
-        rfid_server_base_address,
-        rfid_server_secret,
-    )
-    .await;
-}
-
-async fn submit_link_playlist(
+async fn submit_relink(
     state: RfidState,
-    database: Arc<Database>,
-    broadcast: Arc<NotificationBroadcast>,
+    database: &Database,
+    broadcast: &NotificationBroadcast,
+    tag_id: &str,
     rfid_id: &str,
-    id: u32,
-    rfid_server_base_address: Option<&str>,
-    rfid_server_secret: Option<&str>,
 ) {
-    let reference = ReferenceType::Playlist(id);
-    submit_link(
-        state,
-        database,
-        broadcast,
-        rfid_id,
-        reference,
-        rfid_server_base_address,
-        rfid_server_secret,
-    )
-    .await;
+    match database.relink_rfid_tag(tag_id, rfid_id).await {
+        Ok(_) => {
+            broadcast.send(qobuz_player_controls::notification::Notification::Success(
+                "Tag replaced".to_string(),
+            ));
+            set_state(&state, None).await;
+        }
+        Err(err) => broadcast.report(&err),
+    }
 }
 
 async fn submit_link(
@@ -267,6 +295,24 @@ async fn submit_link(
                 let request = client.post(url);
                 request.body(reference_payload)
             }
+            reference => {
+                let reference_payload = LinkRfid {
+                    rfid_id: rfid_id.to_string(),
+                    reference,
+                };
+
+                let reference_payload = match serde_json::to_string(&reference_payload) {
+                    Ok(res) => res,
+                    Err(err) => {
+                        broadcast.send_error(err.to_string());
+                        return;
+                    }
+                };
+
+                let url = format!("{server}/api/rfid/reference");
+                let request = client.post(url);
+                request.body(reference_payload)
+            }
         };
 
         request =
@@ -317,6 +363,12 @@ pub struct LinkPlaylistRfid {
     pub id: u32,
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct LinkRfid {
+    pub rfid_id: String,
+    pub reference: ReferenceType,
+}
+
 fn set_secret_header(mut request: RequestBuilder, secret: Option<&str>) -> RequestBuilder {
     if let Some(secret) = secret {
         request = request.header("Cookie", &format!("secret={secret}"));
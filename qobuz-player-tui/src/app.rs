@@ -1,28 +1,48 @@
 use crate::{
+    clipboard,
     discover::DiscoverState,
     favorites::FavoritesState,
     genres::GenresState,
+    logs::LogsState,
+    mouse::{self, Target},
     now_playing::NowPlayingState,
-    popup::{Popup, TrackPopupState},
+    popup::{
+        Popup, ProfilePopupState, SmartPlaylistPopupState, TrackPopupState, go_to_track_album,
+        go_to_track_artist,
+    },
     queue::QueueState,
+    recent::RecentState,
     search::SearchState,
+    ui::format_duration,
 };
 use core::fmt;
-use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind};
+use crossterm::event::{
+    Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
 use futures::StreamExt;
 use image::load_from_memory;
 use qobuz_player_controls::{
-    AppResult, PositionReceiver, Status, StatusReceiver, TracklistReceiver,
+    AppResult, PositionReceiver, QuietHoursReceiver, Status, StatusReceiver, TracklistReceiver,
+    artwork_cache::ArtworkCache,
     client::Client,
     controls::Controls,
-    models::{Album, Track},
-    notification::{Notification, NotificationBroadcast},
+    database::Database,
+    diagnostics::Diagnostics,
+    models::{Album, Track, reduced_artwork_url},
+    notification::{Notification, NotificationBroadcast, ResumePoint},
+    profile::{replace_credentials, switch_profile},
+    release_notifications,
+    smart_playlists::SmartPlaylist,
     tracklist::{Tracklist, TracklistType},
 };
 use ratatui::{DefaultTerminal, widgets::*};
 use ratatui_image::{picker::Picker, protocol::StatefulProtocol};
 use std::{io, sync::Arc, time::Instant};
-use tokio::time::{self, Duration};
+use tokio::{
+    sync::watch,
+    time::{self, Duration},
+};
 
 #[derive(Default)]
 pub struct NotificationList {
@@ -48,12 +68,19 @@ impl NotificationList {
     }
 }
 
+/// Size of the cached artwork variant rendered in the terminal
+const ARTWORK_SIZE: u32 = 600;
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
 pub struct App {
     pub client: Arc<Client>,
+    pub artwork_cache: Arc<ArtworkCache>,
+    pub database: Arc<Database>,
     pub controls: Controls,
     pub position: PositionReceiver,
     pub tracklist: TracklistReceiver,
     pub status: StatusReceiver,
+    pub quiet_hours: QuietHoursReceiver,
     pub current_screen: Tab,
     pub exit: bool,
     pub should_draw: bool,
@@ -64,11 +91,21 @@ pub struct App {
     pub queue: QueueState,
     pub discover: DiscoverState,
     pub genres: GenresState,
+    pub recent: RecentState,
+    pub logs: LogsState,
+    /// Changes when a log line is captured
+    pub logs_captured: watch::Receiver<u64>,
     pub broadcast: Arc<NotificationBroadcast>,
     pub notifications: NotificationList,
     pub full_screen: bool,
     pub disable_tui_album_cover: bool,
     pub current_image_url: Option<String>,
+    /// Time and row of the last click, to detect double clicks
+    pub last_click: Option<(Instant, usize)>,
+    /// Latest playback counters, shown in the diagnostics overlay
+    pub diagnostics: Diagnostics,
+    /// Saved position offered when a long track started again
+    pub resume_point: Option<ResumePoint>,
 }
 
 #[derive(Default)]
@@ -77,7 +114,10 @@ pub enum AppState {
     Normal,
     Popup(Vec<Popup>),
     Help,
-    AlbumInfo(Album),
+    AlbumInfo(Box<Album>),
+    Diagnostics,
+    /// Qobuz url of the playing track, shown as a QR code
+    QrCode(String),
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -89,6 +129,11 @@ pub enum Output {
     PopPopupUpdateFavorites,
     AddTrackToPlaylistPopup(Track),
     AddTrackToPlaylistAndPopPopup((u32, u32)), // TODO: Add a type
+    SwitchProfile(String),
+    SetPlaylistTags(u32, Vec<String>),
+    OpenSmartPlaylist(SmartPlaylist),
+    /// Follows the artist with this id and name, or unfollows it when followed
+    ToggleFollowArtist(u32, String),
 }
 
 #[derive(Default, PartialEq)]
@@ -99,6 +144,8 @@ pub enum Tab {
     Queue,
     Discover,
     Genres,
+    Recent,
+    Logs,
 }
 
 impl fmt::Display for Tab {
@@ -109,17 +156,21 @@ impl fmt::Display for Tab {
             Tab::Queue => write!(f, "Queue"),
             Tab::Discover => write!(f, "Discover"),
             Tab::Genres => write!(f, "Genres"),
+            Tab::Recent => write!(f, "Recent"),
+            Tab::Logs => write!(f, "Logs"),
         }
     }
 }
 
 impl Tab {
-    pub const VALUES: [Self; 5] = [
+    pub const VALUES: [Self; 7] = [
         Tab::Favorites,
         Tab::Search,
         Tab::Queue,
         Tab::Discover,
         Tab::Genres,
+        Tab::Recent,
+        Tab::Logs,
     ];
 }
 
@@ -158,9 +209,27 @@ where
     pub fn set_filter(&mut self, items: Vec<T>) {
         self.filter = items;
     }
+
+    pub fn append_items(&mut self, items: Vec<T>) {
+        self.all_items.extend(items.clone());
+        self.filter.extend(items);
+    }
+
+    pub fn is_last_selected(&self) -> bool {
+        self.state
+            .selected()
+            .is_some_and(|index| index + 1 >= self.filter.len())
+    }
 }
 
 impl App {
+    fn artwork_url(&self, url: &str) -> String {
+        match self.client.data_saver() {
+            true => reduced_artwork_url(url),
+            false => url.to_string(),
+        }
+    }
+
     pub async fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
         let mut tick_interval = time::interval(Duration::from_millis(100));
         let mut receiver = self.broadcast.subscribe();
@@ -171,7 +240,7 @@ impl App {
         if let Some(image_url) = self.current_image_url.as_ref()
             && !self.disable_tui_album_cover
         {
-            let image = fetch_image(image_url).await;
+            let image = fetch_image(&self.artwork_cache, &self.artwork_url(image_url)).await;
             self.now_playing.image = image;
         };
 
@@ -196,25 +265,44 @@ impl App {
                     self.queue.set_items(tracklist
                         .queue()
                         .into_iter()
-                        .map(|x| x.track.clone())
+                        .cloned()
                         .collect());
                     let status = self.now_playing.status;
                     let (mut new_state, image_url) = get_current_state_without_image(&tracklist, status);
 
+                    new_state.buffer_progress = self.now_playing.buffer_progress.filter(|progress| {
+                        new_state
+                            .playing_track
+                            .as_ref()
+                            .is_some_and(|track| track.id == progress.track_id)
+                    });
+                    // Kept as is, the format of a queued track arrives before the tracklist update
+                    new_state.stream_format = self.now_playing.stream_format;
+                    new_state.quiet_hours = self.now_playing.quiet_hours;
+
                     if image_url == self.current_image_url {
                         new_state.image = self.now_playing.image.take();
                     } else if !self.disable_tui_album_cover {
-                        if let Some(url) = image_url.clone() {
+                        if let Some(url) = image_url.as_deref().map(|url| self.artwork_url(url)) {
                             let tx = image_tx.clone();
+                            let artwork_cache = self.artwork_cache.clone();
                             tokio::spawn(async move {
-                                let result = fetch_image(&url).await;
+                                let result = fetch_image(&artwork_cache, &url).await;
                                 let _ = tx.send(result).await;
                             });
                         }
                         self.current_image_url = image_url;
                     }
 
+                    if let Some(track) = &new_state.playing_track {
+                        new_state.is_favorite = self.client.is_favorite_track(track.id);
+                    }
                     self.now_playing = new_state;
+
+                    if let Ok(recent) = self.database.get_recent_tracklists().await {
+                        self.recent.set_items(recent);
+                    }
+
                     self.should_draw = true;
                 },
 
@@ -229,14 +317,61 @@ impl App {
                     self.should_draw = true;
                 }
 
+                Ok(_) = self.quiet_hours.changed() => {
+                    self.now_playing.quiet_hours = *self.quiet_hours.borrow_and_update();
+                    self.should_draw = true;
+                }
+
+                Ok(_) = self.logs_captured.changed() => {
+                    self.logs_captured.borrow_and_update();
+                    self.should_draw = self.current_screen == Tab::Logs;
+                }
+
                 _ = tick_interval.tick() => {
                     // Tick is now only used for notification cleanup
                 }
 
                 notification = receiver.recv() => {
-                    if let Ok(notification) = notification {
-                        self.notifications.push(notification);
-                        self.should_draw = true;
+                    match notification {
+                        Ok(Notification::BufferProgress(progress)) => {
+                            let is_playing = self
+                                .now_playing
+                                .playing_track
+                                .as_ref()
+                                .is_some_and(|track| track.id == progress.track_id);
+
+                            if is_playing {
+                                self.now_playing.buffer_progress = Some(progress);
+                                self.should_draw = true;
+                            }
+                        }
+                        Ok(Notification::StreamFormat(format)) => {
+                            self.now_playing.stream_format = Some(format);
+                            self.should_draw = true;
+                        }
+                        Ok(Notification::Diagnostics(diagnostics)) => {
+                            self.diagnostics = diagnostics;
+                            self.should_draw = matches!(self.app_state, AppState::Diagnostics);
+                        }
+                        Ok(Notification::ResumeAvailable(resume_point)) => {
+                            let position = format_duration(resume_point.position_seconds as u32);
+                            self.resume_point = Some(resume_point);
+                            self.notifications.push(Notification::Info(format!(
+                                "Resume from {position}? Press R"
+                            )));
+                            self.should_draw = true;
+                        }
+                        Ok(Notification::SignInRequired) => {
+                            self.notifications.push(Notification::Error(
+                                "The Qobuz login is no longer valid. Press L to sign in again".into(),
+                            ));
+                            self.should_draw = true;
+                        }
+                        Err(_) => {}
+                        Ok(notification) => {
+                            self.notifications.push(notification);
+                            self.should_draw = true;
+                        }
                     }
                 }
             }
@@ -254,19 +389,91 @@ impl App {
         Ok(())
     }
 
+    /// Logs in with the browser in the background, as it waits for the redirect of Qobuz
+    fn sign_in_again(&self) {
+        let client = self.client.clone();
+        let database = self.database.clone();
+        let broadcast = self.broadcast.clone();
+
+        tokio::spawn(async move {
+            let result = async {
+                let (_, oauth_result) =
+                    Client::new_with_oauth_login(client.max_audio_quality(), false).await?;
+                replace_credentials(&database, &client, oauth_result.into()).await
+            }
+            .await;
+
+            match result {
+                Ok(()) => broadcast.send(Notification::Success("Signed in to Qobuz".into())),
+                Err(err) => broadcast.report(&err),
+            }
+        });
+    }
+
     async fn update_favorites(&mut self) {
         let favorites = self.client.favorites().await;
         let Ok(favorites) = favorites else {
             return;
         };
 
-        self.favorites.albums.set_all_items(favorites.albums);
+        if let Err(err) = self
+            .favorites
+            .set_albums(favorites.albums, &self.database)
+            .await
+        {
+            self.notifications
+                .push(Notification::Error(err.to_string()));
+        }
         self.favorites.artists.set_all_items(favorites.artists);
         self.favorites
             .playlists
             .set_all_items(favorites.playlists.into_iter().map(|x| x.into()).collect());
         self.favorites.tracks.set_all_items(favorites.tracks);
         self.favorites.filter.reset();
+        if let Ok(tags) = self.database.get_playlist_tags().await {
+            self.favorites.set_playlist_tags(tags);
+        }
+        if let Some(track) = &self.now_playing.playing_track {
+            self.now_playing.is_favorite = self.client.is_favorite_track(track.id);
+        }
+    }
+
+    async fn toggle_favorite_playing_track(&mut self) {
+        let Some(track) = self.now_playing.playing_track.clone() else {
+            return;
+        };
+
+        match self.client.toggle_favorite_track(track.id).await {
+            Ok(is_favorite) => {
+                let message = match is_favorite {
+                    true => format!("Added {} to favorites", track.title),
+                    false => format!("Removed {} from favorites", track.title),
+                };
+                self.notifications.push(Notification::Info(message));
+                self.update_favorites().await;
+            }
+            Err(err) => {
+                self.notifications
+                    .push(Notification::Error(err.to_string()));
+            }
+        }
+    }
+
+    /// Seeks to the offered resume point while its track is still playing
+    fn resume_playing_track(&mut self) {
+        let Some(resume_point) = self.resume_point.take() else {
+            return;
+        };
+
+        let is_playing = self
+            .now_playing
+            .playing_track
+            .as_ref()
+            .is_some_and(|track| track.id == resume_point.track_id);
+        if is_playing {
+            self.controls
+                .seek(Duration::from_secs(resume_point.position_seconds));
+        }
     }
 
     async fn handle_output(&mut self, key_code: KeyCode, output: AppResult<Output>) {
@@ -300,10 +507,58 @@ impl App {
                         .and_then(|t| t.album_id.clone())
                         && let Ok(album) = self.client.album(&album_id).await
                     {
-                        self.app_state = AppState::AlbumInfo(album);
+                        self.app_state = AppState::AlbumInfo(Box::new(album));
+                        self.should_draw = true;
+                    }
+                }
+                KeyCode::Char('g') | KeyCode::Char('G') => {
+                    if let Some(track) = self.now_playing.playing_track.clone() {
+                        let output = match key_code {
+                            KeyCode::Char('g') => go_to_track_album(&track, &self.client).await,
+                            _ => go_to_track_artist(&track, &self.client).await,
+                        };
+                        match output {
+                            Ok(Output::Popup(popup)) => {
+                                self.app_state = AppState::Popup(vec![popup]);
+                            }
+                            Ok(_) => {}
+                            Err(err) => self
+                                .notifications
+                                .push(Notification::Error(err.to_string())),
+                        }
+                        self.should_draw = true;
+                    }
+                }
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    let tracklist = self.tracklist.borrow().clone();
+                    let target = match key_code {
+                        KeyCode::Char('y') => tracklist.current_track_link(),
+                        _ => tracklist.current_list_link(),
+                    };
+                    if let Some(target) = target {
+                        let url = target.url();
+                        match clipboard::copy(&url) {
+                            Ok(()) => self
+                                .notifications
+                                .push(Notification::Info(format!("Copied {url}"))),
+                            Err(err) => self
+                                .notifications
+                                .push(Notification::Error(err.to_string())),
+                        }
                         self.should_draw = true;
                     }
                 }
+                KeyCode::Char('s') => {
+                    let target = self.tracklist.borrow().current_track_link();
+                    if let Some(target) = target {
+                        self.app_state = AppState::QrCode(target.url());
+                        self.should_draw = true;
+                    }
+                }
+                KeyCode::Char('I') => {
+                    self.app_state = AppState::Diagnostics;
+                    self.should_draw = true;
+                }
                 KeyCode::Char('q') => {
                     self.should_draw = true;
                     self.exit()
@@ -328,6 +583,14 @@ impl App {
                     self.navigate_to_genres();
                     self.should_draw = true;
                 }
+                KeyCode::Char('6') => {
+                    self.navigate_to_recent();
+                    self.should_draw = true;
+                }
+                KeyCode::Char('7') => {
+                    self.navigate_to_logs();
+                    self.should_draw = true;
+                }
                 KeyCode::Char(' ') => {
                     self.controls.play_pause();
                     self.should_draw = true;
@@ -340,6 +603,14 @@ impl App {
                     self.controls.previous();
                     self.should_draw = true;
                 }
+                KeyCode::Char('*') => {
+                    self.toggle_favorite_playing_track().await;
+                    self.should_draw = true;
+                }
+                KeyCode::Char('R') => {
+                    self.resume_playing_track();
+                    self.should_draw = true;
+                }
                 KeyCode::Char('f') => {
                     self.controls.jump_forward();
                     self.should_draw = true;
@@ -348,13 +619,46 @@ impl App {
                     self.controls.jump_backward();
                     self.should_draw = true;
                 }
+                KeyCode::Char('>') => {
+                    self.controls.jump_by(60_000_000);
+                    self.should_draw = true;
+                }
+                KeyCode::Char('<') => {
+                    self.controls.jump_by(-60_000_000);
+                    self.should_draw = true;
+                }
+                KeyCode::Char('m') => {
+                    self.controls.toggle_mute();
+                    self.should_draw = true;
+                }
                 KeyCode::Char('F') => {
                     self.full_screen = !self.full_screen;
                     self.should_draw = true;
                 }
+                KeyCode::Char('L') => {
+                    self.notifications
+                        .push(Notification::Info("Sign in to Qobuz in the browser".into()));
+                    self.should_draw = true;
+                    self.sign_in_again();
+                }
+                KeyCode::Char('P') => match self.database.get_profiles().await {
+                    Ok(profiles) => {
+                        let state = ProfilePopupState::new(profiles, &self.database.profile());
+                        self.app_state = AppState::Popup(vec![Popup::Profile(state)]);
+                        self.should_draw = true;
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .push(Notification::Error(err.to_string()));
+                    }
+                },
                 _ => {}
             },
             Output::Popup(popup) => {
+                if let Popup::Album(album) = &popup {
+                    self.controls.precache_album(album.id());
+                }
+
                 let mut popups = match std::mem::take(&mut self.app_state) {
                     AppState::Popup(popups) => popups,
                     _ => Vec::new(),
@@ -423,6 +727,83 @@ impl App {
                 };
                 self.should_draw = true;
             }
+            Output::ToggleFollowArtist(id, name) => {
+                let followed = self
+                    .database
+                    .get_followed_artists()
+                    .await
+                    .is_ok_and(|artists| artists.iter().any(|artist| artist.id == id));
+
+                let result = match followed {
+                    true => self
+                        .database
+                        .unfollow_artist(id)
+                        .await
+                        .map(|_| format!("No longer notified about new releases of {name}")),
+                    false => release_notifications::follow(&self.database, &self.client, id, &name)
+                        .await
+                        .map(|_| format!("Notified about new releases of {name}")),
+                };
+
+                match result {
+                    Ok(message) => self.notifications.push(Notification::Info(message)),
+                    Err(err) => self
+                        .notifications
+                        .push(Notification::Error(err.to_string())),
+                }
+                self.should_draw = true;
+            }
+            Output::OpenSmartPlaylist(kind) => {
+                match kind.tracks(&self.database, &self.client).await {
+                    Ok(tracks) => {
+                        if let AppState::Popup(popups) = &mut self.app_state {
+                            popups.pop();
+                            popups.push(Popup::SmartPlaylist(SmartPlaylistPopupState::new(
+                                kind, tracks,
+                            )));
+                        }
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .push(Notification::Error(err.to_string()));
+                    }
+                }
+                self.should_draw = true;
+            }
+            Output::SetPlaylistTags(id, tags) => {
+                if let Err(err) = self.database.set_playlist_tags(id, &tags).await {
+                    self.notifications
+                        .push(Notification::Error(err.to_string()));
+                }
+                if let Ok(tags) = self.database.get_playlist_tags().await {
+                    self.favorites.set_playlist_tags(tags);
+                }
+                if let AppState::Popup(popups) = &mut self.app_state {
+                    popups.pop();
+                    if popups.is_empty() {
+                        self.app_state = AppState::Normal;
+                    }
+                }
+                self.should_draw = true;
+            }
+            Output::SwitchProfile(name) => {
+                match switch_profile(&self.database, &self.client, &self.controls, &name).await {
+                    Ok(()) => {
+                        self.app_state = AppState::Normal;
+                        self.update_favorites().await;
+                        if let Ok(history) = self.database.get_search_history().await {
+                            self.search.set_history(history);
+                        }
+                        self.notifications
+                            .push(Notification::Info(format!("Switched to profile {name}")));
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .push(Notification::Error(err.to_string()));
+                    }
+                }
+                self.should_draw = true;
+            }
         }
     }
 
@@ -430,7 +811,10 @@ impl App {
         match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
                 match &mut self.app_state {
-                    AppState::Help | AppState::AlbumInfo(_) => {
+                    AppState::Help
+                    | AppState::AlbumInfo(_)
+                    | AppState::Diagnostics
+                    | AppState::QrCode(_) => {
                         self.app_state = AppState::Normal;
                         self.should_draw = true;
                         return Ok(());
@@ -489,11 +873,16 @@ impl App {
                                 event,
                                 &self.client,
                                 &self.controls,
+                                &self.database,
                                 &mut self.notifications,
                             )
                             .await
                     }
-                    Tab::Queue => Ok(self.queue.handle_events(event, &self.controls).await),
+                    Tab::Queue => {
+                        self.queue
+                            .handle_events(event, &self.client, &self.controls)
+                            .await
+                    }
                     Tab::Discover => {
                         self.discover
                             .handle_events(
@@ -514,17 +903,108 @@ impl App {
                             )
                             .await
                     }
+                    Tab::Recent => Ok(self.recent.handle_events(event, &self.controls).await),
+                    Tab::Logs => Ok(self.logs.handle_events(event)),
                 };
 
                 self.handle_output(key_event.code, screen_output).await;
             }
 
+            Event::Mouse(mouse_event) => self.handle_mouse(mouse_event).await?,
             Event::Resize(_, _) => self.should_draw = true,
             _ => {}
         };
         Ok(())
     }
 
+    /// Mouse events are translated to the key presses that do the same
+    async fn handle_mouse(&mut self, mouse_event: MouseEvent) -> io::Result<()> {
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+
+        let (is_click, is_drag) = match mouse_event.kind {
+            MouseEventKind::ScrollDown => {
+                return Box::pin(self.handle_event(key(KeyCode::Down))).await;
+            }
+            MouseEventKind::ScrollUp => return Box::pin(self.handle_event(key(KeyCode::Up))).await,
+            MouseEventKind::Down(MouseButton::Left) => (true, false),
+            MouseEventKind::Drag(MouseButton::Left) => (false, true),
+            _ => return Ok(()),
+        };
+
+        if is_click
+            && matches!(
+                self.app_state,
+                AppState::Help
+                    | AppState::AlbumInfo(_)
+                    | AppState::Diagnostics
+                    | AppState::QrCode(_)
+            )
+        {
+            self.app_state = AppState::Normal;
+            self.should_draw = true;
+            return Ok(());
+        }
+
+        match mouse::target(mouse_event.column, mouse_event.row) {
+            Some(Target::Progress(percentage)) if is_click || is_drag => {
+                self.controls.seek_percentage(percentage);
+            }
+            Some(Target::Tab(index)) if is_click && matches!(self.app_state, AppState::Normal) => {
+                self.navigate_to_tab(index);
+                self.should_draw = true;
+            }
+            Some(Target::Row { index, selected }) if is_click => {
+                self.search.editing = false;
+                self.favorites.editing = false;
+
+                let selected = match selected {
+                    Some(selected) => selected,
+                    None => {
+                        Box::pin(self.handle_event(key(KeyCode::Down))).await?;
+                        0
+                    }
+                };
+
+                let code = if index > selected {
+                    KeyCode::Down
+                } else {
+                    KeyCode::Up
+                };
+                for _ in 0..index.abs_diff(selected) {
+                    Box::pin(self.handle_event(key(code))).await?;
+                }
+
+                let double_click = self
+                    .last_click
+                    .is_some_and(|(at, row)| row == index && at.elapsed() < DOUBLE_CLICK_INTERVAL);
+
+                if double_click {
+                    self.last_click = None;
+                    Box::pin(self.handle_event(key(KeyCode::Enter))).await?;
+                } else {
+                    self.last_click = Some((Instant::now(), index));
+                }
+                self.should_draw = true;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn navigate_to_tab(&mut self, index: usize) {
+        match Tab::VALUES.get(index) {
+            Some(Tab::Favorites) => self.navigate_to_favorites(),
+            Some(Tab::Search) => self.navigate_to_search(),
+            Some(Tab::Queue) => self.navigate_to_queue(),
+            Some(Tab::Discover) => self.navigate_to_discover(),
+            Some(Tab::Genres) => self.navigate_to_genres(),
+            Some(Tab::Recent) => self.navigate_to_recent(),
+            Some(Tab::Logs) => self.navigate_to_logs(),
+            None => {}
+        }
+    }
+
     fn navigate_to_favorites(&mut self) {
         self.current_screen = Tab::Favorites;
     }
@@ -546,15 +1026,24 @@ impl App {
         self.current_screen = Tab::Genres;
     }
 
+    fn navigate_to_recent(&mut self) {
+        self.current_screen = Tab::Recent;
+    }
+
+    fn navigate_to_logs(&mut self) {
+        self.current_screen = Tab::Logs;
+    }
+
     fn exit(&mut self) {
         self.exit = true;
     }
 }
 
-async fn fetch_image(image_url: &str) -> Option<(StatefulProtocol, f32)> {
-    let client = reqwest::Client::new();
-    let response = client.get(image_url).send().await.ok()?;
-    let img_bytes = response.bytes().await.ok()?;
+async fn fetch_image(
+    artwork_cache: &ArtworkCache,
+    image_url: &str,
+) -> Option<(StatefulProtocol, f32)> {
+    let img_bytes = artwork_cache.image(image_url, ARTWORK_SIZE).await.ok()?;
 
     tokio::task::spawn_blocking(move || {
         let image = load_from_memory(&img_bytes).ok()?;
@@ -597,6 +1086,10 @@ pub fn get_current_state_without_image(
         status,
         tracklist_position: tracklist.current_position(),
         duration_ms: 0,
+        buffer_progress: None,
+        stream_format: None,
+        is_favorite: false,
+        quiet_hours: None,
     };
 
     (state, image)
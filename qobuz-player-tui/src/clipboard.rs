@@ -0,0 +1,11 @@
+use std::io::{Write, stdout};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+/// Copies through the terminal with an OSC 52 escape sequence, which also reaches the local
+/// clipboard over ssh. Terminals without support ignore it
+pub fn copy(text: &str) -> std::io::Result<()> {
+    let mut stdout = stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
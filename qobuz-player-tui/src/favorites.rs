@@ -1,12 +1,22 @@
-use qobuz_player_controls::{AppResult, client::Client, controls::Controls};
+use std::collections::HashMap;
+
+use qobuz_player_controls::{
+    AppResult,
+    client::Client,
+    controls::Controls,
+    database::Database,
+    models::{AlbumSimple, FavoriteAlbumSort, PlaylistSimple, new_favorite_albums},
+};
 use ratatui::{
     crossterm::event::{Event, KeyCode, KeyEventKind},
     prelude::*,
 };
+use time::OffsetDateTime;
 use tui_input::{Input, backend::crossterm::EventHandler};
 
 use crate::{
     app::{NotificationList, Output},
+    popup::{PlaylistTagsPopupState, Popup, SmartPlaylistsPopupState},
     sub_tab::SubTab,
     ui::{block, render_input, tab_bar},
     widgets::{
@@ -25,23 +35,50 @@ pub struct FavoritesState {
     pub playlists: PlaylistList,
     pub tracks: TrackList,
     pub sub_tab: SubTab,
+    /// Only show the playlists with this tag
+    pub tag_filter: Option<String>,
+    pub album_sort: FavoriteAlbumSort,
+    /// When the favorite albums were added, where known
+    albums_added: HashMap<String, OffsetDateTime>,
 }
 
 impl FavoritesState {
-    pub async fn new(client: &Client) -> AppResult<Self> {
+    pub async fn new(client: &Client, database: &Database) -> AppResult<Self> {
         let favorites = client.favorites().await?;
+        let mut playlists =
+            PlaylistList::new(favorites.playlists.into_iter().map(|x| x.into()).collect());
+        playlists.set_tags(database.get_playlist_tags().await?);
 
-        Ok(Self {
+        let mut state = Self {
             editing: Default::default(),
             filter: Default::default(),
-            albums: AlbumList::new(favorites.albums),
+            albums: Default::default(),
             artists: ArtistList::new(favorites.artists),
-            playlists: PlaylistList::new(
-                favorites.playlists.into_iter().map(|x| x.into()).collect(),
-            ),
+            playlists,
             tracks: TrackList::new(favorites.tracks),
             sub_tab: Default::default(),
-        })
+            tag_filter: None,
+            album_sort: Default::default(),
+            albums_added: Default::default(),
+        };
+        state.set_albums(favorites.albums, database).await?;
+
+        Ok(state)
+    }
+
+    /// Sets the favorite albums in the chosen order and marks the recently added ones
+    pub async fn set_albums(
+        &mut self,
+        mut albums: Vec<AlbumSimple>,
+        database: &Database,
+    ) -> AppResult<()> {
+        self.albums_added = database.favorite_albums_added().await?;
+
+        self.album_sort.sort(&mut albums, &self.albums_added);
+        self.albums
+            .set_new_albums(new_favorite_albums(&self.albums_added));
+        self.albums.set_all_items(albums);
+        Ok(())
     }
 
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
@@ -49,12 +86,19 @@ impl FavoritesState {
             .constraints([Constraint::Length(3), Constraint::Min(1)])
             .split(area);
 
+        let filter_title = match (&self.tag_filter, self.sub_tab) {
+            (Some(tag), SubTab::Playlists) => format!("Filter (tag: {tag})"),
+            (_, SubTab::Albums) if self.album_sort != FavoriteAlbumSort::Artist => {
+                format!("Filter (by {})", self.album_sort.label().to_lowercase())
+            }
+            _ => "Filter".to_string(),
+        };
         render_input(
             &self.filter,
             self.editing,
             tab_content_area_split[0],
             frame,
-            "Filter",
+            &filter_title,
         );
 
         let block = block(None);
@@ -67,7 +111,10 @@ impl FavoritesState {
             .constraints([Constraint::Length(2), Constraint::Min(1)])
             .split(tab_content_area);
 
-        let tabs = tab_bar(SubTab::labels(), self.sub_tab.selected().into());
+        let tabs = tab_bar(
+            SubTab::labels(&SubTab::LIBRARY),
+            self.sub_tab.selected().into(),
+        );
         frame.render_widget(tabs, chunks[0]);
 
         match self.sub_tab {
@@ -75,6 +122,7 @@ impl FavoritesState {
             SubTab::Artists => self.artists.render(chunks[1], frame.buffer_mut()),
             SubTab::Playlists => self.playlists.render(chunks[1], frame.buffer_mut()),
             SubTab::Tracks => self.tracks.render(chunks[1], frame.buffer_mut(), true),
+            SubTab::Composers => {}
         };
     }
 
@@ -101,6 +149,38 @@ impl FavoritesState {
                             self.cycle_subtab();
                             Ok(Output::Consumed)
                         }
+                        KeyCode::Char('o') if self.sub_tab == SubTab::Albums => {
+                            self.cycle_album_sort();
+                            Ok(Output::Consumed)
+                        }
+                        KeyCode::Char('t') if self.sub_tab == SubTab::Playlists => {
+                            self.cycle_tag_filter();
+                            Ok(Output::Consumed)
+                        }
+                        KeyCode::Char('M') if self.sub_tab == SubTab::Playlists => Ok(
+                            Output::Popup(Popup::SmartPlaylists(SmartPlaylistsPopupState::new())),
+                        ),
+                        KeyCode::Char('T') if self.sub_tab == SubTab::Playlists => {
+                            let selected = self
+                                .playlists
+                                .selected()
+                                .and_then(|index| self.playlists.get(index));
+
+                            match selected {
+                                Some(playlist) => {
+                                    let tags = self
+                                        .playlists
+                                        .tags()
+                                        .get(&playlist.id)
+                                        .cloned()
+                                        .unwrap_or_default();
+                                    Ok(Output::Popup(Popup::PlaylistTags(
+                                        PlaylistTagsPopupState::new(playlist, &tags),
+                                    )))
+                                }
+                                None => Ok(Output::Consumed),
+                            }
+                        }
                         _ => match self.sub_tab {
                             SubTab::Albums => {
                                 return self
@@ -132,6 +212,7 @@ impl FavoritesState {
                                     )
                                     .await;
                             }
+                            SubTab::Composers => Ok(Output::NotConsumed),
                         },
                     },
                     true => match key_event.code {
@@ -141,23 +222,13 @@ impl FavoritesState {
                         }
                         _ => {
                             self.filter.handle_event(&event);
+                            self.filter_albums();
 
                             let match_in = |s: &str| {
                                 s.to_lowercase()
                                     .contains(&self.filter.value().to_lowercase())
                             };
 
-                            self.albums.set_filter(
-                                self.albums
-                                    .all_items()
-                                    .iter()
-                                    .filter(|album| {
-                                        match_in(&album.title) || match_in(&album.artist.name)
-                                    })
-                                    .cloned()
-                                    .collect(),
-                            );
-
                             self.artists.set_filter(
                                 self.artists
                                     .all_items()
@@ -167,15 +238,6 @@ impl FavoritesState {
                                     .collect(),
                             );
 
-                            self.playlists.set_filter(
-                                self.playlists
-                                    .all_items()
-                                    .iter()
-                                    .filter(|playlist| match_in(&playlist.title))
-                                    .cloned()
-                                    .collect(),
-                            );
-
                             self.tracks.set_filter(
                                 self.tracks
                                     .all_items()
@@ -195,6 +257,8 @@ impl FavoritesState {
                                     .collect(),
                             );
 
+                            self.filter_playlists();
+
                             Ok(Output::Consumed)
                         }
                     },
@@ -204,6 +268,93 @@ impl FavoritesState {
         }
     }
 
+    /// Applies the text filter to the albums
+    fn filter_albums(&mut self) {
+        let filter = self.filter.value().to_lowercase();
+        let match_in = |s: &str| s.to_lowercase().contains(&filter);
+
+        let albums = self
+            .albums
+            .all_items()
+            .iter()
+            .filter(|album| match_in(&album.title) || match_in(&album.artist.name))
+            .cloned()
+            .collect();
+        self.albums.set_filter(albums);
+    }
+
+    /// Sorts the albums in the next order
+    fn cycle_album_sort(&mut self) {
+        let values = FavoriteAlbumSort::VALUES;
+        let position = values
+            .iter()
+            .position(|sort| *sort == self.album_sort)
+            .unwrap_or(0);
+        self.album_sort = values[(position + 1) % values.len()];
+
+        let mut albums = self.albums.all_items().clone();
+        self.album_sort.sort(&mut albums, &self.albums_added);
+        self.albums.set_all_items(albums);
+        self.filter_albums();
+    }
+
+    /// Sets the tags of the playlists and drops a tag filter no playlist has anymore
+    pub fn set_playlist_tags(&mut self, tags: HashMap<u32, Vec<String>>) {
+        self.playlists.set_tags(tags);
+        if self
+            .tag_filter
+            .as_ref()
+            .is_some_and(|tag| !self.playlist_tag_names().contains(tag))
+        {
+            self.tag_filter = None;
+        }
+        self.filter_playlists();
+    }
+
+    /// Applies the text filter and the tag filter to the playlists
+    pub fn filter_playlists(&mut self) {
+        let filter = self.filter.value().to_lowercase();
+        let tags = self.playlists.tags();
+        let has_tag = |playlist: &PlaylistSimple| match &self.tag_filter {
+            Some(tag) => tags
+                .get(&playlist.id)
+                .is_some_and(|tags| tags.contains(tag)),
+            None => true,
+        };
+
+        let playlists = self
+            .playlists
+            .all_items()
+            .iter()
+            .filter(|playlist| playlist.title.to_lowercase().contains(&filter))
+            .filter(|playlist| has_tag(playlist))
+            .cloned()
+            .collect();
+        self.playlists.set_filter(playlists);
+    }
+
+    /// All tags, sorted
+    fn playlist_tag_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.playlists.tags().values().flatten().cloned().collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// No tag, then each tag in turn
+    fn cycle_tag_filter(&mut self) {
+        let names = self.playlist_tag_names();
+        self.tag_filter = match &self.tag_filter {
+            None => names.first().cloned(),
+            Some(current) => names
+                .iter()
+                .position(|name| name == current)
+                .and_then(|position| names.get(position + 1))
+                .cloned(),
+        };
+        self.filter_playlists();
+    }
+
     fn start_editing(&mut self) {
         self.editing = true;
     }
@@ -213,10 +364,10 @@ impl FavoritesState {
     }
 
     fn cycle_subtab_backwards(&mut self) {
-        self.sub_tab = self.sub_tab.previous();
+        self.sub_tab = self.sub_tab.previous(&SubTab::LIBRARY);
     }
 
     fn cycle_subtab(&mut self) {
-        self.sub_tab = self.sub_tab.next();
+        self.sub_tab = self.sub_tab.next(&SubTab::LIBRARY);
     }
 }
@@ -8,6 +8,7 @@ use ratatui::{
 
 use crate::{
     app::{NotificationList, Output},
+    theme::theme,
     ui::{block, tab_bar},
     widgets::{album_list::AlbumList, playlist_list::PlaylistList},
 };
@@ -91,7 +92,7 @@ impl GenresState {
             .split(area);
 
         let title = Paragraph::new("Select a Genre")
-            .style(Style::default().fg(Color::Cyan))
+            .style(theme().accent)
             .alignment(Alignment::Center);
         frame.render_widget(title, chunks[0]);
 
@@ -119,12 +120,9 @@ impl GenresState {
                 if genre_idx < self.genres.len() {
                     let is_selected = genre_idx == self.selected_genre;
                     let style = if is_selected {
-                        Style::default()
-                            .fg(Color::Black)
-                            .bg(Color::Cyan)
-                            .add_modifier(Modifier::BOLD)
+                        theme().highlight.add_modifier(Modifier::BOLD)
                     } else {
-                        Style::default().fg(Color::White)
+                        Style::default()
                     };
 
                     let genre_block = Paragraph::new(self.genres[genre_idx].name.as_str())
@@ -132,9 +130,9 @@ impl GenresState {
                         .alignment(Alignment::Center)
                         .block(Block::default().borders(Borders::ALL).border_style(
                             if is_selected {
-                                Style::default().fg(Color::Cyan)
+                                theme().accent
                             } else {
-                                Style::default().fg(Color::DarkGray)
+                                theme().border
                             },
                         ));
 
@@ -156,7 +154,7 @@ impl GenresState {
 
         let title = format!("← Back | {}", self.genres[self.selected_genre].name);
         let title_widget = Paragraph::new(title)
-            .style(Style::default().fg(Color::Cyan))
+            .style(theme().accent)
             .alignment(Alignment::Left);
         frame.render_widget(title_widget, chunks[0]);
 
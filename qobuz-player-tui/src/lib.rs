@@ -1,51 +1,67 @@
-use std::sync::Arc;
+use std::{io::stdout, sync::Arc};
 
 use app::{App, get_current_state_without_image};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use favorites::FavoritesState;
 use qobuz_player_controls::{
-    AppResult, ExitSender, PositionReceiver, StatusReceiver, TracklistReceiver, client::Client,
-    controls::Controls, error::Error, notification::NotificationBroadcast,
+    AppResult, ExitSender, PositionReceiver, QuietHoursReceiver, StatusReceiver, TracklistReceiver,
+    artwork_cache::ArtworkCache, client::Client, controls::Controls, database::Database,
+    error::Error, log_buffer::LogBuffer, notification::NotificationBroadcast,
 };
 use queue::QueueState;
 use ratatui::{prelude::*, widgets::*};
+use theme::{Theme, set_theme};
 use ui::center;
 
 mod app;
+mod clipboard;
 mod discover;
 mod favorites;
 mod genres;
+mod logs;
+mod mouse;
 mod now_playing;
 mod popup;
 mod queue;
+mod recent;
 mod search;
 mod sub_tab;
+pub mod theme;
 mod ui;
 mod widgets;
 
 #[allow(clippy::too_many_arguments)]
 pub async fn init(
     client: Arc<Client>,
+    database: Arc<Database>,
+    artwork_cache: Arc<ArtworkCache>,
     broadcast: Arc<NotificationBroadcast>,
     controls: Controls,
     position_receiver: PositionReceiver,
     tracklist_receiver: TracklistReceiver,
     status_receiver: StatusReceiver,
+    quiet_hours_receiver: QuietHoursReceiver,
     exit_sender: ExitSender,
     disable_tui_album_cover: bool,
+    disable_mouse: bool,
+    theme: Theme,
+    log_buffer: Arc<LogBuffer>,
 ) -> AppResult<()> {
+    set_theme(theme);
+
     let mut terminal = ratatui::init();
+    if !disable_mouse {
+        _ = crossterm::execute!(stdout(), EnableMouseCapture);
+    }
 
     draw_loading_screen(&mut terminal);
 
     let tracklist_value = tracklist_receiver.borrow().clone();
     let status_value = *status_receiver.borrow();
-    let queue_tracks = tracklist_value
-        .queue()
-        .into_iter()
-        .map(|x| x.track.clone())
-        .collect();
-    let (now_playing, current_image_url) =
+    let queue_items = tracklist_value.queue().into_iter().cloned().collect();
+    let (mut now_playing, current_image_url) =
         get_current_state_without_image(&tracklist_value, status_value);
+    now_playing.quiet_hours = *quiet_hours_receiver.borrow();
 
     let mut app = App {
         broadcast,
@@ -56,21 +72,33 @@ pub async fn init(
         position: position_receiver,
         tracklist: tracklist_receiver,
         status: status_receiver,
+        quiet_hours: quiet_hours_receiver,
         current_screen: Default::default(),
         exit: Default::default(),
         should_draw: true,
         app_state: Default::default(),
         disable_tui_album_cover,
         current_image_url,
-        favorites: FavoritesState::new(&client).await?,
-        search: Default::default(),
-        queue: QueueState::new(queue_tracks),
+        last_click: None,
+        diagnostics: Default::default(),
+        resume_point: None,
+        favorites: FavoritesState::new(&client, &database).await?,
+        search: search::SearchState::new(database.get_search_history().await?),
+        queue: QueueState::new(queue_items),
         discover: discover::DiscoverState::new(&client).await?,
         genres: genres::GenresState::new(&client).await?,
+        recent: recent::RecentState::new(database.get_recent_tracklists().await?),
+        logs_captured: log_buffer.subscribe(),
+        logs: logs::LogsState::new(log_buffer),
         client,
+        artwork_cache,
+        database,
     };
 
     _ = app.run(&mut terminal).await;
+    if !disable_mouse {
+        _ = crossterm::execute!(stdout(), DisableMouseCapture);
+    }
     ratatui::restore();
     match exit_sender.send(true) {
         Ok(_) => Ok(()),
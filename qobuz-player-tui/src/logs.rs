@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use qobuz_player_controls::log_buffer::LogBuffer;
+use ratatui::{
+    crossterm::event::{Event, KeyCode, KeyEventKind},
+    prelude::*,
+    widgets::*,
+};
+
+use crate::{
+    app::Output,
+    theme::theme,
+    ui::{COLUMN_SPACING, block},
+};
+
+pub struct LogsState {
+    buffer: Arc<LogBuffer>,
+    state: TableState,
+    /// Keep the newest line selected as lines arrive
+    follow: bool,
+}
+
+impl LogsState {
+    pub fn new(buffer: Arc<LogBuffer>) -> Self {
+        Self {
+            buffer,
+            state: Default::default(),
+            follow: true,
+        }
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let lines = self.buffer.lines();
+
+        if self.follow {
+            self.state.select(lines.len().checked_sub(1));
+        }
+
+        let rows: Vec<_> = lines
+            .into_iter()
+            .map(|line| {
+                let level_style = match line.level.as_str() {
+                    "ERROR" => Style::new().red(),
+                    "WARN" => Style::new().yellow(),
+                    _ => Style::new().dim(),
+                };
+
+                Row::new(vec![
+                    Line::from(line.time),
+                    Line::from(line.level).style(level_style),
+                    Line::from(format!("{}: {}", line.target, line.message)),
+                ])
+            })
+            .collect();
+
+        let title = match self.follow {
+            true => "Logs",
+            false => "Logs (End to follow)",
+        };
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(19),
+                Constraint::Length(5),
+                Constraint::Min(1),
+            ],
+        )
+        .row_highlight_style(theme().highlight)
+        .column_spacing(COLUMN_SPACING)
+        .block(block(Some(title)));
+
+        frame.render_stateful_widget(table, area, &mut self.state);
+    }
+
+    pub fn handle_events(&mut self, event: Event) -> Output {
+        match event {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                match key_event.code {
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.follow = false;
+                        self.state.select_next();
+                        Output::Consumed
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.follow = false;
+                        self.state.select_previous();
+                        Output::Consumed
+                    }
+                    KeyCode::End => {
+                        self.follow = true;
+                        Output::Consumed
+                    }
+                    _ => Output::NotConsumed,
+                }
+            }
+            _ => Output::NotConsumed,
+        }
+    }
+}
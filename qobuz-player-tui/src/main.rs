@@ -1,25 +1,52 @@
 use futures::executor::block_on;
 use qobuz_player_cli::{
-    ConnectArgs, SharedArgs, SharedCommands, create_player, default_audio_quality, get_client,
-    handle_shared_commands, spawn_clean_up,
+    ConnectArgs, SharedArgs, SharedCommands, Startup, StartupSummary, create_player,
+    default_audio_quality, default_output_device, get_client, handle_shared_commands, spawn_alarms,
+    spawn_clean_up, spawn_favorites_sync, spawn_group, spawn_hooks, spawn_network_monitor,
+    spawn_play_link, spawn_release_notifications, spawn_webhooks,
 };
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use qobuz_player_connect::ConnectionStatus;
+use qobuz_player_tui::theme::{Theme, ThemeName};
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::{broadcast, watch};
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
 
 use clap::Parser;
 #[cfg(any(windows, target_os = "linux", target_os = "macos"))]
 use qobuz_player_controls::StatusReceiver;
 use qobuz_player_controls::{
-    AppResult, database::Database, error::Error, notification::NotificationBroadcast,
+    AppResult, database::Database, error::Error, log_buffer::LogBuffer,
+    notification::NotificationBroadcast,
 };
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Arguments {
+    /// Handle the play/pause, next and previous media keys system wide, where neither mpris nor
+    /// the system media controls are available
+    #[clap(long)]
+    global_hotkeys: bool,
+
     /// Disable the album cover image
     #[clap(long)]
     disable_album_cover: bool,
 
+    /// Disable mouse support, leaving text selection to the terminal
+    #[clap(long)]
+    disable_mouse: bool,
+
+    /// Color theme
+    #[clap(long, value_enum, default_value_t = ThemeName::Dark)]
+    theme: ThemeName,
+
+    /// JSON file overriding the colors of the theme
+    #[clap(long)]
+    theme_file: Option<PathBuf>,
+
+    /// Number of recent log lines kept for the log viewer
+    #[clap(long, default_value_t = 1000)]
+    log_lines: usize,
+
     #[clap(flatten)]
     shared: SharedArgs,
 
@@ -30,52 +57,119 @@ struct Arguments {
     command: Option<SharedCommands>,
 }
 
-#[tokio::main]
-async fn main() {
-    match run().await {
-        Ok(()) => {}
-        Err(err) => {
-            error_exit(err);
+fn main() {
+    qobuz_player_media_controls::run_with_main_loop(|| {
+        let runtime = tokio::runtime::Runtime::new().expect("Failed building the Runtime");
+        match runtime.block_on(run()) {
+            Ok(()) => {}
+            Err(err) => {
+                error_exit(err);
+            }
         }
-    }
+    });
 }
 
 pub async fn run() -> AppResult<()> {
-    let args = Arguments::parse();
+    let mut args = Arguments::parse();
+
+    // Only kept in memory for the logs tab, since output would break the terminal ui
+    let log_buffer = LogBuffer::new(args.log_lines);
+    tracing_subscriber::registry()
+        .with(log_buffer.layer().with_filter(LevelFilter::INFO))
+        .init();
+
+    let theme = match &args.theme_file {
+        Some(path) => Theme::from_file(path, args.theme).unwrap_or_else(|message| {
+            eprintln!("Unable to load theme {}: {message}", path.display());
+            std::process::exit(1);
+        }),
+        None => Theme::builtin(args.theme),
+    };
     let database = Arc::new(Database::new().await?);
+    database.set_profile(&args.shared.profile).await?;
+    args.shared.output_device_id =
+        default_output_device(&database, args.shared.output_device_id.take()).await?;
     let headless = false;
 
-    if let Some(command) = args.command {
-        handle_shared_commands(command, &database, headless).await?;
-        return Ok(());
-    }
+    let play_link =
+        match handle_shared_commands::<Arguments>(args.command, &args.shared, &database, headless)
+            .await?
+        {
+            Startup::Run(play_link) => play_link,
+            Startup::Exit => return Ok(()),
+        };
 
     let (exit_sender, exit_receiver) = broadcast::channel(5);
 
     let max_audio_quality = default_audio_quality(&database, args.shared.max_audio_quality).await?;
-    let client = get_client(&database, max_audio_quality, headless).await?;
+    let client = get_client(
+        &database,
+        max_audio_quality,
+        headless,
+        args.shared.fixtures(),
+    )
+    .await?;
     let client = Arc::new(client);
+    spawn_favorites_sync(
+        database.clone(),
+        client.clone(),
+        args.shared.favorites_refresh_minutes,
+    )
+    .await;
+    spawn_release_notifications(database.clone(), client.clone(), &args.shared);
 
     let broadcast = Arc::new(NotificationBroadcast::new());
 
+    let mut summary = StartupSummary::new(&args.shared, max_audio_quality);
+    #[cfg(target_os = "linux")]
+    {
+        summary.mpris = qobuz_player_mpris::session_bus_available();
+    }
+    summary.global_hotkeys = args.global_hotkeys;
+    summary.connect_name = args
+        .connect
+        .connect
+        .then(|| args.connect.name_args.connect_name.clone());
+    println!("{summary}");
+
+    let audio_output = args.shared.audio_output(true)?;
+    let hooks = args.shared.hooks();
+    let group_role = args.shared.group_role();
+    let artwork_cache = args.shared.artwork();
+
     let mut player = create_player(
-        args.shared.audio_cache,
+        &args.shared,
+        None,
+        audio_output,
         database.clone(),
         client.clone(),
         broadcast.clone(),
-        None,
-        None,
-        args.shared.output_device_id,
     )
     .await?;
+    spawn_network_monitor(client.clone(), player.controls());
+    spawn_alarms(
+        database.clone(),
+        client.clone(),
+        &player,
+        broadcast.clone(),
+        args.shared.alarm_ramp_seconds,
+    );
+    spawn_play_link(
+        play_link,
+        client.clone(),
+        player.controls(),
+        broadcast.clone(),
+    );
 
     #[cfg(target_os = "linux")]
-    {
+    if summary.mpris {
         let position_receiver = player.position();
         let tracklist_receiver = player.tracklist();
         let volume_receiver = player.volume();
         let status_receiver = player.status();
         let controls = player.controls();
+        let broadcast = broadcast.clone();
+        let artwork_cache = artwork_cache.clone();
         let exit_sender = exit_sender.clone();
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_mpris::init(
@@ -84,6 +178,8 @@ pub async fn run() -> AppResult<()> {
                 volume_receiver,
                 status_receiver,
                 controls,
+                broadcast,
+                artwork_cache,
                 exit_sender,
             )
             .await
@@ -93,6 +189,37 @@ pub async fn run() -> AppResult<()> {
         });
     }
 
+    #[cfg(any(windows, target_os = "macos"))]
+    let media_keys_handled = match qobuz_player_media_controls::init(
+        player.position(),
+        player.tracklist(),
+        player.status(),
+        player.controls(),
+        exit_sender.clone(),
+    ) {
+        Ok(media_controls) => {
+            let broadcast = broadcast.clone();
+            tokio::spawn(async move {
+                if let Err(e) = media_controls.await {
+                    broadcast.report(&e);
+                }
+            });
+            true
+        }
+        Err(e) => {
+            broadcast.report(&e);
+            false
+        }
+    };
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let media_keys_handled = summary.mpris;
+
+    // The system media controls already get the media keys, registering them twice would
+    // toggle twice
+    if args.global_hotkeys && !media_keys_handled {
+        qobuz_player_hotkeys::init(player.controls())?;
+    }
+
     #[cfg(any(windows, target_os = "linux", target_os = "macos"))]
     {
         let status_receiver = player.status();
@@ -102,27 +229,35 @@ pub async fn run() -> AppResult<()> {
     let position_receiver = player.position();
     let tracklist_receiver = player.tracklist();
     let status_receiver = player.status();
+    let quiet_hours_receiver = player.quiet_hours();
     let controls = player.controls();
     let client = client.clone();
     let broadcast = broadcast.clone();
+    let tui_database = database.clone();
 
     if args.connect.connect {
         let app_id = client.app_id().await?;
         let position_receiver = player.position();
         let tracklist_receiver = player.tracklist();
         let volume_receiver = player.volume();
+        let muted_receiver = player.muted();
         let status_receiver = player.status();
         let controls = player.controls();
+        let broadcast = broadcast.clone();
+        let (connection_status, _) = watch::channel(ConnectionStatus::default());
 
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_connect::init(
                 &app_id,
                 args.connect.name_args.connect_name,
                 controls,
+                broadcast,
+                connection_status,
                 position_receiver,
                 tracklist_receiver,
                 status_receiver,
                 volume_receiver,
+                muted_receiver,
                 max_audio_quality,
             )
             .await
@@ -135,13 +270,19 @@ pub async fn run() -> AppResult<()> {
     tokio::spawn(async move {
         if let Err(e) = qobuz_player_tui::init(
             client,
+            tui_database,
+            artwork_cache,
             broadcast,
             controls,
             position_receiver,
             tracklist_receiver,
             status_receiver,
+            quiet_hours_receiver,
             exit_sender,
             args.disable_album_cover,
+            args.disable_mouse,
+            theme,
+            log_buffer,
         )
         .await
         {
@@ -149,6 +290,14 @@ pub async fn run() -> AppResult<()> {
         };
     });
 
+    spawn_webhooks(args.shared.webhook_urls.clone(), &player);
+
+    spawn_hooks(hooks, &player);
+
+    if let Some(group_role) = group_role {
+        spawn_group(group_role, &player);
+    }
+
     spawn_clean_up(database, args.shared.audio_cache_time_to_live);
     player.player_loop(exit_receiver).await?;
 
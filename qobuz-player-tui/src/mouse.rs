@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+
+use ratatui::{layout::Rect, widgets::TableState};
+
+/// Screen regions of the last drawn frame that react to mouse clicks.
+#[derive(Default)]
+struct Regions {
+    tabs: Vec<Rect>,
+    progress: Option<Rect>,
+    tables: Vec<ClickableTable>,
+}
+
+struct ClickableTable {
+    rows: Rect,
+    offset: usize,
+    selected: Option<usize>,
+    len: usize,
+}
+
+static REGIONS: Mutex<Regions> = Mutex::new(Regions {
+    tabs: Vec::new(),
+    progress: None,
+    tables: Vec::new(),
+});
+
+pub enum Target {
+    Tab(usize),
+    /// Position in the progress bar, in percent
+    Progress(f32),
+    Row {
+        index: usize,
+        selected: Option<usize>,
+    },
+}
+
+pub fn clear() {
+    *REGIONS.lock().expect("infallible") = Default::default();
+}
+
+/// Registers the labels of a tab bar rendered in `area`, padded and divided like `Tabs`.
+pub fn register_tabs(area: Rect, labels: &[String]) {
+    let mut x = area.x;
+    let tabs = labels
+        .iter()
+        .map(|label| {
+            let width = label.chars().count() as u16 + 2;
+            let rect = Rect::new(x, area.y, width, area.height).intersection(area);
+            x = x.saturating_add(width + 1);
+            rect
+        })
+        .collect();
+
+    REGIONS.lock().expect("infallible").tabs = tabs;
+}
+
+pub fn register_progress(area: Rect) {
+    REGIONS.lock().expect("infallible").progress = Some(area);
+}
+
+/// Registers a table after it has been rendered, so its scroll offset is up to date.
+/// `area` is the table area inside any border.
+pub fn register_table(area: Rect, header: bool, state: &TableState, len: usize) {
+    let header_height = u16::from(header).min(area.height);
+    let rows = Rect {
+        y: area.y + header_height,
+        height: area.height - header_height,
+        ..area
+    };
+
+    REGIONS
+        .lock()
+        .expect("infallible")
+        .tables
+        .push(ClickableTable {
+            rows,
+            offset: state.offset(),
+            selected: state.selected(),
+            len,
+        });
+}
+
+pub fn target(column: u16, row: u16) -> Option<Target> {
+    let regions = REGIONS.lock().expect("infallible");
+    let position = (column, row).into();
+
+    if let Some(index) = regions.tabs.iter().position(|tab| tab.contains(position)) {
+        return Some(Target::Tab(index));
+    }
+
+    if let Some(progress) = regions.progress.filter(|area| area.contains(position)) {
+        let offset = (column - progress.x) as f32;
+        return Some(Target::Progress(
+            offset / progress.width.max(1) as f32 * 100.0,
+        ));
+    }
+
+    // Tables drawn last, like popups, are on top
+    regions
+        .tables
+        .iter()
+        .rev()
+        .find(|table| table.rows.contains(position))
+        .and_then(|table| {
+            let index = table.offset + (row - table.rows.y) as usize;
+            (index < table.len).then_some(Target::Row {
+                index,
+                selected: table.selected,
+            })
+        })
+}
@@ -1,5 +1,14 @@
-use crate::ui::{HIGHLIGHT_TEXT_STYLE, block, format_mseconds, format_seconds};
-use qobuz_player_controls::{Status, models::Track};
+use crate::{
+    mouse,
+    theme::theme,
+    ui::{block, format_mseconds, format_seconds},
+};
+use qobuz_player_controls::{
+    Status,
+    models::Track,
+    notification::{BufferProgress, TrackFormat},
+    quiet_hours::QuietHours,
+};
 use ratatui::{prelude::*, widgets::*};
 use ratatui_image::{StatefulImage, protocol::StatefulProtocol};
 
@@ -12,6 +21,11 @@ pub struct NowPlayingState {
     pub tracklist_position: usize,
     pub status: Status,
     pub duration_ms: u32,
+    pub buffer_progress: Option<BufferProgress>,
+    /// May belong to the previous track until the new one has started
+    pub stream_format: Option<TrackFormat>,
+    pub is_favorite: bool,
+    pub quiet_hours: Option<QuietHours>,
 }
 
 pub fn render(
@@ -26,7 +40,19 @@ pub fn render(
         None => return,
     };
 
-    let title = get_status(state.status).to_string();
+    let title = match state
+        .buffer_progress
+        .filter(|progress| !progress.is_complete())
+    {
+        Some(progress) => format!(
+            "{} {}% ({} / {})",
+            get_status(state.status),
+            progress.percent,
+            format_megabytes(progress.downloaded_bytes),
+            format_megabytes(progress.total_bytes),
+        ),
+        None => get_status(state.status),
+    };
     let block = block(Some(&title));
 
     let length = state
@@ -70,7 +96,13 @@ pub fn render(
         lines.push(Line::from(artist.clone()));
     }
 
-    lines.push(Line::from(track.title.clone()));
+    match state.is_favorite {
+        true => lines.push(Line::from(vec![
+            Span::from(track.title.clone()),
+            Span::from(" ★").style(theme().accent),
+        ])),
+        false => lines.push(Line::from(track.title.clone())),
+    }
 
     lines.push(Line::from(format!(
         "{} of {}",
@@ -78,6 +110,24 @@ pub fn render(
         state.tracklist_length
     )));
 
+    if let Some(stream_format) = state
+        .stream_format
+        .filter(|stream_format| stream_format.track_id == track.id)
+    {
+        lines.push(Line::from(stream_format.format.to_string()).style(Style::new().dim()));
+    }
+
+    if let Some(quiet_hours) = state.quiet_hours {
+        lines.push(
+            Line::from(format!(
+                "Quiet hours until {}, volume up to {}%",
+                quiet_hours.end_time(),
+                quiet_hours.max_volume
+            ))
+            .style(Style::new().dim()),
+        );
+    }
+
     let duration = if state.duration_ms < track.duration_seconds * 1000 {
         state.duration_ms
     } else {
@@ -94,13 +144,18 @@ pub fn render(
 
     let gauge = Gauge::default()
         .ratio(ratio)
-        .gauge_style(HIGHLIGHT_TEXT_STYLE)
+        .gauge_style(theme().accent)
         .label(label);
 
     frame.render_widget(gauge, info_chunks[1]);
+    mouse::register_progress(info_chunks[1]);
     frame.render_widget(Text::from(lines), info_chunks[0]);
 }
 
+fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
 fn get_status(state: Status) -> String {
     match state {
         Status::Playing => "Playing ⏵".to_string(),
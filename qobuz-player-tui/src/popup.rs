@@ -2,7 +2,9 @@ use qobuz_player_controls::{
     AppResult,
     client::Client,
     controls::Controls,
-    models::{Album, Artist, Playlist, PlaylistSimple, Track},
+    database::Profile,
+    models::{Album, AlbumWork, Artist, DiscographySort, Label, Playlist, PlaylistSimple, Track},
+    smart_playlists::SmartPlaylist,
 };
 use ratatui::{
     crossterm::event::{Event, KeyCode, KeyEventKind},
@@ -13,7 +15,8 @@ use tui_input::{Input, backend::crossterm::EventHandler};
 
 use crate::{
     app::{NotificationList, Output},
-    ui::{block, center, centered_rect_fixed, render_input, tab_bar},
+    theme::theme,
+    ui::{block, center, centered_rect_fixed, render_album_info, render_input, tab_bar},
     widgets::{
         album_list::AlbumList,
         playlist_list::PlaylistList,
@@ -30,6 +33,7 @@ pub struct ArtistPopupState {
     selected_sub_tab: usize,
     top_tracks: TrackList,
     id: u32,
+    sort: DiscographySort,
 }
 
 enum SelectedArtistPopupSubtabMut<'a> {
@@ -72,6 +76,7 @@ impl ArtistPopupState {
             selected_sub_tab: 0,
             top_tracks: TrackList::new(artist_page.top_tracks),
             id: artist.id,
+            sort: DiscographySort::default(),
         };
 
         if !is_album_empty {
@@ -84,6 +89,33 @@ impl ArtistPopupState {
         Ok(state)
     }
 
+    fn title(&self) -> String {
+        format!(
+            "{} (by {})",
+            self.artist_name,
+            self.sort.label().to_lowercase()
+        )
+    }
+
+    /// Reloads the releases in the next sort order
+    async fn cycle_sort(&mut self, client: &Client) -> AppResult<()> {
+        let values = DiscographySort::VALUES;
+        let position = values
+            .iter()
+            .position(|sort| *sort == self.sort)
+            .unwrap_or(0);
+        let sort = values[(position + 1) % values.len()];
+
+        let artist_page = client.artist_page_sorted(self.id, sort).await?;
+        self.sort = sort;
+        self.albums.set_all_items(artist_page.albums);
+        self.singles.set_all_items(artist_page.singles);
+        self.live.set_all_items(artist_page.live);
+        self.compilations.set_all_items(artist_page.compilations);
+
+        Ok(())
+    }
+
     fn cycle_subtab_backwards(&mut self) {
         let count = self.tabs().len();
         self.selected_sub_tab = (self.selected_sub_tab + count - 1) % count;
@@ -187,18 +219,107 @@ impl ArtistPopupState {
     }
 }
 
+pub struct ComposerPopupState {
+    name: String,
+    works: TrackList,
+    albums: AlbumList,
+    show_albums: bool,
+}
+
+impl ComposerPopupState {
+    pub async fn new(composer: &Artist, client: &Client) -> AppResult<Self> {
+        let composer_page = client.composer(composer.id).await?;
+
+        // Movements are listed under the title of their work
+        let works = composer_page
+            .works
+            .into_iter()
+            .flat_map(|work| {
+                work.tracks.into_iter().map(move |mut track| {
+                    if track.work.is_some() {
+                        track.title = format!("{}: {}", work.title, track.title);
+                    }
+                    track
+                })
+            })
+            .collect();
+
+        let mut state = Self {
+            name: composer.name.clone(),
+            works: TrackList::new(works),
+            albums: AlbumList::new(composer_page.albums),
+            show_albums: false,
+        };
+        state.works.select_first();
+        state.albums.select_first();
+
+        Ok(state)
+    }
+
+    fn row_count(&self) -> usize {
+        match self.show_albums {
+            true => self.albums.filter().len(),
+            false => self.works.filter().len(),
+        }
+    }
+}
+
+/// Catalogue of a record label
+pub struct LabelPopupState {
+    name: String,
+    albums: AlbumList,
+}
+
+impl LabelPopupState {
+    pub async fn new(label: &Label, client: &Client) -> AppResult<Self> {
+        let label_page = client.label(label.id).await?;
+
+        let mut state = Self {
+            name: label.name.clone(),
+            albums: AlbumList::new(label_page.albums),
+        };
+        state.albums.select_first();
+
+        Ok(state)
+    }
+}
+
 pub struct AlbumPopupState {
     title: String,
     tracks: TrackList,
+    works: Vec<AlbumWork>,
     id: String,
+    /// Shown with `i`
+    info: Album,
 }
 
 impl AlbumPopupState {
     pub fn new(album: Album) -> Self {
         let is_empty = album.tracks.is_empty();
+        let works = album.works();
+
+        // Movements are indented below the first movement, which carries the work title
+        let tracks = works
+            .iter()
+            .flat_map(|work| {
+                work.tracks.iter().enumerate().map(|(index, track)| {
+                    let mut track = track.clone();
+                    if let Some(title) = &work.title {
+                        track.title = match index {
+                            0 => format!("{}: {}", title, track.title),
+                            _ => format!("  {}", track.title),
+                        };
+                    }
+                    track
+                })
+            })
+            .collect();
+
         let mut state = Self {
+            info: album.clone(),
             title: album.title,
-            tracks: TrackList::new(album.tracks),
+            tracks: TrackList::new(tracks),
+            works,
             id: album.id,
         };
 
@@ -207,6 +328,10 @@ impl AlbumPopupState {
         }
         state
     }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
 }
 
 pub struct PlaylistPopupState {
@@ -233,6 +358,40 @@ impl PlaylistPopupState {
     }
 }
 
+pub struct SmartPlaylistsPopupState {
+    state: ListState,
+}
+
+impl SmartPlaylistsPopupState {
+    pub fn new() -> Self {
+        Self {
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+}
+
+pub struct SmartPlaylistPopupState {
+    shuffle: bool,
+    tracks: TrackList,
+    title: &'static str,
+}
+
+impl SmartPlaylistPopupState {
+    pub fn new(kind: SmartPlaylist, tracks: Vec<Track>) -> Self {
+        let is_empty = tracks.is_empty();
+        let mut state = Self {
+            tracks: TrackList::new(tracks),
+            title: kind.title(),
+            shuffle: false,
+        };
+
+        if !is_empty {
+            state.tracks.select_first();
+        }
+        state
+    }
+}
+
 pub struct DeletePlaylistPopupstate {
     title: String,
     id: u32,
@@ -283,14 +442,78 @@ impl NewPlaylistPopupState {
     }
 }
 
+pub struct PlaylistTagsPopupState {
+    title: String,
+    id: u32,
+    /// Separated by commas
+    tags: Input,
+}
+
+impl PlaylistTagsPopupState {
+    pub fn new(playlist: &PlaylistSimple, tags: &[String]) -> Self {
+        Self {
+            title: playlist.title.clone(),
+            id: playlist.id,
+            tags: Input::new(tags.join(", ")),
+        }
+    }
+}
+
+pub struct ProfilePopupState {
+    profiles: Vec<Profile>,
+    state: ListState,
+}
+
+impl ProfilePopupState {
+    pub fn new(profiles: Vec<Profile>, current: &str) -> Self {
+        let selected = profiles.iter().position(|profile| profile.name == current);
+        Self {
+            profiles,
+            state: ListState::default().with_selected(selected),
+        }
+    }
+}
+
+/// Opens the album of the track, on `g` in track lists and the queue
+pub async fn go_to_track_album(track: &Track, client: &Client) -> AppResult<Output> {
+    let Some(album_id) = &track.album_id else {
+        return Ok(Output::Consumed);
+    };
+
+    let album = client.album(album_id).await?;
+    Ok(Output::Popup(Popup::Album(AlbumPopupState::new(album))))
+}
+
+/// Opens the artist of the track, on `G` in track lists and the queue
+pub async fn go_to_track_artist(track: &Track, client: &Client) -> AppResult<Output> {
+    let Some(artist_id) = track.artist_id else {
+        return Ok(Output::Consumed);
+    };
+
+    let artist = Artist {
+        id: artist_id,
+        name: track.artist_name.clone().unwrap_or_default(),
+        image: None,
+    };
+    let state = ArtistPopupState::new(&artist, client).await?;
+    Ok(Output::Popup(Popup::Artist(state)))
+}
+
 #[allow(clippy::large_enum_variant)]
 pub enum Popup {
     Artist(ArtistPopupState),
+    Composer(ComposerPopupState),
+    Label(LabelPopupState),
     Album(AlbumPopupState),
     Playlist(PlaylistPopupState),
+    SmartPlaylists(SmartPlaylistsPopupState),
+    SmartPlaylist(SmartPlaylistPopupState),
     Track(TrackPopupState),
     NewPlaylist(NewPlaylistPopupState),
     DeletePlaylist(DeletePlaylistPopupstate),
+    PlaylistTags(PlaylistTagsPopupState),
+    Profile(ProfilePopupState),
+    AlbumInfo(Album),
 }
 
 impl Popup {
@@ -325,7 +548,8 @@ impl Popup {
 
                 let area = centered_rect_fixed(popup_width, popup_height, frame.area());
 
-                let outer_block = block(Some(&artist.artist_name));
+                let title = artist.title();
+                let outer_block = block(Some(&title));
 
                 let tabs = tab_bar(artist.tabs(), artist.selected_sub_tab);
 
@@ -352,6 +576,46 @@ impl Popup {
                     }
                 }
             }
+            Popup::Composer(composer) => {
+                let visible_rows = (composer.row_count() + 1).min(15) as u16;
+
+                let popup_height =
+                    (visible_rows + 4).clamp(4, frame.area().height.saturating_sub(2));
+                let popup_width = (frame.area().width * 75 / 100).max(30);
+                let area = centered_rect_fixed(popup_width, popup_height, frame.area());
+
+                let outer_block = block(Some(&composer.name));
+                let tabs = tab_bar(vec!["Works", "Recordings"], composer.show_albums.into());
+
+                frame.render_widget(Clear, area);
+                frame.render_widget(&outer_block, area);
+
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(2), Constraint::Min(1)])
+                    .split(outer_block.inner(area));
+
+                frame.render_widget(tabs, chunks[0]);
+
+                match composer.show_albums {
+                    true => composer.albums.render(chunks[1], frame.buffer_mut()),
+                    false => composer.works.render(chunks[1], frame.buffer_mut(), true),
+                }
+            }
+            Popup::Label(label) => {
+                let visible_rows = (label.albums.filter().len() + 1).min(15) as u16;
+
+                let popup_height =
+                    (visible_rows + 2).clamp(4, frame.area().height.saturating_sub(2));
+                let popup_width = (frame.area().width * 75 / 100).max(30);
+                let area = centered_rect_fixed(popup_width, popup_height, frame.area());
+
+                let block = block(Some(&label.name));
+
+                frame.render_widget(Clear, area);
+                frame.render_widget(&block, area);
+                label.albums.render(block.inner(area), frame.buffer_mut());
+            }
             Popup::Playlist(playlist_state) => {
                 let visible_rows = playlist_state.tracks.filter().len().min(15) as u16;
 
@@ -391,6 +655,55 @@ impl Popup {
                     .render(chunks[0], frame.buffer_mut(), true);
                 frame.render_widget(buttons, chunks[2]);
             }
+            Popup::SmartPlaylists(state) => {
+                let area = center(
+                    frame.area(),
+                    Constraint::Percentage(50),
+                    Constraint::Length(SmartPlaylist::ALL.len() as u16 + 2),
+                );
+
+                let items = SmartPlaylist::ALL
+                    .iter()
+                    .map(|kind| ListItem::new(kind.title()));
+                let list = List::new(items)
+                    .block(block(Some("Smart playlists")))
+                    .highlight_style(theme().highlight);
+
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(list, area, &mut state.state);
+            }
+            Popup::SmartPlaylist(smart_state) => {
+                let visible_rows = smart_state.tracks.filter().len().min(15) as u16;
+                let popup_height =
+                    (visible_rows + 3 + 2).clamp(4, frame.area().height.saturating_sub(2));
+                let popup_width = (frame.area().width * 75 / 100).max(30);
+                let area = centered_rect_fixed(popup_width, popup_height, frame.area());
+
+                let buttons = tab_bar(
+                    ["Play", "Shuffle"].into(),
+                    if smart_state.shuffle { 1 } else { 0 },
+                );
+                let block = block(Some(smart_state.title));
+
+                frame.render_widget(Clear, area);
+
+                let inner = block.inner(area);
+                frame.render_widget(block, area);
+
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Min(1),
+                        Constraint::Length(1),
+                        Constraint::Length(1),
+                    ])
+                    .split(inner);
+
+                smart_state
+                    .tracks
+                    .render(chunks[0], frame.buffer_mut(), true);
+                frame.render_widget(buttons, chunks[2]);
+            }
             Popup::Track(track_state) => {
                 let area = center(
                     frame.area(),
@@ -417,6 +730,17 @@ impl Popup {
                 frame.render_widget(Clear, area);
                 render_input(&state.name, false, area, frame, "Create playlist");
             }
+            Popup::PlaylistTags(state) => {
+                let area = center(
+                    frame.area(),
+                    Constraint::Percentage(75),
+                    Constraint::Length(3),
+                );
+
+                let title = format!("Tags of {}, separated by commas", state.title);
+                frame.render_widget(Clear, area);
+                render_input(&state.tags, true, area, frame, &title);
+            }
             Popup::DeletePlaylist(state) => {
                 let block_title = format!("Delete {}?", state.title);
                 let area = center(
@@ -434,6 +758,28 @@ impl Popup {
                 frame.render_widget(Clear, area);
                 frame.render_widget(tabs, area);
             }
+            Popup::Profile(state) => {
+                let area = center(
+                    frame.area(),
+                    Constraint::Percentage(50),
+                    Constraint::Length(state.profiles.len() as u16 + 2),
+                );
+
+                let items = state
+                    .profiles
+                    .iter()
+                    .map(|profile| match profile.logged_in {
+                        true => ListItem::new(profile.name.clone()),
+                        false => ListItem::new(format!("{} (not logged in)", profile.name)),
+                    });
+                let list = List::new(items)
+                    .block(block(Some("Switch profile")))
+                    .highlight_style(theme().highlight);
+
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(list, area, &mut state.state);
+            }
+            Popup::AlbumInfo(album) => render_album_info(frame, album, &mut None),
         };
     }
 
@@ -446,18 +792,46 @@ impl Popup {
     ) -> AppResult<Output> {
         match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => match self {
-                Popup::Album(album_state) => {
-                    album_state
-                        .tracks
-                        .handle_events(
-                            key_event.code,
-                            client,
-                            controls,
-                            notifications,
-                            TrackListEvent::Album(album_state.id.clone()),
-                        )
-                        .await
-                }
+                Popup::Album(album_state) => match key_event.code {
+                    KeyCode::Char('i') => {
+                        Ok(Output::Popup(Popup::AlbumInfo(album_state.info.clone())))
+                    }
+                    KeyCode::Char('L') => match &album_state.info.label {
+                        Some(label) => {
+                            let state = LabelPopupState::new(label, client).await?;
+                            Ok(Output::Popup(Popup::Label(state)))
+                        }
+                        None => Ok(Output::Consumed),
+                    },
+                    KeyCode::Char('w') => {
+                        let work = album_state.tracks.selected().and_then(|selected| {
+                            album_state.works.iter().find(|work| {
+                                work.title.is_some()
+                                    && work.tracks.iter().any(|track| track.id == selected.id)
+                            })
+                        });
+
+                        let Some(work) = work else {
+                            return Ok(Output::Consumed);
+                        };
+
+                        controls
+                            .play_tracks(work.tracks.iter().map(|track| track.id).collect(), false);
+                        Ok(Output::Consumed)
+                    }
+                    _ => {
+                        album_state
+                            .tracks
+                            .handle_events(
+                                key_event.code,
+                                client,
+                                controls,
+                                notifications,
+                                TrackListEvent::Album(album_state.id.clone()),
+                            )
+                            .await
+                    }
+                },
                 Popup::Artist(artist_popup_state) => match key_event.code {
                     KeyCode::Left | KeyCode::Char('h') => {
                         artist_popup_state.cycle_subtab_backwards();
@@ -468,6 +842,15 @@ impl Popup {
                         artist_popup_state.cycle_subtab();
                         Ok(Output::Consumed)
                     }
+
+                    KeyCode::Char('o') => {
+                        artist_popup_state.cycle_sort(client).await?;
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Char('r') => Ok(Output::ToggleFollowArtist(
+                        artist_popup_state.id,
+                        artist_popup_state.artist_name.clone(),
+                    )),
                     _ => {
                         let artist_id = artist_popup_state.id;
                         let current_state = artist_popup_state.current_state_mut();
@@ -499,6 +882,38 @@ impl Popup {
                         }
                     }
                 },
+                Popup::Composer(composer_popup_state) => match key_event.code {
+                    KeyCode::Left | KeyCode::Char('h') | KeyCode::Right | KeyCode::Char('l') => {
+                        composer_popup_state.show_albums = !composer_popup_state.show_albums;
+                        Ok(Output::Consumed)
+                    }
+                    _ => match composer_popup_state.show_albums {
+                        true => {
+                            composer_popup_state
+                                .albums
+                                .handle_events(key_event.code, client, controls, notifications)
+                                .await
+                        }
+                        false => {
+                            composer_popup_state
+                                .works
+                                .handle_events(
+                                    key_event.code,
+                                    client,
+                                    controls,
+                                    notifications,
+                                    TrackListEvent::Track,
+                                )
+                                .await
+                        }
+                    },
+                },
+                Popup::Label(label_popup_state) => {
+                    label_popup_state
+                        .albums
+                        .handle_events(key_event.code, client, controls, notifications)
+                        .await
+                }
                 Popup::Playlist(playlist_popup_state) => match key_event.code {
                     KeyCode::Left | KeyCode::Char('h') | KeyCode::Right | KeyCode::Char('l') => {
                         playlist_popup_state.shuffle = !playlist_popup_state.shuffle;
@@ -520,6 +935,46 @@ impl Popup {
                             .await
                     }
                 },
+                Popup::SmartPlaylists(state) => match key_event.code {
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        state.state.select_previous();
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        state.state.select_next();
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Enter => {
+                        let kind = state
+                            .state
+                            .selected()
+                            .and_then(|index| SmartPlaylist::ALL.get(index));
+
+                        match kind {
+                            Some(kind) => Ok(Output::OpenSmartPlaylist(*kind)),
+                            None => Ok(Output::Consumed),
+                        }
+                    }
+                    _ => Ok(Output::NotConsumed),
+                },
+                Popup::SmartPlaylist(smart_state) => match key_event.code {
+                    KeyCode::Left | KeyCode::Char('h') | KeyCode::Right | KeyCode::Char('l') => {
+                        smart_state.shuffle = !smart_state.shuffle;
+                        Ok(Output::Consumed)
+                    }
+                    _ => {
+                        smart_state
+                            .tracks
+                            .handle_events(
+                                key_event.code,
+                                client,
+                                controls,
+                                notifications,
+                                TrackListEvent::Tracks(smart_state.shuffle),
+                            )
+                            .await
+                    }
+                },
                 Popup::Track(track_popup_state) => match key_event.code {
                     KeyCode::Up | KeyCode::Char('k') => {
                         track_popup_state.select_previous();
@@ -559,6 +1014,16 @@ impl Popup {
                         Ok(Output::Consumed)
                     }
                 },
+                Popup::PlaylistTags(state) => match key_event.code {
+                    KeyCode::Enter => {
+                        let tags = state.tags.value().split(',').map(String::from).collect();
+                        Ok(Output::SetPlaylistTags(state.id, tags))
+                    }
+                    _ => {
+                        state.tags.handle_event(&event);
+                        Ok(Output::Consumed)
+                    }
+                },
                 Popup::DeletePlaylist(state) => match key_event.code {
                     KeyCode::Enter => {
                         if state.confirm {
@@ -574,6 +1039,29 @@ impl Popup {
                     }
                     _ => Ok(Output::Consumed),
                 },
+                Popup::Profile(state) => match key_event.code {
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        state.state.select_previous();
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        state.state.select_next();
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Enter => {
+                        let profile = state
+                            .state
+                            .selected()
+                            .and_then(|index| state.profiles.get(index));
+
+                        match profile {
+                            Some(profile) => Ok(Output::SwitchProfile(profile.name.clone())),
+                            None => Ok(Output::Consumed),
+                        }
+                    }
+                    _ => Ok(Output::NotConsumed),
+                },
+                Popup::AlbumInfo(_) => Ok(Output::Consumed),
             },
             _ => Ok(Output::Consumed),
         }
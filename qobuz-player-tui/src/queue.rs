@@ -1,6 +1,5 @@
 use qobuz_player_controls::{
-    controls::Controls,
-    models::{Track, TrackStatus},
+    AppResult, client::Client, controls::Controls, models::TrackStatus, tracklist::QueueItem,
 };
 use ratatui::{
     crossterm::event::{Event, KeyCode, KeyEventKind},
@@ -11,18 +10,21 @@ use ratatui::{
 
 use crate::{
     app::Output,
-    ui::{basic_list_table, block, mark_explicit_and_hifi},
+    mouse,
+    popup::{go_to_track_album, go_to_track_artist},
+    theme::theme,
+    ui::{basic_list_table, block, mark_explicit_and_hifi, mark_streaming_details},
 };
 
 pub struct QueueState {
-    items: Vec<Track>,
+    items: Vec<QueueItem>,
     state: TableState,
 }
 
 impl QueueState {
-    pub fn new(tracks: Vec<Track>) -> Self {
+    pub fn new(items: Vec<QueueItem>) -> Self {
         Self {
-            items: tracks,
+            items,
             state: Default::default(),
         }
     }
@@ -31,23 +33,31 @@ impl QueueState {
             self.items
                 .iter()
                 .enumerate()
-                .map(|(index, track)| {
+                .map(|(index, item)| {
+                    let track = &item.track;
                     let style = match track.status {
                         TrackStatus::Played => Style::default().add_modifier(Modifier::CROSSED_OUT),
-                        TrackStatus::Playing => Style::default().add_modifier(Modifier::BOLD),
+                        TrackStatus::Playing => theme().playing,
+                        TrackStatus::Unplayed if item.queued_by_user => theme().accent,
                         TrackStatus::Unplayed => Style::default(),
                         TrackStatus::Unplayable => {
                             Style::default().add_modifier(Modifier::CROSSED_OUT)
                         }
                     };
+                    // Tracks added by the user are marked, so they stand out from the played list
+                    let marker = if item.queued_by_user { "+" } else { " " };
                     Row::new(Line::from(vec![
                         format!(
-                            "{} {}",
+                            "{}{} {}",
                             index + 1,
-                            mark_explicit_and_hifi(
-                                track.title.clone(),
-                                track.explicit,
-                                track.hires_available
+                            marker,
+                            mark_streaming_details(
+                                mark_explicit_and_hifi(
+                                    track.title.clone(),
+                                    track.explicit,
+                                    track.hires_available
+                                ),
+                                track
                             )
                         )
                         .set_style(style),
@@ -58,34 +68,45 @@ impl QueueState {
         .block(block(None));
 
         frame.render_stateful_widget(table, area, &mut self.state);
+        mouse::register_table(
+            area.inner(Margin::new(1, 1)),
+            false,
+            &self.state,
+            self.items.len(),
+        );
     }
 
-    pub fn items(&self) -> &Vec<Track> {
+    pub fn items(&self) -> &Vec<QueueItem> {
         &self.items
     }
 
-    pub fn set_items(&mut self, items: Vec<Track>) {
+    pub fn set_items(&mut self, items: Vec<QueueItem>) {
         self.items = items
     }
 
-    pub async fn handle_events(&mut self, event: Event, controls: &Controls) -> Output {
+    pub async fn handle_events(
+        &mut self,
+        event: Event,
+        client: &Client,
+        controls: &Controls,
+    ) -> AppResult<Output> {
         match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
                 match key_event.code {
                     KeyCode::Down | KeyCode::Char('j') => {
                         self.state.select_next();
-                        Output::Consumed
+                        Ok(Output::Consumed)
                     }
                     KeyCode::Up | KeyCode::Char('k') => {
                         self.state.select_previous();
-                        Output::Consumed
+                        Ok(Output::Consumed)
                     }
                     KeyCode::Char('d') => {
                         let index = self.state.selected();
 
                         if let Some(index) = index {
                             if index == self.items().len() - 1 {
-                                return Output::Consumed;
+                                return Ok(Output::Consumed);
                             }
 
                             let mut order: Vec<_> =
@@ -94,14 +115,14 @@ impl QueueState {
                             order.swap(index, index + 1);
                             controls.reorder_queue(order);
                         }
-                        Output::Consumed
+                        Ok(Output::Consumed)
                     }
                     KeyCode::Char('u') => {
                         let index = self.state.selected();
 
                         if let Some(index) = index {
                             if index == 0 {
-                                return Output::Consumed;
+                                return Ok(Output::Consumed);
                             }
                             let mut order: Vec<_> =
                                 self.items().iter().enumerate().map(|x| x.0).collect();
@@ -109,7 +130,7 @@ impl QueueState {
                             order.swap(index, index - 1);
                             controls.reorder_queue(order);
                         }
-                        Output::Consumed
+                        Ok(Output::Consumed)
                     }
                     KeyCode::Char('D') => {
                         let index = self.state.selected();
@@ -117,7 +138,32 @@ impl QueueState {
                         if let Some(index) = index {
                             controls.remove_index_from_queue(index);
                         }
-                        Output::Consumed
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Char('c') => {
+                        let playing = self
+                            .items
+                            .iter()
+                            .position(|item| item.track.status == TrackStatus::Playing);
+
+                        if playing.is_some() {
+                            self.state.select(playing);
+                        }
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Char('g') | KeyCode::Char('G') => {
+                        let selected = self
+                            .state
+                            .selected()
+                            .and_then(|index| self.items.get(index));
+
+                        match (selected, key_event.code) {
+                            (Some(item), KeyCode::Char('g')) => {
+                                go_to_track_album(&item.track, client).await
+                            }
+                            (Some(item), _) => go_to_track_artist(&item.track, client).await,
+                            (None, _) => Ok(Output::NotConsumed),
+                        }
                     }
                     KeyCode::Enter => {
                         let index = self.state.selected();
@@ -125,13 +171,13 @@ impl QueueState {
                         if let Some(index) = index {
                             controls.skip_to_position(index, true);
                         }
-                        Output::Consumed
+                        Ok(Output::Consumed)
                     }
 
-                    _ => Output::NotConsumed,
+                    _ => Ok(Output::NotConsumed),
                 }
             }
-            _ => Output::NotConsumed,
+            _ => Ok(Output::NotConsumed),
         }
     }
 }
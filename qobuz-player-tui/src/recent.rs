@@ -0,0 +1,99 @@
+use qobuz_player_controls::{controls::Controls, tracklist::TracklistType};
+use ratatui::{
+    crossterm::event::{Event, KeyCode, KeyEventKind},
+    prelude::*,
+    widgets::*,
+};
+
+use crate::{
+    app::Output,
+    mouse,
+    theme::theme,
+    ui::{COLUMN_SPACING, block},
+};
+
+pub struct RecentState {
+    items: Vec<TracklistType>,
+    state: TableState,
+}
+
+impl RecentState {
+    pub fn new(items: Vec<TracklistType>) -> Self {
+        Self {
+            items,
+            state: Default::default(),
+        }
+    }
+
+    pub fn set_items(&mut self, items: Vec<TracklistType>) {
+        self.items = items;
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<_> = self
+            .items
+            .iter()
+            .map(|list_type| {
+                let (title, kind) = match list_type {
+                    TracklistType::Album(album) => (album.title.clone(), "Album"),
+                    TracklistType::Playlist(playlist) => (playlist.title.clone(), "Playlist"),
+                    TracklistType::TopTracks(top_tracks) => {
+                        (top_tracks.artist_name.clone(), "Top tracks")
+                    }
+                    TracklistType::Tracks => (String::new(), "Tracks"),
+                };
+
+                Row::new(vec![Line::from(title), Line::from(kind)])
+            })
+            .collect();
+
+        let is_empty = rows.is_empty();
+
+        let mut table = Table::new(rows, [Constraint::Min(1), Constraint::Length(10)])
+            .row_highlight_style(theme().highlight)
+            .column_spacing(COLUMN_SPACING)
+            .block(block(None));
+
+        if !is_empty {
+            table = table.header(Row::new(["Title", "Type"]).add_modifier(Modifier::BOLD));
+        }
+
+        frame.render_stateful_widget(table, area, &mut self.state);
+        mouse::register_table(
+            area.inner(Margin::new(1, 1)),
+            !is_empty,
+            &self.state,
+            self.items.len(),
+        );
+    }
+
+    pub async fn handle_events(&mut self, event: Event, controls: &Controls) -> Output {
+        match event {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                match key_event.code {
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.state.select_next();
+                        Output::Consumed
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.state.select_previous();
+                        Output::Consumed
+                    }
+                    KeyCode::Enter => {
+                        let selected = self
+                            .state
+                            .selected()
+                            .and_then(|index| self.items.get(index));
+
+                        if let Some(list_type) = selected {
+                            controls.play_tracklist_type(list_type);
+                        }
+                        Output::Consumed
+                    }
+                    _ => Output::NotConsumed,
+                }
+            }
+            _ => Output::NotConsumed,
+        }
+    }
+}
@@ -1,14 +1,24 @@
-use qobuz_player_controls::{AppResult, client::Client, controls::Controls};
+use qobuz_player_controls::{
+    AppResult,
+    client::Client,
+    controls::Controls,
+    database::Database,
+    error::Error,
+    models::{Artist, LinkTarget},
+};
 use ratatui::{
     crossterm::event::{Event, KeyCode, KeyEventKind},
     prelude::*,
+    widgets::*,
 };
 use tui_input::{Input, backend::crossterm::EventHandler};
 
 use crate::{
     app::{NotificationList, Output},
+    mouse,
+    popup::{AlbumPopupState, ArtistPopupState, ComposerPopupState, PlaylistPopupState, Popup},
     sub_tab::SubTab,
-    ui::{block, render_input, tab_bar},
+    ui::{basic_list_table, block, render_input, tab_bar},
     widgets::{
         album_list::AlbumList,
         artist_list::ArtistList,
@@ -25,10 +35,33 @@ pub struct SearchState {
     pub artists: ArtistList,
     pub playlists: PlaylistList,
     pub tracks: TrackList,
+    pub composers: ArtistList,
     pub sub_tab: SubTab,
+    query: String,
+    /// Whether each sub tab has more results to load
+    more: [bool; SubTab::COUNT as usize],
+    /// Recent queries, listed while the search input is empty
+    history: Vec<String>,
+    history_state: TableState,
 }
 
 impl SearchState {
+    pub fn new(history: Vec<String>) -> Self {
+        Self {
+            history,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_history(&mut self, history: Vec<String>) {
+        self.history = history;
+        self.history_state.select(None);
+    }
+
+    fn showing_history(&self) -> bool {
+        self.filter.value().is_empty() && !self.history.is_empty()
+    }
+
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
         let tab_content_area_split = Layout::default()
             .constraints([Constraint::Length(3), Constraint::Min(1)])
@@ -42,6 +75,11 @@ impl SearchState {
             "Search",
         );
 
+        if self.showing_history() {
+            self.render_history(frame, tab_content_area_split[1]);
+            return;
+        }
+
         let block = block(None);
         frame.render_widget(block, tab_content_area_split[1]);
 
@@ -52,7 +90,10 @@ impl SearchState {
             .constraints([Constraint::Length(2), Constraint::Min(1)])
             .split(tab_content_area);
 
-        let tabs = tab_bar(SubTab::labels(), self.sub_tab.selected().into());
+        let tabs = tab_bar(
+            SubTab::labels(&SubTab::VALUES),
+            self.sub_tab.selected().into(),
+        );
         frame.render_widget(tabs, chunks[0]);
 
         match self.sub_tab {
@@ -60,18 +101,73 @@ impl SearchState {
             SubTab::Artists => self.artists.render(chunks[1], frame.buffer_mut()),
             SubTab::Playlists => self.playlists.render(chunks[1], frame.buffer_mut()),
             SubTab::Tracks => self.tracks.render(chunks[1], frame.buffer_mut(), true),
+            SubTab::Composers => self.composers.render(chunks[1], frame.buffer_mut()),
         };
     }
 
+    fn render_history(&mut self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<_> = self
+            .history
+            .iter()
+            .map(|query| Row::new(vec![Line::from(query.clone())]))
+            .collect();
+
+        let table = basic_list_table(rows).block(block(Some("Recent searches")));
+        frame.render_stateful_widget(table, area, &mut self.history_state);
+        mouse::register_table(
+            area.inner(Margin::new(1, 1)),
+            false,
+            &self.history_state,
+            self.history.len(),
+        );
+    }
+
     pub async fn handle_events(
         &mut self,
         event: Event,
         client: &Client,
         controls: &Controls,
+        database: &Database,
         notifications: &mut NotificationList,
     ) -> AppResult<Output> {
         match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                if !self.editing && self.showing_history() {
+                    match key_event.code {
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            self.history_state.select_next();
+                            return Ok(Output::Consumed);
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => {
+                            self.history_state.select_previous();
+                            return Ok(Output::Consumed);
+                        }
+                        KeyCode::Enter => {
+                            let selected = self
+                                .history_state
+                                .selected()
+                                .and_then(|index| self.history.get(index))
+                                .cloned();
+                            if let Some(query) = selected {
+                                self.filter = Input::new(query);
+                                self.update_search(client, database).await?;
+                            }
+                            return Ok(Output::Consumed);
+                        }
+                        KeyCode::Char('X') => {
+                            database.clear_search_history().await?;
+                            self.set_history(vec![]);
+                            return Ok(Output::Consumed);
+                        }
+                        _ => {}
+                    }
+                }
+
+                let moves_past_end = matches!(key_event.code, KeyCode::Down | KeyCode::Char('j'));
+                if !self.editing && moves_past_end && self.is_last_selected() {
+                    self.load_more(client).await?;
+                }
+
                 match &mut self.editing {
                     false => match key_event.code {
                         KeyCode::Char('e') => {
@@ -86,6 +182,14 @@ impl SearchState {
                             self.cycle_subtab();
                             Ok(Output::Consumed)
                         }
+                        KeyCode::Enter if self.sub_tab == SubTab::Composers => {
+                            let Some(composer) = self.composers.selected() else {
+                                return Ok(Output::Consumed);
+                            };
+
+                            let state = ComposerPopupState::new(composer, client).await?;
+                            Ok(Output::Popup(Popup::Composer(state)))
+                        }
                         _ => match self.sub_tab {
                             SubTab::Albums => {
                                 self.albums
@@ -113,12 +217,20 @@ impl SearchState {
                                     )
                                     .await
                             }
+                            SubTab::Composers => {
+                                self.composers
+                                    .handle_events(key_event.code, client, notifications)
+                                    .await
+                            }
                         },
                     },
                     true => match key_event.code {
                         KeyCode::Esc | KeyCode::Enter => {
                             self.stop_editing();
-                            self.update_search(client).await?;
+                            if let Some(target) = LinkTarget::parse(self.filter.value()) {
+                                return Ok(Output::Popup(link_popup(target, client).await?));
+                            }
+                            self.update_search(client, database).await?;
                             Ok(Output::Consumed)
                         }
                         _ => {
@@ -132,9 +244,80 @@ impl SearchState {
         }
     }
 
-    async fn update_search(&mut self, client: &Client) -> AppResult<()> {
+    fn is_last_selected(&self) -> bool {
+        match self.sub_tab {
+            SubTab::Albums => self.albums.is_last_selected(),
+            SubTab::Artists => self.artists.is_last_selected(),
+            SubTab::Playlists => self.playlists.is_last_selected(),
+            SubTab::Tracks => self.tracks.is_last_selected(),
+            SubTab::Composers => self.composers.is_last_selected(),
+        }
+    }
+
+    /// Appends the next page of results to the list of the current sub tab
+    async fn load_more(&mut self, client: &Client) -> AppResult<()> {
+        let index = self.sub_tab.selected() as usize;
+        if self.sub_tab == SubTab::Composers || !self.more[index] {
+            return Ok(());
+        }
+
+        let offset = match self.sub_tab {
+            SubTab::Albums => self.albums.all_items().len(),
+            SubTab::Artists => self.artists.all_items().len(),
+            SubTab::Playlists => self.playlists.all_items().len(),
+            SubTab::Tracks => self.tracks.all_items().len(),
+            SubTab::Composers => return Ok(()),
+        };
+        let search_results = client.search_page(self.query.clone(), offset).await?;
+
+        self.more[index] = match self.sub_tab {
+            SubTab::Albums => {
+                self.albums.append_items(
+                    search_results
+                        .albums
+                        .into_iter()
+                        .map(|x| x.into())
+                        .collect(),
+                );
+                search_results.more_albums
+            }
+            SubTab::Artists => {
+                self.artists.append_items(search_results.artists);
+                search_results.more_artists
+            }
+            SubTab::Playlists => {
+                self.playlists.append_items(
+                    search_results
+                        .playlists
+                        .into_iter()
+                        .map(|x| x.into())
+                        .collect(),
+                );
+                search_results.more_playlists
+            }
+            SubTab::Tracks => {
+                self.tracks.append_items(search_results.tracks);
+                search_results.more_tracks
+            }
+            SubTab::Composers => false,
+        };
+
+        Ok(())
+    }
+
+    async fn update_search(&mut self, client: &Client, database: &Database) -> AppResult<()> {
         if !self.filter.value().trim().is_empty() {
-            let search_results = client.search(self.filter.value().to_string()).await?;
+            self.query = self.filter.value().to_string();
+            database.add_search_query(&self.query).await?;
+            self.set_history(database.get_search_history().await?);
+            let search_results = client.search(self.query.clone()).await?;
+            self.more = [
+                search_results.more_albums,
+                search_results.more_artists,
+                search_results.more_playlists,
+                search_results.more_tracks,
+                false,
+            ];
 
             self.albums.set_all_items(
                 search_results
@@ -152,6 +335,7 @@ impl SearchState {
                     .collect(),
             );
             self.tracks.set_all_items(search_results.tracks);
+            self.composers.set_all_items(search_results.composers);
         }
 
         Ok(())
@@ -166,10 +350,36 @@ impl SearchState {
     }
 
     fn cycle_subtab_backwards(&mut self) {
-        self.sub_tab = self.sub_tab.previous();
+        self.sub_tab = self.sub_tab.previous(&SubTab::VALUES);
     }
 
     fn cycle_subtab(&mut self) {
-        self.sub_tab = self.sub_tab.next();
+        self.sub_tab = self.sub_tab.next(&SubTab::VALUES);
     }
 }
+
+/// Pasted Qobuz urls open the item. Tracks open their album
+async fn link_popup(target: LinkTarget, client: &Client) -> AppResult<Popup> {
+    Ok(match target {
+        LinkTarget::Album(id) => Popup::Album(AlbumPopupState::new(client.album(&id).await?)),
+        LinkTarget::Playlist(id) => {
+            Popup::Playlist(PlaylistPopupState::new(client.playlist(id).await?))
+        }
+        LinkTarget::Artist(id) => {
+            let page = client.artist_page(id).await?;
+            let artist = Artist {
+                id: page.id,
+                name: page.name,
+                image: page.image,
+            };
+            Popup::Artist(ArtistPopupState::new(&artist, client).await?)
+        }
+        LinkTarget::Track(id) => {
+            let track = client.track(id).await?;
+            let album_id = track.album_id.ok_or_else(|| Error::InvalidLink {
+                message: format!("{} is not on an album", track.title),
+            })?;
+            Popup::Album(AlbumPopupState::new(client.album(&album_id).await?))
+        }
+    })
+}
@@ -7,30 +7,24 @@ pub enum SubTab {
     Artists = 1,
     Playlists = 2,
     Tracks = 3,
+    Composers = 4,
 }
 
 impl SubTab {
-    pub const COUNT: u8 = 4;
+    pub const COUNT: u8 = 5;
 
     pub fn selected(self) -> u8 {
         self as u8
     }
 
-    pub fn from_u8(v: u8) -> Self {
-        match v % Self::COUNT {
-            0 => Self::Albums,
-            1 => Self::Artists,
-            2 => Self::Playlists,
-            _ => Self::Tracks,
-        }
-    }
-
-    pub fn next(self) -> Self {
-        Self::from_u8(self.selected().wrapping_add(1))
+    pub fn next(self, tabs: &[Self]) -> Self {
+        let position = tabs.iter().position(|tab| *tab == self).unwrap_or(0);
+        tabs[(position + 1) % tabs.len()]
     }
 
-    pub fn previous(self) -> Self {
-        Self::from_u8(self.selected().wrapping_add(Self::COUNT - 1))
+    pub fn previous(self, tabs: &[Self]) -> Self {
+        let position = tabs.iter().position(|tab| *tab == self).unwrap_or(0);
+        tabs[(position + tabs.len() - 1) % tabs.len()]
     }
 
     pub const fn as_str(self) -> &'static str {
@@ -39,14 +33,23 @@ impl SubTab {
             Self::Artists => "Artists",
             Self::Playlists => "Playlists",
             Self::Tracks => "Tracks",
+            Self::Composers => "Composers",
         }
     }
 
-    pub const VALUES: [Self; Self::COUNT as usize] =
-        [Self::Albums, Self::Artists, Self::Playlists, Self::Tracks];
+    pub const VALUES: [Self; Self::COUNT as usize] = [
+        Self::Albums,
+        Self::Artists,
+        Self::Playlists,
+        Self::Tracks,
+        Self::Composers,
+    ];
+
+    /// Sub tabs of the favorites, which has no composers
+    pub const LIBRARY: [Self; 4] = [Self::Albums, Self::Artists, Self::Playlists, Self::Tracks];
 
-    pub fn labels() -> Vec<&'static str> {
-        Self::VALUES.iter().map(|tab| tab.as_str()).collect()
+    pub fn labels(tabs: &[Self]) -> Vec<&'static str> {
+        tabs.iter().map(|tab| tab.as_str()).collect()
     }
 }
 
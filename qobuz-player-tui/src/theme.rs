@@ -0,0 +1,139 @@
+use std::{path::Path, str::FromStr, sync::OnceLock};
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Built-in color schemes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Selected row and active tab
+    pub highlight: Style,
+    /// Progress bar, focused inputs and headings
+    pub accent: Style,
+    pub border: Style,
+    /// Currently playing track in the queue
+    pub playing: Style,
+    pub explicit: Style,
+    pub hires: Style,
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Self {
+        let dim = Style::new().add_modifier(Modifier::DIM);
+
+        match name {
+            ThemeName::Dark => Self {
+                highlight: Style::new().white().on_blue(),
+                accent: Style::new().blue(),
+                border: Style::new(),
+                playing: Style::new().bold(),
+                explicit: dim,
+                hires: dim,
+            },
+            ThemeName::Light => Self {
+                highlight: Style::new().white().on_blue(),
+                accent: Style::new().blue(),
+                border: Style::new().dark_gray(),
+                playing: Style::new().blue().bold(),
+                explicit: Style::new().dark_gray(),
+                hires: Style::new().dark_gray(),
+            },
+            ThemeName::HighContrast => Self {
+                highlight: Style::new().black().on_yellow().bold(),
+                accent: Style::new().yellow().bold(),
+                border: Style::new().white(),
+                playing: Style::new().yellow().bold(),
+                explicit: Style::new().red().bold(),
+                hires: Style::new().cyan().bold(),
+            },
+        }
+    }
+
+    /// Loads a theme file, which picks a built-in theme as `base` and overrides its colors.
+    ///
+    /// ```json
+    /// { "base": "light", "highlight": { "fg": "black", "bg": "#ffd75f" }, "border": { "fg": "gray" } }
+    /// ```
+    pub fn from_file(path: &Path, base: ThemeName) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let file: ThemeFile = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+
+        let mut theme = Self::builtin(file.base.unwrap_or(base));
+        for (style, colors) in [
+            (&mut theme.highlight, file.highlight),
+            (&mut theme.accent, file.accent),
+            (&mut theme.border, file.border),
+            (&mut theme.playing, file.playing),
+            (&mut theme.explicit, file.explicit),
+            (&mut theme.hires, file.hires),
+        ] {
+            if let Some(colors) = colors {
+                *style = colors.apply(*style)?;
+            }
+        }
+
+        Ok(theme)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::builtin(ThemeName::default())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<ThemeName>,
+    highlight: Option<ThemeColors>,
+    accent: Option<ThemeColors>,
+    border: Option<ThemeColors>,
+    playing: Option<ThemeColors>,
+    explicit: Option<ThemeColors>,
+    hires: Option<ThemeColors>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeColors {
+    fg: Option<String>,
+    bg: Option<String>,
+}
+
+impl ThemeColors {
+    fn apply(self, mut style: Style) -> Result<Style, String> {
+        let parse = |color: &str| {
+            Color::from_str(color).map_err(|_| format!("Invalid theme color: {color}"))
+        };
+
+        if let Some(fg) = self.fg {
+            style = style.fg(parse(&fg)?);
+        }
+        if let Some(bg) = self.bg {
+            style = style.bg(parse(&bg)?);
+        }
+
+        Ok(style)
+    }
+}
+
+/// Sets the theme used for rendering. Only the first call has an effect.
+pub fn set_theme(theme: Theme) {
+    _ = THEME.set(theme);
+}
+
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
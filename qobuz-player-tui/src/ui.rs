@@ -1,20 +1,26 @@
-use qobuz_player_controls::{models::Album, notification::Notification};
+use qobuz_player_controls::{
+    diagnostics::Diagnostics,
+    models::{Album, Track},
+    notification::Notification,
+};
+use qrcode::{QrCode, render::unicode::Dense1x2};
 use ratatui::{layout::Flex, prelude::*, widgets::*};
 use ratatui_image::{StatefulImage, protocol::StatefulProtocol};
 use tui_input::Input;
 
 use crate::{
     app::{App, AppState, Tab},
+    mouse,
     now_playing::{self},
+    theme::theme,
 };
 
-pub const HIGHLIGHT_STYLE: Style = Style::new().white().on_blue();
-pub const HIGHLIGHT_TEXT_STYLE: Style = Style::new().blue();
 pub const COLUMN_SPACING: u16 = 2;
 
 impl App {
     pub fn render(&mut self, frame: &mut Frame) {
         let area = frame.area();
+        mouse::clear();
 
         self.render_inner(frame);
 
@@ -27,6 +33,14 @@ impl App {
             render_album_info(frame, &album, &mut self.now_playing.image);
         }
 
+        if matches!(self.app_state, AppState::Diagnostics) {
+            render_diagnostics(frame, &self.diagnostics);
+        }
+
+        if let AppState::QrCode(url) = &self.app_state {
+            render_qr_code(frame, url);
+        }
+
         self.render_notifications(frame, area);
     }
 
@@ -74,6 +88,7 @@ impl App {
         .block(block(None));
 
         frame.render_widget(tabs, chunks[0]);
+        mouse::register_tabs(block(None).inner(chunks[0]), &labels);
 
         if self.now_playing.playing_track.is_some() {
             now_playing::render(
@@ -97,6 +112,8 @@ impl App {
             Tab::Queue => self.queue.render(frame, tab_content_area),
             Tab::Discover => self.discover.render(frame, tab_content_area),
             Tab::Genres => self.genres.render(frame, tab_content_area),
+            Tab::Recent => self.recent.render(frame, tab_content_area),
+            Tab::Logs => self.logs.render(frame, tab_content_area),
         }
 
         if let AppState::Popup(popups) = &mut self.app_state {
@@ -115,11 +132,16 @@ impl App {
 
         let messages = notifications
             .into_iter()
-            .map(|notification| match notification {
-                Notification::Error(msg) => ("Error", msg, Color::Red),
-                Notification::Warning(msg) => ("Warning", msg, Color::Yellow),
-                Notification::Success(msg) => ("Success", msg, Color::Green),
-                Notification::Info(msg) => ("Info", msg, Color::Blue),
+            .filter_map(|notification| match notification {
+                Notification::Error(msg) => Some(("Error", msg, Color::Red)),
+                Notification::Warning(msg) => Some(("Warning", msg, Color::Yellow)),
+                Notification::Success(msg) => Some(("Success", msg, Color::Green)),
+                Notification::Info(msg) => Some(("Info", msg, Color::Blue)),
+                Notification::BufferProgress(_)
+                | Notification::StreamFormat(_)
+                | Notification::Diagnostics(_)
+                | Notification::ResumeAvailable(_)
+                | Notification::SignInRequired => None,
             });
 
         let inner_width = 60;
@@ -202,25 +224,51 @@ fn render_help(frame: &mut Frame) {
         ["Previous song", "p"],
         ["Jump forward", "f"],
         ["Jump backwards", "b"],
+        ["Jump forward one minute", ">"],
+        ["Jump backwards one minute", "<"],
+        ["Toggle mute", "m"],
+        ["Resume long track where it stopped", "R"],
+        ["Favorite / unfavorite playing track", "*"],
         ["Edit filter", "e"],
         ["Stop edit filter", "escape"],
         ["Select in list", "Up/Down"],
         ["Select selected item", "Enter"],
+        ["Select / play with mouse", "Click / double click"],
+        ["Seek with mouse", "Click/drag progress"],
         ["Cycle subgroup", "Left/right"],
         ["Add to queue", "B"],
         ["Shuffle tracks", "S"],
         ["Play next", "N"],
+        ["Play track in CD quality", "Q"],
         ["Delete from queue", "D"],
         ["Move up in queue", "u"],
         ["Move down in queue", "d"],
+        ["Select playing track in queue", "c"],
         ["Remove from favorites", "D"],
         ["Add to favorites", "A"],
         ["Create playlist", "C (playlist page)"],
         ["Delete playlist", "D (playlist page)"],
+        ["Edit playlist tags", "T (playlist page)"],
+        ["Cycle favorite album order", "o (favorite albums)"],
+        ["Cycle playlist tag filter", "t (playlist page)"],
+        ["Smart playlists", "M (playlist page)"],
         ["Add track to playlist", "a"],
         ["Move playlist track up", "u"],
         ["Move playlist track down", "d"],
-        ["Album info", "i"],
+        ["Album info", "i (playing track, album page)"],
+        ["Open album label", "L (album page)"],
+        ["Go to album of track", "g"],
+        ["Go to artist of track", "G"],
+        ["Copy link of playing track", "y"],
+        ["Copy link of playing album or playlist", "Y"],
+        ["QR code of playing track", "s"],
+        ["Playback diagnostics", "I"],
+        ["Switch profile", "P"],
+        ["Sign in to Qobuz again", "L"],
+        ["Clear recent searches", "X (search page)"],
+        ["Cycle artist release order", "o (artist page)"],
+        ["Follow artist releases", "r (artist page)"],
+        ["Play work of selected track", "w (album page)"],
         ["Exit", "q"],
     ];
 
@@ -245,7 +293,71 @@ fn render_help(frame: &mut Frame) {
     frame.render_widget(table, area);
 }
 
-fn render_album_info(
+fn render_diagnostics(frame: &mut Frame, diagnostics: &Diagnostics) {
+    let rows = [
+        ("Tracks played", diagnostics.tracks_played.to_string()),
+        ("Buffer underruns", diagnostics.underruns.to_string()),
+        ("Output errors", diagnostics.output_errors.to_string()),
+        ("Decode errors", diagnostics.decode_errors.to_string()),
+        ("Rebuffers", diagnostics.rebuffers.to_string()),
+        (
+            "Time rebuffering",
+            format!("{} ms", diagnostics.rebuffer_ms),
+        ),
+        ("Gaps between tracks", diagnostics.gaps.to_string()),
+        ("Stalled downloads", diagnostics.download_stalls.to_string()),
+        ("Recoveries", diagnostics.recoveries.to_string()),
+    ];
+
+    let rows: Vec<_> = rows
+        .into_iter()
+        .map(|(label, value)| Row::new([label.to_string(), value]))
+        .collect();
+    let widths = [Constraint::Length(20), Constraint::Length(12)];
+
+    let area = center(
+        frame.area(),
+        Constraint::Length(20 + 12 + 1 + 2),
+        Constraint::Length(rows.len() as u16 + 2),
+    );
+
+    let table = Table::new(rows, widths).block(block(Some("Diagnostics")));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(table, area);
+}
+
+/// Dark modules on light ones regardless of the terminal colors, so phones can scan it
+fn render_qr_code(frame: &mut Frame, url: &str) {
+    let Ok(code) = QrCode::new(url) else {
+        return;
+    };
+    let image = code.render::<Dense1x2>().build();
+
+    let width = image
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or_default();
+    let height = image.lines().count();
+
+    let area = center(
+        frame.area(),
+        Constraint::Length(width.max(url.len() + 2) as u16 + 2),
+        Constraint::Length(height as u16 + 2),
+    );
+
+    let block = block(Some(url));
+    let qr_code = Paragraph::new(image)
+        .style(Style::new().fg(Color::Black).bg(Color::White))
+        .alignment(Alignment::Center);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(qr_code, block.inner(area));
+    frame.render_widget(block, area);
+}
+
+pub fn render_album_info(
     frame: &mut Frame,
     album: &Album,
     image: &mut Option<(StatefulProtocol, f32)>,
@@ -256,10 +368,20 @@ fn render_album_info(
     info_lines.push(Line::from(album.artist.name.clone()));
     info_lines.push(Line::from(""));
 
-    if album.release_year > 0 {
+    if let Some(release_date) = &album.release_date {
+        info_lines.push(Line::from(format!("Released: {release_date}")));
+    } else if album.release_year > 0 {
         info_lines.push(Line::from(format!("Year:     {}", album.release_year)));
     }
 
+    if let Some(label) = &album.label {
+        info_lines.push(Line::from(format!("Label:    {}", label.name)));
+    }
+
+    if let Some(genre) = &album.genre {
+        info_lines.push(Line::from(format!("Genre:    {genre}")));
+    }
+
     info_lines.push(Line::from(format!("Tracks:   {}", album.total_tracks)));
     info_lines.push(Line::from(format!(
         "Duration: {}",
@@ -274,6 +396,10 @@ fn render_album_info(
         info_lines.push(Line::from("Explicit: Yes"));
     }
 
+    if let Some(upc) = &album.upc {
+        info_lines.push(Line::from(format!("UPC:      {upc}")));
+    }
+
     let info_height = info_lines.len() as u16;
 
     let box_width = frame.area().width / 2;
@@ -332,7 +458,7 @@ pub fn render_input(input: &Input, editing: bool, area: Rect, frame: &mut Frame,
     let width = area.width.max(3) - 3;
     let scroll = input.visual_scroll(width as usize);
     let style = match editing {
-        true => HIGHLIGHT_TEXT_STYLE,
+        true => theme().accent,
         _ => Style::default(),
     };
 
@@ -352,7 +478,8 @@ pub fn render_input(input: &Input, editing: bool, area: Rect, frame: &mut Frame,
 pub fn block(title: Option<&str>) -> Block<'_> {
     let mut block = Block::bordered()
         .title_alignment(Alignment::Center)
-        .border_type(BorderType::Rounded);
+        .border_type(BorderType::Rounded)
+        .border_style(theme().border);
 
     if let Some(title) = title {
         block = block.title(format!(" {title} "));
@@ -363,14 +490,14 @@ pub fn block(title: Option<&str>) -> Block<'_> {
 
 pub fn basic_list_table<'a>(rows: Vec<Row<'a>>) -> Table<'a> {
     Table::new(rows, [Constraint::Min(1)])
-        .row_highlight_style(HIGHLIGHT_STYLE)
+        .row_highlight_style(theme().highlight)
         .column_spacing(COLUMN_SPACING)
 }
 
 pub fn tab_bar<'a>(tabs: Vec<&'a str>, selected: usize) -> Tabs<'a> {
     Tabs::new(tabs)
         .not_underlined()
-        .highlight_style(HIGHLIGHT_STYLE)
+        .highlight_style(theme().highlight)
         .divider(symbols::line::VERTICAL)
         .select(selected)
 }
@@ -386,23 +513,36 @@ pub fn mark_explicit_and_hifi(
 
     if explicit {
         parts.push(Span::raw(" "));
-        parts.push(Span::styled(
-            "\u{f0b0c}",
-            Style::default().add_modifier(Modifier::DIM),
-        ));
+        parts.push(Span::styled("\u{f0b0c}", theme().explicit));
     }
 
     if hires_available {
         parts.push(Span::raw(" "));
-        parts.push(Span::styled(
-            "\u{f0435}",
-            Style::default().add_modifier(Modifier::DIM),
-        ));
+        parts.push(Span::styled("\u{f0435}", theme().hires));
     }
 
     Line::from(parts)
 }
 
+/// Appends the format the track was streamed in, and why it is not played in the chosen
+/// quality, if it is not
+pub fn mark_streaming_details(mut line: Line<'static>, track: &Track) -> Line<'static> {
+    if let Some(delivered_format) = &track.delivered_format {
+        line.push_span(Span::raw(" "));
+        line.push_span(Span::styled(delivered_format.clone(), theme().hires));
+    }
+
+    if let Some(restriction) = track.restriction {
+        line.push_span(Span::raw(" "));
+        line.push_span(Span::styled(
+            format!("[{}]", restriction.label()),
+            theme().explicit,
+        ));
+    }
+
+    line
+}
+
 pub fn mark_as_owned(title: String, owned: bool) -> Line<'static> {
     let mut parts: Vec<Span<'static>> = Vec::new();
 
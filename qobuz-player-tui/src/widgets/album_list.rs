@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use qobuz_player_controls::{
     AppResult, client::Client, controls::Controls, models::AlbumSimple, notification::Notification,
 };
@@ -6,30 +8,40 @@ use ratatui::{
     crossterm::event::KeyCode,
     layout::{Constraint, Rect},
     style::{Modifier, Stylize},
-    text::Line,
+    text::{Line, Span},
     widgets::{Row, StatefulWidget, Table},
 };
 
 use crate::{
     app::{FilteredListState, NotificationList, Output},
+    mouse,
     popup::{AlbumPopupState, Popup},
-    ui::{COLUMN_SPACING, HIGHLIGHT_STYLE, format_duration, mark_explicit_and_hifi},
+    theme::theme,
+    ui::{COLUMN_SPACING, format_duration, mark_explicit_and_hifi},
 };
 
 #[derive(Default)]
 pub struct AlbumList {
     items: FilteredListState<AlbumSimple>,
+    /// Albums marked as recently added to the favorites
+    new_albums: HashSet<String>,
 }
 
 impl AlbumList {
     pub fn new(albums: Vec<AlbumSimple>) -> Self {
         let albums = FilteredListState::new(albums);
-        Self { items: albums }
+        Self {
+            items: albums,
+            new_albums: Default::default(),
+        }
     }
 
     pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
-        let table = album_table(self.items.filter());
+        let table = album_table(self.items.filter(), &self.new_albums);
         table.render(area, buf, &mut self.items.state);
+
+        let len = self.items.filter().len();
+        mouse::register_table(area, len > 0, &self.items.state, len);
     }
 
     pub fn select_first(&mut self) {
@@ -52,6 +64,18 @@ impl AlbumList {
         self.items.set_all_items(items);
     }
 
+    pub fn set_new_albums(&mut self, ids: HashSet<String>) {
+        self.new_albums = ids;
+    }
+
+    pub fn append_items(&mut self, items: Vec<AlbumSimple>) {
+        self.items.append_items(items);
+    }
+
+    pub fn is_last_selected(&self) -> bool {
+        self.items.is_last_selected()
+    }
+
     pub async fn handle_events(
         &mut self,
         event: KeyCode,
@@ -159,12 +183,19 @@ impl AlbumList {
     }
 }
 
-pub fn album_table<'a>(rows: &[AlbumSimple]) -> Table<'a> {
+pub fn album_table<'a>(rows: &[AlbumSimple], new_albums: &HashSet<String>) -> Table<'a> {
     let body_rows: Vec<Row<'a>> = rows
         .iter()
         .map(|album| {
+            let mut title =
+                mark_explicit_and_hifi(album.title.clone(), album.explicit, album.hires_available);
+            if new_albums.contains(&album.id) {
+                title.push_span(Span::raw(" "));
+                title.push_span(Span::styled("new", theme().accent));
+            }
+
             Row::new(vec![
-                mark_explicit_and_hifi(album.title.clone(), album.explicit, album.hires_available),
+                title,
                 Line::from(album.artist.name.clone()),
                 Line::from(album.release_year.to_string()),
                 Line::from(format_duration(album.duration_seconds)),
@@ -182,7 +213,7 @@ pub fn album_table<'a>(rows: &[AlbumSimple]) -> Table<'a> {
     ];
 
     let mut table = Table::new(body_rows, constraints)
-        .row_highlight_style(HIGHLIGHT_STYLE)
+        .row_highlight_style(theme().highlight)
         .column_spacing(COLUMN_SPACING);
 
     if !is_empty {
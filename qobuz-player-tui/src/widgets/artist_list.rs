@@ -11,6 +11,7 @@ use ratatui::{
 
 use crate::{
     app::{FilteredListState, NotificationList, Output},
+    mouse,
     popup::{ArtistPopupState, Popup},
     ui::basic_list_table,
 };
@@ -36,6 +37,9 @@ impl ArtistList {
         );
 
         table.render(area, buf, &mut self.items.state);
+
+        let len = self.items.filter().len();
+        mouse::register_table(area, false, &self.items.state, len);
     }
 
     pub fn set_filter(&mut self, items: Vec<Artist>) {
@@ -50,6 +54,21 @@ impl ArtistList {
         self.items.set_all_items(items);
     }
 
+    pub fn append_items(&mut self, items: Vec<Artist>) {
+        self.items.append_items(items);
+    }
+
+    pub fn selected(&self) -> Option<&Artist> {
+        self.items
+            .state
+            .selected()
+            .and_then(|index| self.items.filter().get(index))
+    }
+
+    pub fn is_last_selected(&self) -> bool {
+        self.items.is_last_selected()
+    }
+
     pub async fn handle_events(
         &mut self,
         event: KeyCode,
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use qobuz_player_controls::{
     AppResult, client::Client, controls::Controls, models::PlaylistSimple,
     notification::Notification,
@@ -13,24 +15,42 @@ use ratatui::{
 
 use crate::{
     app::{FilteredListState, NotificationList, Output},
+    mouse,
     popup::{DeletePlaylistPopupstate, NewPlaylistPopupState, PlaylistPopupState, Popup},
-    ui::{COLUMN_SPACING, HIGHLIGHT_STYLE, format_duration, mark_as_owned},
+    theme::theme,
+    ui::{COLUMN_SPACING, format_duration, mark_as_owned},
 };
 
 #[derive(Default)]
 pub struct PlaylistList {
     items: FilteredListState<PlaylistSimple>,
+    /// Local tags by playlist id, shown in a column when any are set
+    tags: HashMap<u32, Vec<String>>,
 }
 
 impl PlaylistList {
     pub fn new(playlists: Vec<PlaylistSimple>) -> Self {
         let playlists = FilteredListState::new(playlists);
-        Self { items: playlists }
+        Self {
+            items: playlists,
+            tags: Default::default(),
+        }
     }
 
     pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
-        let table = playlist_list(self.items.filter());
+        let table = playlist_list(self.items.filter(), &self.tags);
         table.render(area, buf, &mut self.items.state);
+
+        let len = self.items.filter().len();
+        mouse::register_table(area, len > 0, &self.items.state, len);
+    }
+
+    pub fn tags(&self) -> &HashMap<u32, Vec<String>> {
+        &self.tags
+    }
+
+    pub fn set_tags(&mut self, tags: HashMap<u32, Vec<String>>) {
+        self.tags = tags;
     }
 
     pub fn set_filter(&mut self, items: Vec<PlaylistSimple>) {
@@ -45,6 +65,14 @@ impl PlaylistList {
         self.items.set_all_items(items);
     }
 
+    pub fn append_items(&mut self, items: Vec<PlaylistSimple>) {
+        self.items.append_items(items);
+    }
+
+    pub fn is_last_selected(&self) -> bool {
+        self.items.is_last_selected()
+    }
+
     pub fn selected(&self) -> Option<usize> {
         self.items.state.selected()
     }
@@ -185,27 +213,48 @@ impl PlaylistList {
     }
 }
 
-fn playlist_list<'a>(rows: &[PlaylistSimple]) -> Table<'a> {
+fn playlist_list<'a>(rows: &[PlaylistSimple], tags: &HashMap<u32, Vec<String>>) -> Table<'a> {
+    let show_tags = !tags.is_empty();
+
     let body_rows: Vec<Row<'a>> = rows
         .iter()
         .map(|playlist| {
-            Row::new(vec![
+            let mut cells = vec![
                 mark_as_owned(playlist.title.clone(), playlist.is_owned),
                 Line::from(format_duration(playlist.duration_seconds)),
-            ])
+            ];
+            if show_tags {
+                let playlist_tags = tags
+                    .get(&playlist.id)
+                    .map(|tags| tags.join(", "))
+                    .unwrap_or_default();
+                cells.push(Line::from(playlist_tags).dim());
+            }
+            Row::new(cells)
         })
         .collect();
 
     let is_empty = body_rows.is_empty();
 
-    let constraints = [Constraint::Ratio(2, 3), Constraint::Length(10)];
+    let constraints = match show_tags {
+        true => vec![
+            Constraint::Ratio(1, 2),
+            Constraint::Length(10),
+            Constraint::Ratio(1, 3),
+        ],
+        false => vec![Constraint::Ratio(2, 3), Constraint::Length(10)],
+    };
 
     let mut table = Table::new(body_rows, constraints)
-        .row_highlight_style(HIGHLIGHT_STYLE)
+        .row_highlight_style(theme().highlight)
         .column_spacing(COLUMN_SPACING);
 
     if !is_empty {
-        table = table.header(Row::new(["Title", "Duration"]).add_modifier(Modifier::BOLD));
+        let header = match show_tags {
+            true => vec!["Title", "Duration", "Tags"],
+            false => vec!["Title", "Duration"],
+        };
+        table = table.header(Row::new(header).add_modifier(Modifier::BOLD));
     }
 
     table
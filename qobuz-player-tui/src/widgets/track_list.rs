@@ -1,5 +1,6 @@
 use qobuz_player_controls::{
-    AppResult, client::Client, controls::Controls, models::Track, notification::Notification,
+    AppResult, AudioQuality, client::Client, controls::Controls, models::Track,
+    notification::Notification,
 };
 use ratatui::{
     buffer::Buffer,
@@ -12,7 +13,10 @@ use ratatui::{
 
 use crate::{
     app::{FilteredListState, NotificationList, Output},
-    ui::{COLUMN_SPACING, HIGHLIGHT_STYLE, format_duration, mark_explicit_and_hifi},
+    mouse,
+    popup::{go_to_track_album, go_to_track_artist},
+    theme::theme,
+    ui::{COLUMN_SPACING, format_duration, mark_explicit_and_hifi, mark_streaming_details},
 };
 
 #[derive(Default)]
@@ -25,6 +29,8 @@ pub enum TrackListEvent {
     Album(String),
     Playlist(u32, bool),
     Artist(u32),
+    /// Play the listed tracks from the selected one, or all shuffled
+    Tracks(bool),
 }
 
 impl TrackList {
@@ -36,6 +42,9 @@ impl TrackList {
     pub fn render(&mut self, area: Rect, buf: &mut Buffer, show_album: bool) {
         let table = track_table(self.items.filter(), show_album);
         table.render(area, buf, &mut self.items.state);
+
+        let len = self.items.filter().len();
+        mouse::register_table(area, len > 0, &self.items.state, len);
     }
 
     pub fn all_items(&self) -> &Vec<Track> {
@@ -54,10 +63,23 @@ impl TrackList {
         self.items.set_all_items(items);
     }
 
+    pub fn append_items(&mut self, items: Vec<Track>) {
+        self.items.append_items(items);
+    }
+
+    pub fn is_last_selected(&self) -> bool {
+        self.items.is_last_selected()
+    }
+
     pub fn filter(&self) -> &Vec<Track> {
         self.items.filter()
     }
 
+    pub fn selected(&self) -> Option<&Track> {
+        let index = self.items.state.selected()?;
+        self.items.filter().get(index)
+    }
+
     pub async fn handle_events(
         &mut self,
         event: KeyCode,
@@ -88,6 +110,18 @@ impl TrackList {
                 Ok(Output::Consumed)
             }
 
+            KeyCode::Char('g') | KeyCode::Char('G') => {
+                let index = self.items.state.selected();
+                let Some(selected) = index.and_then(|index| self.items.filter().get(index)) else {
+                    return Ok(Output::NotConsumed);
+                };
+
+                match event {
+                    KeyCode::Char('g') => go_to_track_album(selected, client).await,
+                    _ => go_to_track_artist(selected, client).await,
+                }
+            }
+
             KeyCode::Char('N') => {
                 let index = self.items.state.selected();
                 let selected = index.and_then(|index| self.items.filter().get(index));
@@ -100,6 +134,16 @@ impl TrackList {
                 Ok(Output::Consumed)
             }
 
+            KeyCode::Char('Q') => {
+                let index = self.items.state.selected();
+                let selected = index.and_then(|index| self.items.filter().get(index));
+
+                if let Some(selected) = selected {
+                    controls.play_track_in_quality(selected.id, AudioQuality::CD);
+                };
+                Ok(Output::Consumed)
+            }
+
             KeyCode::Char('B') => {
                 let index = self.items.state.selected();
                 let selected = index.and_then(|index| self.items.filter().get(index));
@@ -164,6 +208,17 @@ impl TrackList {
                         controls.play_playlist(id, index, shuffle)
                     }
                     TrackListEvent::Artist(id) => controls.play_top_tracks(id, index),
+                    TrackListEvent::Tracks(shuffle) => {
+                        let skip = if shuffle { 0 } else { index };
+                        let ids = self
+                            .items
+                            .filter()
+                            .iter()
+                            .skip(skip)
+                            .map(|track| track.id)
+                            .collect();
+                        controls.play_tracks(ids, shuffle);
+                    }
                 }
 
                 Ok(Output::Consumed)
@@ -180,10 +235,9 @@ fn track_table<'a>(rows: &[Track], show_album: bool) -> Table<'a> {
         .map(|track| {
             let mut cols: Vec<Line<'a>> = Vec::with_capacity(if show_album { 4 } else { 3 });
 
-            cols.push(mark_explicit_and_hifi(
-                track.title.clone(),
-                track.explicit,
-                track.hires_available,
+            cols.push(mark_streaming_details(
+                mark_explicit_and_hifi(track.title.clone(), track.explicit, track.hires_available),
+                track,
             ));
 
             cols.push(Line::from(track.artist_name.clone().unwrap_or_default()));
@@ -216,7 +270,7 @@ fn track_table<'a>(rows: &[Track], show_album: bool) -> Table<'a> {
     };
 
     let mut table = Table::new(body_rows, constraints)
-        .row_highlight_style(HIGHLIGHT_STYLE)
+        .row_highlight_style(theme().highlight)
         .column_spacing(COLUMN_SPACING);
 
     if !is_empty {
@@ -1,4 +1,27 @@
 let evtSource;
+const prefersLight = window.matchMedia("(prefers-color-scheme: light)");
+
+// Light or dark as picked in the settings, following the system when nothing is picked
+function applyTheme() {
+  const theme = localStorage.getItem("theme") ?? (prefersLight.matches ? "light" : "dark");
+  document.documentElement.dataset.theme = theme;
+}
+
+function setTheme(theme) {
+  if (theme === "system") {
+    localStorage.removeItem("theme");
+  } else {
+    localStorage.setItem("theme", theme);
+  }
+  applyTheme();
+}
+
+applyTheme();
+prefersLight.addEventListener("change", applyTheme);
+
+let bufferPercent = 0;
+// Position events would move the progress slider away from under the pointer
+let draggingProgress = false;
 
 function initSse() {
   if (evtSource) {
@@ -36,22 +59,89 @@ function initSse() {
     });
   }
 
+  evtSource.addEventListener("buffer-progress", (event) => {
+    bufferPercent = Number(event.data);
+    showBufferProgress();
+  });
+
+  evtSource.addEventListener("stream-format", (event) => {
+    for (const el of document.querySelectorAll("[data-stream-format]")) {
+      el.textContent = event.data;
+    }
+  });
+
   evtSource.addEventListener("position", (event) => {
+    if (draggingProgress) return;
+
     const slider = document.getElementById("progress-slider");
     if (!slider) return;
     slider.value = event.data;
+    showPosition(event.data);
+  });
+}
+
+initSse();
 
-    const positionElement = document.getElementById("position");
-    if (!positionElement) return;
+function showPosition(mseconds) {
+  const positionElement = document.getElementById("position");
+  if (!positionElement) return;
 
-    const totalSeconds = Math.floor(event.data / 1000);
-    const minutes = String(Math.floor(totalSeconds / 60)).padStart(2, "0");
-    const seconds = String(totalSeconds % 60).padStart(2, "0");
-    positionElement.textContent = `${minutes}:${seconds}`;
+  const totalSeconds = Math.floor(mseconds / 1000);
+  const minutes = String(Math.floor(totalSeconds / 60)).padStart(2, "0");
+  const seconds = String(totalSeconds % 60).padStart(2, "0");
+  positionElement.textContent = `${minutes}:${seconds}`;
+}
+
+document.addEventListener("pointerdown", (event) => {
+  if (event.target.id === "progress-slider") draggingProgress = true;
+});
+
+for (const type of ["pointerup", "pointercancel"]) {
+  document.addEventListener(type, () => {
+    draggingProgress = false;
   });
 }
 
-initSse();
+document.addEventListener("input", (event) => {
+  if (event.target.id === "progress-slider") showPosition(event.target.value);
+});
+
+// Kiosk: the controls hide after a while without input, and the content shifts a few pixels
+// every minute so the same pixels do not stay lit on an always-on display
+const KIOSK_IDLE_MS = 5000;
+const KIOSK_DRIFT_MS = 60000;
+const KIOSK_DRIFT_PX = 12;
+
+function initKiosk() {
+  if (!document.body.hasAttribute("data-kiosk")) return;
+
+  let idleTimeout;
+  const wake = () => {
+    delete document.body.dataset.idle;
+    clearTimeout(idleTimeout);
+    idleTimeout = setTimeout(() => {
+      document.body.dataset.idle = "";
+    }, KIOSK_IDLE_MS);
+  };
+  for (const type of ["pointermove", "pointerdown", "keydown"]) {
+    document.addEventListener(type, wake);
+  }
+  wake();
+
+  const offset = () => Math.round((Math.random() * 2 - 1) * KIOSK_DRIFT_PX);
+  setInterval(() => {
+    const drift = document.querySelector("[data-kiosk-drift]");
+    if (drift) drift.style.transform = `translate(${offset()}px, ${offset()}px)`;
+  }, KIOSK_DRIFT_MS);
+}
+
+document.addEventListener("DOMContentLoaded", initKiosk);
+
+function showBufferProgress() {
+  for (const ring of document.querySelectorAll("[data-buffer-progress]")) {
+    ring.setAttribute("stroke-dashoffset", 100 - bufferPercent);
+  }
+}
 
 function refreshSse() {
   for (const el of document.querySelectorAll("[hx-trigger='tracklist']")) {
@@ -104,6 +194,39 @@ function loadSearchInput() {
   }
 }
 
+// Recent searches run again from the list under the search box
+function setSearchInput(value) {
+  const input = document.getElementById("query");
+  if (input) input.value = value;
+  updateSearchState(value);
+}
+
+// Suggestions close once the search box loses focus, after a click on one is handled,
+// and when the search is submitted
+function clearSearchSuggestions() {
+  const suggestions = document.getElementById("search-suggestions");
+  if (suggestions) suggestions.innerHTML = "";
+}
+
+document.addEventListener("focusout", (event) => {
+  if (event.target.id === "query") setTimeout(clearSearchSuggestions, 200);
+});
+
+document.addEventListener("keyup", (event) => {
+  if (event.target.id === "query" && event.key === "Enter") clearSearchSuggestions();
+});
+
+// Sent by the copy link actions of the list menus. The clipboard is only available over
+// https and on localhost, otherwise the link is shown to copy by hand
+document.addEventListener("copy-link", (event) => {
+  const url = event.detail.value;
+  if (navigator.clipboard) {
+    navigator.clipboard.writeText(url).catch(() => window.prompt("Qobuz link", url));
+  } else {
+    window.prompt("Qobuz link", url);
+  }
+});
+
 let searchTimeout;
 
 function setSearchQuery(value) {
@@ -112,6 +235,11 @@ function setSearchQuery(value) {
 }
 
 htmx.onLoad(function (content) {
+  showBufferProgress();
+
+  const themeSelect = document.getElementById("theme");
+  if (themeSelect) themeSelect.value = localStorage.getItem("theme") ?? "system";
+
   for (const sortable of content.querySelectorAll(".sortable")) {
     new Sortable(sortable, {
       animation: 150,
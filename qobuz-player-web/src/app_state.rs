@@ -1,12 +1,21 @@
-use axum::response::{Html, IntoResponse, Response};
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
 use futures::try_join;
+use qobuz_player_cli::StartupSummary;
+use qobuz_player_connect::ConnectionStatusReceiver;
 use qobuz_player_controls::{
-    AppResult, PositionReceiver, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
+    AppResult, PositionReceiver, QuietHoursReceiver, Status, StatusReceiver, TracklistReceiver,
+    TransitionReceiver, VolumeReceiver, ZonesReceiver,
+    artwork_cache::{ArtworkCache, MAX_ARTWORK_SIZE},
     client::Client,
     controls::Controls,
     database::Database,
-    models::{Favorites, Track},
-    notification::{Notification, NotificationBroadcast},
+    diagnostics::Diagnostics,
+    log_buffer::LogBuffer,
+    models::{Favorites, Track, TrackRestriction, reduced_artwork_url},
+    notification::{Notification, NotificationBroadcast, TrackFormat},
     tracklist::{Tracklist, TracklistType},
 };
 use qobuz_player_rfid::RfidState;
@@ -15,11 +24,19 @@ use skabelon::Templates;
 use std::sync::Arc;
 use tokio::sync::{broadcast::Sender, watch};
 
-use crate::{AlbumData, ServerSentEvent};
+use crate::{
+    AccentColor, AlbumData, ServerSentEvent, WebAuth,
+    discovery::Discovery,
+    routes::{events::EventLog, party::PartyVotes, status::DeviceCheck},
+};
+
+/// Queued tracks shown after the playing one in the player bar
+const UP_NEXT_TRACKS: usize = 2;
 
 pub struct AppState {
     pub tx: Sender<ServerSentEvent>,
-    pub web_secret: Option<String>,
+    pub web_auth: WebAuth,
+    pub accent_color: Option<AccentColor>,
     pub rfid_state: Option<RfidState>,
     pub broadcast: Arc<NotificationBroadcast>,
     pub client: Arc<Client>,
@@ -28,14 +45,38 @@ pub struct AppState {
     pub tracklist_receiver: TracklistReceiver,
     pub status_receiver: StatusReceiver,
     pub volume_receiver: VolumeReceiver,
+    pub quiet_hours_receiver: QuietHoursReceiver,
+    pub zones_receiver: ZonesReceiver,
+    pub stream_format: watch::Receiver<Option<TrackFormat>>,
+    pub diagnostics: watch::Receiver<Diagnostics>,
+    /// Resubscribed for every websocket client
+    pub transition_receiver: TransitionReceiver,
     pub templates: watch::Receiver<Templates>,
     pub database: Arc<Database>,
+    pub artwork_cache: Arc<ArtworkCache>,
+    pub log_buffer: Arc<LogBuffer>,
+    pub startup_summary: StartupSummary,
+    pub discovery: Discovery,
+    /// `None` when Qobuz Connect is disabled
+    pub connection_status: Option<ConnectionStatusReceiver>,
+    /// Forwards requests to the instance selected in the picker
+    pub proxy_client: reqwest::Client,
+    pub event_log: Arc<EventLog>,
+    pub party_votes: PartyVotes,
+    pub device_check: DeviceCheck,
 }
 
 impl AppState {
     pub fn playing_info(&self) -> PlayingInfo {
         let current_volume = self.volume_receiver.borrow();
         let current_volume = (*current_volume * 100.0) as u32;
+        let quiet_hours = self.quiet_hours_receiver.borrow().map(|quiet_hours| {
+            format!(
+                "Quiet hours until {}, volume up to {}%",
+                quiet_hours.end_time(),
+                quiet_hours.max_volume
+            )
+        });
 
         let tracklist = self.tracklist_receiver.borrow().clone();
         let current_track = tracklist.current_track().cloned();
@@ -44,6 +85,10 @@ impl AppState {
             .as_ref()
             .and_then(|track| track.artist_name.clone());
         let artist_id = current_track.as_ref().and_then(|track| track.artist_id);
+        let restriction = current_track.as_ref().and_then(|track| track.restriction);
+        let delivered_format = current_track
+            .as_ref()
+            .and_then(|track| track.delivered_format.clone());
 
         let (title, artist_link, duration_ms, explicit, hires_available) =
             current_track.as_ref().map_or(
@@ -66,12 +111,34 @@ impl AppState {
             );
 
         let entity = entity_playing(&tracklist, current_track.as_ref());
+        // Playlists and top tracks link to the album of the track besides the list
+        let (album_title, album_link) = current_track
+            .as_ref()
+            .and_then(|track| {
+                let link = format!("/album/{}", track.album_id.as_ref()?);
+                Some((track.album_title.clone(), link))
+            })
+            .filter(|(_, link)| entity.link.as_ref() != Some(link))
+            .map_or((None, None), |(title, link)| (title, Some(link)));
         let now_playing_id = tracklist.currently_playing();
+        let track_url = tracklist.current_track_link().map(|target| target.url());
+        let list_url = tracklist.current_list_link().map(|target| target.url());
 
         let position_ms = self.position_receiver.borrow().as_millis() as u32;
+        let stream_format = self
+            .stream_format
+            .borrow()
+            .filter(|stream_format| Some(stream_format.track_id) == now_playing_id)
+            .map(|stream_format| stream_format.format.to_string());
+
+        let is_favorite = now_playing_id.is_some_and(|id| self.client.is_favorite_track(id));
 
         let number_of_tracks = tracklist.total() as u32;
         let current_position = (tracklist.current_position() + 1) as u32;
+        let up_next = match current_track {
+            Some(_) => up_next(&tracklist),
+            None => vec![],
+        };
 
         PlayingInfo {
             title,
@@ -80,15 +147,26 @@ impl AppState {
             artist_name,
             entity_title: entity.title,
             entity_link: entity.link,
+            album_title,
+            album_link,
+            track_url,
+            list_url,
             status,
             cover_image: entity.cover_link,
             number_of_tracks,
             current_position,
             current_volume,
+            max_volume: self.startup_summary.max_volume.into(),
+            quiet_hours,
             explicit,
             hires_available,
+            restriction,
+            delivered_format,
             duration_ms,
             position_ms,
+            stream_format,
+            is_favorite,
+            up_next,
         }
     }
 
@@ -98,19 +176,50 @@ impl AppState {
     {
         let playing_info = serde_json::json!({"playing_info": self.playing_info()});
 
-        let context = merge_serialized(&playing_info, context).unwrap();
+        let mut context = merge_serialized(&playing_info, context).unwrap();
+        self.cache_artwork(&mut context);
         let templates = self.templates.borrow();
         let render = templates.render(view, &context);
 
         Html(render).into_response()
     }
 
+    /// Points Qobuz artwork urls at the local artwork cache
+    fn cache_artwork(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(string) if string.contains("static.qobuz.com") => {
+                let url = match self.client.data_saver() {
+                    true => reduced_artwork_url(string),
+                    false => string.clone(),
+                };
+                let id = self.artwork_cache.register(&url);
+                *string = format!("/artwork/{id}/{}", artwork_size(&url));
+            }
+            serde_json::Value::Array(values) => values
+                .iter_mut()
+                .for_each(|value| self.cache_artwork(value)),
+            serde_json::Value::Object(map) => {
+                map.values_mut().for_each(|value| self.cache_artwork(value))
+            }
+            _ => {}
+        }
+    }
+
     pub fn send_toast(&self, message: Notification) -> Response {
         let (message_string, severity) = match &message {
             Notification::Error(message) => (message, 1),
             Notification::Warning(message) => (message, 2),
             Notification::Success(message) => (message, 3),
             Notification::Info(message) => (message, 4),
+            Notification::SignInRequired => {
+                return self.render("send-toast.html", &json!({"sign_in": true}));
+            }
+            Notification::BufferProgress(_)
+            | Notification::StreamFormat(_)
+            | Notification::Diagnostics(_)
+            | Notification::ResumeAvailable(_) => {
+                return StatusCode::NO_CONTENT.into_response();
+            }
         };
 
         self.render(
@@ -194,6 +303,13 @@ pub struct PlayingInfo {
     artist_name: Option<String>,
     entity_title: Option<String>,
     entity_link: Option<String>,
+    /// When the track is played from a list other than its album
+    album_title: Option<String>,
+    album_link: Option<String>,
+    /// open.qobuz.com url of the track, to share it
+    track_url: Option<String>,
+    /// open.qobuz.com url of the album, playlist or artist being played
+    list_url: Option<String>,
     status: Status,
     cover_image: Option<String>,
     duration_ms: u32,
@@ -201,8 +317,52 @@ pub struct PlayingInfo {
     number_of_tracks: u32,
     current_position: u32,
     current_volume: u32,
+    /// End of the volume slider
+    max_volume: u32,
+    /// While quiet hours cap the volume
+    quiet_hours: Option<String>,
     explicit: bool,
     hires_available: bool,
+    restriction: Option<TrackRestriction>,
+    delivered_format: Option<String>,
+    stream_format: Option<String>,
+    is_favorite: bool,
+    up_next: Vec<UpNext>,
+}
+
+/// Queued track shown in the player bar, skipped to by its index in the queue
+#[derive(serde::Serialize, serde::Deserialize)]
+struct UpNext {
+    index: usize,
+    title: String,
+    artist_name: Option<String>,
+}
+
+fn up_next(tracklist: &Tracklist) -> Vec<UpNext> {
+    tracklist
+        .queue()
+        .into_iter()
+        .enumerate()
+        .skip(tracklist.current_position() + 1)
+        .filter(|(_, item)| item.track.available)
+        .take(UP_NEXT_TRACKS)
+        .map(|(index, item)| UpNext {
+            index,
+            title: item.track.title.clone(),
+            artist_name: item.track.artist_name.clone(),
+        })
+        .collect()
+}
+
+/// Size of the variant served for a Qobuz artwork url, based on the size it points to
+fn artwork_size(url: &str) -> u32 {
+    if url.contains("_230.") {
+        230
+    } else if url.contains("_max.") {
+        MAX_ARTWORK_SIZE
+    } else {
+        600
+    }
 }
 
 fn merge_serialized<T: serde::Serialize, Y: serde::Serialize>(
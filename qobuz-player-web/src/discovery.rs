@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use parking_lot::RwLock;
+use serde::Serialize;
+
+const SERVICE_TYPE: &str = "_qobuz-player._tcp.local.";
+
+/// Other qobuz-player instances on the local network
+#[derive(Debug, Clone, Serialize)]
+pub struct Peer {
+    pub name: String,
+    pub fullname: String,
+    #[serde(skip)]
+    pub address: IpAddr,
+    pub port: u16,
+    pub version: Option<String>,
+    pub auth_required: bool,
+}
+
+impl Peer {
+    pub fn base_url(&self) -> String {
+        match self.address {
+            IpAddr::V4(address) => format!("http://{address}:{}", self.port),
+            IpAddr::V6(address) => format!("http://[{address}]:{}", self.port),
+        }
+    }
+}
+
+/// Advertises this instance and keeps track of the others
+pub struct Discovery {
+    pub name: String,
+    pub fullname: String,
+    peers: Arc<RwLock<BTreeMap<String, Peer>>>,
+    _daemon: Option<ServiceDaemon>,
+}
+
+impl Discovery {
+    /// Without a name nothing is advertised and no peers are found
+    pub fn start(name: Option<String>, port: u16, auth_required: bool) -> Self {
+        let Some(name) = name else {
+            let name = default_instance_name();
+            return Self {
+                fullname: format!("{name}.{SERVICE_TYPE}"),
+                name,
+                peers: Default::default(),
+                _daemon: None,
+            };
+        };
+
+        let fullname = format!("{name}.{SERVICE_TYPE}");
+        let peers: Arc<RwLock<BTreeMap<String, Peer>>> = Default::default();
+
+        let daemon = match advertise(&name, port, auth_required).and_then(|daemon| {
+            let events = daemon.browse(SERVICE_TYPE)?;
+            Ok((daemon, events))
+        }) {
+            Ok((daemon, events)) => {
+                tokio::spawn(browse(events, fullname.clone(), peers.clone()));
+                Some(daemon)
+            }
+            Err(err) => {
+                tracing::warn!("Unable to start mDNS: {err}");
+                None
+            }
+        };
+
+        Self {
+            name,
+            fullname,
+            peers,
+            _daemon: daemon,
+        }
+    }
+
+    pub fn peers(&self) -> Vec<Peer> {
+        self.peers.read().values().cloned().collect()
+    }
+
+    pub fn peer(&self, fullname: &str) -> Option<Peer> {
+        self.peers.read().get(fullname).cloned()
+    }
+}
+
+/// TXT records let clients find the API and know whether to ask for credentials
+fn advertise(name: &str, port: u16, auth_required: bool) -> mdns_sd::Result<ServiceDaemon> {
+    let daemon = ServiceDaemon::new()?;
+    let host = format!("{}.local.", host_label(name));
+    let properties = [
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("api", "/api"),
+        ("status", "/status"),
+        ("auth", if auth_required { "true" } else { "false" }),
+    ];
+    let service =
+        ServiceInfo::new(SERVICE_TYPE, name, &host, "", port, &properties[..])?.enable_addr_auto();
+    daemon.register(service)?;
+
+    tracing::info!("Advertising {name} on port {port} with mDNS");
+    Ok(daemon)
+}
+
+async fn browse(
+    events: mdns_sd::Receiver<ServiceEvent>,
+    own_fullname: String,
+    peers: Arc<RwLock<BTreeMap<String, Peer>>>,
+) {
+    while let Ok(event) = events.recv_async().await {
+        match event {
+            ServiceEvent::ServiceResolved(service) => {
+                if service.fullname == own_fullname || !service.is_valid() {
+                    continue;
+                }
+
+                let Some(address) = service
+                    .addresses
+                    .iter()
+                    .map(|address| address.to_ip_addr())
+                    .min_by_key(|address| address.is_ipv6())
+                else {
+                    continue;
+                };
+
+                let name = service
+                    .fullname
+                    .strip_suffix(&format!(".{SERVICE_TYPE}"))
+                    .unwrap_or(&service.fullname)
+                    .to_string();
+
+                tracing::debug!("Found qobuz-player instance {name} at {address}");
+                peers.write().insert(
+                    service.fullname.clone(),
+                    Peer {
+                        name,
+                        fullname: service.fullname.clone(),
+                        address,
+                        port: service.port,
+                        version: service
+                            .get_property_val_str("version")
+                            .map(ToString::to_string),
+                        auth_required: service.get_property_val_str("auth") == Some("true"),
+                    },
+                );
+            }
+            ServiceEvent::ServiceRemoved(_, fullname) => {
+                peers.write().remove(&fullname);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// mDNS host names only allow letters, digits and hyphens
+fn host_label(name: &str) -> String {
+    let label: String = name
+        .chars()
+        .map(|character| match character.is_ascii_alphanumeric() {
+            true => character.to_ascii_lowercase(),
+            false => '-',
+        })
+        .collect();
+
+    match label.trim_matches('-') {
+        "" => "qobuz-player".to_string(),
+        label => label.to_string(),
+    }
+}
+
+/// Name to advertise when none is configured
+pub fn default_instance_name() -> String {
+    std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| "qobuz-player".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::host_label;
+
+    #[test]
+    fn host_labels_only_have_letters_digits_and_hyphens() {
+        assert_eq!(host_label("Living Room"), "living-room");
+        assert_eq!(host_label("Køkken #2"), "k-kken--2");
+        assert_eq!(host_label(" (pi) "), "pi");
+        assert_eq!(host_label("Æø"), "qobuz-player");
+    }
+}
@@ -2,24 +2,30 @@ use assets::static_handler;
 use axum::{
     Router,
     extract::State,
-    http::{HeaderMap, StatusCode},
+    http::{HeaderMap, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Response, Sse, sse::Event},
     routing::get,
 };
 use futures::stream::Stream;
+use qobuz_player_cli::StartupSummary;
+use qobuz_player_connect::ConnectionStatusReceiver;
 use qobuz_player_controls::{
-    AppResult, PositionReceiver, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
+    AppResult, PositionReceiver, QuietHoursReceiver, Status, StatusReceiver, TracklistReceiver,
+    TransitionReceiver, VolumeReceiver, ZonesReceiver,
+    artwork_cache::ArtworkCache,
     client::Client,
     controls::Controls,
     database::Database,
-    error::Error,
-    models::{Album, AlbumSimple, Playlist},
-    notification::{Notification, NotificationBroadcast},
+    diagnostics::Diagnostics,
+    error::{Error, ErrorKind},
+    log_buffer::LogBuffer,
+    models::{Album, AlbumSimple, LinkTarget, Playlist},
+    notification::{Notification, NotificationBroadcast, TrackFormat},
 };
 use qobuz_player_rfid::RfidState;
 use serde_json::json;
 use skabelon::Templates;
-use std::{convert::Infallible, env, path::PathBuf, sync::Arc};
+use std::{convert::Infallible, env, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 use tokio::sync::{
     broadcast::{self, Receiver, Sender},
     watch,
@@ -29,15 +35,24 @@ use tokio_stream::wrappers::BroadcastStream;
 
 use crate::{
     app_state::AppState,
+    discovery::Discovery,
     routes::{
-        album, api, artist, auth, controls, discover, favorites, now_playing, playlist, queue,
-        search,
+        album, api, artist, artwork, auth, composer, controls, discover, events, favorites,
+        instances, label, logs, now_playing, party, playlist, queue, recent, rfid, search,
+        settings, smart_playlist, status, theme, transitions,
     },
     views::templates,
 };
 
+pub use discovery::default_instance_name;
+pub use onboarding::onboarding;
+pub use routes::auth::{WebAuth, WebRole, WebUser, read_web_users};
+pub use routes::theme::AccentColor;
+
 mod app_state;
 mod assets;
+mod discovery;
+mod onboarding;
 mod routes;
 mod views;
 
@@ -47,34 +62,59 @@ pub async fn init(
     position_receiver: PositionReceiver,
     tracklist_receiver: TracklistReceiver,
     volume_receiver: VolumeReceiver,
+    quiet_hours_receiver: QuietHoursReceiver,
     status_receiver: StatusReceiver,
+    zones_receiver: ZonesReceiver,
+    transition_receiver: TransitionReceiver,
     port: u16,
-    web_secret: Option<String>,
+    web_auth: WebAuth,
+    accent_color: Option<AccentColor>,
     rfid_state: Option<RfidState>,
     broadcast: Arc<NotificationBroadcast>,
     client: Arc<Client>,
     database: Arc<Database>,
+    artwork_cache: Arc<ArtworkCache>,
+    log_buffer: Arc<LogBuffer>,
+    startup_summary: StartupSummary,
+    connection_status: Option<ConnectionStatusReceiver>,
 ) -> AppResult<()> {
     let interface = format!("0.0.0.0:{port}");
     let listener = tokio::net::TcpListener::bind(&interface)
         .await
         .or(Err(Error::PortInUse { port }))?;
 
+    let discovery = Discovery::start(startup_summary.mdns_name.clone(), port, web_auth.enabled());
+
     let router = create_router(
         controls,
         position_receiver,
         tracklist_receiver,
         volume_receiver,
+        quiet_hours_receiver,
         status_receiver,
-        web_secret,
+        zones_receiver,
+        transition_receiver,
+        web_auth,
+        accent_color,
         rfid_state,
         broadcast,
         client,
         database,
+        artwork_cache,
+        log_buffer,
+        startup_summary,
+        discovery,
+        connection_status,
     )
     .await;
 
-    axum::serve(listener, router).await.expect("infallible");
+    // Party votes are told apart by the address of the guest
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .expect("infallible");
     Ok(())
 }
 
@@ -84,12 +124,21 @@ async fn create_router(
     position_receiver: PositionReceiver,
     tracklist_receiver: TracklistReceiver,
     volume_receiver: VolumeReceiver,
+    quiet_hours_receiver: QuietHoursReceiver,
     status_receiver: StatusReceiver,
-    web_secret: Option<String>,
+    zones_receiver: ZonesReceiver,
+    transition_receiver: TransitionReceiver,
+    web_auth: WebAuth,
+    accent_color: Option<AccentColor>,
     rfid_state: Option<RfidState>,
     broadcast: Arc<NotificationBroadcast>,
     client: Arc<Client>,
     database: Arc<Database>,
+    artwork_cache: Arc<ArtworkCache>,
+    log_buffer: Arc<LogBuffer>,
+    startup_summary: StartupSummary,
+    discovery: Discovery,
+    connection_status: Option<ConnectionStatusReceiver>,
 ) -> Router {
     let (tx, _rx) = broadcast::channel::<ServerSentEvent>(100);
     let broadcast_subscribe = broadcast.subscribe();
@@ -100,6 +149,8 @@ async fn create_router(
 
     #[allow(unused_variables)]
     let (templates_tx, templates_rx) = watch::channel(templates);
+    let (stream_format_tx, stream_format) = watch::channel(None);
+    let (diagnostics_tx, diagnostics) = watch::channel(Diagnostics::default());
 
     #[cfg(all(debug_assertions, target_os = "linux"))]
     {
@@ -131,9 +182,11 @@ async fn create_router(
         watcher.start();
     }
 
+    let event_log = Arc::new(events::EventLog::default());
     let shared_state = Arc::new(AppState {
         controls,
-        web_secret,
+        web_auth,
+        accent_color,
         rfid_state,
         broadcast,
         client,
@@ -141,18 +194,42 @@ async fn create_router(
         position_receiver: position_receiver.clone(),
         tracklist_receiver: tracklist_receiver.clone(),
         volume_receiver: volume_receiver.clone(),
+        quiet_hours_receiver,
         status_receiver: status_receiver.clone(),
+        zones_receiver,
+        transition_receiver,
+        stream_format,
+        diagnostics,
         templates: templates_rx.clone(),
         database,
+        artwork_cache,
+        log_buffer,
+        startup_summary,
+        discovery,
+        connection_status,
+        proxy_client: reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("infallible"),
+        event_log: event_log.clone(),
+        party_votes: party::PartyVotes::default(),
+        device_check: Default::default(),
     });
 
+    if shared_state.web_auth.party {
+        tokio::spawn(party::reorder_on_transition(shared_state.clone()));
+    }
+
     tokio::spawn(background_task(
         tx,
+        event_log,
         broadcast_subscribe,
         position_receiver,
         tracklist_receiver,
         volume_receiver,
         status_receiver,
+        stream_format_tx,
+        diagnostics_tx,
         templates_rx,
     ));
 
@@ -160,30 +237,53 @@ async fn create_router(
         .route("/sse", get(sse_handler))
         .merge(now_playing::routes())
         .merge(queue::routes())
+        .merge(recent::routes())
+        .merge(rfid::routes())
         .merge(api::routes())
         .merge(search::routes())
         .merge(album::routes())
         .merge(artist::routes())
+        .merge(composer::routes())
+        .merge(label::routes())
         .merge(playlist::routes())
+        .merge(smart_playlist::routes())
         .merge(favorites::routes())
         .merge(discover::routes())
         .merge(controls::routes())
+        .merge(settings::routes())
+        .merge(status::routes())
+        .merge(logs::routes())
+        .merge(transitions::routes())
+        .merge(events::routes())
+        .merge(party::routes())
+        .merge(artwork::routes())
+        .layer(axum::middleware::from_fn_with_state(
+            shared_state.clone(),
+            instances::proxy_middleware,
+        ))
+        .merge(instances::routes())
         .layer(axum::middleware::from_fn_with_state(
             shared_state.clone(),
             auth::auth_middleware,
         ))
         .route("/assets/{*file}", get(static_handler))
+        .merge(theme::routes())
         .merge(auth::routes())
+        .merge(status::health_routes())
         .with_state(shared_state.clone())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn background_task(
     tx: Sender<ServerSentEvent>,
+    event_log: Arc<events::EventLog>,
     mut receiver: Receiver<Notification>,
     mut position: PositionReceiver,
     mut tracklist: TracklistReceiver,
     mut volume: VolumeReceiver,
     mut status: StatusReceiver,
+    stream_format: watch::Sender<Option<TrackFormat>>,
+    diagnostics: watch::Sender<Diagnostics>,
     templates: watch::Receiver<Templates>,
 ) {
     loop {
@@ -198,7 +298,8 @@ async fn background_task(
                 _ = tx.send(event);
             },
             Ok(_) = tracklist.changed() => {
-                _ = tracklist.borrow_and_update();
+                let now_playing_id = tracklist.borrow_and_update().currently_playing();
+                event_log.push("tracklist", json!({ "now_playing_id": now_playing_id }));
                 let event = ServerSentEvent {
                     event_name: "tracklist".into(),
                     event_data: "new tracklist".into(),
@@ -208,6 +309,7 @@ async fn background_task(
             Ok(_) = volume.changed() => {
                 let volume = *volume.borrow_and_update();
                 let volume = (volume * 100.0) as u32;
+                event_log.push("volume", json!(volume));
                 let event = ServerSentEvent {
                     event_name: "volume".into(),
                     event_data: volume.to_string(),
@@ -221,6 +323,7 @@ async fn background_task(
                     Status::Playing => "play",
                     Status::Buffering => "buffering",
                 };
+                event_log.push("status", json!(message_data));
 
                 let event = ServerSentEvent {
                     event_name: "status".into(),
@@ -229,37 +332,91 @@ async fn background_task(
                 _ = tx.send(event);
             }
             notification = receiver.recv() => {
-                tracing::info!("notification: {:?}", notification);
+                if let Ok(Notification::BufferProgress(progress)) = notification {
+                    let is_playing = tracklist
+                        .borrow()
+                        .current_track()
+                        .is_some_and(|track| track.id == progress.track_id);
+
+                    if is_playing {
+                        event_log.push("buffer-progress", json!(progress.percent));
+                        _ = tx.send(ServerSentEvent {
+                            event_name: "buffer-progress".into(),
+                            event_data: progress.percent.to_string(),
+                        });
+                    }
+                    continue;
+                }
+
+                if let Ok(Notification::StreamFormat(format)) = notification {
+                    stream_format.send_replace(Some(format));
+                    let is_playing = tracklist
+                        .borrow()
+                        .current_track()
+                        .is_some_and(|track| track.id == format.track_id);
+
+                    if is_playing {
+                        event_log.push("stream-format", json!(format.format.to_string()));
+                        _ = tx.send(ServerSentEvent {
+                            event_name: "stream-format".into(),
+                            event_data: format.format.to_string(),
+                        });
+                    }
+                    continue;
+                }
+
+                if let Ok(Notification::Diagnostics(latest)) = notification {
+                    diagnostics.send_replace(latest);
+                    continue;
+                }
+
+                if let Ok(Notification::ResumeAvailable(resume_point)) = notification {
+                    event_log.push("resume-available", json!(resume_point));
+                    let position = Duration::from_secs(resume_point.position_seconds);
+                    let toast = templates.borrow().render(
+                        "resume-toast.html",
+                        &json!({
+                            "position": now_playing::format_position(position),
+                            "track_id": resume_point.track_id,
+                            "position_seconds": resume_point.position_seconds,
+                        }),
+                    );
+                    _ = tx.send(ServerSentEvent {
+                        event_name: "info".into(),
+                        event_data: toast,
+                    });
+                    continue;
+                }
+
+                if let Ok(Notification::SignInRequired) = notification {
+                    event_log.push("sign-in-required", json!(null));
+                    let toast = templates.borrow().render("sign-in-toast.html", &json!({}));
+                    _ = tx.send(ServerSentEvent {
+                        event_name: "error".into(),
+                        event_data: toast,
+                    });
+                    continue;
+                }
+
                 if let Ok(message) = notification {
-                        let (message_string, severity) = match &message {
-                            Notification::Error(message) => (message, 1),
-                            Notification::Warning(message) => (message, 2),
-                            Notification::Success(message) => (message, 3),
-                            Notification::Info(message) => (message, 4),
-                        };
+                    let (message_string, severity, event_name) = match &message {
+                        Notification::Error(message) => (message, 1, "error"),
+                        Notification::Warning(message) => (message, 2, "warn"),
+                        Notification::Success(message) => (message, 3, "success"),
+                        Notification::Info(message) => (message, 4, "info"),
+                        Notification::BufferProgress(_)
+                        | Notification::StreamFormat(_)
+                        | Notification::Diagnostics(_)
+                        | Notification::ResumeAvailable(_)
+                        | Notification::SignInRequired => continue,
+                    };
+                    tracing::info!("notification: {:?}", message);
+                    event_log.push(event_name, json!(message_string));
 
                     let toast = templates.borrow().render("toast.html", &json!({"message": message_string, "severity": severity}));
-                    let event = match message {
-                        Notification::Error(_) => ServerSentEvent {
-                            event_name: "error".into(),
-                            event_data: toast,
-                        },
-                        Notification::Warning(_) => {
-                            ServerSentEvent {
-                                event_name: "warn".into(),
-                                event_data: toast,
-                            }
-                        }
-                        Notification::Success(_) => {
-                            ServerSentEvent {
-                                event_name: "success".into(),
-                                event_data: toast,
-                            }
-                        }
-                        Notification::Info(_) => ServerSentEvent {
-                            event_name: "info".into(),
-                            event_data: toast,
-                        },
+                    let event = ServerSentEvent {
+                        event_name: event_name.into(),
+                        event_data: toast,
                     };
                     _ = tx.send(event);
                 }
@@ -315,7 +472,7 @@ fn ok_or_send_error_toast<T>(
 ) -> AppResult<T, axum::response::Response> {
     match value {
         Ok(value) => Ok(value),
-        Err(err) => Err(state.send_toast(Notification::Error(err.to_string()))),
+        Err(err) => Err(state.send_toast((&err).into())),
     }
 }
 
@@ -326,12 +483,13 @@ fn ok_or_error_page<T>(
 ) -> AppResult<T, axum::response::Response> {
     match value {
         Ok(value) => Ok(value),
-        Err(err) => Err(Html(
-            state
-                .templates
-                .borrow()
-                .render("error-page.html", &json!({"error": err.to_string()})),
-        )
+        Err(err) => Err(Html(state.templates.borrow().render(
+            "error-page.html",
+            &json!({
+                "error": err.to_string(),
+                "sign_in": matches!(err, Error::InvalidCredentials),
+            }),
+        ))
         .into_response()),
     }
 }
@@ -344,7 +502,7 @@ fn ok_or_broadcast<T>(
     match value {
         Ok(value) => Ok(value),
         Err(err) => {
-            broadcast.send(Notification::Error(err.to_string()));
+            broadcast.report(&err);
 
             let mut response = Html("<div></div>".to_string()).into_response();
             let headers = response.headers_mut();
@@ -355,8 +513,33 @@ fn ok_or_broadcast<T>(
     }
 }
 
+/// Json api error, with a status telling clients whether Qobuz, the login, the item or the audio
+/// device is the problem
+pub(crate) fn api_error(err: &Error, fallback: StatusCode) -> Response {
+    let status = match err.kind() {
+        ErrorKind::ContentUnavailable => StatusCode::NOT_FOUND,
+        ErrorKind::Network | ErrorKind::Authorization | ErrorKind::AudioDevice => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        ErrorKind::Other => fallback,
+    };
+    (status, err.to_string()).into_response()
+}
+
 pub fn hx_redirect(url: &str) -> Response {
     let mut headers = HeaderMap::new();
     headers.insert("HX-Redirect", url.parse().unwrap());
     (StatusCode::OK, headers).into_response()
 }
+
+/// Has the browser copy the Qobuz url of the target, see the `copy-link` listener in script.js
+pub fn copy_link(state: &AppState, target: &LinkTarget) -> Response {
+    let trigger = json!({"copy-link": target.url()}).to_string();
+    let Ok(trigger) = HeaderValue::from_str(&trigger) else {
+        return (StatusCode::BAD_REQUEST, "Invalid link").into_response();
+    };
+
+    let mut response = state.send_toast(Notification::Info("Link copied".into()));
+    response.headers_mut().insert("HX-Trigger", trigger);
+    response
+}
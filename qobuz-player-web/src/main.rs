@@ -1,29 +1,73 @@
+#[cfg(feature = "display")]
+use qobuz_player_cli::DisplayArgs;
 #[cfg(feature = "gpio")]
 use qobuz_player_cli::GpioArgs;
 use qobuz_player_cli::{
-    ConnectArgs, DelayArgs, RfidArgs, SharedArgs, SharedCommands, create_player,
-    default_audio_quality, get_client, handle_shared_commands, spawn_clean_up,
+    ConnectArgs, DelayArgs, RfidArgs, SharedArgs, SharedCommands, Startup, StartupSummary,
+    create_player, default_audio_quality, default_output_device, get_client,
+    handle_shared_commands, spawn_alarms, spawn_clean_up, spawn_favorites_sync, spawn_group,
+    spawn_hooks, spawn_network_monitor, spawn_play_link, spawn_release_notifications,
+    spawn_webhooks,
 };
+use qobuz_player_connect::ConnectionStatus;
 use qobuz_player_rfid::RfidState;
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use qobuz_player_web::{AccentColor, WebAuth, default_instance_name, read_web_users};
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::{broadcast, watch};
 
 use clap::Parser;
 use qobuz_player_controls::{
-    AppResult, database::Database, error::Error, notification::NotificationBroadcast,
+    AppResult, database::Database, error::Error, log_buffer::LogBuffer,
+    notification::NotificationBroadcast,
 };
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Arguments {
-    #[clap(long)]
+    #[clap(long, env = "QOBUZ_PLAYER_WEB_SECRET", hide_env_values = true)]
     /// Secret used for web ui auth
     web_secret: Option<String>,
 
+    #[clap(long, env = "QOBUZ_PLAYER_WEB_USERS_FILE")]
+    /// File with a named web ui user per line as name:password:role, where role is viewer or
+    /// controller (default controller)
+    web_users_file: Option<PathBuf>,
+
+    #[clap(long, default_value_t = 168)]
+    /// Hours a web ui login stays valid
+    web_session_hours: u32,
+
+    #[clap(long, default_value_t = 1000)]
+    /// Number of recent log lines kept for the log viewer
+    log_lines: usize,
+
+    #[clap(long, default_value_t = false)]
+    /// Let anyone see the web ui and JSON api read-only. Playback control, favorites and
+    /// playlists still require a login
+    web_guest: bool,
+
+    #[clap(long, default_value_t = false)]
+    /// Let anyone search and suggest tracks at /party. Suggestions are queued once a
+    /// controller approves them
+    web_party: bool,
+
+    #[clap(long)]
+    /// Color of primary buttons and sliders in the web ui, as a hex color like #e11d48
+    web_accent_color: Option<AccentColor>,
+
     #[clap(long, default_value_t = 9888)]
     /// Specify port for the web server
     port: u16,
 
+    #[clap(long)]
+    /// Name other players and remote apps see for this player. Defaults to the hostname
+    instance_name: Option<String>,
+
+    #[clap(long, default_value_t = false)]
+    /// Do not advertise the web server with mDNS (_qobuz-player._tcp) or look for other players
+    disable_mdns: bool,
+
     #[clap(long, default_value_t = false)]
     /// Enable rfid interface
     rfid: bool,
@@ -44,6 +88,10 @@ struct Arguments {
     #[clap(flatten)]
     gpio: GpioArgs,
 
+    #[cfg(feature = "display")]
+    #[clap(flatten)]
+    display: DisplayArgs,
+
     #[clap(subcommand)]
     command: Option<SharedCommands>,
 }
@@ -59,48 +107,127 @@ async fn main() {
 }
 
 pub async fn run() -> AppResult<()> {
-    tracing_subscriber::fmt().compact().init();
-    let headless = true;
+    let mut args = Arguments::parse();
 
-    let args = Arguments::parse();
+    let log_buffer = LogBuffer::new(args.log_lines);
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().compact())
+        .with(log_buffer.layer())
+        .with(LevelFilter::INFO)
+        .init();
+    let web_users = match &args.web_users_file {
+        Some(path) => read_web_users(path).map_err(|message| Error::Client { message })?,
+        None => Vec::new(),
+    };
+    let headless = true;
     let database = Arc::new(Database::new().await?);
+    database.set_profile(&args.shared.profile).await?;
+    args.shared.output_device_id =
+        default_output_device(&database, args.shared.output_device_id.take()).await?;
 
-    if let Some(command) = args.command {
-        handle_shared_commands(command, &database, headless).await?;
-        return Ok(());
-    }
+    let play_link =
+        match handle_shared_commands::<Arguments>(args.command, &args.shared, &database, headless)
+            .await?
+        {
+            Startup::Run(play_link) => play_link,
+            Startup::Exit => return Ok(()),
+        };
 
     let (_, exit_receiver) = broadcast::channel(5);
 
+    if args.shared.fixtures().is_none() && database.get_credentials().await?.is_none() {
+        qobuz_player_web::onboarding(args.port, database.clone()).await?;
+    }
+
     let max_audio_quality = default_audio_quality(&database, args.shared.max_audio_quality).await?;
-    let client = get_client(&database, max_audio_quality, headless).await?;
+    let client = get_client(
+        &database,
+        max_audio_quality,
+        headless,
+        args.shared.fixtures(),
+    )
+    .await?;
     let client = Arc::new(client);
+    spawn_favorites_sync(
+        database.clone(),
+        client.clone(),
+        args.shared.favorites_refresh_minutes,
+    )
+    .await;
+    spawn_release_notifications(database.clone(), client.clone(), &args.shared);
 
     let broadcast = Arc::new(NotificationBroadcast::new());
 
+    let mut summary = StartupSummary::new(&args.shared, max_audio_quality);
+    summary.web_port = Some(args.port);
+    summary.mdns_name = (!args.disable_mdns).then(|| {
+        args.instance_name
+            .clone()
+            .unwrap_or_else(default_instance_name)
+    });
+    summary.rfid_reader_detected = args.rfid.then(qobuz_player_rfid::reader_detected);
+    summary.connect_name = args
+        .connect
+        .connect
+        .then(|| args.connect.name_args.connect_name.clone());
+    #[cfg(feature = "gpio")]
+    {
+        summary.gpio = args.gpio.gpio;
+    }
+    #[cfg(feature = "display")]
+    {
+        summary.display = args.display.display.is_some();
+    }
+    println!("{summary}");
+
+    let audio_output = args.shared.audio_output(false)?;
+    let hooks = args.shared.hooks();
+    let group_role = args.shared.group_role();
+    let artwork_cache = args.shared.artwork();
+
     let mut player = create_player(
-        args.shared.audio_cache,
+        &args.shared,
+        Some(&args.delay),
+        audio_output,
         database.clone(),
         client.clone(),
         broadcast.clone(),
-        args.delay.state_change_delay_ms,
-        args.delay.sample_rate_change_delay_ms,
-        args.shared.output_device_id,
     )
     .await?;
+    spawn_network_monitor(client.clone(), player.controls());
+    spawn_alarms(
+        database.clone(),
+        client.clone(),
+        &player,
+        broadcast.clone(),
+        args.shared.alarm_ramp_seconds,
+    );
+    spawn_play_link(
+        play_link,
+        client.clone(),
+        player.controls(),
+        broadcast.clone(),
+    );
 
     let rfid_state = args.rfid.then(RfidState::default);
+    let (connection_status, connection_status_receiver) =
+        watch::channel(ConnectionStatus::default());
 
     {
         let position_receiver = player.position();
         let tracklist_receiver = player.tracklist();
         let volume_receiver = player.volume();
+        let quiet_hours_receiver = player.quiet_hours();
         let status_receiver = player.status();
+        let zones_receiver = player.zones();
+        let transition_receiver = player.transitions();
         let controls = player.controls();
         let broadcast = broadcast.clone();
         let client = client.clone();
         let database = database.clone();
         let rfid_state = rfid_state.clone();
+        let summary = summary.clone();
+        let connection_status_receiver = args.connect.connect.then_some(connection_status_receiver);
 
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_web::init(
@@ -108,13 +235,27 @@ pub async fn run() -> AppResult<()> {
                 position_receiver,
                 tracklist_receiver,
                 volume_receiver,
+                quiet_hours_receiver,
                 status_receiver,
+                zones_receiver,
+                transition_receiver,
                 args.port,
-                args.web_secret,
+                WebAuth {
+                    secret: args.web_secret,
+                    users: web_users,
+                    session_lifetime: time::Duration::hours(args.web_session_hours.into()),
+                    guest: args.web_guest,
+                    party: args.web_party,
+                },
+                args.web_accent_color,
                 rfid_state,
                 broadcast,
                 client,
                 database,
+                artwork_cache,
+                log_buffer,
+                summary,
+                connection_status_receiver,
             )
             .await
             {
@@ -125,22 +266,52 @@ pub async fn run() -> AppResult<()> {
 
     #[cfg(feature = "gpio")]
     if args.gpio.gpio {
+        let gpio_settings = args.gpio.settings();
         let status_receiver = player.status();
+        let volume_receiver = player.volume();
+        let controls = player.controls();
+        tokio::spawn(async move {
+            if let Err(e) =
+                qobuz_player_gpio::init(gpio_settings, status_receiver, volume_receiver, controls)
+                    .await
+            {
+                error_exit(e);
+            }
+        });
+    }
+
+    #[cfg(feature = "display")]
+    if let Some(display_settings) = args.display.settings() {
+        let tracklist_receiver = player.tracklist();
+        let position_receiver = player.position();
+        let status_receiver = player.status();
+        let volume_receiver = player.volume();
         tokio::spawn(async move {
-            if let Err(e) = qobuz_player_gpio::init(status_receiver).await {
-                error_exit(e.into());
+            if let Err(e) = qobuz_player_display::init(
+                display_settings,
+                tracklist_receiver,
+                position_receiver,
+                status_receiver,
+                volume_receiver,
+            )
+            .await
+            {
+                error_exit(e);
             }
         });
     }
 
     if let Some(rfid_state) = rfid_state {
         let controls = player.controls();
+        let client = client.clone();
         let database = database.clone();
+        let broadcast = broadcast.clone();
 
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_rfid::init(
                 rfid_state,
                 controls,
+                client,
                 database,
                 broadcast,
                 args.rfid_config.rfid_server_base_address,
@@ -158,18 +329,23 @@ pub async fn run() -> AppResult<()> {
         let position_receiver = player.position();
         let tracklist_receiver = player.tracklist();
         let volume_receiver = player.volume();
+        let muted_receiver = player.muted();
         let status_receiver = player.status();
         let controls = player.controls();
+        let broadcast = broadcast.clone();
 
         tokio::spawn(async move {
             if let Err(e) = qobuz_player_connect::init(
                 &app_id,
                 args.connect.name_args.connect_name,
                 controls,
+                broadcast,
+                connection_status,
                 position_receiver,
                 tracklist_receiver,
                 status_receiver,
                 volume_receiver,
+                muted_receiver,
                 max_audio_quality,
             )
             .await
@@ -179,6 +355,14 @@ pub async fn run() -> AppResult<()> {
         });
     }
 
+    spawn_webhooks(args.shared.webhook_urls.clone(), &player);
+
+    spawn_hooks(hooks, &player);
+
+    if let Some(group_role) = group_role {
+        spawn_group(group_role, &player);
+    }
+
     spawn_clean_up(database, args.shared.audio_cache_time_to_live);
     player.player_loop(exit_receiver).await?;
 
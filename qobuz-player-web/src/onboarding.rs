@@ -0,0 +1,184 @@
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{
+    Router,
+    extract::State,
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+};
+use axum_extra::extract::Form;
+use qobuz_player_controls::{
+    AppResult, AudioQuality,
+    client::{Client, exchange_oauth_code, get_app_id},
+    database::{Credentials, Database},
+    error::Error,
+};
+use serde::Deserialize;
+use serde_json::json;
+use skabelon::Templates;
+use tokio::sync::watch;
+
+use crate::{assets::static_handler, routes::settings::audio_quality_options, views::templates};
+
+struct SetupState {
+    templates: Templates,
+    database: Arc<Database>,
+    app_id: String,
+    done: watch::Sender<bool>,
+}
+
+impl SetupState {
+    fn render(&self, context: serde_json::Value) -> Response {
+        Html(self.templates.render("setup.html", &context)).into_response()
+    }
+
+    fn login_page(&self, error: Option<String>) -> Response {
+        self.render(json!({
+            "step": "login",
+            "login_action": "/setup/login",
+            "oauth_url": oauth_url(&self.app_id),
+            "error": error,
+        }))
+    }
+}
+
+/// Serves a setup page on the web ui port until a Qobuz account is signed in to and a quality
+/// is chosen, so the player can be set up from the browser. Nothing else is served meanwhile,
+/// and the page is unauthenticated as there is nothing to protect before the login.
+pub async fn onboarding(port: u16, database: Arc<Database>) -> AppResult<()> {
+    let interface = format!("0.0.0.0:{port}");
+    let listener = tokio::net::TcpListener::bind(&interface)
+        .await
+        .or(Err(Error::PortInUse { port }))?;
+
+    let (done, mut done_receiver) = watch::channel(false);
+    let template_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("templates");
+    let state = Arc::new(SetupState {
+        templates: templates(&template_path),
+        database,
+        app_id: get_app_id().await?,
+        done,
+    });
+
+    let router = Router::new()
+        .route("/", get(index))
+        .route("/setup/login", post(login))
+        .route("/setup/quality", post(quality))
+        .route("/assets/{*file}", get(static_handler))
+        .with_state(state);
+
+    println!("No Qobuz account is signed in to, finish the setup at http://localhost:{port}");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            _ = done_receiver.wait_for(|done| *done).await;
+        })
+        .await
+        .expect("infallible");
+
+    Ok(())
+}
+
+async fn index(State(state): State<Arc<SetupState>>) -> impl IntoResponse {
+    state.login_page(None)
+}
+
+#[derive(Deserialize)]
+struct LoginParameters {
+    code: String,
+}
+
+async fn login(
+    State(state): State<Arc<SetupState>>,
+    Form(parameters): Form<LoginParameters>,
+) -> impl IntoResponse {
+    let credentials: AppResult<Credentials> = async {
+        let credentials = exchange_code(&parameters.code, &state.app_id).await?;
+        Client::new(Some(credentials.clone()), AudioQuality::Mp3)
+            .favorites()
+            .await?;
+        Ok(credentials)
+    }
+    .await;
+
+    let credentials = match credentials {
+        Ok(credentials) => credentials,
+        Err(err) => return state.login_page(Some(err.to_string())),
+    };
+
+    if let Err(err) = state.database.set_credentials(credentials).await {
+        return state.login_page(Some(err.to_string()));
+    }
+
+    state.render(json!({
+        "step": "quality",
+        "audio_qualities": audio_quality_options(AudioQuality::HIFI192),
+    }))
+}
+
+#[derive(Deserialize)]
+struct QualityParameters {
+    quality: i64,
+}
+
+async fn quality(
+    State(state): State<Arc<SetupState>>,
+    Form(parameters): Form<QualityParameters>,
+) -> impl IntoResponse {
+    let quality = match AudioQuality::try_from(parameters.quality) {
+        Ok(quality) => quality,
+        Err(message) => {
+            return state.render(json!({
+                "step": "quality",
+                "audio_qualities": audio_quality_options(AudioQuality::HIFI192),
+                "error": message,
+            }));
+        }
+    };
+    if let Err(err) = state.database.set_max_audio_quality(quality).await {
+        return state.render(json!({
+            "step": "quality",
+            "audio_qualities": audio_quality_options(quality),
+            "error": err.to_string(),
+        }));
+    }
+
+    _ = state.done.send(true);
+    state.render(json!({ "step": "done" }))
+}
+
+/// Qobuz sign in page, redirecting to a localhost url carrying the authorization code
+pub(crate) fn oauth_url(app_id: &str) -> String {
+    format!(
+        "https://www.qobuz.com/signin/oauth?ext_app_id={app_id}&redirect_url=http%3A%2F%2Flocalhost"
+    )
+}
+
+/// Credentials for the code, or the redirect url, pasted in the sign in form
+pub(crate) async fn exchange_code(input: &str, app_id: &str) -> AppResult<Credentials> {
+    Ok(exchange_oauth_code(authorization_code(input), app_id)
+        .await?
+        .into())
+}
+
+/// The code on its own, or taken from the url Qobuz redirected to after signing in
+fn authorization_code(input: &str) -> &str {
+    let input = input.trim();
+    match input.split_once("code_autorisation=") {
+        Some((_, code)) => code.split(['&', ' ', '#']).next().unwrap_or(code),
+        None => input,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::authorization_code;
+
+    #[test]
+    fn authorization_code_from_redirect_url() {
+        assert_eq!(
+            authorization_code(" http://localhost/?code_autorisation=abc123&foo=bar "),
+            "abc123"
+        );
+        assert_eq!(authorization_code("abc123"), "abc123");
+    }
+}
@@ -1,11 +1,25 @@
 pub mod album;
 pub mod api;
 pub mod artist;
+pub mod artwork;
 pub mod auth;
+pub mod composer;
 pub mod controls;
 pub mod discover;
+pub mod events;
 pub mod favorites;
+pub mod instances;
+pub mod label;
+pub mod logs;
 pub mod now_playing;
+pub mod party;
 pub mod playlist;
 pub mod queue;
+pub mod recent;
+pub mod rfid;
 pub mod search;
+pub mod settings;
+pub mod smart_playlist;
+pub mod status;
+pub mod theme;
+pub mod transitions;
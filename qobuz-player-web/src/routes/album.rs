@@ -7,11 +7,15 @@ use axum::{
     routing::{get, put},
 };
 use axum_extra::extract::Form;
-use qobuz_player_controls::notification::Notification;
+use qobuz_player_controls::{
+    database::ReferenceType,
+    models::{Album, LinkTarget},
+    notification::Notification,
+};
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::{AppState, ResponseResult, ok_or_send_error_toast};
+use crate::{AppState, ResponseResult, copy_link, hx_redirect, ok_or_send_error_toast};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
@@ -22,6 +26,8 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
         .route("/album/{id}/unset-favorite", put(unset_favorite))
         .route("/album/{id}/play", put(play))
         .route("/album/{id}/play/{track_position}", put(play_track))
+        .route("/album/{id}/play/{start}/{index}", put(play_work_track))
+        .route("/album/{id}/work/{start}/play", put(play_work))
         .route("/album/{id}/link", put(link))
         .route("/album/action", put(action))
 }
@@ -29,8 +35,13 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum Action {
+    Play,
     AddToQueue,
     PlayNext,
+    AddToPlaylist,
+    ToggleFavorite,
+    GoToArtist,
+    CopyLink,
 }
 #[derive(Deserialize)]
 struct ActionParameters {
@@ -42,6 +53,10 @@ async fn action(
     Form(req): Form<ActionParameters>,
 ) -> ResponseResult {
     match req.action {
+        Action::Play => {
+            state.controls.play_album(&req.id, 0);
+            Ok(().into_response())
+        }
         Action::AddToQueue => {
             let album_data = ok_or_send_error_toast(&state, state.get_album(&req.id).await)?;
             let track_ids = album_data.album.tracks.into_iter().map(|x| x.id).collect();
@@ -63,6 +78,24 @@ async fn action(
                 album_data.album.title
             ))))
         }
+        Action::AddToPlaylist => Ok(hx_redirect(&format!("/playlist/add-album/{}", req.id))),
+        Action::ToggleFavorite => {
+            let is_favorite =
+                ok_or_send_error_toast(&state, state.is_album_favorite(&req.id).await)?;
+
+            if is_favorite {
+                ok_or_send_error_toast(&state, state.client.remove_favorite_album(&req.id).await)?;
+                Ok(state.send_toast(Notification::Info("Album removed from favorites".into())))
+            } else {
+                ok_or_send_error_toast(&state, state.client.add_favorite_album(&req.id).await)?;
+                Ok(state.send_toast(Notification::Info("Album added to favorites".into())))
+            }
+        }
+        Action::GoToArtist => {
+            let album = ok_or_send_error_toast(&state, state.client.album(&req.id).await)?;
+            Ok(hx_redirect(&format!("/artist/{}", album.artist.id)))
+        }
+        Action::CopyLink => Ok(copy_link(&state, &LinkTarget::Album(req.id))),
     }
 }
 
@@ -73,6 +106,31 @@ async fn play_track(
     state.controls.play_album(&id, track_position);
 }
 
+async fn play_work_track(
+    State(state): State<Arc<AppState>>,
+    Path((id, start, index)): Path<(String, usize, usize)>,
+) -> impl IntoResponse {
+    state.controls.play_album(&id, start + index);
+}
+
+async fn play_work(
+    State(state): State<Arc<AppState>>,
+    Path((id, start)): Path<(String, usize)>,
+) -> ResponseResult {
+    let album = ok_or_send_error_toast(&state, state.client.album(&id).await)?;
+    let Some(work) = album.works().into_iter().find(|work| work.start == start) else {
+        return Ok(state.send_toast(Notification::Error("Work not found".into())));
+    };
+
+    let track_ids = work.tracks.into_iter().map(|x| x.id).collect();
+    state.controls.play_tracks(track_ids, false);
+
+    Ok(state.send_toast(Notification::Info(format!(
+        "Playing {}",
+        work.title.unwrap_or(album.title)
+    ))))
+}
+
 async fn set_favorite(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -101,17 +159,33 @@ async fn play(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> imp
     state.controls.play_album(&id, 0);
 }
 
-async fn link(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> impl IntoResponse {
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LinkMode {
+    Play,
+    Queue,
+}
+
+#[derive(Deserialize)]
+struct LinkParameters {
+    mode: LinkMode,
+}
+
+async fn link(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(parameters): Form<LinkParameters>,
+) -> impl IntoResponse {
     let Some(rfid_state) = state.rfid_state.clone() else {
         return;
     };
 
-    qobuz_player_rfid::link(
-        rfid_state,
-        qobuz_player_controls::database::ReferenceType::Album(id),
-        state.broadcast.clone(),
-    )
-    .await;
+    let reference = match parameters.mode {
+        LinkMode::Play => ReferenceType::Album(id),
+        LinkMode::Queue => ReferenceType::QueueAlbum(id),
+    };
+
+    qobuz_player_rfid::link(rfid_state, reference, state.broadcast.clone()).await;
 }
 
 async fn index(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> impl IntoResponse {
@@ -123,6 +197,8 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<String>) ->
     let album_data = ok_or_send_error_toast(&state, state.get_album(&id).await)?;
     let is_favorite = ok_or_send_error_toast(&state, state.is_album_favorite(&id).await)?;
 
+    state.controls.precache_album(&id);
+
     let duration = album_data.album.duration_seconds / 60;
 
     let click_string = format!("/album/{}/play/", album_data.album.id);
@@ -135,7 +211,8 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<String>) ->
             "suggested_albums": album_data.suggested_albums,
             "is_favorite": is_favorite,
             "rfid": state.rfid_state.is_some(),
-            "click": click_string
+            "click": click_string,
+            "works": works_json(&album_data.album),
         }),
     ))
 }
@@ -146,12 +223,34 @@ async fn album_tracks_partial(
 ) -> ResponseResult {
     let album = ok_or_send_error_toast(&state, state.client.album(&id).await)?;
     let click_string = format!("/album/{}/play/", album.id);
+    let works = works_json(&album);
 
     Ok(state.render(
         "album-tracks.html",
         &json!({
             "album": album,
-            "click": click_string
+            "click": click_string,
+            "works": works,
         }),
     ))
 }
+
+/// Track groups for albums with work metadata, empty when the album has none
+fn works_json(album: &Album) -> Vec<serde_json::Value> {
+    if !album.has_works() {
+        return vec![];
+    }
+
+    album
+        .works()
+        .into_iter()
+        .map(|work| {
+            json!({
+                "title": work.title,
+                "tracks": work.tracks,
+                "click": format!("/album/{}/play/{}/", album.id, work.start),
+                "play": format!("/album/{}/work/{}/play", album.id, work.start),
+            })
+        })
+        .collect()
+}
@@ -6,22 +6,28 @@ use std::{
 
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::StatusCode,
     response::IntoResponse,
     routing::{get, post, put},
 };
 use axum_extra::extract::Form;
 use qobuz_player_controls::{
-    AppResult,
+    AppResult, AudioQuality,
     client::Client,
-    database::ReferenceType,
-    models::{AlbumSimple, Artist, Playlist, Track},
+    controls::parse_position,
+    database::{BanKind, ReferenceType},
+    error::Error,
+    models::{AlbumSimple, Artist, LinkTarget, Playlist, SearchResults, Track},
     notification::Notification,
 };
-use qobuz_player_rfid::{LinkAlbumRfid, LinkPlaylistRfid, handle_play_scan};
-use serde::Deserialize;
+use qobuz_player_rfid::{LinkAlbumRfid, LinkPlaylistRfid, LinkRfid, handle_play_scan};
+use serde::{Deserialize, Serialize};
 
-use crate::{AppState, ResponseResult, hx_redirect, ok_or_send_error_toast};
+use crate::{
+    AppState, ResponseResult, api_error, copy_link, hx_redirect, ok_or_send_error_toast,
+    routes::search::SearchParameters,
+};
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -34,6 +40,7 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/api/volume", post(set_volume))
         .route("/api/volume/up", put(set_volume_up))
         .route("/api/volume/down", put(set_volume_down))
+        .route("/api/mute", put(toggle_mute))
         .route("/api/position", post(set_position))
         .route("/api/skip-to/{track_number}", put(skip_to))
         .route(
@@ -41,16 +48,24 @@ pub fn routes() -> Router<Arc<AppState>> {
             put(remove_index_from_queue),
         )
         .route("/api/track/play/{track_id}", put(play_track))
+        .route("/api/play/link", put(play_link))
         .route("/api/track/action", put(track_action))
+        .route("/api/copy-link/{item}", put(copy_current_link))
         .route("/api/queue/reorder", put(reorder_queue))
         .route("/api/favorites/albums", get(favorite_albums))
         .route("/api/favorites/artists", get(favorite_artists))
         .route("/api/favorites/playlists", get(favorite_playlists))
         .route("/api/favorites/tracks", get(favorite_tracks))
+        .route(
+            "/api/favorites/current",
+            get(current_favorite).put(toggle_current_favorite),
+        )
+        .route("/api/search", get(search))
         .route(
             "/api/rfid/reference/{reference}",
-            get(rfid_reference).put(play_rfid_reference),
+            post(rfid_reference).put(play_rfid_reference),
         )
+        .route("/api/rfid/reference", post(link_rfid_reference))
         .route("/api/rfid/reference/album", post(link_album_rfid_reference))
         .route(
             "/api/rfid/reference/playlist",
@@ -66,11 +81,19 @@ async fn playing_info(State(state): State<Arc<AppState>>) -> impl IntoResponse {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum TrackAction {
+    Play,
+    PlayInCdQuality,
+    PlayInMp3Quality,
     AddFavorite,
     RemoveFavorite,
     AddToQueue,
     PlayNext,
     AddToPlaylist,
+    GoToAlbum,
+    GoToArtist,
+    CopyLink,
+    BanTrack,
+    BanArtist,
 }
 #[derive(Deserialize)]
 struct TrackActionParameters {
@@ -82,6 +105,22 @@ async fn track_action(
     Form(req): Form<TrackActionParameters>,
 ) -> ResponseResult {
     match req.action {
+        TrackAction::Play => {
+            state.controls.play_track(req.track_id);
+            Ok(().into_response())
+        }
+        TrackAction::PlayInCdQuality => {
+            state
+                .controls
+                .play_track_in_quality(req.track_id, AudioQuality::CD);
+            Ok(().into_response())
+        }
+        TrackAction::PlayInMp3Quality => {
+            state
+                .controls
+                .play_track_in_quality(req.track_id, AudioQuality::Mp3);
+            Ok(().into_response())
+        }
         TrackAction::AddFavorite => {
             ok_or_send_error_toast(&state, state.client.add_favorite_track(req.track_id).await)?;
             state.send_sse("tracklist".into(), "New favorite track".into());
@@ -109,6 +148,83 @@ async fn track_action(
             "/playlist/add-track/{}",
             req.track_id
         ))),
+        TrackAction::GoToAlbum => {
+            let track = ok_or_send_error_toast(&state, state.client.track(req.track_id).await)?;
+            match track.album_id {
+                Some(album_id) => Ok(hx_redirect(&format!("/album/{album_id}"))),
+                None => {
+                    Ok(state
+                        .send_toast(Notification::Warning("The track is not on an album".into())))
+                }
+            }
+        }
+        TrackAction::GoToArtist => {
+            let track = ok_or_send_error_toast(&state, state.client.track(req.track_id).await)?;
+            match track.artist_id {
+                Some(artist_id) => Ok(hx_redirect(&format!("/artist/{artist_id}"))),
+                None => {
+                    Ok(state.send_toast(Notification::Warning("The track has no artist".into())))
+                }
+            }
+        }
+        TrackAction::CopyLink => Ok(copy_link(&state, &LinkTarget::Track(req.track_id))),
+        TrackAction::BanTrack => {
+            let track = ok_or_send_error_toast(&state, state.client.track(req.track_id).await)?;
+            let name = match &track.artist_name {
+                Some(artist) => format!("{artist} - {}", track.title),
+                None => track.title.clone(),
+            };
+            ok_or_send_error_toast(
+                &state,
+                state.database.ban(BanKind::Track, track.id, &name).await,
+            )?;
+            state.controls.apply_bans();
+            state.send_sse("tracklist".into(), "Banned track".into());
+            Ok(state.send_toast(Notification::Info(format!("{name} will not be played"))))
+        }
+        TrackAction::BanArtist => {
+            let track = ok_or_send_error_toast(&state, state.client.track(req.track_id).await)?;
+            let (Some(artist_id), Some(artist_name)) = (track.artist_id, track.artist_name) else {
+                return Ok(state.send_toast(Notification::Warning(
+                    "The track has no artist to ban".into(),
+                )));
+            };
+            ok_or_send_error_toast(
+                &state,
+                state
+                    .database
+                    .ban(BanKind::Artist, artist_id, &artist_name)
+                    .await,
+            )?;
+            state.controls.apply_bans();
+            state.send_sse("tracklist".into(), "Banned artist".into());
+            Ok(state.send_toast(Notification::Info(format!(
+                "{artist_name} will not be played"
+            ))))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CurrentItem {
+    Track,
+    List,
+}
+
+async fn copy_current_link(
+    State(state): State<Arc<AppState>>,
+    Path(item): Path<CurrentItem>,
+) -> ResponseResult {
+    let tracklist = state.tracklist_receiver.borrow().clone();
+    let target = match item {
+        CurrentItem::Track => tracklist.current_track_link(),
+        CurrentItem::List => tracklist.current_list_link(),
+    };
+
+    match target {
+        Some(target) => Ok(copy_link(&state, &target)),
+        None => Ok(state.send_toast(Notification::Warning("Nothing is playing".into()))),
     }
 }
 
@@ -134,8 +250,65 @@ async fn remove_index_from_queue(
 async fn play_track(
     State(state): State<Arc<AppState>>,
     Path(track_id): Path<u32>,
+    Query(parameters): Query<PlayTrackParameters>,
 ) -> impl IntoResponse {
-    state.controls.play_track(track_id);
+    match position(parameters.at.as_deref()) {
+        Ok(Some(at)) => state.controls.play_track_at(track_id, at),
+        Ok(None) => state.controls.play_track(track_id),
+        Err(err) => return api_error(&err, StatusCode::BAD_REQUEST),
+    }
+    StatusCode::OK.into_response()
+}
+
+#[derive(Deserialize)]
+struct PlayTrackParameters {
+    /// Position to start the track at, like 3:41
+    at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PlayLinkParameters {
+    link: String,
+    /// Position to start a track at, like 3:41
+    at: Option<String>,
+}
+
+/// Plays a Qobuz url or id, used by `qobuz-player play`
+async fn play_link(
+    State(state): State<Arc<AppState>>,
+    Json(parameters): Json<PlayLinkParameters>,
+) -> impl IntoResponse {
+    let at = match position(parameters.at.as_deref()) {
+        Ok(at) => at,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    let played = state
+        .client
+        .resolve_link(&parameters.link)
+        .await
+        .and_then(|entity| {
+            state.controls.play_linked_entity_at(&entity, at)?;
+            Ok(entity)
+        });
+
+    match played {
+        Ok(entity) => Json(serde_json::json!({"title": entity.title()})).into_response(),
+        Err(err @ Error::InvalidLink { .. }) => {
+            (StatusCode::BAD_REQUEST, err.to_string()).into_response()
+        }
+        Err(err) => api_error(&err, StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Position to start a track at, like 3:41. `None` when no position is given
+pub(crate) fn position(at: Option<&str>) -> AppResult<Option<Duration>> {
+    at.map(|at| {
+        parse_position(at).ok_or_else(|| Error::InvalidLink {
+            message: format!("{at} is not a position like 3:41"),
+        })
+    })
+    .transpose()
 }
 
 async fn play(State(state): State<Arc<AppState>>) -> impl IntoResponse {
@@ -210,6 +383,10 @@ async fn set_volume_down(State(state): State<Arc<AppState>>) -> impl IntoRespons
     state.controls.set_volume(new_volume);
 }
 
+async fn toggle_mute(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    state.controls.toggle_mute();
+}
+
 async fn set_position(
     State(state): State<Arc<AppState>>,
     axum::Form(parameters): axum::Form<SliderParameters>,
@@ -266,17 +443,76 @@ async fn get_favorite_tracks(client: &Client) -> AppResult<Vec<Track>> {
     Ok(favorites.tracks)
 }
 
+#[derive(Serialize)]
+struct CurrentFavorite {
+    track_id: u32,
+    is_favorite: bool,
+}
+
+async fn current_favorite(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let Some(track_id) = state.tracklist_receiver.borrow().currently_playing() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    Json(CurrentFavorite {
+        track_id,
+        is_favorite: state.client.is_favorite_track(track_id),
+    })
+    .into_response()
+}
+
+/// Adds the playing track to the favorites, or removes it if it is one
+async fn toggle_current_favorite(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let Some(track_id) = state.tracklist_receiver.borrow().currently_playing() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    match state.client.toggle_favorite_track(track_id).await {
+        Ok(is_favorite) => {
+            state.send_sse("tracklist".into(), "Toggled favorite track".into());
+            Json(CurrentFavorite {
+                track_id,
+                is_favorite,
+            })
+            .into_response()
+        }
+        // Everything but the lookup of the favorites goes to Qobuz
+        Err(err) => api_error(&err, StatusCode::BAD_GATEWAY),
+    }
+}
+
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Query(parameters): Query<SearchParameters>,
+) -> impl IntoResponse {
+    let Some(query) = parameters.query() else {
+        return Json(SearchResults::default()).into_response();
+    };
+
+    match state.client.search_page(query, parameters.offset).await {
+        Ok(results) => Json(parameters.filter().apply(results)).into_response(),
+        Err(err) => err.to_string().into_response(),
+    }
+}
+
+/// Looks up a tag for a remote reader. A POST, since it also marks the tag as scanned
 async fn rfid_reference(
     State(state): State<Arc<AppState>>,
     Path(reference): Path<String>,
 ) -> Json<Option<ReferenceType>> {
-    Json(state.database.get_reference(&reference).await)
+    let found = state.database.get_reference(&reference).await;
+    if found.is_some() {
+        _ = state.database.set_rfid_tag_scanned(&reference).await;
+    }
+
+    Json(found)
 }
 
 async fn play_rfid_reference(State(state): State<Arc<AppState>>, Path(reference): Path<String>) {
     handle_play_scan(
         &state.database,
         &state.controls,
+        &state.client,
         &state.broadcast,
         &reference,
         None,
@@ -285,6 +521,21 @@ async fn play_rfid_reference(State(state): State<Arc<AppState>>, Path(reference)
     .await;
 }
 
+async fn link_rfid_reference(
+    State(state): State<Arc<AppState>>,
+    Json(link): Json<LinkRfid>,
+) -> ResponseResult {
+    ok_or_send_error_toast(
+        &state,
+        state
+            .database
+            .add_rfid_reference(link.rfid_id, link.reference)
+            .await,
+    )?;
+
+    Ok(state.send_toast(Notification::Success("Link complete".into())))
+}
+
 async fn link_album_rfid_reference(
     State(state): State<Arc<AppState>>,
     Json(link): Json<LinkAlbumRfid>,
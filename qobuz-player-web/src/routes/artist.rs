@@ -2,10 +2,12 @@ use std::sync::Arc;
 
 use axum::{
     Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
     routing::{get, put},
 };
+use qobuz_player_controls::{database::BanKind, models::DiscographySort, release_notifications};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{AppState, ResponseResult, ok_or_send_error_toast};
@@ -22,6 +24,10 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
         )
         .route("/artist/{id}/set-favorite", put(set_favorite))
         .route("/artist/{id}/unset-favorite", put(unset_favorite))
+        .route("/artist/{id}/ban", put(ban))
+        .route("/artist/{id}/unban", put(unban))
+        .route("/artist/{id}/follow", put(follow))
+        .route("/artist/{id}/unfollow", put(unfollow))
         .route(
             "/artist/{artist_id}/play-top-track/{track_index}",
             put(play_top_track),
@@ -111,16 +117,117 @@ async fn unset_favorite(State(state): State<Arc<AppState>>, Path(id): Path<u32>)
     ))
 }
 
+async fn ban(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
+    let artist = ok_or_send_error_toast(&state, state.client.artist_page(id).await)?;
+    ok_or_send_error_toast(
+        &state,
+        state.database.ban(BanKind::Artist, id, &artist.name).await,
+    )?;
+    state.controls.apply_bans();
+
+    Ok(state.render(
+        "toggle-ban.html",
+        &json!({"api": "/artist", "id": id, "is_banned": true}),
+    ))
+}
+
+async fn unban(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.unban(BanKind::Artist, id).await)?;
+
+    Ok(state.render(
+        "toggle-ban.html",
+        &json!({"api": "/artist", "id": id, "is_banned": false}),
+    ))
+}
+
+async fn follow(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
+    let artist = ok_or_send_error_toast(&state, state.client.artist_page(id).await)?;
+    ok_or_send_error_toast(
+        &state,
+        release_notifications::follow(&state.database, &state.client, id, &artist.name).await,
+    )?;
+
+    Ok(state.render(
+        "toggle-follow.html",
+        &json!({"id": id, "is_followed": true}),
+    ))
+}
+
+async fn unfollow(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.unfollow_artist(id).await)?;
+
+    Ok(state.render(
+        "toggle-follow.html",
+        &json!({"id": id, "is_followed": false}),
+    ))
+}
+
 async fn index(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> impl IntoResponse {
     let url = format!("/artist/{id}/content");
     state.render("lazy-load-component.html", &json!({"url": url}))
 }
 
-async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
-    let artist = ok_or_send_error_toast(&state, state.client.artist_page(id).await)?;
+#[derive(Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ReleaseFilter {
+    #[default]
+    All,
+    Albums,
+    Singles,
+    Live,
+    Compilations,
+}
+
+#[derive(Deserialize)]
+struct DiscographyParameters {
+    #[serde(default)]
+    sort: DiscographySort,
+    #[serde(default)]
+    release_type: ReleaseFilter,
+}
+
+async fn content(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u32>,
+    Query(parameters): Query<DiscographyParameters>,
+) -> ResponseResult {
+    let mut artist = ok_or_send_error_toast(
+        &state,
+        state.client.artist_page_sorted(id, parameters.sort).await,
+    )?;
+
+    let release_type = parameters.release_type;
+    if !matches!(release_type, ReleaseFilter::All | ReleaseFilter::Albums) {
+        artist.albums.clear();
+    }
+    if !matches!(release_type, ReleaseFilter::All | ReleaseFilter::Singles) {
+        artist.singles.clear();
+    }
+    if !matches!(release_type, ReleaseFilter::All | ReleaseFilter::Live) {
+        artist.live.clear();
+    }
+    if !matches!(
+        release_type,
+        ReleaseFilter::All | ReleaseFilter::Compilations
+    ) {
+        artist.compilations.clear();
+    }
+
+    let sort_options: Vec<_> = DiscographySort::VALUES
+        .iter()
+        .map(|sort| {
+            json!({
+                "value": sort,
+                "label": sort.label(),
+                "selected": *sort == parameters.sort,
+            })
+        })
+        .collect();
 
     let favorites = ok_or_send_error_toast(&state, state.get_favorites().await)?;
     let is_favorite = favorites.artists.iter().any(|artist| artist.id == id);
+    let ban_list = ok_or_send_error_toast(&state, state.database.get_ban_list().await)?;
+    let followed = ok_or_send_error_toast(&state, state.database.get_followed_artists().await)?;
     let click_string = format!("/artist/{}/play-top-track/", artist.id);
     let top_tracks: Vec<_> = artist.top_tracks.iter().take(5).collect();
 
@@ -130,7 +237,11 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Res
             "artist": artist,
             "top_tracks": top_tracks,
             "is_favorite": is_favorite,
-            "click": click_string
+            "is_banned": ban_list.is_artist_banned(id),
+            "is_followed": followed.iter().any(|artist| artist.id == id),
+            "click": click_string,
+            "sort_options": sort_options,
+            "release_type": release_type,
         }),
     ))
 }
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/artwork/{id}/{size}", get(artwork))
+}
+
+async fn artwork(
+    State(state): State<Arc<AppState>>,
+    Path((id, size)): Path<(String, u32)>,
+) -> impl IntoResponse {
+    match state.artwork_cache.get(&id, size).await {
+        Ok(image) => (
+            [
+                (header::CONTENT_TYPE, "image/jpeg"),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            image,
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::warn!("{err}");
+            StatusCode::NOT_FOUND.into_response()
+        }
+    }
+}
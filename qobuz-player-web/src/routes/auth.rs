@@ -1,9 +1,9 @@
-use crate::AppState;
+use crate::{AppState, routes::party};
 use axum::{
     Form, Router,
     body::Body,
     extract::{Request, State},
-    http::{Response, StatusCode},
+    http::{Method, Response, StatusCode},
     response::IntoResponse,
     routing::{get, post},
 };
@@ -11,27 +11,210 @@ use axum_extra::extract::{
     CookieJar,
     cookie::{Cookie, SameSite},
 };
+use qobuz_player_controls::{constant_time_eq, notification::Notification};
+use rand::RngExt;
 use serde::Deserialize;
 use serde_json::json;
-use std::sync::Arc;
+use std::{fmt::Write, path::Path, str::FromStr, sync::Arc};
+
+pub(crate) const SESSION_COOKIE: &str = "session";
+/// Cookie holding the web secret itself. Still accepted so scripts and remote
+/// RFID readers can authenticate without a login round trip
+const SECRET_COOKIE: &str = "secret";
+
+/// Who may sign in to the web ui
+#[derive(Debug, Clone)]
+pub struct WebAuth {
+    /// Password without a user name. Signs in as a controller
+    pub secret: Option<String>,
+    pub users: Vec<WebUser>,
+    pub session_lifetime: time::Duration,
+    /// Let visitors browse without signing in. Changes still require a login
+    pub guest: bool,
+    /// Let visitors suggest tracks, which a controller approves into the queue
+    pub party: bool,
+}
+
+impl WebAuth {
+    pub(crate) fn enabled(&self) -> bool {
+        self.secret.is_some() || !self.users.is_empty()
+    }
+}
+
+/// Named web ui user, given as `name:password:role`
+#[derive(Debug, Clone)]
+pub struct WebUser {
+    pub name: String,
+    pub password: String,
+    pub role: WebRole,
+}
+
+impl FromStr for WebUser {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.splitn(3, ':');
+        let (Some(name), Some(password)) = (parts.next(), parts.next()) else {
+            return Err("expected name:password or name:password:role".to_string());
+        };
+
+        if name.is_empty() || password.is_empty() {
+            return Err("user name and password must not be empty".to_string());
+        }
+
+        let role = match parts.next() {
+            None | Some("controller") => WebRole::Controller,
+            Some("viewer") => WebRole::Viewer,
+            Some(role) => return Err(format!("unknown role {role}, use viewer or controller")),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            password: password.to_string(),
+            role,
+        })
+    }
+}
+
+/// Reads the named users from a file with one `name:password:role` per line, so the
+/// passwords stay out of the process list. Blank lines and lines starting with `#` are skipped
+pub fn read_web_users(path: &Path) -> Result<Vec<WebUser>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read web users from {}: {err}", path.display()))?;
+
+    parse_web_users(&contents).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn parse_web_users(contents: &str) -> Result<Vec<WebUser>, String> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| line.parse().map_err(|err| format!("line {number}: {err}")))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebRole {
+    /// Visitor that has not signed in, only allowed in guest mode
+    Guest,
+    /// Visitor that has not signed in in party mode without guest mode. Only reaches the
+    /// party page, where it searches, suggests and votes
+    PartyGuest,
+    /// Can browse, but not change playback, favorites or playlists
+    Viewer,
+    Controller,
+}
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/auth", get(index))
         .route("/auth/login", post(login))
+        .route("/auth/logout", post(logout))
 }
 
 pub async fn auth_middleware(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
-    request: Request,
+    mut request: Request,
     next: axum::middleware::Next,
-) -> (CookieJar, Response<Body>) {
-    let Some(state_secret) = state.web_secret.clone() else {
-        return (jar, next.run(request).await);
+) -> Response<Body> {
+    let role = match authenticate(&state, &jar).await {
+        Some(role) => role,
+        None if state.web_auth.guest => WebRole::Guest,
+        None if state.web_auth.party => WebRole::PartyGuest,
+        None => return redirect("/auth"),
     };
 
-    let redirect_response = (
+    if role == WebRole::PartyGuest && !is_party_path(request.uri().path()) {
+        return redirect("/party");
+    }
+
+    if let Some((message, status)) =
+        denied(role, request.method(), is_party_request(&state, &request))
+    {
+        // htmx only swaps successful responses, so the toast needs a 200
+        return match request.headers().contains_key("HX-Request") {
+            true => state.send_toast(Notification::Warning(message.into())),
+            false => status.into_response(),
+        };
+    }
+
+    request.extensions_mut().insert(role);
+    next.run(request).await
+}
+
+async fn authenticate(state: &AppState, jar: &CookieJar) -> Option<WebRole> {
+    let auth = &state.web_auth;
+    if !auth.enabled() {
+        return Some(WebRole::Controller);
+    }
+
+    if let Some(secret) = &auth.secret
+        && jar
+            .get(SECRET_COOKIE)
+            .is_some_and(|x| constant_time_eq(x.value(), secret))
+    {
+        return Some(WebRole::Controller);
+    }
+
+    let token = jar.get(SESSION_COOKIE)?;
+    let user = state
+        .database
+        .get_web_session(token.value())
+        .await
+        .ok()
+        .flatten()?;
+
+    match user {
+        None => auth.secret.is_some().then_some(WebRole::Controller),
+        Some(name) => auth
+            .users
+            .iter()
+            .find(|user| user.name == name)
+            .map(|user| user.role),
+    }
+}
+
+/// Why a change is turned away from the role, `None` when it may make it
+fn denied(
+    role: WebRole,
+    method: &Method,
+    party_request: bool,
+) -> Option<(&'static str, StatusCode)> {
+    if role == WebRole::Controller || is_read_only(method) || party_request {
+        return None;
+    }
+
+    Some(match role {
+        WebRole::Guest | WebRole::PartyGuest => {
+            ("Log in to make changes", StatusCode::UNAUTHORIZED)
+        }
+        _ => ("Viewers can not make changes", StatusCode::FORBIDDEN),
+    })
+}
+
+fn is_read_only(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Pages party guests may reach
+fn is_party_path(path: &str) -> bool {
+    matches!(path, "/party" | "/party/search" | party::SUGGEST_PATH)
+        || path.starts_with(party::VOTES_PATH)
+}
+
+/// Suggestions and votes of party guests
+fn is_party_request(state: &AppState, request: &Request) -> bool {
+    let path = request.uri().path();
+    state.web_auth.party
+        && request.method() == Method::POST
+        && (path == party::SUGGEST_PATH || path.starts_with(party::VOTES_PATH))
+}
+
+fn redirect(location: &'static str) -> Response<Body> {
+    (
         StatusCode::FOUND,
         [
             (
@@ -40,41 +223,46 @@ pub async fn auth_middleware(
             ),
             (
                 axum::http::header::LOCATION,
-                axum::http::HeaderValue::from_static("/auth"),
+                axum::http::HeaderValue::from_static(location),
             ),
         ],
     )
-        .into_response();
-
-    let secret = jar.get("secret");
-
-    match secret {
-        Some(result_secret) => {
-            if state_secret != result_secret.value() {
-                return (jar, redirect_response);
-            }
-        }
-        None => return (jar, redirect_response),
-    }
+        .into_response()
+}
 
-    (set_auth_cookie(jar, state_secret), next.run(request).await)
+pub(crate) fn session_token() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    bytes.iter().fold(String::new(), |mut token, byte| {
+        _ = write!(token, "{byte:02x}");
+        token
+    })
 }
 
-fn set_auth_cookie(jar: CookieJar, secret: String) -> CookieJar {
-    let mut cookie = Cookie::new("secret", secret);
+fn set_session_cookie(jar: CookieJar, token: String, lifetime: time::Duration) -> CookieJar {
+    let mut cookie = Cookie::new(SESSION_COOKIE, token);
     cookie.set_same_site(SameSite::Strict);
+    cookie.set_http_only(true);
     cookie.set_path("/");
-    cookie.set_max_age(time::Duration::weeks(1));
+    cookie.set_max_age(lifetime);
     jar.add(cookie)
 }
 
 async fn index(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    state.render("unauthorized-page.html", &json!({}))
+    state.render(
+        "unauthorized-page.html",
+        &json!({
+            "named_users": !state.web_auth.users.is_empty(),
+            "action": "/auth/login",
+            "title": null,
+        }),
+    )
 }
 
 #[derive(Deserialize)]
 struct LoginParameters {
-    secret: String,
+    #[serde(default)]
+    username: String,
+    password: String,
 }
 
 async fn login(
@@ -82,31 +270,127 @@ async fn login(
     jar: CookieJar,
     Form(parameters): Form<LoginParameters>,
 ) -> (CookieJar, Response<Body>) {
-    let response = (
-        StatusCode::FOUND,
-        [
-            (
-                axum::http::header::CONTENT_TYPE,
-                axum::http::HeaderValue::from_static(mime::TEXT_HTML_UTF_8.as_ref()),
-            ),
-            (
-                axum::http::header::LOCATION,
-                axum::http::HeaderValue::from_static("/"),
-            ),
-        ],
-        "Success",
+    let auth = &state.web_auth;
+    if !auth.enabled() {
+        return (jar, redirect("/"));
+    }
+
+    let valid = match parameters.username.as_str() {
+        "" => auth
+            .secret
+            .as_ref()
+            .is_some_and(|secret| constant_time_eq(&parameters.password, secret)),
+        name => auth.users.iter().any(|user| {
+            user.name == name && constant_time_eq(&parameters.password, &user.password)
+        }),
+    };
+
+    if !valid {
+        return (
+            jar,
+            (StatusCode::UNAUTHORIZED, "Bad credentials").into_response(),
+        );
+    }
+
+    let token = session_token();
+    let name = (!parameters.username.is_empty()).then_some(parameters.username.as_str());
+    if let Err(err) = state
+        .database
+        .create_web_session(&token, name, auth.session_lifetime)
+        .await
+    {
+        tracing::error!("Unable to create web session: {err}");
+        return (jar, StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    (
+        set_session_cookie(jar, token, auth.session_lifetime),
+        redirect("/"),
     )
-        .into_response();
-
-    match state.web_secret.clone() {
-        None => return (jar, response),
-        Some(secret) => {
-            if secret == parameters.secret {
-                return (set_auth_cookie(jar, secret), response);
-            };
-        }
+}
+
+async fn logout(State(state): State<Arc<AppState>>, jar: CookieJar) -> (CookieJar, Response<Body>) {
+    if let Some(token) = jar.get(SESSION_COOKIE)
+        && let Err(err) = state.database.delete_web_session(token.value()).await
+    {
+        tracing::error!("Unable to delete web session: {err}");
     }
 
-    let response = (StatusCode::UNAUTHORIZED, "Bad credentials").into_response();
-    (jar, response)
+    let jar = jar
+        .remove(Cookie::build(SESSION_COOKIE).path("/"))
+        .remove(Cookie::build(SECRET_COOKIE).path("/"));
+
+    (jar, redirect("/auth"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use super::{WebRole, denied, is_party_path, parse_web_users};
+
+    #[test]
+    fn guests_only_read() {
+        assert_eq!(denied(WebRole::Guest, &Method::GET, false), None);
+        assert_eq!(denied(WebRole::Guest, &Method::HEAD, false), None);
+        assert_eq!(
+            denied(WebRole::Guest, &Method::POST, false),
+            Some(("Log in to make changes", StatusCode::UNAUTHORIZED))
+        );
+        assert_eq!(
+            denied(WebRole::Guest, &Method::PUT, false),
+            Some(("Log in to make changes", StatusCode::UNAUTHORIZED))
+        );
+        assert_eq!(denied(WebRole::Guest, &Method::POST, true), None);
+    }
+
+    #[test]
+    fn viewers_are_forbidden_and_controllers_allowed_to_change() {
+        assert_eq!(
+            denied(WebRole::Viewer, &Method::DELETE, false),
+            Some(("Viewers can not make changes", StatusCode::FORBIDDEN))
+        );
+        assert_eq!(denied(WebRole::Controller, &Method::DELETE, false), None);
+    }
+
+    #[test]
+    fn reads_web_users_per_line() {
+        let users = parse_web_users(
+            "# users\nalice:secret:viewer\n\n  bob:pass  \ncarol:hunter2:controller\n",
+        )
+        .unwrap();
+
+        let users: Vec<_> = users
+            .iter()
+            .map(|user| (user.name.as_str(), user.password.as_str(), user.role))
+            .collect();
+        assert_eq!(
+            users,
+            [
+                ("alice", "secret", WebRole::Viewer),
+                ("bob", "pass", WebRole::Controller),
+                ("carol", "hunter2", WebRole::Controller),
+            ]
+        );
+    }
+
+    #[test]
+    fn web_users_file_errors_name_the_line() {
+        let err = parse_web_users("alice:secret\nbob\n").unwrap_err();
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    fn party_guests_only_reach_the_party_pages() {
+        assert!(is_party_path("/party"));
+        assert!(is_party_path("/party/search"));
+        assert!(is_party_path("/party/suggestions"));
+        assert!(is_party_path("/party/votes/12"));
+
+        assert!(!is_party_path("/"));
+        assert!(!is_party_path("/queue"));
+        assert!(!is_party_path("/sse"));
+        assert!(!is_party_path("/party/suggestions/12"));
+        assert!(!is_party_path("/api/play"));
+    }
 }
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::get,
+};
+use serde_json::json;
+
+use crate::{AppState, ResponseResult, ok_or_send_error_toast};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/composer/{id}", get(index))
+        .route("/composer/{id}/content", get(content))
+}
+
+async fn index(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> impl IntoResponse {
+    let url = format!("/composer/{id}/content");
+    state.render("lazy-load-component.html", &json!({"url": url}))
+}
+
+async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
+    let composer = ok_or_send_error_toast(&state, state.client.composer(id).await)?;
+
+    Ok(state.render("composer.html", &json!({"composer": composer})))
+}
@@ -9,7 +9,10 @@ use qobuz_player_controls::error::Error;
 use serde_json::json;
 use tokio::try_join;
 
-use crate::{AppState, Discover, ResponseResult, ok_or_broadcast, ok_or_error_page};
+use crate::{
+    AppState, Discover, ResponseResult, ok_or_broadcast, ok_or_error_page,
+    routes::search::badge_banned,
+};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
@@ -27,7 +30,10 @@ async fn index(State(state): State<Arc<AppState>>) -> ResponseResult {
         ),
     )?;
 
-    let discover = Discover { albums, playlists };
+    let mut discover = json!(Discover { albums, playlists });
+    if let Ok(ban_list) = state.database.get_ban_list().await {
+        badge_banned(&mut discover, &ban_list);
+    }
 
     Ok(state.render(
         "discover.html",
@@ -50,7 +56,10 @@ async fn genres_tab(State(state): State<Arc<AppState>>) -> ResponseResult {
         ),
     )?;
 
-    let discover = Discover { albums, playlists };
+    let mut discover = json!(Discover { albums, playlists });
+    if let Ok(ban_list) = state.database.get_ban_list().await {
+        badge_banned(&mut discover, &ban_list);
+    }
 
     Ok(state.render(
         "discover.html",
@@ -0,0 +1,170 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::get,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::AppState;
+
+/// Events kept for clients to catch up on. Clients further behind are told they missed events
+const EVENT_LOG_SIZE: usize = 200;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+const MAX_TIMEOUT_SECONDS: u64 = 120;
+/// Wait after the first new event, so events sent together are returned together
+const BATCH_DELAY: Duration = Duration::from_millis(50);
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/api/v1/events", get(events))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiEvent {
+    cursor: u64,
+    /// Named like the server sent events
+    event: &'static str,
+    data: Value,
+}
+
+/// Latest events with increasing cursors, for clients unable to use server sent events
+pub struct EventLog {
+    events: Mutex<VecDeque<ApiEvent>>,
+    latest: watch::Sender<u64>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(EVENT_LOG_SIZE)),
+            latest: watch::Sender::new(0),
+        }
+    }
+}
+
+impl EventLog {
+    pub fn push(&self, event: &'static str, data: Value) {
+        let mut events = self.events.lock();
+        let cursor = *self.latest.borrow() + 1;
+        events.push_back(ApiEvent {
+            cursor,
+            event,
+            data,
+        });
+        if events.len() > EVENT_LOG_SIZE {
+            events.pop_front();
+        }
+        self.latest.send_replace(cursor);
+    }
+
+    /// Events after the cursor, and whether events after it were already dropped
+    fn since(&self, cursor: u64) -> (Vec<ApiEvent>, bool) {
+        let events = self.events.lock();
+        let missed = events
+            .front()
+            .is_some_and(|oldest| oldest.cursor > cursor + 1);
+        let newer = events
+            .iter()
+            .filter(|event| event.cursor > cursor)
+            .cloned()
+            .collect();
+        (newer, missed)
+    }
+}
+
+#[derive(Deserialize)]
+struct EventsParameters {
+    /// Cursor of the last event received
+    cursor: Option<u64>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct EventBatch {
+    /// Passed as the cursor of the next request
+    cursor: u64,
+    /// Events were dropped or the player restarted, so the client should fetch the whole state
+    /// again
+    missed: bool,
+    /// Position events are left out, as they are sent twice a second
+    position_ms: u64,
+    events: Vec<ApiEvent>,
+}
+
+/// Long poll returning the events after the cursor, waiting up to the timeout in seconds for
+/// one. Without a cursor the current cursor is returned right away
+async fn events(
+    State(state): State<Arc<AppState>>,
+    Query(parameters): Query<EventsParameters>,
+) -> impl IntoResponse {
+    let log = &state.event_log;
+    let mut latest = log.latest.subscribe();
+    let current_cursor = *latest.borrow_and_update();
+
+    let position_ms = || state.position_receiver.borrow().as_millis() as u64;
+
+    let Some(cursor) = parameters.cursor.filter(|cursor| *cursor <= current_cursor) else {
+        return Json(EventBatch {
+            cursor: current_cursor,
+            missed: parameters.cursor.is_some(),
+            position_ms: position_ms(),
+            events: vec![],
+        });
+    };
+
+    let timeout = parameters
+        .timeout
+        .unwrap_or(DEFAULT_TIMEOUT_SECONDS)
+        .min(MAX_TIMEOUT_SECONDS);
+    if cursor == current_cursor
+        && tokio::time::timeout(
+            Duration::from_secs(timeout),
+            latest.wait_for(|latest| *latest > cursor),
+        )
+        .await
+        .is_ok()
+    {
+        tokio::time::sleep(BATCH_DELAY).await;
+    }
+
+    let (events, missed) = log.since(cursor);
+    Json(EventBatch {
+        cursor: events.last().map_or(cursor, |event| event.cursor),
+        missed,
+        position_ms: position_ms(),
+        events,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{EVENT_LOG_SIZE, EventLog};
+
+    #[test]
+    fn events_after_the_cursor_and_missed_events() {
+        let log = EventLog::default();
+        log.push("volume", json!(50));
+        log.push("status", json!("play"));
+
+        let (events, missed) = log.since(1);
+        assert!(!missed);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].cursor, 2);
+        assert_eq!(events[0].event, "status");
+
+        for volume in 0..EVENT_LOG_SIZE {
+            log.push("volume", json!(volume));
+        }
+        let (events, missed) = log.since(1);
+        assert!(missed);
+        assert_eq!(events.len(), EVENT_LOG_SIZE);
+        assert!(log.since(2 + EVENT_LOG_SIZE as u64).0.is_empty());
+    }
+}
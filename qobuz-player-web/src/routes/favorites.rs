@@ -1,14 +1,18 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use axum::{
     Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
     routing::{get, put},
 };
+use qobuz_player_controls::models::{FavoriteAlbumSort, Playlist, new_favorite_albums};
 use serde_json::json;
 
-use crate::{AppState, ResponseResult, ok_or_error_page, ok_or_send_error_toast};
+use crate::{
+    AppState, ResponseResult, ok_or_error_page, ok_or_send_error_toast,
+    routes::smart_playlist::smart_playlists,
+};
 
 #[derive(Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "lowercase")]
@@ -26,15 +30,166 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
         .route("/favorites/tracks/shuffle", put(shuffle_favorite_tracks))
 }
 
-async fn index(State(state): State<Arc<AppState>>, Path(tab): Path<Tab>) -> ResponseResult {
-    let favorites = ok_or_error_page(&state, state.get_favorites().await)?;
+#[derive(serde::Deserialize)]
+struct FavoritesParameters {
+    /// Only show the playlists with this tag
+    tag: Option<String>,
+    #[serde(default)]
+    sort: FavoriteAlbumSort,
+}
+
+#[derive(serde::Serialize)]
+struct TagFilter {
+    name: String,
+    /// `tag=<name>`, encoded for the filter link
+    query: String,
+    selected: bool,
+}
+
+#[derive(serde::Serialize)]
+struct PlaylistGroup {
+    tag: Option<String>,
+    /// The playlists without tags, kept apart from a tag that happens to be named alike
+    untagged: bool,
+    playlists: Vec<Playlist>,
+}
+
+async fn index(
+    State(state): State<Arc<AppState>>,
+    Path(tab): Path<Tab>,
+    Query(parameters): Query<FavoritesParameters>,
+) -> ResponseResult {
+    let mut favorites = ok_or_error_page(&state, state.get_favorites().await)?;
+    let tags = ok_or_error_page(&state, state.database.get_playlist_tags().await)?;
+
+    let added = ok_or_error_page(&state, state.database.favorite_albums_added().await)?;
+    parameters.sort.sort(&mut favorites.albums, &added);
+
+    let new_albums = new_favorite_albums(&added);
+    let albums: Vec<_> = favorites
+        .albums
+        .iter()
+        .map(|album| {
+            let mut value = json!(album);
+            value["new_favorite"] = json!(new_albums.contains(&album.id));
+            value
+        })
+        .collect();
+
+    let sort_options: Vec<_> = FavoriteAlbumSort::VALUES
+        .iter()
+        .map(|sort| {
+            json!({
+                "value": sort,
+                "label": sort.label(),
+                "selected": *sort == parameters.sort,
+            })
+        })
+        .collect();
+
+    let playlist_tags = tag_filters(&favorites.playlists, &tags, parameters.tag.as_deref());
+    let playlist_groups = group_playlists(&favorites.playlists, &tags, parameters.tag);
 
     Ok(state.render(
         "favorites.html",
-        &json!({"favorites": favorites, "tab": tab}),
+        &json!({
+            "favorites": favorites,
+            "albums": albums,
+            "sort_options": sort_options,
+            "tab": tab,
+            "playlist_tags": playlist_tags,
+            "playlist_groups": playlist_groups,
+            "smart_playlists": smart_playlists(),
+        }),
     ))
 }
 
+/// Tags used by the favorite playlists, sorted
+fn tag_filters(
+    playlists: &[Playlist],
+    tags: &HashMap<u32, Vec<String>>,
+    selected: Option<&str>,
+) -> Vec<TagFilter> {
+    let mut names: Vec<&String> = playlists
+        .iter()
+        .filter_map(|playlist| tags.get(&playlist.id))
+        .flatten()
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| TagFilter {
+            query: serde_urlencoded::to_string([("tag", name)]).unwrap_or_default(),
+            selected: selected == Some(name.as_str()),
+            name: name.clone(),
+        })
+        .collect()
+}
+
+/// The playlists with the selected tag. Without one, a group per tag followed by the
+/// untagged playlists
+fn group_playlists(
+    playlists: &[Playlist],
+    tags: &HashMap<u32, Vec<String>>,
+    selected: Option<String>,
+) -> Vec<PlaylistGroup> {
+    let has_tag = |playlist: &Playlist, tag: &str| {
+        tags.get(&playlist.id)
+            .is_some_and(|tags| tags.iter().any(|candidate| candidate == tag))
+    };
+
+    if let Some(selected) = selected {
+        return vec![PlaylistGroup {
+            tag: None,
+            untagged: false,
+            playlists: playlists
+                .iter()
+                .filter(|playlist| has_tag(playlist, &selected))
+                .cloned()
+                .collect(),
+        }];
+    }
+
+    let filters = tag_filters(playlists, tags, None);
+    if filters.is_empty() {
+        return vec![PlaylistGroup {
+            tag: None,
+            untagged: false,
+            playlists: playlists.to_vec(),
+        }];
+    }
+
+    let mut groups: Vec<PlaylistGroup> = filters
+        .into_iter()
+        .map(|filter| PlaylistGroup {
+            playlists: playlists
+                .iter()
+                .filter(|playlist| has_tag(playlist, &filter.name))
+                .cloned()
+                .collect(),
+            tag: Some(filter.name),
+            untagged: false,
+        })
+        .collect();
+
+    let untagged: Vec<Playlist> = playlists
+        .iter()
+        .filter(|playlist| !tags.contains_key(&playlist.id))
+        .cloned()
+        .collect();
+    if !untagged.is_empty() {
+        groups.push(PlaylistGroup {
+            tag: None,
+            untagged: true,
+            playlists: untagged,
+        });
+    }
+
+    groups
+}
+
 async fn tracks_partial(State(state): State<Arc<AppState>>) -> ResponseResult {
     let favorites = ok_or_send_error_toast(&state, state.get_favorites().await)?;
 
@@ -52,3 +207,71 @@ async fn shuffle_favorite_tracks(State(state): State<Arc<AppState>>) -> Response
 
     Ok(().into_response())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use qobuz_player_controls::models::Playlist;
+
+    use super::{group_playlists, tag_filters};
+
+    fn playlists() -> Vec<Playlist> {
+        (1..=3)
+            .map(|id| Playlist {
+                id,
+                title: format!("Playlist {id}"),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn tags() -> HashMap<u32, Vec<String>> {
+        HashMap::from([
+            (1, vec!["rock & roll".to_string(), "Untagged".to_string()]),
+            (2, vec!["rock & roll".to_string()]),
+        ])
+    }
+
+    #[test]
+    fn tag_filters_encode_the_tag() {
+        let filters = tag_filters(&playlists(), &tags(), Some("rock & roll"));
+
+        let names: Vec<_> = filters.iter().map(|filter| filter.name.as_str()).collect();
+        assert_eq!(names, ["Untagged", "rock & roll"]);
+        assert_eq!(filters[1].query, "tag=rock+%26+roll");
+        assert!(filters[1].selected);
+        assert!(!filters[0].selected);
+    }
+
+    #[test]
+    fn untagged_playlists_get_their_own_group() {
+        let groups = group_playlists(&playlists(), &tags(), None);
+
+        let summary: Vec<_> = groups
+            .iter()
+            .map(|group| {
+                let ids: Vec<_> = group.playlists.iter().map(|playlist| playlist.id).collect();
+                (group.tag.as_deref(), group.untagged, ids)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some("Untagged"), false, vec![1]),
+                (Some("rock & roll"), false, vec![1, 2]),
+                (None, true, vec![3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn selected_tag_shows_one_group() {
+        let groups = group_playlists(&playlists(), &tags(), Some("Untagged".into()));
+
+        assert_eq!(groups.len(), 1);
+        assert!(!groups[0].untagged);
+        assert_eq!(groups[0].playlists.len(), 1);
+        assert_eq!(groups[0].playlists[0].id, 1);
+    }
+}
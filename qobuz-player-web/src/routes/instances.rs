@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use axum_extra::extract::{
+    CookieJar, Form,
+    cookie::{Cookie, SameSite},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{AppState, discovery::Peer, hx_redirect};
+
+/// Cookie naming the peer this browser is controlling
+const TARGET_COOKIE: &str = "instance";
+
+/// Cookie holding the session this browser signed in to the selected peer with. Credentials
+/// of this player are never sent on to peers
+const PEER_SESSION_COOKIE: &str = "instance-session";
+
+/// Session cookie of the web ui, the one the peer hands out when signing in
+const SESSION_COOKIE: &str = "session";
+
+/// Largest request body forwarded to a peer
+const MAX_PROXY_BODY: usize = 1024 * 1024;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/instances", get(instances))
+        .route("/instances/select", post(select))
+        .route("/instances/login", get(login_page).post(login))
+}
+
+async fn instances(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.discovery.peers())
+}
+
+#[derive(Deserialize)]
+struct SelectParameters {
+    fullname: String,
+}
+
+async fn select(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(parameters): Form<SelectParameters>,
+) -> (CookieJar, Response) {
+    // A session only signs in to the peer it came from
+    let jar = jar.remove(Cookie::build(PEER_SESSION_COOKIE).path("/"));
+    let jar = match state.discovery.peer(&parameters.fullname) {
+        Some(peer) => jar.add(cookie(TARGET_COOKIE, peer.fullname)),
+        None => jar.remove(Cookie::build(TARGET_COOKIE).path("/")),
+    };
+
+    (jar, hx_redirect("/"))
+}
+
+fn cookie(name: &'static str, value: String) -> Cookie<'static> {
+    let mut cookie = Cookie::new(name, value);
+    cookie.set_same_site(SameSite::Strict);
+    cookie.set_http_only(true);
+    cookie.set_path("/");
+    cookie
+}
+
+fn selected_peer(state: &AppState, jar: &CookieJar) -> Option<Peer> {
+    jar.get(TARGET_COOKIE)
+        .and_then(|target| state.discovery.peer(target.value()))
+}
+
+/// Sign in form of the selected peer
+async fn login_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let Some(peer) = selected_peer(&state, &jar) else {
+        return Redirect::to("/").into_response();
+    };
+
+    state.render(
+        "unauthorized-page.html",
+        &json!({
+            "named_users": true,
+            "action": "/instances/login",
+            "title": format!("Sign in to {}", peer.name),
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct LoginParameters {
+    #[serde(default)]
+    username: String,
+    password: String,
+}
+
+/// Signs in to the selected peer with the credentials of that peer and keeps its session
+async fn login(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(parameters): Form<LoginParameters>,
+) -> (CookieJar, Response) {
+    let Some(peer) = selected_peer(&state, &jar) else {
+        return (jar, Redirect::to("/").into_response());
+    };
+
+    let response = state
+        .proxy_client
+        .post(format!("{}/auth/login", peer.base_url()))
+        .form(&[
+            ("username", parameters.username),
+            ("password", parameters.password),
+        ])
+        .send()
+        .await;
+
+    let session = match &response {
+        Ok(response) => session_token(response.headers()),
+        Err(err) => {
+            tracing::warn!("Unable to sign in to {}: {err}", peer.name);
+            None
+        }
+    };
+
+    match session {
+        Some(token) => (
+            jar.add(cookie(PEER_SESSION_COOKIE, token)),
+            Redirect::to("/").into_response(),
+        ),
+        None => (jar, Redirect::to("/instances/login").into_response()),
+    }
+}
+
+/// Value of the session cookie set by a peer
+fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| Cookie::parse(value.to_str().ok()?.to_string()).ok())
+        .find(|cookie| cookie.name() == SESSION_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Sends requests on to the selected peer instead of this player
+pub async fn proxy_middleware(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    request: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let Some(peer) = selected_peer(&state, &jar) else {
+        return next.run(request).await;
+    };
+
+    let session = jar
+        .get(PEER_SESSION_COOKIE)
+        .map(|session| session.value().to_string());
+    match proxy(&state, &peer, session.as_deref(), request).await {
+        Ok(response) => response,
+        Err(err) => {
+            tracing::warn!("Unable to reach {}: {err}", peer.name);
+            let message = format!(
+                "Unable to reach {}. Switched back to {}",
+                peer.name, state.discovery.name
+            );
+            let jar = jar.remove(Cookie::build(TARGET_COOKIE).path("/"));
+            (jar, (StatusCode::BAD_GATEWAY, message)).into_response()
+        }
+    }
+}
+
+async fn proxy(
+    state: &AppState,
+    peer: &Peer,
+    session: Option<&str>,
+    request: Request,
+) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+    let (parts, body) = request.into_parts();
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let body = axum::body::to_bytes(body, MAX_PROXY_BODY).await?;
+
+    let htmx = parts.headers.contains_key("HX-Request");
+    let mut headers = parts.headers.clone();
+    headers.remove(header::HOST);
+    headers.remove(header::COOKIE);
+    if let Some(session) = session {
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("{SESSION_COOKIE}={session}"))?,
+        );
+    }
+
+    let response = state
+        .proxy_client
+        .request(parts.method, format!("{}{path}", peer.base_url()))
+        .headers(headers)
+        .body(body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    // The peer asks for a sign in at its own login page
+    if status.is_redirection()
+        && response
+            .headers()
+            .get(header::LOCATION)
+            .is_some_and(|location| location == "/auth")
+    {
+        return Ok(match htmx {
+            true => hx_redirect("/instances/login"),
+            false => Redirect::to("/instances/login").into_response(),
+        });
+    }
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in response.headers() {
+        if !matches!(
+            *name,
+            header::SET_COOKIE | header::TRANSFER_ENCODING | header::CONNECTION
+        ) {
+            headers.append(name, value.clone());
+        }
+    }
+
+    let mut proxied = Response::new(Body::from_stream(response.bytes_stream()));
+    *proxied.status_mut() = status;
+    *proxied.headers_mut() = headers;
+    Ok(proxied)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderMap, HeaderValue, header};
+
+    use super::session_token;
+
+    #[test]
+    fn session_of_the_peer() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::SET_COOKIE,
+            HeaderValue::from_static("theme=dark; Path=/"),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            HeaderValue::from_static("session=abc; HttpOnly; SameSite=Strict; Path=/"),
+        );
+        assert_eq!(session_token(&headers), Some("abc".to_string()));
+
+        assert_eq!(session_token(&HeaderMap::new()), None);
+    }
+}
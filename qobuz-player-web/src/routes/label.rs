@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::get,
+};
+use serde_json::json;
+
+use crate::{AppState, ResponseResult, ok_or_send_error_toast};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/label/{id}", get(index))
+        .route("/label/{id}/content", get(content))
+}
+
+async fn index(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> impl IntoResponse {
+    let url = format!("/label/{id}/content");
+    state.render("lazy-load-component.html", &json!({"url": url}))
+}
+
+async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
+    let label = ok_or_send_error_toast(&state, state.client.label(id).await)?;
+
+    Ok(state.render("label.html", &json!({"label": label})))
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+};
+use serde_json::json;
+
+use crate::{AppState, WebRole, hx_redirect};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/logs", get(index))
+        .route("/logs/lines", get(lines))
+}
+
+/// Logs can hold urls and account details, so only controllers see them
+async fn index(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+) -> Response {
+    if role != WebRole::Controller {
+        return Redirect::to("/auth").into_response();
+    }
+
+    state.render("logs.html", &json!({ "lines": newest_first(&state) }))
+}
+
+/// Polled by htmx, which would swap a plain redirect's page into the list
+async fn lines(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+    headers: HeaderMap,
+) -> Response {
+    if role != WebRole::Controller {
+        return match headers.contains_key("HX-Request") {
+            true => hx_redirect("/auth"),
+            false => Redirect::to("/auth").into_response(),
+        };
+    }
+
+    state.render("log-lines.html", &json!({ "lines": newest_first(&state) }))
+}
+
+fn newest_first(state: &AppState) -> Vec<qobuz_player_controls::log_buffer::LogLine> {
+    let mut lines = state.log_buffer.lines();
+    lines.reverse();
+    lines
+}
@@ -1,28 +1,157 @@
 use std::sync::Arc;
 
+use std::time::Duration;
+
 use axum::{
     Router,
-    extract::State,
-    response::{IntoResponse, Response},
-    routing::get,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use qobuz_player_controls::{
+    AppResult,
+    notification::{Notification, ResumePoint},
 };
+use qrcode::{QrCode, render::svg};
+use serde::Deserialize;
 use serde_json::json;
 
-use crate::AppState;
+use crate::{
+    AppState, ResponseResult, hx_redirect, ok_or_error_page, ok_or_send_error_toast,
+    routes::api::position,
+};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
         .route("/", get(index))
-        .route("/status", get(status_partial))
+        .route("/play-pause", get(play_pause_partial))
         .route("/now-playing", get(now_playing_partial))
         .route("/now-playing/content", get(now_playing_content))
+        .route("/now-playing/link", get(position_link))
+        .route("/now-playing/qr", get(qr_code))
+        .route("/now-playing/resume", post(resume))
+        .route("/play/track/{id}", get(play_at_page).put(play_at))
+        .route("/kiosk", get(kiosk))
+        .route("/kiosk/content", get(kiosk_content))
+}
+
+#[derive(Deserialize)]
+struct PlayAtParameters {
+    at: Option<String>,
+}
+
+impl PlayAtParameters {
+    /// From the start when no position is given
+    fn position(&self) -> AppResult<Duration> {
+        Ok(position(self.at.as_deref())?.unwrap_or_default())
+    }
+}
+
+/// Deep link to the playing track at the current position, for resuming long tracks later
+async fn position_link(State(state): State<Arc<AppState>>) -> Redirect {
+    let Some(track_id) = state.tracklist_receiver.borrow().currently_playing() else {
+        return Redirect::to("/");
+    };
+    let position = *state.position_receiver.borrow();
+
+    Redirect::to(&format!(
+        "/play/track/{track_id}?at={}",
+        format_position(position)
+    ))
+}
+
+/// Seeks to the offered resume point, unless another track started since the toast
+async fn resume(
+    State(state): State<Arc<AppState>>,
+    axum::Form(resume_point): axum::Form<ResumePoint>,
+) -> Response {
+    let playing = state.tracklist_receiver.borrow().currently_playing();
+    if playing != Some(resume_point.track_id) {
+        return state.send_toast(Notification::Warning(
+            "The track is no longer playing".into(),
+        ));
+    }
+
+    state
+        .controls
+        .seek(Duration::from_secs(resume_point.position_seconds));
+    StatusCode::OK.into_response()
+}
+
+#[derive(Deserialize)]
+struct QrParameters {
+    /// Links the playing album or playlist instead of the track
+    #[serde(default)]
+    list: bool,
+}
+
+/// QR code of the Qobuz link of what is playing, for guests to open it on their own devices
+async fn qr_code(
+    State(state): State<Arc<AppState>>,
+    Query(parameters): Query<QrParameters>,
+) -> Response {
+    let target = {
+        let tracklist = state.tracklist_receiver.borrow();
+        match parameters.list {
+            true => tracklist.current_list_link(),
+            false => tracklist.current_track_link(),
+        }
+    };
+    let Some(target) = target else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let Ok(code) = QrCode::new(target.url()) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let image = code.render::<svg::Color>().min_dimensions(200, 200).build();
+
+    ([(header::CONTENT_TYPE, "image/svg+xml")], image).into_response()
+}
+
+/// Opening a link never starts playback by itself, the page asks first
+async fn play_at_page(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u32>,
+    Query(parameters): Query<PlayAtParameters>,
+) -> ResponseResult {
+    let position = ok_or_error_page(&state, parameters.position())?;
+    let track = ok_or_error_page(&state, state.client.track(id).await)?;
+
+    Ok(state.render(
+        "play-at.html",
+        &json!({"track": track, "at": format_position(position)}),
+    ))
+}
+
+async fn play_at(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u32>,
+    Query(parameters): Query<PlayAtParameters>,
+) -> ResponseResult {
+    let position = ok_or_send_error_toast(&state, parameters.position())?;
+    state.controls.play_track_at(id, position);
+
+    Ok(hx_redirect("/now-playing"))
+}
+
+/// Like 3:41, or 1:02:03 for tracks over an hour
+pub(crate) fn format_position(position: Duration) -> String {
+    let seconds = position.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    match hours {
+        0 => format!("{minutes}:{seconds:02}"),
+        hours => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
 }
 
 async fn index(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     now_playing(&state)
 }
 
-async fn status_partial(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn play_pause_partial(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     state.render("play-pause.html", &())
 }
 
@@ -35,6 +164,17 @@ async fn now_playing_content(State(state): State<Arc<AppState>>) -> impl IntoRes
     state.render("now-playing-content.html", &context)
 }
 
+/// Only the playing track and minimal controls, for a display attached to the player
+async fn kiosk(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let context = now_playing_context(&state);
+    state.render("kiosk.html", &context)
+}
+
+async fn kiosk_content(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let context = now_playing_context(&state);
+    state.render("kiosk-content.html", &context)
+}
+
 fn now_playing_context(state: &AppState) -> serde_json::Value {
     let tracklist = state.tracklist_receiver.borrow().clone();
     let position_mseconds = state.position_receiver.borrow().as_millis();
@@ -66,3 +206,25 @@ fn mseconds_to_mm_ss<T: Into<u128>>(mseconds: T) -> String {
     let seconds = seconds % 60;
     format!("{minutes:02}:{seconds:02}")
 }
+
+#[cfg(test)]
+mod tests {
+    use qobuz_player_controls::controls::parse_position;
+
+    use super::*;
+
+    #[test]
+    fn formats_positions() {
+        assert_eq!(format_position(Duration::ZERO), "0:00");
+        assert_eq!(format_position(Duration::from_millis(221_900)), "3:41");
+        assert_eq!(format_position(Duration::from_secs(3723)), "1:02:03");
+    }
+
+    #[test]
+    fn formatted_positions_parse_back() {
+        for seconds in [0, 59, 221, 3600, 3723] {
+            let position = Duration::from_secs(seconds);
+            assert_eq!(parse_position(&format_position(position)), Some(position));
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use std::{cmp::Reverse, collections::HashMap, net::SocketAddr, sync::Arc};
+
+use axum::{
+    Extension, Router,
+    extract::{ConnectInfo, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post, put},
+};
+use axum_extra::extract::{CookieJar, Form};
+use parking_lot::Mutex;
+use qobuz_player_controls::{notification::Notification, tracklist::Tracklist};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    AppState, ResponseResult, ServerSentEvent, WebRole, ok_or_send_error_toast,
+    routes::{auth::SESSION_COOKIE, queue},
+};
+
+/// Guests may post here in party mode, see the auth middleware
+pub(crate) const SUGGEST_PATH: &str = "/party/suggestions";
+/// Guests may post votes below here in party mode
+pub(crate) const VOTES_PATH: &str = "/party/votes/";
+/// Keeps a flood of voters from growing the votes without bound
+const MAX_VOTERS_PER_TRACK: usize = 200;
+/// Keeps guests from flooding the review list
+const MAX_PENDING_SUGGESTIONS: usize = 100;
+const MAX_NAME_LENGTH: usize = 40;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/party", get(index))
+        .route("/party/search", get(search))
+        .route(SUGGEST_PATH, get(suggestions).post(suggest))
+        .route("/party/suggestions/{track_id}", put(approve).delete(reject))
+        .route("/party/votes/{queue_id}", post(vote))
+}
+
+/// Up and down votes on queued tracks, by queue id and voter. Votes last until the track
+/// leaves the queue or the player restarts
+#[derive(Default)]
+pub struct PartyVotes {
+    votes: Mutex<HashMap<u64, HashMap<String, i8>>>,
+}
+
+impl PartyVotes {
+    /// A vote of 0 takes the earlier vote back. False when the track has too many voters to
+    /// take a new one
+    pub(crate) fn vote(&self, queue_id: u64, voter: &str, vote: i8) -> bool {
+        let mut votes = self.votes.lock();
+        let track_votes = votes.entry(queue_id).or_default();
+        match vote.signum() {
+            0 => {
+                track_votes.remove(voter);
+            }
+            _ if track_votes.len() >= MAX_VOTERS_PER_TRACK && !track_votes.contains_key(voter) => {
+                return false;
+            }
+            vote => {
+                track_votes.insert(voter.to_string(), vote);
+            }
+        };
+        true
+    }
+
+    pub fn score(&self, queue_id: u64) -> i32 {
+        self.votes
+            .lock()
+            .get(&queue_id)
+            .map_or(0, |votes| votes.values().map(|vote| i32::from(*vote)).sum())
+    }
+
+    /// 1 for an up vote, -1 for a down vote and 0 when the voter has not voted
+    pub fn vote_of(&self, queue_id: u64, voter: &str) -> i8 {
+        self.votes
+            .lock()
+            .get(&queue_id)
+            .and_then(|votes| votes.get(voter).copied())
+            .unwrap_or_default()
+    }
+
+    /// Forgets the votes of tracks no longer in the queue
+    fn retain(&self, tracklist: &Tracklist) {
+        let queue_ids: Vec<_> = tracklist.queue().iter().map(|item| item.queue_id).collect();
+        self.votes
+            .lock()
+            .retain(|queue_id, _| queue_ids.contains(queue_id));
+    }
+
+    /// Queue order with the tracks after the playing one sorted by score. Tracks with the
+    /// same score keep their order. `None` when the order does not change
+    fn order(&self, tracklist: &Tracklist) -> Option<Vec<usize>> {
+        let queue = tracklist.queue();
+        let upcoming = (tracklist.current_position() + 1).min(queue.len());
+
+        let mut order: Vec<usize> = (0..queue.len()).collect();
+        order[upcoming..].sort_by_cached_key(|index| Reverse(self.score(queue[*index].queue_id)));
+
+        let changed = order.iter().enumerate().any(|(index, item)| index != *item);
+        changed.then_some(order)
+    }
+}
+
+/// Sorts the upcoming tracks by their votes every time a track starts, so the best voted
+/// track plays next
+pub(crate) async fn reorder_on_transition(state: Arc<AppState>) {
+    let mut tracklist = state.tracklist_receiver.clone();
+    let mut playing = None;
+
+    while tracklist.changed().await.is_ok() {
+        let current = tracklist.borrow_and_update().current_queue_id();
+        if current == playing {
+            continue;
+        }
+        playing = current;
+        reorder(&state);
+    }
+}
+
+fn reorder(state: &AppState) {
+    let tracklist = state.tracklist_receiver.borrow().clone();
+    state.party_votes.retain(&tracklist);
+    if let Some(new_order) = state.party_votes.order(&tracklist) {
+        state.controls.reorder_queue(new_order);
+    }
+}
+
+#[derive(Deserialize)]
+struct VoteParameters {
+    vote: i8,
+}
+
+/// Tells voters apart by their session when signed in and otherwise by their address, so
+/// clearing cookies does not give more votes. Guests behind the same address share a vote
+pub(crate) fn voter(
+    state: &AppState,
+    role: WebRole,
+    jar: &CookieJar,
+    address: SocketAddr,
+) -> String {
+    // Sessions are only checked by the auth middleware when auth is enabled
+    let signed_in =
+        state.web_auth.enabled() && matches!(role, WebRole::Viewer | WebRole::Controller);
+    match jar.get(SESSION_COOKIE).filter(|_| signed_in) {
+        Some(session) => session.value().to_string(),
+        None => address.ip().to_string(),
+    }
+}
+
+/// Votes count for the tracks after the playing one
+async fn vote(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    Path(queue_id): Path<u64>,
+    jar: CookieJar,
+    Form(parameters): Form<VoteParameters>,
+) -> Response {
+    if !state.web_auth.party {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let upcoming = {
+        let tracklist = state.tracklist_receiver.borrow();
+        let current_position = tracklist.current_position();
+        tracklist
+            .queue()
+            .iter()
+            .position(|item| item.queue_id == queue_id)
+            .is_some_and(|position| position > current_position)
+    };
+    if !upcoming {
+        return state.send_toast(Notification::Warning(
+            "Only upcoming tracks take votes".into(),
+        ));
+    }
+
+    let voter = voter(&state, role, &jar, address);
+    if !state.party_votes.vote(queue_id, &voter, parameters.vote) {
+        return state.send_toast(Notification::Warning(
+            "This track has all the votes it can take".into(),
+        ));
+    }
+    reorder(&state);
+
+    // Refreshes the tallies in every open queue page, also when the order did not change
+    _ = state.tx.send(ServerSentEvent {
+        event_name: "tracklist".into(),
+        event_data: "new votes".into(),
+    });
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[derive(Deserialize)]
+struct SearchParameters {
+    query: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SuggestParameters {
+    track_id: u32,
+    /// Optional name of the guest, shown to the owner
+    #[serde(default)]
+    name: String,
+}
+
+/// Search and suggest tracks, and vote on the upcoming tracks. Controllers also review the
+/// pending suggestions here
+async fn index(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+) -> Response {
+    if !state.web_auth.party {
+        return Redirect::to("/").into_response();
+    }
+
+    let review = role == WebRole::Controller;
+    let suggestions = match review {
+        true => state.database.get_suggestions().await.unwrap_or_default(),
+        false => vec![],
+    };
+
+    let mut context = queue::queue_context(&state, &voter(&state, role, &jar, address));
+    context["review"] = json!(review);
+    context["suggestions"] = json!(suggestions);
+    context["party_guest"] = json!(role == WebRole::PartyGuest);
+    state.render("party.html", &context)
+}
+
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Query(parameters): Query<SearchParameters>,
+) -> ResponseResult {
+    if !state.web_auth.party {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    let tracks = match parameters.query.filter(|query| !query.trim().is_empty()) {
+        Some(query) => ok_or_send_error_toast(&state, state.client.search(query).await)?.tracks,
+        None => vec![],
+    };
+
+    Ok(state.render("party-results.html", &json!({"tracks": tracks})))
+}
+
+async fn suggest(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<SuggestParameters>,
+) -> ResponseResult {
+    if !state.web_auth.party {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    let pending = ok_or_send_error_toast(&state, state.database.get_suggestions().await)?;
+    if pending.len() >= MAX_PENDING_SUGGESTIONS {
+        return Ok(state.send_toast(Notification::Warning(
+            "Too many suggestions are waiting, try again later".into(),
+        )));
+    }
+
+    let track = ok_or_send_error_toast(&state, state.client.track(parameters.track_id).await)?;
+    let name: String = parameters
+        .name
+        .trim()
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    let name = (!name.is_empty()).then_some(name.as_str());
+    ok_or_send_error_toast(&state, state.database.add_suggestion(&track, name).await)?;
+
+    Ok(state.send_toast(Notification::Success(format!("Suggested {}", track.title))))
+}
+
+/// Pending suggestions, polled by the review list
+async fn suggestions(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+) -> ResponseResult {
+    if role != WebRole::Controller {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    render_suggestions(&state).await
+}
+
+async fn approve(State(state): State<Arc<AppState>>, Path(track_id): Path<u32>) -> ResponseResult {
+    if ok_or_send_error_toast(&state, state.database.remove_suggestion(track_id).await)? {
+        state.controls.add_tracks_to_queue(vec![track_id]);
+    }
+
+    render_suggestions(&state).await
+}
+
+async fn reject(State(state): State<Arc<AppState>>, Path(track_id): Path<u32>) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.remove_suggestion(track_id).await)?;
+
+    render_suggestions(&state).await
+}
+
+async fn render_suggestions(state: &AppState) -> ResponseResult {
+    let suggestions = ok_or_send_error_toast(state, state.database.get_suggestions().await)?;
+
+    Ok(state.render(
+        "party-suggestions.html",
+        &json!({"suggestions": suggestions}),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use qobuz_player_controls::{
+        models::{Track, TrackStatus},
+        tracklist::{QueueItem, Tracklist, TracklistType},
+    };
+
+    use super::{MAX_VOTERS_PER_TRACK, PartyVotes};
+
+    fn tracklist() -> Tracklist {
+        let items = (0..5)
+            .map(|index| QueueItem {
+                track: Track {
+                    id: index as u32,
+                    status: match index {
+                        0 => TrackStatus::Played,
+                        1 => TrackStatus::Playing,
+                        _ => TrackStatus::Unplayed,
+                    },
+                    ..Default::default()
+                },
+                queue_id: 10 + index as u64,
+                index,
+                queued_by_user: true,
+            })
+            .collect();
+        Tracklist::new(TracklistType::Tracks, items)
+    }
+
+    #[test]
+    fn upcoming_tracks_are_sorted_by_score() {
+        let tracklist = tracklist();
+        let votes = PartyVotes::default();
+        assert_eq!(votes.order(&tracklist), None);
+
+        assert!(votes.vote(14, "a", 1));
+        votes.vote(14, "b", 1);
+        votes.vote(13, "a", 1);
+        votes.vote(12, "a", -1);
+        votes.vote(10, "a", 1);
+        assert_eq!(votes.order(&tracklist), Some(vec![0, 1, 4, 3, 2]));
+        assert_eq!(votes.score(14), 2);
+        assert_eq!(votes.vote_of(12, "a"), -1);
+
+        votes.vote(12, "a", 0);
+        votes.vote(14, "b", -1);
+        assert_eq!(votes.vote_of(12, "a"), 0);
+        assert_eq!(votes.order(&tracklist), Some(vec![0, 1, 3, 2, 4]));
+    }
+
+    #[test]
+    fn tracks_take_a_limited_number_of_voters() {
+        let votes = PartyVotes::default();
+        for voter in 0..MAX_VOTERS_PER_TRACK {
+            assert!(votes.vote(12, &voter.to_string(), 1));
+        }
+
+        assert!(!votes.vote(12, "late", 1));
+        assert!(votes.vote(12, "0", -1));
+        assert!(votes.vote(12, "1", 0));
+        assert!(votes.vote(12, "late", 1));
+        assert_eq!(votes.score(12), MAX_VOTERS_PER_TRACK as i32 - 2);
+    }
+}
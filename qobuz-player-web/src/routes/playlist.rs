@@ -7,11 +7,18 @@ use axum::{
     routing::{get, post, put},
 };
 use axum_extra::extract::Form;
-use qobuz_player_controls::{error::Error, notification::Notification, tracklist::PlayingEntity};
+use qobuz_player_controls::{
+    AppResult,
+    database::ReferenceType,
+    error::Error,
+    models::{LinkTarget, Playlist},
+    notification::Notification,
+    tracklist::PlayingEntity,
+};
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::{AppState, ResponseResult, hx_redirect, ok_or_send_error_toast};
+use crate::{AppState, ResponseResult, copy_link, hx_redirect, ok_or_send_error_toast};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
@@ -26,12 +33,19 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
         .route("/playlist/{id}/play/shuffle", put(shuffle))
         .route("/playlist/{id}/play/{track_position}", put(play_track))
         .route("/playlist/{id}/link", put(link))
+        .route("/playlist/{id}/tags", put(set_tags))
         .route("/playlist/add-track/{id}", get(add_track_to_playlist_page))
         .route(
             "/playlist/remove-track",
             post(remove_track_from_playlist_action),
         )
         .route("/playlist/add-track", post(add_track_to_playlist_action))
+        .route("/playlist/add-album/{id}", get(add_album_to_playlist_page))
+        .route(
+            "/playlist/add-playlist/{id}",
+            get(add_playlist_to_playlist_page),
+        )
+        .route("/playlist/add-tracks", post(add_tracks_to_playlist_action))
         .route("/playlist/reorder", post(reorder_tracks))
         .route("/playlist/action", put(action))
 }
@@ -39,8 +53,12 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum Action {
+    Play,
     AddToQueue,
     PlayNext,
+    AddToPlaylist,
+    ToggleFavorite,
+    CopyLink,
 }
 #[derive(Deserialize)]
 struct ActionParameters {
@@ -52,6 +70,10 @@ async fn action(
     Form(req): Form<ActionParameters>,
 ) -> ResponseResult {
     match req.action {
+        Action::Play => {
+            state.controls.play_playlist(req.id, 0, false);
+            Ok(().into_response())
+        }
         Action::AddToQueue => {
             let playlist = ok_or_send_error_toast(&state, state.client.playlist(req.id).await)?;
             let track_ids = playlist.tracks.into_iter().map(|x| x.id).collect();
@@ -73,6 +95,22 @@ async fn action(
                 playlist.title
             ))))
         }
+        Action::AddToPlaylist => Ok(hx_redirect(&format!("/playlist/add-playlist/{}", req.id))),
+        Action::ToggleFavorite => {
+            let favorites = ok_or_send_error_toast(&state, state.get_favorites().await)?;
+
+            if favorites.playlists.iter().any(|x| x.id == req.id) {
+                ok_or_send_error_toast(
+                    &state,
+                    state.client.remove_favorite_playlist(req.id).await,
+                )?;
+                Ok(state.send_toast(Notification::Info("Playlist removed from favorites".into())))
+            } else {
+                ok_or_send_error_toast(&state, state.client.add_favorite_playlist(req.id).await)?;
+                Ok(state.send_toast(Notification::Info("Playlist added to favorites".into())))
+            }
+        }
+        Action::CopyLink => Ok(copy_link(&state, &LinkTarget::Playlist(req.id))),
     }
 }
 
@@ -95,6 +133,30 @@ async fn add_track_to_playlist_action(
     Ok(state.send_toast(Notification::Success(format!("Added to {}", res.title))))
 }
 
+#[derive(Deserialize)]
+struct AddTracksParameters {
+    #[serde(default)]
+    track_ids: Vec<u32>,
+    playlist_id: u32,
+}
+
+async fn add_tracks_to_playlist_action(
+    State(state): State<Arc<AppState>>,
+    Form(req): Form<AddTracksParameters>,
+) -> ResponseResult {
+    let res = state
+        .client
+        .playlist_add_track(req.playlist_id, &req.track_ids)
+        .await;
+    let res = ok_or_send_error_toast(&state, res)?;
+
+    Ok(state.send_toast(Notification::Success(format!(
+        "Added {} tracks to {}",
+        req.track_ids.len(),
+        res.title
+    ))))
+}
+
 async fn remove_track_from_playlist_action(
     State(state): State<Arc<AppState>>,
     Form(req): Form<ModifyTrackParameters>,
@@ -157,13 +219,7 @@ async fn add_track_to_playlist_page(
     let track = state.client.track(id).await;
     let track = ok_or_send_error_toast(&state, track)?;
 
-    let playlists = state.get_favorites().await;
-    let playlists = ok_or_send_error_toast(&state, playlists)?;
-    let playlists: Vec<_> = playlists
-        .playlists
-        .into_iter()
-        .filter(|x| x.is_owned)
-        .collect();
+    let playlists = ok_or_send_error_toast(&state, owned_playlists(&state).await)?;
 
     Ok(state.render(
         "add-track-to-playlist.html",
@@ -171,6 +227,59 @@ async fn add_track_to_playlist_page(
     ))
 }
 
+async fn add_album_to_playlist_page(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ResponseResult {
+    let album = ok_or_send_error_toast(&state, state.client.album(&id).await)?;
+    let playlists = ok_or_send_error_toast(&state, owned_playlists(&state).await)?;
+    let track_ids: Vec<_> = album.tracks.iter().map(|x| x.id).collect();
+
+    Ok(state.render(
+        "add-tracks-to-playlist.html",
+        &json!({
+            "title": album.title,
+            "subtitle": album.artist.name,
+            "image": album.image,
+            "track_ids": track_ids,
+            "playlists": playlists,
+        }),
+    ))
+}
+
+async fn add_playlist_to_playlist_page(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u32>,
+) -> ResponseResult {
+    let playlist = ok_or_send_error_toast(&state, state.client.playlist(id).await)?;
+    let playlists: Vec<_> = ok_or_send_error_toast(&state, owned_playlists(&state).await)?
+        .into_iter()
+        .filter(|x| x.id != id)
+        .collect();
+    let track_ids: Vec<_> = playlist.tracks.iter().map(|x| x.id).collect();
+
+    Ok(state.render(
+        "add-tracks-to-playlist.html",
+        &json!({
+            "title": playlist.title,
+            "subtitle": format!("{} tracks", playlist.tracks_count),
+            "image": playlist.image,
+            "track_ids": track_ids,
+            "playlists": playlists,
+        }),
+    ))
+}
+
+/// Tracks can only be added to playlists of the signed in user
+async fn owned_playlists(state: &AppState) -> AppResult<Vec<Playlist>> {
+    let favorites = state.get_favorites().await?;
+    Ok(favorites
+        .playlists
+        .into_iter()
+        .filter(|x| x.is_owned)
+        .collect())
+}
+
 async fn create(State(state): State<Arc<AppState>>) -> ResponseResult {
     Ok(state.render("create-playlist.html", &json!({})))
 }
@@ -216,16 +325,50 @@ async fn play(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> impl I
     state.controls.play_playlist(id, 0, false);
 }
 
-async fn link(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> impl IntoResponse {
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LinkMode {
+    Play,
+    Queue,
+}
+
+#[derive(Deserialize)]
+struct LinkParameters {
+    mode: LinkMode,
+}
+
+async fn link(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u32>,
+    Form(parameters): Form<LinkParameters>,
+) -> impl IntoResponse {
     let Some(rfid_state) = state.rfid_state.clone() else {
         return;
     };
-    qobuz_player_rfid::link(
-        rfid_state,
-        qobuz_player_controls::database::ReferenceType::Playlist(id),
-        state.broadcast.clone(),
-    )
-    .await;
+
+    let reference = match parameters.mode {
+        LinkMode::Play => ReferenceType::Playlist(id),
+        LinkMode::Queue => ReferenceType::QueuePlaylist(id),
+    };
+
+    qobuz_player_rfid::link(rfid_state, reference, state.broadcast.clone()).await;
+}
+
+#[derive(Deserialize)]
+struct TagsParameters {
+    /// Separated by commas
+    tags: String,
+}
+
+async fn set_tags(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u32>,
+    Form(parameters): Form<TagsParameters>,
+) -> ResponseResult {
+    let tags: Vec<String> = parameters.tags.split(',').map(String::from).collect();
+    ok_or_send_error_toast(&state, state.database.set_playlist_tags(id, &tags).await)?;
+
+    Ok(state.send_toast(Notification::Success("Tags saved".into())))
 }
 
 async fn shuffle(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> impl IntoResponse {
@@ -259,6 +402,11 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Res
     let playlist = ok_or_send_error_toast(&state, state.client.playlist(id).await)?;
     let favorites = ok_or_send_error_toast(&state, state.get_favorites().await)?;
     let is_favorite = favorites.playlists.iter().any(|playlist| playlist.id == id);
+    let tags = ok_or_send_error_toast(&state, state.database.get_playlist_tags().await)?;
+    let tags = tags
+        .get(&id)
+        .map(|tags| tags.join(", "))
+        .unwrap_or_default();
     let duration = playlist.duration_seconds / 60;
     let click_string = format!("/playlist/{}/play/", playlist.id);
 
@@ -271,6 +419,7 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Res
             "playlist": playlist,
             "duration": duration,
             "is_favorite": is_favorite,
+            "tags": tags,
             "rfid": state.rfid_state.is_some(),
             "click": click_string,
             "use_playing_index": playing_index.is_some(),
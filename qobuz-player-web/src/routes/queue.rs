@@ -1,48 +1,203 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
-use axum::{Router, extract::State, response::IntoResponse, routing::get};
-use serde_json::json;
+use axum::{
+    Extension, Router,
+    extract::{ConnectInfo, State},
+    response::IntoResponse,
+    routing::{get, post, put},
+};
+use axum_extra::extract::{CookieJar, Form};
+use qobuz_player_controls::{
+    models::LinkedEntity, notification::Notification, tracklist::Tracklist,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
 
-use crate::app_state::AppState;
+use crate::{
+    ResponseResult, WebRole,
+    app_state::AppState,
+    ok_or_send_error_toast,
+    routes::party::{self, PartyVotes},
+};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
         .route("/queue", get(index))
         .route("/queue/partial", get(queue_partial))
+        .route("/queue/import/preview", post(import_preview))
+        .route("/queue/import", put(import))
 }
 
-async fn index(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let tracklist = state.tracklist_receiver.borrow();
-    let tracks = tracklist
-        .queue()
-        .into_iter()
-        .map(|x| x.track.clone())
-        .collect::<Vec<_>>();
-    let currently_playing_position = tracklist.current_position();
+#[derive(Deserialize)]
+struct ImportParameters {
+    url: String,
+}
+
+async fn import_preview(
+    State(state): State<Arc<AppState>>,
+    Form(req): Form<ImportParameters>,
+) -> impl IntoResponse {
+    if req.url.trim().is_empty() {
+        return state.render("queue-import-preview.html", &json!({}));
+    }
+
+    match state.client.resolve_url(&req.url).await {
+        Ok(entity) => {
+            let (kind, image) = match &entity {
+                LinkedEntity::Album(album) => ("Album", Some(album.image.clone())),
+                LinkedEntity::Playlist(playlist) => ("Playlist", playlist.image.clone()),
+                LinkedEntity::Track(track) => ("Track", track.image.clone()),
+            };
+
+            state.render(
+                "queue-import-preview.html",
+                &json!({
+                    "preview": {
+                        "url": req.url,
+                        "title": entity.title(),
+                        "kind": kind,
+                        "image": image,
+                        "tracks_count": entity.available_track_ids().len(),
+                    }
+                }),
+            )
+        }
+        Err(err) => state.render(
+            "queue-import-preview.html",
+            &json!({"error": err.to_string()}),
+        ),
+    }
+}
+
+async fn import(
+    State(state): State<Arc<AppState>>,
+    Form(req): Form<ImportParameters>,
+) -> ResponseResult {
+    let entity = ok_or_send_error_toast(&state, state.client.resolve_url(&req.url).await)?;
+    let track_ids = entity.available_track_ids();
+
+    if track_ids.is_empty() {
+        return Ok(state.send_toast(Notification::Warning(format!(
+            "{} has no playable tracks",
+            entity.title()
+        ))));
+    }
+
+    state.controls.add_tracks_to_queue(track_ids);
+
+    Ok(state.send_toast(Notification::Success(format!(
+        "{} added to queue",
+        entity.title()
+    ))))
+}
 
-    state.render(
-        "queue.html",
-        &json!({
-            "tracks": tracks,
-            "currently_playing_position": currently_playing_position
-        }),
-    )
+async fn index(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let context = queue_context(&state, &party::voter(&state, role, &jar, address));
+    state.render("queue.html", &context)
 }
 
-async fn queue_partial(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn queue_partial(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let context = queue_context(&state, &party::voter(&state, role, &jar, address));
+    state.render("queue-list.html", &context)
+}
+
+pub(crate) fn queue_context(state: &AppState, voter: &str) -> Value {
     let tracklist = state.tracklist_receiver.borrow();
+    let votes = state.web_auth.party.then_some((&state.party_votes, voter));
+    queue_json(&tracklist, votes)
+}
+
+/// In party mode the tracks carry their vote tally and the vote of the voter. Only upcoming
+/// tracks take votes
+fn queue_json(tracklist: &Tracklist, votes: Option<(&PartyVotes, &str)>) -> Value {
+    let currently_playing_position = tracklist.current_position();
+
     let tracks = tracklist
         .queue()
         .into_iter()
-        .map(|x| x.track.clone())
+        .enumerate()
+        .map(|(index, item)| {
+            let mut track = json!(item.track);
+            if let Some((votes, voter)) = votes {
+                let vote = votes.vote_of(item.queue_id, voter);
+                track["upcoming"] = json!(index > currently_playing_position);
+                track["queue_id"] = json!(item.queue_id);
+                track["score"] = json!(votes.score(item.queue_id));
+                track["voted_up"] = json!(vote > 0);
+                track["voted_down"] = json!(vote < 0);
+            }
+            track
+        })
         .collect::<Vec<_>>();
-    let currently_playing_position = tracklist.current_position();
 
-    state.render(
-        "queue-list.html",
-        &json!({
-            "tracks": tracks,
-            "currently_playing_position": currently_playing_position
-        }),
-    )
+    json!({
+        "tracks": tracks,
+        "currently_playing_position": currently_playing_position,
+        "party": votes.is_some(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use qobuz_player_controls::{
+        models::{Track, TrackStatus},
+        tracklist::{QueueItem, Tracklist, TracklistType},
+    };
+    use serde_json::json;
+
+    use super::queue_json;
+    use crate::routes::party::PartyVotes;
+
+    #[test]
+    fn party_queue_carries_the_votes() {
+        let items = (0..3)
+            .map(|index| QueueItem {
+                track: Track {
+                    id: index as u32,
+                    status: match index {
+                        0 => TrackStatus::Playing,
+                        _ => TrackStatus::Unplayed,
+                    },
+                    ..Default::default()
+                },
+                queue_id: 10 + index as u64,
+                index,
+                queued_by_user: true,
+            })
+            .collect();
+        let tracklist = Tracklist::new(TracklistType::Tracks, items);
+
+        let context = queue_json(&tracklist, None);
+        assert_eq!(context["party"], json!(false));
+        assert_eq!(context["tracks"][1].get("score"), None);
+
+        let votes = PartyVotes::default();
+        votes.vote(11, "10.0.0.2", 1);
+        votes.vote(11, "10.0.0.3", 1);
+        votes.vote(12, "10.0.0.2", -1);
+
+        let context = queue_json(&tracklist, Some((&votes, "10.0.0.2")));
+        assert_eq!(context["party"], json!(true));
+        assert_eq!(context["currently_playing_position"], json!(0));
+
+        let tracks = &context["tracks"];
+        assert_eq!(tracks[0]["upcoming"], json!(false));
+        assert_eq!(tracks[1]["upcoming"], json!(true));
+        assert_eq!(tracks[1]["queue_id"], json!(11));
+        assert_eq!(tracks[1]["score"], json!(2));
+        assert_eq!(tracks[1]["voted_up"], json!(true));
+        assert_eq!(tracks[1]["voted_down"], json!(false));
+        assert_eq!(tracks[2]["score"], json!(-1));
+        assert_eq!(tracks[2]["voted_down"], json!(true));
+    }
 }
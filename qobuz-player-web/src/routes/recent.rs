@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    routing::{get, put},
+};
+use qobuz_player_controls::{notification::Notification, tracklist::TracklistType};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{AppState, ResponseResult, ok_or_error_page, ok_or_send_error_toast};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/recent", get(index))
+        .route("/recent/{index}/play", put(play))
+}
+
+#[derive(Serialize)]
+struct RecentTracklist {
+    title: String,
+    kind: &'static str,
+    link: String,
+    image: Option<String>,
+}
+
+fn recent_tracklist(list_type: TracklistType) -> Option<RecentTracklist> {
+    match list_type {
+        TracklistType::Album(album) => Some(RecentTracklist {
+            title: album.title,
+            kind: "Album",
+            link: format!("/album/{}", album.id),
+            image: album.image,
+        }),
+        TracklistType::Playlist(playlist) => Some(RecentTracklist {
+            title: playlist.title,
+            kind: "Playlist",
+            link: format!("/playlist/{}", playlist.id),
+            image: playlist.image,
+        }),
+        TracklistType::TopTracks(top_tracks) => Some(RecentTracklist {
+            title: top_tracks.artist_name,
+            kind: "Top tracks",
+            link: format!("/artist/{}", top_tracks.id),
+            image: top_tracks.image,
+        }),
+        TracklistType::Tracks => None,
+    }
+}
+
+async fn index(State(state): State<Arc<AppState>>) -> ResponseResult {
+    let recent = ok_or_error_page(&state, state.database.get_recent_tracklists().await)?;
+    let recent: Vec<_> = recent.into_iter().filter_map(recent_tracklist).collect();
+
+    Ok(state.render("recent.html", &json!({ "recent": recent })))
+}
+
+async fn play(State(state): State<Arc<AppState>>, Path(index): Path<usize>) -> ResponseResult {
+    let recent = ok_or_send_error_toast(&state, state.database.get_recent_tracklists().await)?;
+
+    match recent.get(index) {
+        Some(list_type) => {
+            state.controls.play_tracklist_type(list_type);
+            Ok(state.send_toast(Notification::Success("Queue restored".into())))
+        }
+        None => Ok(state.send_toast(Notification::Error("Unable to find recent queue".into()))),
+    }
+}
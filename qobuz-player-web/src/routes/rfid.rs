@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get, put},
+};
+use axum_extra::extract::Form;
+use futures::future::join_all;
+use qobuz_player_controls::{
+    database::{ReferenceType, RfidTag},
+    notification::Notification,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{AppState, ResponseResult, hx_redirect, ok_or_error_page, ok_or_send_error_toast};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/rfid", get(index))
+        .route("/rfid/content", get(content))
+        .route("/rfid/{id}", delete(delete_tag))
+        .route("/rfid/{id}/name", put(rename))
+        .route("/rfid/{id}/relink", put(relink))
+        .route("/rfid/link", put(link))
+}
+
+#[derive(Serialize)]
+struct TagView {
+    id: String,
+    name: Option<String>,
+    kind: &'static str,
+    title: String,
+    link: Option<String>,
+    image: Option<String>,
+    last_scanned: Option<String>,
+}
+
+async fn tag_view(state: &AppState, tag: RfidTag) -> TagView {
+    let kind = tag.reference.label();
+    let (title, link, image) = match &tag.reference {
+        ReferenceType::Album(id) | ReferenceType::QueueAlbum(id) => {
+            let album = state.client.album(id).await.ok();
+            (
+                album.as_ref().map(|album| album.title.clone()),
+                Some(format!("/album/{id}")),
+                album.map(|album| album.image_thumbnail),
+            )
+        }
+        ReferenceType::Playlist(id) | ReferenceType::QueuePlaylist(id) => {
+            let playlist = state.client.playlist(*id).await.ok();
+            (
+                playlist.as_ref().map(|playlist| playlist.title.clone()),
+                Some(format!("/playlist/{id}")),
+                playlist.and_then(|playlist| playlist.image),
+            )
+        }
+        ReferenceType::PlayPause | ReferenceType::Stop => (Some(kind.to_string()), None, None),
+        ReferenceType::Volume(volume) => (Some(format!("Volume {volume}%")), None, None),
+    };
+
+    let last_scanned = tag.last_scanned.map(|time| {
+        format!(
+            "{}-{:02}-{:02} {:02}:{:02}",
+            time.year(),
+            time.month() as u8,
+            time.day(),
+            time.hour(),
+            time.minute()
+        )
+    });
+
+    TagView {
+        title: title.unwrap_or_else(|| "Unavailable".to_string()),
+        id: tag.id,
+        name: tag.name,
+        kind,
+        link,
+        image,
+        last_scanned,
+    }
+}
+
+async fn index(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    state.render("lazy-load-component.html", &json!({"url": "/rfid/content"}))
+}
+
+async fn content(State(state): State<Arc<AppState>>) -> ResponseResult {
+    let tags = ok_or_error_page(&state, state.database.get_rfid_tags().await)?;
+    let tags = join_all(tags.into_iter().map(|tag| tag_view(&state, tag))).await;
+
+    Ok(state.render(
+        "rfid.html",
+        &json!({
+            "tags": tags,
+            "rfid": state.rfid_state.is_some(),
+        }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct RenameParameters {
+    name: String,
+}
+
+async fn rename(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(parameters): Form<RenameParameters>,
+) -> ResponseResult {
+    let name = parameters.name.trim();
+    let name = (!name.is_empty()).then(|| name.to_string());
+
+    ok_or_send_error_toast(&state, state.database.rename_rfid_tag(&id, name).await)?;
+
+    Ok(state.send_toast(Notification::Success("Tag renamed".into())))
+}
+
+async fn delete_tag(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.delete_rfid_tag(&id).await)?;
+
+    Ok(hx_redirect("/rfid"))
+}
+
+async fn relink(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> impl IntoResponse {
+    let Some(rfid_state) = state.rfid_state.clone() else {
+        return;
+    };
+
+    qobuz_player_rfid::relink(rfid_state, id, state.broadcast.clone()).await;
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ControlAction {
+    PlayPause,
+    Stop,
+    Volume,
+}
+
+#[derive(Deserialize)]
+struct LinkParameters {
+    action: ControlAction,
+    volume: Option<u8>,
+}
+
+async fn link(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<LinkParameters>,
+) -> impl IntoResponse {
+    let Some(rfid_state) = state.rfid_state.clone() else {
+        return;
+    };
+
+    let reference = match parameters.action {
+        ControlAction::PlayPause => ReferenceType::PlayPause,
+        ControlAction::Stop => ReferenceType::Stop,
+        ControlAction::Volume => ReferenceType::Volume(parameters.volume.unwrap_or(50).min(100)),
+    };
+
+    qobuz_player_rfid::link(rfid_state, reference, state.broadcast.clone()).await;
+}
@@ -3,11 +3,17 @@ use std::sync::Arc;
 use axum::{
     Form, Router,
     extract::{Path, Query, State},
-    routing::get,
+    response::{IntoResponse, Redirect},
+    routing::{delete, get},
+};
+use qobuz_player_controls::{
+    AppResult,
+    database::BanList,
+    error::Error,
+    models::{LinkTarget, SearchFilter, SearchResults},
 };
-use qobuz_player_controls::models::SearchResults;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{Value, json};
 
 #[derive(Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -16,17 +22,151 @@ pub enum Tab {
     Artists,
     Playlists,
     Tracks,
+    Composers,
 }
 
-use crate::{AppState, ResponseResult, ok_or_error_page, ok_or_send_error_toast};
+use crate::{AppState, ResponseResult, hx_redirect, ok_or_error_page, ok_or_send_error_toast};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
-    Router::new().route("/search/{tab}", get(index).post(search))
+    Router::new()
+        .route("/search/suggestions", get(suggestions))
+        .route("/search/history", delete(clear_history))
+        .route("/search/{tab}", get(index).post(search))
+        .route("/search/{tab}/more", get(more))
 }
 
 #[derive(Deserialize)]
-struct SearchParameters {
+pub(crate) struct SearchParameters {
     query: Option<String>,
+    #[serde(default)]
+    pub offset: usize,
+    hires_only: Option<String>,
+    hide_explicit: Option<String>,
+    min_year: Option<String>,
+    max_year: Option<String>,
+}
+
+impl SearchParameters {
+    /// The query, unless it is empty
+    pub fn query(&self) -> Option<String> {
+        self.query.clone().filter(|query| !query.is_empty())
+    }
+
+    /// Pasted Qobuz urls open the item instead of searching for the url
+    fn link_target(&self) -> Option<LinkTarget> {
+        self.query.as_deref().and_then(LinkTarget::parse)
+    }
+
+    /// Checkboxes are only sent when checked, and empty year inputs are unset
+    pub fn filter(&self) -> SearchFilter {
+        let flag = |value: &Option<String>| value.as_deref().is_some_and(|value| value != "false");
+        let year = |value: &Option<String>| value.as_deref().and_then(|value| value.parse().ok());
+
+        SearchFilter {
+            hires_only: flag(&self.hires_only),
+            hide_explicit: flag(&self.hide_explicit),
+            min_year: year(&self.min_year),
+            max_year: year(&self.max_year),
+        }
+    }
+}
+
+/// Tracks have no page of their own, so they open their album
+async fn link_page(state: &AppState, target: LinkTarget) -> AppResult<String> {
+    Ok(match target {
+        LinkTarget::Album(id) => format!("/album/{id}"),
+        LinkTarget::Playlist(id) => format!("/playlist/{id}"),
+        LinkTarget::Artist(id) => format!("/artist/{id}"),
+        LinkTarget::Track(id) => {
+            let track = state.client.track(id).await?;
+            let album_id = track.album_id.ok_or_else(|| Error::InvalidLink {
+                message: format!("{} is not on an album", track.title),
+            })?;
+            format!("/album/{album_id}")
+        }
+    })
+}
+
+/// Sets `banned` on serialized tracks and artists that are never played, and on albums of
+/// banned artists, so lists can badge them
+pub(crate) fn badge_banned(value: &mut Value, ban_list: &BanList) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                badge_banned(item, ban_list);
+            }
+        }
+        Value::Object(object) => {
+            let id = |value: Option<&Value>| value.and_then(Value::as_u64).map(|id| id as u32);
+            let banned = if object.contains_key("artist_id") {
+                id(object.get("id")).is_some_and(|id| ban_list.is_track_banned(id))
+                    || id(object.get("artist_id")).is_some_and(|id| ban_list.is_artist_banned(id))
+            } else if let Some(artist) = object.get("artist") {
+                id(artist.get("id")).is_some_and(|id| ban_list.is_artist_banned(id))
+            } else if object.contains_key("name") && object.contains_key("image") {
+                id(object.get("id")).is_some_and(|id| ban_list.is_artist_banned(id))
+            } else {
+                false
+            };
+            if banned {
+                object.insert("banned".into(), Value::Bool(true));
+            }
+
+            for value in object.values_mut() {
+                badge_banned(value, ban_list);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Search results as rendered, with banned items badged
+async fn search_results_view(state: &AppState, search_results: SearchResults) -> Value {
+    let mut value = json!(search_results);
+    if let Ok(ban_list) = state.database.get_ban_list().await {
+        badge_banned(&mut value, &ban_list);
+    }
+    value
+}
+
+/// Records the query, and returns the recent searches to list instead of results when
+/// there is no query
+async fn search_history(state: &AppState, query: Option<&str>) -> Vec<String> {
+    match query {
+        Some(query) => {
+            _ = state.database.add_search_query(query).await;
+            vec![]
+        }
+        None => state
+            .database
+            .get_search_history()
+            .await
+            .unwrap_or_default(),
+    }
+}
+
+/// Whether the tab has more results, and the offset of the next page
+fn pagination(search_results: &SearchResults, tab: &Tab, offset: usize) -> (bool, usize) {
+    match tab {
+        Tab::Albums => (
+            search_results.more_albums,
+            offset + search_results.albums.len(),
+        ),
+        Tab::Artists => (
+            search_results.more_artists,
+            offset + search_results.artists.len(),
+        ),
+        Tab::Playlists => (
+            search_results.more_playlists,
+            offset + search_results.playlists.len(),
+        ),
+        Tab::Tracks => (
+            search_results.more_tracks,
+            offset + search_results.tracks.len(),
+        ),
+        // Composers are taken from the first page of tracks
+        Tab::Composers => (false, offset),
+    }
 }
 
 async fn index(
@@ -34,17 +174,25 @@ async fn index(
     Path(tab): Path<Tab>,
     Query(parameters): Query<SearchParameters>,
 ) -> ResponseResult {
-    let query = parameters
-        .query
-        .and_then(|s| if s.is_empty() { None } else { Some(s) });
-    let search_results = match query {
+    if let Some(target) = parameters.link_target() {
+        let page = ok_or_error_page(&state, link_page(&state, target).await)?;
+        return Ok(Redirect::to(&page).into_response());
+    }
+
+    let query = parameters.query();
+    let search_results = match query.clone() {
         Some(query) => ok_or_error_page(&state, state.client.search(query).await)?,
         None => SearchResults::default(),
     };
+    let history = search_history(&state, query.as_deref()).await;
+
+    let filter = parameters.filter();
+    let (more, next_offset) = pagination(&search_results, &tab, 0);
+    let search_results = search_results_view(&state, filter.apply(search_results)).await;
 
     Ok(state.render(
         "search.html",
-        &json!({"search_results": search_results, "tab": tab}),
+        &json!({"search_results": search_results, "tab": tab, "filter": filter, "more": more, "next_offset": next_offset, "history": history}),
     ))
 }
 
@@ -53,16 +201,123 @@ async fn search(
     Path(tab): Path<Tab>,
     Form(parameters): Form<SearchParameters>,
 ) -> ResponseResult {
-    let query = parameters
-        .query
-        .and_then(|s| if s.is_empty() { None } else { Some(s) });
-    let search_results = match query {
+    if let Some(target) = parameters.link_target() {
+        let page = ok_or_send_error_toast(&state, link_page(&state, target).await)?;
+        return Ok(hx_redirect(&page));
+    }
+
+    let query = parameters.query();
+    let search_results = match query.clone() {
         Some(query) => ok_or_send_error_toast(&state, state.client.search(query).await)?,
         None => SearchResults::default(),
     };
+    let history = search_history(&state, query.as_deref()).await;
+
+    let (more, next_offset) = pagination(&search_results, &tab, 0);
+    let search_results =
+        search_results_view(&state, parameters.filter().apply(search_results)).await;
 
     Ok(state.render(
         "search-content.html",
-        &json!({"search_results": search_results, "tab": tab }),
+        &json!({"search_results": search_results, "tab": tab, "more": more, "next_offset": next_offset, "history": history}),
     ))
 }
+
+async fn clear_history(State(state): State<Arc<AppState>>) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.clear_search_history().await)?;
+
+    Ok(().into_response())
+}
+
+/// Dropdown under the search box while typing. Empty for an empty query or a pasted link
+async fn suggestions(
+    State(state): State<Arc<AppState>>,
+    Query(parameters): Query<SearchParameters>,
+) -> ResponseResult {
+    let suggestions = match parameters.query() {
+        Some(_) if parameters.link_target().is_some() => SearchResults::default(),
+        Some(query) => ok_or_send_error_toast(&state, state.client.suggestions(query).await)?,
+        None => SearchResults::default(),
+    };
+    let suggestions = search_results_view(&state, suggestions).await;
+
+    Ok(state.render(
+        "search-suggestions.html",
+        &json!({"suggestions": suggestions}),
+    ))
+}
+
+async fn more(
+    State(state): State<Arc<AppState>>,
+    Path(tab): Path<Tab>,
+    Query(parameters): Query<SearchParameters>,
+) -> ResponseResult {
+    let search_results = match parameters.query() {
+        Some(query) => ok_or_send_error_toast(
+            &state,
+            state.client.search_page(query, parameters.offset).await,
+        )?,
+        None => SearchResults::default(),
+    };
+
+    let (more, next_offset) = pagination(&search_results, &tab, parameters.offset);
+    let search_results =
+        search_results_view(&state, parameters.filter().apply(search_results)).await;
+
+    Ok(state.render(
+        "search-page.html",
+        &json!({"search_results": search_results, "tab": tab, "more": more, "next_offset": next_offset}),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use qobuz_player_controls::database::{Ban, BanList};
+    use serde_json::json;
+
+    use super::badge_banned;
+
+    fn ban(id: u32) -> Ban {
+        Ban {
+            id,
+            name: String::new(),
+        }
+    }
+
+    #[test]
+    fn badges_banned_tracks_artists_and_their_albums() {
+        let ban_list = BanList {
+            tracks: vec![ban(1)],
+            artists: vec![ban(10)],
+        };
+        let mut value = json!({
+            "tracks": [
+                { "id": 1, "artist_id": 20 },
+                { "id": 2, "artist_id": 10 },
+                { "id": 3, "artist_id": 20 },
+            ],
+            "albums": [
+                { "id": "a", "artist": { "id": 10, "name": "Banned", "image": null } },
+                { "id": "b", "artist": { "id": 20, "name": "Other", "image": null } },
+            ],
+            "artists": [
+                { "id": 10, "name": "Banned", "image": null },
+                { "id": 20, "name": "Other", "image": null },
+            ],
+        });
+
+        badge_banned(&mut value, &ban_list);
+
+        let banned = |items: &str| -> Vec<bool> {
+            value[items]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item.get("banned").is_some())
+                .collect()
+        };
+        assert_eq!(banned("tracks"), [true, true, false]);
+        assert_eq!(banned("albums"), [true, false]);
+        assert_eq!(banned("artists"), [true, false]);
+    }
+}
@@ -0,0 +1,447 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post, put},
+};
+use axum_extra::extract::Form;
+use qobuz_player_controls::{
+    AppResult, AudioQuality, alarms,
+    audio_cache::format_size,
+    client::get_app_id,
+    database::BanKind,
+    error::Error,
+    profile::{replace_credentials, switch_profile},
+    quiet_hours::QuietHours,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    AppState, ResponseResult, WebRole, hx_redirect, ok_or_broadcast, ok_or_error_page,
+    ok_or_send_error_toast,
+    onboarding::{exchange_code, oauth_url},
+    routes::status::disk_usage,
+};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/settings", get(index))
+        .route("/settings/balance", post(set_balance))
+        .route("/settings/mono-downmix", put(set_mono_downmix))
+        .route("/settings/trim-silence", put(set_trim_silence))
+        .route("/settings/audio-quality", post(set_audio_quality))
+        .route("/settings/data-saver", put(set_data_saver))
+        .route("/settings/play-previews", put(set_play_previews))
+        .route("/settings/profile", post(set_profile))
+        .route("/settings/sign-in", get(sign_in_page).post(sign_in))
+        .route("/settings/zones/{zone}/volume", post(set_zone_volume))
+        .route("/settings/zones/{zone}/enabled", put(set_zone_enabled))
+        .route("/settings/bans/{kind}/{id}", delete(unban))
+        .route("/settings/quiet-hours", post(set_quiet_hours))
+        .route("/settings/alarms", post(add_alarm))
+        .route("/settings/alarms/{id}", delete(remove_alarm))
+        .route("/settings/alarms/{id}/enabled", put(set_alarm_enabled))
+}
+
+const AUDIO_QUALITIES: [AudioQuality; 4] = [
+    AudioQuality::Mp3,
+    AudioQuality::CD,
+    AudioQuality::HIFI96,
+    AudioQuality::HIFI192,
+];
+
+/// Options of the quality select, with `current` selected
+pub(crate) fn audio_quality_options(current: AudioQuality) -> Vec<serde_json::Value> {
+    AUDIO_QUALITIES
+        .iter()
+        .map(|quality| {
+            json!({
+                "id": *quality as i64,
+                "label": quality.label(),
+                "selected": *quality as i64 == current as i64,
+            })
+        })
+        .collect()
+}
+
+async fn index(
+    State(state): State<Arc<AppState>>,
+    Extension(role): Extension<WebRole>,
+) -> ResponseResult {
+    let configuration = ok_or_error_page(&state, state.database.get_configuration().await)?;
+    let balance = (configuration.balance * 100.0).round() as i32;
+    let profiles = ok_or_error_page(&state, state.database.get_profiles().await)?;
+    let current_profile = state.database.profile();
+    let profiles: Vec<_> = match profiles.len() {
+        0 | 1 => vec![],
+        _ => profiles
+            .into_iter()
+            .map(|profile| {
+                json!({
+                    "selected": profile.name == current_profile,
+                    "name": profile.name,
+                    "logged_in": profile.logged_in,
+                })
+            })
+            .collect(),
+    };
+
+    let ban_list = ok_or_error_page(&state, state.database.get_ban_list().await)?;
+    let alarms = ok_or_error_page(&state, state.database.get_alarms().await)?;
+    let quiet_hours = ok_or_error_page(&state, state.database.get_quiet_hours().await)?;
+
+    let audio_qualities = audio_quality_options(state.client.max_audio_quality());
+
+    Ok(state.render(
+        "settings.html",
+        &json!({
+            "balance": balance,
+            "mono_downmix": configuration.mono_downmix,
+            "trim_silence": configuration.trim_silence,
+            "audio_qualities": audio_qualities,
+            "data_saver": state.client.data_saver(),
+            "play_previews": state.client.play_previews(),
+            "instances": instances(&state),
+            "profiles": profiles,
+            "zones": zones(&state),
+            "cache": cache_usage(&state).await,
+            "has_bans": !ban_list.is_empty(),
+            "ban_list": ban_list,
+            "alarms": alarms,
+            "quiet_hours": quiet_hours_settings(quiet_hours),
+            "party": state.web_auth.party,
+            "login": role == WebRole::Guest,
+            "logout": state.web_auth.enabled() && role != WebRole::Guest,
+            "logs": role == WebRole::Controller,
+        }),
+    ))
+}
+
+async fn unban(
+    State(state): State<Arc<AppState>>,
+    Path((kind, id)): Path<(BanKind, u32)>,
+) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.unban(kind, id).await)?;
+
+    Ok(hx_redirect("/settings"))
+}
+
+/// Form values of the quiet hours, the usual night when they are off
+fn quiet_hours_settings(quiet_hours: Option<QuietHours>) -> serde_json::Value {
+    match quiet_hours {
+        Some(quiet_hours) => json!({
+            "enabled": true,
+            "start": quiet_hours.start_time(),
+            "end": quiet_hours.end_time(),
+            "max_volume": quiet_hours.max_volume,
+        }),
+        None => json!({
+            "enabled": false,
+            "start": "22:00",
+            "end": "07:00",
+            "max_volume": 30,
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct QuietHoursParameters {
+    enabled: Option<String>,
+    start: String,
+    end: String,
+    max_volume: u8,
+}
+
+async fn set_quiet_hours(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<QuietHoursParameters>,
+) -> ResponseResult {
+    let quiet_hours = match parameters.enabled {
+        Some(_) => Some(ok_or_broadcast(
+            &state.broadcast,
+            QuietHours::new(
+                &format!("{}-{}", parameters.start, parameters.end),
+                parameters.max_volume,
+            )
+            .map_err(|message| Error::Client { message }),
+        )?),
+        None => None,
+    };
+
+    state.controls.set_quiet_hours(quiet_hours);
+    Ok(().into_response())
+}
+
+#[derive(Deserialize)]
+struct AlarmParameters {
+    schedule: String,
+    link: String,
+    volume: u8,
+}
+
+async fn add_alarm(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<AlarmParameters>,
+) -> ResponseResult {
+    // Keeps the list when the alarm can not be added
+    ok_or_broadcast(
+        &state.broadcast,
+        alarms::add(
+            &state.database,
+            &state.client,
+            &parameters.schedule,
+            &parameters.link,
+            parameters.volume,
+        )
+        .await,
+    )?;
+
+    render_alarms(&state).await
+}
+
+async fn remove_alarm(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.remove_alarm(id).await)?;
+
+    render_alarms(&state).await
+}
+
+async fn set_alarm_enabled(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Form(parameters): Form<ToggleParameters>,
+) -> ResponseResult {
+    ok_or_send_error_toast(
+        &state,
+        state
+            .database
+            .set_alarm_enabled(id, parameters.enabled.is_some())
+            .await,
+    )?;
+
+    render_alarms(&state).await
+}
+
+async fn render_alarms(state: &AppState) -> ResponseResult {
+    let alarms = ok_or_send_error_toast(state, state.database.get_alarms().await)?;
+
+    Ok(state.render("settings-alarms.html", &json!({"alarms": alarms})))
+}
+
+/// Human readable sizes of the audio and artwork caches
+async fn cache_usage(state: &AppState) -> serde_json::Value {
+    let audio = disk_usage(state.startup_summary.audio_cache.clone()).await;
+    let artwork = disk_usage(state.artwork_cache.dir().to_path_buf()).await;
+
+    json!({
+        "audio": audio.to_string(),
+        "audio_limit": state.startup_summary.audio_cache_max_bytes.map(format_size),
+        "artwork": artwork.to_string(),
+        "artwork_limit": state.artwork_cache.max_bytes().map(format_size),
+    })
+}
+
+/// This player followed by the peers found on the network. Empty when there are no peers.
+/// The page is rendered by the player being controlled, so this player is the selected one
+fn instances(state: &AppState) -> Vec<serde_json::Value> {
+    let peers = state.discovery.peers();
+    if peers.is_empty() {
+        return vec![];
+    }
+
+    let this = json!({
+        "fullname": state.discovery.fullname,
+        "name": format!("{} (this player)", state.discovery.name),
+        "selected": true,
+    });
+
+    std::iter::once(this)
+        .chain(
+            peers.into_iter().map(
+                |peer| json!({"fullname": peer.fullname, "name": peer.name, "selected": false}),
+            ),
+        )
+        .collect()
+}
+
+/// Outputs with their volume in percent. Empty when playing to a single output
+fn zones(state: &AppState) -> Vec<serde_json::Value> {
+    let zones = state.zones_receiver.borrow();
+    if zones.len() < 2 {
+        return vec![];
+    }
+
+    zones
+        .iter()
+        .enumerate()
+        .map(|(index, zone)| {
+            json!({
+                "index": index,
+                "name": zone.name,
+                "volume": (zone.volume * 100.0).round() as i32,
+                "enabled": zone.enabled,
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct BalanceParameters {
+    value: i32,
+}
+
+async fn set_balance(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<BalanceParameters>,
+) -> impl IntoResponse {
+    let balance = parameters.value.clamp(-100, 100) as f32 / 100.0;
+    state.controls.set_balance(balance);
+}
+
+#[derive(Deserialize)]
+struct ToggleParameters {
+    enabled: Option<String>,
+}
+
+async fn set_mono_downmix(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<ToggleParameters>,
+) -> impl IntoResponse {
+    state
+        .controls
+        .set_mono_downmix(parameters.enabled.is_some());
+}
+
+async fn set_trim_silence(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<ToggleParameters>,
+) -> impl IntoResponse {
+    state
+        .controls
+        .set_trim_silence(parameters.enabled.is_some());
+}
+
+#[derive(Deserialize)]
+struct VolumeParameters {
+    value: i32,
+}
+
+async fn set_zone_volume(
+    State(state): State<Arc<AppState>>,
+    Path(zone): Path<usize>,
+    Form(parameters): Form<VolumeParameters>,
+) -> impl IntoResponse {
+    let volume = parameters.value.clamp(0, 100) as f32 / 100.0;
+    state.controls.set_zone_volume(zone, volume);
+}
+
+async fn set_zone_enabled(
+    State(state): State<Arc<AppState>>,
+    Path(zone): Path<usize>,
+    Form(parameters): Form<ToggleParameters>,
+) -> impl IntoResponse {
+    state
+        .controls
+        .set_zone_enabled(zone, parameters.enabled.is_some());
+}
+
+#[derive(Deserialize)]
+struct AudioQualityParameters {
+    quality: i64,
+}
+
+async fn set_audio_quality(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<AudioQualityParameters>,
+) -> Response {
+    match AudioQuality::try_from(parameters.quality) {
+        Ok(quality) => {
+            state.controls.set_max_audio_quality(quality);
+            StatusCode::OK.into_response()
+        }
+        Err(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+    }
+}
+
+async fn set_data_saver(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<ToggleParameters>,
+) -> impl IntoResponse {
+    state.controls.set_data_saver(parameters.enabled.is_some());
+}
+
+async fn set_play_previews(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<ToggleParameters>,
+) -> impl IntoResponse {
+    state
+        .controls
+        .set_play_previews(parameters.enabled.is_some());
+}
+
+#[derive(Deserialize)]
+struct ProfileParameters {
+    name: String,
+}
+
+/// Reloads the page, as favorites and settings belong to the profile
+async fn set_profile(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<ProfileParameters>,
+) -> ResponseResult {
+    ok_or_send_error_toast(
+        &state,
+        switch_profile(
+            &state.database,
+            &state.client,
+            &state.controls,
+            &parameters.name,
+        )
+        .await,
+    )?;
+
+    Ok(hx_redirect("/settings"))
+}
+
+/// Shown when Qobuz rejects the stored login, reusing the first setup page
+async fn sign_in_page(State(state): State<Arc<AppState>>) -> ResponseResult {
+    let app_id = ok_or_error_page(&state, get_app_id().await.map_err(Into::into))?;
+    Ok(sign_in_form(&state, &app_id, None))
+}
+
+fn sign_in_form(state: &AppState, app_id: &str, error: Option<String>) -> Response {
+    state.render(
+        "setup.html",
+        &json!({
+            "step": "login",
+            "login_action": "/settings/sign-in",
+            "oauth_url": oauth_url(app_id),
+            "error": error,
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct SignInParameters {
+    code: String,
+}
+
+async fn sign_in(
+    State(state): State<Arc<AppState>>,
+    Form(parameters): Form<SignInParameters>,
+) -> ResponseResult {
+    let app_id = ok_or_error_page(&state, get_app_id().await.map_err(Into::into))?;
+    let result: AppResult<()> = async {
+        let credentials = exchange_code(&parameters.code, &app_id).await?;
+        replace_credentials(&state.database, &state.client, credentials).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Ok(Redirect::to("/").into_response()),
+        Err(err) => Ok(sign_in_form(&state, &app_id, Some(err.to_string()))),
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, put},
+};
+use qobuz_player_controls::smart_playlists::SmartPlaylist;
+use serde_json::json;
+
+use crate::{AppState, ResponseResult, ok_or_send_error_toast};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/smart-playlist/{kind}", get(index))
+        .route("/smart-playlist/{kind}/content", get(content))
+        .route("/smart-playlist/{kind}/play", put(play))
+        .route("/smart-playlist/{kind}/play/shuffle", put(shuffle))
+        .route("/smart-playlist/{kind}/play/{index}", put(play_from))
+}
+
+/// Smart playlists as listed on the favorites page
+pub fn smart_playlists() -> serde_json::Value {
+    SmartPlaylist::ALL
+        .iter()
+        .map(|kind| {
+            json!({
+                "id": kind.id(),
+                "title": kind.title(),
+                "description": kind.description(),
+            })
+        })
+        .collect()
+}
+
+async fn index(
+    State(state): State<Arc<AppState>>,
+    Path(kind): Path<SmartPlaylist>,
+) -> impl IntoResponse {
+    let url = format!("/smart-playlist/{}/content", kind.id());
+    state.render("lazy-load-component.html", &json!({"url": url}))
+}
+
+async fn content(
+    State(state): State<Arc<AppState>>,
+    Path(kind): Path<SmartPlaylist>,
+) -> ResponseResult {
+    let tracks = kind.tracks(&state.database, &state.client).await;
+    let tracks = ok_or_send_error_toast(&state, tracks)?;
+    let duration = tracks
+        .iter()
+        .map(|track| track.duration_seconds)
+        .sum::<u32>()
+        / 60;
+
+    Ok(state.render(
+        "smart-playlist.html",
+        &json!({
+            "id": kind.id(),
+            "title": kind.title(),
+            "description": kind.description(),
+            "tracks_count": tracks.len(),
+            "tracks": tracks,
+            "duration": duration,
+            "click": format!("/smart-playlist/{}/play/", kind.id()),
+        }),
+    ))
+}
+
+async fn play(
+    State(state): State<Arc<AppState>>,
+    Path(kind): Path<SmartPlaylist>,
+) -> ResponseResult {
+    play_tracks(&state, kind, 0, false).await
+}
+
+async fn shuffle(
+    State(state): State<Arc<AppState>>,
+    Path(kind): Path<SmartPlaylist>,
+) -> ResponseResult {
+    play_tracks(&state, kind, 0, true).await
+}
+
+async fn play_from(
+    State(state): State<Arc<AppState>>,
+    Path((kind, index)): Path<(SmartPlaylist, usize)>,
+) -> ResponseResult {
+    play_tracks(&state, kind, index, false).await
+}
+
+/// Plays the tracks from `index` on, as the list is computed again and may have changed
+async fn play_tracks(
+    state: &AppState,
+    kind: SmartPlaylist,
+    index: usize,
+    shuffle: bool,
+) -> ResponseResult {
+    let tracks = kind.tracks(&state.database, &state.client).await;
+    let tracks = ok_or_send_error_toast(state, tracks)?;
+    let track_ids = tracks
+        .into_iter()
+        .skip(index)
+        .map(|track| track.id)
+        .collect();
+
+    state.controls.play_tracks(track_ids, shuffle);
+
+    Ok(().into_response())
+}
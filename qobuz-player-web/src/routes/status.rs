@@ -0,0 +1,173 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use parking_lot::Mutex;
+use qobuz_player_cli::StartupSummary;
+use qobuz_player_connect::ConnectionStatus;
+use qobuz_player_controls::{
+    audio_cache::{self, CacheUsage},
+    client::ApiHealthState,
+    diagnostics::Diagnostics,
+    output_device_available,
+};
+use serde::Serialize;
+
+use crate::AppState;
+
+/// How long a lookup of the output device is reused before the devices are enumerated again
+const DEVICE_CHECK_TTL: Duration = Duration::from_secs(30);
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/status", get(status))
+}
+
+/// Unauthenticated, so supervisors can probe it without the web secret
+pub fn health_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/healthz", get(healthz))
+}
+
+#[derive(Serialize)]
+struct Health {
+    healthy: bool,
+    logged_in: bool,
+    audio_device_available: bool,
+    api_healthy: bool,
+}
+
+#[derive(Serialize)]
+struct Status {
+    #[serde(flatten)]
+    summary: StartupSummary,
+    #[serde(flatten)]
+    health: Health,
+    last_api_error: Option<ApiErrorStatus>,
+    connect_status: Option<ConnectionStatus>,
+    audio_cache_bytes: u64,
+    artwork_cache_bytes: u64,
+    /// Underruns, rebuffers and decode errors since startup
+    diagnostics: Diagnostics,
+}
+
+#[derive(Serialize)]
+struct ApiErrorStatus {
+    message: String,
+    seconds_ago: u64,
+}
+
+async fn healthz(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let health = health(&state, &state.client.api_health()).await;
+    let code = match health.healthy {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (code, Json(health))
+}
+
+async fn status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let api_health = state.client.api_health();
+    let health = health(&state, &api_health).await;
+
+    let last_api_error = api_health.last_error.map(|error| ApiErrorStatus {
+        message: error.message,
+        seconds_ago: SystemTime::now()
+            .duration_since(error.at)
+            .unwrap_or_default()
+            .as_secs(),
+    });
+
+    Json(Status {
+        summary: state.startup_summary.clone(),
+        health,
+        last_api_error,
+        connect_status: state
+            .connection_status
+            .as_ref()
+            .map(|receiver| *receiver.borrow()),
+        audio_cache_bytes: disk_usage(state.startup_summary.audio_cache.clone())
+            .await
+            .bytes,
+        artwork_cache_bytes: disk_usage(state.artwork_cache.dir().to_path_buf())
+            .await
+            .bytes,
+        diagnostics: *state.diagnostics.borrow(),
+    })
+}
+
+/// The latest lookup of the output device, so frequent probes do not enumerate the devices
+/// every time
+#[derive(Default)]
+pub struct DeviceCheck {
+    last: Mutex<Option<(Instant, bool)>>,
+}
+
+impl DeviceCheck {
+    fn cached(&self, now: Instant) -> Option<bool> {
+        self.last
+            .lock()
+            .filter(|(checked_at, _)| now.duration_since(*checked_at) < DEVICE_CHECK_TTL)
+            .map(|(_, available)| available)
+    }
+
+    fn store(&self, now: Instant, available: bool) {
+        *self.last.lock() = Some((now, available));
+    }
+
+    async fn available(&self, output_device: Option<String>) -> bool {
+        if let Some(available) = self.cached(Instant::now()) {
+            return available;
+        }
+
+        let available =
+            tokio::task::spawn_blocking(move || output_device_available(output_device.as_deref()))
+                .await
+                .unwrap_or(false);
+        self.store(Instant::now(), available);
+        available
+    }
+}
+
+/// Healthy while an output device is present and the latest Qobuz call did not fail
+async fn health(state: &AppState, api_health: &ApiHealthState) -> Health {
+    let audio_device_available = state
+        .device_check
+        .available(state.startup_summary.output_device.clone())
+        .await;
+    let api_healthy = api_health.healthy();
+
+    Health {
+        healthy: audio_device_available && api_healthy,
+        logged_in: state.client.logged_in(),
+        audio_device_available,
+        api_healthy,
+    }
+}
+
+/// Walks the directory off the async runtime
+pub(crate) async fn disk_usage(path: PathBuf) -> CacheUsage {
+    tokio::task::spawn_blocking(move || audio_cache::usage(&path))
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{DEVICE_CHECK_TTL, DeviceCheck};
+
+    #[test]
+    fn device_check_is_reused_until_it_expires() {
+        let check = DeviceCheck::default();
+        let now = Instant::now();
+        assert_eq!(check.cached(now), None);
+
+        check.store(now, true);
+        assert_eq!(check.cached(now + Duration::from_secs(1)), Some(true));
+        assert_eq!(check.cached(now + DEVICE_CHECK_TTL), None);
+    }
+}
@@ -0,0 +1,74 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+
+use crate::AppState;
+
+/// Color of primary buttons and sliders, given as a hex color like `#e11d48`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccentColor(String);
+
+impl FromStr for AccentColor {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let is_hex_color = value.strip_prefix('#').is_some_and(|digits| {
+            matches!(digits.len(), 3 | 6) && digits.chars().all(|char| char.is_ascii_hexdigit())
+        });
+
+        match is_hex_color {
+            true => Ok(Self(value.to_lowercase())),
+            false => Err(format!("{value} is not a hex color like #e11d48")),
+        }
+    }
+}
+
+impl fmt::Display for AccentColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Unauthenticated like the other assets, the sign in page is themed too
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/theme.css", get(theme))
+}
+
+/// Primary buttons use the blue shades, so the accent takes their place
+async fn theme(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let css = match &state.accent_color {
+        Some(accent) => format!(
+            ":root{{--color-blue-600:{accent};\
+             --color-blue-700:color-mix(in oklab,{accent} 85%,black);\
+             --color-blue-800:color-mix(in oklab,{accent} 70%,black)}}\
+             input[type=range]{{accent-color:{accent}}}"
+        ),
+        None => String::new(),
+    };
+
+    ([(header::CONTENT_TYPE, "text/css")], css)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccentColor;
+
+    #[test]
+    fn accent_color_accepts_hex_colors() {
+        let accent: AccentColor = "#E11D48".parse().unwrap();
+        assert_eq!(accent.to_string(), "#e11d48");
+
+        let accent: AccentColor = "#abc".parse().unwrap();
+        assert_eq!(accent.to_string(), "#abc");
+    }
+
+    #[test]
+    fn accent_color_rejects_anything_else() {
+        for value in ["e11d48", "#12345", "#ggg", "#e11d48;}", ""] {
+            assert_eq!(
+                value.parse::<AccentColor>(),
+                Err(format!("{value} is not a hex color like #e11d48"))
+            );
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    response::Response,
+    routing::get,
+};
+use qobuz_player_controls::TransitionReceiver;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/api/transitions", get(transitions))
+}
+
+/// Websocket sending every track transition event as a json text message
+async fn transitions(State(state): State<Arc<AppState>>, upgrade: WebSocketUpgrade) -> Response {
+    let receiver = state.transition_receiver.resubscribe();
+    upgrade.on_upgrade(move |socket| send_transitions(socket, receiver))
+}
+
+async fn send_transitions(mut socket: WebSocket, mut receiver: TransitionReceiver) {
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    let Ok(text) = serde_json::to_string(&event) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Transitions websocket skipped {skipped} events");
+                }
+                Err(RecvError::Closed) => break,
+            },
+
+            // Reading answers pings, anything else from the client is ignored
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}